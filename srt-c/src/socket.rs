@@ -397,9 +397,12 @@ impl SocketData {
     pub fn listen(&mut self, sock: Arc<Mutex<SocketData>>) -> Result<(), SrtError> {
         let sd = replace(self, SocketData::InvalidIntermediateState);
         if let SocketData::Bound(so, socket, _, initial_opts) = sd {
-            let options = ListenerOptions { socket: so }
-                .try_validate()
-                .map_err(|e| SrtError::new(SRT_EINVOP, e))?;
+            let options = ListenerOptions {
+                socket: so,
+                max_connections: None,
+            }
+            .try_validate()
+            .map_err(|e| SrtError::new(SRT_EINVOP, e))?;
             let (listener, mut incoming) = TOKIO_RUNTIME
                 .block_on(SrtListener::bind_with_socket(options, socket))
                 .map_err(|e| SrtError::new(SRT_EINVOP, e))?;
@@ -0,0 +1,133 @@
+//! Parsing and serializing the packets a live stream is made of
+//!
+//! `cargo bench -p srt-protocol --bench codec [filter]` times each case and compares it to the
+//! last run, `cargo test --benches` only runs each once to check they still work.
+
+use std::{io::Cursor, time::Duration};
+
+use bytes::{Bytes, BytesMut};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use srt_protocol::{options::*, packet::*};
+
+fn data() -> Packet {
+    Packet::Data(DataPacket {
+        seq_number: SeqNumber(1_827_131),
+        message_loc: PacketLocation::ONLY,
+        in_order_delivery: false,
+        encryption: DataEncryption::None,
+        retransmitted: false,
+        message_number: MsgNumber(1234),
+        timestamp: TimeStamp::from_micros(113_703),
+        dest_sockid: SocketId(2_453_706_529),
+        // the usual payload of live streams, 7 MPEG-TS packets
+        payload: Bytes::from(vec![0x47; 1316]),
+    })
+}
+
+fn ack() -> Packet {
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(113_703),
+        dest_sockid: SocketId(2_453_706_529),
+        control_type: ControlTypes::Ack(Acknowledgement::Full(
+            SeqNumber(282_049_186),
+            AckStatistics {
+                rtt: Rtt::new(TimeSpan::from_micros(10_002), TimeSpan::from_micros(1000)),
+                buffer_available: 1314,
+                packet_receive_rate: Some(8000),
+                estimated_link_capacity: Some(100_000),
+                data_receive_rate: Some(10_528_000),
+            },
+            FullAckSeqNumber::new(1).unwrap(),
+        )),
+    })
+}
+
+fn nak() -> Packet {
+    // single packets and ranges, as a NAK after a burst of losses has them
+    let lost = (0..64).flat_map(|i| (i * 10..i * 10 + i % 3 + 1).map(SeqNumber));
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(113_703),
+        dest_sockid: SocketId(2_453_706_529),
+        control_type: ControlTypes::Nak(CompressedLossList::try_from_iter(lost).unwrap()),
+    })
+}
+
+fn handshake() -> Packet {
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: SocketId(0),
+        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+            init_seq_num: SeqNumber(1_827_131),
+            max_packet_size: PacketSize(1500),
+            max_flow_size: PacketCount(25600),
+            shake_type: ShakeType::Conclusion,
+            socket_id: SocketId(1231),
+            syn_cookie: 0,
+            peer_addr: [127, 0, 0, 1].into(),
+            info: HandshakeVsInfo::V5(HsV5Info {
+                key_size: KeySize::Unspecified,
+                ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
+                    version: SrtVersion::CURRENT,
+                    flags: SrtShakeFlags::SUPPORTED,
+                    send_latency: Duration::from_millis(120),
+                    recv_latency: Duration::from_millis(120),
+                })),
+                ext_km: None,
+                ext_group: None,
+                sid: Some("#!::r=live/stream,m=publish".into()),
+                filter: None,
+                resume: None,
+                auth: None,
+                challenge: None,
+            }),
+        }),
+    })
+}
+
+fn codec(c: &mut Criterion) {
+    let mut parse = c.benchmark_group("parse");
+    for (name, packet) in packets() {
+        let wire = wire(&packet);
+        assert_eq!(
+            Packet::parse(&mut Cursor::new(&wire[..]), false),
+            Ok(packet)
+        );
+        parse.throughput(Throughput::Bytes(wire.len() as u64));
+        parse.bench_function(name, |b| {
+            b.iter(|| Packet::parse(&mut Cursor::new(&wire[..]), false).unwrap())
+        });
+    }
+    parse.finish();
+
+    let mut serialize = c.benchmark_group("serialize");
+    for (name, packet) in packets() {
+        let mut buffer = BytesMut::with_capacity(wire(&packet).len());
+        serialize.throughput(Throughput::Bytes(buffer.capacity() as u64));
+        serialize.bench_function(name, |b| {
+            b.iter(|| {
+                buffer.clear();
+                packet.serialize(&mut buffer);
+                buffer.len()
+            })
+        });
+    }
+    serialize.finish();
+}
+
+fn packets() -> [(&'static str, Packet); 4] {
+    [
+        ("data", data()),
+        ("ack", ack()),
+        ("nak", nak()),
+        ("handshake", handshake()),
+    ]
+}
+
+fn wire(packet: &Packet) -> Bytes {
+    let mut wire = BytesMut::new();
+    packet.serialize(&mut wire);
+    wire.freeze()
+}
+
+criterion_group!(benches, codec);
+criterion_main!(benches);
@@ -0,0 +1,66 @@
+//! Encrypting and decrypting the payloads of data packets, with each key size
+//!
+//! `cargo bench -p srt-protocol --bench encryption [filter]` times each case and compares it to
+//! the last run, `cargo test --benches` only runs each once to check they still work.
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use srt_protocol::{
+    options::*,
+    packet::*,
+    protocol::encryption::{Decryption, Encryption},
+    settings::*,
+};
+
+fn data(seq_number: SeqNumber) -> DataPacket {
+    DataPacket {
+        seq_number,
+        message_loc: PacketLocation::ONLY,
+        in_order_delivery: false,
+        encryption: DataEncryption::None,
+        retransmitted: false,
+        message_number: MsgNumber(1234),
+        timestamp: TimeStamp::from_micros(113_703),
+        dest_sockid: SocketId(2_453_706_529),
+        // the usual payload of live streams, 7 MPEG-TS packets
+        payload: Bytes::from(vec![0x47; 1316]),
+    }
+}
+
+fn encryption(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encryption");
+    group.throughput(Throughput::Bytes(data(SeqNumber(0)).payload.len() as u64));
+    for (name, key_size) in [
+        ("aes128", KeySize::AES128),
+        ("aes192", KeySize::AES192),
+        ("aes256", KeySize::AES256),
+    ] {
+        let key_settings = KeySettings {
+            key_size,
+            passphrase: "benchmark passphrase".into(),
+        };
+        let settings = CipherSettings::new_random(&key_settings, &Default::default());
+
+        let mut encryption = Encryption::new(Some(settings.clone()));
+        let mut seq_number = SeqNumber(0);
+        group.bench_function(format!("encrypt/{name}"), |b| {
+            b.iter(|| {
+                seq_number += 1;
+                encryption.encrypt(data(seq_number)).unwrap()
+            })
+        });
+
+        // the same packet over and over, encrypted with the keys the decryption starts with
+        let (_, encrypted, _) = Encryption::new(Some(settings.clone()))
+            .encrypt(data(seq_number))
+            .unwrap();
+        let decryption = Decryption::new(Some(settings));
+        group.bench_function(format!("decrypt/{name}"), |b| {
+            b.iter(|| decryption.decrypt(encrypted.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encryption);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use std::time::Instant;
+
+use crate::{
+    options::PacketCount,
+    packet::{Packet, ShakeType},
+    protocol::pending_connection::{ConnectionReject, ConnectionResult},
+};
+
+/// Lifecycle events of a connection, each with the time at which it happened
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConnectionEvent {
+    /// The handshake with the peer started
+    Connecting(Instant),
+    /// The handshake was sent again, because the peer hasn't answered it yet
+    Reconnecting(Instant),
+    /// A handshake packet of this type was sent to the peer
+    HandshakeSent(Instant, ShakeType),
+    /// A handshake packet of this type was received from the peer
+    HandshakeReceived(Instant, ShakeType),
+    /// The handshake ended in a rejection, by the peer or of the peer
+    Rejected(Instant, ConnectionReject),
+    /// The handshake completed, data can be exchanged
+    Connected(Instant),
+    /// The peer sent new stream encrypting keys and they were accepted
+    KeyRefreshed(Instant),
+    /// This many packets were given up on, right before the message delivered with this time
+    Gap(Instant, PacketCount),
+    /// The send buffer was full, this many of its oldest packets were dropped to make room for
+    /// the data sent at this time
+    SendBufferFull(Instant, PacketCount),
+    /// Nothing was received from the peer within the peer idle timeout, the connection is
+    /// shutting down
+    PeerIdle(Instant),
+    /// The connection is closed, no more data will be sent or received
+    Closed(Instant),
+}
+
+impl ConnectionEvent {
+    pub fn time(&self) -> Instant {
+        use ConnectionEvent::*;
+        match *self {
+            Connecting(time)
+            | Reconnecting(time)
+            | HandshakeSent(time, _)
+            | HandshakeReceived(time, _)
+            | Rejected(time, _)
+            | Connected(time)
+            | KeyRefreshed(time)
+            | Gap(time, _)
+            | SendBufferFull(time, _)
+            | PeerIdle(time)
+            | Closed(time) => time,
+        }
+    }
+
+    /// The events of a step of the handshake: the handshake packet it received, if any, the one
+    /// it sends and the rejection it ends in
+    pub fn of_handshake(
+        now: Instant,
+        received: Option<&Packet>,
+        result: &ConnectionResult,
+    ) -> Vec<Self> {
+        use ConnectionResult::*;
+        let mut events: Vec<_> = received
+            .and_then(shake_type)
+            .map(|shake_type| Self::HandshakeReceived(now, shake_type))
+            .into_iter()
+            .collect();
+        let sent = match result {
+            SendPacket((packet, _))
+            | Connected(Some((packet, _)), _)
+            | Reject(Some((packet, _)), _) => shake_type(packet),
+            _ => None,
+        };
+        events.extend(sent.map(|shake_type| Self::HandshakeSent(now, shake_type)));
+        if let Reject(_, reject) = result {
+            events.push(Self::Rejected(now, *reject));
+        }
+        events
+    }
+}
+
+fn shake_type(packet: &Packet) -> Option<ShakeType> {
+    let handshake = packet.control()?.handshake()?;
+    Some(handshake.shake_type)
+}
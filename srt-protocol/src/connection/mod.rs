@@ -1,11 +1,20 @@
+pub mod event;
+pub mod resumption;
 pub mod status;
+#[cfg(feature = "tracing")]
+pub(crate) mod trace;
+pub use event::*;
+pub use resumption::*;
 pub use status::*;
 
 use std::{
+    cmp::{max, min},
+    collections::VecDeque,
     convert::TryFrom,
     fmt::Debug,
     io,
     net::SocketAddr,
+    ops::Range,
     time::{Duration, Instant},
 };
 
@@ -15,6 +24,7 @@ use crate::{
     options::*,
     packet::*,
     protocol::{
+        filter::{self, PacketFilter},
         handshake::Handshake,
         output::Output,
         receiver::{Receiver, ReceiverContext},
@@ -22,7 +32,7 @@ use crate::{
         time::Timers,
     },
     settings::CipherSettings,
-    statistics::SocketStatistics,
+    statistics::{LossRateWindow, SocketStatistics},
 };
 
 #[derive(Debug, Eq, PartialEq)]
@@ -52,12 +62,22 @@ pub struct ConnectionSettings {
     /// The first sequence number that will be sent/received
     pub init_seq_num: SeqNumber,
 
-    /// The maximum packet size
+    /// The maximum packet size, the payload of a data packet. It's never larger than what fits in
+    /// the negotiated MSS once the headers are taken off
     pub max_packet_size: PacketSize,
 
+    /// The MSS both sides agreed on, the smaller of the two advertised during the handshake
+    pub max_segment_size: PacketSize,
+
     /// The maximum flow size
     pub max_flow_size: PacketCount,
 
+    /// How long the sender is idle before it restarts from a small number of packets in flight
+    pub idle_restart_threshold: Option<Duration>,
+
+    /// How many times a packet is retransmitted before the connection is given up on
+    pub max_retransmits: Option<u32>,
+
     /// The TSBPD of the connection--the max of each side's respective latencies
     pub send_tsbpd_latency: Duration,
     pub recv_tsbpd_latency: Duration,
@@ -67,16 +87,130 @@ pub struct ConnectionSettings {
     /// the receiver to skip missing packets that have not been delivered in time
     pub too_late_packet_drop: bool,
 
+    /// Whether messages after missing packets wait for them to be recovered or dropped
+    pub delivery_mode: DeliveryMode,
+
+    /// How long the receiver waits before reporting the packets it's still missing again
+    pub nak_period: NakPeriodConfig,
+
+    /// Whether messages are released whole, or a packet's payload at a time
+    pub recv_granularity: RecvGranularity,
+
     pub peer_idle_timeout: Duration,
 
     /// Size of the receive buffer, in packets
     pub recv_buffer_size: PacketCount,
+    /// The size the receive buffer may grow to as the stream needs it, in packets, see
+    /// [`Receiver::adaptive_buffer_cap`](crate::options::Receiver::adaptive_buffer_cap)
+    pub recv_buffer_cap: Option<PacketCount>,
     /// Size of the send buffer, in packets
     pub send_buffer_size: PacketCount,
+    /// Whether data is dropped or waits while the send buffer is full
+    pub send_buffer_full: SendBufferFull,
+    /// Whether data waits, is refused or is dropped while the flow window is exhausted
+    pub flow_window_full: FlowWindowFull,
+    /// Whether data waiting to be sent is dropped once it falls too far behind
+    pub input_overflow: InputOverflowPolicy,
     pub cipher: Option<CipherSettings>,
     pub stream_id: Option<String>,
     pub bandwidth: LiveBandwidthMode,
+    /// Whether retransmissions count against the bandwidth limit
+    pub retransmit_bandwidth: RetransmitBandwidth,
     pub statistics_interval: Duration,
+    /// The span of time over which the recent loss rates are calculated
+    pub loss_rate_window: Duration,
+    /// The round trip time assumed until it's measured, see [`ConnectionSettings::rtt_estimate`]
+    pub initial_rtt: Option<Duration>,
+    /// How much later than its latency a packet may be before it's too late, see
+    /// [`Session::too_late_drop_margin`](crate::options::Session::too_late_drop_margin)
+    pub too_late_drop_margin: Percent,
+
+    /// The SRT version the peer advertised during the handshake
+    pub peer_version: SrtVersion,
+    /// The capabilities advertised by both sides during the handshake
+    pub features: SrtShakeFlags,
+    /// The packet filter config both sides agreed on during the handshake
+    pub packet_filter: Option<FilterSpec>,
+    /// The values asked for that the handshake settled on differently
+    pub negotiation_adjustments: Vec<NegotiationAdjustment>,
+    /// The session the connection is part of, if it can be resumed, see [`Resumption`]
+    pub session_token: Option<u64>,
+    /// Where a listener records how far the session got, for a connection resuming it later
+    pub resumable_sessions: Option<ResumableSessions>,
+}
+
+/// A value asked for that the handshake changed to what the peer asked for or supports, a sign
+/// that the two sides are configured differently
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum NegotiationAdjustment {
+    /// The peer asked for a higher receive latency, which the sender has to go by
+    SendLatency {
+        requested: Duration,
+        effective: Duration,
+    },
+    /// The peer asked for a higher send latency, which the receiver has to go by
+    RecvLatency {
+        requested: Duration,
+        effective: Duration,
+    },
+    /// The peer supports a smaller MSS
+    MaxSegmentSize {
+        requested: PacketSize,
+        effective: PacketSize,
+    },
+}
+
+/// The state of the encryption negotiated during the handshake
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CryptoState {
+    /// Neither side has a passphrase, data is exchanged in the clear
+    Unencrypted,
+    /// Data is encrypted with keys of this many bits
+    Secured { key_bits: usize },
+    /// The last keys the peer sent couldn't be unwrapped with the passphrase, e.g. because the
+    /// peer changed it and this side hasn't yet. Packets encrypted with them can't be decrypted.
+    BadSecret,
+}
+
+impl ConnectionSettings {
+    /// The encryption negotiated during the handshake
+    pub fn crypto_state(&self) -> CryptoState {
+        match &self.cipher {
+            Some(cipher) => CryptoState::Secured {
+                key_bits: cipher.key_settings.key_size.as_usize() * 8,
+            },
+            None => CryptoState::Unencrypted,
+        }
+    }
+
+    /// The passphrase the keys are wrapped with, for a peer with alternate passphrases the one
+    /// that fit the keys it was sent
+    pub fn passphrase(&self) -> Option<&Passphrase> {
+        self.cipher
+            .as_ref()
+            .map(|cipher| &cipher.key_settings.passphrase)
+    }
+
+    /// The round trip time the timers start from, the initial RTT or the default estimate
+    pub fn rtt_estimate(&self) -> Rtt {
+        self.initial_rtt.map(Rtt::from_estimate).unwrap_or_default()
+    }
+
+    /// The largest message that can be sent. Messages are only released once fully reassembled,
+    /// so they have to fit in both the send and the receive buffer. The peer's receive buffer size
+    /// isn't exchanged during the handshake, so the local one is used in its place. The packets
+    /// carry less than the full packet size when the built-in packet filter reserves room in them.
+    /// This is the limit the connection starts out with, the sender lowers it along with the
+    /// payload size.
+    pub fn max_message_size(&self) -> ByteCount {
+        let max_payload_size = self.max_packet_size.0 - filter::extra_size(self) as u64;
+        self.max_message_packets() * PacketSize(max_payload_size)
+    }
+
+    /// The most packets a message can be split into, see [`Self::max_message_size`]
+    pub fn max_message_packets(&self) -> PacketCount {
+        min(self.send_buffer_size, self.recv_buffer_size)
+    }
 }
 
 #[derive(Debug)]
@@ -88,19 +222,49 @@ pub struct DuplexConnection {
     sender: Sender,
     receiver: Receiver,
     stats: SocketStatistics,
+    tx_loss_rate: LossRateWindow,
+    rx_loss_rate: LossRateWindow,
     status: ConnectionStatus,
+    events: VecDeque<ConnectionEvent>,
+    packet_filter: Box<dyn PacketFilter>,
+    last_recv: Option<Instant>,
+    last_send: Option<Instant>,
+    // packets given up on since the last message released
+    gap: u64,
 }
 
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Action<'a> {
-    ReleaseData((Instant, Bytes)),
+    ReleaseData((Instant, Bytes, MessageInfo)),
     SendPacket((Packet, SocketAddr)),
     UpdateStatistics(&'a SocketStatistics),
     WaitForData(Duration),
     Close,
 }
 
+/// How urgent a message is, not available in the reference implementation
+///
+/// While messages wait to be sent, e.g. under a bandwidth cap, a message goes out ahead of the
+/// ones with a lower priority, and its packets are retransmitted before theirs. It's given its
+/// sequence number as it goes out, so it's still delivered in order with the rest of the stream.
+/// Messages of the same priority go out in the order they were sent.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// The message a received item belongs to, and which of its packets carried it: all of them for
+/// a whole message, or a single one with [`RecvGranularity::Payload`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct MessageInfo {
+    pub message_number: MsgNumber,
+    pub location: PacketLocation,
+}
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Input {
@@ -115,21 +279,57 @@ pub enum Input {
 impl DuplexConnection {
     pub fn new(connection: Connection) -> DuplexConnection {
         let settings = connection.settings;
+        let packet_filter = filter::new_packet_filter(&settings);
+        for adjustment in &settings.negotiation_adjustments {
+            log::warn!("{:?}|negotiated - {:?}", settings.local_sockid, adjustment);
+        }
 
-        DuplexConnection {
+        let mut connection = DuplexConnection {
             settings: settings.clone(),
             handshake: connection.handshake,
             output: Output::new(&settings),
-            status: ConnectionStatus::new(settings.send_tsbpd_latency * 2), // the timeout should be larger than latency as otherwise packets that have just arrived definitely have a change to flush
+            // the timeout should be larger than latency as otherwise packets that have just arrived
+            // don't have a chance to flush, and the receiver holds on to packets for its own latency
+            status: ConnectionStatus::new(
+                max(settings.send_tsbpd_latency, settings.recv_tsbpd_latency) * 2,
+            ),
             timers: Timers::new(
                 settings.socket_start_time,
                 settings.statistics_interval,
                 settings.peer_idle_timeout,
+                settings.nak_period,
             ),
             stats: SocketStatistics::new(),
+            tx_loss_rate: LossRateWindow::new(
+                settings.socket_start_time,
+                settings.loss_rate_window,
+            ),
+            rx_loss_rate: LossRateWindow::new(
+                settings.socket_start_time,
+                settings.loss_rate_window,
+            ),
             receiver: Receiver::new(settings.clone()),
             sender: Sender::new(settings),
-        }
+            events: VecDeque::new(),
+            packet_filter: Box::new(filter::PassThrough),
+            last_recv: None,
+            last_send: None,
+            gap: 0,
+        };
+        let rtt = connection.settings.rtt_estimate();
+        connection.timers.update_rtt(&rtt);
+        connection.set_packet_filter(packet_filter);
+        connection
+    }
+
+    /// Plugs in the filter for the config negotiated in [`ConnectionSettings::packet_filter`],
+    /// replacing the built-in one. Meant to be called before any data is exchanged.
+    pub fn set_packet_filter(&mut self, filter: Box<dyn PacketFilter>) {
+        self.sender.reserve_payload(filter.extra_size());
+        // a filter rebuilding lost packets on its own may take longer than the retransmission
+        // timeout, the receiver asks for what it can't rebuild
+        self.sender.set_rto_retransmit(filter.report_losses());
+        self.packet_filter = filter;
     }
 
     pub fn handle_input(&mut self, now: Instant, input: Input) -> Action {
@@ -148,7 +348,7 @@ impl DuplexConnection {
             Action::UpdateStatistics(&self.stats)
         } else if let Some(packet) = self.next_packet(now) {
             Action::SendPacket(packet)
-        } else if let Some(data) = self.next_data(now) {
+        } else if let Some(data) = self.next_data_with_info(now) {
             Action::ReleaseData(data)
         } else {
             Action::WaitForData(self.next_timer(now) - now)
@@ -158,6 +358,19 @@ impl DuplexConnection {
         action
     }
 
+    /// The state of the encryption, which goes bad if the peer sends keys that can't be unwrapped
+    pub fn crypto_state(&self) -> CryptoState {
+        match self.settings.crypto_state() {
+            CryptoState::Secured { .. } if self.receiver.has_bad_secret() => CryptoState::BadSecret,
+            state => state,
+        }
+    }
+
+    /// Why the connection is closing or closed, if it's because of the peer rather than this side
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.status.close_reason()
+    }
+
     pub fn is_open(&self) -> bool {
         self.status.is_open()
     }
@@ -166,13 +379,156 @@ impl DuplexConnection {
         &self.settings
     }
 
+    /// Whether more data can be handed to the connection. With backpressure configured it waits
+    /// while the send buffer is full, taking the data anyway drops nothing but grows the buffer.
+    pub fn is_accepting_data(&self) -> bool {
+        self.sender.is_accepting_data()
+    }
+
+    /// The payload bytes of the data handed to the connection that the peer hasn't acknowledged
+    /// yet, zero once everything sent has been acknowledged or dropped
+    pub fn unacked_bytes(&self) -> usize {
+        self.sender.unacked_bytes()
+    }
+
+    /// One past the last packet of the data handed to the connection, see
+    /// [`acked_seq_number`](DuplexConnection::acked_seq_number)
+    pub fn written_seq_number(&self) -> SeqNumber {
+        self.sender.written_seq_number()
+    }
+
+    /// One past the last packet the peer acknowledged. Unlike [`unacked_bytes`], data dropped on
+    /// this side for being too late only counts once the peer acknowledges past it.
+    ///
+    /// [`unacked_bytes`]: DuplexConnection::unacked_bytes
+    pub fn acked_seq_number(&self) -> SeqNumber {
+        self.sender.acked_seq_number()
+    }
+
+    /// How long the connection has been up, counting from when the handshake started
+    pub fn uptime(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.settings.socket_start_time)
+    }
+
+    /// When the last packet from the peer was received, keepalives included
+    pub fn last_recv(&self) -> Option<Instant> {
+        self.last_recv
+    }
+
+    /// When the last packet was handed out to be sent
+    pub fn last_send(&self) -> Option<Instant> {
+        self.last_send
+    }
+
+    /// Sends a keepalive right away to probe the link. If both sides offered
+    /// [`SrtShakeFlags::KEEPALIVE_ECHO`] in the handshake, the peer answers it with a light ACK, so
+    /// [`last_recv`](Connection::last_recv) moves past `now` within a round trip at the latest.
+    pub fn ping(&mut self, now: Instant) {
+        self.output.send_control(now, ControlTypes::KeepAlive);
+    }
+
+    /// Ask the peer to use a new receive latency, up to [`SrtControlPacket::MAX_LATENCY`]. The
+    /// request is repeated every NAK period until the peer confirms it, then the jitter buffer
+    /// moves towards the new latency gradually rather than all at once.
+    ///
+    /// Ignored unless both sides offered [`SrtShakeFlags::LATENCY_CHANGE`] in the handshake.
+    pub fn set_latency(&mut self, now: Instant, latency: Duration) {
+        if !self
+            .settings
+            .features
+            .contains(SrtShakeFlags::LATENCY_CHANGE)
+        {
+            self.warn(now, "latency change not agreed on", &latency);
+            return;
+        }
+        let latency = latency.min(SrtControlPacket::MAX_LATENCY);
+        self.receiver().request_latency_change(now, latency);
+    }
+
+    /// Wrap the keys with a new passphrase. A key refresh starts right away, and the current key
+    /// stays in use until the peer, with the new passphrase too, confirms the new keys.
+    pub fn set_passphrase(&mut self, passphrase: Passphrase) {
+        if let Some(cipher) = &mut self.settings.cipher {
+            cipher.key_settings.passphrase = passphrase.clone();
+        }
+        self.receiver.decryption.set_passphrase(passphrase.clone());
+        self.sender.set_passphrase(passphrase);
+    }
+
+    /// Stops sending data, retransmissions included, until resumed. Data is still buffered, and
+    /// control packets, keepalives included, still go both ways, so the connection stays up.
+    pub fn pause(&mut self) {
+        self.sender.pause();
+    }
+
+    /// Goes on sending data after a pause. What built up meanwhile is clocked out by the ACKs,
+    /// starting from a small window, rather than sent in one burst.
+    pub fn resume(&mut self) {
+        self.sender.resume();
+    }
+
+    /// What the peer didn't acknowledge, to be sent first on the connection that resumes this
+    /// one once it's done, see [`DuplexConnection::handle_resumption`]. None if the connection
+    /// can't be resumed.
+    pub fn take_resumption(&mut self) -> Option<Resumption> {
+        let token = self.settings.session_token?;
+        let (seq_number, packets) = self.sender.take_unacked();
+        Some(Resumption {
+            token,
+            seq_number,
+            packets,
+        })
+    }
+
+    /// Sends what the connection this one resumes left unacknowledged ahead of anything else,
+    /// skipping what the peer said it received already. Meant to be called before any data is
+    /// handed to the connection.
+    pub fn handle_resumption(&mut self, now: Instant, resumption: Resumption) {
+        let mut resumption = resumption;
+        resumption.skip_to(self.settings.init_seq_num);
+        self.debug(now, "resume", &resumption.session());
+        self.sender().handle_resumption(now, resumption.packets);
+    }
+
+    /// Counts DATA packets that whoever reads the socket suspects the system dropped, see
+    /// [`SocketStatistics::rx_suspected_kernel_drops`]
+    pub fn on_suspected_kernel_drops(&mut self, packets: u64) {
+        self.stats.rx_suspected_kernel_drops += packets;
+    }
+
+    /// Records how much waits in front of the send buffer for the connection to take it, see
+    /// [`SocketStatistics::tx_queued_messages`]
+    pub fn on_send_queue(&mut self, messages: u64, bytes: u64) {
+        self.stats.tx_queued_messages = messages;
+        self.stats.tx_queued_bytes = bytes;
+        self.stats.tx_queued_messages_max = self.stats.tx_queued_messages_max.max(messages);
+    }
+
     pub fn update_statistics(&mut self, now: Instant) {
         self.stats.elapsed_time = now - self.settings.socket_start_time;
         self.stats.tx_buffered_time = self.sender.tx_buffered_time();
         self.stats.tx_buffered_data = self.sender.tx_buffered_packets();
         self.stats.tx_buffered_bytes = self.sender.tx_buffered_bytes();
+        self.stats.tx_unacknowledged_data = self.sender.tx_unacknowledged_packets();
+        self.stats.tx_max_payload_size = self.sender.tx_max_payload_size();
+        self.stats.tx_tsbpd_delay = self.sender.tsbpd_latency();
 
         self.stats.rx_acknowledged_time = self.receiver.rx_acknowledged_time();
+        self.stats.rx_buffer_size = self.receiver.buffer_size().0;
+        self.stats.rx_buffered_data = self.receiver.rx_buffered_packets();
+        self.stats.rx_buffered_time = self.receiver.rx_buffered_time();
+        self.stats.rx_pending_data = self.receiver.rx_pending_packets(now);
+        self.stats.rx_tsbpd_delay = self.receiver.tsbpd_latency(now);
+
+        let stats = &mut self.stats;
+        stats.tx_loss_rate_recent_ppm =
+            self.tx_loss_rate
+                .update(now, stats.tx_unique_data, stats.tx_loss_data);
+        stats.rx_loss_rate_recent_ppm = self.rx_loss_rate.update(
+            now,
+            stats.rx_unique_data + stats.rx_retransmit_data + stats.rx_dropped_data,
+            stats.rx_loss_data,
+        );
     }
 
     pub fn next_packet(&mut self, now: Instant) -> Option<(Packet, SocketAddr)> {
@@ -185,6 +541,7 @@ impl DuplexConnection {
             Packet::Data(d) => {
                 self.stats.tx_data += 1;
                 self.stats.tx_bytes += u64::try_from(d.wire_size()).unwrap();
+                self.stats.tx_payload_bytes += u64::try_from(d.payload.len()).unwrap();
             }
             Packet::Control(c) => match c.control_type {
                 ControlTypes::Ack(ref a) => {
@@ -202,26 +559,50 @@ impl DuplexConnection {
                 _ => {}
             },
         }
+        #[cfg(feature = "tracing")]
+        if let Packet::Control(control) = &p {
+            trace::control_sent(&control.control_type);
+        }
         self.debug(now, "send", &p);
+        self.last_send = Some(now);
         Some((p, self.settings.remote))
     }
 
     pub fn next_data(&mut self, now: Instant) -> Option<(Instant, Bytes)> {
-        match self.receiver.arq.pop_next_message(now) {
+        self.next_data_with_info(now)
+            .map(|(time, data, _)| (time, data))
+    }
+
+    /// The next item released, along with the message number and the location in the message of
+    /// the packets it came in
+    pub fn next_data_with_info(&mut self, now: Instant) -> Option<(Instant, Bytes, MessageInfo)> {
+        match self.receiver.arq.pop_next_message_with_info(now) {
             Ok(Some(data)) => {
                 self.debug(now, "output", &data);
+                if self.gap > 0 {
+                    let gap = PacketCount(std::mem::take(&mut self.gap));
+                    self.events.push_back(ConnectionEvent::Gap(data.0, gap));
+                }
                 Some(data)
             }
             Err(error) => {
                 self.warn(now, "output", &error);
+                #[cfg(feature = "tracing")]
+                trace::too_late_dropped("receiver", &error.too_late_packets);
                 let dropped = error.too_late_packets.end - error.too_late_packets.start;
                 self.stats.rx_dropped_data += dropped as u64;
+                self.stats.rx_too_late_dropped_data += dropped as u64;
+                self.gap += dropped as u64;
                 None
             }
             _ => None,
         }
     }
 
+    pub fn next_event(&mut self) -> Option<ConnectionEvent> {
+        self.events.pop_front()
+    }
+
     pub fn next_timer(&self, now: Instant) -> Instant {
         let has_packets_to_send = self.sender.has_packets_to_send();
         let next_message = self.receiver.arq.next_message_release_time();
@@ -247,9 +628,16 @@ impl DuplexConnection {
         &self.stats
     }
 
+    /// A snapshot of the packets the receiver is still missing, for diagnostics
+    pub fn receiver_loss_list(&self) -> Vec<Range<SeqNumber>> {
+        self.receiver.loss_list()
+    }
+
     pub fn check_timers(&mut self, now: Instant) -> Instant {
+        let was_open = self.status.is_open();
         if self.timers.check_full_ack(now).is_some() {
             self.receiver().on_full_ack_event(now);
+            self.record_resume_point(now);
         }
         if self.timers.check_nak(now).is_some() {
             self.receiver().on_nak_event(now);
@@ -279,21 +667,41 @@ impl DuplexConnection {
 
         self.output.ensure_alive(now);
 
+        if was_open && self.status.is_closed() {
+            self.events.push_back(ConnectionEvent::Closed(now));
+        }
+
         self.next_timer(now)
     }
 
     pub fn handle_data_input(&mut self, now: Instant, data: Option<(Instant, Bytes)>) {
-        self.debug(now, "input", &data);
         match data {
-            Some(item) => {
-                self.sender().handle_data(now, item);
-            }
+            Some(item) => self.handle_prioritized_data_input(now, item, Priority::Normal),
             None => {
+                self.debug(now, "input", &data);
                 self.handle_data_stream_close(now);
             }
         }
     }
 
+    pub fn handle_prioritized_data_input(
+        &mut self,
+        now: Instant,
+        item: (Instant, Bytes),
+        priority: Priority,
+    ) {
+        self.debug(now, "input", &(&item, priority));
+        match self.sender().handle_data(now, item, priority) {
+            Ok(PacketCount(0)) => {}
+            Ok(dropped) => {
+                self.debug(now, "send buffer full, dropped", &dropped);
+                self.events
+                    .push_back(ConnectionEvent::SendBufferFull(now, dropped));
+            }
+            Err(e) => self.warn(now, "input", &e),
+        }
+    }
+
     pub fn handle_packet_input(&mut self, now: Instant, packet: ReceivePacketResult) {
         self.debug(now, "packet", &packet);
         use ReceivePacketError::*;
@@ -306,6 +714,8 @@ impl DuplexConnection {
 
     fn handle_data_stream_close(&mut self, now: Instant) {
         self.info(now, "closed data", &());
+        // what's buffered still goes out before shutting down
+        self.sender.resume();
         self.status.on_data_stream_closed(now);
     }
 
@@ -317,6 +727,7 @@ impl DuplexConnection {
     pub fn on_peer_idle_timeout(&mut self, now: Instant) {
         self.output.send_control(now, ControlTypes::Shutdown);
         self.status.on_peer_idle_timeout(now);
+        self.events.push_back(ConnectionEvent::PeerIdle(now));
     }
 
     fn handle_packet(&mut self, now: Instant, (packet, from): (Packet, SocketAddr)) {
@@ -333,6 +744,7 @@ impl DuplexConnection {
         }
 
         self.timers.reset_exp(now);
+        self.last_recv = Some(now);
 
         self.stats.rx_all_packets += 1;
         self.stats.rx_all_bytes += u64::try_from(packet.wire_size()).unwrap();
@@ -344,6 +756,8 @@ impl DuplexConnection {
 
     fn handle_control_packet(&mut self, now: Instant, control: ControlPacket) {
         self.receiver().synchronize_clock(now, control.timestamp);
+        #[cfg(feature = "tracing")]
+        trace::control_received(&control.control_type);
 
         use ControlTypes::*;
         match control.control_type {
@@ -358,7 +772,16 @@ impl DuplexConnection {
             Shutdown => self
                 .status
                 .handle_shutdown_packet(now, self.settings.local_sockid),
-            // neither--this exists just to keep the connection alive
+            // answered, so the peer can tell the link is up without waiting for data
+            KeepAlive
+                if self
+                    .settings
+                    .features
+                    .contains(SrtShakeFlags::KEEPALIVE_ECHO) =>
+            {
+                self.receiver().handle_keepalive(now)
+            }
+            // otherwise this exists just to keep the connection alive
             KeepAlive => {}
             // TODO: case UMSG_CGWARNING: // 100 - Delay Warning
             //            // One way packet delay is increasing, so decrease the sending rate
@@ -372,6 +795,13 @@ impl DuplexConnection {
     }
 
     fn handle_handshake_packet(&mut self, now: Instant, handshake: HandshakeControlInfo) {
+        // a retransmitted handshake has to come from the peer socket that was agreed on, it
+        // could make us answer with the connection's settings otherwise
+        if handshake.socket_id != self.settings.remote_sockid {
+            self.info(now, "invalid handshake socket id", &handshake);
+            return;
+        }
+
         if let Some(control) = self.handshake.handle_handshake(handshake) {
             self.output.send_control(now, control);
         }
@@ -381,16 +811,53 @@ impl DuplexConnection {
         use self::SrtControlPacket::*;
         match pack {
             HandshakeRequest(_) | HandshakeResponse(_) => self.warn(now, "handshake", &pack),
-            KeyRefreshRequest(keying_material) => self
-                .receiver()
-                .handle_key_refresh_request(now, keying_material),
+            KeyRefreshRequest(keying_material) => {
+                if self
+                    .receiver()
+                    .handle_key_refresh_request(now, keying_material)
+                {
+                    self.events.push_back(ConnectionEvent::KeyRefreshed(now));
+                }
+            }
             KeyRefreshResponse(keying_material) => {
                 self.sender().handle_key_refresh_response(keying_material)
             }
+            LatencyChangeRequest(_) | LatencyChangeResponse(_)
+                if !self
+                    .settings
+                    .features
+                    .contains(SrtShakeFlags::LATENCY_CHANGE) =>
+            {
+                self.warn(now, "latency change not agreed on", &pack)
+            }
+            LatencyChangeRequest(latency) => {
+                let latency = latency.min(SrtControlPacket::MAX_LATENCY);
+                self.sender().handle_latency_change_request(now, latency);
+                self.status.extend_flush_timeout(latency * 2);
+                self.settings.send_tsbpd_latency = latency;
+            }
+            LatencyChangeResponse(latency) => {
+                if self.receiver().handle_latency_change_response(now, latency) {
+                    self.status.extend_flush_timeout(latency * 2);
+                    self.settings.recv_tsbpd_latency = latency;
+                } else {
+                    self.debug(now, "unsolicited latency change", &latency);
+                }
+            }
             _ => unimplemented!("{:?}", pack),
         }
     }
 
+    // the listener's connection keeps track of how far the session got, for the one resuming it
+    fn record_resume_point(&self, now: Instant) {
+        if let (Some(token), Some(sessions)) = (
+            self.settings.session_token,
+            &self.settings.resumable_sessions,
+        ) {
+            sessions.update(now, token, self.receiver.arq.next_ack_dsn());
+        }
+    }
+
     fn sender(&mut self) -> SenderContext {
         SenderContext::new(
             &mut self.status,
@@ -398,6 +865,7 @@ impl DuplexConnection {
             &mut self.output,
             &mut self.stats,
             &mut self.sender,
+            self.packet_filter.as_mut(),
         )
     }
 
@@ -407,6 +875,7 @@ impl DuplexConnection {
             &mut self.output,
             &mut self.stats,
             &mut self.receiver,
+            self.packet_filter.as_mut(),
         )
     }
 
@@ -443,6 +912,8 @@ impl DuplexConnection {
 
 #[cfg(test)]
 mod duplex_connection {
+    use std::collections::{BTreeSet, VecDeque};
+
     use assert_matches::assert_matches;
 
     use Action::*;
@@ -479,22 +950,128 @@ mod duplex_connection {
                 rtt: Duration::default(),
                 init_seq_num: SeqNumber::new_truncate(0),
                 max_packet_size: PacketSize(1316),
+                max_segment_size: PacketSize(1500),
                 max_flow_size: PacketCount(8192),
+                idle_restart_threshold: None,
+                max_retransmits: None,
                 send_tsbpd_latency: TSBPD,
                 recv_tsbpd_latency: TSBPD,
                 recv_buffer_size: PacketCount(1024),
+                recv_buffer_cap: None,
                 send_buffer_size: PacketCount(1024),
                 cipher: None,
                 stream_id: None,
                 bandwidth: LiveBandwidthMode::Unlimited,
+                retransmit_bandwidth: RetransmitBandwidth::Capped,
+                send_buffer_full: SendBufferFull::Drop,
+                flow_window_full: FlowWindowFull::Wait,
+                input_overflow: InputOverflowPolicy::Queue,
                 statistics_interval: Duration::from_secs(10),
+                loss_rate_window: Duration::from_secs(5),
+                initial_rtt: None,
+                too_late_drop_margin: Percent(0),
                 peer_idle_timeout: Duration::from_secs(5),
                 too_late_packet_drop: true,
+                delivery_mode: DeliveryMode::Strict,
+                nak_period: Default::default(),
+                recv_granularity: RecvGranularity::Message,
+                peer_version: SrtVersion::CURRENT,
+                features: SrtShakeFlags::SUPPORTED,
+                packet_filter: None,
+                negotiation_adjustments: Vec::new(),
+                session_token: None,
+                resumable_sessions: None,
             },
             handshake: crate::protocol::handshake::Handshake::Connector,
         }
     }
 
+    #[test]
+    fn peer_idle_events() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+
+        let mut now = start;
+        while connection.is_open() {
+            now = connection.check_timers(now);
+        }
+
+        let events: Vec<_> = std::iter::from_fn(|| connection.next_event()).collect();
+        assert_matches!(
+            events[..],
+            [ConnectionEvent::PeerIdle(idle), ConnectionEvent::Closed(closed)]
+                if idle >= start + Duration::from_secs(5) && closed >= idle
+        );
+    }
+
+    #[test]
+    fn loss_before_unique_data() {
+        let start = Instant::now();
+        let with_fec = || {
+            let mut connection = new_connection(start);
+            connection.settings.packet_filter = Some("fec,cols:1".parse().unwrap());
+            DuplexConnection::new(connection)
+        };
+        let (mut sender, mut receiver) = (with_fec(), with_fec());
+
+        for _ in 0..2 {
+            sender.handle_data_input(start, Some((start, Bytes::from("data"))));
+        }
+        let mut packets = Vec::new();
+        let mut now = start;
+        for _ in 0..10 {
+            now += SND;
+            sender.check_timers(now);
+            packets.extend(std::iter::from_fn(|| sender.next_packet(now)));
+        }
+
+        // the first packet and its FEC packet are lost, the second is rebuilt from its FEC packet,
+        // with nothing received to estimate the size of the first from
+        for (packet, from) in packets {
+            if let Data(data) = &packet {
+                if data.seq_number == SeqNumber(1) && data.payload != "data" {
+                    receiver.handle_packet_input(now, Ok((packet, from)));
+                }
+            }
+        }
+        receiver.update_statistics(now);
+        let statistics = receiver.statistics();
+        assert_eq!(statistics.rx_filter_supply_data, 1);
+        assert_eq!(statistics.rx_unique_data, 0);
+        assert_eq!(statistics.rx_loss_data, 1);
+        assert_eq!(statistics.rx_loss_bytes, 0);
+    }
+
+    #[test]
+    fn activity() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+        assert_eq!(connection.uptime(start + TSBPD), TSBPD);
+        assert_eq!(connection.last_recv(), None);
+        assert_eq!(connection.last_send(), None);
+
+        let keepalive = |dest_sockid| ControlPacket {
+            timestamp: TimeStamp::MIN,
+            dest_sockid,
+            control_type: KeepAlive,
+        };
+        let now = start + MILLIS;
+        connection
+            .handle_packet_input(now, Ok((Control(keepalive(local_sockid())), remote_addr())));
+        assert_eq!(connection.last_recv(), Some(now));
+
+        // only packets from the peer count
+        let later = now + MILLIS;
+        connection.handle_packet_input(later, Ok((Control(keepalive(SocketId(5))), remote_addr())));
+        assert_eq!(connection.last_recv(), Some(now));
+
+        connection.handle_data_input(later, Some((later, Bytes::new())));
+        let later = later + SND;
+        while let WaitForData(_) = connection.handle_input(later, Input::Timer) {}
+        assert_eq!(connection.last_send(), Some(later));
+        assert_eq!(connection.last_recv(), Some(now));
+    }
+
     #[test]
     fn input_data_close() {
         let start = Instant::now();
@@ -576,6 +1153,397 @@ mod duplex_connection {
         assert_eq!(connection.handle_input(now, Input::Timer), Close);
     }
 
+    #[test]
+    fn byte_counts() {
+        const PACKETS: u64 = 10;
+        const PAYLOAD: u64 = 1000;
+        let start = Instant::now();
+        let mut sender = DuplexConnection::new(new_connection(start));
+        let mut receiver = DuplexConnection::new(new_connection(start));
+
+        let mut now = start;
+        for _ in 0..PACKETS {
+            let payload = Bytes::from(vec![0; PAYLOAD as usize]);
+            let _ = sender.handle_input(now, Input::Data(Some((start, payload))));
+        }
+        let mut sent = 0;
+        while sent < PACKETS {
+            now += SND;
+            while let SendPacket((packet @ Data(_), _)) = sender.handle_input(now, Input::Timer) {
+                let _ = receiver.handle_input(now, Input::Packet(Ok((packet, remote_addr()))));
+                sent += 1;
+            }
+        }
+
+        // 16 bytes SRT + 8 bytes UDP + 20 bytes IPv4 headers
+        let header = Packet::HEADER_SIZE.0;
+        let sender = sender.statistics();
+        assert_eq!(sender.tx_data, PACKETS);
+        assert_eq!(sender.tx_payload_bytes, PACKETS * PAYLOAD);
+        assert_eq!(sender.tx_bytes, PACKETS * (PAYLOAD + header));
+
+        let receiver = receiver.statistics();
+        assert_eq!(receiver.rx_data, PACKETS);
+        assert_eq!(receiver.rx_payload_bytes, PACKETS * PAYLOAD);
+        assert_eq!(receiver.rx_bytes, PACKETS * (PAYLOAD + header));
+    }
+
+    #[test]
+    fn cumulative_ack_with_selective_nak() {
+        const PACKETS: u32 = 20;
+        // lost the first time they're sent, in between packets that get through
+        const LOST: [u32; 4] = [3, 7, 8, 12];
+        let start = Instant::now();
+        let mut sender = DuplexConnection::new(new_connection(start));
+        // only NAKs cause retransmissions
+        sender.sender.set_rto_retransmit(false);
+        let mut receiver = DuplexConnection::new(new_connection(start));
+        for _ in 0..PACKETS {
+            let payload = Bytes::from_static(b"data");
+            let _ = sender.handle_input(start, Input::Data(Some((start, payload))));
+        }
+
+        let mut received = BTreeSet::new();
+        let mut sent = 0;
+        let mut retransmitted = Vec::new();
+        // retransmissions take a while to arrive, so that ACKs go out while there are gaps
+        let mut in_flight = VecDeque::new();
+        let mut acks_behind_loss = 0;
+        let mut now = start;
+        while now < start + 200 * MILLIS {
+            now += SND;
+            while let SendPacket((packet, _)) = sender.handle_input(now, Input::Timer) {
+                match &packet {
+                    Data(data) if data.retransmitted => {
+                        retransmitted.push(data.seq_number.0);
+                        in_flight.push_back((now + 15 * MILLIS, packet));
+                        continue;
+                    }
+                    Data(data) if LOST.contains(&data.seq_number.0) => {
+                        sent = max(sent, data.seq_number.0 + 1);
+                        continue;
+                    }
+                    Data(data) => {
+                        sent = max(sent, data.seq_number.0 + 1);
+                        received.insert(data.seq_number.0);
+                    }
+                    _ => {}
+                }
+                receiver.handle_packet_input(now, Ok((packet, remote_addr())));
+            }
+            while in_flight
+                .front()
+                .is_some_and(|(arrival, _)| *arrival <= now)
+            {
+                let (_, packet) = in_flight.pop_front().unwrap();
+                if let Data(data) = &packet {
+                    received.insert(data.seq_number.0);
+                }
+                receiver.handle_packet_input(now, Ok((packet, remote_addr())));
+            }
+
+            while let SendPacket((packet, _)) = receiver.handle_input(now, Input::Timer) {
+                let ack_number = match &packet {
+                    // the ACK is the next packet expected in order, whatever came after it
+                    Control(ControlPacket {
+                        control_type: Ack(ack),
+                        ..
+                    }) => {
+                        let next = (0..).find(|n| !received.contains(n)).unwrap();
+                        assert_eq!(ack.ack_number(), SeqNumber(next));
+                        if received.last().is_some_and(|last| *last > next) {
+                            acks_behind_loss += 1;
+                        }
+                        Some(next)
+                    }
+                    // the NAKs report the gaps only
+                    Control(ControlPacket {
+                        control_type: Nak(nak),
+                        ..
+                    }) => {
+                        for seq_number in nak.iter_decompressed() {
+                            assert!(LOST.contains(&seq_number.0), "{seq_number:?}");
+                        }
+                        None
+                    }
+                    _ => None,
+                };
+                sender.handle_packet_input(now, Ok((packet, remote_addr())));
+
+                // the send buffer is trimmed up to the ACK, and no further
+                if let Some(ack_number) = ack_number {
+                    sender.update_statistics(now);
+                    let unacknowledged = sender.statistics().tx_unacknowledged_data;
+                    assert_eq!(unacknowledged, u64::from(sent - ack_number));
+                }
+            }
+        }
+        assert!(acks_behind_loss > 0);
+
+        // exactly the gaps were retransmitted, once each
+        retransmitted.sort();
+        assert_eq!(retransmitted, LOST);
+        sender.update_statistics(now);
+        assert_eq!(sender.statistics().tx_unacknowledged_data, 0);
+        assert_eq!(sender.statistics().tx_buffered_data, 0);
+    }
+
+    #[test]
+    fn ping() {
+        let start = Instant::now();
+        let mut connection = new_connection(start);
+        connection.settings.features |= SrtShakeFlags::KEEPALIVE_ECHO;
+        let mut connection = DuplexConnection::new(connection);
+        let now = start + MILLIS;
+        connection.ping(now);
+        assert_matches!(
+            connection.handle_input(now, Input::Timer),
+            SendPacket((
+                Control(ControlPacket {
+                    control_type: KeepAlive,
+                    ..
+                }),
+                _
+            ))
+        );
+
+        // the peer's keepalive is answered right away
+        let keepalive = ControlPacket {
+            timestamp: TimeStamp::MIN,
+            dest_sockid: local_sockid(),
+            control_type: KeepAlive,
+        };
+        let later = now + MILLIS;
+        connection.handle_packet_input(later, Ok((Control(keepalive), remote_addr())));
+        assert_matches!(
+            connection.handle_input(later, Input::Timer),
+            SendPacket((
+                Control(ControlPacket {
+                    control_type: Ack(Acknowledgement::Lite(_)),
+                    ..
+                }),
+                _
+            ))
+        );
+    }
+
+    #[test]
+    fn keepalive_without_echo() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+
+        // a peer that didn't offer to echo keepalives, like the reference implementation, doesn't
+        // expect an answer
+        let keepalive = ControlPacket {
+            timestamp: TimeStamp::MIN,
+            dest_sockid: local_sockid(),
+            control_type: KeepAlive,
+        };
+        let now = start + MILLIS;
+        connection.handle_packet_input(now, Ok((Control(keepalive), remote_addr())));
+        assert_matches!(connection.handle_input(now, Input::Timer), WaitForData(_));
+    }
+
+    #[test]
+    fn latency_change() {
+        let start = Instant::now();
+        let mut connection = new_connection(start);
+        connection.settings.features |= SrtShakeFlags::LATENCY_CHANGE;
+        let mut connection = DuplexConnection::new(connection);
+        let response = |latency| ControlPacket {
+            timestamp: TimeStamp::MIN,
+            dest_sockid: local_sockid(),
+            control_type: Srt(SrtControlPacket::LatencyChangeResponse(latency)),
+        };
+
+        // nothing was asked for yet
+        let now = start + MILLIS;
+        let packet = Control(response(TSBPD * 2));
+        connection.handle_packet_input(now, Ok((packet, remote_addr())));
+        assert_eq!(connection.settings().recv_tsbpd_latency, TSBPD);
+
+        // more than the handshake could agree on is clamped
+        connection.set_latency(now, Duration::from_secs(100));
+        assert_matches!(
+            connection.handle_input(now, Input::Timer),
+            SendPacket((
+                Control(ControlPacket {
+                    control_type: Srt(SrtControlPacket::LatencyChangeRequest(latency)),
+                    ..
+                }),
+                _
+            )) if latency == SrtControlPacket::MAX_LATENCY
+        );
+        let packet = Control(response(Duration::from_secs(100)));
+        connection.handle_packet_input(now, Ok((packet, remote_addr())));
+        assert_eq!(connection.settings().recv_tsbpd_latency, TSBPD);
+
+        let packet = Control(response(SrtControlPacket::MAX_LATENCY));
+        connection.handle_packet_input(now, Ok((packet, remote_addr())));
+        assert_eq!(
+            connection.settings().recv_tsbpd_latency,
+            SrtControlPacket::MAX_LATENCY
+        );
+    }
+
+    #[test]
+    fn pause_and_resume() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+        connection.pause();
+
+        // nothing goes out while paused, data included, but the connection is kept alive
+        let mut now = start;
+        let mut keepalives = 0;
+        while now < start + 1100 * MILLIS {
+            now += SND;
+            if now == start + 500 * MILLIS {
+                for _ in 0..100 {
+                    let payload = Bytes::from(vec![0; 1000]);
+                    let _ = connection.handle_input(now, Input::Data(Some((now, payload))));
+                }
+            }
+            while let SendPacket((packet, _)) = connection.handle_input(now, Input::Timer) {
+                assert_matches!(packet, Control(_));
+                if matches!(
+                    packet,
+                    Control(ControlPacket {
+                        control_type: KeepAlive,
+                        ..
+                    })
+                ) {
+                    keepalives += 1;
+                }
+            }
+        }
+        assert!(keepalives > 0);
+
+        // then the backlog is clocked out by the ACKs, starting from a small window
+        connection.resume();
+        let mut sent = 0;
+        for _ in 0..50 {
+            now += SND;
+            while let SendPacket((packet, _)) = connection.handle_input(now, Input::Timer) {
+                if matches!(
+                    packet,
+                    Data(DataPacket {
+                        retransmitted: false,
+                        ..
+                    })
+                ) {
+                    sent += 1;
+                }
+            }
+        }
+        // the initial window, and the probe that goes out right behind the 16th packet
+        assert_eq!(sent, 16 + 1);
+    }
+
+    #[test]
+    fn spoofed_packets_ignored() {
+        let start = Instant::now();
+        let mut connection = DuplexConnection::new(new_connection(start));
+
+        let mut now = start;
+        assert_matches!(connection.handle_input(now, Input::Timer), WaitForData(_));
+        assert_eq!(
+            connection.handle_input(now, Input::Data(Some((start, Bytes::new())))),
+            WaitForData(SND)
+        );
+        now += SND;
+        assert_matches!(
+            connection.handle_input(now, Input::Timer),
+            SendPacket((Data(_), _))
+        );
+        assert_matches!(connection.handle_input(now, Input::Timer), WaitForData(_));
+
+        let ack = |dest_sockid| {
+            Control(ControlPacket {
+                timestamp: TimeStamp::MIN,
+                dest_sockid,
+                control_type: Ack(Acknowledgement::Full(
+                    SeqNumber(1),
+                    AckStatistics {
+                        rtt: Rtt::new(TimeSpan::ZERO, TimeSpan::ZERO),
+                        buffer_available: 10000,
+                        packet_receive_rate: None,
+                        estimated_link_capacity: None,
+                        data_receive_rate: None,
+                    },
+                    FullAckSeqNumber::INITIAL,
+                )),
+            })
+        };
+
+        // from another host, and to another socket on the right host
+        let spoofed_addr = ([127, 0, 0, 1], 2224).into();
+        assert_matches!(
+            connection.handle_input(now, Input::Packet(Ok((ack(SocketId(2)), spoofed_addr)))),
+            WaitForData(_)
+        );
+        assert_matches!(
+            connection.handle_input(now, Input::Packet(Ok((ack(SocketId(3)), remote_addr())))),
+            WaitForData(_)
+        );
+        assert_eq!(connection.statistics().rx_all_packets, 0);
+        assert_eq!(connection.statistics().rx_ack, 0);
+
+        // a handshake from another peer socket isn't answered
+        let mut listener = new_connection(start);
+        listener.handshake = crate::protocol::handshake::Handshake::Listener(KeepAlive);
+        let mut listener = DuplexConnection::new(listener);
+        assert_matches!(listener.handle_input(start, Input::Timer), WaitForData(_));
+        let handshake = |socket_id| {
+            Control(ControlPacket {
+                timestamp: TimeStamp::MIN,
+                dest_sockid: local_sockid(),
+                control_type: Handshake(HandshakeControlInfo {
+                    init_seq_num: SeqNumber(0),
+                    max_packet_size: PacketSize(1316),
+                    max_flow_size: PacketCount(8192),
+                    shake_type: ShakeType::Conclusion,
+                    socket_id,
+                    syn_cookie: 0,
+                    peer_addr: remote_addr().ip(),
+                    info: HandshakeVsInfo::V5(HsV5Info::default()),
+                }),
+            })
+        };
+        assert_matches!(
+            listener.handle_input(
+                start,
+                Input::Packet(Ok((handshake(SocketId(3)), remote_addr())))
+            ),
+            WaitForData(_)
+        );
+        assert_matches!(
+            listener.handle_input(
+                start,
+                Input::Packet(Ok((handshake(remote_sockid()), remote_addr())))
+            ),
+            SendPacket((
+                Control(ControlPacket {
+                    control_type: KeepAlive,
+                    ..
+                }),
+                _
+            ))
+        );
+
+        // the real peer is still heard
+        assert_eq!(
+            connection.handle_input(now, Input::Packet(Ok((ack(SocketId(2)), remote_addr())))),
+            SendPacket((
+                Control(ControlPacket {
+                    timestamp: TimeStamp::from_micros(1_000),
+                    dest_sockid: SocketId(2),
+                    control_type: Ack2(FullAckSeqNumber::INITIAL),
+                }),
+                remote_addr()
+            ))
+        );
+    }
+
     #[test]
     fn too_late_packet_drop() {
         let start = Instant::now();
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::packet::{DataPacket, SeqNumber, SessionResume};
+
+/// What a resumable connection sent that the peer didn't acknowledge, to be sent first on the
+/// connection that resumes it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Resumption {
+    pub token: u64,
+    /// The sequence number of the first packet, or of the next one the connection would have
+    /// sent if there are none
+    pub seq_number: SeqNumber,
+    /// Unencrypted, in sequence number order
+    pub packets: Vec<DataPacket>,
+}
+
+impl Resumption {
+    /// What the caller asks for in the handshake of the connection that resumes it
+    pub fn session(&self) -> SessionResume {
+        SessionResume {
+            token: self.token,
+            seq_number: self.seq_number,
+        }
+    }
+
+    /// The packets from `seq_number` on, the ones before it were received already
+    pub fn skip_to(&mut self, seq_number: SeqNumber) {
+        let skipped = self
+            .packets
+            .iter()
+            .take_while(|packet| packet.seq_number < seq_number)
+            .count();
+        let _ = self.packets.drain(..skipped);
+        self.seq_number = seq_number;
+    }
+}
+
+/// How far the listener got with the stream of each resumable connection, shared by all the
+/// connections of a listener. Each is forgotten once the window passes without hearing of it.
+#[derive(Clone)]
+pub struct ResumableSessions {
+    window: Duration,
+    sessions: Arc<Mutex<HashMap<u64, (SeqNumber, Instant)>>>,
+}
+
+impl ResumableSessions {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            sessions: Default::default(),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Records that everything before `seq_number` was received on the session, it never goes
+    /// back, e.g. for a connection that lingers on after it was resumed
+    pub fn update(&self, now: Instant, token: u64, seq_number: SeqNumber) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let (received, last_heard) = sessions.entry(token).or_insert((seq_number, now));
+        if seq_number > *received {
+            *received = seq_number;
+        }
+        *last_heard = now;
+    }
+
+    /// Where the stream of the session goes on from, if it was heard of within the window
+    pub fn resume_point(&self, now: Instant, token: u64) -> Option<SeqNumber> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, (_, last_heard)| now - *last_heard <= self.window);
+        sessions.get(&token).map(|(received, _)| *received)
+    }
+}
+
+impl fmt::Debug for ResumableSessions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResumableSessions")
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+// the same sessions, rather than ones that look alike
+impl PartialEq for ResumableSessions {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.sessions, &other.sessions)
+    }
+}
+
+impl Eq for ResumableSessions {}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::packet::*;
+
+    #[test]
+    fn skip_to() {
+        let packet = |n| DataPacket {
+            seq_number: SeqNumber(n),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            message_number: MsgNumber(n),
+            timestamp: TimeStamp::MIN,
+            dest_sockid: SocketId(2),
+            payload: Bytes::new(),
+            retransmitted: false,
+        };
+        let mut resumption = Resumption {
+            token: 1,
+            seq_number: SeqNumber(10),
+            packets: (10..15).map(packet).collect(),
+        };
+
+        resumption.skip_to(SeqNumber(12));
+        assert_eq!(resumption.seq_number, SeqNumber(12));
+        assert_eq!(resumption.packets, (12..15).map(packet).collect::<Vec<_>>());
+
+        // the peer got everything, the connection goes on from where it left off
+        resumption.skip_to(SeqNumber(15));
+        assert_eq!(resumption.session().seq_number, SeqNumber(15));
+        assert!(resumption.packets.is_empty());
+    }
+
+    #[test]
+    fn resume_point() {
+        let start = Instant::now();
+        let window = Duration::from_secs(1);
+        let sessions = ResumableSessions::new(window);
+        let seq = SeqNumber::new_truncate;
+
+        assert_eq!(sessions.resume_point(start, 1), None);
+        sessions.update(start, 1, seq(10));
+        sessions.update(start, 2, seq(20));
+        assert_eq!(sessions.resume_point(start, 1), Some(seq(10)));
+
+        // a connection the session moved on from doesn't take it back
+        sessions.update(start, 1, seq(15));
+        sessions.update(start, 1, seq(12));
+        assert_eq!(sessions.resume_point(start, 1), Some(seq(15)));
+
+        // clones share the sessions
+        let later = start + window / 2;
+        sessions.clone().update(later, 2, seq(25));
+        assert_eq!(sessions.resume_point(later, 2), Some(seq(25)));
+
+        // forgotten once the window passes without hearing of it
+        let expired = start + window + Duration::from_millis(1);
+        assert_eq!(sessions.resume_point(expired, 1), None);
+        assert_eq!(sessions.resume_point(expired, 2), Some(seq(25)));
+    }
+}
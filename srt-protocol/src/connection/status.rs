@@ -12,11 +12,23 @@ enum Status {
     Closed,
 }
 
+/// Why the peer went away, for a connection that wasn't closed locally
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CloseReason {
+    /// The peer shut the connection down
+    PeerShutdown,
+    /// Nothing was received from the peer within the peer idle timeout
+    PeerIdle,
+    /// A packet didn't get through to the peer within the maximum retransmissions
+    RetransmitLimit,
+}
+
 #[derive(Debug)]
 pub struct ConnectionStatus {
     connection: Status,
     sender: Status,
     receiver: Status,
+    close_reason: Option<CloseReason>,
 }
 
 impl ConnectionStatus {
@@ -25,9 +37,14 @@ impl ConnectionStatus {
             connection: Status::Open(flush_timeout),
             receiver: Status::Open(flush_timeout),
             sender: Status::Open(flush_timeout),
+            close_reason: None,
         }
     }
 
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.close_reason
+    }
+
     pub fn is_open(&self) -> bool {
         !self.is_closed()
     }
@@ -36,6 +53,16 @@ impl ConnectionStatus {
         matches!(self.connection, Status::Closed)
     }
 
+    /// Extends the flush timeout of the parts that are still open, e.g. when the latency grows
+    pub fn extend_flush_timeout(&mut self, flush_timeout: Duration) {
+        use Status::*;
+        for status in [&mut self.connection, &mut self.sender, &mut self.receiver] {
+            if let Open(timeout) = status {
+                *timeout = (*timeout).max(flush_timeout);
+            }
+        }
+    }
+
     pub fn should_drain_send_buffer(&self) -> bool {
         use Status::*;
         matches!(self.sender, Shutdown(_) | Drain(_))
@@ -62,6 +89,16 @@ impl ConnectionStatus {
         if let Open(timeout) = self.receiver {
             info!("peer idle timeout, receiver is draining");
             self.receiver = Drain(now + timeout);
+            self.close_reason = Some(CloseReason::PeerIdle);
+        }
+    }
+
+    pub fn on_retransmit_limit(&mut self, now: Instant) {
+        use Status::*;
+        if let Open(timeout) = self.receiver {
+            info!("retransmit limit exceeded, receiver is draining");
+            self.receiver = Drain(now + timeout);
+            self.close_reason = Some(CloseReason::RetransmitLimit);
         }
     }
 
@@ -70,6 +107,7 @@ impl ConnectionStatus {
         if let Open(timeout) = self.receiver {
             info!("{log_sockid:?} received shutdown packet, draining for {timeout:?}");
             self.receiver = Drain(now + timeout);
+            self.close_reason = Some(CloseReason::PeerShutdown);
         }
     }
 
@@ -111,19 +149,35 @@ impl ConnectionStatus {
         match self.receiver {
             Shutdown(_) | Drain(_) if receive_buffer_flushed => {
                 self.receiver = Closed;
-                self.connection = Closed;
-                info!("{log_sockid:?} reciever closed and flushed, connection is closed");
+                info!("{log_sockid:?} reciever closed and flushed");
+                self.close_unless_sending(log_sockid);
                 false
             }
             Shutdown(timeout) | Drain(timeout) if now > timeout => {
                 self.receiver = Closed;
-                self.connection = Closed;
-                info!("{log_sockid:?} reciever timed out flushing ({:?} too late), connection is closed", now - timeout);
+                info!(
+                    "{log_sockid:?} reciever timed out flushing ({:?} too late)",
+                    now - timeout
+                );
+                self.close_unless_sending(log_sockid);
                 true
             }
             _ => false,
         }
     }
+
+    // when both sides close at once, their shutdowns cross: the peer is still there to acknowledge
+    // what this side is flushing, so the sender gets to finish, and closes the connection itself
+    fn close_unless_sending(&mut self, log_sockid: SocketId) {
+        use Status::*;
+        let peer_shutdown = self.close_reason == Some(CloseReason::PeerShutdown);
+        if peer_shutdown && matches!(self.sender, Shutdown(_) | Drain(_)) {
+            info!("{log_sockid:?} sender still flushing, connection closes once it's done");
+        } else {
+            self.connection = Closed;
+            info!("{log_sockid:?} connection is closed");
+        }
+    }
 }
 
 #[cfg(test)]
@@ -146,4 +200,66 @@ mod tests {
         assert!(!status.is_closed());
         assert!(!status.should_drain_send_buffer());
     }
+    #[test]
+    fn close_reason() {
+        let timeout = Duration::from_secs(10);
+        let now = Instant::now();
+        let sockid = SocketId(1);
+
+        let mut status = ConnectionStatus::new(timeout);
+        status.handle_shutdown_packet(now, sockid);
+        assert_eq!(status.close_reason(), Some(CloseReason::PeerShutdown));
+
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_peer_idle_timeout(now);
+        assert_eq!(status.close_reason(), Some(CloseReason::PeerIdle));
+
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_retransmit_limit(now);
+        assert_eq!(status.close_reason(), Some(CloseReason::RetransmitLimit));
+
+        // the peer answering a shutdown sent from this side doesn't count
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_socket_closed(now);
+        status.handle_shutdown_packet(now, sockid);
+        assert_eq!(status.close_reason(), None);
+    }
+
+    #[test]
+    fn simultaneous_close() {
+        let timeout = Duration::from_secs(10);
+        let now = Instant::now();
+        let sockid = SocketId(1);
+
+        // the peer's shutdown crosses this side's, which is still flushing
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_data_stream_closed(now);
+        status.handle_shutdown_packet(now, sockid);
+        assert!(!status.check_receive_close_timeout(now, true, sockid));
+        assert!(status.is_open());
+
+        // the sender gets to send what's left, and its own shutdown
+        assert!(!status.check_sender_shutdown(now, false, true, true));
+        assert!(status.check_sender_shutdown(now, true, true, true));
+        assert!(status.is_open());
+        assert!(!status.check_sender_shutdown(now, true, true, true));
+        assert!(status.is_closed());
+
+        // not past the flush deadline though
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_data_stream_closed(now);
+        status.handle_shutdown_packet(now, sockid);
+        assert!(!status.check_receive_close_timeout(now, true, sockid));
+        let late = now + timeout + Duration::from_millis(1);
+        assert!(status.check_sender_shutdown(late, false, true, true));
+        assert!(!status.check_sender_shutdown(late, false, true, true));
+        assert!(status.is_closed());
+
+        // a peer that went quiet isn't waited for
+        let mut status = ConnectionStatus::new(timeout);
+        status.on_data_stream_closed(now);
+        status.on_peer_idle_timeout(now);
+        assert!(!status.check_receive_close_timeout(now, true, sockid));
+        assert!(status.is_closed());
+    }
 }
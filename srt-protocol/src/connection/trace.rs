@@ -0,0 +1,44 @@
+//! Structured events for the optional `tracing` integration, where the connection acts on what
+//! the peer reports and reports back: ACKs, NAKs and drops. They carry no fields of the connection
+//! themselves, they're meant to be emitted within a span that does, like the `srt_connection`
+//! span of srt-tokio.
+
+use std::ops::Range;
+
+use tracing::{debug, trace, warn};
+
+use crate::packet::{ControlTypes, SeqNumber};
+
+pub fn control_received(control: &ControlTypes) {
+    use ControlTypes::*;
+    match control {
+        Ack(ack) => trace!(ack = ?ack.ack_number(), "ack received"),
+        Nak(nak) => debug!(lost = nak.iter_decompressed().count(), "nak received"),
+        DropRequest { range, .. } => {
+            debug!(first = ?range.start(), last = ?range.end(), "drop request received")
+        }
+        _ => {}
+    }
+}
+
+pub fn control_sent(control: &ControlTypes) {
+    use ControlTypes::*;
+    match control {
+        Ack(ack) => trace!(ack = ?ack.ack_number(), "ack sent"),
+        Nak(nak) => debug!(lost = nak.iter_decompressed().count(), "nak sent"),
+        DropRequest { range, .. } => {
+            debug!(first = ?range.start(), last = ?range.end(), "drop request sent")
+        }
+        _ => {}
+    }
+}
+
+/// `side` gave up on these packets, they were due before they could be sent or received
+pub fn too_late_dropped(side: &str, packets: &Range<SeqNumber>) {
+    warn!(
+        side,
+        first = ?packets.start,
+        count = packets.end - packets.start,
+        "too late packets dropped"
+    );
+}
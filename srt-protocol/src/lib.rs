@@ -1,8 +1,25 @@
+//! SRT protocol state machines, free of any I/O. Without the default `std` feature, only the
+//! packet codec and the option types it uses are built, for `no_std` targets with an allocator.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod access;
+#[cfg(feature = "std")]
 pub mod connection;
+#[cfg(feature = "std")]
 pub mod listener;
 pub mod options;
 pub mod packet;
+#[cfg(feature = "std")]
 pub mod protocol;
+#[cfg(feature = "std")]
 pub mod settings;
+#[cfg(feature = "std")]
 pub mod statistics;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+pub use options::{compatible_features, protocol_version};
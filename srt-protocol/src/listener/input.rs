@@ -19,6 +19,8 @@ pub enum Input {
     AccessResponse(Option<(SessionId, AccessControlResponse)>),
     Success(ResultOf),
     Failure(ResultOf),
+    /// The socket of an open connection was closed, or dropped
+    ConnectionClosed(SessionId),
     Timer,
 }
 
@@ -20,6 +20,7 @@ pub struct MultiplexListener {
     local_address: SocketAddr,
     settings: ConnInitSettings,
     sessions: HashMap<SessionId, SessionState>,
+    max_connections: Option<usize>,
     stats: ListenerStatistics,
     stats_timer: Timer,
 }
@@ -31,15 +32,31 @@ impl MultiplexListener {
             local_address,
             settings,
             sessions: Default::default(),
+            max_connections: None,
             stats: Default::default(),
             stats_timer: Timer::new(now, Duration::from_secs(1)),
         }
     }
 
+    /// Handshakes beyond this many open connections are rejected with
+    /// [`CoreRejectReason::Backlog`], `None` for no limit
+    pub fn set_max_connections(&mut self, max_connections: Option<usize>) {
+        self.max_connections = max_connections;
+    }
+
+    /// The number of connections currently open, pending handshakes are not counted
+    pub fn connection_count(&self) -> usize {
+        self.sessions
+            .values()
+            .filter(|session| matches!(session, SessionState::Open))
+            .count()
+    }
+
     pub fn handle_input(&mut self, now: Instant, input: Input) -> Action {
         match input {
             Input::Packet(packet) => self.handle_input_packet(now, packet),
             Input::AccessResponse(response) => self.handle_input_access_response(now, response),
+            Input::ConnectionClosed(session_id) => Action::DropConnection(session_id),
             Input::Timer => self.handle_timer(now),
             Input::Success(result_of) => self.handle_success(now, result_of),
             Input::Failure(result_of) => self.handle_failure(now, result_of),
@@ -70,11 +87,30 @@ impl MultiplexListener {
         self.stats.rx_packets += 1;
         //self.stats.rx_bytes += packet
         let session_id = SessionId(packet.1);
+        // data only goes to an open connection, what comes before the handshake is done (or with
+        // no handshake at all) is dropped, without starting a session for it
+        if packet.0.data().is_some() && !self.is_open(session_id) {
+            self.stats.rx_early_data += 1;
+            log::debug!(
+                "{:?}|listen:{}|early data - {:?}",
+                TimeSpan::from_interval(self.start_time, now),
+                self.local_address.port(),
+                packet
+            );
+            return Action::WaitForInput;
+        }
+        let at_capacity = self.at_capacity();
         let settings = &self.settings;
-        self.sessions
+        let session = self
+            .sessions
             .entry(session_id)
-            .or_insert_with(|| SessionState::new_pending(settings.clone()))
-            .handle_packet(now, session_id, packet)
+            .or_insert_with(|| SessionState::new_pending(settings.clone()));
+        match session.handle_packet(now, session_id, packet) {
+            Action::RequestAccess(_, _) if at_capacity => {
+                session.handle_access_control_response(now, session_id, Self::backlog_exceeded())
+            }
+            action => action,
+        }
     }
 
     fn handle_packet_receive_error(&mut self, now: Instant, error: ReceivePacketError) -> Action {
@@ -95,12 +131,30 @@ impl MultiplexListener {
         session_id: SessionId,
         response: AccessControlResponse,
     ) -> Action {
+        // another connection may have been opened while this one was waiting for approval
+        let response = match response {
+            AccessControlResponse::Accepted(_) if self.at_capacity() => Self::backlog_exceeded(),
+            response => response,
+        };
         match self.sessions.get_mut(&session_id) {
             Some(session) => session.handle_access_control_response(now, session_id, response),
             None => Action::DropConnection(session_id),
         }
     }
 
+    fn is_open(&self, session_id: SessionId) -> bool {
+        matches!(self.sessions.get(&session_id), Some(SessionState::Open))
+    }
+
+    fn at_capacity(&self) -> bool {
+        self.max_connections
+            .is_some_and(|max| self.connection_count() >= max)
+    }
+
+    fn backlog_exceeded() -> AccessControlResponse {
+        AccessControlResponse::Rejected(RejectReason::Core(CoreRejectReason::Backlog))
+    }
+
     fn handle_timer(&mut self, now: Instant) -> Action {
         if self.stats_timer.check_expired(now).is_some() {
             Action::UpdateStatistics(&self.stats)
@@ -173,9 +227,13 @@ mod test {
         time::Duration,
     };
 
+    use bytes::Bytes;
     use rand::random;
 
-    use crate::options::{KeySize, PacketCount, PacketSize, SrtVersion};
+    use crate::{
+        options::{KeySize, PacketCount, PacketSize, SrtVersion},
+        settings::AcceptParameters,
+    };
 
     use super::*;
 
@@ -216,6 +274,10 @@ mod test {
                 ext_km: None,
                 ext_group: None,
                 sid: None,
+                filter: None,
+                resume: None,
+                auth: None,
+                challenge: None,
             }),
         }
     }
@@ -254,7 +316,10 @@ mod test {
 
         let action = listener.handle_input(
             Instant::now(),
-            Input::AccessResponse(Some((session_id(), AccessControlResponse::Accepted(None)))),
+            Input::AccessResponse(Some((
+                session_id(),
+                AccessControlResponse::Accepted(AcceptParameters::new()),
+            ))),
         );
         assert_matches!(action, Action::OpenConnection(_, _));
 
@@ -270,6 +335,65 @@ mod test {
         assert_matches!(action, Action::DelegatePacket(_, _));
     }
 
+    fn data_packet() -> Packet {
+        Packet::Data(DataPacket {
+            seq_number: random(),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: random(),
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: dest_sock_id(),
+            payload: Bytes::from_static(b"early"),
+        })
+    }
+
+    #[test]
+    fn early_data() {
+        use crate::listener::ResultOf::*;
+
+        let settings = ConnInitSettings::default();
+        let local = "127.0.0.1:2000".parse().unwrap();
+        let mut listener = MultiplexListener::new(Instant::now(), local, settings);
+
+        // with no handshake at all, it doesn't start a session
+        let other_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8766);
+        let action = listener.handle_input(
+            Instant::now(),
+            Input::Packet(Ok((data_packet(), other_addr))),
+        );
+        assert_eq!(action, Action::WaitForInput);
+        assert!(listener.sessions.is_empty());
+
+        // reordered ahead of the end of the handshake, it's dropped and the handshake goes on
+        let action = handshake(&mut listener, conn_addr(), build_hs_pack(test_conclusion()));
+        assert_matches!(action, Action::RequestAccess(_, _));
+        let action = listener.handle_input(
+            Instant::now(),
+            Input::Packet(Ok((data_packet(), conn_addr()))),
+        );
+        assert_eq!(action, Action::WaitForInput);
+        let action = listener.handle_input(
+            Instant::now(),
+            Input::AccessResponse(Some((
+                session_id(),
+                AccessControlResponse::Accepted(AcceptParameters::new()),
+            ))),
+        );
+        assert_matches!(action, Action::OpenConnection(_, _));
+        listener.handle_input(Instant::now(), Input::Success(OpenConnection(session_id())));
+        assert_eq!(listener.stats.rx_early_data, 2);
+
+        // once the connection is open, it gets the data
+        let action = listener.handle_input(
+            Instant::now(),
+            Input::Packet(Ok((data_packet(), conn_addr()))),
+        );
+        assert_matches!(action, Action::DelegatePacket(_, (Packet::Data(_), _)));
+        assert_eq!(listener.stats.rx_early_data, 2);
+    }
+
     #[test]
     fn reject() {
         let settings = ConnInitSettings::default();
@@ -312,4 +436,80 @@ mod test {
         );
         assert_eq!(action, Action::WaitForInput);
     }
+
+    fn handshake(
+        listener: &mut MultiplexListener,
+        addr: SocketAddr,
+        conclusion: Packet,
+    ) -> Action<'_> {
+        let packet = build_hs_pack(test_induction());
+        let action = listener.handle_input(Instant::now(), Input::Packet(Ok((packet, addr))));
+        assert_matches!(action, Action::SendPacket(_));
+        listener.handle_input(Instant::now(), Input::Packet(Ok((conclusion, addr))))
+    }
+
+    #[test]
+    fn max_connections() {
+        use crate::listener::ResultOf::*;
+
+        let settings = ConnInitSettings::default();
+        let local = "127.0.0.1:2000".parse().unwrap();
+        let mut listener = MultiplexListener::new(Instant::now(), local, settings);
+        listener.set_max_connections(Some(1));
+
+        let other_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8766);
+        let other_conclusion = || {
+            let mut conclusion = test_conclusion();
+            conclusion.syn_cookie =
+                crate::protocol::pending_connection::cookie::gen_cookie(&other_addr);
+            build_hs_pack(conclusion)
+        };
+        let action = handshake(&mut listener, conn_addr(), build_hs_pack(test_conclusion()));
+        assert_matches!(action, Action::RequestAccess(_, _));
+        let action = listener.handle_input(
+            Instant::now(),
+            Input::AccessResponse(Some((
+                session_id(),
+                AccessControlResponse::Accepted(AcceptParameters::new()),
+            ))),
+        );
+        assert_matches!(action, Action::OpenConnection(_, _));
+        listener.handle_input(Instant::now(), Input::Success(OpenConnection(session_id())));
+        assert_eq!(listener.connection_count(), 1);
+
+        // the listener is full
+        let action = handshake(&mut listener, other_addr, other_conclusion());
+        assert_matches!(
+            action,
+            Action::RejectConnection(
+                _,
+                Some((
+                    Packet::Control(ControlPacket {
+                        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                            shake_type: ShakeType::Rejection(RejectReason::Core(
+                                CoreRejectReason::Backlog
+                            )),
+                            ..
+                        }),
+                        ..
+                    }),
+                    _
+                ))
+            )
+        );
+        listener.handle_input(
+            Instant::now(),
+            Input::Success(RejectConnection(SessionId(other_addr))),
+        );
+        assert_eq!(listener.stats.cx_rejected, 1);
+
+        // closing the open connection makes room for another one
+        let action = listener.handle_input(Instant::now(), Input::ConnectionClosed(session_id()));
+        assert_eq!(action, Action::DropConnection(session_id()));
+        listener.handle_input(Instant::now(), Input::Success(DropConnection(session_id())));
+        assert_eq!(listener.connection_count(), 0);
+
+        let action = handshake(&mut listener, other_addr, other_conclusion());
+        assert_matches!(action, Action::RequestAccess(_, _));
+    }
 }
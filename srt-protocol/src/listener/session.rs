@@ -9,6 +9,7 @@ use crate::{
 
 use super::*;
 
+// sessions never hand out the listener's statistics, so their actions don't borrow anything
 #[derive(Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum SessionState {
@@ -28,7 +29,7 @@ impl SessionState {
         now: Instant,
         session_id: SessionId,
         packet: (Packet, SocketAddr),
-    ) -> Action {
+    ) -> Action<'static> {
         use SessionState::*;
         match self {
             Pending(listen) => {
@@ -46,7 +47,7 @@ impl SessionState {
         now: Instant,
         session_id: SessionId,
         response: AccessControlResponse,
-    ) -> Action {
+    ) -> Action<'static> {
         use SessionState::*;
         match self {
             Pending(listen) => {
@@ -79,7 +80,7 @@ impl SessionState {
         &mut self,
         session_id: SessionId,
         result: ConnectionResult,
-    ) -> Action {
+    ) -> Action<'static> {
         use ConnectionResult::*;
         match result {
             // TODO: do something with the error?
@@ -96,14 +97,18 @@ impl SessionState {
         }
     }
 
-    fn reject(&mut self, session_id: SessionId, packet: Option<(Packet, SocketAddr)>) -> Action {
+    fn reject(
+        &mut self,
+        session_id: SessionId,
+        packet: Option<(Packet, SocketAddr)>,
+    ) -> Action<'static> {
         if !matches!(self, SessionState::Rejecting(_)) {
             *self = SessionState::Rejecting(packet.clone());
         }
         Action::RejectConnection(session_id, packet)
     }
 
-    fn drop(&mut self, session_id: SessionId) -> Action {
+    fn drop(&mut self, session_id: SessionId) -> Action<'static> {
         if !matches!(self, SessionState::Dropping) {
             *self = SessionState::Dropping;
         }
@@ -115,7 +120,7 @@ impl SessionState {
         session_id: SessionId,
         packet: Option<(Packet, SocketAddr)>,
         connection: Connection,
-    ) -> Action {
+    ) -> Action<'static> {
         if !matches!(self, SessionState::Open) {
             *self = SessionState::Open;
         }
@@ -4,6 +4,7 @@ pub struct ListenerStatistics {
     pub rx_bytes: u64,
     pub rx_parse_errors: u64,
     pub rx_io_errors: u64,
+    pub rx_early_data: u64,
 
     pub tx_packets: u64,
     pub tx_bytes: u64,
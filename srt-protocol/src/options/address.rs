@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     convert::{TryFrom, TryInto},
+    fmt,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     num::ParseIntError,
     str::FromStr,
@@ -157,3 +158,151 @@ impl From<url::ParseError> for SocketAddressParseError {
         SocketAddressParseError::InvalidHost(error.to_string())
     }
 }
+
+/// An IP network, an address and the number of leading bits of it that are fixed, e.g.
+/// 10.0.0.0/8 or fd00::/8. A plain address is a network of just that address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self, IpNetParseError> {
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix_len > max_prefix_len {
+            return Err(IpNetParseError::InvalidPrefixLength(prefix_len.to_string()));
+        }
+        Ok(Self { addr, prefix_len })
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Whether the address is in the network, an IPv4 address mapped to IPv6 (::ffff:a.b.c.d)
+    /// is compared as the IPv4 address it maps
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            addr => addr,
+        };
+        let leading_bits_match = |net: u128, addr: u128, bits: u32| match self.prefix_len {
+            0 => true,
+            len => (net ^ addr) >> (bits - u32::from(len)) == 0,
+        };
+        match (self.addr, addr) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                leading_bits_match(u32::from(net).into(), u32::from(addr).into(), 32)
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => leading_bits_match(net.into(), addr.into(), 128),
+            _ => false,
+        }
+    }
+}
+
+impl From<IpAddr> for IpNet {
+    fn from(addr: IpAddr) -> Self {
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        Self { addr, prefix_len }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = IpNetParseError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value, None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .map_err(|_| IpNetParseError::InvalidAddress(addr.to_string()))?;
+        match prefix_len {
+            Some(prefix_len) => {
+                let len = u8::from_str(prefix_len)
+                    .map_err(|_| IpNetParseError::InvalidPrefixLength(prefix_len.to_string()))?;
+                Self::new(addr, len)
+            }
+            None => Ok(addr.into()),
+        }
+    }
+}
+
+impl TryFrom<&str> for IpNet {
+    type Error = IpNetParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl TryFrom<String> for IpNet {
+    type Error = IpNetParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+impl From<IpNet> for String {
+    fn from(net: IpNet) -> Self {
+        net.to_string()
+    }
+}
+
+#[derive(Error, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum IpNetParseError {
+    #[error("Invalid network address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid network prefix length: {0}")]
+    InvalidPrefixLength(String),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ip_net() {
+        let net: IpNet = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains([10, 1, 200, 3].into()));
+        assert!(!net.contains([10, 2, 0, 1].into()));
+        assert!(net.contains("::ffff:10.1.0.1".parse().unwrap()));
+        assert!(!net.contains("::1".parse().unwrap()));
+
+        let host: IpNet = "127.0.0.2".parse().unwrap();
+        assert_eq!(host.prefix_len(), 32);
+        assert!(host.contains([127, 0, 0, 2].into()));
+        assert!(!host.contains([127, 0, 0, 1].into()));
+
+        let any: IpNet = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains([192, 168, 1, 1].into()));
+
+        let v6: IpNet = "fd00::/8".parse().unwrap();
+        assert!(v6.contains("fd12::1".parse().unwrap()));
+        assert!(!v6.contains("fe80::1".parse().unwrap()));
+        assert_eq!(v6.to_string(), "fd00::/8");
+
+        assert_eq!(
+            "10.0.0.0/33".parse::<IpNet>(),
+            Err(IpNetParseError::InvalidPrefixLength("33".into()))
+        );
+        assert_eq!(
+            "10.0.0/8".parse::<IpNet>(),
+            Err(IpNetParseError::InvalidAddress("10.0.0".into()))
+        );
+    }
+}
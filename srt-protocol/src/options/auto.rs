@@ -0,0 +1,157 @@
+use std::{convert::TryInto, time::Duration};
+
+use super::*;
+
+/// Calls the remote for `call_timeout`, and if it doesn't answer, meets it in rendezvous on the
+/// same local port. For two peers that don't know which of them starts first: whichever misses
+/// the other as a caller ends up in rendezvous, where the other one joins it once its own call
+/// times out, not available in the reference implementation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AutoOptions {
+    pub remote: SocketAddress,
+    pub call_timeout: Duration,
+    pub socket: SocketOptions,
+}
+
+impl AutoOptions {
+    pub fn new(
+        remote: impl TryInto<SocketAddress>,
+        call_timeout: Duration,
+    ) -> Result<Valid<Self>, OptionsError> {
+        let socket = Default::default();
+        Self::with(remote, call_timeout, socket)
+    }
+
+    pub fn with(
+        remote: impl TryInto<SocketAddress>,
+        call_timeout: Duration,
+        socket: SocketOptions,
+    ) -> Result<Valid<AutoOptions>, OptionsError> {
+        let remote = remote
+            .try_into()
+            .map_err(|_| OptionsError::InvalidRemoteAddress)?;
+        Self {
+            remote,
+            call_timeout,
+            socket,
+        }
+        .try_validate()
+    }
+
+    /// The options to call the remote with, giving up after `call_timeout`
+    pub fn caller(&self) -> Result<Valid<CallerOptions>, OptionsError> {
+        let mut socket = self.socket.clone();
+        socket.connect.timeout = self.call_timeout;
+        CallerOptions::with(self.remote.clone(), None, socket)
+    }
+
+    /// The options to meet the remote in rendezvous with, once calling it failed
+    pub fn rendezvous(&self) -> Result<Valid<RendezvousOptions>, OptionsError> {
+        RendezvousOptions::with(self.remote.clone(), self.socket.clone())
+    }
+}
+
+impl Validation for AutoOptions {
+    type Error = OptionsError;
+
+    fn is_valid(&self) -> Result<(), Self::Error> {
+        let local = &self.socket.connect.local;
+        use SocketHost::*;
+        let mismatch = match (&self.remote.host, local.ip().is_ipv4()) {
+            (Ipv4(ipv4), false) => Some((*ipv4).into()),
+            (Ipv6(ipv6), true) => Some((*ipv6).into()),
+            _ => None,
+        };
+        if let Some(remote_ip) = mismatch {
+            return Err(OptionsError::MismatchedAddressFamilies(
+                remote_ip,
+                local.ip(),
+            ));
+        }
+        // the peer has to know where to find it once it falls back to rendezvous
+        if local.port() == 0 {
+            return Err(OptionsError::LocalPortRequiredToListen);
+        }
+        if self.call_timeout.is_zero() {
+            return Err(OptionsError::CallTimeoutOutOfRange(self.call_timeout));
+        }
+        self.socket.is_valid()?;
+        self.is_valid_composite()
+    }
+}
+
+impl CompositeValidation for AutoOptions {
+    fn is_valid_composite(&self) -> Result<(), <Self as Validation>::Error> {
+        Ok(())
+    }
+}
+
+impl OptionsOf<SocketOptions> for AutoOptions {
+    fn set_options(&mut self, value: SocketOptions) {
+        self.socket = value;
+    }
+}
+
+impl OptionsOf<Connect> for AutoOptions {
+    fn set_options(&mut self, value: Connect) {
+        self.socket.connect = value;
+    }
+}
+
+impl OptionsOf<Session> for AutoOptions {
+    fn set_options(&mut self, value: Session) {
+        self.socket.session = value;
+    }
+}
+
+impl OptionsOf<Encryption> for AutoOptions {
+    fn set_options(&mut self, value: Encryption) {
+        self.socket.encryption = value;
+    }
+}
+
+impl OptionsOf<Sender> for AutoOptions {
+    fn set_options(&mut self, value: Sender) {
+        self.socket.sender = value;
+    }
+}
+
+impl OptionsOf<Receiver> for AutoOptions {
+    fn set_options(&mut self, value: Receiver) {
+        self.socket.receiver = value;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use super::*;
+
+    #[test]
+    fn validation() {
+        let mut socket = SocketOptions::default();
+        socket.connect.local = "0.0.0.0:3000".parse().unwrap();
+        let timeout = Duration::from_secs(1);
+
+        let options = AutoOptions::with("127.0.0.1:3001", timeout, socket.clone()).unwrap();
+        assert_eq!(options.caller().unwrap().socket.connect.timeout, timeout);
+        assert_eq!(options.rendezvous().unwrap().remote, options.remote);
+
+        assert_eq!(
+            AutoOptions::new("127.0.0.1:3001", timeout),
+            Err(OptionsError::LocalPortRequiredToListen)
+        );
+        assert_eq!(
+            AutoOptions::with("127.0.0.1:3001", Duration::ZERO, socket.clone()),
+            Err(OptionsError::CallTimeoutOutOfRange(Duration::ZERO))
+        );
+        assert_eq!(
+            AutoOptions::with("[::1]:3001".parse::<SocketAddr>().unwrap(), timeout, socket),
+            Err(OptionsError::MismatchedAddressFamilies(
+                "::1".parse().unwrap(),
+                "0.0.0.0".parse().unwrap()
+            ))
+        );
+    }
+}
@@ -6,6 +6,7 @@ use super::*;
 /// Note that Maximum Bandwidth, Input Rate, and Input Rate Estimate are bytes per second
 /// and Overhead is a percentage.
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LiveBandwidthMode {
     /// Set the maximum bandwidth explicitly.
     ///
@@ -64,3 +65,20 @@ pub enum LiveBandwidthMode {
     #[default]
     Unlimited,
 }
+
+/// Whether retransmissions count against the bandwidth the sender is limited to, not available in
+/// the reference implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RetransmitBandwidth {
+    /// Retransmissions take their turn with new packets at the sending period, so everything sent
+    /// stays within the maximum bandwidth. New packets are held back while the lost ones go out,
+    /// so recovering from loss takes longer the less room there is above the input rate.
+    #[default]
+    Capped,
+
+    /// Retransmissions go out as soon as they are due, on top of the new packets sent at the
+    /// sending period. Loss is recovered from faster, at the cost of bursts above the maximum
+    /// bandwidth.
+    Burst,
+}
@@ -5,6 +5,7 @@ pub enum BindOptions {
     Listen(Valid<ListenerOptions>),
     Call(Valid<CallerOptions>),
     Rendezvous(Valid<RendezvousOptions>),
+    Auto(Valid<AutoOptions>),
 }
 
 impl From<Valid<ListenerOptions>> for BindOptions {
@@ -25,6 +26,12 @@ impl From<Valid<RendezvousOptions>> for BindOptions {
     }
 }
 
+impl From<Valid<AutoOptions>> for BindOptions {
+    fn from(options: Valid<AutoOptions>) -> Self {
+        BindOptions::Auto(options)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -6,16 +6,84 @@ use std::{
 use super::*;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Connect {
     pub local: SocketAddr,
 
+    /// SRTO_BINDTODEVICE
+    ///
+    /// The network interface the UDP socket is bound to (see SO_BINDTODEVICE), so it only sends
+    /// and receives through that device regardless of routing. Only supported on Linux, binding
+    /// the socket fails elsewhere.
+    ///
+    /// Default is None.
+    pub bind_device: Option<String>,
+
+    /// SRTO_REUSEADDR
+    ///
+    /// Allows binding the UDP socket to an address that is in use (see SO_REUSEADDR), e.g. by a
+    /// socket lingering from a previous run.
+    ///
+    /// Default is false.
+    pub reuse_address: bool,
+
+    /// Allows several UDP sockets, each with this set, to bind to the same address and port (see
+    /// SO_REUSEPORT), so listeners in separate processes or threads share the port and the system
+    /// balances incoming datagrams between them, not available in the reference implementation.
+    /// Only supported on Unix platforms, binding the socket fails elsewhere.
+    ///
+    /// Default is false.
+    pub reuse_port: bool,
+
     /// Connect timeout. This option applies to the caller and rendezvous connection modes.
     /// For the rendezvous mode (see SRTO_RENDEZVOUS) the effective connection timeout will be 10 times
-    /// the value set with SRTO_CONNTIMEO.
+    /// the value set with SRTO_CONNTIMEO. A listener forgets a half-open connection after it.
     ///
     /// Default is 3 seconds.
     pub timeout: Duration,
 
+    /// The interval at which the handshake is sent again while waiting for the peer to answer.
+    /// This option applies to the caller and rendezvous connection modes. On links with a round
+    /// trip time close to or above it, raise it to avoid sending duplicate handshakes.
+    ///
+    /// Default is 100ms.
+    pub handshake_retry_interval: Duration,
+
+    /// The number of times each handshake packet is sent again before giving up with a timeout.
+    /// This option applies to the caller and rendezvous connection modes. With None, the handshake
+    /// is retried until the connect timeout expires.
+    ///
+    /// Default is None.
+    pub max_handshake_retries: Option<u32>,
+
+    /// The number of half-open connections, whose induction was answered but whose conclusion
+    /// hasn't come yet, a listener keeps track of. Inductions beyond it are dropped, so a flood of
+    /// them can't grow the listener's state without bound, and a half-open connection is forgotten
+    /// once the connect timeout passes. This option applies to the listener connection mode, not
+    /// available in the reference implementation.
+    ///
+    /// Default is 64.
+    pub max_half_open: usize,
+
+    /// The networks a listener accepts handshakes from. A handshake from any other address is
+    /// rejected as soon as it arrives, before the listener keeps any state or does any crypto
+    /// work for it. This option applies to the listener connection mode, not available in the
+    /// reference implementation.
+    ///
+    /// Default is None, which accepts handshakes from anywhere.
+    pub allow_from: Option<Vec<IpNet>>,
+
+    /// How long a dropped connection can be resumed for, not available in the reference
+    /// implementation. A caller with it set makes its connections resumable, and keeps the data
+    /// the listener didn't acknowledge when one drops, to send first on the connection that
+    /// resumes it. A listener with it set remembers how far it got with each resumable connection
+    /// for this long after last hearing from it, so a resumed stream goes on from there without
+    /// gaps, or data the application already received. It needs setting on both sides.
+    ///
+    /// Default is None, connections can't be resumed.
+    pub resumption_window: Option<Duration>,
+
     /// SRTO_MINVERSION
     /// The minimum SRT version that is required from the peer. A connection to a peer that does not
     /// satisfy the minimum version requirement will be rejected. See SRTO_VERSION for the version
@@ -39,6 +107,25 @@ pub struct Connect {
     /// Default is 64k
     pub udp_send_buffer_size: ByteCount,
 
+    /// Reads the UDP socket on a task of its own, in batches, rather than one datagram at a time
+    /// in between the connection's other work, see UdpReadLoop. Not available in the reference
+    /// implementation.
+    ///
+    /// A listener reads the socket all of its connections share this way.
+    ///
+    /// Default is None, which reads it along with the rest of the connection.
+    pub udp_read_loop: Option<UdpReadLoop>,
+
+    /// Rejects handshakes with reserved fields that aren't zero, a socket id of zero, or
+    /// extensions that don't add up, rather than warning about them and carrying on, see
+    /// HandshakeViolation. For catching buggy peers while developing against them, not available
+    /// in the reference implementation. It applies to the datagrams read from the UDP socket, the
+    /// packets of a custom transport come parsed already.
+    ///
+    /// Default is false.
+    #[cfg(feature = "strict-handshake")]
+    pub strict_handshake: bool,
+
     /// SRTO_IPTTL
     ///
     /// IPv4 Time To Live (see IP_TTL option for IP) or IPv6 unicast hops (see IPV6_UNICAST_HOPS for IPv6) depending on socket address family. Applies to sender only.
@@ -47,6 +134,15 @@ pub struct Connect {
     /// Sender: user configurable, default: 64
     pub ip_ttl: u8,
 
+    /// SRTO_IPTOS
+    ///
+    /// IPv4 Type of Service (see IP_TOS option for IP) or IPv6 Traffic Class (see IPV6_TCLASS for
+    /// IPv6) depending on socket address family, set on the UDP socket before anything is sent. The
+    /// DSCP is the upper six bits. Binding the socket fails if the platform refuses it.
+    ///
+    /// Default is None, which leaves the system default.
+    pub ip_tos: Option<u8>,
+
     /// Linger time on close (see [SO_LINGER](http://man7.org/linux/man-pages/man7/socket.7.html)).
     /// Set to None to disable linger
     ///
@@ -54,16 +150,53 @@ pub struct Connect {
     pub linger: Option<Duration>,
 }
 
+/// How the UDP socket is read at high packet rates, to keep the kernel from dropping datagrams
+/// once its receive buffer (SRTO_UDP_RCVBUF) fills up while the connection is busy.
+///
+/// The reading task drains the socket as soon as it's readable, up to `batch_size` datagrams at a
+/// time, and hands them over to the connection through a queue of `queue_depth` datagrams. Once
+/// the queue is full, the task waits for the connection to catch up, and the kernel buffers what
+/// comes in meanwhile, so the queue is in addition to the kernel buffer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UdpReadLoop {
+    /// The most datagrams read from the socket before they're handed over
+    pub batch_size: usize,
+    /// The most datagrams read but not yet taken by the connection
+    pub queue_depth: usize,
+}
+
+impl Default for UdpReadLoop {
+    fn default() -> Self {
+        Self {
+            batch_size: 64,
+            queue_depth: 8192,
+        }
+    }
+}
+
 impl Connect {}
 impl Default for Connect {
     fn default() -> Self {
         Self {
             local: SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0),
+            bind_device: None,
+            reuse_address: false,
+            reuse_port: false,
             timeout: Duration::from_secs(3),
+            handshake_retry_interval: Duration::from_millis(100),
+            max_handshake_retries: None,
+            max_half_open: 64,
+            allow_from: None,
+            resumption_window: None,
             min_version: SrtVersion::new(1, 0, 0),
             udp_recv_buffer_size: ByteCount(65536),
             udp_send_buffer_size: ByteCount(65536),
+            udp_read_loop: None,
+            #[cfg(feature = "strict-handshake")]
+            strict_handshake: false,
             ip_ttl: 64,
+            ip_tos: None,
             linger: Some(Duration::from_secs(180)),
         }
     }
@@ -77,6 +210,25 @@ impl Validation for Connect {
             return Err(OptionsError::InvalidIpTtl);
         }
 
+        if self.handshake_retry_interval.is_zero() {
+            return Err(OptionsError::HandshakeRetryIntervalOutOfRange(
+                self.handshake_retry_interval,
+            ));
+        }
+
+        if self.max_half_open == 0 {
+            return Err(OptionsError::MaxHalfOpenOutOfRange(self.max_half_open));
+        }
+
+        if let Some(read_loop) = &self.udp_read_loop {
+            if read_loop.batch_size == 0 {
+                return Err(OptionsError::UdpReadBatchOutOfRange(read_loop.batch_size));
+            }
+            if read_loop.queue_depth == 0 {
+                return Err(OptionsError::UdpReadQueueOutOfRange(read_loop.queue_depth));
+            }
+        }
+
         Ok(())
     }
 }
@@ -96,4 +248,56 @@ mod test {
             Err(OptionsError::InvalidIpTtl)
         );
     }
+
+    #[test]
+    fn handshake_retry_interval_validate() {
+        assert_eq!(
+            Connect {
+                handshake_retry_interval: Duration::ZERO,
+                ..Default::default()
+            }
+            .is_valid(),
+            Err(OptionsError::HandshakeRetryIntervalOutOfRange(
+                Duration::ZERO
+            ))
+        );
+    }
+
+    #[test]
+    fn max_half_open_validate() {
+        assert_eq!(
+            Connect {
+                max_half_open: 0,
+                ..Default::default()
+            }
+            .is_valid(),
+            Err(OptionsError::MaxHalfOpenOutOfRange(0))
+        );
+    }
+
+    #[test]
+    fn udp_read_loop_validate() {
+        let read_loop = UdpReadLoop::default();
+        let connect = |udp_read_loop| Connect {
+            udp_read_loop: Some(udp_read_loop),
+            ..Default::default()
+        };
+        assert_eq!(connect(read_loop).is_valid(), Ok(()));
+        assert_eq!(
+            connect(UdpReadLoop {
+                batch_size: 0,
+                ..read_loop
+            })
+            .is_valid(),
+            Err(OptionsError::UdpReadBatchOutOfRange(0))
+        );
+        assert_eq!(
+            connect(UdpReadLoop {
+                queue_depth: 0,
+                ..read_loop
+            })
+            .is_valid(),
+            Err(OptionsError::UdpReadQueueOutOfRange(0))
+        );
+    }
 }
@@ -1,13 +1,20 @@
+#[cfg(feature = "authentication")]
+use std::collections::BTreeMap;
 use std::{
     convert::TryFrom,
     fmt::{self, Debug, Display, Formatter},
 };
 
+#[cfg(feature = "authentication")]
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
 use super::*;
 
 // https://datatracker.ietf.org/doc/html/draft-sharabayko-srt-00#section-6
 // https://github.com/Haivision/srt/blob/master/docs/features/encryption.md
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Encryption {
     // TODO: support unspecified key length
     //  also check to ensure we implement key negotiation algorithm correctly
@@ -74,10 +81,63 @@ pub struct Encryption {
     /// passphrase.
     pub passphrase: Option<Passphrase>,
 
+    /// Other passphrases accepted from a peer besides the passphrase, not available in the
+    /// reference implementation
+    ///
+    /// Lets a listener roll over to a new passphrase without turning away callers that still use
+    /// the old one: the keys a caller sends are unwrapped with the passphrase first, then with each
+    /// of these in turn, and the connection goes on with the first one that fits. Only a peer that
+    /// responds to a handshake makes use of these, the initiator always sends keys wrapped with
+    /// the passphrase.
+    pub alternate_passphrases: Vec<Passphrase>,
+
     pub km_refresh: KeyMaterialRefresh,
+
+    /// Who this party is to its peers, and who it lets connect, not available in the reference
+    /// implementation
+    ///
+    /// Both sides prove in the handshake that they hold the signing key of the identity they
+    /// claim, and a side that has credentials only connects to a peer that proves one of its
+    /// trusted identities, anything else is rejected as unauthorized. This is independent of the
+    /// passphrase, which only protects the payload.
+    #[cfg(feature = "authentication")]
+    pub credentials: Option<Credentials>,
+}
+
+/// An identity with its Ed25519 signing key, and the identities of the peers that are trusted,
+/// with their public keys
+///
+/// The proof of an identity is a signature with its key over a nonce the other side picked, so
+/// only the public keys are handed out to the peers. A peer that trusts an identity can't claim
+/// it.
+#[cfg(feature = "authentication")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Credentials {
+    pub identity: String,
+    pub key: SigningKey,
+    pub trusted_peers: BTreeMap<String, VerifyingKey>,
+}
+
+#[cfg(feature = "authentication")]
+impl Credentials {
+    pub fn new(identity: impl Into<String>, key: SigningKey) -> Self {
+        Self {
+            identity: identity.into(),
+            key,
+            trusted_peers: BTreeMap::new(),
+        }
+    }
+
+    pub fn trust(mut self, identity: impl Into<String>, key: VerifyingKey) -> Self {
+        self.trusted_peers.insert(identity.into(), key);
+        self
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct KeyMaterialRefresh {
     /// SRTO_KMREFRESHRATE
     /// KM Refresh Period specifies the number of packets to be sent
@@ -155,7 +215,20 @@ impl Validation for Encryption {
                 PacketCount(period),
                 PacketCount(pre_announcement_period),
             ))
+        } else if self.passphrase.is_none() && !self.alternate_passphrases.is_empty() {
+            Err(OptionsError::AlternatePassphrasesWithoutPassphrase)
         } else {
+            #[cfg(feature = "authentication")]
+            if let Some(credentials) = &self.credentials {
+                for identity in Some(&credentials.identity)
+                    .into_iter()
+                    .chain(credentials.trusted_peers.keys())
+                {
+                    if !(1..=255).contains(&identity.len()) {
+                        return Err(OptionsError::IdentityLength(identity.len()));
+                    }
+                }
+            }
             Ok(())
         }
     }
@@ -163,6 +236,8 @@ impl Validation for Encryption {
 
 // https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#srto_passphrase
 #[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String"))]
 pub struct Passphrase(String);
 
 impl<'a> From<&'a str> for Passphrase {
@@ -200,50 +275,11 @@ impl Debug for Passphrase {
     }
 }
 
-// https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#srto_pbkeylen
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
-pub enum KeySize {
-    #[default]
-    Unspecified,
-    AES128,
-    AES192,
-    AES256,
-}
-
-impl KeySize {
-    pub fn as_raw(self) -> u8 {
-        use KeySize::*;
-        match self {
-            Unspecified => 0,
-            AES128 => 16,
-            AES192 => 24,
-            AES256 => 32,
-        }
-    }
-
-    pub fn as_usize(self) -> usize {
-        use KeySize::*;
-        match self {
-            Unspecified => 16,
-            AES128 => 16,
-            AES192 => 24,
-            AES256 => 32,
-        }
-    }
-}
-
 impl TryFrom<u16> for KeySize {
     type Error = OptionsError;
 
     fn try_from(value: u16) -> Result<Self, OptionsError> {
-        use KeySize::*;
-        match value {
-            0 => Ok(Unspecified),
-            16 => Ok(AES128),
-            24 => Ok(AES192),
-            32 => Ok(AES256),
-            value => Err(OptionsError::InvalidKeySize(value)),
-        }
+        KeySize::from_raw(value).ok_or(OptionsError::InvalidKeySize(value))
     }
 }
 
@@ -271,4 +307,42 @@ mod tests {
             Err(PassphraseLength(80))
         );
     }
+
+    #[test]
+    fn alternate_passphrases() {
+        let mut encryption = Encryption {
+            alternate_passphrases: vec!["old password".into()],
+            ..Default::default()
+        };
+        assert_eq!(
+            encryption.is_valid(),
+            Err(OptionsError::AlternatePassphrasesWithoutPassphrase)
+        );
+
+        encryption.passphrase = Some("new password".into());
+        assert_eq!(encryption.is_valid(), Ok(()));
+    }
+
+    #[cfg(feature = "authentication")]
+    #[test]
+    fn credentials() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let peer = SigningKey::from_bytes(&[2; 32]).verifying_key();
+
+        let mut encryption = Encryption {
+            credentials: Some(Credentials::new("", key.clone())),
+            ..Default::default()
+        };
+        assert_eq!(encryption.is_valid(), Err(OptionsError::IdentityLength(0)));
+
+        encryption.credentials =
+            Some(Credentials::new("encoder", key.clone()).trust("x".repeat(256), peer));
+        assert_eq!(
+            encryption.is_valid(),
+            Err(OptionsError::IdentityLength(256))
+        );
+
+        encryption.credentials = Some(Credentials::new("encoder", key).trust("decoder", peer));
+        assert_eq!(encryption.is_valid(), Ok(()));
+    }
 }
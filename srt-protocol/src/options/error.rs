@@ -2,7 +2,7 @@ use std::{io, io::ErrorKind, net::IpAddr, time::Duration};
 
 use thiserror::Error;
 
-use crate::options::*;
+use crate::{options::*, protocol::filter::fec::FecConfigError};
 
 // https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#list-of-options
 #[derive(Error, Clone, Debug, Eq, PartialEq)]
@@ -20,6 +20,12 @@ pub enum OptionsError {
     #[error("Receive buffer too small {0}")]
     ReceiveBufferMin(ByteCount),
 
+    #[error("Adaptive receive buffer cap {0} is below the receive buffer size {1}.")]
+    AdaptiveBufferCapBelowSize(ByteCount, ByteCount),
+
+    #[error("NAK period floor is out of range: {0:?}. It must be greater than zero.")]
+    NakPeriodFloorOutOfRange(Duration),
+
     #[error("Receive buffer too big - buffer: {buffer}, max_segment: {max_segment}, flow_control_window: {flow_control_window}")]
     ReceiveBufferTooLarge {
         buffer: ByteCount,
@@ -64,6 +70,42 @@ pub enum OptionsError {
 
     #[error("Statistics interval is out of range: {0:?}. The minimum interval is 200ms.")]
     StatisticsIntervalOutOfRange(Duration),
+
+    #[error("Loss rate window is out of range: {0:?}. It must be at least the statistics interval {1:?}.")]
+    LossRateWindowOutOfRange(Duration, Duration),
+
+    #[error("Handshake retry interval is out of range: {0:?}. It must be greater than zero.")]
+    HandshakeRetryIntervalOutOfRange(Duration),
+
+    #[error("Call timeout is out of range: {0:?}. It must be greater than zero.")]
+    CallTimeoutOutOfRange(Duration),
+
+    #[error("Max half-open connections is out of range: {0}. It must be greater than zero.")]
+    MaxHalfOpenOutOfRange(usize),
+
+    #[error("UDP read batch size is out of range: {0}. It must be greater than zero.")]
+    UdpReadBatchOutOfRange(usize),
+
+    #[error("UDP read queue depth is out of range: {0}. It must be greater than zero.")]
+    UdpReadQueueOutOfRange(usize),
+
+    #[error("Send queue depth is out of range: {0}. It must be greater than zero.")]
+    SendQueueDepthOutOfRange(usize),
+
+    #[error("Initial RTT is out of range: {0:?}. It must be greater than zero and at most 10s.")]
+    InitialRttOutOfRange(Duration),
+
+    #[error("Invalid packet filter config: {0}")]
+    InvalidPacketFilter(FecConfigError),
+
+    #[error("Alternate passphrases are only accepted along with a passphrase.")]
+    AlternatePassphrasesWithoutPassphrase,
+
+    #[cfg(feature = "authentication")]
+    #[error(
+        "Invalid identity length: {0}. The identity must be minimum 1 and maximum 255 bytes long."
+    )]
+    IdentityLength(usize),
 }
 
 impl From<OptionsError> for io::Error {
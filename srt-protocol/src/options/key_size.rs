@@ -0,0 +1,43 @@
+// https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md#srto_pbkeylen
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeySize {
+    #[default]
+    Unspecified,
+    AES128,
+    AES192,
+    AES256,
+}
+
+impl KeySize {
+    pub fn as_raw(self) -> u8 {
+        use KeySize::*;
+        match self {
+            Unspecified => 0,
+            AES128 => 16,
+            AES192 => 24,
+            AES256 => 32,
+        }
+    }
+
+    pub fn from_raw(value: u16) -> Option<Self> {
+        use KeySize::*;
+        match value {
+            0 => Some(Unspecified),
+            16 => Some(AES128),
+            24 => Some(AES192),
+            32 => Some(AES256),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(self) -> usize {
+        use KeySize::*;
+        match self {
+            Unspecified => 16,
+            AES128 => 16,
+            AES192 => 24,
+            AES256 => 32,
+        }
+    }
+}
@@ -8,6 +8,13 @@ use super::*;
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ListenerOptions {
     pub socket: SocketOptions,
+    /// The most connections a multiplexing listener keeps open at a time, further handshakes
+    /// are rejected with [`CoreRejectReason::Backlog`] until one of them is closed.
+    ///
+    /// Default: `None`, no limit
+    ///
+    /// [`CoreRejectReason::Backlog`]: crate::packet::CoreRejectReason::Backlog
+    pub max_connections: Option<usize>,
 }
 
 impl ListenerOptions {
@@ -30,7 +37,10 @@ impl ListenerOptions {
             Domain(_) => return Err(OptionsError::InvalidLocalAddress),
         };
 
-        let mut options = Self { socket };
+        let mut options = Self {
+            socket,
+            max_connections: None,
+        };
         options.socket.connect.local.set_port(local.port());
         if local.ip() != Ipv4Addr::UNSPECIFIED {
             options.socket.connect.local.set_ip(local.ip());
@@ -1,44 +1,88 @@
+mod key_size;
+mod srt_version;
+mod units;
+
+pub use key_size::*;
+pub use srt_version::*;
+pub use units::*;
+
+// the packet codec only needs the types above, everything else needs std
+#[cfg(feature = "std")]
 mod address;
+#[cfg(feature = "std")]
+mod auto;
+#[cfg(feature = "std")]
 mod bandwidth;
+#[cfg(feature = "std")]
 mod bind;
+#[cfg(feature = "std")]
 mod caller;
+#[cfg(feature = "std")]
 mod connect;
+#[cfg(feature = "std")]
 mod encryption;
+#[cfg(feature = "std")]
 mod error;
+#[cfg(feature = "std")]
 mod listener;
+#[cfg(feature = "std")]
 mod receiver;
+#[cfg(feature = "std")]
 mod rendezvous;
+#[cfg(feature = "std")]
 mod sender;
+#[cfg(feature = "std")]
 mod session;
+#[cfg(feature = "std")]
 mod socket;
-mod srt_version;
+#[cfg(feature = "std")]
 mod stream_id;
-mod units;
+#[cfg(feature = "std")]
 mod uri;
+#[cfg(feature = "std")]
 mod validation;
 
+#[cfg(feature = "std")]
 pub use address::*;
+#[cfg(feature = "std")]
+pub use auto::*;
+#[cfg(feature = "std")]
 pub use bandwidth::*;
+#[cfg(feature = "std")]
 pub use bind::*;
+#[cfg(feature = "std")]
 pub use caller::*;
+#[cfg(feature = "std")]
 pub use connect::*;
+#[cfg(feature = "std")]
 pub use encryption::*;
+#[cfg(feature = "std")]
 pub use error::*;
+#[cfg(feature = "std")]
 pub use listener::*;
+#[cfg(feature = "std")]
 pub use receiver::*;
+#[cfg(feature = "std")]
 pub use rendezvous::*;
+#[cfg(feature = "std")]
 pub use sender::*;
+#[cfg(feature = "std")]
 pub use session::*;
+#[cfg(feature = "std")]
 pub use socket::*;
-pub use srt_version::*;
+#[cfg(feature = "std")]
 pub use stream_id::*;
-pub use units::*;
+#[cfg(feature = "std")]
 pub use uri::url_parse;
+#[cfg(feature = "std")]
 pub use validation::*;
 
 // see https://github.com/Haivision/srt/blob/master/docs/API/API-socket-options.md
 
-// SRTO_PACKETFILTER - FEC not supported... yet
+// SRTO_PACKETFILTER - see Session::packet_filter, the built-in filter is fec
+// SRTO_BINDTODEVICE - see Connect::bind_device
+// SRTO_IPTOS - see Connect::ip_tos
+// SRTO_REUSEADDR - see Connect::reuse_address
 
 // TODO: look over these options, they could be useful for statistics
 //
@@ -54,13 +98,10 @@ pub use validation::*;
 
 // NOTE: will not implement these configuration options
 //
-// SRTO_IPTOS - socket specific
 // SRTO_IPTTL - socket specific
 // SRTO_IPV6ONLY - socket specific
 // SRTO_LINGER - socket specific
 // SRTO_TRANSTYPE - socket specific
-// SRTO_BINDTODEVICE - socket only
-// SRTO_REUSEADDR - socket specific
 // SRTO_STATE - socket specific
 // SRTO_RCVSYN - not even relevant for tokio
 // SRTO_RCVTIMEO - not even relevant for tokio
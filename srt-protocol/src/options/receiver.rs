@@ -3,6 +3,8 @@ use std::time::Duration;
 use super::*;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Receiver {
     /// SRTO_RCVLATENCY
     ///
@@ -65,6 +67,18 @@ pub struct Receiver {
     /// Flight Flag size).
     pub buffer_size: ByteCount,
 
+    /// Grows the receive buffer from buffer_size as the stream needs it, up to this size, in
+    /// bytes. Not available in the reference implementation.
+    ///
+    /// The buffer has to hold the packets that arrive over the latency window, and the round trip
+    /// the losses take to recover, at the rate they arrive; on links with a high bandwidth-delay
+    /// product more than a fixed size fits, and the packets that don't are dropped. With a cap,
+    /// the receiver keeps the buffer large enough for the arrival rate it measures, with a quarter
+    /// to spare. The buffer never shrinks back.
+    ///
+    /// Default: None, the buffer stays at buffer_size
+    pub adaptive_buffer_cap: Option<ByteCount>,
+
     /// SRTO_NAKREPORT
     /// When set to true, every report for a detected loss will be repeated when the timeout for the
     /// expected retransmission of this loss has expired and the missing packet still wasn't
@@ -85,6 +99,82 @@ pub struct Receiver {
     /// SRTO_DRIFTTRACER - Enable/disable drift tracer - unit: bool, default: true, range: t|f
     /// Enables or disables time drift tracer (receiver).
     pub drift_tracer: bool,
+
+    /// How messages after missing packets are delivered, see DeliveryMode
+    ///
+    /// Default: Strict
+    pub delivery_mode: DeliveryMode,
+
+    /// Whether messages are received whole, or as the payloads of their packets, see
+    /// RecvGranularity
+    ///
+    /// Default: Message
+    pub granularity: RecvGranularity,
+
+    /// How long the receiver waits before reporting the packets it's still missing again, see
+    /// NakPeriodConfig
+    ///
+    /// Default: half of RTT + 4 * RTTVar, at least 20ms
+    pub nak_period: NakPeriodConfig,
+}
+
+/// Whether the receiver waits for the packets it's missing, not available in the reference
+/// implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DeliveryMode {
+    /// Messages are delivered in order, the ones after missing packets wait until those are
+    /// recovered or dropped as too late (SRTO_TLPKTDROP).
+    #[default]
+    Strict,
+
+    /// A message is delivered as soon as it's due, even while packets before it are still being
+    /// recovered. Those are given up on, and the gap is reported with a
+    /// [`ConnectionEvent::Gap`](crate::connection::ConnectionEvent::Gap).
+    AsAvailable,
+}
+
+/// What each item received is, for messages that take more than one packet to send, not
+/// available in the reference implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecvGranularity {
+    /// A whole message, reassembled from its packets
+    #[default]
+    Message,
+
+    /// The payload of a single packet. A message is still only released once all of its packets
+    /// are, then its payloads are received one after the other, all with the message's time.
+    Payload,
+}
+
+/// The period of the periodic NAK reports, not configurable in the reference implementation
+///
+/// It's recalculated as the RTT estimate changes: the multiplier applies to RTT + 4 * RTTVar, and
+/// the period never gets shorter than the floor. A smaller period recovers lost packets sooner,
+/// as the reports for them are repeated more often, at the cost of more NAK packets.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct NakPeriodConfig {
+    /// Share of RTT + 4 * RTTVar
+    ///
+    /// Default: 50%
+    pub multiplier: Percent,
+
+    /// The shortest period, whatever the RTT
+    ///
+    /// Default: 20ms
+    pub floor: Duration,
+}
+
+impl Default for NakPeriodConfig {
+    fn default() -> Self {
+        Self {
+            multiplier: Percent(50),
+            floor: Duration::from_millis(20),
+        }
+    }
 }
 
 impl Default for Receiver {
@@ -93,9 +183,13 @@ impl Default for Receiver {
             latency: Duration::from_millis(120),
             reorder_tolerance_max: PacketCount(0),
             buffer_size: ByteCount(8192 * 1500),
+            adaptive_buffer_cap: None,
             nak_report: true,
             too_late_packet_drop: true,
             drift_tracer: false,
+            delivery_mode: Default::default(),
+            granularity: Default::default(),
+            nak_period: Default::default(),
         }
     }
 }
@@ -107,6 +201,16 @@ impl Validation for Receiver {
         use OptionsError::*;
         if self.buffer_size < ByteCount(46592) {
             Err(ReceiveBufferMin(self.buffer_size))
+        } else if self
+            .adaptive_buffer_cap
+            .is_some_and(|cap| cap < self.buffer_size)
+        {
+            Err(AdaptiveBufferCapBelowSize(
+                self.adaptive_buffer_cap.unwrap(),
+                self.buffer_size,
+            ))
+        } else if self.nak_period.floor.is_zero() {
+            Err(NakPeriodFloorOutOfRange(self.nak_period.floor))
         } else {
             Ok(())
         }
@@ -130,5 +234,32 @@ mod tests {
             result.try_validate(),
             Err(ReceiveBufferMin(ByteCount(46591)))
         );
+
+        let result = Receiver {
+            nak_period: NakPeriodConfig {
+                multiplier: Percent(0),
+                floor: Duration::ZERO,
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            result.try_validate(),
+            Err(NakPeriodFloorOutOfRange(Duration::ZERO))
+        );
+
+        let result = Receiver {
+            buffer_size: ByteCount(100_000),
+            adaptive_buffer_cap: Some(ByteCount(99_999)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            result.try_validate(),
+            Err(AdaptiveBufferCapBelowSize(
+                ByteCount(99_999),
+                ByteCount(100_000)
+            ))
+        );
     }
 }
@@ -3,6 +3,8 @@ use std::{default::Default, time::Duration};
 use super::*;
 
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Sender {
     // OptName	Since	Restrict	Type	Units	Default	Range	Dir	Entity
     // SRTO_PEERLATENCY	1.3.0	pre	int32_t	ms	0	0..	RW	GSD
@@ -43,12 +45,34 @@ pub struct Sender {
     /// Sender Buffer Size. See SRTO_RCVBUF for more information.
     pub buffer_size: ByteCount,
 
+    /// What happens to data sent while the send buffer is full, see SendBufferFull
+    ///
+    /// Default: Drop
+    pub buffer_full: SendBufferFull,
+
+    /// What happens to data sent while the flow window is exhausted, with as many packets in
+    /// flight as the peer takes, see FlowWindowFull
+    ///
+    /// Default: Wait
+    pub flow_window_full: FlowWindowFull,
+
+    /// What happens to data sent faster than the bandwidth settings let it out, see
+    /// InputOverflowPolicy
+    ///
+    /// Default: Queue
+    pub input_overflow: InputOverflowPolicy,
+
     // SRTO_OHEADBW - see LiveBandwidthMode
     // SRTO_MAXBW - see LiveBandwidthMode
     // SRTO_INPUTBW - see LiveBandwidthMode
     // SRTO_MININPUTBW - see LiveBandwidthMode
     pub bandwidth: LiveBandwidthMode,
 
+    /// Whether retransmissions count against the bandwidth limit, see RetransmitBandwidth
+    ///
+    /// Default: Capped
+    pub retransmit_bandwidth: RetransmitBandwidth,
+
     /// SRTO_FC - Flow Control Window Size - unit: packets, default 25600, range: 32..
     /// Flow Control limits the maximum number of packets "in flight" - payload (data) packets that
     /// were sent but reception is not yet acknowledged with an ACK control packet. It also includes
@@ -116,6 +140,43 @@ pub struct Sender {
     /// NOTE: The efficient retransmission algorithm can only be used when a receiver sends Periodic
     /// NAK reports. See SRTO_NAKREPORT.
     pub intensive_retransmission: bool,
+
+    /// Restart after idle, not available in the reference implementation
+    ///
+    /// When nothing was sent for this long, e.g. at the start of the connection or after the input
+    /// paused, a backlog isn't sent all at once. The sender starts over with at most 16 packets in
+    /// flight, and every packet acknowledged lets it send two more, so the ACKs clock the backlog
+    /// out. Once a packet is lost, the number of packets allowed in flight is halved and grows
+    /// slower from there. This goes on until the backlog is sent, or as many packets are allowed
+    /// in flight as the flow control window (SRTO_FC) does.
+    ///
+    /// None sends the backlog as fast as the bandwidth settings allow.
+    ///
+    /// Default: 1s
+    pub idle_restart_threshold: Option<Duration>,
+
+    /// Maximum retransmissions, not available in the reference implementation
+    ///
+    /// Once a packet was retransmitted this many times, whether after a NAK or a timeout, and is
+    /// due to go out again, the path is taken to be dead. The connection is closed, and the
+    /// SrtSocket reports it with SrtError::RetransmitLimit, rather than retransmitting until the
+    /// packet is too late or the peer idle timeout runs out.
+    ///
+    /// None retransmits as long as it takes.
+    ///
+    /// Default: None
+    pub max_retransmits: Option<u32>,
+
+    /// Send queue depth, not available in the reference implementation
+    ///
+    /// How many messages sent to the socket wait in front of the send buffer for the connection
+    /// to take them. The connection takes them as they come, unless the send buffer is full and
+    /// pushes back, see SendBufferFull::Backpressure, and then sending waits once the queue is
+    /// full. The depth is reported in tx_queued_messages and tx_queued_bytes, and the most that
+    /// waited at once in tx_queued_messages_max.
+    ///
+    /// Default: 128
+    pub queue_depth: usize,
 }
 
 impl Default for Sender {
@@ -124,14 +185,75 @@ impl Default for Sender {
             peer_latency: Duration::from_millis(120),
             drop_delay: Duration::ZERO,
             buffer_size: ByteCount(46592),
+            buffer_full: Default::default(),
+            flow_window_full: Default::default(),
+            input_overflow: Default::default(),
             bandwidth: Default::default(),
+            retransmit_bandwidth: Default::default(),
             flow_control_window_size: PacketCount(25600),
             max_payload_size: PacketSize(1316),
             intensive_retransmission: false,
+            idle_restart_threshold: Some(Duration::from_secs(1)),
+            max_retransmits: None,
+            queue_depth: 128,
         }
     }
 }
 
+/// What the sender does with data while its buffer is full, e.g. when the network stalls, not
+/// available in the reference implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SendBufferFull {
+    /// The oldest packets are dropped to make room, whether they were sent or not. They're counted
+    /// in tx_dropped_data and tx_buffer_full_dropped_data, and reported with a SendBufferFull
+    /// connection event.
+    #[default]
+    Drop,
+
+    /// No more data is taken until there is room again, so sending it waits, pushing back on the
+    /// application. Nothing is dropped to make room, but a message of more than one packet may
+    /// take the buffer past its size.
+    Backpressure,
+}
+
+/// What the sender does with data while the flow window is exhausted, e.g. when the peer stops
+/// acknowledging, not available in the reference implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FlowWindowFull {
+    /// The data is taken and waits to be sent until acknowledgements open the window, see
+    /// SendBufferFull for what happens once the send buffer is full.
+    #[default]
+    Wait,
+
+    /// No more data is taken until acknowledgements open the window, so sending it waits, pushing
+    /// back on the application.
+    Block,
+
+    /// Only the newest of the messages queued behind the window waits for it to open, the older
+    /// ones are dropped before they're given sequence numbers, so a live source stays current.
+    /// They're counted in tx_dropped_data and tx_flow_window_dropped_data.
+    DropOldest,
+}
+
+/// What the sender does with data that waits to be sent because it comes in faster than the
+/// bandwidth settings (SRTO_MAXBW) let it out, not available in the reference implementation
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputOverflowPolicy {
+    /// The data waits its turn for as long as there is room in the send buffer, see
+    /// SendBufferFull
+    #[default]
+    Queue,
+
+    /// Once the data waiting to be sent spans more than this, from the origin time of the oldest
+    /// message to that of the newest, the oldest messages are dropped before they're given
+    /// sequence numbers, so a live source feeding too fast stays current. They're counted in
+    /// tx_dropped_data and tx_input_dropped_data.
+    DropOldest(Duration),
+}
+
 impl Validation for Sender {
     type Error = OptionsError;
 
@@ -139,6 +261,8 @@ impl Validation for Sender {
         use OptionsError::*;
         if self.flow_control_window_size < PacketCount(32) {
             Err(FlowControlWindowMin(self.flow_control_window_size))
+        } else if self.queue_depth == 0 {
+            Err(SendQueueDepthOutOfRange(self.queue_depth))
         } else {
             Ok(())
         }
@@ -162,5 +286,12 @@ mod tests {
             result.try_validate(),
             Err(FlowControlWindowMin(PacketCount(31)))
         );
+
+        let result = Sender {
+            queue_depth: 0,
+            ..Default::default()
+        };
+
+        assert_eq!(result.try_validate(), Err(SendQueueDepthOutOfRange(0)));
     }
 }
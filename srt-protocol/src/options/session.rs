@@ -1,8 +1,12 @@
 use std::time::Duration;
 
+use crate::{packet::FilterSpec, protocol::filter::fec::FecConfig};
+
 use super::*;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Session {
     /// SRTO_PEERIDLETIMEO
     /// The maximum time to wait until another packet is received from a peer since the last
@@ -26,6 +30,51 @@ pub struct Session {
     pub max_segment_size: PacketSize,
 
     pub statistics_interval: Duration,
+
+    /// The span of time over which the recent loss rates in the statistics are calculated. They
+    /// are only sampled when statistics are updated, so it can't be shorter than the statistics
+    /// interval.
+    ///
+    /// The default value is 5000ms
+    pub loss_rate_window: Duration,
+
+    /// SRTO_PACKETFILTER
+    /// The packet filter configuration, e.g. for FEC, as comma separated key:value pairs. It's
+    /// sent during the handshake, and both peers must agree on it: if only one of them sets it,
+    /// the other adopts it, otherwise parameters only one of them sets are added to the other's.
+    /// Different filter types or values are rejected with `CoreRejectReason::Filter`, as is a
+    /// listener that doesn't answer with the agreed configuration. The filter itself is
+    /// plugged into the connection, see `DuplexConnection::set_packet_filter`, except for the
+    /// built-in `fec` filter, e.g. `fec,cols:10,rows:5`.
+    ///
+    /// The default value is None
+    pub packet_filter: Option<FilterSpec>,
+
+    /// The round trip time assumed until it's measured, which the NAK, EXP and retransmission
+    /// timers are based on. On a link with a known long round trip time, the default makes the
+    /// receiver report the same losses again and the sender retransmit before the first
+    /// retransmission could have arrived. Once measured, the round trip time follows the
+    /// measurements. Not available in the reference implementation.
+    ///
+    /// The default value is None, which assumes 10ms
+    pub initial_rtt: Option<Duration>,
+
+    /// How much later than its latency a packet may be, as a share of the latency, before it's
+    /// given up on for being too late (TLPKTDROP). The receiver waits this much longer for a
+    /// missing packet before releasing the data after it, at the cost of releasing that data
+    /// late, and the sender keeps packets for retransmission this much longer, though never for
+    /// less than 125% of the latency or a second. Not available in the reference implementation.
+    ///
+    /// The default value is 0%
+    pub too_late_drop_margin: Percent,
+
+    /// Offers the peer to carry several logical streams over the connection, each message tagged
+    /// with the stream it belongs to. It's only agreed on if the peer offers it too, which it
+    /// can be checked for once connected in the features of the connection settings. Not
+    /// available in the reference implementation.
+    ///
+    /// The default value is false
+    pub multiplex: bool,
 }
 
 impl Default for Session {
@@ -34,6 +83,11 @@ impl Default for Session {
             peer_idle_timeout: Duration::from_secs(5),
             max_segment_size: PacketSize(1500),
             statistics_interval: Duration::from_secs(1),
+            loss_rate_window: Duration::from_secs(5),
+            packet_filter: None,
+            initial_rtt: None,
+            too_late_drop_margin: Percent(0),
+            multiplex: false,
         }
     }
 }
@@ -47,8 +101,61 @@ impl Validation for Session {
             Err(MaxSegmentSizeOutOfRange(self.max_segment_size))
         } else if self.statistics_interval < Duration::from_millis(200) {
             Err(StatisticsIntervalOutOfRange(self.statistics_interval))
+        } else if self.loss_rate_window < self.statistics_interval {
+            Err(LossRateWindowOutOfRange(
+                self.loss_rate_window,
+                self.statistics_interval,
+            ))
+        } else if let Some(Err(error)) = self.packet_filter.as_ref().map(FecConfig::from_spec) {
+            Err(InvalidPacketFilter(error))
+        } else if let Some(rtt) = self
+            .initial_rtt
+            .filter(|rtt| rtt.is_zero() || *rtt > Duration::from_secs(10))
+        {
+            Err(InitialRttOutOfRange(rtt))
         } else {
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::protocol::filter::fec::FecConfigError;
+
+    use super::*;
+
+    #[test]
+    fn packet_filter_validate() {
+        let session = |filter: &str| Session {
+            packet_filter: Some(filter.parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(session("fec,cols:10,rows:5").is_valid(), Ok(()));
+        // other filters have to be plugged into the connection, there's nothing to check
+        assert_eq!(session("custom,depth:2").is_valid(), Ok(()));
+        assert_eq!(
+            session("fec,rows:5").is_valid(),
+            Err(OptionsError::InvalidPacketFilter(
+                FecConfigError::MissingColumns
+            ))
+        );
+    }
+
+    #[test]
+    fn initial_rtt_validate() {
+        let session = |rtt| Session {
+            initial_rtt: Some(rtt),
+            ..Default::default()
+        };
+        assert_eq!(session(Duration::from_millis(300)).is_valid(), Ok(()));
+        assert_eq!(
+            session(Duration::ZERO).is_valid(),
+            Err(OptionsError::InitialRttOutOfRange(Duration::ZERO))
+        );
+        assert_eq!(
+            session(Duration::from_secs(11)).is_valid(),
+            Err(OptionsError::InitialRttOutOfRange(Duration::from_secs(11)))
+        );
+    }
+}
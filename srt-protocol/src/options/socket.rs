@@ -1,6 +1,8 @@
 use super::*;
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct SocketOptions {
     pub connect: Connect,
     pub session: Session,
@@ -1,8 +1,9 @@
-use std::{cmp::Ordering, fmt};
+use core::{cmp::Ordering, fmt};
 
 /// Serialied, it looks like:
 /// major * 0x10000 + minor * 0x100 + patch
 #[derive(PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SrtVersion {
     pub major: u8,
     pub minor: u8,
@@ -41,6 +42,62 @@ impl SrtVersion {
     }
 }
 
+/// The version of the SRT protocol this crate implements, the one its handshakes carry
+pub fn protocol_version() -> SrtVersion {
+    SrtVersion::CURRENT
+}
+
+/// The features this crate implements that a peer of `peer_version` supports too, e.g. to know
+/// what to expect of a bridge to a given version of the reference implementation
+pub fn compatible_features(peer_version: SrtVersion) -> impl Iterator<Item = SrtFeature> {
+    SrtFeature::ALL
+        .into_iter()
+        .filter(move |feature| peer_version >= feature.min_version())
+}
+
+/// A part of the protocol that depends on the version of the peer, see [`compatible_features`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum SrtFeature {
+    /// Timestamp-based packet delivery, at the latency agreed on in the handshake
+    Tsbpd,
+    /// AES-CTR encryption of the payload, with the keys exchanged in the handshake
+    Encryption,
+    /// The flag telling retransmitted data packets apart from the original ones
+    RetransmittedFlag,
+    /// The version 5 handshake, with its extensions in the conclusion
+    HandshakeV5,
+    /// Data and latency in both directions over the same connection
+    Bidirectional,
+    /// The stream ID the caller sends, for the listener to pick what it's connecting to
+    StreamId,
+    /// Packet filters, like forward error correction, negotiated in the handshake. This crate
+    /// implements them, though its own version predates them in the reference implementation
+    PacketFilter,
+}
+
+impl SrtFeature {
+    pub const ALL: [SrtFeature; 7] = [
+        SrtFeature::Tsbpd,
+        SrtFeature::Encryption,
+        SrtFeature::RetransmittedFlag,
+        SrtFeature::HandshakeV5,
+        SrtFeature::Bidirectional,
+        SrtFeature::StreamId,
+        SrtFeature::PacketFilter,
+    ];
+
+    /// The first version of the reference implementation with the feature
+    pub fn min_version(self) -> SrtVersion {
+        use SrtFeature::*;
+        match self {
+            Tsbpd | Encryption => SrtVersion::new(1, 0, 0),
+            RetransmittedFlag => SrtVersion::new(1, 2, 0),
+            HandshakeV5 | Bidirectional | StreamId => SrtVersion::new(1, 3, 0),
+            PacketFilter => SrtVersion::new(1, 4, 0),
+        }
+    }
+}
+
 impl PartialOrd for SrtVersion {
     fn partial_cmp(&self, other: &SrtVersion) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -73,13 +130,41 @@ impl fmt::Debug for SrtVersion {
 
 #[cfg(test)]
 mod test {
-    use super::SrtVersion;
+    use super::{SrtFeature, SrtVersion};
     #[test]
     fn test_parse() {
         assert_eq!(SrtVersion::parse(0x01_01_01), SrtVersion::new(1, 1, 1));
         assert_eq!(SrtVersion::parse(0x00_00_00), SrtVersion::new(0, 0, 0));
     }
 
+    #[test]
+    fn compatible_features() {
+        use super::{compatible_features, protocol_version, SrtFeature::*};
+        use alloc::vec::Vec;
+
+        let features = |version| compatible_features(version).collect::<Vec<_>>();
+        assert_eq!(protocol_version(), SrtVersion::new(1, 3, 1));
+        assert_eq!(features(SrtVersion::new(0, 9, 0)), []);
+        assert_eq!(features(SrtVersion::new(1, 1, 3)), [Tsbpd, Encryption]);
+        assert_eq!(
+            features(SrtVersion::new(1, 2, 3)),
+            [Tsbpd, Encryption, RetransmittedFlag]
+        );
+        assert_eq!(
+            features(protocol_version()),
+            [
+                Tsbpd,
+                Encryption,
+                RetransmittedFlag,
+                HandshakeV5,
+                Bidirectional,
+                StreamId
+            ]
+        );
+        assert_eq!(features(SrtVersion::new(1, 4, 0)), SrtFeature::ALL);
+        assert_eq!(features(SrtVersion::new(1, 5, 3)), SrtFeature::ALL);
+    }
+
     #[test]
     fn test_display_debug() {
         assert_eq!(format!("{}", SrtVersion::new(12, 12, 12)), "12.12.12");
@@ -1,4 +1,4 @@
-use std::{
+use core::{
     ops::{Div, Mul},
     time::Duration,
 };
@@ -6,6 +6,7 @@ use std::{
 use derive_more::*;
 
 #[derive(Debug, Deref, Display, Into, Mul, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{_0} bytes")]
 pub struct ByteCount(pub u64);
 
@@ -24,6 +25,7 @@ impl Div<PacketSize> for ByteCount {
 }
 
 #[derive(Debug, Deref, Display, Into, Add, Sub, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{_0} bytes")]
 pub struct PacketSize(pub u64);
 
@@ -34,6 +36,7 @@ impl From<PacketSize> for usize {
 }
 
 #[derive(Debug, Deref, Display, Into, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{_0} packets")]
 pub struct PacketCount(pub u64);
 
@@ -66,6 +69,7 @@ impl PacketCount {
 }
 
 #[derive(Debug, Deref, Display, Into, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{_0} bytes/s")]
 pub struct DataRate(pub u64);
 
@@ -160,6 +164,7 @@ impl PacketPeriod {
 }
 
 #[derive(Debug, Deref, Display, Into, Add, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[display(fmt = "{_0}%")]
 pub struct Percent(pub u64);
 
@@ -179,6 +184,14 @@ impl Mul<Percent> for DataRate {
     }
 }
 
+impl Mul<Percent> for Duration {
+    type Output = Duration;
+
+    fn mul(self, rhs: Percent) -> Self::Output {
+        Duration::from_nanos((self.as_nanos() * u128::from(rhs.0) / 100) as u64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -203,6 +216,14 @@ mod tests {
         assert_eq!(packets, PacketCount(1_000))
     }
 
+    #[test]
+    fn duration_and_percent_multiplication() {
+        let latency = Duration::from_millis(120);
+
+        assert_eq!(latency * Percent(25), Duration::from_millis(30));
+        assert_eq!(latency * Percent(150), Duration::from_millis(180));
+    }
+
     #[test]
     fn packet_period_from_data_rate_and_packet_size() {
         let data_rate = DataRate(20_000);
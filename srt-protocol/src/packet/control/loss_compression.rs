@@ -1,4 +1,4 @@
-use std::iter::Iterator;
+use core::iter::Iterator;
 
 use super::SeqNumber;
 
@@ -91,7 +91,7 @@ where
 pub struct DecompressLossList<I> {
     iterator: I,
 
-    loop_next_end: Option<(u32, u32)>,
+    loop_next_end: Option<(SeqNumber, SeqNumber)>,
 }
 
 impl<I: Iterator<Item = u32>> Iterator for DecompressLossList<I> {
@@ -103,13 +103,13 @@ impl<I: Iterator<Item = u32>> Iterator for DecompressLossList<I> {
                 // loop is over
                 self.loop_next_end = None;
 
-                Some(SeqNumber::new_truncate(next))
+                Some(next)
             }
             Some((next, end)) => {
-                // continue the loop
+                // continue the loop, wrapping past the maximum sequence number if need be
                 self.loop_next_end = Some((next + 1, end));
 
-                Some(SeqNumber::new_truncate(next))
+                Some(next)
             }
             None => {
                 // no current loop
@@ -118,16 +118,16 @@ impl<I: Iterator<Item = u32>> Iterator for DecompressLossList<I> {
                 // is this a loop start
                 if next & (1 << 31) != 0 {
                     // set the first bit to zero
-                    let next_num = next & !(1 << 31);
+                    let next_num = SeqNumber::new_truncate(next & !(1 << 31));
                     self.loop_next_end = Some((
                         next_num + 1,
                         match self.iterator.next() {
-                            Some(i) => i,
+                            Some(i) => SeqNumber::new_truncate(i),
                             None => panic!("unterminated loop while decompressing loss list"),
                         },
                     ));
 
-                    Some(SeqNumber::new_truncate(next_num))
+                    Some(next_num)
                 } else {
                     // no looping is possible
                     Some(SeqNumber::new_truncate(next))
@@ -184,6 +184,10 @@ mod test {
             [1_687_761_238, 1_687_761_239],
             [1_687_761_238 | ONE, 1_687_761_239]
         );
+
+        // loops across the sequence number wrap
+        test_comp_decomp!([ONE - 2, ONE - 1, 0, 1, 2, 5], [(ONE - 2) | ONE, 2, 5]);
+        test_comp_decomp!([ONE - 1, 0], [(ONE - 1) | ONE, 0]);
     }
 
     #[test]
@@ -3,7 +3,8 @@ mod srt;
 
 pub use srt::*;
 
-use std::{
+use alloc::{string::String, vec, vec::Vec};
+use core::{
     convert::TryFrom,
     convert::TryInto,
     fmt::{self, Debug, Display, Formatter},
@@ -17,10 +18,7 @@ use bitflags::bitflags;
 use bytes::{Buf, BufMut};
 use log::warn;
 
-use crate::{
-    options::{KeySize, PacketCount, PacketSize},
-    protocol::time::Rtt,
-};
+use crate::options::{KeySize, PacketCount, PacketSize};
 
 use super::*;
 
@@ -137,6 +135,19 @@ pub struct HsV5Info {
 
     /// The SID
     pub sid: Option<String>,
+
+    /// The packet filter config, SRTO_PACKETFILTER
+    pub filter: Option<FilterSpec>,
+
+    /// The session the connection resumes, not part of the reference implementation
+    pub resume: Option<SessionResume>,
+
+    /// The proof of the peer's identity, not part of the reference implementation
+    pub auth: Option<HandshakeAuthentication>,
+
+    /// The nonce a responder with credentials issues for the initiator to sign, the only
+    /// extension an induction response carries, not part of the reference implementation
+    pub challenge: Option<[u8; 16]>,
 }
 
 /// HS-version dependenent data
@@ -340,10 +351,15 @@ impl HandshakeVsInfo {
             HandshakeVsInfo::V4(ty) => *ty as u32,
             HandshakeVsInfo::V5(hs) => {
                 if shake_type == ShakeType::Induction
-                    && (hs.ext_hs.is_some() || hs.ext_km.is_some() || hs.sid.is_some())
+                    && (hs.ext_hs.is_some()
+                        || hs.ext_km.is_some()
+                        || hs.sid.is_some()
+                        || hs.filter.is_some()
+                        || hs.resume.is_some()
+                        || hs.auth.is_some())
                 {
-                    // induction does not include any extensions, and instead has the
-                    // magic code. this is an incompatialbe place to be.
+                    // induction does not include any extensions but the challenge, and instead has
+                    // the magic code. this is an incompatialbe place to be.
                     panic!("Handshake is both induction and has SRT extensions, not valid");
                 }
 
@@ -355,7 +371,12 @@ impl HandshakeVsInfo {
                 if hs.ext_km.is_some() {
                     flags |= ExtFlags::KM;
                 }
-                if hs.sid.is_some() {
+                if hs.sid.is_some()
+                    || hs.filter.is_some()
+                    || hs.resume.is_some()
+                    || hs.auth.is_some()
+                    || hs.challenge.is_some()
+                {
                     flags |= ExtFlags::CONFIG;
                 }
                 // take the crypto size, get rid of the frist three (guaranteed zero) bits, then shift it into the
@@ -393,6 +414,23 @@ impl SocketType {
 
 impl ControlPacket {
     pub fn parse(buf: &mut impl Buf, is_ipv6: bool) -> Result<ControlPacket, PacketParseError> {
+        Self::parse_with(buf, is_ipv6, false)
+    }
+
+    /// Parses like [`parse`](Self::parse), but rejects handshakes lenient parsing warns about, see
+    /// [`Packet::parse_strict`](super::Packet::parse_strict)
+    pub fn parse_strict(
+        buf: &mut impl Buf,
+        is_ipv6: bool,
+    ) -> Result<ControlPacket, PacketParseError> {
+        Self::parse_with(buf, is_ipv6, true)
+    }
+
+    pub(crate) fn parse_with(
+        buf: &mut impl Buf,
+        is_ipv6: bool,
+        strict: bool,
+    ) -> Result<ControlPacket, PacketParseError> {
         let control_type = buf.get_u16() << 1 >> 1; // clear first bit
 
         // get reserved data, which is the last two bytes of the first four bytes
@@ -411,6 +449,7 @@ impl ControlPacket {
                 add_info,
                 buf,
                 is_ipv6,
+                strict,
             )?,
         })
     }
@@ -498,14 +537,28 @@ impl ControlTypes {
         extra_info: u32,
         mut buf: T,
         is_ipv6: bool,
+        strict: bool,
     ) -> Result<ControlTypes, PacketParseError> {
         match packet_type {
             0x0 => {
+                use HandshakeViolation::*;
+                // what lenient parsing only warns about
+                let tolerate = |violation| match strict {
+                    true => Err(PacketParseError::StrictHandshake(violation)),
+                    false => Ok(()),
+                };
+
                 // Handshake
                 // make sure the packet is large enough -- 8 32-bit words, 1 128 (ip)
                 if buf.remaining() < 8 * 4 + 16 {
                     return Err(PacketParseError::NotEnoughData);
                 }
+                if reserved != 0 {
+                    tolerate(ReservedField("control subtype", reserved.into()))?;
+                }
+                if extra_info != 0 {
+                    tolerate(ReservedField("type-specific information", extra_info))?;
+                }
 
                 let udt_version = buf.get_i32();
                 if udt_version != 4 && udt_version != 5 {
@@ -532,13 +585,19 @@ impl ControlTypes {
                     Err(err_ct) => return Err(PacketParseError::BadConnectionType(err_ct)),
                 };
                 let socket_id = SocketId(buf.get_u32());
+                if socket_id == SocketId(0) {
+                    tolerate(ZeroSocketId)?;
+                }
                 let syn_cookie = buf.get_i32();
 
                 let peer_addr = if !is_ipv6 {
                     let ip = buf.get_u32_le();
-                    buf.get_u32();
-                    buf.get_u32();
-                    buf.get_u32();
+                    for _ in 0..3 {
+                        let padding = buf.get_u32();
+                        if padding != 0 {
+                            tolerate(ReservedField("IPv4 address padding", padding))?;
+                        }
+                    }
                     IpAddr::from(Ipv4Addr::from(ip))
                 } else {
                     let mut ip_buf = [0u8; 16];
@@ -548,18 +607,23 @@ impl ControlTypes {
 
                 let info = match udt_version {
                     4 => HandshakeVsInfo::V4(match SocketType::from_u16(type_ext_socket_type) {
+                        Ok(t) if crypto_size != 0 => {
+                            tolerate(ReservedField("UDT4 socket type", crypto_size.into()))?;
+                            t
+                        }
                         Ok(t) => t,
                         Err(e) => return Err(PacketParseError::BadSocketType(e)),
                     }),
                     5 => {
                         // make sure crypto size is of a valid variant
-                        let crypto_size = match KeySize::try_from(crypto_size) {
-                            Ok(size) => size,
-                            Err(_) => {
+                        let crypto_size = match KeySize::from_raw(crypto_size) {
+                            Some(size) => size,
+                            None => {
                                 warn!(
                                     "Unrecognized crypto key length: {}, disabling encryption. Should be 0, 16, 24, or 32 bytes.",
                                    crypto_size
                                 );
+                                tolerate(CryptoLength(crypto_size))?;
                                 KeySize::Unspecified
                             }
                         };
@@ -567,10 +631,54 @@ impl ControlTypes {
                         if shake_type == ShakeType::Induction {
                             if type_ext_socket_type != SRT_MAGIC_CODE {
                                 // TODO: should this bail? What does the reference implementation do?
-                                warn!("HSv5 induction response did not have SRT_MAGIC_CODE, which is suspicious")
+                                warn!("HSv5 induction response did not have SRT_MAGIC_CODE, which is suspicious");
+                                tolerate(MissingMagicCode(type_ext_socket_type))?;
+                            }
+
+                            // a responder with credentials issues its challenge after the magic
+                            // code, there are no flags for it
+                            let mut challenge = None;
+                            while buf.remaining() > 4 {
+                                let pack_type = buf.get_u16();
+
+                                let pack_size_words = buf.get_u16();
+                                let pack_size = usize::from(pack_size_words) * 4;
+
+                                if buf.remaining() < pack_size {
+                                    return Err(PacketParseError::NotEnoughData);
+                                }
+
+                                let mut buffer = buf.take(pack_size);
+                                match SrtControlPacket::parse(pack_type, &mut buffer) {
+                                    //104 = authentication challenge:
+                                    Ok(SrtControlPacket::AuthenticationChallenge(nonce)) => {
+                                        if challenge.replace(nonce).is_some() {
+                                            tolerate(DuplicateExtension(pack_type))?;
+                                        }
+                                    }
+                                    Ok(_)
+                                    | Err(PacketParseError::UnsupportedSrtExtensionType(_)) => {
+                                        warn!("Induction contains extension type {}, skipping its {} bytes", pack_type, pack_size);
+                                        tolerate(UnknownExtension(pack_type))?;
+                                    }
+                                    Err(e) => return Err(e),
+                                }
+                                if buffer.remaining() != 0 {
+                                    tolerate(ExtensionLength(pack_type, pack_size_words))?;
+                                }
+                                buffer.advance(buffer.remaining());
+                                buf = buffer.into_inner();
+                            }
+
+                            if buf.remaining() != 0 {
+                                warn!("Induction has data left, but not enough for an extension!");
+                                tolerate(TrailingBytes(buf.remaining()))?;
                             }
 
-                            HandshakeVsInfo::V5(HsV5Info::default())
+                            HandshakeVsInfo::V5(HsV5Info {
+                                challenge,
+                                ..HsV5Info::default()
+                            })
                         } else {
                             // if this is not induction, this is the extension flags
                             let extensions = match ExtFlags::from_bits(type_ext_socket_type) {
@@ -580,6 +688,7 @@ impl ControlTypes {
                                         "Unnecessary bits in extensions flags: {:b}",
                                         type_ext_socket_type
                                     );
+                                    tolerate(ExtensionFlags(type_ext_socket_type))?;
 
                                     ExtFlags::from_bits_truncate(type_ext_socket_type)
                                 }
@@ -588,6 +697,10 @@ impl ControlTypes {
                             // parse out extensions
 
                             let mut sid = None;
+                            let mut filter = None;
+                            let mut resume = None;
+                            let mut auth = None;
+                            let mut challenge = None;
                             let mut ext_hs = None;
                             let mut ext_km = None;
 
@@ -606,9 +719,11 @@ impl ControlTypes {
                                     1 | 2 => {
                                         if !extensions.contains(ExtFlags::HS) {
                                             warn!("Handshake contains handshake extension type {} without HSREQ flag!", pack_type);
+                                            tolerate(UnflaggedExtension(pack_type))?;
                                         }
                                         if ext_hs.is_some() {
                                             warn!("Handshake contains multiple handshake extensions, only the last will be applied!");
+                                            tolerate(DuplicateExtension(pack_type))?;
                                         }
                                         ext_hs =
                                             Some(SrtControlPacket::parse(pack_type, &mut buffer)?);
@@ -616,9 +731,11 @@ impl ControlTypes {
                                     3 | 4 => {
                                         if !extensions.contains(ExtFlags::KM) {
                                             warn!("Handshake contains key material extension type {} without KMREQ flag!", pack_type);
+                                            tolerate(UnflaggedExtension(pack_type))?;
                                         }
                                         if ext_km.is_some() {
                                             warn!("Handshake contains multiple key material extensions, only the last will be applied!");
+                                            tolerate(DuplicateExtension(pack_type))?;
                                         }
                                         ext_km =
                                             Some(SrtControlPacket::parse(pack_type, &mut buffer)?);
@@ -626,27 +743,79 @@ impl ControlTypes {
                                     _ => {
                                         if !extensions.contains(ExtFlags::CONFIG) {
                                             warn!("Handshake contains config extension type {} without CONFIG flag!", pack_type);
+                                            tolerate(UnflaggedExtension(pack_type))?;
                                         }
-                                        match SrtControlPacket::parse(pack_type, &mut buffer)? {
+                                        let duplicate = match SrtControlPacket::parse(
+                                            pack_type,
+                                            &mut buffer,
+                                        ) {
                                             //5 = sid:
-                                            SrtControlPacket::StreamId(stream_id) => {
-                                                sid = Some(stream_id)
+                                            Ok(SrtControlPacket::StreamId(stream_id)) => {
+                                                sid.replace(stream_id).is_some()
+                                            }
+                                            //7 = filter:
+                                            Ok(SrtControlPacket::Filter(spec)) => {
+                                                filter.replace(spec).is_some()
+                                            }
+                                            //102 = session resumption:
+                                            Ok(SrtControlPacket::SessionResume(session)) => {
+                                                resume.replace(session).is_some()
+                                            }
+                                            //103 = authentication:
+                                            Ok(SrtControlPacket::Authentication(proof)) => {
+                                                auth.replace(proof).is_some()
                                             }
-                                            _ => unimplemented!("Implement other kinds"),
+                                            //104 = authentication challenge:
+                                            Ok(SrtControlPacket::AuthenticationChallenge(
+                                                nonce,
+                                            )) => challenge.replace(nonce).is_some(),
+                                            Ok(other) => {
+                                                warn!("Handshake contains unused extension {:?}, ignoring it", other);
+                                                false
+                                            }
+                                            // from a newer version, the rest is still understood
+                                            Err(PacketParseError::UnsupportedSrtExtensionType(
+                                                _,
+                                            )) => {
+                                                warn!("Handshake contains unknown extension type {}, skipping its {} bytes", pack_type, pack_size);
+                                                tolerate(UnknownExtension(pack_type))?;
+                                                false
+                                            }
+                                            Err(e) => return Err(e),
+                                        };
+                                        if duplicate {
+                                            tolerate(DuplicateExtension(pack_type))?;
                                         }
                                     }
                                 }
+                                if buffer.remaining() != 0 {
+                                    tolerate(ExtensionLength(pack_type, pack_size_words))?;
+                                }
+                                // the declared length is skipped whatever was read of it
+                                buffer.advance(buffer.remaining());
                                 buf = buffer.into_inner();
                             }
 
                             if buf.remaining() != 0 {
                                 warn!("Handshake has data left, but not enough for an extension!");
+                                tolerate(TrailingBytes(buf.remaining()))?;
                             }
                             if ext_hs.is_none() && extensions.contains(ExtFlags::HS) {
                                 warn!("Handshake has HSREQ flag, but contains no handshake extensions!");
+                                tolerate(MissingExtension(ExtFlags::HS.bits()))?;
                             }
                             if ext_km.is_none() && extensions.contains(ExtFlags::KM) {
                                 warn!("Handshake has KMREQ flag, but contains no key material extensions!");
+                                tolerate(MissingExtension(ExtFlags::KM.bits()))?;
+                            }
+                            if sid.is_none()
+                                && filter.is_none()
+                                && resume.is_none()
+                                && auth.is_none()
+                                && challenge.is_none()
+                                && extensions.contains(ExtFlags::CONFIG)
+                            {
+                                tolerate(MissingExtension(ExtFlags::CONFIG.bits()))?;
                             }
 
                             HandshakeVsInfo::V5(HsV5Info {
@@ -655,6 +824,10 @@ impl ControlTypes {
                                 ext_km,
                                 ext_group: None,
                                 sid,
+                                filter,
+                                resume,
+                                auth,
+                                challenge,
                             })
                         }
                     }
@@ -1072,6 +1245,9 @@ impl Debug for HandshakeVsInfo {
                 if let Some(sid) = &hs.sid {
                     write!(f, " sid={sid:?}")?;
                 }
+                if let Some(filter) = &hs.filter {
+                    write!(f, " filter={filter}")?;
+                }
                 Ok(())
             }
         }
@@ -1296,6 +1472,14 @@ impl HandshakeControlInfo {
                 info.ext_km.as_ref().map(|hs| 2 * size_of::<u16>() + usize::from(hs.size_words()) * size_of::<u32>()).unwrap_or(0)
                 +
                 info.sid.as_ref().map(|sid| 2 * size_of::<u16>() + ((sid.len() + 3) / 4 * 4)).unwrap_or(0)
+                +
+                info.filter.clone().map(|filter| 2 * size_of::<u16>() + usize::from(SrtControlPacket::Filter(filter).size_words()) * size_of::<u32>()).unwrap_or(0)
+                +
+                info.resume.map(|resume| 2 * size_of::<u16>() + usize::from(SrtControlPacket::SessionResume(resume).size_words()) * size_of::<u32>()).unwrap_or(0)
+                +
+                info.auth.clone().map(|auth| 2 * size_of::<u16>() + usize::from(SrtControlPacket::Authentication(auth).size_words()) * size_of::<u32>()).unwrap_or(0)
+                +
+                info.challenge.map(|nonce| 2 * size_of::<u16>() + usize::from(SrtControlPacket::AuthenticationChallenge(nonce).size_words()) * size_of::<u32>()).unwrap_or(0)
             }
         }
     }
@@ -1331,6 +1515,10 @@ impl HandshakeControlInfo {
                 &hs.ext_hs,
                 &hs.ext_km,
                 &hs.sid.clone().map(SrtControlPacket::StreamId),
+                &hs.filter.clone().map(SrtControlPacket::Filter),
+                &hs.resume.map(SrtControlPacket::SessionResume),
+                &hs.auth.clone().map(SrtControlPacket::Authentication),
+                &hs.challenge.map(SrtControlPacket::AuthenticationChallenge),
             ]
             .into_iter()
             .filter_map(|s| s.as_ref())
@@ -1381,6 +1569,12 @@ mod test {
         let des = ControlPacket::parse(&mut cursor, false).unwrap();
         assert_eq!(cursor.remaining(), 0);
         assert_eq!(pack, des);
+        // what's serialized passes strict parsing too, but for the socket ids left zero here
+        if !matches!(&pack.control_type, ControlTypes::Handshake(hs) if hs.socket_id == SocketId(0))
+        {
+            let strict = ControlPacket::parse_strict(&mut Cursor::new(&buf), false);
+            assert_eq!(strict.as_ref(), Ok(&pack));
+        }
         assert_eq!(
             pack.wire_size(),
             buf.len() + 28,
@@ -1423,6 +1617,10 @@ mod test {
                     ext_km: None,
                     ext_group: None,
                     sid: None,
+                    filter: None,
+                    resume: None,
+                    auth: None,
+                    challenge: None,
                 }),
             }),
         });
@@ -1478,6 +1676,10 @@ mod test {
                     ext_hs: None,
                     ext_group: None,
                     sid: None,
+                    filter: None,
+                    resume: None,
+                    auth: None,
+                    challenge: None,
                 }),
             }),
         });
@@ -1502,11 +1704,103 @@ mod test {
                     ext_hs: None,
                     ext_group: None,
                     sid: Some("Hello hello".into()),
+                    filter: None,
+                    resume: None,
+                    auth: None,
+                    challenge: None,
                 }),
             }),
         });
     }
 
+    #[test]
+    fn session_resume_ser_des_test() {
+        ser_des_test(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketId(0),
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: SeqNumber(1234),
+                max_packet_size: PacketSize(1816),
+                max_flow_size: PacketCount(0),
+                shake_type: ShakeType::Conclusion,
+                socket_id: SocketId(0),
+                syn_cookie: 0,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVsInfo::V5(HsV5Info {
+                    key_size: KeySize::Unspecified,
+                    ext_km: None,
+                    ext_hs: None,
+                    ext_group: None,
+                    sid: Some("Hello hello".into()),
+                    filter: None,
+                    resume: Some(SessionResume {
+                        token: 0x0123_4567_89ab_cdef,
+                        seq_number: SeqNumber(1234),
+                    }),
+                    auth: None,
+                    challenge: None,
+                }),
+            }),
+        });
+    }
+
+    #[test]
+    fn authentication_ser_des_test() {
+        for identity in ["a", "ab", "abc", "abcd", "encoder-01"] {
+            ser_des_test(ControlPacket {
+                timestamp: TimeStamp::from_micros(0),
+                dest_sockid: SocketId(0),
+                control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                    init_seq_num: SeqNumber(1234),
+                    max_packet_size: PacketSize(1816),
+                    max_flow_size: PacketCount(0),
+                    shake_type: ShakeType::Conclusion,
+                    socket_id: SocketId(0),
+                    syn_cookie: 0,
+                    peer_addr: [127, 0, 0, 1].into(),
+                    info: HandshakeVsInfo::V5(HsV5Info {
+                        key_size: KeySize::Unspecified,
+                        ext_km: None,
+                        ext_hs: None,
+                        ext_group: None,
+                        sid: None,
+                        filter: None,
+                        resume: None,
+                        auth: Some(HandshakeAuthentication {
+                            identity: identity.into(),
+                            nonce: [7; 16],
+                            signature: vec![0xab; 64],
+                        }),
+                        challenge: None,
+                    }),
+                }),
+            });
+        }
+    }
+
+    #[test]
+    fn authentication_challenge_ser_des_test() {
+        for shake_type in [ShakeType::Induction, ShakeType::Waveahand] {
+            ser_des_test(ControlPacket {
+                timestamp: TimeStamp::from_micros(0),
+                dest_sockid: SocketId(0),
+                control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                    init_seq_num: SeqNumber(1234),
+                    max_packet_size: PacketSize(1816),
+                    max_flow_size: PacketCount(0),
+                    shake_type,
+                    socket_id: SocketId(1),
+                    syn_cookie: 5678,
+                    peer_addr: [127, 0, 0, 1].into(),
+                    info: HandshakeVsInfo::V5(HsV5Info {
+                        challenge: Some([9; 16]),
+                        ..HsV5Info::default()
+                    }),
+                }),
+            });
+        }
+    }
+
     #[test]
     fn keepalive_ser_des_test() {
         ser_des_test(ControlPacket {
@@ -1662,6 +1956,10 @@ mod test {
                         ext_km: None,
                         ext_group: None,
                         sid: None,
+                        filter: None,
+                        resume: None,
+                        auth: None,
+                        challenge: None,
                     })
                 })
             }
@@ -1710,6 +2008,10 @@ mod test {
                         ext_km: None,
                         ext_group: None,
                         sid: Some(String::from("abcdefghij")),
+                        filter: None,
+                        resume: None,
+                        auth: None,
+                        challenge: None,
                     })
                 })
             }
@@ -1722,6 +2024,101 @@ mod test {
         assert_eq!(&buf[..], &packet_data[..]);
     }
 
+    #[test]
+    fn raw_handshake_strict() {
+        // the HSv5 conclusions from the reference implementation above pass strict parsing
+        for hex in [
+            "8000000000000000000F9EC400000000000000050000000144BEA60D000005DC00002000FFFFFFFF3D6936B6E3E405DD0100007F00000000000000000000000000010003000103010000002F00780000",
+            "800000000000000000000b1400000000000000050000000563444b2e000005dc00002000ffffffff37eb0ee52154fbd60100007f0000000000000000000000000001000300010401000000bf0014001400050003646362616867666500006a69",
+            "800000000000000000175E8A0000000000000005000000036FEFB8D8000005DC00002000FFFFFFFF35E790ED5D16CCEA0100007F00000000000000000000000000010003000103010000002F01F401F40003000E122029010000000002000200000004049D75B0AC924C6E4C9EC40FEB4FE973DB1D215D426C18A2871EBF77E2646D9BAB15DBD7689AEF60EC",
+        ] {
+            let packet_data = hex::decode(hex).unwrap();
+            let lenient = ControlPacket::parse(&mut Cursor::new(&packet_data[..]), false);
+            let strict = ControlPacket::parse_strict(&mut Cursor::new(&packet_data[..]), false);
+            assert_eq!(strict, lenient);
+        }
+    }
+
+    #[test]
+    fn raw_handshake_strict_violations() {
+        use HandshakeViolation::*;
+        let conclusion = hex::decode("8000000000000000000F9EC400000000000000050000000144BEA60D000005DC00002000FFFFFFFF3D6936B6E3E405DD0100007F00000000000000000000000000010003000103010000002F00780000").unwrap();
+
+        // the conclusion above, subtly off, which lenient parsing takes as it is
+        let malformed = |change: &dyn Fn(&mut Vec<u8>)| {
+            let mut packet_data = conclusion.clone();
+            change(&mut packet_data);
+            let lenient = ControlPacket::parse(&mut Cursor::new(&packet_data[..]), false);
+            assert!(lenient.is_ok(), "{lenient:?}");
+            match ControlPacket::parse_strict(&mut Cursor::new(&packet_data[..]), false) {
+                Err(PacketParseError::StrictHandshake(violation)) => violation,
+                strict => panic!("{strict:?}"),
+            }
+        };
+
+        assert_eq!(
+            malformed(&|p| p[7] = 1),
+            ReservedField("type-specific information", 1)
+        );
+        assert_eq!(
+            malformed(&|p| p[63] = 1),
+            ReservedField("IPv4 address padding", 1)
+        );
+        assert_eq!(malformed(&|p| p[40..44].fill(0)), ZeroSocketId);
+        assert_eq!(malformed(&|p| p[21] = 5), CryptoLength(40));
+        assert_eq!(malformed(&|p| p[23] = 0b1001), ExtensionFlags(0b1001));
+        assert_eq!(malformed(&|p| p[23] = 0b101), MissingExtension(0b100));
+        // the HSREQ flag cleared, or the HSREQ declaring a word it doesn't take
+        assert_eq!(malformed(&|p| p[23] = 0), UnflaggedExtension(1));
+        assert_eq!(
+            malformed(&|p| {
+                p[67] = 4;
+                p.extend([0; 4]);
+            }),
+            ExtensionLength(1, 4)
+        );
+        assert_eq!(malformed(&|p| p.extend([0; 2])), TrailingBytes(2));
+        assert_eq!(
+            malformed(&|p| p.extend(p[64..80].to_vec())),
+            DuplicateExtension(1)
+        );
+        assert_eq!(
+            malformed(&|p| {
+                p[23] = 0b101;
+                p.extend([0, 0xc8, 0, 1, 0xde, 0xad, 0xbe, 0xef]);
+            }),
+            UnknownExtension(0xc8)
+        );
+    }
+
+    #[test]
+    fn raw_handshake_unknown_extensions() {
+        // the stream id conclusion above, with extensions interleaved that are skipped by their
+        // length: an unknown one, an empty unknown one and a congestion control one that's unused
+        let header = "800000000000000000000b1400000000000000050000000563444b2e000005dc00002000ffffffff37eb0ee52154fbd60100007f000000000000000000000000";
+        let hsreq = "0001000300010401000000bf00140014";
+        let sid = "00050003646362616867666500006a69";
+        let unknown = "00c80002deadbeef01020304";
+        let empty = "00c90000";
+        let congestion = "000600016576696c";
+
+        let parse = |hex: String| {
+            let packet_data = hex::decode(hex).unwrap();
+            ControlPacket::parse(&mut Cursor::new(&packet_data[..]), false)
+        };
+        let expected = parse(format!("{header}{hsreq}{sid}")).unwrap();
+        let packet = parse(format!("{header}{hsreq}{unknown}{empty}{sid}{congestion}")).unwrap();
+        assert_eq!(packet, expected);
+        let packet = parse(format!("{header}{unknown}{hsreq}{congestion}{sid}{empty}")).unwrap();
+        assert_eq!(packet, expected);
+
+        // but one that claims more than there is still fails
+        assert_eq!(
+            parse(format!("{header}{hsreq}{sid}00c80004deadbeef")),
+            Err(PacketParseError::NotEnoughData)
+        );
+    }
+
     #[test]
     fn raw_handshake_crypto() {
         // this is an example HSv5 conclusion packet from the reference implementation that has crypto data embedded.
@@ -1767,6 +2164,10 @@ mod test {
                         })),
                         ext_group: None,
                         sid: None,
+                        filter: None,
+                        resume: None,
+                        auth: None,
+                        challenge: None,
                     })
                 })
             }
@@ -1863,6 +2264,10 @@ mod test {
                     })),
                     ext_group: None,
                     sid: Some("#!::u=hex".into()),
+                    filter: None,
+                    resume: None,
+                    auth: None,
+                    challenge: None,
                 }),
             }),
         };
@@ -1872,4 +2277,31 @@ mod test {
 
     #[test]
     fn drop_request() {}
+
+    #[test]
+    fn seq_number_bounds_ser_des_test() {
+        let max = SeqNumber::new(SeqNumber::MAX - 1).unwrap();
+        let zero = SeqNumber::new(0).unwrap();
+        let control_types = [
+            ControlTypes::Ack(Acknowledgement::Lite(max)),
+            ControlTypes::Ack(Acknowledgement::Lite(zero)),
+            ControlTypes::Nak(
+                CompressedLossList::try_from_iter([max - 1, max, zero].into_iter()).unwrap(),
+            ),
+            ControlTypes::Nak(CompressedLossList::try_from_iter([max].into_iter()).unwrap()),
+            ControlTypes::DropRequest {
+                msg_to_drop: MsgNumber::new_truncate(MsgNumber::MAX - 1),
+                range: max..=max + 1,
+            },
+        ];
+        for control_type in control_types {
+            let buf = ser_des_test(ControlPacket {
+                timestamp: TimeStamp::from_micros(0),
+                dest_sockid: SocketId(u32::MAX),
+                control_type,
+            });
+            // the control flag survives sequence numbers at the top of the range
+            assert_eq!(buf[0] & 0x80, 0x80);
+        }
+    }
 }
@@ -1,13 +1,25 @@
-use std::{
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    convert::TryFrom,
     fmt::{self, Display, Formatter},
-    {collections::BTreeMap, convert::TryFrom, time::Duration},
+    str::FromStr,
+    time::Duration,
 };
 
 use bitflags::bitflags;
 use bytes::{Buf, BufMut};
 use log::warn;
 
-use crate::{options::SrtVersion, packet::PacketParseError};
+use crate::{
+    options::SrtVersion,
+    packet::{PacketParseError, SeqNumber},
+};
 
 /// The SRT-specific control packets
 /// These are `Packet::Custom` types
@@ -53,9 +65,63 @@ pub enum SrtControlPacket {
         flags: GroupFlags,
         weight: u16,
     },
+
+    /// Asks the peer to use a new TSBPD latency for the data it sends, not part of the reference
+    /// implementation, so only sent once both sides offered [`SrtShakeFlags::LATENCY_CHANGE`]
+    /// ID = 100
+    LatencyChangeRequest(Duration),
+
+    /// Confirms the TSBPD latency the peer will use from now on
+    /// ID = 101
+    LatencyChangeResponse(Duration),
+
+    /// Resumes a session a connection dropped out of, in the handshake, not part of the reference
+    /// implementation
+    /// ID = 102
+    SessionResume(SessionResume),
+
+    /// Proves the identity of a peer in the handshake, not part of the reference implementation
+    /// ID = 103
+    Authentication(HandshakeAuthentication),
+
+    /// The nonce a responder with credentials issues for the initiator to sign, in the induction
+    /// response or with the waves in rendezvous, not part of the reference implementation
+    /// ID = 104
+    AuthenticationChallenge([u8; 16]),
+}
+
+/// The session a connection resumes, and the sequence number the stream goes on from. The caller
+/// asks with the first one it still has the data of, the listener answers with the one the
+/// connection starts from, which skips what it received already.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SessionResume {
+    pub token: u64,
+    pub seq_number: SeqNumber,
+}
+
+/// Who a peer claims to be, and the proof it holds the signing key of that identity: an Ed25519
+/// signature over the identity, the handshake cookie and the nonce the other side issued. The
+/// caller signs the challenge from the induction response and sends a nonce of its own, which the
+/// listener signs and echoes, so neither proof can be replayed into another handshake.
+#[derive(Clone, Eq, PartialEq)]
+pub struct HandshakeAuthentication {
+    pub identity: String,
+    pub nonce: [u8; 16],
+    pub signature: Vec<u8>,
+}
+
+impl fmt::Debug for HandshakeAuthentication {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("HandshakeAuthentication")
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
 }
 
+/// A packet filter config, e.g. `fec,cols:10,rows:5`. The filter type comes first and has no
+/// value, it's kept as a key with an empty value.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FilterSpec(pub BTreeMap<String, String>);
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -266,6 +332,19 @@ bitflags! {
         /// Again not sure... TODO:
         const PACKET_FILTER = 0x80;
 
+        /// Data is tagged with the logical stream it belongs to, see `Session::multiplex`. Not in
+        /// the reference implementation, which leaves it out of its answer like any other flag it
+        /// doesn't know
+        const MULTIPLEX = 0x8000_0000;
+
+        /// The TSBPD latency can be changed on a live connection with `LatencyChangeRequest`. Not
+        /// in the reference implementation either, which wouldn't answer the request
+        const LATENCY_CHANGE = 0x4000_0000;
+
+        /// A keepalive is answered with a light ACK, so the peer can time the round trip without
+        /// data flowing. Not in the reference implementation, which leaves keepalives unanswered
+        const KEEPALIVE_ECHO = 0x2000_0000;
+
         // currently implemented flags
         const SUPPORTED = Self::TSBPDSND.bits() | Self::TSBPDRCV.bits() | Self::HAICRYPT.bits() | Self::REXMITFLG.bits();
     }
@@ -310,19 +389,92 @@ fn string_to_le_bytes(str: &str, into: &mut impl BufMut) {
     }
 }
 
+impl FilterSpec {
+    /// The filter type, e.g. `fec`
+    pub fn filter_type(&self) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, v)| v.is_empty())
+            .map(|(k, _)| k.as_str())
+    }
+
+    /// Combines the configs of both peers, None if they disagree on the filter type or on the
+    /// value of a parameter. Parameters only one of them sets are taken as they are.
+    pub fn merge(&self, other: &FilterSpec) -> Option<FilterSpec> {
+        if self.filter_type() != other.filter_type() {
+            return None;
+        }
+        let mut merged = self.0.clone();
+        for (key, value) in &other.0 {
+            match merged.get(key) {
+                Some(v) if v != value => return None,
+                Some(_) => {}
+                None => {
+                    merged.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Some(FilterSpec(merged))
+    }
+}
+
+impl FromStr for FilterSpec {
+    type Err = PacketParseError;
+
+    fn from_str(filter_str: &str) -> Result<Self, Self::Err> {
+        let bad_filter = || PacketParseError::BadFilter(filter_str.to_string());
+        let mut filter_types = 0;
+        let spec = filter_str
+            .split(',')
+            .map(|kv| {
+                let mut colon_split_iter = kv.split(':');
+                let k = colon_split_iter.next().ok_or_else(bad_filter)?;
+                let v = match colon_split_iter.next() {
+                    Some(v) if !v.is_empty() => v,
+                    Some(_) => return Err(bad_filter()),
+                    None => {
+                        filter_types += 1;
+                        ""
+                    }
+                };
+                // only one colon
+                if k.is_empty() || colon_split_iter.next().is_some() {
+                    return Err(bad_filter());
+                }
+                Ok((k.to_string(), v.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        // the filter type is the only entry without a value
+        if filter_types > 1 {
+            return Err(bad_filter());
+        }
+        Ok(FilterSpec(spec))
+    }
+}
+
 impl Display for FilterSpec {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
-        for (i, (k, v)) in self.0.iter().enumerate() {
-            write!(f, "{k}:{v}")?;
-            if i != self.0.len() - 1 {
+        // the filter type goes first
+        let (filter_type, parameters): (Vec<_>, Vec<_>) =
+            self.0.iter().partition(|(_, v)| v.is_empty());
+        for (i, (k, v)) in filter_type.into_iter().chain(parameters).enumerate() {
+            if i > 0 {
                 write!(f, ",")?;
             }
+            if v.is_empty() {
+                write!(f, "{k}")?;
+            } else {
+                write!(f, "{k}:{v}")?;
+            }
         }
         Ok(())
     }
 }
 
 impl SrtControlPacket {
+    /// The largest latency a `LatencyChangeRequest` asks for, as much as the handshake can agree on
+    pub const MAX_LATENCY: Duration = Duration::from_millis(u16::MAX as u64);
+
     pub fn parse<T: Buf>(
         packet_type: u16,
         buf: &mut T,
@@ -344,25 +496,7 @@ impl SrtControlPacket {
             // Filter
             7 => {
                 let filter_str = le_bytes_to_string(buf)?;
-                Ok(Filter(FilterSpec(
-                    filter_str
-                        .split(',')
-                        .map(|kv| {
-                            let mut colon_split_iter = kv.split(':');
-                            let k = colon_split_iter
-                                .next()
-                                .ok_or_else(|| PacketParseError::BadFilter(filter_str.clone()))?;
-                            let v = colon_split_iter
-                                .next()
-                                .ok_or_else(|| PacketParseError::BadFilter(filter_str.clone()))?;
-                            // only one colon
-                            if colon_split_iter.next().is_some() {
-                                return Err(PacketParseError::BadFilter(filter_str.clone()));
-                            }
-                            Ok((k.to_string(), v.to_string()))
-                        })
-                        .collect::<Result<_, _>>()?,
-                )))
+                Ok(Filter(filter_str.parse()?))
             }
             8 => {
                 let ty = buf.get_u8().into();
@@ -370,6 +504,56 @@ impl SrtControlPacket {
                 let weight = buf.get_u16_le();
                 Ok(Group { ty, flags, weight })
             }
+            100 | 101 => {
+                if buf.remaining() < 4 {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let latency = Duration::from_millis(u64::from(buf.get_u32()));
+                if packet_type == 100 {
+                    Ok(LatencyChangeRequest(latency))
+                } else {
+                    Ok(LatencyChangeResponse(latency))
+                }
+            }
+            102 => {
+                if buf.remaining() < 12 {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let token = buf.get_u64();
+                let seq_number = SeqNumber::new_truncate(buf.get_u32());
+                Ok(SessionResume(self::SessionResume { token, seq_number }))
+            }
+            103 => {
+                if buf.remaining() < 20 {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let identity_len = usize::from(buf.get_u16());
+                let signature_len = usize::from(buf.get_u16());
+                let mut nonce = [0; 16];
+                buf.copy_to_slice(&mut nonce);
+                if buf.remaining() < identity_len + signature_len {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let mut signature = vec![0; signature_len];
+                buf.copy_to_slice(&mut signature);
+                let mut identity = vec![0; identity_len];
+                buf.copy_to_slice(&mut identity);
+                let identity = String::from_utf8(identity)
+                    .map_err(|e| PacketParseError::IdentityNotUtf8(e.utf8_error()))?;
+                Ok(Authentication(HandshakeAuthentication {
+                    identity,
+                    nonce,
+                    signature,
+                }))
+            }
+            104 => {
+                if buf.remaining() < 16 {
+                    return Err(PacketParseError::NotEnoughData);
+                }
+                let mut nonce = [0; 16];
+                buf.copy_to_slice(&mut nonce);
+                Ok(AuthenticationChallenge(nonce))
+            }
             _ => Err(PacketParseError::UnsupportedSrtExtensionType(packet_type)),
         }
     }
@@ -388,6 +572,11 @@ impl SrtControlPacket {
             Congestion(_) => 6,
             Filter(_) => 7,
             Group { .. } => 8,
+            LatencyChangeRequest(_) => 100,
+            LatencyChangeResponse(_) => 101,
+            SessionResume(_) => 102,
+            Authentication(_) => 103,
+            AuthenticationChallenge(_) => 104,
         }
     }
     pub fn serialize<T: BufMut>(&self, into: &mut T) {
@@ -408,6 +597,24 @@ impl SrtControlPacket {
                 into.put_u8(flags.bits());
                 into.put_u16_le(*weight);
             }
+            LatencyChangeRequest(latency) | LatencyChangeResponse(latency) => {
+                into.put_u32(u32::try_from(latency.as_millis()).unwrap_or(u32::MAX));
+            }
+            SessionResume(resume) => {
+                into.put_u64(resume.token);
+                into.put_u32(resume.seq_number.as_raw());
+            }
+            Authentication(auth) => {
+                into.put_u16(auth.identity.len() as u16);
+                into.put_u16(auth.signature.len() as u16);
+                into.put_slice(&auth.nonce);
+                into.put_slice(&auth.signature);
+                into.put_slice(auth.identity.as_bytes());
+                // pad to the next 32-bit word
+                let len = 20 + auth.signature.len() + auth.identity.len();
+                into.put_bytes(0, (4 - len % 4) % 4);
+            }
+            AuthenticationChallenge(nonce) => into.put_slice(nonce),
             Reject => {}
             StreamId(str) | Congestion(str) => {
                 // the stream id string and congestion string is stored as 32-bit little endian words
@@ -430,6 +637,16 @@ impl SrtControlPacket {
             Congestion(str) | StreamId(str) => ((str.len() + 3) / 4) as u16, // round up to nearest multiple of 4
             // 1 32-bit word packed with type, flags, and weight
             Group { .. } => 1,
+            // 1 32-bit word, latency in milliseconds
+            LatencyChangeRequest(_) | LatencyChangeResponse(_) => 1,
+            // 3 32-bit words, the token and the sequence number
+            SessionResume(_) => 3,
+            // 1 32-bit word of lengths, 4 of nonce, then the signature and identity rounded up
+            Authentication(auth) => {
+                (20 + auth.signature.len() + auth.identity.len()).div_ceil(4) as u16
+            }
+            // 4 32-bit words of nonce
+            AuthenticationChallenge(_) => 4,
             Filter(filter) => ((format!("{filter}").len() + 3) / 4) as u16, // TODO: not optimial performace, but probably okay
             _ => unimplemented!("{:?}", self),
         }
@@ -658,6 +875,15 @@ impl fmt::Debug for SrtControlPacket {
             SrtControlPacket::Group { ty, flags, weight } => {
                 write!(f, "group=({ty:?}, {flags:?}, {weight:?})")
             }
+            SrtControlPacket::LatencyChangeRequest(latency) => write!(f, "latencyreq={latency:?}"),
+            SrtControlPacket::LatencyChangeResponse(latency) => {
+                write!(f, "latencyresp={latency:?}")
+            }
+            SrtControlPacket::SessionResume(resume) => {
+                write!(f, "resume={:x}@{:?}", resume.token, resume.seq_number)
+            }
+            SrtControlPacket::Authentication(auth) => write!(f, "auth={auth:?}"),
+            SrtControlPacket::AuthenticationChallenge(_) => write!(f, "authchallenge"),
         }
     }
 }
@@ -677,7 +903,7 @@ impl TryFrom<u8> for CipherType {
 
 #[cfg(test)]
 mod tests {
-    use super::{KeyingMaterialMessage, SrtControlPacket, SrtHandshake, SrtShakeFlags};
+    use super::{FilterSpec, KeyingMaterialMessage, SrtControlPacket, SrtHandshake, SrtShakeFlags};
 
     use crate::{options::*, packet::*};
 
@@ -720,6 +946,64 @@ mod tests {
         assert_eq!(sid, deser);
     }
 
+    #[test]
+    fn ser_deser_latency_change() {
+        for control_type in [
+            SrtControlPacket::LatencyChangeRequest(Duration::from_millis(1500)),
+            SrtControlPacket::LatencyChangeResponse(Duration::from_millis(80)),
+        ] {
+            let packet = Packet::Control(ControlPacket {
+                timestamp: TimeStamp::from_micros(123),
+                dest_sockid: SocketId(1234),
+                control_type: ControlTypes::Srt(control_type),
+            });
+
+            let mut buf = Vec::new();
+            packet.serialize(&mut buf);
+
+            let deser = Packet::parse(&mut Cursor::new(buf), false).unwrap();
+
+            assert_eq!(packet, deser);
+        }
+    }
+
+    #[test]
+    fn ser_deser_filter_config() {
+        let spec: FilterSpec = "fec,cols:10,rows:5,arq:never".parse().unwrap();
+        assert_eq!(spec.filter_type(), Some("fec"));
+        assert_eq!(spec.0["cols"], "10");
+        // the filter type stays in front, as the reference implementation expects
+        assert_eq!(spec.to_string(), "fec,arq:never,cols:10,rows:5");
+
+        let packet = Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(123),
+            dest_sockid: SocketId(1234),
+            control_type: ControlTypes::Srt(SrtControlPacket::Filter(spec)),
+        });
+        let mut buf = Vec::new();
+        packet.serialize(&mut buf);
+        assert_eq!(Packet::parse(&mut Cursor::new(buf), false).unwrap(), packet);
+
+        for bad in ["", "fec,cols:", "fec,rows", "fec,cols:1:2", ",cols:1"] {
+            assert!(bad.parse::<FilterSpec>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn merge_filter_config() {
+        let spec = |s: &str| s.parse::<FilterSpec>().unwrap();
+        assert_eq!(
+            spec("fec,cols:10").merge(&spec("fec,cols:10,rows:5")),
+            Some(spec("fec,cols:10,rows:5"))
+        );
+        assert_eq!(
+            spec("fec,cols:10,arq:never").merge(&spec("fec,rows:5")),
+            Some(spec("fec,arq:never,cols:10,rows:5"))
+        );
+        assert_eq!(spec("fec,cols:10").merge(&spec("fec,cols:8")), None);
+        assert_eq!(spec("fec,cols:10").merge(&spec("other,cols:10")), None);
+    }
+
     #[test]
     fn srt_key_message_debug() {
         let salt = b"\x00\x00\x00\x00\x00\x00\x00\x00\x85\x2c\x3c\xcd\x02\x65\x1a\x22";
@@ -1,5 +1,5 @@
-use std::cmp::min;
-use std::{convert::TryFrom, fmt};
+use core::cmp::min;
+use core::{convert::TryFrom, fmt};
 
 use bitflags::bitflags;
 use bytes::{Buf, BufMut, Bytes};
@@ -178,6 +178,7 @@ impl fmt::Debug for DataPacket {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::packet::Packet;
     use proptest::prelude::*;
     use std::io::Cursor;
 
@@ -222,4 +223,43 @@ mod tests {
             assert_eq!(v, v2);
         }
     }
+
+    fn data_packet(seq_number: SeqNumber) -> DataPacket {
+        DataPacket {
+            seq_number,
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber::new_truncate(MsgNumber::MAX - 1),
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: SocketId(u32::MAX),
+            payload: Bytes::from_static(b"payload"),
+        }
+    }
+
+    #[test]
+    fn seq_number_bounds() {
+        let max = SeqNumber::new(SeqNumber::MAX - 1).unwrap();
+        for seq_number in [SeqNumber::new(0).unwrap(), max, max + 1, max - 1, max + 2] {
+            let packet = data_packet(seq_number);
+            let mut v = vec![];
+            packet.serialize(&mut v);
+
+            // the top bit is the control flag
+            assert_eq!(v[0] & 0x80, 0, "{seq_number:?}");
+            assert_eq!(
+                u32::from_be_bytes(v[0..4].try_into().unwrap()),
+                seq_number.as_raw()
+            );
+
+            let parsed = Packet::parse(&mut Cursor::new(&v), false).unwrap();
+            assert_eq!(parsed, Packet::Data(packet));
+
+            let mut v2 = vec![];
+            parsed.serialize(&mut v2);
+            assert_eq!(v, v2);
+        }
+        assert_eq!(max + 1, SeqNumber::new(0).unwrap());
+    }
 }
@@ -1,4 +1,5 @@
-use std::{error::Error, fmt, str::Utf8Error};
+use alloc::string::String;
+use core::{error::Error, fmt, str::Utf8Error};
 
 #[derive(Debug, Eq, PartialEq)]
 #[non_exhaustive]
@@ -19,8 +20,40 @@ pub enum PacketParseError {
     StreamEncapsulationNotSrt,
     BadDataEncryption(u8),
     StreamTypeNotUtf8(Utf8Error),
+    IdentityNotUtf8(Utf8Error),
     ZeroAckSequenceNumber,
     BadFilter(String),
+    /// A handshake strict parsing rejects, see [`Packet::parse_strict`](super::Packet::parse_strict)
+    StrictHandshake(HandshakeViolation),
+}
+
+/// What strict parsing rejects a handshake for, where lenient parsing only warns and carries on
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum HandshakeViolation {
+    /// The named field is reserved, but not zero
+    ReservedField(&'static str, u32),
+    /// The socket id is zero, which is never a socket's
+    ZeroSocketId,
+    /// An HSv5 induction without the SRT magic code in the extension field
+    MissingMagicCode(u16),
+    /// The encryption field isn't 0, 16, 24 or 32 bytes
+    CryptoLength(u16),
+    /// Extension flags that aren't defined
+    ExtensionFlags(u16),
+    /// An extension of this type without the flag for it
+    UnflaggedExtension(u16),
+    /// These extension flags, without an extension for them
+    MissingExtension(u16),
+    /// More than one extension of this type
+    DuplicateExtension(u16),
+    /// An extension of a type that isn't known, e.g. from a newer version
+    UnknownExtension(u16),
+    /// The extension of this type declares this many 32-bit words, but its contents take another
+    /// length
+    ExtensionLength(u16, u16),
+    /// This many bytes after the last extension, too few for another one
+    TrailingBytes(usize),
 }
 
 impl fmt::Display for PacketParseError {
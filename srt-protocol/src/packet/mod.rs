@@ -6,6 +6,9 @@ mod data;
 mod error;
 mod modular_num;
 mod msg_number;
+#[cfg(feature = "std")]
+mod receive;
+mod rtt;
 mod seq_number;
 mod socket_id;
 mod time;
@@ -14,15 +17,14 @@ pub use control::*;
 pub use data::*;
 pub use error::*;
 pub use msg_number::*;
+#[cfg(feature = "std")]
+pub use receive::*;
+pub use rtt::*;
 pub use seq_number::*;
 pub use socket_id::*;
 pub use time::*;
 
-use std::{
-    fmt::{self, Debug, Formatter},
-    io,
-    net::SocketAddr,
-};
+use core::fmt::{self, Debug, Formatter};
 
 use bytes::{Buf, BufMut};
 
@@ -93,6 +95,22 @@ impl Packet {
     }
 
     pub fn parse<T: Buf>(buf: &mut T, is_ipv6: bool) -> Result<Packet, PacketParseError> {
+        Self::parse_with(buf, is_ipv6, false)
+    }
+
+    /// Parses like [`parse`](Self::parse), but rejects handshakes with reserved fields that
+    /// aren't zero, a socket id of zero, or extensions that don't add up, rather than warning
+    /// about them, see [`HandshakeViolation`]. For checking peers during development, the
+    /// reference implementation and older versions of it are lenient about these.
+    pub fn parse_strict<T: Buf>(buf: &mut T, is_ipv6: bool) -> Result<Packet, PacketParseError> {
+        Self::parse_with(buf, is_ipv6, true)
+    }
+
+    fn parse_with<T: Buf>(
+        buf: &mut T,
+        is_ipv6: bool,
+        strict: bool,
+    ) -> Result<Packet, PacketParseError> {
         // Buffer must be at least 16 bytes,
         // the length of a header packet
         if buf.remaining() < 16 {
@@ -108,7 +126,7 @@ impl Packet {
         Ok(if (first & 0x80) == 0 {
             Packet::Data(DataPacket::parse(buf)?)
         } else {
-            Packet::Control(ControlPacket::parse(buf, is_ipv6)?)
+            Packet::Control(ControlPacket::parse_with(buf, is_ipv6, strict)?)
         })
     }
 
@@ -144,58 +162,3 @@ impl Debug for Packet {
         }
     }
 }
-
-#[derive(Debug)]
-pub enum ReceivePacketError {
-    Parse(PacketParseError),
-    Io(io::Error),
-}
-
-impl From<io::Error> for ReceivePacketError {
-    fn from(error: io::Error) -> Self {
-        ReceivePacketError::Io(error)
-    }
-}
-
-impl From<PacketParseError> for ReceivePacketError {
-    fn from(error: PacketParseError) -> Self {
-        ReceivePacketError::Parse(error)
-    }
-}
-
-impl fmt::Display for ReceivePacketError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use ReceivePacketError::*;
-        match self {
-            Parse(e) => <PacketParseError as fmt::Display>::fmt(e, f),
-            Io(e) => <io::Error as fmt::Display>::fmt(e, f),
-        }
-    }
-}
-
-impl std::error::Error for ReceivePacketError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        use ReceivePacketError::*;
-        match self {
-            Parse(e) => Some(e),
-            Io(e) => Some(e),
-        }
-    }
-}
-
-// NOTE: Eq, PartialEq are only here to accommodate structural comparison in tests without losing
-//  the ability to surface network errors for logging purposes, improve on this as needed, but don't
-//  remove.
-impl Eq for ReceivePacketError {}
-impl PartialEq for ReceivePacketError {
-    fn eq(&self, other: &Self) -> bool {
-        use ReceivePacketError::*;
-        match (self, other) {
-            (Parse(s), Parse(o)) => s.eq(o),
-            (Io(s), Io(o)) => s.kind().eq(&o.kind()) && s.raw_os_error().eq(&o.raw_os_error()),
-            _ => false,
-        }
-    }
-}
-
-pub type ReceivePacketResult = Result<(Packet, SocketAddr), ReceivePacketError>;
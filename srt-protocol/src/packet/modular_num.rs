@@ -1,5 +1,5 @@
 /// Defines a macro to define a modular number that uses a predefined number of bits
-use std::fmt::{self, Display, Formatter};
+use core::fmt::{self, Display, Formatter};
 
 #[derive(Debug)]
 pub struct OutOfRangeError(pub &'static str);
@@ -38,7 +38,7 @@ macro_rules! modular_num_impls {
                 $x(from % $x::MAX)
             }
             pub fn new(from: $type) -> Result<$x, $crate::packet::modular_num::OutOfRangeError> {
-                if from > $x::MAX {
+                if from >= $x::MAX {
                     Err($crate::packet::modular_num::OutOfRangeError(stringify!($x)))
                 } else {
                     Ok($x(from))
@@ -50,7 +50,7 @@ macro_rules! modular_num_impls {
             }
         }
 
-        impl ::std::convert::TryFrom<$type> for $x {
+        impl ::core::convert::TryFrom<$type> for $x {
             type Error = $crate::packet::modular_num::OutOfRangeError;
 
             fn try_from(from: $type) -> Result<Self, Self::Error> {
@@ -58,6 +58,7 @@ macro_rules! modular_num_impls {
             }
         }
 
+        #[cfg(feature = "std")]
         impl ::rand::distributions::Distribution<$x> for ::rand::distributions::Standard {
             fn sample<T: ::rand::Rng + ?Sized>(&self, rng: &mut T) -> $x {
                 $x::new_truncate(rng.gen::<$type>())
@@ -65,7 +66,7 @@ macro_rules! modular_num_impls {
         }
 
         #[allow(clippy::suspicious_arithmetic_impl)]
-        impl ::std::ops::Add<$type> for $x {
+        impl ::core::ops::Add<$type> for $x {
             type Output = Self;
 
             fn add(self, other: $type) -> Self {
@@ -78,7 +79,7 @@ macro_rules! modular_num_impls {
         /// Move a sequence number backwards by an offset
         /// ie: SeqNumber(3) - 2 == 1
         /// and SeqNumber(0) - 1 == SeqNumber(MAX)
-        impl ::std::ops::Sub<$type> for $x {
+        impl ::core::ops::Sub<$type> for $x {
             type Output = Self;
 
             fn sub(self, other: $type) -> Self {
@@ -95,7 +96,7 @@ macro_rules! modular_num_impls {
         /// Always measured with first one first and the second one second
         /// ie: SeqNumber(0) - SeqNumber(MAX) == 1
         /// and SeqNumber(1) - SeqNumber(0) == 1
-        impl ::std::ops::Sub<$x> for $x {
+        impl ::core::ops::Sub<$x> for $x {
             type Output = $type;
 
             fn sub(self, other: Self) -> Self::Output {
@@ -111,25 +112,25 @@ macro_rules! modular_num_impls {
         /// Ordering sequence numbers is difficult, as they are modular
         /// How it works is if the absolute value of the difference between sequence numbers is greater than
         /// MAX_DIFF, then wrapping is assumed
-        impl ::std::cmp::Ord for $x {
-            fn cmp(&self, other: &Self) -> ::std::cmp::Ordering {
+        impl ::core::cmp::Ord for $x {
+            fn cmp(&self, other: &Self) -> ::core::cmp::Ordering {
                 let diff = *self - *other;
 
                 if diff == 0 {
-                    return ::std::cmp::Ordering::Equal;
+                    return ::core::cmp::Ordering::Equal;
                 }
 
                 if diff < $x::MAX_DIFF {
                     // this means self was bigger than other
-                    ::std::cmp::Ordering::Greater
+                    ::core::cmp::Ordering::Greater
                 } else {
                     // this means other was greater
-                    ::std::cmp::Ordering::Less
+                    ::core::cmp::Ordering::Less
                 }
             }
         }
 
-        impl ::std::ops::Rem<$type> for $x {
+        impl ::core::ops::Rem<$type> for $x {
             type Output = $type;
 
             fn rem(self, other: $type) -> Self::Output {
@@ -137,20 +138,20 @@ macro_rules! modular_num_impls {
             }
         }
 
-        impl ::std::cmp::PartialOrd for $x {
-            fn partial_cmp(&self, other: &Self) -> Option<::std::cmp::Ordering> {
+        impl ::core::cmp::PartialOrd for $x {
+            fn partial_cmp(&self, other: &Self) -> Option<::core::cmp::Ordering> {
                 Some(self.cmp(other))
             }
         }
 
-        impl ::std::ops::AddAssign<$type> for $x {
+        impl ::core::ops::AddAssign<$type> for $x {
             fn add_assign(&mut self, rhs: $type) {
                 *self = *self + rhs
             }
         }
 
-        impl ::std::fmt::Display for $x {
-            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        impl ::core::fmt::Display for $x {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 write!(f, "{}", self.0)
             }
         }
@@ -171,6 +172,13 @@ mod tests {
             1_687_761_238
         );
         assert!(SeqNumber::new(1_687_761_239 | 1 << 31).is_err());
+
+        // MAX is the first value that doesn't fit
+        assert_eq!(
+            SeqNumber::new(SeqNumber::MAX - 1).unwrap().as_raw(),
+            (1 << 31) - 1
+        );
+        assert!(SeqNumber::new(SeqNumber::MAX).is_err());
     }
 
     #[test]
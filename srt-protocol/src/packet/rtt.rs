@@ -1,4 +1,4 @@
-use std::{convert::TryInto, time::Duration};
+use core::{convert::TryInto, time::Duration};
 
 use crate::packet::TimeSpan;
 
@@ -22,6 +22,16 @@ impl Rtt {
         Self { mean, variance }
     }
 
+    /// Starts from an estimate of the round trip time, with half of it as the variance like the
+    /// reference implementation starts from
+    pub fn from_estimate(rtt: Duration) -> Self {
+        let mean = TimeSpan::from_micros(rtt.as_micros().try_into().unwrap_or(i32::MAX));
+        Self {
+            mean,
+            variance: mean / 2,
+        }
+    }
+
     pub fn update(&mut self, rtt: TimeSpan) {
         self.mean = TimeSpan::from_micros(
             ((self.mean.as_micros() as i64 * 7 + rtt.as_micros() as i64) / 8) as i32,
@@ -12,7 +12,32 @@ impl SeqNumber {
         next
     }
 
+    /// The number of sequence numbers from `other` up to `self`, or zero if `self` comes first,
+    /// taking wrapping into account
     pub fn saturating_sub(self, other: Self) -> usize {
-        self.0.saturating_sub(other.0) as usize
+        if self < other {
+            0
+        } else {
+            (self - other) as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn saturating_sub_wraps() {
+        assert_eq!(SeqNumber(5).saturating_sub(SeqNumber(2)), 3);
+        assert_eq!(SeqNumber(2).saturating_sub(SeqNumber(5)), 0);
+        assert_eq!(
+            SeqNumber(2).saturating_sub(SeqNumber(SeqNumber::MAX - 3)),
+            5
+        );
+        assert_eq!(
+            SeqNumber(SeqNumber::MAX - 3).saturating_sub(SeqNumber(2)),
+            0
+        );
     }
 }
@@ -1,12 +1,15 @@
+#[cfg(feature = "std")]
 use rand::distributions::{Distribution, Standard};
+#[cfg(feature = "std")]
 use rand::Rng;
 
-use std::fmt;
+use core::fmt;
 
 /// A newtype wrapper for strongly-typed SocketIDs
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct SocketId(pub u32);
 
+#[cfg(feature = "std")]
 impl Distribution<SocketId> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> SocketId {
         SocketId(rng.sample(self))
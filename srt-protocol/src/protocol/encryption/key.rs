@@ -55,7 +55,8 @@ impl Salt {
             out[i + 10] ^= *b;
         }
 
-        // TODO: the ref impl doesn't put ctr in here....
+        // ctr is left at zero, as in the reference implementation's hcrypt_SetCtrIV, it's counted
+        // up block by block by the cipher
         // https://github.com/Haivision/srt/blob/9f7068d4f45eb3276e30fcc6e920f82b387c6852/haicrypt/hcrypt.h#L136-L136
 
         StreamInitializationVector(out)
@@ -322,6 +323,44 @@ mod test {
         assert_ne!(Salt::new_random(), Salt::new_random());
     }
 
+    #[test]
+    fn kek_generate_key_sizes() {
+        // the reference implementation derives a KEK as long as the SEK with PBKDF2-HMAC-SHA1,
+        // 2048 rounds, over the last 8 bytes of the salt, so the shorter keys are prefixes
+        let salt =
+            Salt::try_from(&hex::decode(b"7D59759C2B1A3F0B06C7028790C81C7D").unwrap()[..]).unwrap();
+        let kek = |key_size| {
+            let key_settings = KeySettings {
+                key_size,
+                passphrase: "password123".into(),
+            };
+            KeyEncryptionKey::new(&key_settings, &salt)
+        };
+
+        assert_eq!(
+            kek(KeySize::AES192).as_bytes(),
+            &hex::decode(b"08F2758F41E4244D00057C9CEBEB95FC4C44DC5254FA3996").unwrap()[..]
+        );
+        assert_eq!(
+            kek(KeySize::AES256).as_bytes(),
+            &hex::decode(b"08F2758F41E4244D00057C9CEBEB95FC4C44DC5254FA39963126DE2B6C6EBF94")
+                .unwrap()[..]
+        );
+        assert_eq!(kek(KeySize::Unspecified), kek(KeySize::AES128));
+
+        // only the last 8 bytes of the salt are used
+        let other_salt =
+            Salt::try_from(&hex::decode(b"000000000000000006C7028790C81C7D").unwrap()[..]).unwrap();
+        let key_settings = KeySettings {
+            key_size: KeySize::AES128,
+            passphrase: "password123".into(),
+        };
+        assert_eq!(
+            KeyEncryptionKey::new(&key_settings, &other_salt),
+            kek(KeySize::AES128)
+        );
+    }
+
     #[test]
     fn generate_iv() {
         // example from the reference implementation
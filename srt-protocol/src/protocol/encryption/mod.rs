@@ -58,6 +58,12 @@ impl Decryption {
         *stream_keys = StreamEncryptionKeys::unwrap_from(key_settings, &keying_material)?;
         Ok(Some(keying_material))
     }
+    /// Unwraps the keys the peer sends from now on with `passphrase`
+    pub fn set_passphrase(&mut self, passphrase: Passphrase) {
+        if let Some((_, key_settings)) = &mut self.0 {
+            key_settings.passphrase = passphrase;
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -115,15 +121,14 @@ impl EncryptionState {
 
     fn try_switch_stream_keys(&mut self) {
         use DataEncryption::*;
-        if self.packets_until_key_switch == 0 {
+        // if the peer didn't confirm the new keys in time, switch as soon as it does
+        if self.packets_until_key_switch == 0 && self.last_key_material.is_none() {
             self.packets_until_key_switch = self.key_refresh.period();
-            if self.last_key_material.is_none() {
-                self.active_sek = match self.active_sek {
-                    Even => Odd,
-                    Odd => Even,
-                    None => None,
-                };
-            }
+            self.active_sek = match self.active_sek {
+                Even => Odd,
+                Odd => Even,
+                None => None,
+            };
         }
     }
 }
@@ -157,7 +162,7 @@ impl Encryption {
                 let km = this.try_send_key_material();
 
                 this.packets_until_pre_announcement -= 1;
-                this.packets_until_key_switch -= 1;
+                this.packets_until_key_switch = this.packets_until_key_switch.saturating_sub(1);
 
                 Some((bytes, packet, km))
             }
@@ -165,6 +170,35 @@ impl Encryption {
         }
     }
 
+    /// Undoes the encryption of a packet that was sent, e.g. to send it again with other keys
+    pub fn decrypt(&self, mut packet: DataPacket) -> Option<DataPacket> {
+        if let Some(this) = &self.0 {
+            let mut data = BytesMut::with_capacity(packet.payload.len());
+            data.extend_from_slice(&packet.payload[..]);
+            let _ = this
+                .stream_keys
+                .decrypt(packet.encryption, packet.seq_number, &mut data)?;
+            packet.encryption = DataEncryption::None;
+            packet.payload = data.freeze();
+        }
+        Some(packet)
+    }
+
+    /// Starts a key refresh with keys wrapped with `passphrase`. The key in use stays in use until
+    /// the peer confirms it unwrapped the new keys, which it can't do before it has the new
+    /// passphrase too, so the keys are sent again until it does.
+    pub fn set_passphrase(&mut self, passphrase: Passphrase) {
+        if let Some(this) = &mut self.0 {
+            this.key_settings.passphrase = passphrase;
+            // keys still waiting for the peer are wrapped again, otherwise new ones are commissioned
+            if this.last_key_material.is_some() {
+                this.last_key_material = this.stream_keys.wrap_with(&this.key_settings);
+            }
+            this.packets_until_pre_announcement = 0;
+            this.packets_until_key_switch = this.key_refresh.pre_announcement_period();
+        }
+    }
+
     pub fn handle_key_refresh_response(
         &mut self,
         keying_material: KeyingMaterialMessage,
@@ -330,6 +364,69 @@ mod tests {
         assert_eq!(decrypted_packet, original_packet);
     }
 
+    #[test]
+    fn set_passphrase() {
+        let settings = CipherSettings {
+            key_refresh: KeyMaterialRefreshSettings::new(3_000, 1_000).unwrap(),
+            ..new_settings()
+        };
+        let mut encryption = Encryption::new(Some(settings.clone()));
+        let mut decryption = Decryption::new(Some(settings.clone()));
+        let original_packet = data_packet(DataEncryption::None, "test set_passphrase");
+        let encrypt =
+            |encryption: &mut Encryption| encryption.encrypt(original_packet.clone()).unwrap();
+
+        let (_, old_key_packet, km) = encrypt(&mut encryption);
+        assert_eq!(km, None);
+
+        // the refresh starts right away, with the new passphrase
+        let passphrase = Passphrase::from("new passphrase");
+        encryption.set_passphrase(passphrase.clone());
+        let (_, packet, km) = encrypt(&mut encryption);
+        assert_eq!(packet.encryption, DataEncryption::Even);
+        let key_material = km.unwrap();
+        assert_matches::assert_matches!(
+            decryption.refresh_key_material(key_material.clone()),
+            Err(_)
+        );
+
+        // the peer can't confirm the keys yet, the key in use doesn't change
+        let pre_announcement_period = settings.key_refresh.pre_announcement_period();
+        let mut key_material = None;
+        for _ in 0..=pre_announcement_period {
+            let (_, packet, km) = encrypt(&mut encryption);
+            assert_eq!(packet.encryption, DataEncryption::Even);
+            key_material = km.or(key_material);
+        }
+
+        // and the keys are sent again until it can
+        decryption.set_passphrase(passphrase);
+        let key_material = key_material.unwrap();
+        assert_eq!(
+            decryption.refresh_key_material(key_material.clone()),
+            Ok(Some(key_material.clone()))
+        );
+        assert_eq!(encryption.handle_key_refresh_response(key_material), Ok(()));
+
+        let mut switch = (0..settings.key_refresh.period()).map(|_| {
+            let (_, packet, km) = encrypt(&mut encryption);
+            if let Some(km) = km {
+                let response = decryption.refresh_key_material(km).unwrap().unwrap();
+                encryption.handle_key_refresh_response(response).unwrap();
+            }
+            packet
+        });
+        let new_key_packet = switch
+            .find(|packet| packet.encryption == DataEncryption::Odd)
+            .unwrap();
+
+        // data sent with either key can be decrypted
+        for packet in [old_key_packet, packet, new_key_packet] {
+            let (_, decrypted_packet) = decryption.decrypt(packet).unwrap();
+            assert_eq!(decrypted_packet, original_packet);
+        }
+    }
+
     #[test]
     fn retry_refresh_key_material() {
         let settings = CipherSettings {
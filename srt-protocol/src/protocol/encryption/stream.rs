@@ -3,9 +3,10 @@ use std::fmt::Debug;
 use aes::cipher::StreamCipher;
 use cipher::KeyIvInit;
 
-type Aes128Ctr = ctr::Ctr64BE<aes::Aes128>;
-type Aes192Ctr = ctr::Ctr64BE<aes::Aes192>;
-type Aes256Ctr = ctr::Ctr64BE<aes::Aes256>;
+// the whole IV is the counter, like the reference implementation's AES-CTR
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+type Aes192Ctr = ctr::Ctr128BE<aes::Aes192>;
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
 
 use crate::{
     packet::*,
@@ -21,6 +22,7 @@ pub enum KeyMaterialError {
     InvalidKeyFlags(KeyFlags, KeySize, usize),
     InvalidInitializationVector(WrapInitializationVector),
     InvalidRefreshResponse(KeyingMaterialMessage),
+    UnsupportedCipher(CipherType),
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -78,7 +80,20 @@ impl StreamEncryptionKeys {
     ) -> Result<Self, KeyMaterialError> {
         use KeyMaterialError::*;
         // TODO: revisit errors, KeyingMaterialMessage has a lot of fields that ought be validated
+        if key_material.cipher != CipherType::Ctr {
+            return Err(UnsupportedCipher(key_material.cipher));
+        }
         let salt = Salt::try_from(key_material.salt.as_slice()).map_err(|_| InvalidSaltLength)?;
+
+        // without a configured key size, adopt the one the keys were wrapped with, same as the
+        // reference implementation does for SRTO_PBKEYLEN = 0
+        let key_settings = &match key_settings.key_size {
+            KeySize::Unspecified => KeySettings {
+                key_size: Self::wrapped_key_size(key_material),
+                passphrase: key_settings.passphrase.clone(),
+            },
+            _ => key_settings.clone(),
+        };
         let kek = KeyEncryptionKey::new(key_settings, &salt);
 
         if key_material.key_flags.bits().count_ones() as usize * key_settings.key_size.as_usize()
@@ -117,6 +132,21 @@ impl StreamEncryptionKeys {
         })
     }
 
+    /// The size of the stream encrypting keys, if there are any
+    pub fn key_size(&self) -> Option<KeySize> {
+        let key = self.even_key.as_ref().or(self.odd_key.as_ref())?;
+        KeySize::try_from(key.len() as u16).ok()
+    }
+
+    fn wrapped_key_size(key_material: &KeyingMaterialMessage) -> KeySize {
+        let key_count = key_material.key_flags.bits().count_ones() as usize;
+        let keys_len = key_material.wrapped_keys.len().saturating_sub(8);
+        match key_count {
+            0 => KeySize::Unspecified,
+            _ => KeySize::try_from((keys_len / key_count) as u16).unwrap_or(KeySize::Unspecified),
+        }
+    }
+
     pub fn wrap_with(&self, key_settings: &KeySettings) -> Option<KeyingMaterialMessage> {
         let kek = KeyEncryptionKey::new(key_settings, &self.salt);
 
@@ -301,4 +331,76 @@ mod test {
             Some(keying_material)
         );
     }
+
+    #[test]
+    fn reference_ciphertext() {
+        // AES-CTR as the reference implementation does it, with OpenSSL:
+        // openssl enc -aes-128-ctr -K 000102030405060708090a0b0c0d0e0f \
+        //     -iv 87647f8a2361fb1a9e6907af1b810000
+        let plaintext = b"SRT payloads are encrypted with AES-CTR, one IV per packet.";
+        let salt =
+            Salt::try_from(&hex::decode("87647f8a2361fb1a9e692de576985949").unwrap()).unwrap();
+        let seq_number = SeqNumber(709520665);
+        let key = hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f")
+            .unwrap();
+        let expected = [
+            "d3d3aeb27d64dbd898a8b54bfe3f3f84b4cfd78203aa6e8d3d23ded5e1179fd9567988af86de24df91f70e9a6bd1bc4c6565129a64b92b9e9e644e",
+            "f7da330095487d3bdef1b510691edf73355d9fd41dbb704bab3f1b6b03b7b0e5927519fdde1cb45775cd8f17b090aa749c1d21d82a126fcf4dde93",
+            "6f4cd0761278bd27267d97e8b2e011731e9b3d88b9d683afcff4dc8e3e3afc4d137fa28b6da5b4b07ac7ec8dae7a5b8002710f441c346f5b6f86c7",
+        ];
+
+        for (key_size, expected) in [16, 24, 32].into_iter().zip(expected) {
+            let keys = StreamEncryptionKeys {
+                salt: salt.clone(),
+                even_key: None,
+                odd_key: EncryptionKey::try_from(&key[..key_size]).ok(),
+            };
+            let mut data = plaintext.to_vec();
+            assert_eq!(
+                keys.encrypt(DataEncryption::Odd, seq_number, &mut data),
+                Some(plaintext.len())
+            );
+            assert_eq!(hex::encode(&data), expected, "AES-{}", key_size * 8);
+
+            keys.decrypt(DataEncryption::Odd, seq_number, &mut data);
+            assert_eq!(&data[..], &plaintext[..]);
+        }
+    }
+
+    #[test]
+    fn unsupported_cipher() {
+        let mut keying_material = StreamEncryptionKeys::new_random(KeySize::AES128)
+            .wrap_with(&key_settings())
+            .unwrap();
+        keying_material.cipher = CipherType::Cbc;
+        assert_eq!(
+            StreamEncryptionKeys::unwrap_from(&key_settings(), &keying_material),
+            Err(KeyMaterialError::UnsupportedCipher(CipherType::Cbc))
+        );
+    }
+
+    #[test]
+    fn unwrap_adopts_key_size() {
+        let aes256 = KeySettings {
+            key_size: KeySize::AES256,
+            passphrase: "password123".into(),
+        };
+        let unspecified = KeySettings {
+            key_size: KeySize::Unspecified,
+            passphrase: "password123".into(),
+        };
+        let stream_encryption = StreamEncryptionKeys::new_random(KeySize::AES256);
+        let keying_material = stream_encryption.wrap_with(&aes256).unwrap();
+
+        let unwrapped = StreamEncryptionKeys::unwrap_from(&unspecified, &keying_material).unwrap();
+        assert_eq!(unwrapped, stream_encryption);
+        assert_eq!(unwrapped.key_size(), Some(KeySize::AES256));
+
+        // a configured key size still has to match
+        let aes128 = key_settings();
+        assert_matches!(
+            StreamEncryptionKeys::unwrap_from(&aes128, &keying_material),
+            Err(KeyMaterialError::InvalidKeyFlags(_, KeySize::AES128, 72))
+        );
+    }
 }
@@ -0,0 +1,755 @@
+//! SRT's built-in `fec` packet filter, XOR forward error correction over rows and columns of
+//! packets, e.g. `fec,cols:10,rows:5,layout:staircase,arq:onreq`.
+//!
+//! Packets are laid out in a matrix `cols` packets wide and `rows` packets high. A row FEC packet
+//! is sent after each row, and with more than one row a column FEC packet after each column, so
+//! any single packet lost in a row or a column can be rebuilt, and with both, many combinations of
+//! several lost packets. The layout matches the reference implementation: FEC packets take the
+//! sequence number of the last packet in their group and the reserved message number 0, the
+//! timestamp is the XOR of the group's timestamps and the payload is laid out as
+//!
+//! ```text
+//! | index (i8) | flags clip (u8) | length clip (u16) | payload clip ...
+//! ```
+//!
+//! where the index is -1 for rows and the column otherwise, and the clips are the XOR of the
+//! encryption flags, payload lengths and zero padded payloads of the group.
+//!
+//! The message number and location of a packet aren't covered, rebuilt packets are always single
+//! packet messages, so FEC is only meant for live streams.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+};
+
+use bytes::{Buf, BufMut, Bytes};
+use thiserror::Error;
+
+use crate::packet::*;
+
+use super::{FilteredPackets, PacketFilter};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FecLayout {
+    /// Column groups start on the first row
+    Even,
+    /// Each column group starts one row below the previous one, so the column FEC packets are
+    /// spread out instead of all being sent at the end of the matrix
+    Staircase,
+}
+
+/// How FEC is combined with retransmissions
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ArqLevel {
+    /// Lost packets are reported as soon as they are noticed, as without FEC
+    Always,
+    /// Only the lost packets FEC wasn't able to rebuild are reported
+    OnRequest,
+    /// Lost packets are never reported
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FecConfig {
+    /// The number of packets in a row
+    pub cols: u32,
+    /// The number of packets in a column, 1 for row FEC only
+    pub rows: u32,
+    /// Whether row FEC packets are sent, turned off by a negative number of rows
+    pub row_fec: bool,
+    pub layout: FecLayout,
+    pub arq: ArqLevel,
+}
+
+#[derive(Clone, Debug, Error, Eq, PartialEq)]
+pub enum FecConfigError {
+    #[error("FEC config is missing the number of columns")]
+    MissingColumns,
+    #[error("Invalid FEC config value {0}:{1}")]
+    InvalidValue(String, String),
+    #[error("Unknown FEC config parameter {0}")]
+    UnknownParameter(String),
+}
+
+impl FecConfig {
+    /// The extra bytes in front of the payload clip of a FEC packet
+    pub const HEADER_SIZE: usize = 4;
+
+    /// Reads the FEC config from a packet filter config, None if it's for another filter
+    pub fn from_spec(spec: &FilterSpec) -> Result<Option<FecConfig>, FecConfigError> {
+        use FecConfigError::*;
+        if spec.filter_type() != Some("fec") {
+            return Ok(None);
+        }
+
+        let mut config = FecConfig {
+            cols: 0,
+            rows: 1,
+            row_fec: true,
+            layout: FecLayout::Staircase,
+            arq: ArqLevel::OnRequest,
+        };
+        for (key, value) in &spec.0 {
+            let invalid = || InvalidValue(key.clone(), value.clone());
+            match key.as_str() {
+                "fec" => {}
+                "cols" => {
+                    config.cols = value.parse().ok().filter(|c| *c > 0).ok_or_else(invalid)?;
+                }
+                "rows" => {
+                    let rows: i32 = value.parse().ok().filter(|r| *r != 0).ok_or_else(invalid)?;
+                    config.rows = rows.unsigned_abs();
+                    config.row_fec = rows > 0;
+                }
+                "layout" => {
+                    config.layout = match value.as_str() {
+                        "even" => FecLayout::Even,
+                        "staircase" => FecLayout::Staircase,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "arq" => {
+                    config.arq = match value.as_str() {
+                        "always" => ArqLevel::Always,
+                        "onreq" => ArqLevel::OnRequest,
+                        "never" => ArqLevel::Never,
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(UnknownParameter(key.clone())),
+            }
+        }
+
+        if config.cols == 0 {
+            return Err(MissingColumns);
+        }
+        // the column index of column FEC packets is a signed byte
+        if config.has_columns() && config.cols > i8::MAX as u32 {
+            return Err(InvalidValue("cols".into(), config.cols.to_string()));
+        }
+        // no rows and a single column leaves nothing to send
+        if !config.row_fec && !config.has_columns() {
+            return Err(InvalidValue("rows".into(), "-1".into()));
+        }
+        Ok(Some(config))
+    }
+
+    fn has_columns(&self) -> bool {
+        self.rows > 1
+    }
+
+    fn cols(&self) -> u64 {
+        self.cols.into()
+    }
+
+    fn rows(&self) -> u64 {
+        self.rows.into()
+    }
+
+    fn row_group(&self, position: u64) -> Option<Group> {
+        self.row_fec.then(|| Group::Row(position / self.cols()))
+    }
+
+    /// The column group a packet belongs to, and its index in the group
+    fn column_group(&self, position: u64) -> Option<(Group, u64)> {
+        if !self.has_columns() {
+            return None;
+        }
+        let column = position % self.cols();
+        // with the staircase layout, the first few packets of a column aren't in any group
+        let offset = position.checked_sub(self.column_start(column))?;
+        let matrix = self.rows() * self.cols();
+        let group = Group::Column(column, offset / matrix);
+        Some((group, offset % matrix / self.cols()))
+    }
+
+    fn column_start(&self, column: u64) -> u64 {
+        match self.layout {
+            FecLayout::Even => column,
+            FecLayout::Staircase => column + column % self.rows() * self.cols(),
+        }
+    }
+
+    fn groups(&self, position: u64) -> impl Iterator<Item = Group> {
+        let column = self.column_group(position).map(|(group, _)| group);
+        self.row_group(position).into_iter().chain(column)
+    }
+
+    fn members(&self, group: Group) -> Vec<u64> {
+        match group {
+            Group::Row(row) => (row * self.cols()..(row + 1) * self.cols()).collect(),
+            Group::Column(column, index) => {
+                let first = self.column_start(column) + index * self.rows() * self.cols();
+                (0..self.rows()).map(|i| first + i * self.cols()).collect()
+            }
+        }
+    }
+
+    /// How far behind the latest packet a lost one can still be rebuilt
+    fn window(&self) -> u64 {
+        (self.rows() + 1) * self.cols()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Group {
+    Row(u64),
+    /// The column, and the group's index in it
+    Column(u64, u64),
+}
+
+/// The XOR of the packets in a group
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Clip {
+    flags: u8,
+    length: u16,
+    timestamp: u32,
+    payload: Vec<u8>,
+}
+
+impl Clip {
+    fn add(&mut self, packet: &DataPacket) {
+        self.flags ^= encryption_flags(packet.encryption);
+        self.length ^= packet.payload.len() as u16;
+        self.timestamp ^= packet.timestamp.as_micros();
+        if self.payload.len() < packet.payload.len() {
+            self.payload.resize(packet.payload.len(), 0);
+        }
+        for (clip, byte) in self.payload.iter_mut().zip(&packet.payload[..]) {
+            *clip ^= byte;
+        }
+    }
+
+    fn into_packet(self, index: i8, last: &DataPacket) -> DataPacket {
+        let mut payload = Vec::with_capacity(FecConfig::HEADER_SIZE + self.payload.len());
+        payload.put_i8(index);
+        payload.put_u8(self.flags);
+        payload.put_u16(self.length);
+        payload.extend_from_slice(&self.payload);
+
+        DataPacket {
+            seq_number: last.seq_number,
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber(0),
+            timestamp: TimeStamp::from_micros(self.timestamp),
+            dest_sockid: last.dest_sockid,
+            payload: payload.into(),
+        }
+    }
+
+    /// The column index, -1 for rows, and the clip carried by a FEC packet
+    fn from_packet(packet: &DataPacket) -> Option<(i8, Clip)> {
+        let mut payload = &packet.payload[..];
+        if payload.len() < FecConfig::HEADER_SIZE {
+            return None;
+        }
+        let index = payload.get_i8();
+        let clip = Clip {
+            flags: payload.get_u8(),
+            length: payload.get_u16(),
+            timestamp: packet.timestamp.as_micros(),
+            payload: payload.to_vec(),
+        };
+        Some((index, clip))
+    }
+
+    /// Once every other packet of the group has been added, what's left is the missing one
+    fn rebuild(mut self, seq_number: SeqNumber, dest_sockid: SocketId) -> Option<DataPacket> {
+        let encryption = match self.flags {
+            0 => DataEncryption::None,
+            1 => DataEncryption::Even,
+            2 => DataEncryption::Odd,
+            _ => return None,
+        };
+        let length = usize::from(self.length);
+        if self.payload.len() < length {
+            return None;
+        }
+        self.payload.truncate(length);
+
+        Some(DataPacket {
+            seq_number,
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption,
+            retransmitted: false,
+            message_number: MsgNumber(1),
+            timestamp: TimeStamp::from_micros(self.timestamp),
+            dest_sockid,
+            payload: Bytes::from(self.payload),
+        })
+    }
+}
+
+fn encryption_flags(encryption: DataEncryption) -> u8 {
+    match encryption {
+        DataEncryption::None => 0,
+        DataEncryption::Even => 1,
+        DataEncryption::Odd => 2,
+    }
+}
+
+/// Maps sequence numbers to positions counted from the initial sequence number, which keep
+/// growing across the sequence number wrap
+#[derive(Debug)]
+struct Positions {
+    latest: (SeqNumber, u64),
+}
+
+impl Positions {
+    fn new(init_seq_num: SeqNumber) -> Self {
+        Self {
+            latest: (init_seq_num, 0),
+        }
+    }
+
+    fn position(&self, seq_number: SeqNumber) -> Option<u64> {
+        let (latest, position) = self.latest;
+        if seq_number >= latest {
+            Some(position + u64::from(seq_number - latest))
+        } else {
+            position.checked_sub(u64::from(latest - seq_number))
+        }
+    }
+
+    fn seq_number(&self, position: u64) -> SeqNumber {
+        let (latest, latest_position) = self.latest;
+        let distance = position.abs_diff(latest_position) as u32;
+        if position >= latest_position {
+            latest + distance
+        } else {
+            latest - distance
+        }
+    }
+
+    fn update(&mut self, seq_number: SeqNumber, position: u64) {
+        if position > self.latest.1 {
+            self.latest = (seq_number, position);
+        }
+    }
+
+    fn latest(&self) -> u64 {
+        self.latest.1
+    }
+}
+
+#[derive(Debug)]
+struct FecSender {
+    positions: Positions,
+    row: (u64, Clip),
+    columns: HashMap<u64, (Group, Clip)>,
+}
+
+#[derive(Debug)]
+struct FecReceiver {
+    positions: Positions,
+    /// Packets received or rebuilt in the window
+    packets: BTreeMap<u64, DataPacket>,
+    clips: HashMap<Group, Clip>,
+    /// Every packet before this one is out of the window
+    window_start: u64,
+    dest_sockid: Option<SocketId>,
+}
+
+#[derive(Debug)]
+pub struct FecFilter {
+    config: FecConfig,
+    sender: FecSender,
+    receiver: FecReceiver,
+}
+
+impl FecFilter {
+    pub fn new(config: FecConfig, init_seq_num: SeqNumber) -> Self {
+        Self {
+            config,
+            sender: FecSender {
+                positions: Positions::new(init_seq_num),
+                row: (0, Clip::default()),
+                columns: HashMap::new(),
+            },
+            receiver: FecReceiver {
+                positions: Positions::new(init_seq_num),
+                packets: BTreeMap::new(),
+                clips: HashMap::new(),
+                window_start: 0,
+                dest_sockid: None,
+            },
+        }
+    }
+
+    fn receive_control(&mut self, position: u64, index: i8, clip: Clip) -> Vec<DataPacket> {
+        let config = &self.config;
+        let group = match index {
+            -1 => config.row_group(position),
+            column if u64::try_from(column).ok() == Some(position % config.cols()) => {
+                config.column_group(position).map(|(group, _)| group)
+            }
+            _ => None,
+        };
+        match group {
+            Some(group) => {
+                self.receiver.clips.entry(group).or_insert(clip);
+                self.rebuild([group].into())
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Rebuilds the single missing packet of each group, which may leave a single packet
+    /// missing in another group
+    fn rebuild(&mut self, mut groups: Vec<Group>) -> Vec<DataPacket> {
+        let receiver = &mut self.receiver;
+        let mut rebuilt = Vec::new();
+        while let Some(group) = groups.pop() {
+            if !receiver.clips.contains_key(&group) {
+                continue;
+            }
+            let members = self.config.members(group);
+            // the packets out of the window are gone, so there's no telling what's missing
+            if members[0] < receiver.window_start {
+                receiver.clips.remove(&group);
+                continue;
+            }
+            let mut missing = members
+                .iter()
+                .filter(|position| !receiver.packets.contains_key(position));
+            let (missing, None) = (missing.next(), missing.next()) else {
+                continue;
+            };
+
+            let mut clip = receiver.clips.remove(&group).unwrap();
+            let (Some(&position), Some(dest_sockid)) = (missing, receiver.dest_sockid) else {
+                continue;
+            };
+            for member in &members {
+                if let Some(packet) = receiver.packets.get(member) {
+                    clip.add(packet);
+                }
+            }
+            let seq_number = receiver.positions.seq_number(position);
+            if let Some(packet) = clip.rebuild(seq_number, dest_sockid) {
+                receiver.packets.insert(position, packet.clone());
+                rebuilt.push(packet);
+                groups.extend(self.config.groups(position));
+            }
+        }
+        rebuilt
+    }
+
+    /// Moves the window along, returns the packets that went out of it without being received
+    fn slide_window(&mut self) -> Vec<SeqNumber> {
+        let receiver = &mut self.receiver;
+        let window_start = receiver
+            .positions
+            .latest()
+            .saturating_sub(self.config.window());
+        let mut lost = Vec::new();
+        for position in receiver.window_start..window_start {
+            if receiver.packets.remove(&position).is_none() {
+                lost.push(receiver.positions.seq_number(position));
+            }
+        }
+        if window_start > receiver.window_start {
+            receiver.window_start = window_start;
+            let config = &self.config;
+            receiver
+                .clips
+                .retain(|group, _| config.members(*group)[0] >= window_start);
+        }
+        lost
+    }
+}
+
+impl PacketFilter for FecFilter {
+    fn on_send(&mut self, packet: &DataPacket) -> Vec<DataPacket> {
+        let config = &self.config;
+        let sender = &mut self.sender;
+        let Some(position) = sender.positions.position(packet.seq_number) else {
+            return Vec::new();
+        };
+        sender.positions.update(packet.seq_number, position);
+
+        let mut control = Vec::new();
+        if config.row_fec {
+            // start over if some packets were never sent, e.g. dropped for being too late
+            let row = position / config.cols();
+            if sender.row.0 != row {
+                sender.row = (row, Clip::default());
+            }
+            sender.row.1.add(packet);
+            if position % config.cols() == config.cols() - 1 {
+                let clip = mem::take(&mut sender.row.1);
+                control.push(clip.into_packet(-1, packet));
+            }
+        }
+        if let Some((group, index)) = config.column_group(position) {
+            let column = position % config.cols();
+            let (current, clip) = sender
+                .columns
+                .entry(column)
+                .or_insert_with(|| (group, Clip::default()));
+            if *current != group {
+                *current = group;
+                *clip = Clip::default();
+            }
+            clip.add(packet);
+            if index == config.rows() - 1 {
+                let (_, clip) = sender.columns.remove(&column).unwrap();
+                control.push(clip.into_packet(column as i8, packet));
+            }
+        }
+        control
+    }
+
+    fn on_receive(&mut self, packet: DataPacket) -> FilteredPackets {
+        let receiver = &mut self.receiver;
+        let Some(position) = receiver
+            .positions
+            .position(packet.seq_number)
+            .filter(|position| *position >= receiver.window_start)
+        else {
+            // too old to be of any use
+            return match packet.message_number {
+                MsgNumber(0) => FilteredPackets::default(),
+                _ => FilteredPackets {
+                    packet: Some(packet),
+                    ..Default::default()
+                },
+            };
+        };
+        receiver.positions.update(packet.seq_number, position);
+        receiver.dest_sockid = Some(packet.dest_sockid);
+
+        let mut filtered = FilteredPackets::default();
+        if packet.message_number == MsgNumber(0) {
+            if let Some((index, clip)) = Clip::from_packet(&packet) {
+                filtered.rebuilt = self.receive_control(position, index, clip);
+            }
+        } else {
+            receiver
+                .packets
+                .entry(position)
+                .or_insert_with(|| packet.clone());
+            filtered.packet = Some(packet);
+            filtered.rebuilt = self.rebuild(self.config.groups(position).collect());
+        }
+
+        let lost = self.slide_window();
+        if self.config.arq == ArqLevel::OnRequest {
+            filtered.lost = lost;
+        }
+        filtered
+    }
+
+    fn report_losses(&self) -> bool {
+        self.config.arq == ArqLevel::Always
+    }
+
+    fn extra_size(&self) -> usize {
+        FecConfig::HEADER_SIZE
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(spec: &str) -> FecConfig {
+        FecConfig::from_spec(&spec.parse().unwrap())
+            .unwrap()
+            .unwrap()
+    }
+
+    fn data_packet(seq_number: u32) -> DataPacket {
+        DataPacket {
+            seq_number: SeqNumber(seq_number),
+            message_loc: PacketLocation::ONLY,
+            in_order_delivery: false,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            message_number: MsgNumber(1),
+            timestamp: TimeStamp::from_micros(seq_number * 1_000),
+            dest_sockid: SocketId(7),
+            payload: vec![seq_number as u8; seq_number as usize % 5 + 1].into(),
+        }
+    }
+
+    // sends the packets, loses some of them, and returns everything the receiver ended up with
+    fn transfer(
+        config: FecConfig,
+        count: u32,
+        lost: &[u32],
+    ) -> (Vec<DataPacket>, Vec<SeqNumber>, usize) {
+        let mut sender = FecFilter::new(config, SeqNumber(0));
+        let mut receiver = FecFilter::new(config, SeqNumber(0));
+        let mut received = Vec::new();
+        let mut unrecovered = Vec::new();
+        let mut control_packets = 0;
+        for packet in (0..count).map(data_packet) {
+            let control = sender.on_send(&packet);
+            control_packets += control.len();
+            for packet in [packet].into_iter().chain(control) {
+                if lost.contains(&packet.seq_number.0) && packet.message_number != MsgNumber(0) {
+                    continue;
+                }
+                let filtered = receiver.on_receive(packet);
+                received.extend(filtered.packet);
+                received.extend(filtered.rebuilt);
+                unrecovered.extend(filtered.lost);
+            }
+        }
+        received.sort_by_key(|p| p.seq_number);
+        (received, unrecovered, control_packets)
+    }
+
+    #[test]
+    fn parse_config() {
+        assert_eq!(
+            config("fec,cols:10"),
+            FecConfig {
+                cols: 10,
+                rows: 1,
+                row_fec: true,
+                layout: FecLayout::Staircase,
+                arq: ArqLevel::OnRequest,
+            }
+        );
+        assert_eq!(
+            config("fec,cols:4,rows:-3,layout:even,arq:never"),
+            FecConfig {
+                cols: 4,
+                rows: 3,
+                row_fec: false,
+                layout: FecLayout::Even,
+                arq: ArqLevel::Never,
+            }
+        );
+
+        let parse = |spec: &str| FecConfig::from_spec(&spec.parse().unwrap());
+        assert_eq!(parse("other,cols:4"), Ok(None));
+        use FecConfigError::*;
+        assert_eq!(parse("fec,rows:4"), Err(MissingColumns));
+        assert_eq!(
+            parse("fec,cols:0"),
+            Err(InvalidValue("cols".into(), "0".into()))
+        );
+        assert_eq!(
+            parse("fec,cols:200,rows:2"),
+            Err(InvalidValue("cols".into(), "200".into()))
+        );
+        assert_eq!(
+            parse("fec,cols:4,rows:-1"),
+            Err(InvalidValue("rows".into(), "-1".into()))
+        );
+        assert_eq!(
+            parse("fec,cols:4,arq:sometimes"),
+            Err(InvalidValue("arq".into(), "sometimes".into()))
+        );
+        assert_eq!(
+            parse("fec,cols:4,depth:2"),
+            Err(UnknownParameter("depth".into()))
+        );
+    }
+
+    #[test]
+    fn staircase_columns() {
+        let config = config("fec,cols:3,rows:3");
+        // each column starts a row lower than the previous one, wrapping around after 3 rows
+        let members = |column| config.members(Group::Column(column, 0));
+        assert_eq!(members(0), [0, 3, 6]);
+        assert_eq!(members(1), [4, 7, 10]);
+        assert_eq!(members(2), [8, 11, 14]);
+        assert_eq!(config.column_group(1), None);
+        assert_eq!(config.column_group(7), Some((Group::Column(1, 0), 1)));
+        assert_eq!(config.column_group(13), Some((Group::Column(1, 1), 0)));
+
+        let config = FecConfig {
+            layout: FecLayout::Even,
+            ..config
+        };
+        assert_eq!(config.members(Group::Column(1, 1)), [10, 13, 16]);
+    }
+
+    #[test]
+    fn control_packet_layout() {
+        let mut filter = FecFilter::new(config("fec,cols:2"), SeqNumber(0));
+        assert!(filter.on_send(&data_packet(0)).is_empty());
+        let control = filter.on_send(&data_packet(1));
+
+        // lengths 1 and 2, timestamps 0 and 1000
+        let [control] = &control[..] else {
+            panic!("{control:?}");
+        };
+        assert_eq!(control.seq_number, SeqNumber(1));
+        assert_eq!(control.message_number, MsgNumber(0));
+        assert_eq!(control.timestamp, TimeStamp::from_micros(1_000));
+        assert_eq!(&control.payload[..], [0xff, 0, 0, 3, 1, 1]);
+    }
+
+    #[test]
+    fn rebuild_row() {
+        let (received, lost, control) = transfer(config("fec,cols:5"), 20, &[2, 13]);
+        assert_eq!(control, 4);
+        assert_eq!(received, (0..20).map(data_packet).collect::<Vec<_>>());
+        assert_eq!(lost, []);
+
+        // two in the same row are too many
+        let (received, lost, _) = transfer(config("fec,cols:5"), 20, &[6, 7]);
+        assert_eq!(received.len(), 18);
+        assert_eq!(lost, [SeqNumber(6), SeqNumber(7)]);
+    }
+
+    #[test]
+    fn rebuild_rows_and_columns() {
+        // 2 lost in a row and 2 lost in a column, each rebuilt packet unblocks another group
+        for layout in ["even", "staircase"] {
+            let config = config(&format!("fec,cols:4,rows:4,layout:{layout}"));
+            let (received, lost, _) = transfer(config, 64, &[20, 21, 25, 29]);
+            assert_eq!(
+                received,
+                (0..64).map(data_packet).collect::<Vec<_>>(),
+                "{layout}"
+            );
+            assert_eq!(lost, [], "{layout}");
+        }
+    }
+
+    #[test]
+    fn arq_level() {
+        let (_, lost, _) = transfer(config("fec,cols:5,arq:never"), 20, &[6, 7]);
+        assert_eq!(lost, []);
+        let (_, lost, _) = transfer(config("fec,cols:5,arq:always"), 20, &[6, 7]);
+        assert_eq!(lost, []);
+        assert!(FecFilter::new(config("fec,cols:5,arq:always"), SeqNumber(0)).report_losses());
+        assert!(!FecFilter::new(config("fec,cols:5"), SeqNumber(0)).report_losses());
+    }
+
+    #[test]
+    fn sequence_number_wrap() {
+        let init_seq_num = SeqNumber(SeqNumber::MAX - 3);
+        let mut sender = FecFilter::new(config("fec,cols:4"), init_seq_num);
+        let mut receiver = FecFilter::new(config("fec,cols:4"), init_seq_num);
+        let packets: Vec<_> = (0..8)
+            .map(|i| DataPacket {
+                seq_number: init_seq_num + i,
+                ..data_packet(i)
+            })
+            .collect();
+
+        let mut rebuilt = Vec::new();
+        for packet in &packets {
+            let control = sender.on_send(packet);
+            if packet.seq_number != SeqNumber(1) {
+                receiver.on_receive(packet.clone());
+            }
+            for control in control {
+                rebuilt.extend(receiver.on_receive(control).rebuilt);
+            }
+        }
+        assert_eq!(rebuilt, [packets[4].clone()]);
+    }
+}
@@ -0,0 +1,87 @@
+//! Packet filters sit between the connection and the network, e.g. to add forward error
+//! correction. Which filter to use is negotiated during the handshake through the
+//! SRTO_PACKETFILTER config, see [`ConnectionSettings::packet_filter`]. The built-in `fec`
+//! filter is set up from it automatically, others can be plugged into the connection with
+//! [`DuplexConnection::set_packet_filter`].
+//!
+//! [`ConnectionSettings::packet_filter`]: crate::connection::ConnectionSettings::packet_filter
+//! [`DuplexConnection::set_packet_filter`]: crate::connection::DuplexConnection::set_packet_filter
+
+pub mod fec;
+
+use std::fmt::Debug;
+
+use crate::{
+    connection::ConnectionSettings,
+    packet::{DataPacket, SeqNumber},
+};
+
+use fec::{FecConfig, FecFilter};
+
+pub trait PacketFilter: Debug + Send {
+    /// Called with every data packet right after it's first sent, as it goes on the wire.
+    /// Returns the extra packets to send after it, e.g. FEC packets. The filter is responsible
+    /// for marking them so it can tell them apart on the receiving side. They are never
+    /// retransmitted, and retransmissions of regular packets don't go through the filter.
+    fn on_send(&mut self, packet: &DataPacket) -> Vec<DataPacket>;
+
+    /// Called with every data packet received, before it's decrypted.
+    fn on_receive(&mut self, packet: DataPacket) -> FilteredPackets;
+
+    /// Whether the receiver should request the retransmission of lost packets as soon as it
+    /// notices them, a filter that rebuilds them on its own can turn it off.
+    fn report_losses(&self) -> bool {
+        true
+    }
+
+    /// How many bytes the filter's packets may add on top of the largest payload, data packets
+    /// are made that much smaller.
+    fn extra_size(&self) -> usize {
+        0
+    }
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct FilteredPackets {
+    /// The received packet, unless it belonged to the filter itself
+    pub packet: Option<DataPacket>,
+    /// Lost packets the filter was able to rebuild
+    pub rebuilt: Vec<DataPacket>,
+    /// Lost packets the filter gave up on, their retransmission is requested even if the
+    /// filter doesn't [report losses](PacketFilter::report_losses) otherwise
+    pub lost: Vec<SeqNumber>,
+}
+
+/// Hands every packet over as is, used when no filter is configured
+#[derive(Debug, Default)]
+pub struct PassThrough;
+
+impl PacketFilter for PassThrough {
+    fn on_send(&mut self, _packet: &DataPacket) -> Vec<DataPacket> {
+        Vec::new()
+    }
+
+    fn on_receive(&mut self, packet: DataPacket) -> FilteredPackets {
+        FilteredPackets {
+            packet: Some(packet),
+            ..Default::default()
+        }
+    }
+}
+
+/// How many bytes the built-in filter for the negotiated config adds on top of the payload, see
+/// [`PacketFilter::extra_size`]
+pub fn extra_size(settings: &ConnectionSettings) -> usize {
+    match settings.packet_filter.as_ref().map(FecConfig::from_spec) {
+        Some(Ok(Some(_))) => FecConfig::HEADER_SIZE,
+        _ => 0,
+    }
+}
+
+/// The built-in filter for the negotiated config, or a pass-through if there is none
+pub fn new_packet_filter(settings: &ConnectionSettings) -> Box<dyn PacketFilter> {
+    match settings.packet_filter.as_ref().map(FecConfig::from_spec) {
+        Some(Ok(Some(config))) => Box::new(FecFilter::new(config, settings.init_seq_num)),
+        _ => Box::new(PassThrough),
+    }
+}
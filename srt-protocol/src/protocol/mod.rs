@@ -1,4 +1,5 @@
 pub mod encryption;
+pub mod filter;
 pub mod handshake;
 pub mod output;
 pub mod pending_connection;
@@ -0,0 +1,220 @@
+//! Proves the identity of each peer in the handshake with the keys in their credentials
+//!
+//! A responder with credentials issues a random challenge in its induction response, or with its
+//! waves in rendezvous. The initiator sends its identity, an Ed25519 signature over it, the
+//! handshake cookie and that challenge, and a nonce of its own. The responder checks the
+//! signature against the public key it trusts for that identity, and answers the same way with
+//! its own identity and key, over the initiator's nonce, which the initiator checks in turn. Each
+//! side only accepts a signature over the nonce it picked itself, and the role is part of what is
+//! signed so a proof can't be reflected back to its sender.
+
+use ed25519_dalek::{Signature, Signer};
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{options::Credentials, packet::HandshakeAuthentication};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Role {
+    Initiator,
+    Responder,
+}
+
+impl Role {
+    fn label(self) -> &'static [u8] {
+        match self {
+            Role::Initiator => b"initiator",
+            Role::Responder => b"responder",
+        }
+    }
+}
+
+/// A fresh nonce for the peer to sign
+pub fn challenge() -> [u8; 16] {
+    let mut nonce = [0; 16];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// The proof of an initiator's identity over the responder's challenge, with a fresh nonce for the
+/// responder to sign in turn
+pub fn initiate(
+    credentials: &Credentials,
+    cookie: i32,
+    challenge: [u8; 16],
+) -> HandshakeAuthentication {
+    HandshakeAuthentication {
+        identity: credentials.identity.clone(),
+        nonce: self::challenge(),
+        signature: sign(credentials, Role::Initiator, cookie, &challenge),
+    }
+}
+
+/// The proof of a responder's identity over the nonce the initiator sent with its proof
+pub fn respond(
+    credentials: &Credentials,
+    cookie: i32,
+    request: &HandshakeAuthentication,
+) -> HandshakeAuthentication {
+    HandshakeAuthentication {
+        identity: credentials.identity.clone(),
+        nonce: request.nonce,
+        signature: sign(credentials, Role::Responder, cookie, &request.nonce),
+    }
+}
+
+/// If the peer proved an identity the credentials trust, for the handshake with the cookie and
+/// the challenge issued to it
+pub fn verify(
+    credentials: &Credentials,
+    role: Role,
+    cookie: i32,
+    challenge: [u8; 16],
+    proof: &HandshakeAuthentication,
+) -> bool {
+    let key = match credentials.trusted_peers.get(&proof.identity) {
+        Some(key) => key,
+        None => return false,
+    };
+    let signature = match Signature::from_slice(&proof.signature) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    key.verify_strict(
+        &message(role, &proof.identity, cookie, &challenge),
+        &signature,
+    )
+    .is_ok()
+}
+
+fn sign(credentials: &Credentials, role: Role, cookie: i32, challenge: &[u8; 16]) -> Vec<u8> {
+    let message = message(role, &credentials.identity, cookie, challenge);
+    credentials.key.sign(&message).to_vec()
+}
+
+fn message(role: Role, identity: &str, cookie: i32, challenge: &[u8; 16]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(9 + 2 + identity.len() + 4 + 16);
+    message.extend_from_slice(role.label());
+    message.extend_from_slice(&(identity.len() as u16).to_be_bytes());
+    message.extend_from_slice(identity.as_bytes());
+    message.extend_from_slice(&cookie.to_be_bytes());
+    message.extend_from_slice(challenge);
+    message
+}
+
+#[cfg(test)]
+mod test {
+    use crate::options::SigningKey;
+
+    use super::*;
+
+    fn encoder_key() -> SigningKey {
+        SigningKey::from_bytes(&[1; 32])
+    }
+
+    fn decoder_key() -> SigningKey {
+        SigningKey::from_bytes(&[2; 32])
+    }
+
+    fn encoder() -> Credentials {
+        Credentials::new("encoder", encoder_key()).trust("decoder", decoder_key().verifying_key())
+    }
+
+    fn decoder() -> Credentials {
+        Credentials::new("decoder", decoder_key()).trust("encoder", encoder_key().verifying_key())
+    }
+
+    #[test]
+    fn mutual() {
+        let challenge = challenge();
+        let request = initiate(&encoder(), 1234, challenge);
+        assert_ne!(request.nonce, challenge);
+        assert!(verify(
+            &decoder(),
+            Role::Initiator,
+            1234,
+            challenge,
+            &request
+        ));
+
+        let response = respond(&decoder(), 1234, &request);
+        assert_eq!(response.identity, "decoder");
+        assert!(verify(
+            &encoder(),
+            Role::Responder,
+            1234,
+            request.nonce,
+            &response
+        ));
+    }
+
+    #[test]
+    fn rejected() {
+        let challenge = challenge();
+        let request = initiate(&encoder(), 1234, challenge);
+
+        // another handshake
+        assert!(!verify(
+            &decoder(),
+            Role::Initiator,
+            4321,
+            challenge,
+            &request
+        ));
+        // signed over a nonce the responder didn't issue
+        assert!(!verify(
+            &decoder(),
+            Role::Initiator,
+            1234,
+            request.nonce,
+            &request
+        ));
+        // reflected back to the initiator
+        assert!(!verify(
+            &Credentials::new("encoder", encoder_key())
+                .trust("encoder", encoder_key().verifying_key()),
+            Role::Responder,
+            1234,
+            challenge,
+            &request
+        ));
+        // a peer that trusts the identity, but doesn't hold its key
+        let impostor = Credentials::new("encoder", decoder_key());
+        assert!(!verify(
+            &decoder(),
+            Role::Initiator,
+            1234,
+            challenge,
+            &initiate(&impostor, 1234, challenge)
+        ));
+        // an identity that isn't trusted
+        let stranger = Credentials::new("stranger", encoder_key());
+        assert!(!verify(
+            &decoder(),
+            Role::Initiator,
+            1234,
+            challenge,
+            &initiate(&stranger, 1234, challenge)
+        ));
+        // a signature that isn't one
+        let truncated = HandshakeAuthentication {
+            signature: request.signature[..20].to_vec(),
+            ..request.clone()
+        };
+        assert!(!verify(
+            &decoder(),
+            Role::Initiator,
+            1234,
+            challenge,
+            &truncated
+        ));
+        // an answer to another nonce
+        let stale = respond(&decoder(), 1234, &initiate(&encoder(), 1234, challenge));
+        assert!(!verify(
+            &encoder(),
+            Role::Responder,
+            1234,
+            request.nonce,
+            &stale
+        ));
+    }
+}
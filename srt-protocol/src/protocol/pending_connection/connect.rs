@@ -5,6 +5,7 @@ use std::{
 };
 
 use log::info;
+use rand::random;
 use ConnectError::*;
 use ConnectState::*;
 use ConnectionResult::*;
@@ -14,8 +15,8 @@ use crate::{
 };
 
 use super::{
-    hsv5::{start_hsv5_initiation, StartedInitiator},
-    ConnectError, ConnectionReject, ConnectionResult,
+    hsv5::{start_hsv5_initiation, FinishHsv5Result, StartedInitiator},
+    retries_exhausted, ConnectError, ConnectionReject, ConnectionResult,
 };
 
 #[allow(clippy::large_enum_variant)]
@@ -47,16 +48,30 @@ pub struct Connect {
     state: ConnectState,
     streamid: Option<String>,
     starting_send_seqnum: SeqNumber,
+    /// when the request of the current state was last sent, and how many times it was sent again
+    last_send: Option<Instant>,
+    retries: u32,
 }
 
 impl Connect {
     pub fn new(
         remote: SocketAddr,
         local_addr: IpAddr,
-        init_settings: ConnInitSettings,
+        mut init_settings: ConnInitSettings,
         streamid: Option<String>,
         starting_send_seqnum: SeqNumber,
     ) -> Self {
+        // a resumable connection starts a session of its own, unless it resumes one, then the
+        // stream goes on from the first packet still to be sent
+        if init_settings.resumable_sessions.is_some() && init_settings.resume.is_none() {
+            init_settings.resume = Some(SessionResume {
+                token: random(),
+                seq_number: starting_send_seqnum,
+            });
+        }
+        let starting_send_seqnum = init_settings
+            .resume
+            .map_or(starting_send_seqnum, |resume| resume.seq_number);
         Connect {
             remote,
             local_addr,
@@ -64,16 +79,18 @@ impl Connect {
             state: ConnectState::new(),
             streamid,
             starting_send_seqnum,
+            last_send: None,
+            retries: 0,
         }
     }
 
-    fn on_start(&mut self) -> ConnectionResult {
+    fn on_start(&mut self, now: Instant) -> ConnectionResult {
         let packet = Packet::Control(ControlPacket {
             dest_sockid: SocketId(0),
             timestamp: TimeStamp::from_micros(0), // TODO: this is not zero in the reference implementation
             control_type: ControlTypes::Handshake(HandshakeControlInfo {
                 init_seq_num: self.starting_send_seqnum,
-                max_packet_size: self.init_settings.max_packet_size,
+                max_packet_size: self.init_settings.max_segment_size,
                 max_flow_size: self.init_settings.max_flow_size,
                 socket_id: self.init_settings.local_sockid,
                 shake_type: ShakeType::Induction,
@@ -83,6 +100,7 @@ impl Connect {
             }),
         });
         self.state = InductionResponseWait(packet.clone());
+        self.last_send = Some(now);
         SendPacket((packet, self.remote))
     }
 
@@ -94,9 +112,14 @@ impl Connect {
         now: Instant,
     ) -> ConnectionResult {
         match (info.shake_type, &info.info, from) {
-            (ShakeType::Induction, HandshakeVsInfo::V5 { .. }, from) if from == self.remote => {
-                let (hsv5, cm) =
-                    start_hsv5_initiation(self.init_settings.clone(), self.streamid.clone(), now);
+            (ShakeType::Induction, HandshakeVsInfo::V5(induction), from) if from == self.remote => {
+                let (hsv5, cm) = start_hsv5_initiation(
+                    self.init_settings.clone(),
+                    self.streamid.clone(),
+                    info.syn_cookie,
+                    induction.challenge,
+                    now,
+                );
 
                 // send back a packet with the same syn cookie
                 let packet = Packet::Control(ControlPacket {
@@ -111,6 +134,8 @@ impl Connect {
                     }),
                 });
                 self.state = ConclusionResponseWait(packet.clone(), cm);
+                self.last_send = Some(now);
+                self.retries = 0;
                 SendPacket((packet, from))
             }
             (ShakeType::Induction, HandshakeVsInfo::V5 { .. }, from) => {
@@ -119,6 +144,9 @@ impl Connect {
             (ShakeType::Induction, version, _) => {
                 NotHandled(UnsupportedProtocolVersion(version.version()))
             }
+            (ShakeType::Rejection(rej), _, from) if from == self.remote => {
+                Reject(None, ConnectionReject::Rejected(rej))
+            }
             (_, _, _) => NotHandled(InductionExpected(info)),
         }
     }
@@ -126,6 +154,7 @@ impl Connect {
     fn wait_for_conclusion(
         &mut self,
         from: SocketAddr,
+        timestamp: TimeStamp,
         now: Instant,
         info: HandshakeControlInfo,
         initiator: StartedInitiator,
@@ -133,8 +162,21 @@ impl Connect {
         match (info.shake_type, info.info.version(), from) {
             (ShakeType::Conclusion, 5, from) if from == self.remote => {
                 let settings = match initiator.finish_hsv5_initiation(&info, from, now) {
-                    Ok(s) => s,
-                    Err(rr) => return NotHandled(rr),
+                    FinishHsv5Result::Accept(s) => s,
+                    FinishHsv5Result::NotHandled(rr) => return NotHandled(rr),
+                    FinishHsv5Result::Reject(rr) => {
+                        // let the responder know, it considers itself connected already
+                        let rejection = Packet::Control(ControlPacket {
+                            timestamp,
+                            dest_sockid: info.socket_id,
+                            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                                shake_type: ShakeType::Rejection(rr.reason()),
+                                socket_id: self.init_settings.local_sockid,
+                                ..info
+                            }),
+                        });
+                        return Reject(Some((rejection, from)), rr);
+                    }
                 };
 
                 // TODO: no handshake retransmit packet needed? is this right? Needs testing.
@@ -172,7 +214,7 @@ impl Connect {
                 (ConclusionResponseWait(_, cm), Packet::Control(control)) => {
                     match control.control_type {
                         ControlTypes::Handshake(shake) => {
-                            self.wait_for_conclusion(from, now, shake, cm)
+                            self.wait_for_conclusion(from, control.timestamp, now, shake, cm)
                         }
                         control_type => NotHandled(HandshakeExpected(control_type)),
                     }
@@ -189,18 +231,38 @@ impl Connect {
                 ErrorKind::ConnectionReset,
                 Parse(PacketParseError::BadConnectionType(c)),
             )),
+            // only parsed strictly when asked to, to give up on a peer that gets it wrong
+            Err(Parse(e @ PacketParseError::StrictHandshake(_))) => {
+                Failure(std::io::Error::new(ErrorKind::InvalidData, Parse(e)))
+            }
             Err(Parse(e)) => NotHandled(ConnectError::ParseFailed(e)),
         }
     }
 
-    pub fn handle_tick(&mut self, _now: Instant) -> ConnectionResult {
-        match &self.state {
-            Configured => self.on_start(),
-            InductionResponseWait(request_packet) => {
-                SendPacket((request_packet.clone(), self.remote))
+    pub fn handle_tick(&mut self, now: Instant) -> ConnectionResult {
+        let request_packet = match &self.state {
+            Configured => return self.on_start(now),
+            InductionResponseWait(request_packet) | ConclusionResponseWait(request_packet, _) => {
+                request_packet.clone()
             }
-            ConclusionResponseWait(request_packet, _) => {
-                SendPacket((request_packet.clone(), self.remote))
+        };
+
+        // the conclusion is sent as soon as the induction is answered, so it may be too early
+        // to send it again
+        let retry_interval = self.init_settings.handshake_retry_interval;
+        if self
+            .last_send
+            .is_some_and(|last| now < last + retry_interval)
+        {
+            return NoAction;
+        }
+
+        match self.init_settings.max_handshake_retries {
+            Some(max) if self.retries >= max => Failure(retries_exhausted(self.retries)),
+            _ => {
+                self.last_send = Some(now);
+                self.retries += 1;
+                SendPacket((request_packet, self.remote))
             }
         }
     }
@@ -214,7 +276,7 @@ mod test {
     use rand::random;
 
     use crate::{
-        options::{self, PacketCount, PacketSize},
+        options::{self, PacketCount, PacketSize, SrtVersion},
         protocol::pending_connection::ConnectionReject,
     };
 
@@ -281,6 +343,102 @@ mod test {
         );
     }
 
+    #[test]
+    fn reject_induction() {
+        let mut c = test_connect(None);
+        c.handle_tick(Instant::now());
+
+        // a listener that doesn't take callers from here answers the induction with a rejection
+        let rejection = Packet::Control(ControlPacket {
+            timestamp: TimeStamp::from_micros(0),
+            dest_sockid: TEST_SOCKID,
+            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                init_seq_num: random(),
+                max_packet_size: PacketSize(8192),
+                max_flow_size: PacketCount(1234),
+                shake_type: ShakeType::Rejection(RejectReason::Server(
+                    ServerRejectReason::Forbidden,
+                )),
+                socket_id: SocketId(5678),
+                syn_cookie: 0,
+                peer_addr: [127, 0, 0, 1].into(),
+                info: HandshakeVsInfo::V5(HsV5Info::default()),
+            }),
+        });
+
+        let resp = c.handle_packet(Ok((rejection, test_remote())), Instant::now());
+        assert_matches!(
+            resp,
+            ConnectionResult::Reject(
+                None,
+                ConnectionReject::Rejected(RejectReason::Server(ServerRejectReason::Forbidden)),
+            )
+        );
+    }
+
+    #[test]
+    fn reject_filter_left_out() {
+        let mut c = test_connect(None);
+        c.init_settings.packet_filter = Some("fec,cols:4".parse().unwrap());
+        c.handle_tick(Instant::now());
+
+        let shake = |shake_type, info| {
+            Packet::Control(ControlPacket {
+                timestamp: TimeStamp::from_micros(0),
+                dest_sockid: TEST_SOCKID,
+                control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                    syn_cookie: 5554,
+                    socket_id: SocketId(5678),
+                    info,
+                    init_seq_num: random(),
+                    max_packet_size: PacketSize(8192),
+                    max_flow_size: PacketCount(1234),
+                    shake_type,
+                    peer_addr: [127, 0, 0, 1].into(),
+                }),
+            })
+        };
+        let induction = shake(
+            ShakeType::Induction,
+            HandshakeVsInfo::V5(HsV5Info::default()),
+        );
+        c.handle_packet(Ok((induction, test_remote())), Instant::now());
+
+        // the responder answers without a filter config, as one that doesn't support it would
+        let conclusion = shake(
+            ShakeType::Conclusion,
+            HandshakeVsInfo::V5(HsV5Info {
+                ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
+                    version: SrtVersion::CURRENT,
+                    flags: SrtShakeFlags::SUPPORTED,
+                    send_latency: Duration::from_millis(20),
+                    recv_latency: Duration::from_millis(20),
+                })),
+                ..HsV5Info::default()
+            }),
+        );
+        let resp = c.handle_packet(Ok((conclusion, test_remote())), Instant::now());
+        assert_matches!(
+            resp,
+            ConnectionResult::Reject(
+                Some((
+                    Packet::Control(ControlPacket {
+                        dest_sockid: SocketId(5678),
+                        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                            shake_type: ShakeType::Rejection(RejectReason::Core(
+                                CoreRejectReason::Filter
+                            )),
+                            ..
+                        }),
+                        ..
+                    }),
+                    _
+                )),
+                ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Filter)),
+            )
+        );
+    }
+
     fn test_remote() -> SocketAddr {
         ([127, 0, 0, 1], 6666).into()
     }
@@ -292,17 +450,44 @@ mod test {
             ConnInitSettings {
                 local_sockid: TEST_SOCKID,
                 key_settings: None,
+                alternate_passphrases: Vec::new(),
                 key_refresh: Default::default(),
                 send_latency: Duration::from_millis(20),
                 recv_latency: Duration::from_millis(20),
                 bandwidth: Default::default(),
+                retransmit_bandwidth: Default::default(),
+                send_buffer_full: Default::default(),
+                flow_window_full: Default::default(),
+                input_overflow: Default::default(),
+                send_queue_depth: 128,
                 statistics_interval: Duration::from_secs(1),
+                loss_rate_window: Duration::from_secs(5),
+                initial_rtt: None,
+                too_late_drop_margin: options::Percent(0),
                 recv_buffer_size: options::PacketCount(8192),
+                recv_buffer_cap: None,
                 send_buffer_size: options::PacketCount(8192),
-                max_packet_size: options::PacketSize(1500),
+                max_packet_size: options::PacketSize(1316),
+                max_segment_size: options::PacketSize(1500),
                 max_flow_size: options::PacketCount(8192),
+                idle_restart_threshold: None,
+                max_retransmits: None,
                 peer_idle_timeout: Duration::from_secs(5),
                 too_late_packet_drop: true,
+                delivery_mode: Default::default(),
+                nak_period: Default::default(),
+                recv_granularity: Default::default(),
+                features: SrtShakeFlags::SUPPORTED,
+                handshake_retry_interval: Duration::from_millis(100),
+                max_handshake_retries: None,
+                max_half_open: 64,
+                half_open_timeout: Duration::from_secs(3),
+                allow_from: None,
+                resumable_sessions: None,
+                resume: None,
+                packet_filter: None,
+                #[cfg(feature = "authentication")]
+                credentials: None,
             },
             sid,
             random(),
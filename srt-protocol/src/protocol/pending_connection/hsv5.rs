@@ -3,11 +3,19 @@
 use std::{
     cmp::{max, min},
     net::SocketAddr,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use crate::{connection::ConnectionSettings, options::*, packet::*, settings::*};
+use crate::{
+    connection::{ConnectionSettings, NegotiationAdjustment},
+    options::*,
+    packet::*,
+    protocol::filter::fec::FecConfig,
+    settings::*,
+};
 
+#[cfg(feature = "authentication")]
+use super::authentication::{self, Role};
 use super::{ConnectError, ConnectionReject};
 
 #[allow(clippy::large_enum_variant)]
@@ -17,11 +25,19 @@ pub enum GenHsv5Result {
     Reject(ConnectionReject),
 }
 
+#[allow(clippy::large_enum_variant)]
+pub enum FinishHsv5Result {
+    Accept(ConnectionSettings),
+    NotHandled(ConnectError),
+    Reject(ConnectionReject),
+}
+
 pub fn gen_hsv5_response(
     settings: &mut ConnInitSettings,
     with_hsv5: &HandshakeControlInfo,
     from: SocketAddr,
     induction_time: Instant,
+    challenge: Option<[u8; 16]>,
     now: Instant,
 ) -> GenHsv5Result {
     let incoming = match &with_hsv5.info {
@@ -38,25 +54,28 @@ pub fn gen_hsv5_response(
         settings,
         from,
         induction_time,
+        challenge,
         with_hsv5.clone(),
         incoming.clone(),
-        None,
+        AcceptParameters::new(),
     )
 }
 
+/// The challenge is the nonce the responder issued for the initiator to sign, if it has credentials
+#[allow(clippy::too_many_arguments)]
+#[cfg_attr(not(feature = "authentication"), allow(unused_variables))]
 pub fn gen_access_control_response(
     now: Instant,
     settings: &mut ConnInitSettings,
     from: SocketAddr,
     induction_time: Instant,
+    challenge: Option<[u8; 16]>,
     with_hsv5: HandshakeControlInfo,
     incoming: HsV5Info,
-    key_settings: Option<KeySettings>,
+    parameters: AcceptParameters,
 ) -> GenHsv5Result {
     // apply parameters generated by acceptor
-    if let Some(ks) = key_settings {
-        settings.key_settings = Some(ks);
-    }
+    parameters.apply(settings);
 
     let hs = match incoming.ext_hs {
         Some(SrtControlPacket::HandshakeRequest(hs)) => hs,
@@ -68,11 +87,21 @@ pub fn gen_access_control_response(
     let cipher = match (&settings.key_settings, &incoming.ext_km) {
         // ok, both sides have crypto
         (Some(key_settings), Some(SrtControlPacket::KeyRefreshRequest(km))) => {
-            if key_settings.key_size != incoming.key_size {
-                unimplemented!("Key size mismatch");
-            }
+            // the initiator's key size takes precedence, same as in the reference implementation
+            let key_settings = match incoming.key_size {
+                KeySize::Unspecified => key_settings.clone(),
+                key_size => KeySettings {
+                    key_size,
+                    passphrase: key_settings.passphrase.clone(),
+                },
+            };
 
-            let cipher = match CipherSettings::new(key_settings, &settings.key_refresh, km) {
+            let cipher = match CipherSettings::new_with_alternates(
+                &key_settings,
+                &settings.alternate_passphrases,
+                &settings.key_refresh,
+                km,
+            ) {
                 Ok(cm) => cm,
                 Err(_) => {
                     return GenHsv5Result::Reject(ConnectionReject::Rejecting(
@@ -94,6 +123,33 @@ pub fn gen_access_control_response(
         (None, Some(_)) => unimplemented!("expected no secrets"),
     };
 
+    // packet filter, a peer without a config takes the other one's, the agreed config is sent
+    // back to the initiator
+    let packet_filter = match (&settings.packet_filter, &incoming.filter) {
+        (Some(local), Some(remote)) => match local.merge(remote) {
+            Some(merged) => Some(merged),
+            None => {
+                return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+                    CoreRejectReason::Filter.into(),
+                ))
+            }
+        },
+        (local, remote) => local.clone().or_else(|| remote.clone()),
+    };
+    if let Some(Err(_)) = packet_filter.as_ref().map(FecConfig::from_spec) {
+        return GenHsv5Result::Reject(ConnectionReject::Rejecting(CoreRejectReason::Filter.into()));
+    }
+
+    let (max_segment_size, max_packet_size) =
+        match negotiate_packet_size(settings, with_hsv5.max_packet_size) {
+            Some(sizes) => sizes,
+            None => {
+                return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+                    CoreRejectReason::Rogue.into(),
+                ))
+            }
+        };
+
     let outgoing_ext_km = cipher
         .as_ref()
         .and_then(CipherSettings::wrap_keying_material);
@@ -104,8 +160,59 @@ pub fn gen_access_control_response(
         None
     };
 
+    // a listener that resumes sessions goes on from what it received of the session already, or
+    // from the first packet the caller still has if it doesn't know of it
+    let resume = match (&settings.resumable_sessions, incoming.resume) {
+        (Some(sessions), Some(asked)) => Some(SessionResume {
+            token: asked.token,
+            seq_number: sessions
+                .resume_point(now, asked.token)
+                .filter(|received| *received > asked.seq_number)
+                .unwrap_or(asked.seq_number),
+        }),
+        _ => None,
+    };
+
+    // a responder with credentials only lets in a peer that proves an identity it trusts over the
+    // challenge it issued, and proves its own identity in turn
+    #[cfg(feature = "authentication")]
+    let auth = match (&settings.credentials, &incoming.auth, challenge) {
+        (None, _, _) => None,
+        (Some(credentials), Some(proof), Some(challenge))
+            if authentication::verify(
+                credentials,
+                Role::Initiator,
+                with_hsv5.syn_cookie,
+                challenge,
+                proof,
+            ) =>
+        {
+            Some(authentication::respond(
+                credentials,
+                with_hsv5.syn_cookie,
+                proof,
+            ))
+        }
+        (Some(_), _, _) => {
+            return GenHsv5Result::Reject(ConnectionReject::Rejecting(
+                ServerRejectReason::Unauthorized.into(),
+            ))
+        }
+    };
+    #[cfg(not(feature = "authentication"))]
+    let auth = None;
+
     let rtt = now - induction_time;
 
+    let send_tsbpd_latency = max(settings.send_latency, hs.recv_latency);
+    let recv_tsbpd_latency = max(settings.recv_latency, hs.send_latency);
+    let negotiation_adjustments = negotiation_adjustments(
+        settings,
+        send_tsbpd_latency,
+        recv_tsbpd_latency,
+        max_segment_size,
+    );
+
     GenHsv5Result::Accept(
         HandshakeVsInfo::V5(HsV5Info {
             key_size: cipher
@@ -114,49 +221,130 @@ pub fn gen_access_control_response(
                 .unwrap_or(KeySize::Unspecified),
             ext_hs: Some(SrtControlPacket::HandshakeResponse(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: settings.features,
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km: outgoing_ext_km.map(SrtControlPacket::KeyRefreshResponse),
             ext_group: None,
             sid,
+            filter: packet_filter.clone(),
+            resume,
+            auth,
+            challenge: None,
         }),
         ConnectionSettings {
             remote: from,
             rtt,
             socket_start_time: now.checked_sub(rtt / 2).unwrap(), // initiate happened 0.5RTT ago
             remote_sockid: with_hsv5.socket_id,
-            init_seq_num: with_hsv5.init_seq_num,
+            init_seq_num: resume.map_or(with_hsv5.init_seq_num, |resume| resume.seq_number),
             cipher,
             stream_id: incoming.sid,
             max_flow_size: max(settings.max_flow_size, with_hsv5.max_flow_size),
-            max_packet_size: min(settings.max_packet_size, with_hsv5.max_packet_size),
-            send_tsbpd_latency: max(settings.send_latency, hs.recv_latency),
-            recv_tsbpd_latency: max(settings.recv_latency, hs.send_latency),
+            idle_restart_threshold: settings.idle_restart_threshold,
+            max_retransmits: settings.max_retransmits,
+            max_packet_size,
+            max_segment_size,
+            send_tsbpd_latency,
+            recv_tsbpd_latency,
             bandwidth: settings.bandwidth.clone(),
+            retransmit_bandwidth: settings.retransmit_bandwidth,
+            send_buffer_full: settings.send_buffer_full,
+            flow_window_full: settings.flow_window_full,
+            input_overflow: settings.input_overflow,
             local_sockid: settings.local_sockid,
             recv_buffer_size: settings.recv_buffer_size,
+            recv_buffer_cap: settings.recv_buffer_cap,
             send_buffer_size: settings.send_buffer_size,
             statistics_interval: settings.statistics_interval,
+            loss_rate_window: settings.loss_rate_window,
+            initial_rtt: settings.initial_rtt,
+            too_late_drop_margin: settings.too_late_drop_margin,
             peer_idle_timeout: settings.peer_idle_timeout,
             too_late_packet_drop: settings.too_late_packet_drop,
+            delivery_mode: settings.delivery_mode,
+            nak_period: settings.nak_period,
+            recv_granularity: settings.recv_granularity,
+            peer_version: hs.version,
+            features: settings.features & hs.flags,
+            packet_filter,
+            negotiation_adjustments,
+            session_token: resume.map(|resume| resume.token),
+            resumable_sessions: resume.and(settings.resumable_sessions.clone()),
         },
     )
 }
 
+// the smallest MSS that has room for a handshake, as in the reference implementation
+const MIN_SEGMENT_SIZE: PacketSize = PacketSize(76);
+
+/// The smaller of the two MSS, and the largest payload that fits in it, or None if the MSS is too
+/// small for any packet to fit
+fn negotiate_packet_size(
+    settings: &ConnInitSettings,
+    peer_max_segment_size: PacketSize,
+) -> Option<(PacketSize, PacketSize)> {
+    let max_segment_size = min(settings.max_segment_size, peer_max_segment_size);
+    if max_segment_size < MIN_SEGMENT_SIZE {
+        return None;
+    }
+    let max_packet_size = min(
+        settings.max_packet_size,
+        max_segment_size - Packet::HEADER_SIZE,
+    );
+    Some((max_segment_size, max_packet_size))
+}
+
+/// The values asked for that differ from the ones the handshake settled on
+fn negotiation_adjustments(
+    settings: &ConnInitSettings,
+    send_tsbpd_latency: Duration,
+    recv_tsbpd_latency: Duration,
+    max_segment_size: PacketSize,
+) -> Vec<NegotiationAdjustment> {
+    use NegotiationAdjustment::*;
+    let mut adjustments = Vec::new();
+    if settings.send_latency != send_tsbpd_latency {
+        adjustments.push(SendLatency {
+            requested: settings.send_latency,
+            effective: send_tsbpd_latency,
+        });
+    }
+    if settings.recv_latency != recv_tsbpd_latency {
+        adjustments.push(RecvLatency {
+            requested: settings.recv_latency,
+            effective: recv_tsbpd_latency,
+        });
+    }
+    if settings.max_segment_size != max_segment_size {
+        adjustments.push(MaxSegmentSize {
+            requested: settings.max_segment_size,
+            effective: max_segment_size,
+        });
+    }
+    adjustments
+}
+
 #[derive(Debug, Clone)] // TOOD: make not clone
 pub struct StartedInitiator {
     cipher: Option<CipherSettings>,
     settings: ConnInitSettings,
     streamid: Option<String>,
     initiate_time: Instant,
+    /// The cookie and the nonce of our own the responder proves its identity over
+    #[cfg(feature = "authentication")]
+    challenge: Option<(i32, [u8; 16])>,
 }
 
 // TODO: this could check that the responder returns the same initial sequence number that we send
+/// The challenge is the nonce the responder issued for an initiator with credentials to sign
+#[cfg_attr(not(feature = "authentication"), allow(unused_variables))]
 pub fn start_hsv5_initiation(
     settings: ConnInitSettings,
     streamid: Option<String>,
+    cookie: i32,
+    challenge: Option<[u8; 16]>,
     now: Instant,
 ) -> (HandshakeVsInfo, StartedInitiator) {
     let self_crypto_size = settings
@@ -179,24 +367,41 @@ pub fn start_hsv5_initiation(
         (None, None)
     };
 
+    // a responder without credentials issues no challenge, and doesn't check the proof either
+    #[cfg(feature = "authentication")]
+    let auth = settings.credentials.as_ref().map(|credentials| {
+        authentication::initiate(credentials, cookie, challenge.unwrap_or_default())
+    });
+    #[cfg(not(feature = "authentication"))]
+    let auth = None;
+
     (
         HandshakeVsInfo::V5(HsV5Info {
             key_size: self_crypto_size,
             ext_hs: Some(SrtControlPacket::HandshakeRequest(SrtHandshake {
                 version: SrtVersion::CURRENT,
-                flags: SrtShakeFlags::SUPPORTED,
+                flags: settings.features,
                 send_latency: settings.send_latency,
                 recv_latency: settings.recv_latency,
             })),
             ext_km,
             ext_group: None,
             sid: streamid.clone(),
+            filter: settings.packet_filter.clone(),
+            resume: settings.resume,
+            #[cfg(feature = "authentication")]
+            auth: auth.clone(),
+            #[cfg(not(feature = "authentication"))]
+            auth,
+            challenge: None,
         }),
         StartedInitiator {
             cipher,
             settings,
             streamid,
             initiate_time: now,
+            #[cfg(feature = "authentication")]
+            challenge: auth.map(|auth| (cookie, auth.nonce)),
         },
     )
 }
@@ -207,23 +412,86 @@ impl StartedInitiator {
         response: &HandshakeControlInfo,
         from: SocketAddr,
         now: Instant,
-    ) -> Result<ConnectionSettings, ConnectError> {
+    ) -> FinishHsv5Result {
+        use FinishHsv5Result::*;
         // TODO: factor this out with above...
         let incoming = match &response.info {
             HandshakeVsInfo::V5(hs) => hs,
-            i => return Err(ConnectError::UnsupportedProtocolVersion(i.version())),
+            i => return NotHandled(ConnectError::UnsupportedProtocolVersion(i.version())),
         };
 
         let hs = match incoming.ext_hs {
             Some(SrtControlPacket::HandshakeResponse(hs)) => hs,
-            Some(_) => return Err(ConnectError::ExpectedHsResp),
-            None => return Err(ConnectError::ExpectedExtFlags),
+            Some(_) => return NotHandled(ConnectError::ExpectedHsResp),
+            None => return NotHandled(ConnectError::ExpectedExtFlags),
         };
 
         // todo: validate km!
 
+        // an initiator with credentials only connects to a responder that proves an identity it
+        // trusts over the nonce it was sent
+        #[cfg(feature = "authentication")]
+        if let (Some(credentials), Some((cookie, nonce))) =
+            (&self.settings.credentials, self.challenge)
+        {
+            match &incoming.auth {
+                Some(proof)
+                    if authentication::verify(
+                        credentials,
+                        Role::Responder,
+                        cookie,
+                        nonce,
+                        proof,
+                    ) => {}
+                _ => {
+                    return Reject(ConnectionReject::Rejecting(
+                        ServerRejectReason::Unauthorized.into(),
+                    ))
+                }
+            }
+        }
+
+        // the responder answers with the config both sides agreed on, which has to include
+        // everything asked for. A responder that doesn't know about filters leaves it out.
+        let agreed = match (&self.settings.packet_filter, &incoming.filter) {
+            (Some(local), Some(agreed)) => local.merge(agreed).as_ref() == Some(agreed),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        if !agreed
+            || matches!(
+                incoming.filter.as_ref().map(FecConfig::from_spec),
+                Some(Err(_))
+            )
+        {
+            return Reject(ConnectionReject::Rejecting(CoreRejectReason::Filter.into()));
+        }
+
+        // the responder answers with the MSS both sides agreed on
+        let (max_segment_size, max_packet_size) =
+            match negotiate_packet_size(&self.settings, response.max_packet_size) {
+                Some(sizes) => sizes,
+                None => return Reject(ConnectionReject::Rejecting(CoreRejectReason::Rogue.into())),
+            };
+
+        // the responder answers with the session if it resumes sessions, and with where the
+        // stream goes on from as the initial sequence number
+        let session_token = match (self.settings.resume, incoming.resume) {
+            (Some(asked), Some(answer)) if asked.token == answer.token => Some(asked.token),
+            _ => None,
+        };
+
+        let send_tsbpd_latency = max(self.settings.send_latency, hs.recv_latency);
+        let recv_tsbpd_latency = max(self.settings.recv_latency, hs.send_latency);
+        let negotiation_adjustments = negotiation_adjustments(
+            &self.settings,
+            send_tsbpd_latency,
+            recv_tsbpd_latency,
+            max_segment_size,
+        );
+
         // validate response
-        Ok(ConnectionSettings {
+        Accept(ConnectionSettings {
             remote: from,
             rtt: now - self.initiate_time,
             socket_start_time: self.initiate_time,
@@ -232,16 +500,36 @@ impl StartedInitiator {
             cipher: self.cipher,
             stream_id: self.streamid,
             max_flow_size: max(self.settings.max_flow_size, response.max_flow_size),
-            max_packet_size: min(self.settings.max_packet_size, response.max_packet_size),
-            send_tsbpd_latency: max(self.settings.send_latency, hs.recv_latency),
-            recv_tsbpd_latency: max(self.settings.recv_latency, hs.send_latency),
+            idle_restart_threshold: self.settings.idle_restart_threshold,
+            max_retransmits: self.settings.max_retransmits,
+            max_packet_size,
+            max_segment_size,
+            send_tsbpd_latency,
+            recv_tsbpd_latency,
             bandwidth: self.settings.bandwidth,
+            retransmit_bandwidth: self.settings.retransmit_bandwidth,
+            send_buffer_full: self.settings.send_buffer_full,
+            flow_window_full: self.settings.flow_window_full,
+            input_overflow: self.settings.input_overflow,
             local_sockid: self.settings.local_sockid,
             recv_buffer_size: self.settings.recv_buffer_size,
+            recv_buffer_cap: self.settings.recv_buffer_cap,
             send_buffer_size: self.settings.send_buffer_size,
             statistics_interval: self.settings.statistics_interval,
+            loss_rate_window: self.settings.loss_rate_window,
+            initial_rtt: self.settings.initial_rtt,
+            too_late_drop_margin: self.settings.too_late_drop_margin,
             peer_idle_timeout: self.settings.peer_idle_timeout,
             too_late_packet_drop: self.settings.too_late_packet_drop,
+            delivery_mode: self.settings.delivery_mode,
+            nak_period: self.settings.nak_period,
+            recv_granularity: self.settings.recv_granularity,
+            peer_version: hs.version,
+            features: self.settings.features & hs.flags,
+            packet_filter: incoming.filter.clone(),
+            negotiation_adjustments,
+            session_token,
+            resumable_sessions: None,
         })
     }
 }
@@ -1,7 +1,9 @@
-use std::{convert::TryInto, net::SocketAddr, time::Instant};
+use std::{collections::HashMap, convert::TryInto, net::SocketAddr, time::Instant};
 
 use crate::{packet::*, protocol::handshake::Handshake, settings::*};
 
+#[cfg(feature = "authentication")]
+use super::authentication;
 use super::{
     cookie::gen_cookie, hsv5::gen_access_control_response, hsv5::GenHsv5Result,
     AccessControlRequest, AccessControlResponse, ConnectError, Connection, ConnectionReject,
@@ -15,7 +17,9 @@ use ListenState::*;
 pub struct Listen {
     init_settings: ConnInitSettings,
     state: ListenState,
+    half_open: HashMap<SocketAddr, ConclusionWaitState>,
     enable_access_control: bool,
+    cookie_generator: fn(&SocketAddr) -> i32,
 }
 
 #[derive(Clone, Debug)]
@@ -24,13 +28,14 @@ pub struct ConclusionWaitState {
     cookie: i32,
     induction_response: Packet,
     induction_time: Instant,
+    /// The nonce the caller signs to prove its identity, if the listener has credentials
+    challenge: Option<[u8; 16]>,
 }
 
 #[derive(Clone, Debug)]
 #[allow(clippy::large_enum_variant)]
 enum ListenState {
-    InductionWait,
-    ConclusionWait(ConclusionWaitState),
+    HandshakeWait,
     AccessControlRequested(
         ConclusionWaitState,
         TimeStamp,
@@ -42,9 +47,19 @@ enum ListenState {
 impl Listen {
     pub fn new(init_settings: ConnInitSettings, enable_access_control: bool) -> Listen {
         Listen {
-            state: InductionWait,
+            state: HandshakeWait,
+            half_open: HashMap::new(),
             init_settings,
             enable_access_control,
+            cookie_generator: gen_cookie,
+        }
+    }
+
+    /// Replace the SYN cookie generator, e.g. to pin the cookie to a known value in tests
+    pub fn with_cookie_generator(self, cookie_generator: fn(&SocketAddr) -> i32) -> Self {
+        Listen {
+            cookie_generator,
+            ..self
         }
     }
 
@@ -52,6 +67,11 @@ impl Listen {
         &self.init_settings
     }
 
+    /// The number of callers whose induction was answered, but whose conclusion hasn't come yet
+    pub fn half_open_count(&self) -> usize {
+        self.half_open.len()
+    }
+
     pub fn handle_packet(&mut self, now: Instant, packet: ReceivePacketResult) -> ConnectionResult {
         use ReceivePacketError::*;
         match packet {
@@ -71,12 +91,12 @@ impl Listen {
     ) -> ConnectionResult {
         match self.state.clone() {
             // TODO: something other than ExpectedHsReq
-            InductionWait | ConclusionWait(_) => NotHandled(ConnectError::ExpectedHsReq),
+            HandshakeWait => NotHandled(ConnectError::ExpectedHsReq),
             AccessControlRequested(state, timestamp, shake, info) => {
                 use AccessControlResponse::*;
                 match response {
-                    Accepted(key_settings) => {
-                        self.accept_connection(now, &state, timestamp, shake, info, key_settings)
+                    Accepted(parameters) => {
+                        self.accept_connection(now, &state, timestamp, shake, info, parameters)
                     }
                     Rejected(rr) => self.make_rejection(
                         &shake,
@@ -105,27 +125,45 @@ impl Listen {
         from: SocketAddr,
         control: ControlPacket,
     ) -> ConnectionResult {
-        match (self.state.clone(), control.control_type) {
-            (InductionWait, ControlTypes::Handshake(shake)) => {
-                self.wait_for_induction(from, control.timestamp, shake, now)
-            }
-            (ConclusionWait(state), ControlTypes::Handshake(shake)) => self.wait_for_conclusion(
-                now,
-                from,
-                control.dest_sockid,
-                control.timestamp,
-                state,
-                shake,
-            ),
+        match (&self.state, control.control_type) {
+            // only the allowed callers get as far as an induction response
+            (HandshakeWait, ControlTypes::Handshake(shake)) if !self.is_allowed(from) => self
+                .make_rejection(
+                    &shake,
+                    from,
+                    control.timestamp,
+                    ConnectionReject::Rejecting(RejectReason::Server(
+                        ServerRejectReason::Forbidden,
+                    )),
+                ),
+            // each caller is half-open from its induction until its conclusion
+            (HandshakeWait, ControlTypes::Handshake(shake)) => match self.half_open.get(&from) {
+                Some(state) => self.wait_for_conclusion(
+                    now,
+                    from,
+                    control.dest_sockid,
+                    control.timestamp,
+                    state.clone(),
+                    shake,
+                ),
+                None => self.wait_for_induction(from, control.timestamp, shake, now),
+            },
             (AccessControlRequested(_, _, _, _), _) => {
                 NotHandled(ConnectError::ExpectedAccessControlResponse)
             }
-            (InductionWait, control_type) | (ConclusionWait(_), control_type) => {
+            (HandshakeWait, control_type) => {
                 NotHandled(ConnectError::HandshakeExpected(control_type))
             }
         }
     }
 
+    fn is_allowed(&self, from: SocketAddr) -> bool {
+        match &self.init_settings.allow_from {
+            Some(allowed) => allowed.iter().any(|net| net.contains(from.ip())),
+            None => true,
+        }
+    }
+
     fn wait_for_induction(
         &mut self,
         from: SocketAddr,
@@ -135,14 +173,40 @@ impl Listen {
     ) -> ConnectionResult {
         match shake.shake_type {
             ShakeType::Induction => {
+                // callers that never concluded are forgotten after the connect timeout, once
+                // there's no room for another one
+                let max_half_open = self.init_settings.max_half_open;
+                if self.half_open.len() >= max_half_open {
+                    let timeout = self.init_settings.half_open_timeout;
+                    self.half_open
+                        .retain(|_, state| now < state.induction_time + timeout);
+                }
+                if self.half_open.len() >= max_half_open {
+                    return NotHandled(ConnectError::HalfOpenLimit(max_half_open));
+                }
+
                 // https://tools.ietf.org/html/draft-gg-udt-03#page-9
                 // When the server first receives the connection request from a client,
                 // it generates a cookie value according to the client address and a
                 // secret key and sends it back to the client. The client must then send
                 // back the same cookie to the server.
 
-                // generate the cookie, which is just a hash of the address + time
-                let cookie = gen_cookie(&from);
+                // generate the cookie, which by default is just a hash of the address + time
+                let cookie = (self.cookie_generator)(&from);
+
+                // a listener with credentials issues a challenge for the caller to sign, the same
+                // one again if the caller sent its induction more than once
+                #[cfg(feature = "authentication")]
+                let challenge = match self.half_open.get(&from) {
+                    Some(state) => state.challenge,
+                    None => self
+                        .init_settings
+                        .credentials
+                        .as_ref()
+                        .map(|_| authentication::challenge()),
+                };
+                #[cfg(not(feature = "authentication"))]
+                let challenge = None;
 
                 // we expect HSv5, so upgrade it
                 // construct a packet to send back
@@ -152,19 +216,26 @@ impl Listen {
                     control_type: ControlTypes::Handshake(HandshakeControlInfo {
                         syn_cookie: cookie,
                         socket_id: self.init_settings.local_sockid,
-                        info: HandshakeVsInfo::V5(HsV5Info::default()),
+                        info: HandshakeVsInfo::V5(HsV5Info {
+                            challenge,
+                            ..HsV5Info::default()
+                        }),
                         ..shake
                     }),
                 });
 
                 // save induction message for potential later retransmit
                 let save_induction_response = induction_response.clone();
-                self.state = ConclusionWait(ConclusionWaitState {
+                self.half_open.insert(
                     from,
-                    cookie,
-                    induction_response: save_induction_response,
-                    induction_time: now,
-                });
+                    ConclusionWaitState {
+                        from,
+                        cookie,
+                        induction_response: save_induction_response,
+                        induction_time: now,
+                        challenge,
+                    },
+                );
                 SendPacket((induction_response, from))
             }
             _ => NotHandled(ConnectError::InductionExpected(shake)),
@@ -211,8 +282,8 @@ impl Listen {
                 if self.enable_access_control {
                     self.request_access(from, local_socket_id, timestamp, state, shake, incoming)
                 } else {
-                    let key_settings = self.settings().key_settings.clone();
-                    self.accept_connection(now, &state, timestamp, shake, incoming, key_settings)
+                    let parameters = AcceptParameters::new();
+                    self.accept_connection(now, &state, timestamp, shake, incoming, parameters)
                 }
             }
             (ShakeType::Conclusion, VERSION_5, syn_cookie) => NotHandled(
@@ -257,16 +328,17 @@ impl Listen {
         timestamp: TimeStamp,
         shake: HandshakeControlInfo,
         info: HsV5Info,
-        key_settings: Option<KeySettings>,
+        parameters: AcceptParameters,
     ) -> ConnectionResult {
         let response = gen_access_control_response(
             now,
             &mut self.init_settings,
             state.from,
             state.induction_time,
+            state.challenge,
             shake.clone(),
             info,
-            key_settings,
+            parameters,
         );
         let (hsv5, settings) = match response {
             GenHsv5Result::Accept(h, c) => (h, c),
@@ -284,6 +356,8 @@ impl Listen {
                 socket_id: self.init_settings.local_sockid,
                 info: hsv5,
                 shake_type: ShakeType::Conclusion,
+                max_packet_size: settings.max_segment_size,
+                init_seq_num: settings.init_seq_num,
                 ..shake // TODO: this will pass peer wrong
             }),
         };
@@ -335,7 +409,7 @@ mod test {
     use bytes::Bytes;
     use rand::random;
 
-    use crate::options::*;
+    use crate::{connection::ConnectionSettings, options::*};
 
     use super::*;
 
@@ -380,6 +454,10 @@ mod test {
                 ext_km: None,
                 ext_group: None,
                 sid: None,
+                filter: None,
+                resume: None,
+                auth: None,
+                challenge: None,
             }),
         }
     }
@@ -595,6 +673,10 @@ mod test {
                 ext_km: None,
                 ext_group: None,
                 sid: None,
+                filter: None,
+                resume: None,
+                auth: None,
+                challenge: None,
             }),
             ..test_conclusion()
         };
@@ -613,4 +695,338 @@ mod test {
 
         assert_eq!(request_access.key_size, hs_key_size);
     }
+
+    #[test]
+    fn packet_filter_negotiation() {
+        let filter = |cols: &str| FilterSpec([("cols".to_string(), cols.to_string())].into());
+        let conclusion = |filter| {
+            let mut conclusion = test_conclusion();
+            if let HandshakeVsInfo::V5(info) = &mut conclusion.info {
+                info.filter = filter;
+            }
+            conclusion
+        };
+        let connect = |listen_filter, conclusion| {
+            let mut l = Listen::new(
+                ConnInitSettings {
+                    packet_filter: listen_filter,
+                    ..ConnInitSettings::default()
+                },
+                false,
+            );
+            l.handle_packet(
+                Instant::now(),
+                Ok((build_hs_pack(test_induction()), conn_addr())),
+            );
+            l.handle_packet(Instant::now(), Ok((build_hs_pack(conclusion), conn_addr())))
+        };
+
+        // the side without a config adopts the other one's, and answers with it
+        for (listen_filter, caller_filter) in [(None, Some(filter("4"))), (Some(filter("4")), None)]
+        {
+            let resp = connect(listen_filter, conclusion(caller_filter));
+            let Connected(Some((Packet::Control(response), _)), connection) = resp else {
+                panic!("expected a connection, got {resp:?}");
+            };
+            assert_eq!(connection.settings.packet_filter, Some(filter("4")));
+            assert_matches!(
+                response.control_type,
+                ControlTypes::Handshake(HandshakeControlInfo {
+                    info: HandshakeVsInfo::V5(HsV5Info { filter: Some(f), .. }),
+                    ..
+                }) if f == filter("4")
+            );
+        }
+
+        // parameters only one side sets are added to the agreed config
+        let rows = FilterSpec(
+            [
+                ("cols".to_string(), "4".to_string()),
+                ("rows".to_string(), "2".to_string()),
+            ]
+            .into(),
+        );
+        let resp = connect(Some(filter("4")), conclusion(Some(rows.clone())));
+        assert_matches!(
+            resp,
+            Connected(
+                Some((Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                        info: HandshakeVsInfo::V5(HsV5Info { filter: Some(f), .. }),
+                        ..
+                    }),
+                    ..
+                }), _)),
+                Connection { settings, .. }
+            ) if f == rows && settings.packet_filter == Some(rows.clone())
+        );
+
+        let resp = connect(None, conclusion(None));
+        assert_matches!(resp, Connected(_, Connection { settings, .. }) if settings.packet_filter.is_none());
+
+        let resp = connect(Some(filter("4")), conclusion(Some(filter("8"))));
+        assert_matches!(
+            resp,
+            Reject(
+                _,
+                ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Filter))
+            )
+        );
+    }
+
+    #[test]
+    fn max_segment_size_negotiation() {
+        let connect = |listen_mss, caller_mss| {
+            let mut l = Listen::new(
+                ConnInitSettings {
+                    max_segment_size: PacketSize(listen_mss),
+                    ..ConnInitSettings::default()
+                },
+                false,
+            );
+            l.handle_packet(
+                Instant::now(),
+                Ok((build_hs_pack(test_induction()), conn_addr())),
+            );
+            let conclusion = HandshakeControlInfo {
+                max_packet_size: PacketSize(caller_mss),
+                ..test_conclusion()
+            };
+            l.handle_packet(Instant::now(), Ok((build_hs_pack(conclusion), conn_addr())))
+        };
+
+        // the smaller MSS is used and sent back, the payload is what fits in it
+        for (listen_mss, caller_mss) in [(1000, 1500), (1500, 1000)] {
+            let resp = connect(listen_mss, caller_mss);
+            let Connected(Some((Packet::Control(response), _)), connection) = resp else {
+                panic!("expected a connection, got {resp:?}");
+            };
+            assert_eq!(connection.settings.max_segment_size, PacketSize(1000));
+            assert_eq!(connection.settings.max_packet_size, PacketSize(956));
+            assert_eq!(
+                response.handshake().map(|hs| hs.max_packet_size),
+                Some(PacketSize(1000))
+            );
+        }
+
+        // the payload size isn't raised to fill the MSS
+        let resp = connect(1500, 1500);
+        assert_matches!(resp, Connected(_, Connection { settings, .. }) if settings.max_packet_size == PacketSize(1316));
+
+        // no room for a handshake
+        let resp = connect(1500, 50);
+        assert_matches!(
+            resp,
+            Reject(
+                _,
+                ConnectionReject::Rejecting(RejectReason::Core(CoreRejectReason::Rogue))
+            )
+        );
+    }
+
+    #[test]
+    fn fixed_cookie() {
+        const COOKIE: i32 = 0x1234_5678;
+        let mut l = test_listen().with_cookie_generator(|_| COOKIE);
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(
+            resp,
+            SendPacket((
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                        syn_cookie: COOKIE,
+                        ..
+                    }),
+                    ..
+                }),
+                _
+            ))
+        );
+
+        // the caller must echo the injected cookie in its conclusion
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_conclusion()), conn_addr())),
+        );
+        assert_matches!(
+            resp,
+            NotHandled(ConnectError::InvalidHandshakeCookie(COOKIE, _))
+        );
+
+        let conclusion = HandshakeControlInfo {
+            syn_cookie: COOKIE,
+            ..test_conclusion()
+        };
+        let resp = l.handle_packet(Instant::now(), Ok((build_hs_pack(conclusion), conn_addr())));
+        assert_matches!(
+            resp,
+            Connected(
+                Some((
+                    Packet::Control(ControlPacket {
+                        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                            syn_cookie: COOKIE,
+                            ..
+                        }),
+                        ..
+                    }),
+                    _
+                )),
+                _
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_extensions() {
+        const COOKIE: i32 = 0x1234_5678;
+        let mut l = test_listen().with_cookie_generator(|_| COOKIE);
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(resp, SendPacket(_));
+
+        let mut conclusion = HandshakeControlInfo {
+            syn_cookie: COOKIE,
+            ..test_conclusion()
+        };
+        if let HandshakeVsInfo::V5(info) = &mut conclusion.info {
+            info.sid = Some("abc".into());
+        }
+        let mut bytes = Vec::new();
+        build_hs_pack(conclusion).serialize(&mut bytes);
+
+        // a newer peer's extension, between the handshake one and the stream id that ends it
+        let sid_block = bytes.split_off(bytes.len() - 8);
+        bytes.extend_from_slice(&[0x00, 0xc8, 0x00, 0x02, 1, 2, 3, 4, 5, 6, 7, 8]);
+        bytes.extend_from_slice(&sid_block);
+        let packet = Packet::parse(&mut &bytes[..], false).unwrap();
+
+        let resp = l.handle_packet(Instant::now(), Ok((packet, conn_addr())));
+        assert_matches!(
+            resp,
+            Connected(
+                Some(_),
+                Connection {
+                    settings: ConnectionSettings { stream_id: Some(sid), .. },
+                    handshake: Handshake::Listener(ControlTypes::Handshake(HandshakeControlInfo {
+                        info: HandshakeVsInfo::V5(HsV5Info {
+                            ext_hs: Some(_),
+                            ..
+                        }),
+                        ..
+                    })),
+                },
+            ) if sid == "abc"
+        );
+    }
+
+    #[test]
+    fn half_open_limit() {
+        const COOKIE: i32 = 0x1234_5678;
+        let timeout = Duration::from_secs(3);
+        let mut l = Listen::new(
+            ConnInitSettings {
+                max_half_open: 8,
+                half_open_timeout: timeout,
+                ..ConnInitSettings::default()
+            },
+            false,
+        )
+        .with_cookie_generator(|_| COOKIE);
+        let flooder = |port| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), port);
+        let conclusion = HandshakeControlInfo {
+            syn_cookie: COOKIE,
+            ..test_conclusion()
+        };
+
+        // a caller is half-open from its induction on
+        let start = Instant::now();
+        let resp = l.handle_packet(start, Ok((build_hs_pack(test_induction()), conn_addr())));
+        assert_matches!(resp, SendPacket(_));
+        assert_eq!(l.half_open_count(), 1);
+
+        // a flood of inductions fills up the rest, beyond that they're dropped
+        for port in 0..100 {
+            let resp = l.handle_packet(start, Ok((build_hs_pack(test_induction()), flooder(port))));
+            if port < 7 {
+                assert_matches!(resp, SendPacket((_, to)) if to == flooder(port));
+            } else {
+                assert_matches!(resp, NotHandled(ConnectError::HalfOpenLimit(8)));
+            }
+        }
+        assert_eq!(l.half_open_count(), 8);
+
+        // a caller already half-open still gets its induction answered, and concludes
+        let resp = l.handle_packet(start, Ok((build_hs_pack(test_induction()), conn_addr())));
+        assert_matches!(resp, SendPacket((_, to)) if to == conn_addr());
+        let resp = l.handle_packet(start, Ok((build_hs_pack(conclusion.clone()), conn_addr())));
+        assert_matches!(resp, Connected(Some((_, to)), _) if to == conn_addr());
+
+        // once the connect timeout has passed, the flood is forgotten to make room for new callers
+        let mut l = Listen::new(l.settings().clone(), false).with_cookie_generator(|_| COOKIE);
+        for port in 0..100 {
+            l.handle_packet(start, Ok((build_hs_pack(test_induction()), flooder(port))));
+        }
+        assert_eq!(l.half_open_count(), 8);
+        let resp = l.handle_packet(
+            start + timeout / 2,
+            Ok((build_hs_pack(test_induction()), conn_addr())),
+        );
+        assert_matches!(resp, NotHandled(ConnectError::HalfOpenLimit(8)));
+
+        let later = start + timeout;
+        let resp = l.handle_packet(later, Ok((build_hs_pack(test_induction()), conn_addr())));
+        assert_matches!(resp, SendPacket((_, to)) if to == conn_addr());
+        assert_eq!(l.half_open_count(), 1);
+        let resp = l.handle_packet(later, Ok((build_hs_pack(conclusion), conn_addr())));
+        assert_matches!(resp, Connected(Some((_, to)), _) if to == conn_addr());
+    }
+
+    #[test]
+    fn allow_from() {
+        let mut l = Listen::new(
+            ConnInitSettings {
+                allow_from: Some(vec!["10.0.0.0/8".parse().unwrap()]),
+                ..ConnInitSettings::default()
+            },
+            false,
+        );
+        let allowed = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)), 8765);
+
+        // anyone else is rejected from the induction on, and isn't kept track of
+        for shake in [test_induction(), test_conclusion()] {
+            let resp = l.handle_packet(Instant::now(), Ok((build_hs_pack(shake), conn_addr())));
+            assert_matches!(
+                resp,
+                Reject(
+                    Some((
+                        Packet::Control(ControlPacket {
+                            control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                                shake_type: ShakeType::Rejection(RejectReason::Server(
+                                    ServerRejectReason::Forbidden
+                                )),
+                                ..
+                            }),
+                            ..
+                        }),
+                        to
+                    )),
+                    ConnectionReject::Rejecting(_)
+                ) if to == conn_addr()
+            );
+        }
+        assert_eq!(l.half_open_count(), 0);
+
+        let resp = l.handle_packet(
+            Instant::now(),
+            Ok((build_hs_pack(test_induction()), allowed)),
+        );
+        assert_matches!(resp, SendPacket((_, to)) if to == allowed);
+        assert_eq!(l.half_open_count(), 1);
+    }
 }
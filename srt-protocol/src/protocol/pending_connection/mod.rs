@@ -1,3 +1,5 @@
+#[cfg(feature = "authentication")]
+mod authentication;
 pub mod connect;
 mod hsv5;
 pub mod listen;
@@ -8,7 +10,7 @@ pub(crate) mod cookie;
 use std::{error::Error, fmt, io, net::SocketAddr};
 
 use crate::options::KeySize;
-use crate::{connection::Connection, options::StreamId, packet::*, settings::KeySettings};
+use crate::{connection::Connection, options::StreamId, packet::*, settings::AcceptParameters};
 
 #[non_exhaustive]
 #[derive(Debug)]
@@ -23,6 +25,7 @@ pub enum ConnectError {
     ConclusionExpected(HandshakeControlInfo),
     UnsupportedProtocolVersion(u32),
     InvalidHandshakeCookie(i32, i32),
+    HalfOpenLimit(usize),
     RendezvousExpected(HandshakeControlInfo),
     CookiesMatched(i32),
     ExpectedHsReq,
@@ -44,12 +47,12 @@ pub struct AccessControlRequest {
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum AccessControlResponse {
-    Accepted(Option<KeySettings>),
+    Accepted(AcceptParameters),
     Rejected(RejectReason),
     Dropped,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum ConnectionReject {
     /// local rejected remote
     Rejecting(RejectReason),
@@ -92,6 +95,10 @@ impl fmt::Display for ConnectError {
             InvalidHandshakeCookie(expected, got) => {
                 write!(f, "Received invalid cookie, expected {expected}, got {got}")
             }
+            HalfOpenLimit(max) => write!(
+                f,
+                "Induction dropped, already {max} half-open connections waiting for a conclusion"
+            ),
             RendezvousExpected(got) => write!(f, "Expected rendezvous packet, got {got:?}"),
             CookiesMatched(cookie) => write!(
                 f,
@@ -131,7 +138,7 @@ impl fmt::Display for ConnectionReject {
 }
 
 impl ConnectionReject {
-    fn reason(&self) -> RejectReason {
+    pub fn reason(&self) -> RejectReason {
         match self {
             ConnectionReject::Rejecting(r) | ConnectionReject::Rejected(r) => *r,
         }
@@ -139,3 +146,10 @@ impl ConnectionReject {
 }
 
 impl Error for ConnectionReject {}
+
+fn retries_exhausted(retries: u32) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("handshake was not answered after {retries} retries"),
+    )
+}
@@ -16,10 +16,15 @@ use crate::{
 
 use super::{
     cookie::gen_cookie,
-    hsv5::{gen_hsv5_response, start_hsv5_initiation, GenHsv5Result, StartedInitiator},
-    ConnectError, ConnectionReject, ConnectionResult,
+    hsv5::{
+        gen_hsv5_response, start_hsv5_initiation, FinishHsv5Result, GenHsv5Result, StartedInitiator,
+    },
+    retries_exhausted, ConnectError, ConnectionReject, ConnectionResult,
 };
 
+#[cfg(feature = "authentication")]
+use super::authentication;
+
 pub struct Rendezvous {
     init_settings: ConnInitSettings,
     local_addr: SocketAddr,
@@ -29,6 +34,11 @@ pub struct Rendezvous {
     last_packet: (Packet, SocketAddr),
     last_send: Option<Instant>,
     starting_seqnum: SeqNumber,
+    /// times the last packet was sent again
+    retries: u32,
+    /// The nonce the peer signs to prove its identity if it turns out to be the initiator, sent
+    /// with the waves if there are credentials
+    challenge: Option<[u8; 16]>,
 }
 
 // see haivision/srt/docs/handshake.md for documentation
@@ -53,19 +63,26 @@ impl Rendezvous {
         starting_seqnum: SeqNumber,
     ) -> Self {
         let cookie = gen_cookie(&local_addr);
+        #[cfg(feature = "authentication")]
+        let challenge = init_settings
+            .credentials
+            .as_ref()
+            .map(|_| authentication::challenge());
+        #[cfg(not(feature = "authentication"))]
+        let challenge = None;
         let last_packet = (
             ControlPacket {
                 dest_sockid: SocketId(0),
                 timestamp: TimeStamp::from_micros(0),
                 control_type: ControlTypes::Handshake(HandshakeControlInfo {
                     init_seq_num: starting_seqnum,
-                    max_packet_size: init_settings.max_packet_size,
+                    max_packet_size: init_settings.max_segment_size,
                     max_flow_size: init_settings.max_flow_size,
                     socket_id: init_settings.local_sockid,
                     shake_type: ShakeType::Waveahand,
                     peer_addr: local_addr.ip(),
                     syn_cookie: cookie, // TODO: !!
-                    info: Rendezvous::empty_flags(),
+                    info: Rendezvous::challenge_flags(challenge),
                 }),
             }
             .into(),
@@ -81,6 +98,8 @@ impl Rendezvous {
             remote_public,
             last_send: None,
             starting_seqnum,
+            retries: 0,
+            challenge,
         }
     }
 }
@@ -104,6 +123,14 @@ fn get_handshake(packet: &Packet) -> Result<&HandshakeControlInfo, ConnectError>
     }
 }
 
+/// The nonce the peer issued to sign, if it has credentials
+fn peer_challenge(info: &HandshakeControlInfo) -> Option<[u8; 16]> {
+    match &info.info {
+        HandshakeVsInfo::V5(hs) => hs.challenge,
+        _ => None,
+    }
+}
+
 fn extract_ext_info(
     info: &HandshakeControlInfo,
 ) -> Result<Option<&SrtControlPacket>, ConnectError> {
@@ -118,6 +145,13 @@ impl Rendezvous {
         HandshakeVsInfo::V5(HsV5Info::default())
     }
 
+    fn challenge_flags(challenge: Option<[u8; 16]>) -> HandshakeVsInfo {
+        HandshakeVsInfo::V5(HsV5Info {
+            challenge,
+            ..HsV5Info::default()
+        })
+    }
+
     fn transition(&mut self, state: RendezvousState) {
         debug!(
             "Rendezvous {:?} transitioning from {:?} to {:?}",
@@ -129,7 +163,7 @@ impl Rendezvous {
     fn gen_packet(&self, shake_type: ShakeType, info: HandshakeVsInfo) -> HandshakeControlInfo {
         HandshakeControlInfo {
             init_seq_num: self.starting_seqnum,
-            max_packet_size: self.init_settings.max_packet_size,
+            max_packet_size: self.init_settings.max_segment_size,
             max_flow_size: self.init_settings.max_flow_size,
             socket_id: self.init_settings.local_sockid,
             shake_type,
@@ -150,6 +184,7 @@ impl Rendezvous {
             self.remote_public,
         );
         self.last_packet = pack_pair.clone();
+        self.retries = 0;
         SendPacket(pack_pair)
     }
 
@@ -237,8 +272,13 @@ impl Rendezvous {
         match (info.shake_type, role) {
             (ShakeType::Waveahand, Initiator) => {
                 // NOTE: streamid not supported in rendezvous
-                let (hsv5, initiator) =
-                    start_hsv5_initiation(self.init_settings.clone(), None, now);
+                let (hsv5, initiator) = start_hsv5_initiation(
+                    self.init_settings.clone(),
+                    None,
+                    self.cookie,
+                    peer_challenge(info),
+                    now,
+                );
 
                 self.transition(AttentionInitiator(hsv5.clone(), initiator));
 
@@ -247,7 +287,7 @@ impl Rendezvous {
             (ShakeType::Waveahand, Responder) => {
                 self.starting_seqnum = info.init_seq_num; // responder, take initiator's seqnum
                 self.transition(AttentionResponder(now));
-                self.send_conclusion(info.socket_id, Rendezvous::empty_flags())
+                self.send_conclusion(info.socket_id, Rendezvous::challenge_flags(self.challenge))
             }
             (ShakeType::Conclusion, role) => {
                 let ext_info = match extract_ext_info(info) {
@@ -268,6 +308,7 @@ impl Rendezvous {
                                     );
                                 }
                             },
+                            self.challenge,
                             now,
                         ) {
                             GenHsv5Result::Accept(h, c) => (h, c),
@@ -282,8 +323,13 @@ impl Rendezvous {
                         hsv5
                     }
                     (Initiator, None) => {
-                        let (hsv5, initiator) =
-                            start_hsv5_initiation(self.init_settings.clone(), None, now); // NOTE: streamid not supported in rendezvous
+                        let (hsv5, initiator) = start_hsv5_initiation(
+                            self.init_settings.clone(),
+                            None,
+                            self.cookie,
+                            peer_challenge(info),
+                            now,
+                        ); // NOTE: streamid not supported in rendezvous
                         self.transition(FineInitiator(hsv5.clone(), initiator));
                         hsv5
                     }
@@ -306,6 +352,7 @@ impl Rendezvous {
         info: &HandshakeControlInfo,
         hsv5: HandshakeVsInfo,
         initiator: StartedInitiator,
+        timestamp: TimeStamp,
         now: Instant,
     ) -> ConnectionResult {
         match info.shake_type {
@@ -316,8 +363,11 @@ impl Rendezvous {
 
                     let settings =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
-                            Ok(s) => s,
-                            Err(r) => return NotHandled(r),
+                            FinishHsv5Result::Accept(s) => s,
+                            FinishHsv5Result::NotHandled(r) => return NotHandled(r),
+                            FinishHsv5Result::Reject(r) => {
+                                return self.make_rejection(info, timestamp, r)
+                            }
                         };
 
                     self.set_connected(settings, Some(agreement.clone()), Some(agreement))
@@ -353,6 +403,7 @@ impl Rendezvous {
                     info,
                     self.remote_public,
                     induction_time,
+                    self.challenge,
                     now,
                 ) {
                     GenHsv5Result::Accept(h, c) => (h, c),
@@ -373,6 +424,7 @@ impl Rendezvous {
         info: &HandshakeControlInfo,
         hsv5: HandshakeVsInfo,
         initiator: StartedInitiator,
+        timestamp: TimeStamp,
         now: Instant,
     ) -> ConnectionResult {
         match info.shake_type {
@@ -382,8 +434,11 @@ impl Rendezvous {
 
                     let settings =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
-                            Ok(s) => s,
-                            Err(r) => return NotHandled(r),
+                            FinishHsv5Result::Accept(s) => s,
+                            FinishHsv5Result::NotHandled(r) => return NotHandled(r),
+                            FinishHsv5Result::Reject(r) => {
+                                return self.make_rejection(info, timestamp, r)
+                            }
                         };
 
                     self.set_connected(settings, Some(agreement.clone()), Some(agreement))
@@ -424,6 +479,7 @@ impl Rendezvous {
         &mut self,
         info: &HandshakeControlInfo,
         initiator: StartedInitiator,
+        timestamp: TimeStamp,
         now: Instant,
     ) -> ConnectionResult {
         match info.shake_type {
@@ -431,8 +487,11 @@ impl Rendezvous {
                 Ok(Some(SrtControlPacket::HandshakeResponse(_))) => {
                     let connection =
                         match initiator.finish_hsv5_initiation(info, self.remote_public, now) {
-                            Ok(c) => c,
-                            Err(e) => return NotHandled(e),
+                            FinishHsv5Result::Accept(c) => c,
+                            FinishHsv5Result::NotHandled(e) => return NotHandled(e),
+                            FinishHsv5Result::Reject(r) => {
+                                return self.make_rejection(info, timestamp, r)
+                            }
                         };
 
                     let agreement =
@@ -485,20 +544,19 @@ impl Rendezvous {
                 let hs = get_handshake(&packet);
                 match (self.state.clone(), hs) {
                     (Waving, Ok(hs)) => self.handle_waving(hs, packet.timestamp(), now),
-                    (AttentionInitiator(hsv5, initiator), Ok(hs)) => {
-                        self.handle_attention_initiator(hs, hsv5, initiator, now)
-                    }
+                    (AttentionInitiator(hsv5, initiator), Ok(hs)) => self
+                        .handle_attention_initiator(hs, hsv5, initiator, packet.timestamp(), now),
                     (AttentionResponder(induction_time), Ok(hs)) => {
                         self.handle_attention_responder(hs, packet.timestamp(), induction_time, now)
                     }
                     (InitiatedInitiator(initiator), Ok(hs)) => {
-                        self.handle_initiated_initiator(hs, initiator, now)
+                        self.handle_initiated_initiator(hs, initiator, packet.timestamp(), now)
                     }
                     (InitiatedResponder(connection), _) => {
                         self.handle_initiated_responder(&packet, connection)
                     }
                     (FineInitiator(hsv5, initiator), Ok(hs)) => {
-                        self.handle_fine_initiator(hs, hsv5, initiator, now)
+                        self.handle_fine_initiator(hs, hsv5, initiator, packet.timestamp(), now)
                     }
                     (FineResponder(conn), _) => self.handle_fine_responder(&packet, conn),
                     (_, Err(e)) => NotHandled(e),
@@ -516,7 +574,25 @@ impl Rendezvous {
     }
 
     pub fn handle_tick(&mut self, now: Instant) -> ConnectionResult {
-        self.last_send = Some(now);
-        SendPacket(self.last_packet.clone())
+        let retry_interval = self.init_settings.handshake_retry_interval;
+        if self
+            .last_send
+            .is_some_and(|last| now < last + retry_interval)
+        {
+            return NoAction;
+        }
+
+        // the first tick sends the initial wavehand, it's not a retry
+        let retry = self.last_send.is_some();
+        match self.init_settings.max_handshake_retries {
+            Some(max) if retry && self.retries >= max => Failure(retries_exhausted(self.retries)),
+            _ => {
+                if retry {
+                    self.retries += 1;
+                }
+                self.last_send = Some(now);
+                SendPacket(self.last_packet.clone())
+            }
+        }
     }
 }
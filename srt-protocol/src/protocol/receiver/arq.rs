@@ -1,4 +1,5 @@
 use std::{
+    cmp::min,
     ops::Range,
     time::{Duration, Instant},
 };
@@ -8,7 +9,8 @@ use arraydeque::{behavior::Wrapping, ArrayDeque};
 use bytes::Bytes;
 
 use crate::{
-    options::PacketCount,
+    connection::MessageInfo,
+    options::{DeliveryMode, PacketCount, PacketRate, Percent, RecvGranularity},
     packet::*,
     protocol::{
         receiver::{
@@ -168,6 +170,9 @@ pub struct AutomaticRepeatRequestAlgorithm {
     ack_history_window: AckHistoryWindow,
 
     rtt: Rtt,
+
+    /// The size the receive buffer may grow to with the arrival rate
+    buffer_cap: Option<PacketCount>,
 }
 
 impl AutomaticRepeatRequestAlgorithm {
@@ -190,6 +195,7 @@ impl AutomaticRepeatRequestAlgorithm {
             ),
             ack_history_window: AckHistoryWindow::new(tsbpd_latency, init_seq_num),
             rtt: Rtt::default(),
+            buffer_cap: None,
         }
     }
 
@@ -205,6 +211,11 @@ impl AutomaticRepeatRequestAlgorithm {
             .unacked_packet_count(self.receive_buffer.next_ack_dsn())
     }
 
+    /// Everything before it was received, or given up on
+    pub fn next_ack_dsn(&self) -> SeqNumber {
+        self.receive_buffer.next_ack_dsn()
+    }
+
     pub fn next_message_release_time(&self) -> Option<Instant> {
         self.receive_buffer.next_message_release_time()
     }
@@ -224,6 +235,11 @@ impl AutomaticRepeatRequestAlgorithm {
     }
 
     pub fn on_full_ack_event(&mut self, now: Instant) -> Option<Acknowledgement> {
+        let arrival_speed = self.arrival_speed.calculate();
+        if let Some((packets, _)) = arrival_speed {
+            self.grow_buffer(now, PacketRate(packets.into()));
+        }
+
         // NOTE: if a Full ACK is sent when the receive buffer is full, the Sender will stall
         if self.receive_buffer.buffer_available() == 0 {
             return None;
@@ -235,8 +251,6 @@ impl AutomaticRepeatRequestAlgorithm {
             self.receive_buffer.next_ack_dsn(),
         )?;
 
-        let arrival_speed = self.arrival_speed.calculate();
-
         let statistics = AckStatistics {
             rtt: self.rtt,
             buffer_available: self.receive_buffer.buffer_available() as u32,
@@ -248,6 +262,18 @@ impl AutomaticRepeatRequestAlgorithm {
         Some(Acknowledgement::Full(dsn, statistics, fasn))
     }
 
+    // room for what arrives over the latency window, and the round trip the losses take to
+    // recover, at the rate it's arriving at, with a quarter to spare
+    fn grow_buffer(&mut self, now: Instant, arrival_rate: PacketRate) {
+        let cap = match self.buffer_cap {
+            Some(cap) => cap,
+            None => return,
+        };
+        let window = self.receive_buffer.tsbpd_latency(now) + self.rtt.mean_as_duration();
+        let needed = arrival_rate * (window + window / 4);
+        self.receive_buffer.grow_to(min(needed, cap));
+    }
+
     pub fn on_nak_event(&mut self, now: Instant) -> Option<CompressedLossList> {
         self.receive_buffer.prepare_loss_list(now, self.rtt.mean())
     }
@@ -320,9 +346,66 @@ impl AutomaticRepeatRequestAlgorithm {
         self.receive_buffer.pop_next_message(now)
     }
 
+    pub fn pop_next_message_with_info(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<(Instant, Bytes, MessageInfo)>, MessageError> {
+        self.receive_buffer.pop_next_message_with_info(now)
+    }
+
+    pub fn tsbpd_latency(&self, now: Instant) -> Duration {
+        self.receive_buffer.tsbpd_latency(now)
+    }
+
+    pub fn set_tsbpd_latency(&mut self, now: Instant, latency: Duration) {
+        self.receive_buffer.set_tsbpd_latency(now, latency)
+    }
+
+    pub fn set_delivery_mode(&mut self, delivery_mode: DeliveryMode) {
+        self.receive_buffer.set_delivery_mode(delivery_mode)
+    }
+
+    pub fn set_granularity(&mut self, granularity: RecvGranularity) {
+        self.receive_buffer.set_granularity(granularity)
+    }
+
+    pub fn set_drop_margin(&mut self, drop_margin: Percent) {
+        self.receive_buffer.set_drop_margin(drop_margin)
+    }
+
+    /// Starts over from this round trip time, until it's measured
+    pub fn set_rtt(&mut self, rtt: Rtt) {
+        self.rtt = rtt
+    }
+
+    /// Lets the receive buffer grow with the arrival rate, up to `cap`
+    pub fn set_buffer_cap(&mut self, cap: Option<PacketCount>) {
+        self.buffer_cap = cap;
+    }
+
+    pub fn buffer_size(&self) -> PacketCount {
+        self.receive_buffer.buffer_size()
+    }
+
     pub fn rx_acknowledged_time(&self) -> Duration {
         self.receive_buffer.rx_acknowledged_time()
     }
+
+    pub fn loss_list(&self) -> Vec<Range<SeqNumber>> {
+        self.receive_buffer.loss_list()
+    }
+
+    pub fn rx_buffered_packets(&self) -> u64 {
+        self.receive_buffer.rx_buffered_packets()
+    }
+
+    pub fn rx_buffered_time(&self) -> Duration {
+        self.receive_buffer.rx_buffered_time()
+    }
+
+    pub fn rx_pending_packets(&self, now: Instant) -> u64 {
+        self.receive_buffer.rx_pending_packets(now)
+    }
 }
 
 #[cfg(test)]
@@ -629,6 +712,53 @@ mod automatic_repeat_request_algorithm {
         assert_eq!(arq.on_nak_event(now), None);
     }
 
+    #[test]
+    fn buffer_grows_with_arrival_rate() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(0);
+        let mut arq = AutomaticRepeatRequestAlgorithm::new(
+            start,
+            Duration::from_millis(800),
+            true,
+            init_seq_num,
+            PacketCount(32),
+        );
+        arq.set_rtt(Rtt::from_estimate(Duration::from_millis(200)));
+
+        let mut now = start;
+        let mut seq_number = init_seq_num;
+        let mut arrive = |arq: &mut AutomaticRepeatRequestAlgorithm, count, interval| {
+            for _ in 0..count {
+                now += interval;
+                let _ = arq.handle_data_packet(
+                    now,
+                    DataPacket {
+                        seq_number,
+                        ..basic_pack()
+                    },
+                );
+                seq_number += 1;
+            }
+            let _ = arq.on_full_ack_event(now);
+        };
+
+        // 1000 packets/s, with nowhere to grow to
+        arrive(&mut arq, 20, Duration::from_millis(1));
+        assert_eq!(arq.buffer_size(), PacketCount(32));
+
+        // 1000 packets/s over the latency window and a round trip, with a quarter to spare
+        arq.set_buffer_cap(Some(PacketCount(1000)));
+        arrive(&mut arq, 1, Duration::from_millis(1));
+        assert_eq!(arq.buffer_size(), PacketCount(1000));
+        arq.set_buffer_cap(Some(PacketCount(5000)));
+        arrive(&mut arq, 1, Duration::from_millis(1));
+        assert_eq!(arq.buffer_size(), PacketCount(1250));
+
+        // it doesn't shrink back as the rate goes down
+        arrive(&mut arq, 20, Duration::from_millis(10));
+        assert_eq!(arq.buffer_size(), PacketCount(1250));
+    }
+
     #[test]
     fn arrival_speed() {
         let seconds = Duration::from_secs;
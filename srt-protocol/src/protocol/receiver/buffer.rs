@@ -1,5 +1,5 @@
 use std::{
-    cmp::min,
+    cmp::{max, min},
     collections::VecDeque,
     convert::TryFrom,
     ops::Range,
@@ -8,9 +8,14 @@ use std::{
 
 use bytes::{Bytes, BytesMut};
 
-use crate::{options::PacketCount, packet::*};
+use crate::{
+    connection::MessageInfo,
+    options::{DeliveryMode, PacketCount, Percent, RecvGranularity},
+    packet::*,
+};
 
 use super::{
+    latency::TsbpdLatency,
     time::{ClockAdjustment, SynchronizedRemoteClock},
     DataPacketAction, DataPacketError,
 };
@@ -74,13 +79,12 @@ impl BufferPacket {
         matches!(self, BufferPacket::Received(data) if data.message_loc.contains(PacketLocation::FIRST))
     }
 
-    fn lost_or_dropped(&self) -> Option<SeqNumber> {
+    fn lost(&self) -> Option<SeqNumber> {
         match self {
             BufferPacket::Lost(LostPacket {
                 data_sequence_number: sn,
                 ..
-            })
-            | BufferPacket::Dropped(sn) => Some(*sn),
+            }) => Some(*sn),
             _ => None,
         }
     }
@@ -166,9 +170,11 @@ pub struct MessageError {
 
 #[derive(Debug)]
 pub struct ReceiveBuffer {
-    tsbpd_latency: Duration,
+    tsbpd_latency: TsbpdLatency,
     /// Adds an extra delay to the TSBPD threshold for dropping too late packets
     tsbpd_tolerance: Duration,
+    // how much longer than the latency missing packets are waited for, as a share of it
+    drop_margin: Percent,
 
     // Sequence number that all packets up to have been received + 1
     lrsn: SeqNumber,
@@ -177,8 +183,12 @@ pub struct ReceiveBuffer {
     seqno0: SeqNumber,
 
     too_late_packet_drop: bool,
+    delivery_mode: DeliveryMode,
+    granularity: RecvGranularity,
     remote_clock: SynchronizedRemoteClock,
     buffer: VecDeque<BufferPacket>,
+    // the rest of the payloads of the message released last, in payload granularity
+    payloads: VecDeque<(Instant, Bytes, MessageInfo)>,
     max_buffer_size: PacketCount,
 }
 
@@ -191,20 +201,48 @@ impl ReceiveBuffer {
         max_buffer_size: PacketCount,
     ) -> Self {
         Self {
-            tsbpd_latency,
+            tsbpd_latency: TsbpdLatency::new(socket_start_time, tsbpd_latency),
             // TODO: perhaps make this configurable
             tsbpd_tolerance: Duration::from_millis(5),
+            drop_margin: Percent(0),
             too_late_packet_drop,
+            delivery_mode: DeliveryMode::Strict,
+            granularity: RecvGranularity::Message,
             lrsn: init_seq_num,
             seqno0: init_seq_num,
             remote_clock: SynchronizedRemoteClock::new(socket_start_time),
             buffer: VecDeque::with_capacity(max_buffer_size.into()),
+            payloads: VecDeque::new(),
             max_buffer_size,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.buffer.is_empty()
+        self.buffer.is_empty() && self.payloads.is_empty()
+    }
+
+    pub fn tsbpd_latency(&self, now: Instant) -> Duration {
+        self.tsbpd_latency.at(now)
+    }
+
+    /// Moves the TSBPD latency towards `latency` gradually, so that packets already in the
+    /// buffer are neither held back for the whole difference nor all made due at once
+    pub fn set_tsbpd_latency(&mut self, now: Instant, latency: Duration) {
+        self.tsbpd_latency.set_target(now, latency);
+    }
+
+    pub fn set_delivery_mode(&mut self, delivery_mode: DeliveryMode) {
+        self.delivery_mode = delivery_mode;
+    }
+
+    pub fn set_granularity(&mut self, granularity: RecvGranularity) {
+        self.granularity = granularity;
+    }
+
+    /// Waits this much longer than the latency, as a share of it, before giving up on missing
+    /// packets, holding back the data after them
+    pub fn set_drop_margin(&mut self, drop_margin: Percent) {
+        self.drop_margin = drop_margin;
     }
 
     /// Data Sequence Number of the packet following the last acknowledged packet
@@ -214,6 +252,7 @@ impl ReceiveBuffer {
 
     pub fn clear(&mut self) {
         self.buffer.clear();
+        self.payloads.clear();
     }
 
     pub fn synchronize_clock(
@@ -229,6 +268,16 @@ impl ReceiveBuffer {
         usize::from(self.max_buffer_size) - self.buffer.len()
     }
 
+    /// The size of the buffer, in packets
+    pub fn buffer_size(&self) -> PacketCount {
+        self.max_buffer_size
+    }
+
+    /// Makes room for `size` packets, if there isn't already
+    pub fn grow_to(&mut self, size: PacketCount) {
+        self.max_buffer_size = max(self.max_buffer_size, size);
+    }
+
     // next expected packet (1 + last received packet)
     fn next_packet_dsn(&self) -> SeqNumber {
         self.seqno0 + u32::try_from(self.buffer.len()).unwrap()
@@ -266,19 +315,31 @@ impl ReceiveBuffer {
         &mut self,
         now: Instant,
     ) -> Result<Option<(Instant, Bytes)>, MessageError> {
+        let message = self.pop_next_message_with_info(now)?;
+        Ok(message.map(|(time, data, _)| (time, data)))
+    }
+
+    pub fn pop_next_message_with_info(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<(Instant, Bytes, MessageInfo)>, MessageError> {
+        if let Some(payload) = self.payloads.pop_front() {
+            return Ok(Some(payload));
+        }
+
         let timestamp = match self.front_ts() {
             Some(timestamp) => timestamp,
-            None => return self.drop_too_late_packets(now),
+            None => return self.skip_to_available_message(now),
         };
 
         let sent_time = self.remote_clock.instant_from(timestamp);
-        if now < sent_time + self.tsbpd_latency {
+        if now < self.tsbpd_latency.release_time(sent_time) {
             return Ok(None);
         }
 
         let packet_count = match self.next_message_packet_count() {
             Some(packet_count) => packet_count,
-            None => return self.drop_too_late_packets(now),
+            None => return self.skip_to_available_message(now),
         };
 
         self.seqno0 += u32::try_from(packet_count).unwrap();
@@ -286,6 +347,8 @@ impl ReceiveBuffer {
         let release_time = self.remote_clock.monotonic_instant_from(timestamp);
         let message = if packet_count == 1 {
             self.release_single_packet_message(release_time)
+        } else if self.granularity == RecvGranularity::Payload {
+            self.release_payloads(release_time, packet_count)
         } else {
             self.release_full_message(release_time, packet_count)
         };
@@ -296,28 +359,54 @@ impl ReceiveBuffer {
         self.buffer.front()?.data_packet().map(|d| d.timestamp)
     }
 
-    fn release_single_packet_message(&mut self, release_time: Instant) -> Option<(Instant, Bytes)> {
-        Some((
-            release_time,
-            self.buffer.pop_front()?.into_data_packet()?.payload,
-        ))
+    fn release_single_packet_message(
+        &mut self,
+        release_time: Instant,
+    ) -> Option<(Instant, Bytes, MessageInfo)> {
+        let packet = self.buffer.pop_front()?.into_data_packet()?;
+        let info = MessageInfo {
+            message_number: packet.message_number,
+            location: packet.message_loc,
+        };
+        Some((release_time, packet.payload, info))
     }
 
     fn release_full_message(
         &mut self,
         release_time: Instant,
         packet_count: usize,
-    ) -> Option<(Instant, Bytes)> {
-        Some((
-            release_time,
-            self.buffer
-                .drain(0..packet_count)
-                .fold(BytesMut::new(), |mut bytes, pack| {
-                    bytes.extend(pack.into_data_packet().unwrap().payload);
-                    bytes
-                })
-                .freeze(),
-        ))
+    ) -> Option<(Instant, Bytes, MessageInfo)> {
+        let mut packets = self
+            .buffer
+            .drain(0..packet_count)
+            .map(|pack| pack.into_data_packet().unwrap());
+        let first = packets.next()?;
+        let info = MessageInfo {
+            message_number: first.message_number,
+            location: PacketLocation::ONLY,
+        };
+        let mut bytes = BytesMut::from(&first.payload[..]);
+        for packet in packets {
+            bytes.extend(packet.payload);
+        }
+        Some((release_time, bytes.freeze(), info))
+    }
+
+    fn release_payloads(
+        &mut self,
+        release_time: Instant,
+        packet_count: usize,
+    ) -> Option<(Instant, Bytes, MessageInfo)> {
+        let payloads = self.buffer.drain(0..packet_count).map(|pack| {
+            let packet = pack.into_data_packet().unwrap();
+            let info = MessageInfo {
+                message_number: packet.message_number,
+                location: packet.message_loc,
+            };
+            (release_time, packet.payload, info)
+        });
+        self.payloads.extend(payloads);
+        self.payloads.pop_front()
     }
 
     pub fn prepare_loss_list(
@@ -344,23 +433,77 @@ impl ReceiveBuffer {
         CompressedLossList::try_from_iter(loss_list)
     }
 
+    /// The packets still missing, as ranges of consecutive sequence numbers
+    pub fn loss_list(&self) -> Vec<Range<SeqNumber>> {
+        let mut loss_list: Vec<Range<SeqNumber>> = Vec::new();
+        let lost = self
+            .buffer
+            .range(self.lost_list_index()..)
+            .filter(|p| matches!(p, BufferPacket::Lost(_)))
+            .map(|p| p.data_sequence_number());
+        for seq_number in lost {
+            match loss_list.last_mut() {
+                Some(range) if range.end == seq_number => range.end = seq_number + 1,
+                _ => loss_list.push(seq_number..seq_number + 1),
+            }
+        }
+        loss_list
+    }
+
     /// Returns how many packets were actually dropped
     pub fn drop_packets(&mut self, range: Range<SeqNumber>) -> usize {
         // if start of the range has been dropped already, just drop everything after
         let first_idx = self.clamped_index_for_seqno(range.start);
         let last_idx = self.clamped_index_for_seqno(range.end);
-        self.buffer
+        let dropped = self
+            .buffer
             .range_mut(first_idx..last_idx)
             .filter_map(|p| p.drop_unreceived())
-            .count()
+            .count();
+        // the sender gave up on them and trimmed its buffer past them, so they're acknowledged
+        // like received ones, or the ACKs would fall behind the sender's buffer until released
+        if dropped > 0 {
+            self.recalculate_lrsn(0);
+        }
+        dropped
     }
 
     pub fn next_message_release_time(&self) -> Option<Instant> {
-        self.buffer
-            .front()
-            .filter(|p| p.is_first())?
-            .data_packet()
-            .map(|d| self.remote_clock.instant_from(d.timestamp) + self.tsbpd_latency)
+        // released already, the payloads just haven't been taken yet
+        if let Some((release_time, _, _)) = self.payloads.front() {
+            return Some(*release_time);
+        }
+
+        if self.delivery_mode == DeliveryMode::AsAvailable
+            && self.next_message_packet_count().is_none()
+        {
+            if let Some((_, release_time)) = self.next_message_after_gap() {
+                return Some(release_time);
+            }
+        }
+
+        let front = self.buffer.front()?;
+        if !front.is_first() {
+            // the packets in front of the next message are given up on once it's due
+            return self
+                .buffer
+                .iter()
+                .find(|p| p.is_first())
+                .filter(|_| self.too_late_packet_drop)
+                .and_then(|p| self.packet_release_time(p))
+                .map(|release_time| release_time + self.drop_margin_at(release_time));
+        }
+        let release_time = self.packet_release_time(front)?;
+
+        // a partially received message can't be released, so there is nothing to do until the
+        // rest of it arrives or it is late enough to be dropped
+        match self.next_message_packet_count() {
+            Some(_) => Some(release_time),
+            None if self.too_late_packet_drop => {
+                Some(release_time + self.drop_margin_at(release_time) + self.tsbpd_tolerance)
+            }
+            None => None,
+        }
     }
 
     fn append_next(&mut self, data: DataPacket) -> Result<DataPacketAction, DataPacketError> {
@@ -397,9 +540,20 @@ impl ReceiveBuffer {
                 buffer_required,
             })
         } else {
+            // the sender only retransmits what it already sent, so the packets before this one are
+            // most likely retransmitted too and still on their way. Leave them to the periodic NAK
+            // instead of reporting them right away.
+            let retransmitted = data.retransmitted;
             self.append_lost_packets(now, &lost);
             self.append_data(data);
-            Ok(DataPacketAction::ReceivedWithLoss(lost.into()))
+            if retransmitted {
+                Ok(DataPacketAction::Received {
+                    lrsn: self.lrsn,
+                    recovered: false,
+                })
+            } else {
+                Ok(DataPacketAction::ReceivedWithLoss(lost.into()))
+            }
         }
     }
 
@@ -448,65 +602,125 @@ impl ReceiveBuffer {
     }
 
     fn next_message_packet_count(&self) -> Option<usize> {
-        let first = self.buffer.front()?.data_packet()?;
+        self.message_packet_count(0)
+    }
+
+    fn message_packet_count(&self, index: usize) -> Option<usize> {
+        let first = self.buffer.get(index)?.data_packet()?;
         self.buffer
-            .iter()
+            .range(index..)
             // once stabilized in std, take_while & filter_map can be replaced with map_while
             .take_while(|p| p.in_message(first.message_number))
             .try_fold(MessagePacketCount::new(), |a, p| a.accumulate(p))?
             .calculate()
     }
 
+    fn drop_margin_at(&self, now: Instant) -> Duration {
+        self.tsbpd_latency.at(now) * self.drop_margin
+    }
+
+    fn packet_release_time(&self, packet: &BufferPacket) -> Option<Instant> {
+        let sent_time = self
+            .remote_clock
+            .instant_from(packet.data_packet()?.timestamp);
+        Some(self.tsbpd_latency.release_time(sent_time))
+    }
+
+    /// The first complete message behind the packets at the front that can't be released, and when
+    /// it's due
+    fn next_message_after_gap(&self) -> Option<(usize, Instant)> {
+        let index = (1..self.buffer.len()).find(|index| {
+            self.buffer[*index].is_first() && self.message_packet_count(*index).is_some()
+        })?;
+        Some((index, self.packet_release_time(&self.buffer[index])?))
+    }
+
+    /// In as-available delivery, gives up on the packets in front of the next message once it's
+    /// due, otherwise waits for them until they are too late
+    fn skip_to_available_message(
+        &mut self,
+        now: Instant,
+    ) -> Result<Option<(Instant, Bytes, MessageInfo)>, MessageError> {
+        if self.delivery_mode == DeliveryMode::Strict {
+            return self.drop_too_late_packets(now);
+        }
+        let (index, release_time) = match self.next_message_after_gap() {
+            Some((index, release_time)) if now >= release_time => (index, release_time),
+            _ => return self.drop_too_late_packets(now),
+        };
+
+        let begin_packet = self.seqno0;
+        let end_packet = begin_packet + u32::try_from(index).unwrap();
+
+        self.seqno0 = end_packet;
+        self.buffer.drain(0..index);
+        self.recalculate_lrsn(0);
+
+        Err(MessageError {
+            delay: TimeSpan::from_interval(release_time, now),
+            too_late_packets: begin_packet..end_packet,
+        })
+    }
+
     /// Drops the packets that are deemed to be too late
     /// i.e.: there is a packet after it that is ready to be released
     fn drop_too_late_packets(
         &mut self,
         now: Instant,
-    ) -> Result<Option<(Instant, Bytes)>, MessageError> {
+    ) -> Result<Option<(Instant, Bytes, MessageInfo)>, MessageError> {
         if !self.too_late_packet_drop {
             return Ok(None);
         }
 
-        let data_packets = self.buffer.iter().map(|packet| {
-            packet.data_packet().map(|data| {
-                (
-                    data.seq_number,
-                    self.remote_clock.instant_from(data.timestamp),
-                    data.message_loc,
-                )
-            })
-        });
-
-        let tsbpd_threshold = now - self.tsbpd_latency - self.tsbpd_tolerance;
-        let too_late_packets = data_packets.take_while(|packet| {
-            packet.map_or(true, |(_, packet_time, message_loc)| {
-                packet_time <= tsbpd_threshold || !message_loc.contains(PacketLocation::FIRST)
+        let tsbpd_latency = self.tsbpd_latency.at(now);
+        let drop_margin = tsbpd_latency * self.drop_margin;
+        let tsbpd_threshold = now - tsbpd_latency - drop_margin - self.tsbpd_tolerance;
+        let too_late_count = self
+            .buffer
+            .iter()
+            .take_while(|packet| {
+                packet.data_packet().map_or(true, |data| {
+                    self.remote_clock.instant_from(data.timestamp) <= tsbpd_threshold
+                        || !data.message_loc.contains(PacketLocation::FIRST)
+                })
             })
-        });
+            .count();
 
-        let dropped_packets = too_late_packets.fold(None, |last, packet| match (last, packet) {
-            (None, Some((seq_number, packet_time, _))) => Some((seq_number, packet_time)),
-            (Some((_, first_packet_time)), Some((seq_number, _, _))) => {
-                Some((seq_number, first_packet_time))
-            }
-            (last, _) => last,
-        });
+        // missing packets are waited for until the message after them is due, then they are
+        // dropped right away, so it can be released on time
+        let next_release_time = self
+            .buffer
+            .get(too_late_count)
+            .and_then(|packet| self.packet_release_time(packet))
+            .filter(|release_time| now >= *release_time + drop_margin);
+        let drop_count = match next_release_time {
+            Some(_) => too_late_count,
+            None => self
+                .buffer
+                .range(..too_late_count)
+                .rposition(|packet| packet.data_packet().is_some())
+                .map_or(0, |index| index + 1),
+        };
 
-        let (last_seq_number, first_packet_time) = match dropped_packets {
-            Some(packets) => packets,
-            None => return Ok(None),
+        let first_packet_time = self
+            .buffer
+            .range(..drop_count)
+            .find_map(|packet| packet.data_packet())
+            .map(|data| self.remote_clock.instant_from(data.timestamp) + tsbpd_latency);
+        let due_time = match first_packet_time.or(next_release_time) {
+            Some(due_time) if drop_count > 0 => due_time,
+            _ => return Ok(None),
         };
 
         let begin_packet = self.seqno0;
-        let end_packet = last_seq_number + 1;
-        let drop_count = end_packet.saturating_sub(begin_packet);
+        let end_packet = begin_packet + u32::try_from(drop_count).unwrap();
 
         self.seqno0 = end_packet;
         self.buffer.drain(0..drop_count);
         self.recalculate_lrsn(0);
 
         Err(MessageError {
-            delay: TimeSpan::from_interval(first_packet_time + self.tsbpd_latency, now),
+            delay: TimeSpan::from_interval(due_time, now),
             too_late_packets: begin_packet..end_packet,
         })
     }
@@ -515,7 +729,7 @@ impl ReceiveBuffer {
         self.lrsn = self
             .buffer
             .range(start_idx..)
-            .filter_map(|p| p.lost_or_dropped())
+            .filter_map(|p| p.lost())
             .next()
             .unwrap_or_else(|| self.next_packet_dsn())
     }
@@ -534,6 +748,37 @@ impl ReceiveBuffer {
             Duration::from_micros(0)
         }
     }
+
+    /// The number of received packets in the buffer waiting to be released, acknowledged or not
+    pub fn rx_buffered_packets(&self) -> u64 {
+        self.received_packets().count() as u64
+    }
+
+    /// The timespan between the oldest and the newest received packet in the buffer
+    pub fn rx_buffered_time(&self) -> Duration {
+        let mut received = self.received_packets();
+        match (received.next(), received.last()) {
+            (Some(s), Some(e)) => Duration::from_micros(
+                u64::try_from((e.timestamp - s.timestamp).as_micros()).unwrap_or(0),
+            ),
+            _ => Duration::from_micros(0),
+        }
+    }
+
+    /// The number of received packets whose TSBPD release time has not been reached yet
+    pub fn rx_pending_packets(&self, now: Instant) -> u64 {
+        self.received_packets()
+            .filter(|d| {
+                now < self
+                    .tsbpd_latency
+                    .release_time(self.remote_clock.instant_from(d.timestamp))
+            })
+            .count() as u64
+    }
+
+    fn received_packets(&self) -> impl Iterator<Item = &DataPacket> {
+        self.buffer.iter().filter_map(|p| p.data_packet())
+    }
 }
 
 #[cfg(test)]
@@ -595,7 +840,10 @@ mod receive_buffer {
             })
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 1);
-        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + buf.tsbpd_tolerance)
+        );
         assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
     }
 
@@ -622,7 +870,10 @@ mod receive_buffer {
             })
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 1);
-        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + buf.tsbpd_tolerance)
+        );
         assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
 
         // 1 lost packet
@@ -638,7 +889,10 @@ mod receive_buffer {
             Ok(ReceivedWithLoss([init_seq_num + 1].iter().collect()))
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 1);
-        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + buf.tsbpd_tolerance)
+        );
         assert_eq!(
             buf.pop_next_message(start + tsbpd * 2),
             Err(MessageError {
@@ -671,7 +925,10 @@ mod receive_buffer {
             })
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 1);
-        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + buf.tsbpd_tolerance)
+        );
         assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
         assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
 
@@ -690,7 +947,10 @@ mod receive_buffer {
             })
         );
         assert_eq!(buf.next_ack_dsn(), init_seq_num + 2);
-        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + buf.tsbpd_tolerance)
+        );
         assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
     }
 
@@ -803,6 +1063,196 @@ mod receive_buffer {
         assert_eq!(buf.buffer.len(), 0);
     }
 
+    #[test]
+    fn payload_granularity() {
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+        buf.set_granularity(RecvGranularity::Payload);
+        for (n, (message_loc, payload)) in [
+            (PacketLocation::FIRST, "hello"),
+            (PacketLocation::empty(), "yas"),
+            (PacketLocation::LAST, "nas"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let _ = buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + n as u32,
+                    message_loc,
+                    payload: payload.as_bytes().into(),
+                    ..basic_pack()
+                },
+            );
+        }
+        // incomplete until the last packet
+        assert_eq!(buf.pop_next_message(start), Ok(None));
+
+        // then released a packet at a time, all at once
+        let now = start + tsbpd;
+        for payload in ["hello", "yas", "nas"] {
+            assert!(!buf.is_empty());
+            assert!(buf.next_message_release_time().unwrap() <= now);
+            assert_eq!(
+                buf.pop_next_message(now),
+                Ok(Some((start, payload.as_bytes().into())))
+            );
+        }
+        assert!(buf.is_empty());
+        assert_eq!(buf.next_message_release_time(), None);
+        assert_eq!(buf.pop_next_message(now), Ok(None));
+    }
+
+    #[test]
+    fn message_info() {
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+        let locations = [
+            PacketLocation::FIRST,
+            PacketLocation::MIDDLE,
+            PacketLocation::LAST,
+        ];
+
+        for granularity in [RecvGranularity::Message, RecvGranularity::Payload] {
+            let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+            buf.set_granularity(granularity);
+            for (n, message_loc) in locations.into_iter().enumerate() {
+                let _ = buf.push_packet(
+                    start,
+                    DataPacket {
+                        seq_number: init_seq_num + n as u32,
+                        message_loc,
+                        message_number: MsgNumber(7),
+                        ..basic_pack()
+                    },
+                );
+            }
+
+            let mut released = Vec::new();
+            while let Ok(Some((_, _, info))) = buf.pop_next_message_with_info(start + tsbpd) {
+                assert_eq!(info.message_number, MsgNumber(7));
+                released.push(info.location);
+            }
+            match granularity {
+                RecvGranularity::Message => assert_eq!(released, [PacketLocation::ONLY]),
+                RecvGranularity::Payload => assert_eq!(released, locations),
+            }
+        }
+    }
+
+    proptest::proptest! {
+        // the loss list is exactly the sequence numbers still missing, in order from the oldest
+        // and merged into the fewest ranges, wherever the sequence numbers wrap
+        #[test]
+        fn loss_list_across_wrap(
+            back in 1u32..300,
+            arrived in proptest::collection::vec(proptest::bool::ANY, 1..200),
+            recovered in proptest::collection::vec(proptest::bool::ANY, 200),
+        ) {
+            let start = Instant::now();
+            let init_seq_num = SeqNumber(SeqNumber::MAX - back);
+            let mut buf = ReceiveBuffer::new(start, Duration::from_secs(2), true, init_seq_num, PacketCount(8192));
+            let count = arrived.len() as u32 + 1;
+            let push = |buf: &mut ReceiveBuffer, n: u32| {
+                let _ = buf.push_packet(start, DataPacket { seq_number: init_seq_num + n, ..basic_pack() });
+            };
+            let check = |buf: &ReceiveBuffer, missing: &[u32]| {
+                let loss_list = buf.loss_list();
+                let listed: Vec<_> = loss_list
+                    .iter()
+                    .flat_map(|range| (0..range.end - range.start).map(move |i| range.start + i))
+                    .collect();
+                let expected: Vec<_> = missing.iter().map(|n| init_seq_num + *n).collect();
+                proptest::prop_assert_eq!(listed, expected);
+                for pair in loss_list.windows(2) {
+                    proptest::prop_assert!(pair[0].end < pair[1].start, "{:?}", pair);
+                }
+                Ok(())
+            };
+
+            // the last one arrives, so everything missing before it is known to be lost
+            for n in (0..count).filter(|n| arrived.get(*n as usize).copied().unwrap_or(true)) {
+                push(&mut buf, n);
+            }
+            let mut missing: Vec<u32> = (0..count).filter(|n| !arrived.get(*n as usize).copied().unwrap_or(true)).collect();
+            check(&buf, &missing)?;
+
+            // retransmissions fill the gaps newest first, splitting and shrinking the ranges
+            for n in missing.clone().into_iter().rev().filter(|n| recovered[*n as usize]) {
+                push(&mut buf, n);
+            }
+            missing.retain(|n| !recovered[*n as usize]);
+            check(&buf, &missing)?;
+
+            // and the NAK reports the same
+            let nak = buf.prepare_loss_list(start + Duration::from_secs(10), TimeSpan::from_millis(100));
+            let reported: Vec<_> = nak.map(|nak| nak.into_iter_decompressed().collect()).unwrap_or_default();
+            proptest::prop_assert_eq!(reported, missing.iter().map(|n| init_seq_num + *n).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn message_number_wraparound() {
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(SeqNumber::MAX - 4);
+        let locations = [
+            PacketLocation::FIRST,
+            PacketLocation::MIDDLE,
+            PacketLocation::LAST,
+        ];
+        // the sender skips message number 0 when it wraps
+        let message_numbers = [MsgNumber::MAX - 2, MsgNumber::MAX - 1, 1, 2].map(MsgNumber);
+        let packets = message_numbers.iter().flat_map(|message_number| {
+            locations
+                .iter()
+                .enumerate()
+                .map(|(n, message_loc)| DataPacket {
+                    message_loc: *message_loc,
+                    message_number: *message_number,
+                    payload: format!("{}-{n} ", message_number.0).into(),
+                    ..basic_pack()
+                })
+        });
+
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+        let mut late = None;
+        for (n, packet) in packets.enumerate() {
+            let packet = DataPacket {
+                seq_number: init_seq_num + n as u32,
+                ..packet
+            };
+            // the last packet of the message before the wrap arrives after the ones behind it
+            if n == 5 {
+                late = Some(packet);
+            } else {
+                let _ = buf.push_packet(start, packet);
+            }
+        }
+
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 5);
+        assert_eq!(buf.pop_next_message(start), Ok(None));
+        let _ = buf.push_packet(start, late.unwrap());
+
+        let now = start + tsbpd + buf.tsbpd_tolerance;
+        let mut released = Vec::new();
+        while let Ok(Some((_, payload, info))) = buf.pop_next_message_with_info(now) {
+            released.push((info.message_number, payload));
+        }
+
+        let expected = message_numbers.map(|message_number| {
+            let n = message_number.0;
+            (message_number, format!("{n}-0 {n}-1 {n}-2 ").into())
+        });
+        assert_eq!(released, expected);
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn prepare_loss_list() {
         let tsbpd = Duration::from_secs(2);
@@ -871,6 +1321,154 @@ mod receive_buffer {
         assert_eq!(buf.prepare_loss_list(now, mean_rtt), None);
     }
 
+    #[test]
+    fn loss_list() {
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+        let mut buf = ReceiveBuffer::new(
+            start,
+            Duration::from_secs(2),
+            true,
+            init_seq_num,
+            PacketCount(8192),
+        );
+        assert_eq!(buf.loss_list(), vec![]);
+
+        for n in [0, 3, 4, 8] {
+            let _ = buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + n,
+                    ..basic_pack()
+                },
+            );
+        }
+        assert_eq!(
+            buf.loss_list(),
+            vec![
+                init_seq_num + 1..init_seq_num + 3,
+                init_seq_num + 5..init_seq_num + 8
+            ]
+        );
+
+        // recovering a packet splits the range it was in
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 6,
+                ..basic_pack()
+            },
+        );
+        assert_eq!(
+            buf.loss_list(),
+            vec![
+                init_seq_num + 1..init_seq_num + 3,
+                init_seq_num + 5..init_seq_num + 6,
+                init_seq_num + 7..init_seq_num + 8,
+            ]
+        );
+    }
+
+    #[test]
+    fn retransmitted_packet_after_gap() {
+        let start = Instant::now();
+        let rtt = TimeSpan::from_micros(10_000);
+        let init_seq_num = SeqNumber(5);
+        let mut buf = ReceiveBuffer::new(
+            start,
+            Duration::from_secs(2),
+            true,
+            init_seq_num,
+            PacketCount(8192),
+        );
+
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num,
+                ..basic_pack()
+            },
+        );
+        assert_eq!(
+            buf.push_packet(
+                start,
+                DataPacket {
+                    seq_number: init_seq_num + 3,
+                    retransmitted: true,
+                    ..basic_pack()
+                }
+            ),
+            Ok(Received {
+                lrsn: init_seq_num + 1,
+                recovered: false
+            })
+        );
+
+        // the gap is still tracked, but only reported once the retransmissions had time to arrive
+        assert_eq!(buf.loss_list(), vec![init_seq_num + 1..init_seq_num + 3]);
+        assert_eq!(buf.prepare_loss_list(start + rtt, rtt), None);
+        assert_eq!(
+            buf.prepare_loss_list(start + rtt * 3, rtt),
+            Some((1..3).map(|a| init_seq_num + a).collect())
+        );
+    }
+
+    #[test]
+    fn duplicate_packets() {
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+        let packet = |n, payload: &'static [u8]| DataPacket {
+            seq_number: init_seq_num + n,
+            payload: payload.into(),
+            ..basic_pack()
+        };
+
+        let _ = buf.push_packet(start, packet(0, b"0"));
+        let _ = buf.push_packet(start, packet(2, b"2"));
+        assert_eq!(
+            buf.push_packet(start, packet(2, b"corrupted")),
+            Err(DiscardedDuplicate {
+                seq_number: init_seq_num + 2
+            })
+        );
+
+        // the retransmission requested by a NAK and the one triggered by a timeout
+        assert_eq!(
+            buf.push_packet(start, packet(1, b"1")),
+            Ok(Received {
+                lrsn: init_seq_num + 3,
+                recovered: true
+            })
+        );
+        assert_eq!(
+            buf.push_packet(start, packet(1, b"corrupted")),
+            Err(DiscardedDuplicate {
+                seq_number: init_seq_num + 1
+            })
+        );
+        assert_eq!(buf.buffer.len(), 3);
+
+        let release = start + tsbpd;
+        for payload in [b"0", b"1", b"2"] {
+            assert_eq!(
+                buf.pop_next_message(release),
+                Ok(Some((start, payload[..].into())))
+            );
+        }
+
+        // a duplicate of a delivered packet is too late to be buffered again
+        assert_eq!(
+            buf.push_packet(release, packet(0, b"corrupted")),
+            Err(PacketTooLate {
+                seq_number: init_seq_num,
+                seq_number_0: init_seq_num + 3
+            })
+        );
+        assert_eq!(buf.pop_next_message(release), Ok(None));
+    }
+
     #[test]
     fn drop_too_late_packets() {
         // packets:
@@ -909,8 +1507,16 @@ mod receive_buffer {
                 ..basic_pack()
             },
         );
+        // the missing packet is dropped as soon as the message after it is due
+        assert_eq!(
+            buf.pop_next_message(now),
+            Err(MessageError {
+                too_late_packets: SeqNumber(0)..SeqNumber(1),
+                delay: TimeSpan::from_millis(0)
+            })
+        );
+        assert_eq!(buf.next_ack_dsn(), SeqNumber(2));
         assert_eq!(buf.pop_next_message(now), Ok(None));
-        assert_eq!(buf.next_ack_dsn(), init_seq_num);
 
         // 5 ms buffer release tolerance, we are ok with releasing them 5ms late
         let now = now + Duration::from_millis(5);
@@ -918,7 +1524,7 @@ mod receive_buffer {
         assert_eq!(
             buf.pop_next_message(now),
             Err(MessageError {
-                too_late_packets: SeqNumber(0)..SeqNumber(2),
+                too_late_packets: SeqNumber(1)..SeqNumber(2),
                 delay: TimeSpan::from_millis(5)
             })
         );
@@ -929,6 +1535,47 @@ mod receive_buffer {
         assert_eq!(buf.next_ack_dsn(), SeqNumber(2));
     }
 
+    #[test]
+    fn drop_margin() {
+        let _ = pretty_env_logger::try_init();
+
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(0);
+
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+        buf.set_drop_margin(Percent(25));
+
+        // the first packet is missing, the message after it is due at the latency
+        let _ = buf.push_packet(
+            start,
+            DataPacket {
+                seq_number: init_seq_num + 1,
+                ..basic_pack()
+            },
+        );
+        let margin = tsbpd / 4;
+        assert_eq!(
+            buf.next_message_release_time(),
+            Some(start + tsbpd + margin)
+        );
+        assert_eq!(buf.pop_next_message(start + tsbpd), Ok(None));
+
+        // it's waited for until the margin runs out, then the message is released that late
+        let now = start + tsbpd + margin;
+        assert_eq!(
+            buf.pop_next_message(now),
+            Err(MessageError {
+                too_late_packets: SeqNumber(0)..SeqNumber(1),
+                delay: TimeSpan::from_interval(start + tsbpd, now),
+            })
+        );
+        assert_eq!(
+            buf.pop_next_message(now),
+            Ok(Some((start, b"basic payload"[..].into())))
+        );
+    }
+
     #[test]
     fn not_dropping_too_late_packets() {
         let _ = pretty_env_logger::try_init();
@@ -991,6 +1638,67 @@ mod receive_buffer {
         assert_eq!(buf.next_ack_dsn(), init_seq_num);
     }
 
+    #[test]
+    fn as_available_delivery() {
+        // packets:
+        //  (0) missing
+        //  (1) on time
+        //  (2) on time, second part of a message missing its first
+
+        let tsbpd = Duration::from_secs(2);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(0);
+
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(8192));
+        buf.set_delivery_mode(DeliveryMode::AsAvailable);
+
+        let now = start;
+        let _ = buf.push_packet(
+            now,
+            DataPacket {
+                seq_number: init_seq_num + 1,
+                payload: b"first"[..].into(),
+                ..basic_pack()
+            },
+        );
+        let _ = buf.push_packet(
+            now,
+            DataPacket {
+                seq_number: init_seq_num + 3,
+                message_loc: PacketLocation::LAST,
+                message_number: MsgNumber(1),
+                ..basic_pack()
+            },
+        );
+        assert_eq!(buf.next_message_release_time(), Some(start + tsbpd));
+        assert_eq!(buf.pop_next_message(now), Ok(None));
+
+        // the message after the missing packet is not held back for it, unlike in strict delivery
+        let now = start + tsbpd;
+        assert_eq!(
+            buf.pop_next_message(now),
+            Err(MessageError {
+                too_late_packets: SeqNumber(0)..SeqNumber(1),
+                delay: TimeSpan::from_millis(0)
+            })
+        );
+        assert_eq!(
+            buf.pop_next_message(now),
+            Ok(Some((start, b"first"[..].into())))
+        );
+
+        // what is left can never make up a message, so it's dropped too
+        assert_eq!(
+            buf.pop_next_message(now),
+            Err(MessageError {
+                too_late_packets: SeqNumber(2)..SeqNumber(4),
+                delay: TimeSpan::from_millis(0)
+            })
+        );
+        assert_eq!(buf.next_ack_dsn(), SeqNumber(4));
+        assert_eq!(buf.pop_next_message(now), Ok(None));
+    }
+
     #[test]
     fn drop_message() {
         let tsbpd = Duration::from_secs(2);
@@ -1020,6 +1728,9 @@ mod receive_buffer {
         // only drop packets that are marked Lost, i.e. pending NAK
         assert_eq!(buf.drop_packets(init_seq_num - 1..init_seq_num + 5), 3);
 
+        // and acknowledge them along with the packet after them
+        assert_eq!(buf.next_ack_dsn(), init_seq_num + 4);
+
         // no longer schedule the dropped packets for NAK
         let now = now + mean_rtt * 3;
         assert_eq!(buf.prepare_loss_list(now, mean_rtt), None);
@@ -1201,4 +1912,50 @@ mod receive_buffer {
         );
         assert_eq!(buf.rx_acknowledged_time(), Duration::from_micros(0));
     }
+
+    #[test]
+    fn rx_buffered() {
+        let tsbpd = Duration::from_millis(100);
+        let start = Instant::now();
+        let init_seq_num = SeqNumber(5);
+
+        let mut buf = ReceiveBuffer::new(start, tsbpd, true, init_seq_num, PacketCount(10));
+
+        let add_packet = |i, buf: &mut ReceiveBuffer| {
+            buf.push_packet(
+                start + Duration::from_millis(u64::from(i) * 10),
+                DataPacket {
+                    seq_number: init_seq_num + i,
+                    timestamp: TimeStamp::from_micros(i * 10_000),
+                    ..basic_pack()
+                },
+            )
+            .unwrap();
+        };
+
+        assert_eq!(buf.rx_buffered_packets(), 0);
+        assert_eq!(buf.rx_buffered_time(), Duration::from_millis(0));
+        assert_eq!(buf.rx_pending_packets(start), 0);
+
+        add_packet(0, &mut buf);
+        assert_eq!(buf.rx_buffered_packets(), 1);
+        assert_eq!(buf.rx_buffered_time(), Duration::from_millis(0));
+
+        // lost packets don't count towards the depth, but the span still covers them
+        add_packet(2, &mut buf);
+        add_packet(3, &mut buf);
+        assert_eq!(buf.rx_buffered_packets(), 3);
+        assert_eq!(buf.rx_buffered_time(), Duration::from_millis(30));
+
+        assert_eq!(buf.rx_pending_packets(start), 3);
+        assert_eq!(buf.rx_pending_packets(start + tsbpd), 2);
+        assert_eq!(
+            buf.rx_pending_packets(start + tsbpd + Duration::from_millis(30)),
+            0
+        );
+
+        buf.pop_next_message(start + tsbpd).unwrap().unwrap();
+        assert_eq!(buf.rx_buffered_packets(), 2);
+        assert_eq!(buf.rx_buffered_time(), Duration::from_millis(10));
+    }
 }
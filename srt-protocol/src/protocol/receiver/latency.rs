@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+/// The TSBPD latency of the receive buffer.
+///
+/// Changing it all at once either stalls delivery for the whole difference, or makes every packet
+/// already in the buffer due at once. Instead, it moves towards the new target by a fraction of
+/// the time that passed, so delivery only speeds up or slows down a little until it gets there.
+#[derive(Debug, Clone)]
+pub struct TsbpdLatency {
+    from: Duration,
+    to: Duration,
+    start: Instant,
+}
+
+impl TsbpdLatency {
+    /// The latency changes by 1 / SLEW_RATE of the time that passed, e.g. playback runs at
+    /// 8/7 speed while the latency is being lowered
+    pub const SLEW_RATE: u32 = 8;
+
+    pub fn new(now: Instant, latency: Duration) -> Self {
+        Self {
+            from: latency,
+            to: latency,
+            start: now,
+        }
+    }
+
+    pub fn set_target(&mut self, now: Instant, target: Duration) {
+        self.from = self.at(now);
+        self.to = target;
+        self.start = now;
+    }
+
+    pub fn at(&self, now: Instant) -> Duration {
+        let slewed = now.saturating_duration_since(self.start) / Self::SLEW_RATE;
+        if self.to > self.from {
+            self.to.min(self.from + slewed)
+        } else {
+            self.to.max(self.from.saturating_sub(slewed))
+        }
+    }
+
+    /// The earliest time at which a packet sent at `sent` is due, i.e. `sent + self.at(release)`
+    pub fn release_time(&self, sent: Instant) -> Instant {
+        let before_change = sent + self.from;
+        if before_change <= self.start || self.from == self.to {
+            return before_change;
+        }
+
+        // while slewing, the latency keeps moving as time passes, solve for the point where the
+        // packet catches up with it
+        let behind = before_change - self.start;
+        let elapsed = if self.to > self.from {
+            behind * Self::SLEW_RATE / (Self::SLEW_RATE - 1)
+        } else {
+            behind * Self::SLEW_RATE / (Self::SLEW_RATE + 1)
+        };
+        let slew_duration = self.to.abs_diff(self.from) * Self::SLEW_RATE;
+        if elapsed < slew_duration {
+            self.start + elapsed
+        } else {
+            sent + self.to
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn slews_towards_target() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        let mut latency = TsbpdLatency::new(start, ms(100));
+        assert_eq!(latency.at(start + ms(1_000)), ms(100));
+
+        latency.set_target(start, ms(200));
+        assert_eq!(latency.at(start), ms(100));
+        assert_eq!(latency.at(start + ms(400)), ms(150));
+        assert_eq!(latency.at(start + ms(800)), ms(200));
+        assert_eq!(latency.at(start + ms(5_000)), ms(200));
+
+        // changing direction half way starts from where it got to
+        latency.set_target(start + ms(400), ms(50));
+        assert_eq!(latency.at(start + ms(400)), ms(150));
+        assert_eq!(latency.at(start + ms(800)), ms(100));
+        assert_eq!(latency.at(start + ms(5_000)), ms(50));
+    }
+
+    #[test]
+    fn release_time_while_slewing() {
+        let start = Instant::now();
+        let ms = Duration::from_millis;
+
+        let mut latency = TsbpdLatency::new(start, ms(100));
+        assert_eq!(latency.release_time(start), start + ms(100));
+
+        latency.set_target(start + ms(10), ms(200));
+        // due before the change started
+        assert_eq!(latency.release_time(start - ms(95)), start + ms(5));
+        // due half way, the latency grew by 1/7th of the time it took to get there
+        let release = latency.release_time(start + ms(10));
+        assert_eq!(release, start + ms(10) + ms(800) / 7);
+        assert_eq!(release, start + ms(10) + latency.at(release));
+        // due after the change completed
+        assert_eq!(latency.release_time(start + ms(1_000)), start + ms(1_200));
+
+        latency.set_target(start + ms(10), ms(20));
+        let release = latency.release_time(start + ms(10));
+        let caught_up = start + ms(10) + latency.at(release);
+        assert!(release.max(caught_up) - release.min(caught_up) <= Duration::from_nanos(1));
+        assert_eq!(latency.release_time(start + ms(5_000)), start + ms(5_020));
+    }
+}
@@ -1,10 +1,11 @@
 mod arq;
 mod buffer;
 mod history;
+mod latency;
 mod time;
 
 use std::{
-    ops::RangeInclusive,
+    ops::{Range, RangeInclusive},
     time::{Duration, Instant},
 };
 
@@ -12,9 +13,11 @@ use arq::AutomaticRepeatRequestAlgorithm;
 
 use crate::{
     connection::ConnectionSettings,
+    options::PacketCount,
     packet::*,
     protocol::{
         encryption::{Decryption, DecryptionError},
+        filter::PacketFilter,
         output::Output,
         time::Timers,
     },
@@ -63,29 +66,73 @@ pub enum DataPacketAction {
 pub struct Receiver {
     pub arq: AutomaticRepeatRequestAlgorithm,
     pub decryption: Decryption,
+    // the last keys the peer sent couldn't be unwrapped
+    bad_secret: bool,
+    // the latency asked of the peer that it hasn't confirmed yet
+    latency_change: Option<Duration>,
 }
 
 impl Receiver {
     pub fn new(settings: ConnectionSettings) -> Self {
+        // the peer's timestamps map to when its packets arrive, half a round trip after they were
+        // sent, so the latency comes on top of the path delay, like the reference TSBPD base. The
+        // first control packet synchronizes the clock the same way
+        let mut arq = AutomaticRepeatRequestAlgorithm::new(
+            settings.socket_start_time + settings.rtt / 2,
+            settings.recv_tsbpd_latency,
+            settings.too_late_packet_drop,
+            settings.init_seq_num,
+            settings.recv_buffer_size,
+        );
+        arq.set_delivery_mode(settings.delivery_mode);
+        arq.set_granularity(settings.recv_granularity);
+        arq.set_drop_margin(settings.too_late_drop_margin);
+        arq.set_rtt(settings.rtt_estimate());
+        arq.set_buffer_cap(settings.recv_buffer_cap);
         Self {
-            arq: AutomaticRepeatRequestAlgorithm::new(
-                settings.socket_start_time,
-                settings.recv_tsbpd_latency,
-                settings.too_late_packet_drop,
-                settings.init_seq_num,
-                settings.recv_buffer_size,
-            ),
+            arq,
             decryption: Decryption::new(settings.cipher),
+            bad_secret: false,
+            latency_change: None,
         }
     }
 
+    /// Whether the last keys the peer sent couldn't be unwrapped with the passphrase
+    pub fn has_bad_secret(&self) -> bool {
+        self.bad_secret
+    }
+
     pub fn is_flushed(&self) -> bool {
         self.arq.is_flushed()
     }
 
+    pub fn tsbpd_latency(&self, now: Instant) -> Duration {
+        self.arq.tsbpd_latency(now)
+    }
+
     pub fn rx_acknowledged_time(&self) -> Duration {
         self.arq.rx_acknowledged_time()
     }
+
+    pub fn buffer_size(&self) -> PacketCount {
+        self.arq.buffer_size()
+    }
+
+    pub fn rx_buffered_packets(&self) -> u64 {
+        self.arq.rx_buffered_packets()
+    }
+
+    pub fn rx_buffered_time(&self) -> Duration {
+        self.arq.rx_buffered_time()
+    }
+
+    pub fn rx_pending_packets(&self, now: Instant) -> u64 {
+        self.arq.rx_pending_packets(now)
+    }
+
+    pub fn loss_list(&self) -> Vec<Range<SeqNumber>> {
+        self.arq.loss_list()
+    }
 }
 
 pub struct ReceiverContext<'a> {
@@ -93,6 +140,7 @@ pub struct ReceiverContext<'a> {
     output: &'a mut Output,
     stats: &'a mut SocketStatistics,
     receiver: &'a mut Receiver,
+    filter: &'a mut dyn PacketFilter,
 }
 
 impl<'a> ReceiverContext<'a> {
@@ -101,12 +149,14 @@ impl<'a> ReceiverContext<'a> {
         output: &'a mut Output,
         stats: &'a mut SocketStatistics,
         receiver: &'a mut Receiver,
+        filter: &'a mut dyn PacketFilter,
     ) -> Self {
         Self {
             timers,
             stats,
             output,
             receiver,
+            filter,
         }
     }
 
@@ -118,12 +168,27 @@ impl<'a> ReceiverContext<'a> {
     }
 
     pub fn handle_data_packet(&mut self, now: Instant, data: DataPacket) {
+        self.stats.rx_data += 1;
+        self.stats.rx_bytes += data.wire_size() as u64;
+        self.stats.rx_payload_bytes += data.payload.len() as u64;
+
+        let filtered = self.filter.on_receive(data);
+        match filtered.packet {
+            Some(data) => self.receive_data_packet(now, data, false),
+            None => self.stats.rx_filter_extra_data += 1,
+        }
+        for data in filtered.rebuilt {
+            self.receive_data_packet(now, data, true);
+        }
+        if let Some(loss_list) = CompressedLossList::try_from_iter(filtered.lost.into_iter()) {
+            self.output.send_control(now, ControlTypes::Nak(loss_list));
+        }
+    }
+
+    fn receive_data_packet(&mut self, now: Instant, data: DataPacket, rebuilt: bool) {
         use Acknowledgement::*;
         use ControlTypes::*;
         let bytes = data.wire_size() as u64;
-        self.stats.rx_data += 1;
-        self.stats.rx_bytes += bytes;
-
         let data = self
             .receiver
             .decryption
@@ -138,7 +203,9 @@ impl<'a> ReceiverContext<'a> {
 
         match data {
             Ok(action) => {
-                if action.is_recovered() {
+                if rebuilt {
+                    self.stats.rx_filter_supply_data += 1;
+                } else if action.is_recovered() {
                     self.stats.rx_retransmit_data += 1;
                 } else {
                     self.stats.rx_unique_data += 1;
@@ -148,7 +215,16 @@ impl<'a> ReceiverContext<'a> {
                 use DataPacketAction::*;
                 match action {
                     ReceivedWithLoss(loss_list) => {
-                        self.output.send_control(now, Nak(loss_list));
+                        // estimate the size of the missing packets from the average so far, there's
+                        // none while all that arrived was rebuilt by the packet filter
+                        let lost = loss_list.iter_decompressed().count() as u64;
+                        self.stats.rx_loss_data += lost;
+                        self.stats.rx_loss_bytes += (lost * self.stats.rx_unique_bytes)
+                            .checked_div(self.stats.rx_unique_data)
+                            .unwrap_or(0);
+                        if self.filter.report_losses() {
+                            self.output.send_control(now, Nak(loss_list));
+                        }
                     }
                     ReceivedWithLightAck { light_ack, .. } => {
                         self.output.send_control(now, Ack(Lite(light_ack)));
@@ -159,7 +235,12 @@ impl<'a> ReceiverContext<'a> {
             Err(e) => {
                 use DataPacketError::*;
                 match e {
-                    BufferFull { .. } | PacketTooEarly { .. } | PacketTooLate { .. } => {
+                    BufferFull { .. } | PacketTooEarly { .. } => {
+                        self.stats.rx_dropped_data += 1;
+                        self.stats.rx_dropped_bytes += bytes;
+                    }
+                    PacketTooLate { .. } => {
+                        self.stats.rx_belated_data += 1;
                         self.stats.rx_dropped_data += 1;
                         self.stats.rx_dropped_bytes += bytes;
                     }
@@ -167,7 +248,7 @@ impl<'a> ReceiverContext<'a> {
                         self.stats.rx_decrypt_errors += 1;
                         self.stats.rx_decrypt_error_bytes += bytes;
                     }
-                    DiscardedDuplicate { .. } => {}
+                    DiscardedDuplicate { .. } => self.stats.rx_duplicate_data += 1,
                 }
             }
         }
@@ -183,6 +264,12 @@ impl<'a> ReceiverContext<'a> {
         }
     }
 
+    pub fn handle_keepalive(&mut self, now: Instant) {
+        let ack = self.receiver.arq.next_ack_dsn();
+        self.output
+            .send_control(now, ControlTypes::Ack(Acknowledgement::Lite(ack)));
+    }
+
     pub fn handle_drop_request(&mut self, now: Instant, drop: RangeInclusive<SeqNumber>) {
         let range = *drop.start()..*drop.end() + 1;
         let dropped = self.receiver.arq.handle_drop_request(now, range) as u64;
@@ -196,29 +283,51 @@ impl<'a> ReceiverContext<'a> {
         &mut self,
         now: Instant,
         keying_material: KeyingMaterialMessage,
-    ) {
+    ) -> bool {
         match self
             .receiver
             .decryption
             .refresh_key_material(keying_material)
         {
             Ok(Some(response)) => {
-                // TODO: add statistic or "event" notification?
                 // key rotation
+                self.receiver.bad_secret = false;
                 self.output.send_control(
                     now,
                     ControlTypes::Srt(SrtControlPacket::KeyRefreshResponse(response)),
-                )
+                );
+                true
             }
             Ok(None) => {
                 //self.debug("key refresh request", &"duplicate key"),
+                false
             }
             Err(_err) => {
                 //self.warn("key refresh", &err),
+                self.receiver.bad_secret = true;
+                false
             }
         }
     }
 
+    pub fn request_latency_change(&mut self, now: Instant, latency: Duration) {
+        self.receiver.latency_change = Some(latency);
+        self.output.send_control(
+            now,
+            ControlTypes::Srt(SrtControlPacket::LatencyChangeRequest(latency)),
+        );
+    }
+
+    /// Applies the latency the peer confirmed, unless it's not the one last asked for
+    pub fn handle_latency_change_response(&mut self, now: Instant, latency: Duration) -> bool {
+        if self.receiver.latency_change != Some(latency) {
+            return false;
+        }
+        self.receiver.latency_change = None;
+        self.receiver.arq.set_tsbpd_latency(now, latency);
+        true
+    }
+
     pub fn on_full_ack_event(&mut self, now: Instant) {
         if let Some(ack) = self.receiver.arq.on_full_ack_event(now) {
             // Pack the ACK packet with RTT, RTT Variance, and flow window size (available
@@ -228,6 +337,16 @@ impl<'a> ReceiverContext<'a> {
     }
 
     pub fn on_nak_event(&mut self, now: Instant) {
+        // the request or its response may have been lost, ask again until the peer confirms it
+        if let Some(latency) = self.receiver.latency_change {
+            self.output.send_control(
+                now,
+                ControlTypes::Srt(SrtControlPacket::LatencyChangeRequest(latency)),
+            );
+        }
+        if !self.filter.report_losses() {
+            return;
+        }
         if let Some(loss_list) = self.receiver.arq.on_nak_event(now) {
             self.output.send_control(now, ControlTypes::Nak(loss_list));
         }
@@ -9,8 +9,8 @@ use std::{
 use keyed_priority_queue::KeyedPriorityQueue;
 
 use crate::{
-    connection::ConnectionSettings,
-    options::{ByteCount, PacketCount},
+    connection::{ConnectionSettings, Priority},
+    options::{ByteCount, PacketCount, Percent, RetransmitBandwidth, SendBufferFull},
     packet::*,
     protocol::time::{Rtt, Timers},
 };
@@ -18,7 +18,10 @@ use crate::{
 #[derive(Debug)]
 pub struct SendBuffer {
     latency_window: Duration,
+    drop_margin: Percent,
     flow_window_size: usize,
+    // a smaller limit on the packets in flight while restarting after idle
+    congestion_window: Option<usize>,
     buffer: VecDeque<SendBufferEntry>,
     max_buffer_size: usize,
     buffer_len_bytes: usize, // Invariant: buffer_len_bytes = sum of wire sizes of buffer
@@ -31,6 +34,14 @@ pub struct SendBuffer {
     lost_list: BTreeSet<SeqNumber>,
     rtt: Rtt,
     rto_queue: KeyedPriorityQueue<SeqNumber, Reverse<(TimeStamp, SeqNumber)>>,
+    rto_retransmit: bool,
+    retransmit_bandwidth: RetransmitBandwidth,
+    buffer_full: SendBufferFull,
+    max_retransmits: Option<u32>,
+    // the packet that went over max_retransmits, until it's reported
+    retransmit_limit_exceeded: Option<SeqNumber>,
+    // once a packet went over max_retransmits, nothing more is sent
+    given_up: bool,
 }
 
 #[derive(Debug)]
@@ -39,6 +50,7 @@ struct SendBufferEntry {
     // this is transmit count, including the one that may be lost
     // ie, the first time a packet is sent, this is one
     transmit_count: i32,
+    priority: Priority,
 }
 
 type DroppedPackets = (PacketCount, ByteCount);
@@ -53,27 +65,61 @@ impl SendBuffer {
             next_full_ack: FullAckSeqNumber::INITIAL,
             lost_list: BTreeSet::new(),
             flow_window_size: settings.max_flow_size.0 as usize,
+            congestion_window: None,
             max_buffer_size: settings.send_buffer_size.0 as usize,
-            latency_window: max(
-                settings.send_tsbpd_latency + settings.send_tsbpd_latency / 4, // 125% of TSBPD
-                Duration::from_secs(1),
+            latency_window: Self::latency_window(
+                settings.send_tsbpd_latency,
+                settings.too_late_drop_margin,
             ),
-            rtt: Rtt::default(),
+            drop_margin: settings.too_late_drop_margin,
+            rtt: settings.rtt_estimate(),
             rto_queue: Default::default(),
+            rto_retransmit: true,
+            retransmit_bandwidth: settings.retransmit_bandwidth,
+            buffer_full: settings.send_buffer_full,
+            max_retransmits: settings.max_retransmits,
+            retransmit_limit_exceeded: None,
+            given_up: false,
         }
     }
 
-    pub fn push_data(&mut self, packet: DataPacket) -> PushDataResult {
-        let result = if self.buffer.len() < self.max_buffer_size {
-            Ok(())
-        } else if let Some(entry) = self.buffer.pop_front() {
-            self.buffer_len_bytes -= entry.packet.wire_size();
+    fn latency_window(tsbpd_latency: Duration, drop_margin: Percent) -> Duration {
+        max(
+            // at least 125% of TSBPD
+            tsbpd_latency + max(tsbpd_latency / 4, tsbpd_latency * drop_margin),
+            Duration::from_secs(1),
+        )
+    }
+
+    /// The window only ever grows, dropping packets any earlier could discard lost packets the
+    /// receiver is still waiting for while its latency shrinks
+    pub fn set_latency(&mut self, tsbpd_latency: Duration) {
+        let window = Self::latency_window(tsbpd_latency, self.drop_margin);
+        self.latency_window = max(self.latency_window, window);
+    }
+
+    pub fn set_rto_retransmit(&mut self, enabled: bool) {
+        self.rto_retransmit = enabled;
+    }
+
+    /// Limits the packets in flight below the flow window, None lifts the limit
+    pub fn set_congestion_window(&mut self, window: Option<usize>) {
+        self.congestion_window = window;
+    }
 
+    pub fn push_data(&mut self, packet: DataPacket, priority: Priority) -> PushDataResult {
+        let result = if !self.is_full() || self.buffer_full == SendBufferFull::Backpressure {
+            Ok(())
+        } else if let Some(entry) = self.pop_front() {
             // remove packet from lost list if we are dropping it
             if self.lost_list.first() == Some(&entry.packet.seq_number) {
                 self.pop_lost_list();
             }
 
+            // the packet may not have been sent yet, never leave next_send behind the front of
+            // the buffer
+            self.next_send = max(self.next_send, entry.packet.seq_number + 1);
+
             Err((PacketCount(1), ByteCount(entry.packet.wire_size() as u64)))
         } else {
             Ok(())
@@ -83,17 +129,35 @@ impl SendBuffer {
         self.buffer.push_back(SendBufferEntry {
             packet,
             transmit_count: 0,
+            priority,
         });
 
         result
     }
 
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() >= self.max_buffer_size
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.max_buffer_size
+    }
+
     pub fn is_flushed(&self) -> bool {
         self.lost_list.is_empty() && self.buffer.is_empty()
     }
 
     pub fn has_packets_to_send(&self) -> bool {
-        self.get(self.next_send).is_some() || !self.lost_list.is_empty()
+        self.has_unsent_packets() || !self.lost_list.is_empty()
+    }
+
+    pub fn has_unsent_packets(&self) -> bool {
+        self.get(self.next_send).is_some()
+    }
+
+    /// The packets that haven't been sent once yet
+    pub fn unsent_packets(&self) -> usize {
+        self.buffer.len() - self.number_of_unacked_packets()
     }
 
     pub fn duration(&self) -> Duration {
@@ -130,10 +194,8 @@ impl SendBuffer {
             });
         }
 
-        if let Some(rtt) = rtt {
-            self.rtt = rtt;
-        }
-
+        // an ACK that arrived out of order is dropped before anything is taken from it, its RTT
+        // is older than the one already applied and would skew the RTO
         if let Some(received_full_ack) = full_ack {
             if received_full_ack < self.next_full_ack {
                 return Err(InvalidFullAck {
@@ -144,6 +206,10 @@ impl SendBuffer {
             self.next_full_ack = received_full_ack + 1;
         }
 
+        if let Some(rtt) = rtt {
+            self.rtt = rtt;
+        }
+
         let mut recovered = 0;
         let mut received = 0;
         while self.peek_next_lost(ack_number).is_some() {
@@ -152,9 +218,7 @@ impl SendBuffer {
         }
 
         while self.front_packet().filter(|f| *f < ack_number).is_some() {
-            let p = self.pop_front().unwrap();
-            self.buffer_len_bytes = self.buffer_len_bytes.saturating_sub(p.packet.wire_size());
-
+            let _ = self.pop_front();
             received += 1;
         }
 
@@ -176,6 +240,32 @@ impl SendBuffer {
         }
     }
 
+    /// The payload size of every packet from the first to the last one the NAK reports lost, and
+    /// whether it's one of them, the others got through
+    #[cfg(feature = "path-mtu")]
+    pub fn loss_report(&self, nak: &CompressedLossList) -> Vec<(usize, bool)> {
+        let front = match self.front_packet() {
+            Some(front) => front,
+            None => return Vec::new(),
+        };
+        let lost: BTreeSet<_> = nak
+            .iter_decompressed()
+            .skip_while(|seq| *seq < front)
+            .take_while(|seq| *seq < self.next_send)
+            .collect();
+        let (first, last) = match (lost.first(), lost.last()) {
+            (Some(first), Some(last)) => (*first - front, *last - front),
+            _ => return Vec::new(),
+        };
+        self.buffer
+            .range(first as usize..=last as usize)
+            .map(|entry| {
+                let lost = lost.contains(&entry.packet.seq_number);
+                (entry.packet.payload.len(), lost)
+            })
+            .collect()
+    }
+
     pub fn next_snd_actions(
         &mut self,
         ts_now: TimeStamp,
@@ -200,16 +290,20 @@ impl SendBuffer {
     }
 
     fn send_next_lost_packet(&mut self, ts_now: TimeStamp) -> Option<DataPacket> {
-        let seq = self.pop_lost_list()?;
+        let seq = self.pop_lost_list_by_priority()?;
         match self
             .send_packet(ts_now, seq)
         {
             Some(packet) => Some(packet),
+            None if self.given_up => None,
             None => panic!("Packet in loss list was not in buffer! seq={} front_packet={:?} buffer.len={} back_packet={:?}", seq, self.front_packet(), self.buffer.len(), self.buffer.back().map(|b| b.packet.seq_number)),
         }
     }
 
     fn send_next_rto_packet(&mut self, ts_now: TimeStamp) -> Option<DataPacket> {
+        if !self.rto_retransmit {
+            return None;
+        }
         let next_rto = *self
             .rto_queue
             .peek()
@@ -221,9 +315,22 @@ impl SendBuffer {
     // All packets that are sent go through this function
     // It records transmit count and sets the per entry RTO timer
     fn send_packet(&mut self, ts_now: TimeStamp, seq_number: SeqNumber) -> Option<DataPacket> {
+        if self.given_up {
+            return None;
+        }
         let index = seq_number - self.front_packet()?;
         let entry = self.buffer.get_mut(index as usize)?;
 
+        // the first transmission isn't a retransmission
+        if self
+            .max_retransmits
+            .is_some_and(|max| entry.transmit_count > max as i32)
+        {
+            self.retransmit_limit_exceeded = Some(seq_number);
+            self.given_up = true;
+            return None;
+        }
+
         // RTT + 4 * RTTVar + 2 * SYN
         let rto_constant = self.rtt.mean() + 4 * self.rtt.variance() + 2 * Timers::SYN;
         let rto = if entry.transmit_count == 0 {
@@ -269,8 +376,9 @@ impl SendBuffer {
 
         let drop_range = first..last + 1;
 
-        let count = last - first + 1;
-        let _ = self.buffer.drain(0..count as usize).count();
+        for _ in 0..last - first + 1 {
+            let _ = self.pop_front();
+        }
 
         // remove any lost packets from loss list
         while let Some(&seq) = self.lost_list.iter().next() {
@@ -289,25 +397,39 @@ impl SendBuffer {
         if should_drain && self.buffer.len() == 1 {
             // self.next_send = None; TODO: i'm not sure what functionality this was supposed to expose
 
-            let p = self.pop_front().map(|p| p.packet);
-            // This needs to be saturating because of the hack in Self::push_data, can be regular subtract otherwise
-            self.buffer_len_bytes = self
-                .buffer_len_bytes
-                .saturating_sub(p.as_ref().unwrap().wire_size());
-            p
+            self.pop_front().map(|p| p.packet)
         } else {
             None
         }
     }
 
-    fn flow_window_exceeded(&self) -> bool {
+    /// Whether there are more packets in flight than the peer takes, whatever the congestion
+    /// window
+    pub fn flow_window_exhausted(&self) -> bool {
         self.number_of_unacked_packets() > self.flow_window_size
     }
 
-    fn number_of_unacked_packets(&self) -> usize {
-        self.buffer
-            .front()
-            .map_or(0, |e| self.next_send - e.packet.seq_number) as usize
+    fn flow_window_exceeded(&self) -> bool {
+        let unacked = self.number_of_unacked_packets();
+        self.flow_window_exhausted()
+            || self
+                .congestion_window
+                .is_some_and(|window| unacked >= window)
+    }
+
+    /// Empties the buffer of a connection that's done, the packets in sequence number order
+    pub fn take_packets(&mut self) -> Vec<DataPacket> {
+        let mut packets = Vec::with_capacity(self.buffer.len());
+        while let Some(entry) = self.pop_front() {
+            packets.push(entry.packet);
+        }
+        self.lost_list.clear();
+        packets
+    }
+
+    pub fn number_of_unacked_packets(&self) -> usize {
+        self.front_packet()
+            .map_or(0, |front| self.next_send.saturating_sub(front))
     }
 
     fn pop_lost_list(&mut self) -> Option<SeqNumber> {
@@ -316,13 +438,29 @@ impl SendBuffer {
         Some(next)
     }
 
+    // the first of the lost packets with the highest priority, max_by_key settles ties on the last
+    // one it comes across
+    fn pop_lost_list_by_priority(&mut self) -> Option<SeqNumber> {
+        let next = self
+            .lost_list
+            .iter()
+            .rev()
+            .copied()
+            .max_by_key(|seq| self.get(*seq).map(|entry| entry.priority))?;
+        let _ = self.lost_list.remove(&next);
+        Some(next)
+    }
+
     // find the first item that has a sequence number less than seq_num in the loss list
     fn peek_next_lost(&self, seq_num: SeqNumber) -> Option<SeqNumber> {
         self.lost_list.range(..seq_num).copied().next()
     }
 
+    // All packets leave the buffer through this function, so that the byte count and the RTO
+    // timers stay in sync with it
     fn pop_front(&mut self) -> Option<SendBufferEntry> {
         let entry = self.buffer.pop_front()?;
+        self.buffer_len_bytes -= entry.packet.wire_size();
         let _ = self.rto_queue.remove(&entry.packet.seq_number);
         Some(entry)
     }
@@ -427,6 +565,9 @@ pub enum SenderAction {
     // Retransmission from NAK
     RetransmitNak(DataPacket),
     Drop(Range<SeqNumber>),
+    // this packet was due to be retransmitted more than max_retransmits times, nothing more is
+    // sent
+    RetransmitLimit(SeqNumber),
     WaitForInput,
     // sender flow window exceeded"
     WaitForAck {
@@ -465,15 +606,22 @@ impl<'a> SenderAlgorithmIterator<'a> {
     }
 
     fn retransmit_nak(&mut self, p: DataPacket) -> Option<SenderAction> {
-        self.packets_to_send = self.packets_to_send.saturating_sub(1);
+        self.count_retransmission();
         Some(SenderAction::RetransmitNak(p))
     }
 
     fn retransmit_rto(&mut self, p: DataPacket) -> Option<SenderAction> {
-        self.packets_to_send = self.packets_to_send.saturating_sub(1);
+        self.count_retransmission();
         Some(SenderAction::RetransmitRto(p))
     }
 
+    fn count_retransmission(&mut self) {
+        // bursting retransmissions leaves the packets of this period to new data
+        if self.buffer.retransmit_bandwidth == RetransmitBandwidth::Capped {
+            self.packets_to_send = self.packets_to_send.saturating_sub(1);
+        }
+    }
+
     fn wait_for_input(&mut self) -> Option<SenderAction> {
         self.packets_to_send = 0;
         Some(SenderAction::WaitForInput)
@@ -525,6 +673,9 @@ impl<'a> Iterator for SenderAlgorithmIterator<'a> {
             self.retransmit_nak(p)
         } else if let Some(p) = self.buffer.send_next_rto_packet(self.ts_now) {
             self.retransmit_rto(p)
+        } else if let Some(seq) = self.buffer.retransmit_limit_exceeded.take() {
+            self.packets_to_send = 0;
+            Some(SenderAction::RetransmitLimit(seq))
         }
         //   4)
         //        a. If the number of unacknowledged packets exceeds the
@@ -544,7 +695,8 @@ impl<'a> Iterator for SenderAlgorithmIterator<'a> {
 
             self.send(p)
         } else if let Some(p) = self.buffer.flush_on_close(self.should_drain) {
-            self.send(p)
+            // the last packet has already been sent once, this is a retransmission
+            self.retransmit_rto(p)
         } else {
             // NOTE: we wait the same amount regardless if we are waiting for packets or data
             //
@@ -571,7 +723,7 @@ mod test {
     use assert_matches::assert_matches;
     use bytes::Bytes;
 
-    use crate::options::{PacketCount, PacketSize};
+    use crate::options::{PacketCount, PacketSize, SrtVersion};
 
     const MILLIS: Duration = Duration::from_millis(1);
     const TSBPD: Duration = Duration::from_secs(2);
@@ -585,17 +737,37 @@ mod test {
             rtt: Duration::default(),
             init_seq_num: SeqNumber::new_truncate(0),
             max_packet_size: PacketSize(1316),
+            max_segment_size: PacketSize(1500),
             max_flow_size: PacketCount(8192),
+            idle_restart_threshold: None,
+            max_retransmits: None,
             send_tsbpd_latency: TSBPD,
             recv_tsbpd_latency: TSBPD,
             cipher: None,
             stream_id: None,
             bandwidth: Default::default(),
+            retransmit_bandwidth: Default::default(),
+            send_buffer_full: Default::default(),
+            flow_window_full: Default::default(),
+            input_overflow: Default::default(),
             recv_buffer_size: PacketCount(8196),
+            recv_buffer_cap: None,
             send_buffer_size: PacketCount(8196),
             statistics_interval: Duration::from_secs(10),
+            loss_rate_window: Duration::from_secs(5),
+            initial_rtt: None,
+            too_late_drop_margin: Percent(0),
             peer_idle_timeout: Duration::from_secs(5),
             too_late_packet_drop: true,
+            delivery_mode: Default::default(),
+            nak_period: Default::default(),
+            recv_granularity: Default::default(),
+            peer_version: SrtVersion::CURRENT,
+            features: SrtShakeFlags::SUPPORTED,
+            packet_filter: None,
+            negotiation_adjustments: Vec::new(),
+            session_token: None,
+            resumable_sessions: None,
         }
     }
 
@@ -627,7 +799,7 @@ mod test {
         let start = TimeStamp::MIN;
         let mut buffer = SendBuffer::new(&new_settings());
         for n in 0..=16u32 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         for n in 0..=16 {
@@ -651,7 +823,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=13 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         let actions = buffer
@@ -692,6 +864,41 @@ mod test {
         assert!(!buffer.has_packets_to_send());
     }
 
+    #[test]
+    fn retransmit_bandwidth() {
+        let start = TimeStamp::MIN;
+        let lost_then_new = |retransmit_bandwidth| {
+            let mut buffer = SendBuffer::new(&ConnectionSettings {
+                retransmit_bandwidth,
+                ..new_settings()
+            });
+            for n in 0..=5 {
+                let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
+            }
+            let _ = buffer.next_snd_actions(start, 3, false).count();
+            let _ = buffer
+                .add_to_loss_list([SeqNumber(0), SeqNumber(1)].iter().collect())
+                .count();
+            buffer.next_snd_actions(start, 1, false).collect::<Vec<_>>()
+        };
+
+        // the retransmission takes the place of a new packet
+        assert_eq!(
+            lost_then_new(RetransmitBandwidth::Capped),
+            vec![nak_retransmit_packet(0)]
+        );
+
+        // the retransmissions come on top of the new packet
+        assert_eq!(
+            lost_then_new(RetransmitBandwidth::Burst),
+            vec![
+                nak_retransmit_packet(0),
+                nak_retransmit_packet(1),
+                send_data_packet(3)
+            ]
+        );
+    }
+
     #[test]
     fn rto_retransmit() {
         use SenderAction::*;
@@ -699,7 +906,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         assert_eq!(buffer.next_snd_actions(start, 3, false).count(), 3);
@@ -722,6 +929,45 @@ mod test {
         );
     }
 
+    #[test]
+    fn max_retransmits() {
+        use SenderAction::*;
+        let start = TimeStamp::MAX;
+        let mut buffer = SendBuffer::new(&ConnectionSettings {
+            max_retransmits: Some(1),
+            ..new_settings()
+        });
+
+        for n in 0..=1 {
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
+        }
+        assert_eq!(buffer.next_snd_actions(start, 2, false).count(), 2);
+
+        let now = start + TimeSpan::from_millis(1_000);
+        assert_eq!(
+            buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>(),
+            vec![
+                RetransmitRto(test_data_packet(0, true)),
+                RetransmitRto(test_data_packet(1, true)),
+            ]
+        );
+
+        // neither packet gets another retransmission, and it's only reported once
+        let now = now + TimeSpan::from_millis(1_000);
+        let _ = buffer
+            .add_to_loss_list([SeqNumber(1)].iter().collect())
+            .count();
+        assert_eq!(
+            buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>(),
+            vec![RetransmitLimit(SeqNumber(1))]
+        );
+        let _ = buffer.push_data(test_data_packet(2, false), Priority::Normal);
+        assert_eq!(
+            buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>(),
+            vec![WaitForInput]
+        );
+    }
+
     #[test]
     fn ack() {
         use AckError::*;
@@ -729,7 +975,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=5 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         let _ = buffer.next_snd_actions(now, 5, false).count();
@@ -781,6 +1027,72 @@ mod test {
         );
     }
 
+    #[test]
+    fn out_of_order_ack() {
+        use AckError::*;
+        use SenderAction::*;
+        let start = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&new_settings());
+
+        for n in 0..=5 {
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
+        }
+        assert_eq!(buffer.next_snd_actions(start, 2, false).count(), 2);
+
+        // the newer ACK arrives first
+        let rtt = Rtt::new(TimeSpan::from_millis(500), TimeSpan::from_millis(0));
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(
+                SeqNumber(2),
+                FullAckSeqNumber::new(2),
+                Some(rtt)
+            ),
+            Ok(AckAction {
+                received: 2,
+                recovered: 0,
+                send_ack2: FullAckSeqNumber::new(2),
+            })
+        );
+
+        // then the older one, for fewer packets and with a shorter RTT
+        let stale_rtt = Rtt::new(TimeSpan::from_millis(1), TimeSpan::from_millis(0));
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(
+                SeqNumber(1),
+                FullAckSeqNumber::new(1),
+                Some(stale_rtt)
+            ),
+            Err(InvalidAck {
+                ack_number: SeqNumber(1),
+                first: SeqNumber(2),
+                next: SeqNumber(2)
+            })
+        );
+        // or for as many packets
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(
+                SeqNumber(2),
+                FullAckSeqNumber::new(1),
+                Some(stale_rtt)
+            ),
+            Err(InvalidFullAck {
+                received_full_ack: FullAckSeqNumber::new(1).unwrap(),
+                next_full_ack: FullAckSeqNumber::new(3).unwrap()
+            })
+        );
+
+        // the acknowledged packets stay acknowledged
+        assert_eq!(buffer.front_packet(), Some(SeqNumber(2)));
+
+        // and the RTO still follows the newer RTT, nothing is retransmitted early
+        assert_eq!(buffer.next_snd_actions(start, 4, false).count(), 4);
+        let now = start + TimeSpan::from_millis(100);
+        assert_eq!(
+            buffer.next_snd_actions(now, 4, false).collect::<Vec<_>>(),
+            vec![WaitForInput]
+        );
+    }
+
     #[test]
     fn nak() {
         use Loss::*;
@@ -788,7 +1100,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         let _ = buffer.next_snd_actions(now, 3, false).count();
@@ -826,7 +1138,7 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         let _ = buffer.next_snd_actions(now, 3, false).count();
@@ -846,6 +1158,29 @@ mod test {
         assert!(!buffer.has_packets_to_send());
     }
 
+    #[test]
+    fn retransmit_by_priority() {
+        let now = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&new_settings());
+        for n in 0..=4 {
+            let priority = if n % 2 == 1 {
+                Priority::High
+            } else {
+                Priority::Low
+            };
+            let _ = buffer.push_data(test_data_packet(n, false), priority);
+        }
+
+        let _ = buffer.next_snd_actions(now, 5, false).count();
+        let _ = buffer
+            .add_to_loss_list((0..=4).map(SeqNumber).collect::<Vec<_>>().iter().collect())
+            .count();
+
+        // the high priority packets go first, each priority in order
+        let actions = buffer.next_snd_actions(now, 5, false).collect::<Vec<_>>();
+        assert_eq!(actions, [1, 3, 0, 2, 4].map(nak_retransmit_packet).to_vec());
+    }
+
     #[test]
     fn drop_too_late_packets() {
         use Loss::*;
@@ -853,7 +1188,7 @@ mod test {
         let start = TimeStamp::MIN;
         let mut buffer = SendBuffer::new(&new_settings());
         for n in 0..=4 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
         }
 
         // drop queued packets when they are too late
@@ -899,6 +1234,31 @@ mod test {
         assert!(buffer.lost_list.is_empty());
     }
 
+    #[test]
+    fn drop_margin() {
+        use SenderAction::*;
+        let start = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&ConnectionSettings {
+            too_late_drop_margin: Percent(50),
+            ..new_settings()
+        });
+        for n in 0..=1 {
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
+        }
+
+        // kept past 125% of the latency, for as long as the margin
+        let ts_now = start + TSBPD + TSBPD / 4 + 2 * MILLIS;
+        let actions = buffer.next_snd_actions(ts_now, 1, false);
+        assert_eq!(actions.collect::<Vec<_>>(), vec![send_data_packet(0)]);
+
+        let ts_now = start + TSBPD + TSBPD / 2 + 2 * MILLIS;
+        let actions = buffer.next_snd_actions(ts_now, 1, false);
+        assert_eq!(
+            actions.collect::<Vec<_>>(),
+            vec![Drop(SeqNumber(0)..SeqNumber(2)), WaitForInput]
+        );
+    }
+
     #[test]
     fn buffer_duration_size() {
         use SenderAction::*;
@@ -909,7 +1269,7 @@ mod test {
         let wire_size = test_data_packet(0, false).wire_size();
 
         for n in 0..10 {
-            let _ = buffer.push_data(test_data_packet(n, false));
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
             assert_eq!(buffer.duration(), Duration::from_millis(1) * n);
             assert_eq!(buffer.len(), n as usize + 1);
             assert_eq!(buffer.len_bytes(), wire_size * (n as usize + 1));
@@ -947,7 +1307,10 @@ mod test {
 
         let max_flow_size = new_settings().max_flow_size.0 as u32 + 1;
         for n in 0..max_flow_size {
-            assert_eq!(buffer.push_data(test_data_packet(n, false)), Ok(()));
+            assert_eq!(
+                buffer.push_data(test_data_packet(n, false), Priority::Normal),
+                Ok(())
+            );
         }
 
         // if the buffer is full of unsent packets it
@@ -971,15 +1334,43 @@ mod test {
 
         let send_buffer_size = new_settings().send_buffer_size.0 as u32;
         for n in 0..send_buffer_size {
-            assert_eq!(buffer.push_data(test_data_packet(n, false)), Ok(()));
+            assert_eq!(
+                buffer.push_data(test_data_packet(n, false), Priority::Normal),
+                Ok(())
+            );
         }
 
         let expected_dropped_bytes = test_data_packet(0, false).wire_size() as u64;
         let overflow_packet = test_data_packet(send_buffer_size, false);
         assert_eq!(
-            buffer.push_data(overflow_packet),
+            buffer.push_data(overflow_packet, Priority::Normal),
             Err((PacketCount(1), ByteCount(expected_dropped_bytes)))
         );
+        assert!(buffer.is_full());
+    }
+
+    #[test]
+    fn backpressure_when_full() {
+        let mut buffer = SendBuffer::new(&ConnectionSettings {
+            send_buffer_size: PacketCount(4),
+            send_buffer_full: SendBufferFull::Backpressure,
+            ..new_settings()
+        });
+        for n in 0..4 {
+            assert!(!buffer.is_full());
+            assert_eq!(
+                buffer.push_data(test_data_packet(n, false), Priority::Normal),
+                Ok(())
+            );
+        }
+        assert!(buffer.is_full());
+
+        // the rest of a message still fits, nothing is dropped for it
+        assert_eq!(
+            buffer.push_data(test_data_packet(4, false), Priority::Normal),
+            Ok(())
+        );
+        assert_eq!(buffer.len(), 5);
     }
 
     #[test]
@@ -988,7 +1379,10 @@ mod test {
         let mut buffer = SendBuffer::new(&new_settings());
 
         for n in 0..=2 {
-            assert_matches!(buffer.push_data(test_data_packet(n, false)), Ok(_));
+            assert_matches!(
+                buffer.push_data(test_data_packet(n, false), Priority::Normal),
+                Ok(_)
+            );
         }
 
         let _ = buffer.next_snd_actions(now, 3, false).count();
@@ -997,11 +1391,168 @@ mod test {
             .count();
 
         for n in 3..=8195 {
-            assert_matches!(buffer.push_data(test_data_packet(n, false)), Ok(_));
+            assert_matches!(
+                buffer.push_data(test_data_packet(n, false), Priority::Normal),
+                Ok(_)
+            );
         }
-        assert_matches!(buffer.push_data(test_data_packet(8296, false)), Err(_));
-        assert_matches!(buffer.push_data(test_data_packet(8297, false)), Err(_));
+        assert_matches!(
+            buffer.push_data(test_data_packet(8296, false), Priority::Normal),
+            Err(_)
+        );
+        assert_matches!(
+            buffer.push_data(test_data_packet(8297, false), Priority::Normal),
+            Err(_)
+        );
 
         buffer.send_next_lost_packet(now);
     }
+
+    fn wrapping_settings(init_seq_num: SeqNumber) -> ConnectionSettings {
+        ConnectionSettings {
+            init_seq_num,
+            ..new_settings()
+        }
+    }
+
+    fn wrapping_data_packet(init_seq_num: SeqNumber, n: u32) -> DataPacket {
+        DataPacket {
+            seq_number: init_seq_num + n,
+            ..test_data_packet(n, false)
+        }
+    }
+
+    #[test]
+    fn ack_across_sequence_wrap() {
+        use SenderAction::*;
+        let now = TimeStamp::MIN;
+        let init_seq_num = SeqNumber(SeqNumber::MAX - 5);
+        let mut buffer = SendBuffer::new(&wrapping_settings(init_seq_num));
+
+        for n in 0..12 {
+            let _ = buffer.push_data(wrapping_data_packet(init_seq_num, n), Priority::Normal);
+        }
+        let actions = buffer.next_snd_actions(now, 8, false).collect::<Vec<_>>();
+        assert_eq!(actions.len(), 8);
+        assert!(actions.iter().all(|a| matches!(a, Send(_))));
+        assert_eq!(buffer.number_of_unacked_packets(), 8);
+
+        // up to right before the wrap
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(init_seq_num + 4, None, None),
+            Ok(AckAction {
+                received: 4,
+                recovered: 0,
+                send_ack2: None,
+            })
+        );
+        assert_eq!(buffer.front_packet(), Some(SeqNumber(SeqNumber::MAX - 1)));
+        assert_eq!(buffer.number_of_unacked_packets(), 4);
+
+        // across the wrap
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(init_seq_num + 8, None, None),
+            Ok(AckAction {
+                received: 4,
+                recovered: 0,
+                send_ack2: None,
+            })
+        );
+        assert_eq!(buffer.front_packet(), Some(SeqNumber(3)));
+        assert_eq!(buffer.number_of_unacked_packets(), 0);
+        assert_eq!(buffer.len(), 4);
+
+        // packets that haven't been sent yet can't be acknowledged
+        assert_eq!(
+            buffer.update_largest_acked_seq_number(init_seq_num + 9, None, None),
+            Err(AckError::InvalidAck {
+                ack_number: SeqNumber(4),
+                first: SeqNumber(3),
+                next: SeqNumber(3),
+            })
+        );
+
+        let actions = buffer.next_snd_actions(now, 5, false).collect::<Vec<_>>();
+        assert_eq!(actions.len(), 5);
+        assert_eq!(actions[4], WaitForInput);
+        assert_eq!(buffer.number_of_unacked_packets(), 4);
+        assert_matches!(
+            buffer.update_largest_acked_seq_number(init_seq_num + 12, None, None),
+            Ok(AckAction { received: 4, .. })
+        );
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.len_bytes(), 0);
+        assert_eq!(buffer.number_of_unacked_packets(), 0);
+    }
+
+    #[test]
+    fn full_buffer_across_sequence_wrap() {
+        use SenderAction::*;
+        let now = TimeStamp::MIN;
+        let init_seq_num = SeqNumber(SeqNumber::MAX - 2);
+        let settings = ConnectionSettings {
+            send_buffer_size: PacketCount(4),
+            ..wrapping_settings(init_seq_num)
+        };
+        let mut buffer = SendBuffer::new(&settings);
+
+        // the oldest packets are dropped before they are ever sent
+        for n in 0..8 {
+            let result = buffer.push_data(wrapping_data_packet(init_seq_num, n), Priority::Normal);
+            assert_eq!(result.is_err(), n >= 4, "n={n}");
+        }
+        assert_eq!(buffer.front_packet(), Some(init_seq_num + 4));
+        assert_eq!(buffer.number_of_unacked_packets(), 0);
+
+        // sending picks up from the front of the buffer instead of waiting for an ACK
+        let actions = buffer.next_snd_actions(now, 5, false).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            [4, 5, 6, 7]
+                .map(|n| Send(wrapping_data_packet(init_seq_num, n)))
+                .into_iter()
+                .chain([WaitForInput])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(buffer.number_of_unacked_packets(), 4);
+        assert_eq!(
+            buffer.len_bytes(),
+            4 * wrapping_data_packet(init_seq_num, 0).wire_size()
+        );
+    }
+
+    #[test]
+    fn rto_retransmit_after_drop() {
+        use SenderAction::*;
+        let start = TimeStamp::MIN;
+        let mut buffer = SendBuffer::new(&new_settings());
+
+        for n in 0..=1 {
+            let _ = buffer.push_data(test_data_packet(n, false), Priority::Normal);
+        }
+        assert_eq!(buffer.next_snd_actions(start, 2, false).count(), 2);
+
+        // the sent packets expire before they're acknowledged
+        let now = start + TSBPD + TSBPD / 4 + 2 * MILLIS;
+        let _ = buffer.push_data(
+            DataPacket {
+                timestamp: now,
+                ..test_data_packet(2, false)
+            },
+            Priority::Normal,
+        );
+        let actions = buffer.next_snd_actions(now, 2, false).collect::<Vec<_>>();
+        assert_matches!(actions[..], [Drop(_), Send(_), ..]);
+
+        // their retransmission timers must not hold up the ones that are still in the buffer
+        let now = now + TimeSpan::from_millis(1_000);
+        assert_eq!(
+            buffer.next_snd_actions(now, 1, false).collect::<Vec<_>>(),
+            vec![RetransmitRto(DataPacket {
+                timestamp: start + TSBPD + TSBPD / 4 + 2 * MILLIS,
+                ..test_data_packet(2, true)
+            })]
+        );
+        assert_eq!(buffer.len_bytes(), test_data_packet(2, false).wire_size());
+    }
 }
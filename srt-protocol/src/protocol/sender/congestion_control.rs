@@ -87,6 +87,7 @@ pub struct SenderCongestionControl {
     next: Option<Instant>,
     estimation: InputRateEstimation,
     bandwidth_mode: LiveBandwidthMode,
+    send_rate: DataRate,
 }
 
 // https://datatracker.ietf.org/doc/html/draft-sharabayko-srt-00#section-5.1.2
@@ -98,6 +99,7 @@ impl SenderCongestionControl {
             next: None,
             estimation: InputRateEstimation::default(),
             bandwidth_mode,
+            send_rate: DataRate(0),
         }
     }
 
@@ -125,7 +127,14 @@ impl SenderCongestionControl {
                 let data_rate = estimate.bytes.mean;
                 let packet_rate = estimate.packets.mean;
 
-                Some(self.calculate_snd_period(PacketRate(packet_rate), DataRate(data_rate)))
+                let snd_period =
+                    self.calculate_snd_period(PacketRate(packet_rate), DataRate(data_rate));
+                self.send_rate = Self::calculate_send_rate(
+                    PacketRate(packet_rate),
+                    DataRate(data_rate),
+                    snd_period,
+                );
+                Some(snd_period)
             }
         };
 
@@ -134,6 +143,24 @@ impl SenderCongestionControl {
         result
     }
 
+    /// The rate packets of the mean input size are sent at with the last sending period
+    /// calculated, zero until there is one
+    pub fn send_rate(&self) -> DataRate {
+        self.send_rate
+    }
+
+    fn calculate_send_rate(
+        packet_rate: PacketRate,
+        data_rate: DataRate,
+        snd_period: Duration,
+    ) -> DataRate {
+        if packet_rate == PacketRate(0) {
+            return DataRate(0);
+        }
+        let packet_size = data_rate / packet_rate;
+        DataRate(packet_size.0 * 1_000_000 / snd_period.as_micros().max(1) as u64)
+    }
+
     fn calculate_max_data_rate(&self, actual_data_rate: DataRate) -> DataRate {
         use LiveBandwidthMode::*;
         match self.bandwidth_mode {
@@ -226,11 +253,14 @@ mod sender_congestion_control {
             control.on_input(start, PacketCount(1), ByteCount(mean_packet_size)),
             None
         );
+        assert_eq!(control.send_rate(), DataRate(0));
         let snd_period = control.on_input(start + micros(100_000), PacketCount(0), ByteCount(0));
 
         let expected_snd_period = (mean_packet_size * 10 * 100_000) / expected_data_rate;
 
         assert_eq!(snd_period, Some(micros(expected_snd_period)));
+        // packets of the mean size sent that far apart go at the max rate
+        assert_eq!(control.send_rate(), DataRate(max_data_rate));
     }
 
     #[test]
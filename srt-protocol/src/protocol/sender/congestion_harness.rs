@@ -0,0 +1,198 @@
+//! Drives the sender's congestion control with scripted events instead of a network: the send
+//! period that paces packets, and the window that clocks them out with ACKs after being idle.
+
+use std::time::{Duration, Instant};
+
+use crate::options::{ByteCount, LiveBandwidthMode, PacketCount};
+
+use super::{congestion_control::SenderCongestionControl, idle_restart::IdleRestart};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// The application sends a message of this many packets and bytes
+    Input {
+        packets: u64,
+        bytes: u64,
+    },
+    /// The send timer fires, and the sender decides whether to start over after idling
+    SndTimer,
+    /// Packets left the sender, taking them off the backlog
+    Sent(u64),
+    /// The peer acknowledged this many more packets
+    Ack(u64),
+    /// The peer reported a loss
+    Nak,
+    Advance(Duration),
+}
+
+#[derive(Debug)]
+pub struct CongestionHarness {
+    now: Instant,
+    control: SenderCongestionControl,
+    restart: IdleRestart,
+    backlog: u64,
+    snd_period: Option<Duration>,
+}
+
+impl CongestionHarness {
+    pub fn new(bandwidth: LiveBandwidthMode, idle_restart_threshold: Option<Duration>) -> Self {
+        Self {
+            now: Instant::now(),
+            control: SenderCongestionControl::new(bandwidth),
+            restart: IdleRestart::new(idle_restart_threshold, PacketCount(8192)),
+            backlog: 0,
+            snd_period: None,
+        }
+    }
+
+    pub fn feed(&mut self, events: impl IntoIterator<Item = Event>) -> &mut Self {
+        use Event::*;
+        for event in events {
+            match event {
+                Input { packets, bytes } => {
+                    self.backlog += packets;
+                    let period =
+                        self.control
+                            .on_input(self.now, PacketCount(packets), ByteCount(bytes));
+                    if period.is_some() {
+                        self.snd_period = period;
+                    }
+                }
+                SndTimer => {
+                    self.restart.on_snd_event(self.now);
+                }
+                Sent(packets) => {
+                    self.backlog = self.backlog.saturating_sub(packets);
+                    self.restart.on_sent(self.now);
+                }
+                Ack(received) => self.restart.on_ack(received, self.backlog > 0),
+                Nak => self.restart.on_loss(),
+                Advance(elapsed) => self.now += elapsed,
+            }
+        }
+        self
+    }
+
+    /// The input of a steady stream of single packet messages, one every interval
+    pub fn steady_input(
+        interval: Duration,
+        bytes: u64,
+        count: usize,
+    ) -> impl Iterator<Item = Event> {
+        (0..count).flat_map(move |_| [Event::Input { packets: 1, bytes }, Event::Advance(interval)])
+    }
+
+    /// The last send period calculated, none before the first input rate estimate
+    pub fn snd_period(&self) -> Option<Duration> {
+        self.snd_period
+    }
+
+    /// The packets allowed in flight, none when only the flow window limits them
+    pub fn window(&self) -> Option<usize> {
+        self.restart.window()
+    }
+}
+
+mod test {
+    use super::{Event::*, *};
+    use crate::options::{DataRate, Percent};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn estimated() -> LiveBandwidthMode {
+        LiveBandwidthMode::Estimated {
+            expected: DataRate(0),
+            overhead: Percent(25),
+        }
+    }
+
+    #[test]
+    fn startup_ramp() {
+        let mut harness = CongestionHarness::new(estimated(), Some(Duration::from_secs(1)));
+
+        // no pacing until the input rate is first estimated
+        harness.feed(CongestionHarness::steady_input(MS, 1000, 99));
+        assert_eq!(harness.snd_period(), None);
+        harness.feed([Input {
+            packets: 1,
+            bytes: 1000,
+        }]);
+        assert_eq!(harness.snd_period(), None);
+        harness.feed([
+            Advance(MS),
+            Input {
+                packets: 1,
+                bytes: 1000,
+            },
+        ]);
+        // 1000 byte packets at 1.25 MB/s, the input rate plus the overhead
+        assert_eq!(harness.snd_period(), Some(Duration::from_micros(800)));
+
+        // a backlog is clocked out from the initial window, doubling every round trip
+        harness.feed([
+            Input {
+                packets: 1000,
+                bytes: 1_000_000,
+            },
+            SndTimer,
+        ]);
+        assert_eq!(harness.window(), Some(16));
+        for window in [32, 64, 128, 256] {
+            let in_flight = harness.window().unwrap() as u64;
+            harness.feed([Sent(in_flight), Advance(10 * MS), Ack(in_flight)]);
+            assert_eq!(harness.window(), Some(window));
+        }
+    }
+
+    #[test]
+    fn loss() {
+        let mut harness = CongestionHarness::new(estimated(), Some(Duration::from_secs(1)));
+        harness.feed(CongestionHarness::steady_input(MS, 1000, 1000));
+        harness.feed([SndTimer, Sent(16), Ack(16), Sent(32), Ack(32)]);
+        assert_eq!(harness.window(), Some(64));
+        let snd_period = harness.snd_period();
+
+        // the window halves and then grows by a share of what's acknowledged
+        harness.feed([Nak]);
+        assert_eq!(harness.window(), Some(32));
+        harness.feed([Sent(32), Ack(32)]);
+        assert_eq!(harness.window(), Some(48));
+
+        // but never below the initial window
+        harness.feed([Nak, Nak, Nak]);
+        assert_eq!(harness.window(), Some(16));
+
+        // the pacing follows the input, not the loss
+        assert_eq!(harness.snd_period(), snd_period);
+    }
+
+    #[test]
+    fn steady_state() {
+        let mut harness = CongestionHarness::new(estimated(), Some(Duration::from_secs(1)));
+        harness.feed(CongestionHarness::steady_input(MS, 1000, 101));
+        harness.feed([SndTimer, Sent(101)]);
+
+        // sending as fast as the input comes, the send period doesn't budge
+        for _ in 0..20 {
+            harness.feed(CongestionHarness::steady_input(MS, 1000, 100));
+            assert_eq!(harness.snd_period(), Some(Duration::from_micros(800)));
+            harness.feed([Sent(100), SndTimer, Ack(100)]);
+        }
+        // and once the backlog is gone the window doesn't limit it anymore
+        assert_eq!(harness.window(), None);
+
+        // then eases off when the input slows down, a period at a time
+        let mut last = harness.snd_period().unwrap();
+        for _ in 0..10 {
+            harness.feed(CongestionHarness::steady_input(2 * MS, 1000, 50));
+            harness.feed([Input {
+                packets: 1,
+                bytes: 1000,
+            }]);
+            let snd_period = harness.snd_period().unwrap();
+            assert!(snd_period > last, "{snd_period:?} > {last:?}");
+            assert!(snd_period < Duration::from_micros(1600));
+            last = snd_period;
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::{connection::ConnectionSettings, options::PacketSize, packet::*};
 pub struct Encapsulation {
     remote_socket_id: SocketId,
     max_packet_size: PacketSize,
+    settings_max_packet_size: PacketSize,
     next_message_number: MsgNumber,
     next_sequence_number: SeqNumber,
 }
@@ -15,32 +16,83 @@ impl Encapsulation {
         Self {
             remote_socket_id: settings.remote_sockid,
             max_packet_size: settings.max_packet_size,
+            settings_max_packet_size: settings.max_packet_size,
             next_sequence_number: settings.init_seq_num,
-            next_message_number: MsgNumber::new_truncate(0),
+            // 0 is reserved for packet filter control packets, as in the reference implementation
+            next_message_number: MsgNumber::new_truncate(1),
         }
     }
 
+    pub fn reserve_payload(&mut self, size: usize) {
+        self.max_packet_size = PacketSize(self.settings_max_packet_size.0 - size as u64);
+    }
+
+    pub fn max_payload_size(&self) -> PacketSize {
+        self.max_packet_size
+    }
+
+    /// Lowers the payload size, e.g. for a smaller path MTU, it's never raised again
+    #[cfg(feature = "path-mtu")]
+    pub fn limit_payload(&mut self, size: PacketSize) {
+        self.max_packet_size = self.max_packet_size.min(size);
+    }
+
+    /// The sequence number of the next packet encapsulated
+    pub fn next_sequence_number(&self) -> SeqNumber {
+        self.next_sequence_number
+    }
+
     /// In the case of a message longer than the packet size,
     /// It will be split into multiple packets
     pub fn encapsulate(
         &mut self,
         timestamp: TimeStamp,
         data: Bytes,
-    ) -> impl Iterator<Item = DataPacket> + '_ {
+    ) -> impl Iterator<Item = DataPacket> {
+        let message_number = self.next_message_number();
+        // the sequence numbers of all of its packets are taken up front, so the packets don't
+        // hold on to the encapsulation
+        let max_packet_size = usize::from(self.max_packet_size);
+        let packets = data.len().div_ceil(max_packet_size).max(1);
+        let next_sequence_number = self.next_sequence_number;
+        self.next_sequence_number = next_sequence_number + packets as u32;
         MessageEncapsulationIterator {
             timestamp,
-            message_number: self.next_message_number.increment(),
+            message_number,
             remaining: data,
             packet_location: PacketLocation::FIRST,
             remote_socket_id: self.remote_socket_id,
             max_packet_size: self.max_packet_size,
-            next_sequence_number: &mut self.next_sequence_number,
+            next_sequence_number,
         }
     }
+
+    /// Takes over a packet of a connection this one resumes, it keeps its sequence and message
+    /// numbers, and what's encapsulated after it follows on from them
+    pub fn resume(&mut self, timestamp: TimeStamp, packet: DataPacket) -> DataPacket {
+        self.next_sequence_number = packet.seq_number + 1;
+        self.next_message_number = packet.message_number;
+        let _ = self.next_message_number();
+        DataPacket {
+            dest_sockid: self.remote_socket_id,
+            encryption: DataEncryption::None,
+            retransmitted: false,
+            timestamp,
+            ..packet
+        }
+    }
+
+    fn next_message_number(&mut self) -> MsgNumber {
+        let message_number = self.next_message_number.increment();
+        if self.next_message_number == MsgNumber(0) {
+            self.next_message_number = MsgNumber(1);
+        }
+        message_number
+    }
 }
 
-struct MessageEncapsulationIterator<'a> {
-    next_sequence_number: &'a mut SeqNumber,
+struct MessageEncapsulationIterator {
+    next_sequence_number: SeqNumber,
     remote_socket_id: SocketId,
     max_packet_size: PacketSize,
     remaining: Bytes,
@@ -49,7 +101,7 @@ struct MessageEncapsulationIterator<'a> {
     timestamp: TimeStamp,
 }
 
-impl<'a> Iterator for MessageEncapsulationIterator<'a> {
+impl Iterator for MessageEncapsulationIterator {
     type Item = DataPacket;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -90,6 +142,7 @@ mod encapsulation {
         Encapsulation {
             remote_socket_id: SocketId(2),
             max_packet_size: PacketSize(1024),
+            settings_max_packet_size: PacketSize(1024),
             next_message_number: MsgNumber(1),
             next_sequence_number: SeqNumber(0),
         }
@@ -112,4 +165,22 @@ mod encapsulation {
 
         assert_eq!(encapsulation.encapsulate(TimeStamp::MAX, data).count(), 10);
     }
+
+    #[test]
+    fn message_number_skips_zero() {
+        let mut encapsulation = Encapsulation {
+            next_message_number: MsgNumber(MsgNumber::MAX - 1),
+            ..new_encapsulation()
+        };
+
+        let mut message_numbers = Vec::new();
+        for _ in 0..2 {
+            let packets = encapsulation.encapsulate(TimeStamp::MAX, Bytes::new());
+            message_numbers.extend(packets.map(|packet| packet.message_number));
+        }
+        assert_eq!(
+            message_numbers,
+            [MsgNumber(MsgNumber::MAX - 1), MsgNumber(1)]
+        );
+    }
 }
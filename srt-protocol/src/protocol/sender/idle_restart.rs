@@ -0,0 +1,169 @@
+//! Restart after idle, like TCP's (RFC 5681, section 4.1). The send period only paces packets
+//! while there's something to send, so a backlog that builds up while the sender was idle would go
+//! out in one burst, possibly more than the path can take. Instead, the packets in flight start over
+//! from a small window. The window grows with every packet acknowledged, and loss shrinks it, until
+//! the backlog is sent. Resuming a paused sender starts over the same way.
+
+use std::{
+    cmp::max,
+    time::{Duration, Instant},
+};
+
+use crate::options::PacketCount;
+
+const INITIAL_WINDOW: usize = 16;
+
+#[derive(Debug)]
+pub struct IdleRestart {
+    threshold: Option<Duration>,
+    flow_window_size: usize,
+    last_sent: Option<Instant>,
+    window: Option<usize>,
+    slow_start: bool,
+    restart: bool,
+}
+
+impl IdleRestart {
+    pub fn new(threshold: Option<Duration>, flow_window_size: PacketCount) -> Self {
+        Self {
+            threshold,
+            flow_window_size: flow_window_size.0 as usize,
+            last_sent: None,
+            window: None,
+            slow_start: false,
+            restart: false,
+        }
+    }
+
+    /// The number of packets allowed in flight, if fewer than the flow window
+    pub fn window(&self) -> Option<usize> {
+        self.window
+    }
+
+    /// Starts over from the initial window on the next send event, idle or not, even with no
+    /// threshold
+    pub fn restart(&mut self) {
+        self.restart = true;
+    }
+
+    /// Starts over from the initial window when nothing was sent for the threshold or longer, or
+    /// when asked to. Returns whether it did.
+    pub fn on_snd_event(&mut self, now: Instant) -> bool {
+        let idle = std::mem::take(&mut self.restart)
+            || self.threshold.is_some_and(|threshold| {
+                self.last_sent
+                    .is_none_or(|last_sent| now.saturating_duration_since(last_sent) >= threshold)
+            });
+        if idle {
+            self.window = Some(INITIAL_WINDOW);
+            self.slow_start = true;
+        }
+        idle
+    }
+
+    pub fn on_sent(&mut self, now: Instant) {
+        self.last_sent = Some(now);
+    }
+
+    /// Grows the window by the packets acknowledged until a packet is lost, and by a share of them
+    /// after that. Once nothing is left to send, the backlog is sent and the restart is over.
+    pub fn on_ack(&mut self, received: u64, backlog: bool) {
+        let Some(window) = self.window else {
+            return;
+        };
+        let received = received as usize;
+        let window = if self.slow_start {
+            window + received
+        } else {
+            window + (received * INITIAL_WINDOW).div_ceil(window)
+        };
+        self.window = (backlog && window < self.flow_window_size).then_some(window);
+    }
+
+    pub fn on_loss(&mut self) {
+        if let Some(window) = &mut self.window {
+            *window = max(*window / 2, INITIAL_WINDOW);
+            self.slow_start = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn restart_after_idle() {
+        let start = Instant::now();
+        let mut restart = IdleRestart::new(Some(Duration::from_secs(1)), PacketCount(1000));
+
+        // nothing was sent yet
+        assert!(restart.on_snd_event(start));
+        assert_eq!(restart.window(), Some(16));
+        restart.on_sent(start);
+        restart.on_ack(16, true);
+        assert_eq!(restart.window(), Some(32));
+        restart.on_ack(32, true);
+        assert_eq!(restart.window(), Some(64));
+
+        // after a loss, it grows by less the larger it is
+        restart.on_loss();
+        assert_eq!(restart.window(), Some(32));
+        restart.on_ack(32, true);
+        assert_eq!(restart.window(), Some(48));
+        restart.on_ack(32, true);
+        assert_eq!(restart.window(), Some(59));
+        restart.on_ack(0, true);
+        assert_eq!(restart.window(), Some(59));
+
+        // still sending, not idle yet, until the backlog is sent
+        let now = start + Duration::from_millis(999);
+        assert!(!restart.on_snd_event(now));
+        restart.on_sent(now);
+        restart.on_ack(32, false);
+        assert_eq!(restart.window(), None);
+        restart.on_ack(32, true);
+        assert_eq!(restart.window(), None);
+
+        // idle again
+        assert!(!restart.on_snd_event(now + Duration::from_millis(999)));
+        assert_eq!(restart.window(), None);
+        assert!(restart.on_snd_event(now + Duration::from_secs(1)));
+        assert_eq!(restart.window(), Some(16));
+    }
+
+    #[test]
+    fn up_to_the_flow_window() {
+        let mut restart = IdleRestart::new(Some(Duration::from_secs(1)), PacketCount(100));
+        assert!(restart.on_snd_event(Instant::now()));
+        restart.on_ack(83, true);
+        assert_eq!(restart.window(), Some(99));
+        restart.on_ack(1, true);
+        assert_eq!(restart.window(), None);
+    }
+
+    #[test]
+    fn disabled() {
+        let start = Instant::now();
+        let mut restart = IdleRestart::new(None, PacketCount(100));
+        assert!(!restart.on_snd_event(start));
+        restart.on_sent(start);
+        assert!(!restart.on_snd_event(start + Duration::from_secs(10)));
+        assert_eq!(restart.window(), None);
+    }
+
+    #[test]
+    fn restart_when_asked() {
+        let start = Instant::now();
+        let mut restart = IdleRestart::new(None, PacketCount(100));
+        restart.on_sent(start);
+        restart.restart();
+        assert!(restart.on_snd_event(start));
+        assert_eq!(restart.window(), Some(16));
+
+        // only once
+        assert!(!restart.on_snd_event(start));
+        restart.on_ack(16, true);
+        assert_eq!(restart.window(), Some(32));
+    }
+}
@@ -1,8 +1,15 @@
 mod buffer;
 mod congestion_control;
+#[cfg(test)]
+mod congestion_harness;
 mod encapsulate;
+mod idle_restart;
+#[cfg(feature = "path-mtu")]
+mod path_mtu;
 
 use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, VecDeque},
     convert::TryFrom,
     time::{Duration, Instant},
 };
@@ -10,11 +17,12 @@ use std::{
 use bytes::Bytes;
 
 use crate::{
-    connection::{ConnectionSettings, ConnectionStatus},
+    connection::{ConnectionSettings, ConnectionStatus, Priority},
     options::*,
     packet::*,
     protocol::{
         encryption::Encryption,
+        filter::PacketFilter,
         output::Output,
         time::{TimeBase, Timers},
     },
@@ -24,6 +32,9 @@ use crate::{
 use buffer::{AckAction, Loss, SendBuffer, SenderAction};
 use congestion_control::SenderCongestionControl;
 use encapsulate::Encapsulation;
+use idle_restart::IdleRestart;
+#[cfg(feature = "path-mtu")]
+use path_mtu::PathMtuDetection;
 
 #[derive(Debug)]
 pub struct Sender {
@@ -32,6 +43,37 @@ pub struct Sender {
     encryption: Encryption,
     send_buffer: SendBuffer,
     congestion_control: SenderCongestionControl,
+    idle_restart: IdleRestart,
+    #[cfg(feature = "path-mtu")]
+    path_mtu: PathMtuDetection,
+    // the limit on the size of a message is this many packets of the current payload size
+    max_message_packets: PacketCount,
+    tsbpd_latency: Duration,
+    buffer_full: SendBufferFull,
+    flow_window_full: FlowWindowFull,
+    input_overflow: InputOverflowPolicy,
+    paused: bool,
+    // messages waiting for the send buffer to run out of packets to send, highest priority first,
+    // they're only given their sequence numbers once released into it
+    queue: BTreeMap<Reverse<Priority>, VecDeque<QueuedMessage>>,
+    queued_packets: usize,
+    queued_bytes: usize,
+    // one past the last packet the peer acknowledged, which may be past what the send buffer
+    // still holds once packets are dropped on this side
+    peer_acked: SeqNumber,
+}
+
+#[derive(Debug)]
+struct QueuedMessage {
+    timestamp: TimeStamp,
+    data: Bytes,
+    packets: usize,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct MessageTooLargeError {
+    pub size: ByteCount,
+    pub max_message_size: ByteCount,
 }
 
 impl Sender {
@@ -42,15 +84,73 @@ impl Sender {
             encryption: Encryption::new(settings.cipher.clone()),
             send_buffer: SendBuffer::new(&settings),
             congestion_control: SenderCongestionControl::new(settings.bandwidth.clone()),
+            idle_restart: IdleRestart::new(settings.idle_restart_threshold, settings.max_flow_size),
+            #[cfg(feature = "path-mtu")]
+            path_mtu: PathMtuDetection::new(settings.max_packet_size),
+            max_message_packets: settings.max_message_packets(),
+            tsbpd_latency: settings.send_tsbpd_latency,
+            buffer_full: settings.send_buffer_full,
+            flow_window_full: settings.flow_window_full,
+            input_overflow: settings.input_overflow,
+            paused: false,
+            queue: BTreeMap::new(),
+            queued_packets: 0,
+            queued_bytes: 0,
+            peer_acked: settings.init_seq_num,
+        }
+    }
+
+    /// Makes room for what a packet filter adds on top of the payload
+    pub fn reserve_payload(&mut self, size: usize) {
+        self.encapsulation.reserve_payload(size);
+    }
+
+    /// Whether packets that aren't acknowledged in time are retransmitted without a NAK
+    pub fn set_rto_retransmit(&mut self, enabled: bool) {
+        self.send_buffer.set_rto_retransmit(enabled);
+    }
+
+    /// Refreshes the keys with ones wrapped with a new passphrase
+    pub fn set_passphrase(&mut self, passphrase: Passphrase) {
+        self.encryption.set_passphrase(passphrase);
+    }
+
+    /// Stops sending data, retransmissions included, while still taking it in
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Goes on sending data, clocking out what built up while paused with the ACKs, from the
+    /// initial window, rather than in one burst
+    pub fn resume(&mut self) {
+        if std::mem::take(&mut self.paused) {
+            self.idle_restart.restart();
         }
     }
 
     pub fn is_flushed(&self) -> bool {
-        self.send_buffer.is_flushed()
+        self.queue.is_empty() && self.send_buffer.is_flushed()
+    }
+
+    /// Whether more data can be taken, which with backpressure waits for room in the send buffer,
+    /// and when blocking on the flow window, for acknowledgements to open it
+    pub fn is_accepting_data(&self) -> bool {
+        let blocked = self.flow_window_full == FlowWindowFull::Block
+            && self.send_buffer.flow_window_exhausted();
+        (self.buffer_full == SendBufferFull::Drop || !self.is_full()) && !blocked
     }
 
     pub fn has_packets_to_send(&self) -> bool {
-        self.send_buffer.has_packets_to_send()
+        !self.paused && (self.send_buffer.has_packets_to_send() || !self.queue.is_empty())
+    }
+
+    // the queued messages take up room in the send buffer already
+    fn is_full(&self) -> bool {
+        self.send_buffer.len() + self.queued_packets >= self.send_buffer.capacity()
+    }
+
+    pub fn tsbpd_latency(&self) -> Duration {
+        self.tsbpd_latency
     }
 
     pub fn tx_buffered_time(&self) -> Duration {
@@ -64,6 +164,66 @@ impl Sender {
     pub fn tx_buffered_bytes(&self) -> u64 {
         u64::try_from(self.send_buffer.len_bytes()).unwrap()
     }
+
+    pub fn tx_max_payload_size(&self) -> u64 {
+        self.encapsulation.max_payload_size().0
+    }
+
+    /// The largest message taken, which shrinks along with the payload, when a packet filter
+    /// reserves room in it or the path MTU turns out to be smaller
+    pub fn max_message_size(&self) -> ByteCount {
+        self.max_message_packets * self.encapsulation.max_payload_size()
+    }
+
+    /// The payload bytes of the data taken but not acknowledged by the peer yet, including the
+    /// messages still queued for the send buffer
+    pub fn unacked_bytes(&self) -> usize {
+        let buffered =
+            self.send_buffer.len_bytes() - self.send_buffer.len() * DataPacket::HEADER_SIZE;
+        buffered + self.queued_bytes
+    }
+
+    /// One past the last packet of the data taken, counting the packets of the queued messages,
+    /// which get their sequence numbers once released into the send buffer
+    pub fn written_seq_number(&self) -> SeqNumber {
+        self.encapsulation.next_sequence_number() + self.queued_packets as u32
+    }
+
+    /// One past the last packet the peer acknowledged
+    pub fn acked_seq_number(&self) -> SeqNumber {
+        self.peer_acked
+    }
+
+    pub fn tx_unacknowledged_packets(&self) -> u64 {
+        u64::try_from(self.send_buffer.number_of_unacked_packets()).unwrap()
+    }
+
+    /// Takes out what the peer didn't acknowledge, unencrypted, for a connection that's done: the
+    /// buffered packets, then the queued messages. Along with the sequence number of the first
+    /// one, or of the next one to be sent if there are none.
+    pub fn take_unacked(&mut self) -> (SeqNumber, Vec<DataPacket>) {
+        let mut packets: Vec<_> = self
+            .send_buffer
+            .take_packets()
+            .into_iter()
+            .filter_map(|packet| self.encryption.decrypt(packet))
+            .collect();
+        let first = match packets.first() {
+            Some(packet) => packet.seq_number,
+            None => self.encapsulation.next_sequence_number(),
+        };
+        for (_, messages) in std::mem::take(&mut self.queue) {
+            for message in messages {
+                packets.extend(
+                    self.encapsulation
+                        .encapsulate(message.timestamp, message.data),
+                );
+            }
+        }
+        self.queued_packets = 0;
+        self.queued_bytes = 0;
+        (first, packets)
+    }
 }
 
 pub struct SenderContext<'a> {
@@ -72,6 +232,7 @@ pub struct SenderContext<'a> {
     output: &'a mut Output,
     stats: &'a mut SocketStatistics,
     sender: &'a mut Sender,
+    filter: &'a mut dyn PacketFilter,
 }
 
 impl<'a> SenderContext<'a> {
@@ -81,6 +242,7 @@ impl<'a> SenderContext<'a> {
         output: &'a mut Output,
         stats: &'a mut SocketStatistics,
         sender: &'a mut Sender,
+        filter: &'a mut dyn PacketFilter,
     ) -> Self {
         Self {
             status,
@@ -88,39 +250,205 @@ impl<'a> SenderContext<'a> {
             output,
             stats,
             sender,
+            filter,
         }
     }
 
-    pub fn handle_data(&mut self, now: Instant, item: (Instant, Bytes)) {
+    /// Buffers a message for sending, returning the number of packets dropped to make room for it
+    ///
+    /// It goes straight into the send buffer unless there are packets there still waiting to be
+    /// sent, then it's queued by its priority until there aren't.
+    pub fn handle_data(
+        &mut self,
+        now: Instant,
+        item: (Instant, Bytes),
+        priority: Priority,
+    ) -> Result<PacketCount, MessageTooLargeError> {
         let (time, data) = item;
-        let (mut packets, mut bytes) = (0, 0);
-        let ts = self.sender.time_base.timestamp_from(time);
-        for packet in self.sender.encapsulation.encapsulate(ts, data) {
-            if let Some((bytes_enc, packet, km)) = self.sender.encryption.encrypt(packet) {
-                packets += 1;
-                bytes += packet.payload.len() as u64;
-                if bytes_enc > 0 {
-                    self.stats.tx_encrypted_data += 1;
-                }
+        let size = ByteCount(data.len() as u64);
+        let max_message_size = self.sender.max_message_size();
+        if size > max_message_size {
+            self.stats.tx_dropped_data += 1;
+            self.stats.tx_dropped_bytes += size.0;
+            return Err(MessageTooLargeError {
+                size,
+                max_message_size,
+            });
+        }
 
-                if let Err((p_count, b_count)) = self.sender.send_buffer.push_data(packet) {
-                    self.stats.tx_dropped_data += p_count.0;
-                    self.stats.tx_dropped_bytes += b_count.0;
+        // stamped with the origin time rather than when it goes out, so however long it waits in
+        // the queue, the receiver plays it out as it came in
+        let timestamp = self.sender.time_base.timestamp_from(time);
+        let (packets, bytes, dropped) =
+            if self.sender.queue.is_empty() && !self.sender.send_buffer.has_unsent_packets() {
+                self.buffer_message(now, timestamp, data, priority)
+            } else {
+                let max_payload_size = self.sender.encapsulation.max_payload_size().0 as usize;
+                let packets = data.len().div_ceil(max_payload_size).max(1);
+                let bytes = data.len() as u64;
+                self.sender.queued_packets += packets;
+                self.sender.queued_bytes += data.len();
+                self.sender
+                    .queue
+                    .entry(Reverse(priority))
+                    .or_default()
+                    .push_back(QueuedMessage {
+                        timestamp,
+                        data,
+                        packets,
+                    });
+                if let InputOverflowPolicy::DropOldest(span) = self.sender.input_overflow {
+                    self.drop_queued_before(timestamp - span);
                 }
-
-                let control = km.map(ControlTypes::new_key_refresh_request);
-                if let Some(control) = control {
-                    self.output.send_control(now, control);
+                if self.sender.flow_window_full == FlowWindowFull::DropOldest
+                    && self.sender.send_buffer.flow_window_exhausted()
+                {
+                    self.drop_queued_but_newest(priority);
                 }
+                // the oldest packets make room, as if the queued messages were in the buffer
+                let dropped =
+                    if self.sender.buffer_full == SendBufferFull::Drop && self.sender.is_full() {
+                        self.release_queued(now, usize::MAX)
+                    } else {
+                        0
+                    };
+                (packets as u64, bytes, dropped)
+            };
+
+        let snd_period =
+            self.sender
+                .congestion_control
+                .on_input(now, PacketCount(packets), ByteCount(bytes));
+        if let Some(snd_period) = snd_period {
+            self.timers.update_snd_period(snd_period);
+            self.stats.tx_snd_period = snd_period;
+            self.stats.tx_send_rate = self.sender.congestion_control.send_rate().0;
+        }
+
+        Ok(PacketCount(dropped))
+    }
+
+    // packetizes the queued messages, highest priority first, until there are `packets` waiting
+    // to be sent in the send buffer
+    fn release_queued(&mut self, now: Instant, packets: usize) -> u64 {
+        let mut dropped = 0;
+        while self.sender.send_buffer.unsent_packets() < packets {
+            let Some(mut entry) = self.sender.queue.first_entry() else {
+                break;
+            };
+            let Reverse(priority) = *entry.key();
+            let message = entry.get_mut().pop_front().unwrap();
+            if entry.get().is_empty() {
+                let _ = entry.remove();
+            }
+            self.sender.queued_packets -= message.packets;
+            self.sender.queued_bytes -= message.data.len();
+            let (_, _, d) = self.buffer_message(now, message.timestamp, message.data, priority);
+            dropped += d;
+        }
+        dropped
+    }
+
+    // drops the queued messages from before `oldest`, whatever their priority
+    fn drop_queued_before(&mut self, oldest: TimeStamp) {
+        for messages in self.sender.queue.values_mut() {
+            while let Some(message) = messages.front().filter(|m| m.timestamp < oldest) {
+                self.sender.queued_packets -= message.packets;
+                self.sender.queued_bytes -= message.data.len();
+                self.stats.tx_dropped_data += message.packets as u64;
+                self.stats.tx_dropped_bytes += message.data.len() as u64;
+                self.stats.tx_input_dropped_data += message.packets as u64;
+                let _ = messages.pop_front();
             }
         }
+        self.sender.queue.retain(|_, messages| !messages.is_empty());
+    }
+
+    // drops the queued messages but the newest, which was just queued with `priority`
+    fn drop_queued_but_newest(&mut self, priority: Priority) {
+        for (Reverse(p), messages) in self.sender.queue.iter_mut() {
+            let keep = usize::from(*p == priority);
+            while messages.len() > keep {
+                let message = messages.pop_front().unwrap();
+                self.sender.queued_packets -= message.packets;
+                self.sender.queued_bytes -= message.data.len();
+                self.stats.tx_dropped_data += message.packets as u64;
+                self.stats.tx_dropped_bytes += message.data.len() as u64;
+                self.stats.tx_flow_window_dropped_data += message.packets as u64;
+            }
+        }
+        self.sender.queue.retain(|_, messages| !messages.is_empty());
+    }
+
+    // returns the packets and bytes buffered, and the packets dropped to make room for them
+    fn buffer_message(
+        &mut self,
+        now: Instant,
+        timestamp: TimeStamp,
+        data: Bytes,
+        priority: Priority,
+    ) -> (u64, u64, u64) {
+        let (mut packets, mut bytes, mut dropped) = (0, 0, 0);
+        for packet in self.sender.encapsulation.encapsulate(timestamp, data) {
+            if let Some((size, d)) = self.buffer_packet(now, packet, priority) {
+                packets += 1;
+                bytes += size;
+                dropped += d;
+            }
+        }
+        (packets, bytes, dropped)
+    }
+
+    // encrypts and buffers a packet, returning its payload size once encrypted, and the packets
+    // dropped to make room for it
+    fn buffer_packet(
+        &mut self,
+        now: Instant,
+        packet: DataPacket,
+        priority: Priority,
+    ) -> Option<(u64, u64)> {
+        let (bytes_enc, packet, km) = self.sender.encryption.encrypt(packet)?;
+        let size = packet.payload.len() as u64;
+        if bytes_enc > 0 {
+            self.stats.tx_encrypted_data += 1;
+        }
+
+        let mut dropped = 0;
+        if let Err((p_count, b_count)) = self.sender.send_buffer.push_data(packet, priority) {
+            self.stats.tx_dropped_data += p_count.0;
+            self.stats.tx_dropped_bytes += b_count.0;
+            self.stats.tx_buffer_full_dropped_data += p_count.0;
+            dropped = p_count.0;
+        }
+
+        let control = km.map(ControlTypes::new_key_refresh_request);
+        if let Some(control) = control {
+            self.output.send_control(now, control);
+        }
+        Some((size, dropped))
+    }
 
+    /// Buffers what a connection this one resumes didn't get acknowledged, ahead of anything
+    /// else, stamped as sent now. The connection starts from the sequence number of the first
+    /// packet, they keep theirs.
+    pub fn handle_resumption(&mut self, now: Instant, packets: Vec<DataPacket>) {
+        let timestamp = self.sender.time_base.timestamp_from(now);
+        let (mut count, mut bytes) = (0, 0);
+        for packet in packets {
+            let packet = self.sender.encapsulation.resume(timestamp, packet);
+            if let Some((size, _)) = self.buffer_packet(now, packet, Priority::Normal) {
+                count += 1;
+                bytes += size;
+            }
+        }
         let snd_period =
             self.sender
                 .congestion_control
-                .on_input(now, PacketCount(packets), ByteCount(bytes));
+                .on_input(now, PacketCount(count), ByteCount(bytes));
         if let Some(snd_period) = snd_period {
-            self.timers.update_snd_period(snd_period)
+            self.timers.update_snd_period(snd_period);
+            self.stats.tx_send_rate = self.sender.congestion_control.send_rate().0;
+            self.stats.tx_snd_period = snd_period;
         }
     }
 
@@ -130,17 +458,26 @@ impl<'a> SenderContext<'a> {
             self.stats.rx_light_ack += 1;
         }
 
+        let ack_number = ack.ack_number();
+        if ack_number > self.sender.peer_acked
+            && ack_number <= self.sender.encapsulation.next_sequence_number()
+        {
+            self.sender.peer_acked = ack_number;
+        }
+
         match self.sender.send_buffer.update_largest_acked_seq_number(
             ack.ack_number(),
             ack.full_ack_seq_number(),
             ack.rtt(),
         ) {
             Ok(AckAction {
-                received: _,
+                received,
                 recovered: _,
                 send_ack2,
             }) => {
                 // TODO: add received and recovered to connection statistics
+                let backlog = self.sender.send_buffer.has_packets_to_send();
+                self.sender.idle_restart.on_ack(received, backlog);
                 if let Some(full_ack) = send_ack2 {
                     self.output.send_control(now, ControlTypes::Ack2(full_ack))
                 }
@@ -155,14 +492,16 @@ impl<'a> SenderContext<'a> {
 
     pub fn handle_nak_packet(&mut self, now: Instant, nak: CompressedLossList) {
         self.stats.rx_nak += 1;
+        self.sender.idle_restart.on_loss();
+        #[cfg(feature = "path-mtu")]
+        self.detect_path_mtu(&nak);
         // 1) Add all sequence numbers carried in the NAK into the sender's loss list.
         for (loss, range) in self.sender.send_buffer.add_to_loss_list(nak) {
             //self.debug("nak", now, &(&loss, &range));
-            // TODO: figure out better statistics
             use Loss::*;
             match loss {
                 Ignored | Added => {
-                    self.stats.tx_loss_data += 1;
+                    self.stats.tx_loss_data += (range.end - range.start) as u64;
                 }
                 Dropped => {
                     self.stats.tx_dropped_data += 1;
@@ -187,6 +526,17 @@ impl<'a> SenderContext<'a> {
         }
     }
 
+    #[cfg(feature = "path-mtu")]
+    fn detect_path_mtu(&mut self, nak: &CompressedLossList) {
+        let report = self.sender.send_buffer.loss_report(nak);
+        if let Some(size) = self.sender.path_mtu.on_loss_report(report) {
+            log::warn!(
+                "only packets with up to {size} of payload get through, falling back to that size"
+            );
+            self.sender.encapsulation.limit_payload(size);
+        }
+    }
+
     pub fn handle_key_refresh_response(&mut self, keying_material: KeyingMaterialMessage) {
         match self
             .sender
@@ -202,8 +552,34 @@ impl<'a> SenderContext<'a> {
         }
     }
 
+    pub fn handle_latency_change_request(&mut self, now: Instant, latency: Duration) {
+        // the receiver decides how much latency it needs, accept it as is. Requests repeated
+        // until the response gets through change nothing more
+        self.sender.tsbpd_latency = latency;
+        self.sender.send_buffer.set_latency(latency);
+        self.output.send_control(
+            now,
+            ControlTypes::Srt(SrtControlPacket::LatencyChangeResponse(latency)),
+        );
+    }
+
     pub fn on_snd_event(&mut self, now: Instant, elapsed_periods: u32) {
         use SenderAction::*;
+        if self.sender.paused {
+            return;
+        }
+        // the periods that went by while idle aren't made up for, the backlog is clocked out by
+        // the ACKs instead
+        let elapsed_periods = if self.sender.idle_restart.on_snd_event(now) {
+            1
+        } else {
+            elapsed_periods
+        };
+        let window = self.sender.idle_restart.window();
+        self.sender.send_buffer.set_congestion_window(window);
+        // one more than the periods, the packet after a 16n one goes out right along with it
+        let _ = self.release_queued(now, elapsed_periods as usize + 1);
+
         let ts_now = self.sender.time_base.timestamp_from(now);
         let actions = self.sender.send_buffer.next_snd_actions(
             ts_now,
@@ -214,17 +590,37 @@ impl<'a> SenderContext<'a> {
             match action {
                 Send(d) => {
                     self.stats.tx_unique_data += 1;
+                    self.stats.tx_unique_bytes += d.wire_size() as u64;
+                    let extra = self.filter.on_send(&d);
                     self.output.send_data(now, d);
+                    self.sender.idle_restart.on_sent(now);
+                    for packet in extra {
+                        self.stats.tx_filter_extra_data += 1;
+                        self.output.send_data(now, packet);
+                    }
                 }
                 RetransmitNak(d) => {
                     self.stats.tx_retransmit_data += 1;
+                    self.stats.tx_retransmit_bytes += d.wire_size() as u64;
                     self.output.send_data(now, d);
+                    self.sender.idle_restart.on_sent(now);
                 }
                 RetransmitRto(d) => {
                     self.stats.tx_retransmit_data += 1;
+                    self.stats.tx_retransmit_bytes += d.wire_size() as u64;
                     self.output.send_data(now, d);
+                    self.sender.idle_restart.on_sent(now);
                 }
+                #[cfg(feature = "tracing")]
+                Drop(range) => crate::connection::trace::too_late_dropped("sender", &range),
+                #[cfg(not(feature = "tracing"))]
                 Drop(_) => {}
+                RetransmitLimit(seq) => {
+                    log::warn!("{seq:?} went over the maximum retransmissions, giving up");
+                    self.output.send_control(now, ControlTypes::Shutdown);
+                    self.status.on_retransmit_limit(now);
+                    break;
+                }
                 WaitForInput => {
                     break;
                 }
@@ -0,0 +1,130 @@
+//! Path MTU blackhole detection. When the path MTU drops below the size of the data packets,
+//! e.g. after a route change, every packet that's too large is lost while smaller ones still get
+//! through, so the sender falls back to the next smaller common MTU.
+
+use crate::options::PacketSize;
+
+// payloads for the path MTU plateaus of RFC 1191 (PPPoE, the IPv6 minimum, SLIP, the IPv4
+// minimum), less the 44 bytes of IPv4, UDP and SRT headers
+const PLATEAUS: [u64; 4] = [1492 - 44, 1280 - 44, 1006 - 44, 576 - 44];
+
+// how many losses of packets above the next plateau it takes to fall back to it, as long as
+// none of them got through and smaller ones did
+const LARGE_LOSSES: u32 = 16;
+const SMALL_RECEIVED: u32 = 4;
+
+#[derive(Debug)]
+pub struct PathMtuDetection {
+    max_payload_size: PacketSize,
+    large_lost: u32,
+    large_received: u32,
+    small_received: u32,
+}
+
+impl PathMtuDetection {
+    pub fn new(max_payload_size: PacketSize) -> Self {
+        Self {
+            max_payload_size,
+            large_lost: 0,
+            large_received: 0,
+            small_received: 0,
+        }
+    }
+
+    /// Takes the payload size of each packet a NAK covers, and whether it was lost. Returns the
+    /// payload size to fall back to when only the larger packets don't get through.
+    pub fn on_loss_report(
+        &mut self,
+        packets: impl IntoIterator<Item = (usize, bool)>,
+    ) -> Option<PacketSize> {
+        let fallback = self.fallback()?;
+        for (size, lost) in packets {
+            let size = size as u64;
+            // sent before the last fallback, no news there
+            if size > self.max_payload_size.0 {
+                continue;
+            }
+            match (size > fallback.0, lost) {
+                (true, true) => self.large_lost += 1,
+                (true, false) => self.large_received += 1,
+                (false, false) => self.small_received += 1,
+                (false, true) => {}
+            }
+        }
+
+        if self.large_received > 0 {
+            // large packets make it, the losses are nothing out of the ordinary
+            self.reset();
+            None
+        } else if self.large_lost >= LARGE_LOSSES && self.small_received >= SMALL_RECEIVED {
+            self.max_payload_size = fallback;
+            self.reset();
+            Some(fallback)
+        } else {
+            None
+        }
+    }
+
+    fn fallback(&self) -> Option<PacketSize> {
+        PLATEAUS
+            .into_iter()
+            .find(|size| *size < self.max_payload_size.0)
+            .map(PacketSize)
+    }
+
+    fn reset(&mut self) {
+        self.large_lost = 0;
+        self.large_received = 0;
+        self.small_received = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn large_packets_lost() {
+        let mut detection = PathMtuDetection::new(PacketSize(1316));
+        let report = [(1316, true), (1316, true), (1316, true), (200, false)];
+        for _ in 0..5 {
+            assert_eq!(detection.on_loss_report(report), None);
+        }
+        assert_eq!(detection.on_loss_report(report), Some(PacketSize(1236)));
+
+        // packets sent before falling back are ignored, the next plateau is tried the same way
+        let report = [(1316, true), (1236, true), (1236, true), (200, false)];
+        for _ in 0..7 {
+            assert_eq!(detection.on_loss_report(report), None);
+        }
+        assert_eq!(detection.on_loss_report(report), Some(PacketSize(962)));
+    }
+
+    #[test]
+    fn large_packet_received() {
+        let mut detection = PathMtuDetection::new(PacketSize(1316));
+        let report = [(1316, true), (1316, true), (1316, true), (200, false)];
+        for _ in 0..5 {
+            assert_eq!(detection.on_loss_report(report), None);
+        }
+        assert_eq!(detection.on_loss_report([(1316, false)]), None);
+        assert_eq!(detection.on_loss_report(report), None);
+    }
+
+    #[test]
+    fn small_packets_lost() {
+        let mut detection = PathMtuDetection::new(PacketSize(1316));
+        let report = [(1316, true), (200, true)];
+        for _ in 0..100 {
+            assert_eq!(detection.on_loss_report(report), None);
+        }
+    }
+
+    #[test]
+    fn smallest_plateau() {
+        let mut detection = PathMtuDetection::new(PacketSize(532));
+        for _ in 0..100 {
+            assert_eq!(detection.on_loss_report([(532, true), (100, false)]), None);
+        }
+    }
+}
@@ -1,11 +1,13 @@
 mod base;
-mod rtt;
 mod timer;
 
 pub use base::*;
-pub use rtt::*;
 pub use timer::*;
 
+pub use crate::packet::Rtt;
+
+use crate::options::NakPeriodConfig;
+
 use std::{
     cmp::{max, min},
     time::{Duration, Instant},
@@ -32,6 +34,7 @@ pub struct Timers {
     //   is dynamically updated to 4 * RTT_+ RTTVar + SYN, where RTTVar is the
     //   variance of RTT samples.
     nak: Timer,
+    nak_period: NakPeriodConfig,
 
     //   EXP is used to trigger data packets retransmission and maintain
     //   connection status. Its period is dynamically updated to 4 * RTT +
@@ -47,12 +50,18 @@ impl Timers {
     pub const SYN: Duration = Duration::from_millis(10);
     const EXP_MAX: u32 = 16;
 
-    pub fn new(now: Instant, statistics_interval: Duration, peer_idle_timeout: Duration) -> Self {
-        let (ack, nak, exp) = Self::calculate_periods(1, &Rtt::default());
+    pub fn new(
+        now: Instant,
+        statistics_interval: Duration,
+        peer_idle_timeout: Duration,
+        nak_period: NakPeriodConfig,
+    ) -> Self {
+        let (ack, nak, exp) = Self::calculate_periods(1, &Rtt::default(), &nak_period);
         Self {
             snd: Timer::new(now, Duration::from_millis(1)),
             full_ack: Timer::new(now, ack),
             nak: Timer::new(now, nak),
+            nak_period,
             exp: Timer::new(now, exp),
             exp_count: 1,
             peer_idle: Timer::new(now, peer_idle_timeout),
@@ -118,7 +127,7 @@ impl Timers {
     }
 
     pub fn update_rtt(&mut self, rtt: &Rtt) {
-        let (ack, nak, exp) = Self::calculate_periods(self.exp_count, rtt);
+        let (ack, nak, exp) = Self::calculate_periods(self.exp_count, rtt, &self.nak_period);
         self.full_ack.set_period(ack);
         self.nak.set_period(nak);
         self.exp.set_period(exp);
@@ -129,12 +138,18 @@ impl Timers {
         self.peer_idle.reset(now)
     }
 
-    fn calculate_periods(exp_count: u32, rtt: &Rtt) -> (Duration, Duration, Duration) {
+    fn calculate_periods(
+        exp_count: u32,
+        rtt: &Rtt,
+        nak: &NakPeriodConfig,
+    ) -> (Duration, Duration, Duration) {
         let ms = Duration::from_millis;
 
-        // NAKInterval = min((RTT + 4 * RTTVar) / 2, 20000) - i.e. floor of 20ms
-        let nak_rtt_period = (rtt.mean_as_duration() + 4 * rtt.variance_as_duration()) / 2;
-        let nak_period = max(nak_rtt_period, ms(20));
+        // NAKInterval = min((RTT + 4 * RTTVar) / 2, 20000) - i.e. floor of 20ms by default
+        let nak_rtt = rtt.mean_as_duration() + 4 * rtt.variance_as_duration();
+        let multiplier = u32::try_from(nak.multiplier.0).unwrap_or(u32::MAX);
+        let nak_rtt_period = nak_rtt.saturating_mul(multiplier) / 100;
+        let nak_period = max(nak_rtt_period, nak.floor);
 
         // 0.5s minimum, according to page 9
         // but 0.3s in reference implementation
@@ -152,7 +167,7 @@ mod tests {
 
     use super::*;
 
-    use crate::packet::TimeSpan;
+    use crate::{options::Percent, packet::TimeSpan};
 
     proptest! {
         #[test]
@@ -171,7 +186,7 @@ mod tests {
             prop_assume!((rtt_mean + 4 * rtt_variance) / 2 > ms(20));
 
             let start = Instant::now();
-            let mut timers = Timers::new(start, ms(10_000), ms(5_000));
+            let mut timers = Timers::new(start, ms(10_000), ms(5_000), Default::default());
 
             timers.update_rtt(&rtt);
 
@@ -199,7 +214,7 @@ mod tests {
             prop_assume!(4 * rtt_mean + rtt_variance + syn > ms(300));
 
             let start = Instant::now();
-            let mut timers = Timers::new(start, ms(10_000), ms(5_000));
+            let mut timers = Timers::new(start, ms(10_000), ms(5_000), Default::default());
 
             timers.update_rtt(&rtt);
 
@@ -229,7 +244,7 @@ mod tests {
             prop_assume!((rtt_mean + 4 * rtt_variance) / 2 <= ms(20));
 
             let start = Instant::now();
-            let mut timers = Timers::new(start, ms(10_000), ms(5_000));
+            let mut timers = Timers::new(start, ms(10_000), ms(5_000), Default::default());
 
             timers.update_rtt(&rtt);
 
@@ -257,7 +272,7 @@ mod tests {
             prop_assume!(4 * rtt_mean + rtt_variance + syn <= ms(300));
 
             let start = Instant::now();
-            let mut timers = Timers::new(start, ms(10_000), ms(5_000));
+            let mut timers = Timers::new(start, ms(10_000), ms(5_000), Default::default());
 
             timers.update_rtt(&rtt);
 
@@ -269,11 +284,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn nak_period_config() {
+        let ms = Duration::from_millis;
+        let rtt_of = |micros| {
+            let mut rtt = Rtt::default();
+            for _ in 0..1000 {
+                rtt.update(TimeSpan::from_micros(micros));
+            }
+            rtt
+        };
+        let nak_period = NakPeriodConfig {
+            multiplier: Percent(25),
+            floor: ms(5),
+        };
+        let start = Instant::now();
+        let mut timers = Timers::new(start, ms(10_000), ms(5_000), nak_period);
+
+        // a quarter of RTT + 4 * RTTVar, which follows the RTT as it changes
+        let rtt = rtt_of(100_000);
+        timers.update_rtt(&rtt);
+        let nak_rtt = rtt.mean_as_duration() + 4 * rtt.variance_as_duration();
+        assert_eq!(timers.nak.next_instant() - start, nak_rtt / 4);
+
+        let rtt = rtt_of(200_000);
+        timers.update_rtt(&rtt);
+        let nak_rtt = rtt.mean_as_duration() + 4 * rtt.variance_as_duration();
+        assert_eq!(timers.nak.next_instant() - start, nak_rtt / 4);
+
+        // but no shorter than the floor
+        timers.update_rtt(&rtt_of(10_000));
+        assert_eq!(timers.nak.next_instant() - start, ms(5));
+    }
+
     #[test]
     fn next_timer() {
         let ms = TimeSpan::from_millis;
         let start = Instant::now();
-        let mut timers = Timers::new(start, Duration::MAX, Duration::from_millis(5_000));
+        let mut timers = Timers::new(
+            start,
+            Duration::MAX,
+            Duration::from_millis(5_000),
+            Default::default(),
+        );
 
         // next timer should be ack, 10ms
         let now = start;
@@ -1,14 +1,27 @@
-use std::{convert::TryInto, marker::PhantomData, net::SocketAddr};
+use std::{convert::TryInto, marker::PhantomData, net::SocketAddr, time::Duration};
 
-use crate::{packet::RejectReason, settings::KeySettings};
+use crate::{
+    options::PacketCount,
+    packet::RejectReason,
+    settings::{ConnInitSettings, KeySettings},
+};
 
+/// What an accepted connection uses in place of the listener's own settings, the ones left unset
+/// are the listener's. They apply before the handshake concludes, so the latencies are
+/// negotiated with the caller's as usual.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct AcceptParameters {
     key_settings: Option<KeySettings>,
+    send_latency: Option<Duration>,
+    recv_latency: Option<Duration>,
+    recv_buffer_size: Option<PacketCount>,
+    send_buffer_size: Option<PacketCount>,
+    peer_idle_timeout: Option<Duration>,
 }
 
 impl AcceptParameters {
     pub fn new() -> AcceptParameters {
-        AcceptParameters { key_settings: None }
+        AcceptParameters::default()
     }
 
     pub fn set_key_settings(&mut self, passphrase: impl Into<String>, size: u16) -> &mut Self {
@@ -19,14 +32,68 @@ impl AcceptParameters {
         self
     }
 
+    /// Like [`set_key_settings`](Self::set_key_settings), with settings that are valid already
+    pub fn set_validated_key_settings(&mut self, key_settings: KeySettings) -> &mut Self {
+        self.key_settings = Some(key_settings);
+        self
+    }
+
     pub fn take_key_settings(&mut self) -> Option<KeySettings> {
         self.key_settings.take()
     }
-}
 
-impl Default for AcceptParameters {
-    fn default() -> Self {
-        AcceptParameters::new()
+    /// Both the send and the receive latency
+    pub fn set_latency(&mut self, latency: Duration) -> &mut Self {
+        self.set_send_latency(latency).set_receive_latency(latency)
+    }
+
+    pub fn set_send_latency(&mut self, latency: Duration) -> &mut Self {
+        self.send_latency = Some(latency);
+        self
+    }
+
+    pub fn set_receive_latency(&mut self, latency: Duration) -> &mut Self {
+        self.recv_latency = Some(latency);
+        self
+    }
+
+    /// In packets, like [`ConnInitSettings::recv_buffer_size`]
+    pub fn set_receive_buffer_size(&mut self, size: PacketCount) -> &mut Self {
+        self.recv_buffer_size = Some(size);
+        self
+    }
+
+    /// In packets, like [`ConnInitSettings::send_buffer_size`]
+    pub fn set_send_buffer_size(&mut self, size: PacketCount) -> &mut Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    pub fn set_peer_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.peer_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Overrides the listener's settings with the ones that are set, for a single connection
+    pub fn apply(self, settings: &mut ConnInitSettings) {
+        if let Some(key_settings) = self.key_settings {
+            settings.key_settings = Some(key_settings);
+        }
+        if let Some(latency) = self.send_latency {
+            settings.send_latency = latency;
+        }
+        if let Some(latency) = self.recv_latency {
+            settings.recv_latency = latency;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            settings.recv_buffer_size = size;
+        }
+        if let Some(size) = self.send_buffer_size {
+            settings.send_buffer_size = size;
+        }
+        if let Some(timeout) = self.peer_idle_timeout {
+            settings.peer_idle_timeout = timeout;
+        }
     }
 }
 
@@ -2,7 +2,11 @@ use std::time::Duration;
 
 use rand::random;
 
-use crate::{options, packet::Packet};
+use crate::{
+    connection::ResumableSessions,
+    options,
+    packet::{FilterSpec, Packet, SessionResume, SrtShakeFlags},
+};
 
 use super::*;
 
@@ -10,19 +14,62 @@ use super::*;
 pub struct ConnInitSettings {
     pub local_sockid: SocketId,
     pub key_settings: Option<KeySettings>,
+    /// Passphrases the keys a peer sends are unwrapped with when the one in the key settings fails
+    pub alternate_passphrases: Vec<Passphrase>,
     pub key_refresh: KeyMaterialRefreshSettings,
     pub send_latency: Duration,
     pub recv_latency: Duration,
     pub peer_idle_timeout: Duration,
     pub bandwidth: options::LiveBandwidthMode,
+    pub retransmit_bandwidth: options::RetransmitBandwidth,
+    pub send_buffer_full: options::SendBufferFull,
+    pub flow_window_full: options::FlowWindowFull,
+    pub input_overflow: options::InputOverflowPolicy,
+    /// How many messages may wait in front of the send buffer, for whatever feeds the connection
+    pub send_queue_depth: usize,
     pub statistics_interval: Duration,
+    pub loss_rate_window: Duration,
+    /// The round trip time assumed until it's measured
+    pub initial_rtt: Option<Duration>,
+    /// How much later than its latency a packet may be before it's too late
+    pub too_late_drop_margin: options::Percent,
     pub too_late_packet_drop: bool,
+    pub delivery_mode: options::DeliveryMode,
+    pub nak_period: options::NakPeriodConfig,
+    pub recv_granularity: options::RecvGranularity,
     /// Receive buffer size in packets
     pub recv_buffer_size: options::PacketCount,
+    /// The size the receive buffer may grow to as the stream needs it, in packets
+    pub recv_buffer_cap: Option<options::PacketCount>,
     /// Size of the send buffer, in packets
     pub send_buffer_size: options::PacketCount,
+    /// The largest payload of a data packet
     pub max_packet_size: options::PacketSize,
+    /// The MSS advertised to the peer during the handshake, including the UDP/IP headers
+    pub max_segment_size: options::PacketSize,
     pub max_flow_size: options::PacketCount,
+    /// How long the sender is idle before it restarts from a small number of packets in flight
+    pub idle_restart_threshold: Option<Duration>,
+    /// How many times a packet is retransmitted before the connection is given up on
+    pub max_retransmits: Option<u32>,
+    /// Capabilities advertised to the peer during the handshake
+    pub features: SrtShakeFlags,
+    /// How often, and how many times, an unanswered handshake is sent again
+    pub handshake_retry_interval: Duration,
+    pub max_handshake_retries: Option<u32>,
+    /// How many half-open connections a listener keeps track of, and for how long
+    pub max_half_open: usize,
+    pub half_open_timeout: Duration,
+    /// The networks a listener accepts handshakes from, None for anywhere
+    pub allow_from: Option<Vec<options::IpNet>>,
+    /// Set when connections can be resumed, a listener records how far each got in it
+    pub resumable_sessions: Option<ResumableSessions>,
+    /// The session a caller resumes, None to start one of its own
+    pub resume: Option<SessionResume>,
+    pub packet_filter: Option<FilterSpec>,
+    /// Who this party proves to be in the handshake, and who it accepts
+    #[cfg(feature = "authentication")]
+    pub credentials: Option<options::Credentials>,
 }
 
 impl Default for ConnInitSettings {
@@ -52,6 +99,7 @@ impl From<options::SocketOptions> for ConnInitSettings {
                     key_size: options.encryption.key_size,
                     passphrase,
                 }),
+            alternate_passphrases: options.encryption.alternate_passphrases.clone(),
             key_refresh: KeyMaterialRefreshSettings::new(
                 options.encryption.km_refresh.period.into(),
                 options.encryption.km_refresh.pre_announcement_period.into(),
@@ -61,14 +109,53 @@ impl From<options::SocketOptions> for ConnInitSettings {
             recv_latency: options.receiver.latency,
             peer_idle_timeout: options.session.peer_idle_timeout,
             bandwidth: options.sender.bandwidth,
+            retransmit_bandwidth: options.sender.retransmit_bandwidth,
+            send_buffer_full: options.sender.buffer_full,
+            flow_window_full: options.sender.flow_window_full,
+            input_overflow: options.sender.input_overflow,
+            send_queue_depth: options.sender.queue_depth,
             statistics_interval: options.session.statistics_interval,
+            loss_rate_window: options.session.loss_rate_window,
+            initial_rtt: options.session.initial_rtt,
+            too_late_drop_margin: options.session.too_late_drop_margin,
             recv_buffer_size: options.receiver.buffer_size
                 / (options.session.max_segment_size - Packet::HEADER_SIZE),
+            recv_buffer_cap: options
+                .receiver
+                .adaptive_buffer_cap
+                .map(|cap| cap / (options.session.max_segment_size - Packet::HEADER_SIZE)),
             send_buffer_size: options.sender.buffer_size
                 / (options.session.max_segment_size - Packet::HEADER_SIZE),
             max_packet_size: options.sender.max_payload_size,
+            max_segment_size: options.session.max_segment_size,
             max_flow_size: options.sender.flow_control_window_size,
+            idle_restart_threshold: options.sender.idle_restart_threshold,
+            max_retransmits: options.sender.max_retransmits,
             too_late_packet_drop: options.receiver.too_late_packet_drop,
+            delivery_mode: options.receiver.delivery_mode,
+            nak_period: options.receiver.nak_period,
+            recv_granularity: options.receiver.granularity,
+            features: SrtShakeFlags::SUPPORTED
+                | SrtShakeFlags::LATENCY_CHANGE
+                | SrtShakeFlags::KEEPALIVE_ECHO
+                | if options.session.multiplex {
+                    SrtShakeFlags::MULTIPLEX
+                } else {
+                    SrtShakeFlags::empty()
+                },
+            handshake_retry_interval: options.connect.handshake_retry_interval,
+            max_handshake_retries: options.connect.max_handshake_retries,
+            max_half_open: options.connect.max_half_open,
+            half_open_timeout: options.connect.timeout,
+            allow_from: options.connect.allow_from.clone(),
+            resumable_sessions: options
+                .connect
+                .resumption_window
+                .map(ResumableSessions::new),
+            resume: None,
+            packet_filter: options.session.packet_filter,
+            #[cfg(feature = "authentication")]
+            credentials: options.encryption.credentials,
         }
     }
 }
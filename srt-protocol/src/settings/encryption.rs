@@ -116,13 +116,39 @@ impl CipherSettings {
         km_refresh: &KeyMaterialRefreshSettings,
         key_material: &KeyingMaterialMessage,
     ) -> Result<Self, KeyMaterialError> {
+        let stream_keys = StreamEncryptionKeys::unwrap_from(key_settings, key_material)?;
         Ok(Self {
-            stream_keys: StreamEncryptionKeys::unwrap_from(key_settings, key_material)?,
-            key_settings: key_settings.clone(),
+            key_settings: KeySettings {
+                key_size: stream_keys.key_size().unwrap_or(key_settings.key_size),
+                passphrase: key_settings.passphrase.clone(),
+            },
+            stream_keys,
             key_refresh: km_refresh.clone(),
         })
     }
 
+    /// Unwraps the keys with the passphrase in the key settings, or else with the first of the
+    /// alternates that fits, which is the one kept for the connection
+    pub fn new_with_alternates(
+        key_settings: &KeySettings,
+        alternate_passphrases: &[Passphrase],
+        km_refresh: &KeyMaterialRefreshSettings,
+        key_material: &KeyingMaterialMessage,
+    ) -> Result<Self, KeyMaterialError> {
+        let mut result = Self::new(key_settings, km_refresh, key_material);
+        for passphrase in alternate_passphrases {
+            if result.is_ok() {
+                break;
+            }
+            let key_settings = KeySettings {
+                key_size: key_settings.key_size,
+                passphrase: passphrase.clone(),
+            };
+            result = Self::new(&key_settings, km_refresh, key_material);
+        }
+        result
+    }
+
     pub fn wrap_keying_material(&self) -> Option<KeyingMaterialMessage> {
         self.stream_keys.wrap_with(&self.key_settings)
     }
@@ -0,0 +1,64 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Loss rate over the most recent window of time, computed from snapshots of the cumulative
+/// packet counters taken every time the statistics are updated.
+#[derive(Debug, Clone)]
+pub struct LossRateWindow {
+    window: Duration,
+    // (time, total packets, lost packets)
+    samples: VecDeque<(Instant, u64, u64)>,
+}
+
+impl LossRateWindow {
+    pub fn new(now: Instant, window: Duration) -> Self {
+        Self {
+            window,
+            samples: [(now, 0, 0)].into(),
+        }
+    }
+
+    /// Records the current counter values and returns the fraction of `total` that was lost since
+    /// the start of the window, in parts per million.
+    pub fn update(&mut self, now: Instant, total: u64, lost: u64) -> u32 {
+        self.samples.push_back((now, total, lost));
+
+        // keep the newest sample at or before the window start as the baseline
+        if let Some(window_start) = now.checked_sub(self.window) {
+            while self.samples.len() > 1 && self.samples[1].0 <= window_start {
+                self.samples.pop_front();
+            }
+        }
+
+        let (_, first_total, first_lost) = self.samples.front().unwrap();
+        let total = total.saturating_sub(*first_total);
+        let lost = lost.saturating_sub(*first_lost);
+        match (lost * 1_000_000).checked_div(total) {
+            Some(rate) => rate.min(1_000_000) as u32,
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rate_over_window() {
+        let start = Instant::now();
+        let s = Duration::from_secs;
+
+        let mut loss_rate = LossRateWindow::new(start, s(2));
+        assert_eq!(loss_rate.update(start, 0, 0), 0);
+        assert_eq!(loss_rate.update(start + s(1), 100, 10), 100_000);
+        assert_eq!(loss_rate.update(start + s(2), 200, 10), 50_000);
+        // the first second drops out of the window
+        assert_eq!(loss_rate.update(start + s(3), 300, 10), 0);
+        assert_eq!(loss_rate.update(start + s(4), 400, 60), 250_000);
+        // nothing sent since the window started
+        assert_eq!(loss_rate.update(start + s(7), 400, 60), 0);
+    }
+}
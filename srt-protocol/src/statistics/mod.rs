@@ -1,5 +1,9 @@
+mod loss_rate;
+
 pub use super::listener::ListenerStatistics;
 
+pub(crate) use loss_rate::LossRateWindow;
+
 use std::time::Duration;
 
 /// SRT provides a powerful set of statistical data on a socket. This data can be used to keep an eye
@@ -86,6 +90,13 @@ pub struct SocketStatistics {
     /// Refer to `FASTREXMIT` and `LATEREXMIT` algorithms.
     pub tx_loss_data: u64, // pktSndLossTotal
 
+    /// The fraction of unique DATA packets sent over the recent loss rate window that were
+    /// reported as lost ([tx_loss_data](#tx_loss_data)), in parts per million.
+    ///
+    /// The window is configured with the `loss_rate_window` session option. Unlike the totals,
+    /// this follows the current state of the link, so it's suitable to drive rate adaptation.
+    pub tx_loss_rate_recent_ppm: u32,
+
     /// The total number of SRT DATA packets detected as presently missing (either reordered or lost)
     /// at the receiver side.
     ///
@@ -108,6 +119,14 @@ pub struct SocketStatistics {
     //  In SRT v1.4.0, v1.4.1, the `pktRcvLossTotal` statistic includes packets that failed to be decrypted. To receive the number of presently missing packets, substract [pktRcvUndecryptTotal](#pktRcvUndecryptTotal) from the current one. This is going to be fixed in SRT v.1.5.0.
     pub rx_loss_data: u64, // pktRcvLossTotal
 
+    /// The fraction of DATA packets expected over the recent loss rate window that were detected
+    /// as missing ([rx_loss_data](#rx_loss_data)), in parts per million. Expected packets are the
+    /// unique, recovered and dropped packets, so a packet that went missing is counted once
+    /// whether it was recovered later or not.
+    ///
+    /// The window is configured with the `loss_rate_window` session option.
+    pub rx_loss_rate_recent_ppm: u32,
+
     /// The total number of retransmitted packets sent by the SRT sender.
     ///
     /// This statistic is not interchangeable with the receiver [rx_retransmit_data](#rx_retransmit_data)
@@ -127,6 +146,12 @@ pub struct SocketStatistics {
     //  This is going to be implemented in SRT v1.5.0, see issue [#1208](https://github.com/Haivision/srt/issues/1208).
     pub rx_retransmit_data: u64, // pktRcvRetransTotal
 
+    /// The total number of DATA packets discarded by the receiver because a packet with the same
+    /// sequence number was already in its buffer, e.g. when both a loss report and a timeout made
+    /// the sender retransmit it. Duplicates of packets already delivered to the application are
+    /// counted in [rx_belated_data](#rx_belated_data) instead.
+    pub rx_duplicate_data: u64,
+
     /// The total number of sent ACK (Acknowledgement) control packets.
     pub tx_ack: u64, // pktSentACKTotal
 
@@ -185,30 +210,17 @@ pub struct SocketStatistics {
     /// The total number of packets that failed to be decrypted at the receiver side.
     pub rx_decrypt_errors: u64, // pktRcvUndecryptTotal
 
-    // The total number of packet filter control packets generated by the packet filter (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktSndFilterExtraTotal
+    /// The total number of packets generated by the packet filter, e.g. FEC packets. They are
+    /// not counted in [tx_unique_data](#tx_unique_data).
+    pub tx_filter_extra_data: u64, // pktSndFilterExtraTotal
 
-    // The total number of packet filter control packets received by the packet filter (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // Packet filter control packets contain only control information necessary for the packet filter. The type of these packets is DATA.
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktRcvFilterExtraTotal
+    /// The total number of packets received that belonged to the packet filter itself, they are
+    /// not counted in [rx_unique_data](#rx_unique_data).
+    pub rx_filter_extra_data: u64, // pktRcvFilterExtraTotal
 
-    // The total number of lost DATA packets recovered by the packet filter at the receiver side (e.g., FEC rebuilt packets; refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
-    //
-    // If the `SRTO_PACKETFILTER` socket option is disabled (refer to [SRT API Socket Options](API-socket-options.md)), this statistic is equal to 0. Introduced in SRT v1.4.0.
-    //
-    // TODO: this probably won't be implemented; extensible packet filtering seems like over engineering
-    // #### pktRcvFilterSupplyTotal
+    /// The total number of lost packets rebuilt by the packet filter at the receiver side, e.g.
+    /// through FEC, instead of being retransmitted.
+    pub rx_filter_supply_data: u64, // pktRcvFilterSupplyTotal
 
     // The total number of lost DATA packets **not** recovered by the packet filter at the receiver side (refer to [SRT Packet Filtering & FEC](../features/packet-filtering-and-fec.md)).
     //
@@ -224,6 +236,13 @@ pub struct SocketStatistics {
     /// (20 bytes IPv4 + 8 bytes UDP + 16 bytes SRT).
     pub rx_bytes: u64, // byteRecvTotal
 
+    /// Same as [tx_bytes](#tx_bytes), but only the payload, without any of the headers. The headers
+    /// are what sets the two apart, the encryption flags take no bytes of their own.
+    pub tx_payload_bytes: u64,
+
+    /// Same as [rx_bytes](#rx_bytes), but only the payload, without any of the headers.
+    pub rx_payload_bytes: u64,
+
     /// Same as [tx_unique_data](#tx_unique_data), but expressed in bytes, including payload and all
     /// the headers (20 bytes IPv4 + 8 bytes UDP + 16 bytes SRT).
     pub tx_unique_bytes: u64, // byteSentUniqueTotal
@@ -271,6 +290,18 @@ pub struct SocketStatistics {
     //  be placed in the same sending queue, which may affect the send timing.
     pub tx_snd_period: Duration, // usPktSndPeriod
 
+    /// The rate the sender paces data packets at, in bytes per second, i.e. the mean payload size
+    /// of the input divided by [tx_snd_period](#tx_snd_period). With a maximum bandwidth set, it's
+    /// that bandwidth, give or take the rounding of the period to whole microseconds.
+    ///
+    /// Both are estimated from the input every 100ms, and are zero until the first estimate.
+    pub tx_send_rate: u64,
+
+    /// The largest payload the sender puts in a data packet, in bytes. It's the negotiated
+    /// maximum, less what a packet filter needs, unless path MTU detection (the `path-mtu`
+    /// feature) found that packets that large don't get through and fell back to a smaller size.
+    pub tx_max_payload_size: u64,
+
     /// The maximum number of packets that can be "in flight".
     ///  See also [tx_unacknowledged_data](#tx_unacknowledged_data).
     ///
@@ -355,6 +386,12 @@ pub struct SocketStatistics {
     /// with every packet received from the sender over the UDP link.
     pub rx_buffer_available_bytes: u64, // byteAvailRcvBuf
 
+    /// The size of the receiver's buffer, in packets.
+    ///
+    /// It starts out at the configured size, and grows with the rate the packets arrive at up to
+    /// [`Receiver::adaptive_buffer_cap`](crate::options::Receiver::adaptive_buffer_cap), if set.
+    pub rx_buffer_size: u64,
+
     // Transmission bandwidth limit, in Mbps.
     // Usually this is the setting from
     // the `SRTO_MAXBW` option, which may include the value 0 (unlimited). Under certain
@@ -397,14 +434,10 @@ pub struct SocketStatistics {
     // TODO: also calculate average
     pub tx_buffered_time: Duration, // msSndBuf
 
-    // Timestamp-based Packet Delivery Delay value of the peer.
-    // If `SRTO_TSBPDMODE` is on (default for **live mode**), it
-    // returns the value of `SRTO_PEERLATENCY`, otherwise 0.
-    // The sender reports the TSBPD delay value of the receiver.
-    // The receiver reports the TSBPD delay of the sender.
-    // TODO: Should we implement this?
-    //  it's not actually dynamic, is it? if not then it's uninteresting as a statistic.
-    // #### msSndTsbPdDelay
+    /// Timestamp-based Packet Delivery Delay value of the peer, i.e. the latency negotiated for
+    /// the data sent by this socket. It changes when the peer asks for a different latency.
+    pub tx_tsbpd_delay: Duration, // msSndTsbPdDelay
+
     /// The number of acknowledged packets in receiver's buffer.
     ///
     /// This measurement does not include received but not acknowledged packets, stored in the
@@ -425,13 +458,76 @@ pub struct SocketStatistics {
     /// TODO: also calculate average
     pub rx_acknowledged_time: Duration, // msRcvBuf
 
-    // Timestamp-based Packet Delivery Delay value set on the socket via `SRTO_RCVLATENCY` or `SRTO_LATENCY`.
-    // The value is used to apply TSBPD delay for reading the received data on the socket.
-    //
-    // If `SRTO_TSBPDMODE` is off (default for **file mode**), 0 is returned.
-    // TODO: Should we implement this?
-    //  it's not actually dynamic, is it? if not then it's uninteresting as a statistic.
-    // #### msRcvTsbPdDelay
+    /// The number of received packets held in the receiver's TSBPD buffer, acknowledged or not.
+    ///
+    /// Together with [rx_buffered_time](#rx_buffered_time) this is the current depth of the
+    /// jitter buffer, useful to judge whether the configured latency is adequate.
+    pub rx_buffered_data: u64,
+
+    /// The timespan between the oldest and the newest received packet in the receiver's buffer.
+    ///
+    /// With too late packet drop enabled this stays within the configured latency window.
+    pub rx_buffered_time: Duration,
+
+    /// The number of received packets in the receiver's buffer whose time-to-play has not been
+    /// reached yet.
+    pub rx_pending_data: u64,
+
+    /// The total number of DATA packets dropped by the receiver's TLPKTDROP mechanism for not
+    /// arriving before their time-to-play.
+    ///
+    /// Unlike [rx_dropped_data](#rx_dropped_data), this does not include packets dropped for
+    /// decryption errors.
+    pub rx_too_late_dropped_data: u64,
+
+    /// The total number of DATA packets suspected to have been dropped by the system before they
+    /// were read from the UDP socket, because its receive buffer was full.
+    ///
+    /// These are the gaps in the sequence of original DATA packets read while more datagrams were
+    /// waiting in the socket, which the network alone doesn't explain. They're still reported
+    /// lost and recovered like any other loss, and are included in [rx_loss_data](#rx_loss_data).
+    /// Only counted when the socket is read on a task of its own, see `UdpReadLoop`.
+    pub rx_suspected_kernel_drops: u64,
+
+    /// The total number of DATA packets the sender dropped from its buffer to make room for new
+    /// data, before they were acknowledged, and possibly before they were ever sent.
+    ///
+    /// These are included in [tx_dropped_data](#tx_dropped_data). Always zero when the sender
+    /// waits for room instead, see `SendBufferFull`.
+    pub tx_buffer_full_dropped_data: u64,
+
+    /// The total number of DATA packets the sender dropped before giving them sequence numbers,
+    /// because the data was taken in faster than it could be sent and fell too far behind.
+    ///
+    /// These are included in [tx_dropped_data](#tx_dropped_data). Always zero unless the sender
+    /// drops the oldest data, see `InputOverflowPolicy`.
+    pub tx_input_dropped_data: u64,
+
+    /// The total number of DATA packets the sender dropped before giving them sequence numbers,
+    /// because newer data came in while the flow window was exhausted.
+    ///
+    /// These are included in [tx_dropped_data](#tx_dropped_data). Always zero unless the sender
+    /// drops the oldest data, see `FlowWindowFull`.
+    pub tx_flow_window_dropped_data: u64,
+
+    /// The number of messages sent to the socket that wait in front of the send buffer for the
+    /// connection to take them, as of the last statistics update.
+    ///
+    /// This only builds up when the send buffer pushes back, see `SendBufferFull`, up to the
+    /// configured queue depth. A queue that stays full while the send buffer does means the
+    /// network is the bottleneck, a queue that stays empty means it's the application.
+    pub tx_queued_messages: u64,
+
+    /// The payload bytes of the messages in [tx_queued_messages](#tx_queued_messages).
+    pub tx_queued_bytes: u64,
+
+    /// The most messages that waited in front of the send buffer at once, the high-watermark of
+    /// [tx_queued_messages](#tx_queued_messages).
+    pub tx_queued_messages_max: u64,
+
+    /// Timestamp-based Packet Delivery Delay value applied to the received data. While a latency
+    /// change is in progress this is the value the jitter buffer has reached so far.
+    pub rx_tsbpd_delay: Duration, // msRcvTsbPdDelay
 
     // #### pktReorderDistance
     //
@@ -0,0 +1,330 @@
+//! A deterministic harness for protocol tests, only available with the `test-util` feature.
+//!
+//! [`TestHarness`] connects a sender and a receiver over an in-memory link that delays and loses
+//! packets like the `LossyConn` transport of the integration tests, except that nothing happens
+//! on the wall clock: time only moves when the test [`step`](TestHarness::step)s it, and the
+//! protocol timers and the link delays both go by that manual clock. The losses are drawn from a
+//! seeded generator, so the same test always sees the same packets dropped, at the same times.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use crate::{
+    connection::{Connection, ConnectionSettings, DuplexConnection},
+    options::*,
+    packet::*,
+    protocol::handshake::Handshake,
+    settings::ConnInitSettings,
+};
+
+const SENDER_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2000);
+const RECEIVER_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2001);
+
+/// Which end of the link a packet came from
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Side {
+    Sender,
+    Receiver,
+}
+
+/// A packet that went onto the link, whether it got through or not
+#[derive(Clone, Debug)]
+pub struct Transmission {
+    pub at: Instant,
+    pub from: Side,
+    pub packet: Packet,
+    pub dropped: bool,
+}
+
+/// Decides which packets the link loses on top of the random ones, `true` to drop it
+pub type DropFilter = Box<dyn FnMut(&Transmission) -> bool + Send>;
+
+/// A sender and a receiver connected over a lossy, delayed in-memory link, driven by a manual
+/// clock.
+///
+/// ```
+/// # use std::time::Duration;
+/// # use srt_protocol::test_util::TestHarness;
+/// let mut harness = TestHarness::new(Duration::from_millis(100));
+/// harness.set_delay(Duration::from_millis(20));
+/// harness.send("hello");
+/// harness.step(Duration::from_millis(200));
+/// let received = harness.take_received();
+/// assert_eq!(received[0].1, "hello");
+/// ```
+pub struct TestHarness {
+    start: Instant,
+    now: Instant,
+    pub sender: DuplexConnection,
+    pub receiver: DuplexConnection,
+    delay: Duration,
+    loss: f64,
+    rng: StdRng,
+    drop_filter: Option<DropFilter>,
+    in_flight: BinaryHeap<Reverse<InFlight>>,
+    next_id: u64,
+    transmissions: Vec<Transmission>,
+    received: Vec<(Instant, Bytes)>,
+}
+
+// ordered by release time, then by when it was sent, so packets released at the same instant
+// arrive in order
+struct InFlight {
+    release_at: Instant,
+    id: u64,
+    to: Side,
+    packet: Packet,
+}
+
+impl PartialEq for InFlight {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for InFlight {}
+
+impl PartialOrd for InFlight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InFlight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.release_at, self.id).cmp(&(other.release_at, other.id))
+    }
+}
+
+impl TestHarness {
+    /// Connected with the default options and `latency` both ways, over a link with no delay
+    /// and no loss
+    pub fn new(latency: Duration) -> Self {
+        Self::with_settings(|settings| {
+            settings.send_tsbpd_latency = latency;
+            settings.recv_tsbpd_latency = latency;
+        })
+    }
+
+    /// Like [`new`](TestHarness::new), `configure` adjusts the settings both ends share
+    pub fn with_settings(configure: impl FnOnce(&mut ConnectionSettings)) -> Self {
+        let start = Instant::now();
+        let init = ConnInitSettings::default();
+        let mut sender = ConnectionSettings {
+            remote: RECEIVER_ADDR,
+            remote_sockid: SocketId(2),
+            local_sockid: SocketId(1),
+            socket_start_time: start,
+            rtt: Duration::ZERO,
+            init_seq_num: SeqNumber::new_truncate(0),
+            max_packet_size: init.max_packet_size,
+            max_segment_size: init.max_segment_size,
+            max_flow_size: init.max_flow_size,
+            idle_restart_threshold: init.idle_restart_threshold,
+            max_retransmits: init.max_retransmits,
+            send_tsbpd_latency: init.send_latency,
+            recv_tsbpd_latency: init.recv_latency,
+            too_late_packet_drop: init.too_late_packet_drop,
+            delivery_mode: init.delivery_mode,
+            nak_period: init.nak_period,
+            recv_granularity: init.recv_granularity,
+            peer_idle_timeout: init.peer_idle_timeout,
+            recv_buffer_size: init.recv_buffer_size,
+            recv_buffer_cap: init.recv_buffer_cap,
+            send_buffer_size: init.send_buffer_size,
+            cipher: None,
+            stream_id: None,
+            bandwidth: init.bandwidth,
+            retransmit_bandwidth: init.retransmit_bandwidth,
+            send_buffer_full: init.send_buffer_full,
+            flow_window_full: init.flow_window_full,
+            input_overflow: init.input_overflow,
+            statistics_interval: init.statistics_interval,
+            loss_rate_window: init.loss_rate_window,
+            initial_rtt: init.initial_rtt,
+            too_late_drop_margin: init.too_late_drop_margin,
+            peer_version: SrtVersion::CURRENT,
+            features: init.features,
+            packet_filter: None,
+            negotiation_adjustments: Vec::new(),
+            session_token: None,
+            resumable_sessions: None,
+        };
+        configure(&mut sender);
+        let receiver = ConnectionSettings {
+            remote: SENDER_ADDR,
+            remote_sockid: sender.local_sockid,
+            local_sockid: sender.remote_sockid,
+            ..sender.clone()
+        };
+        let connect = |settings| {
+            DuplexConnection::new(Connection {
+                settings,
+                handshake: Handshake::Connector,
+            })
+        };
+        Self {
+            start,
+            now: start,
+            sender: connect(sender),
+            receiver: connect(receiver),
+            delay: Duration::ZERO,
+            loss: 0.,
+            rng: StdRng::seed_from_u64(0),
+            drop_filter: None,
+            in_flight: BinaryHeap::new(),
+            next_id: 0,
+            transmissions: Vec::new(),
+            received: Vec::new(),
+        }
+    }
+
+    /// How long every packet takes to get across, either way
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Lose packets at random, either way, with the probability `loss`, drawn from a generator
+    /// seeded with `seed`
+    pub fn set_loss(&mut self, loss: f64, seed: u64) {
+        self.loss = loss;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Lose the packets `filter` picks, on top of the random loss
+    pub fn drop_where(&mut self, filter: impl FnMut(&Transmission) -> bool + Send + 'static) {
+        self.drop_filter = Some(Box::new(filter));
+    }
+
+    /// When the harness was created, the time the connection started at
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Hands `data` to the sender, with the current time as its origin time
+    pub fn send(&mut self, data: impl Into<Bytes>) {
+        let now = self.now;
+        self.sender.handle_data_input(now, Some((now, data.into())));
+        self.pump();
+    }
+
+    /// Moves the clock forward by `duration`, firing the timers and delivering the packets that
+    /// come due on the way, in time order
+    pub fn step(&mut self, duration: Duration) {
+        let until = self.now + duration;
+        loop {
+            self.pump();
+            let next = self
+                .sender
+                .next_timer(self.now)
+                .min(self.receiver.next_timer(self.now));
+            let next = match self.in_flight.peek() {
+                Some(Reverse(packet)) => next.min(packet.release_at),
+                None => next,
+            };
+            if next > until {
+                break;
+            }
+            self.now = self.now.max(next);
+        }
+        self.now = until;
+        self.pump();
+    }
+
+    /// Every packet that went onto the link so far, in the order they were sent
+    pub fn transmissions(&self) -> &[Transmission] {
+        &self.transmissions
+    }
+
+    /// The data the receiver released so far, with the origin time it was sent with
+    pub fn take_received(&mut self) -> Vec<(Instant, Bytes)> {
+        std::mem::take(&mut self.received)
+    }
+
+    // runs both ends at the current time until neither has anything more to do
+    fn pump(&mut self) {
+        let now = self.now;
+        loop {
+            let mut idle = true;
+            for side in [Side::Sender, Side::Receiver] {
+                let connection = self.connection(side);
+                connection.check_timers(now);
+                let mut packets = Vec::new();
+                while let Some((packet, _)) = connection.next_packet(now) {
+                    packets.push(packet);
+                }
+                if side == Side::Receiver {
+                    while let Some(data) = self.receiver.next_data(now) {
+                        self.received.push(data);
+                    }
+                }
+                idle &= packets.is_empty();
+                for packet in packets {
+                    self.transmit(side, packet);
+                }
+            }
+            while let Some(Reverse(packet)) = self.in_flight.peek() {
+                if packet.release_at > now {
+                    break;
+                }
+                let Reverse(packet) = self.in_flight.pop().unwrap();
+                let from = match packet.to {
+                    Side::Sender => RECEIVER_ADDR,
+                    Side::Receiver => SENDER_ADDR,
+                };
+                self.connection(packet.to)
+                    .handle_packet_input(now, Ok((packet.packet, from)));
+                idle = false;
+            }
+            if idle {
+                break;
+            }
+        }
+    }
+
+    fn transmit(&mut self, from: Side, packet: Packet) {
+        let mut transmission = Transmission {
+            at: self.now,
+            from,
+            packet,
+            dropped: false,
+        };
+        let lost = self.loss > 0. && self.rng.gen_bool(self.loss);
+        let filtered = match &mut self.drop_filter {
+            Some(filter) => filter(&transmission),
+            None => false,
+        };
+        transmission.dropped = lost || filtered;
+        if !transmission.dropped {
+            self.in_flight.push(Reverse(InFlight {
+                release_at: self.now + self.delay,
+                id: self.next_id,
+                to: match from {
+                    Side::Sender => Side::Receiver,
+                    Side::Receiver => Side::Sender,
+                },
+                packet: transmission.packet.clone(),
+            }));
+            self.next_id += 1;
+        }
+        self.transmissions.push(transmission);
+    }
+
+    fn connection(&mut self, side: Side) -> &mut DuplexConnection {
+        match side {
+            Side::Sender => &mut self.sender,
+            Side::Receiver => &mut self.receiver,
+        }
+    }
+}
@@ -12,6 +12,7 @@ use rand_distr::{Bernoulli, Normal};
 
 use srt_protocol::{
     connection::{Connection, Input},
+    options::SrtVersion,
     packet::*,
     protocol::pending_connection::{
         connect::Connect, listen::Listen, rendezvous::Rendezvous, ConnectionResult,
@@ -172,8 +173,8 @@ fn precise_ts0() {
 
     let sim = RandomLossSimulation {
         rng: StdRng::seed_from_u64(0),
-        delay_dist: Normal::new(rtt2.as_secs_f64(), 0.0).unwrap(),
-        drop_dist: Bernoulli::new(0.).unwrap(),
+        delay_dist: DelayDistribution::Normal(Normal::new(rtt2.as_secs_f64(), 0.0).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
     };
 
     let (a, b) = complete(
@@ -197,6 +198,73 @@ fn precise_ts0() {
     assert_eq!(b.settings.rtt, rtt2 * 2);
 }
 
+#[test]
+fn feature_negotiation() {
+    let r_sa = ([127, 0, 0, 1], 2222).into();
+    let s_sa: SocketAddr = ([127, 0, 0, 1], 2224).into();
+
+    let start = Instant::now();
+
+    let send = ConnectEntity::PendingC(
+        Connect::new(
+            r_sa,
+            s_sa.ip(),
+            ConnInitSettings {
+                local_sockid: SocketId(1234),
+                features: SrtShakeFlags::SUPPORTED | SrtShakeFlags::NAKREPORT,
+                ..ConnInitSettings::default()
+            },
+            None,
+            SeqNumber::new_truncate(0),
+        ),
+        start,
+    );
+
+    let recv = ConnectEntity::PendingL(Listen::new(
+        ConnInitSettings {
+            local_sockid: SocketId(5678),
+            features: SrtShakeFlags::TSBPDSND
+                | SrtShakeFlags::TSBPDRCV
+                | SrtShakeFlags::NAKREPORT
+                | SrtShakeFlags::STREAM,
+            packet_filter: Some(packet_filter()),
+            ..ConnInitSettings::default()
+        },
+        false,
+    ));
+
+    let conn = NetworkSimulator::new(s_sa, r_sa);
+
+    let sim = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(10)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+
+    let (a, b) = complete(
+        Conn {
+            a: send,
+            b: recv,
+            conn,
+            sim,
+        },
+        start,
+    );
+
+    let expected = SrtShakeFlags::TSBPDSND | SrtShakeFlags::TSBPDRCV | SrtShakeFlags::NAKREPORT;
+    assert_eq!(a.settings.features, expected);
+    assert_eq!(b.settings.features, expected);
+    assert_eq!(a.settings.peer_version, SrtVersion::CURRENT);
+    assert_eq!(b.settings.peer_version, SrtVersion::CURRENT);
+    // the caller has no packet filter config, so it takes the listener's
+    assert_eq!(a.settings.packet_filter, Some(packet_filter()));
+    assert_eq!(b.settings.packet_filter, Some(packet_filter()));
+}
+
+fn packet_filter() -> FilterSpec {
+    FilterSpec([("cols".to_string(), "4".to_string())].into())
+}
+
 #[test]
 fn lossy_connect() {
     // previously failing seeds
@@ -226,8 +294,8 @@ fn do_lossy_connect(seed: u64) {
 
     let sim = RandomLossSimulation {
         rng: StdRng::seed_from_u64(seed),
-        delay_dist: Normal::new(0.02, 0.02).unwrap(),
-        drop_dist: Bernoulli::new(0.7).unwrap(),
+        delay_dist: DelayDistribution::Normal(Normal::new(0.02, 0.02).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(0.7).unwrap()),
     };
 
     let c = ConnectEntity::PendingC(
@@ -297,8 +365,8 @@ fn do_lossy_rendezvous(seed: u64) {
 
     let sim = RandomLossSimulation {
         rng,
-        delay_dist: Normal::new(0.02, 0.02).unwrap(),
-        drop_dist: Bernoulli::new(0.70).unwrap(),
+        delay_dist: DelayDistribution::Normal(Normal::new(0.02, 0.02).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(0.70).unwrap()),
     };
 
     let a = ConnectEntity::PendingR(
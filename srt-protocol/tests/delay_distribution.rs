@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use rand::{
+    distributions::{Bernoulli, Uniform},
+    prelude::StdRng,
+    SeedableRng,
+};
+use rand_distr::{Exp, LogNormal, Normal};
+
+pub mod simulator;
+
+use simulator::*;
+
+const SAMPLES: u32 = 10_000;
+
+fn mean_delay(delay_dist: DelayDistribution) -> Duration {
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist,
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+
+    let now = Instant::now();
+    let total: Duration = (0..SAMPLES)
+        .map(|_| simulation.next_packet_schedule(now).unwrap() - now)
+        .sum();
+    total / SAMPLES
+}
+
+fn assert_mean_near(delay_dist: DelayDistribution, expected: Duration) {
+    let actual = mean_delay(delay_dist.clone());
+    let tolerance = expected / 20; // 5%
+    assert!(
+        actual > expected - tolerance && actual < expected + tolerance,
+        "{delay_dist:?}: mean delay was {actual:?}, expected {expected:?}"
+    );
+}
+
+#[test]
+fn constant() {
+    let delay = Duration::from_millis(20);
+    assert_eq!(mean_delay(DelayDistribution::Constant(delay)), delay);
+}
+
+#[test]
+fn uniform() {
+    assert_mean_near(
+        DelayDistribution::Uniform(Uniform::new(0.01, 0.03)),
+        Duration::from_millis(20),
+    );
+}
+
+#[test]
+fn normal() {
+    assert_mean_near(
+        DelayDistribution::Normal(Normal::new(0.02, 0.002).unwrap()),
+        Duration::from_millis(20),
+    );
+}
+
+#[test]
+fn normal_folded_or_clamped() {
+    // with the deviation as large as the mean, a sixth of the samples are negative
+    let normal = Normal::new(0.02, 0.02).unwrap();
+
+    // folded, the realized mean is E|X| = 23.33ms
+    assert_mean_near(
+        DelayDistribution::Normal(normal),
+        Duration::from_micros(23_333),
+    );
+    // clamped, E[max(X, 0)] = 21.67ms, half the overshoot
+    assert_mean_near(
+        DelayDistribution::ClampedNormal(normal),
+        Duration::from_micros(21_666),
+    );
+    assert!(
+        mean_delay(DelayDistribution::ClampedNormal(normal))
+            < mean_delay(DelayDistribution::Normal(normal))
+    );
+
+    // and with a small deviation, the configured mean
+    assert_mean_near(
+        DelayDistribution::ClampedNormal(Normal::new(0.02, 0.002).unwrap()),
+        Duration::from_millis(20),
+    );
+}
+
+#[test]
+fn exponential() {
+    assert_mean_near(
+        DelayDistribution::Exponential(Exp::new(1. / 0.02).unwrap()),
+        Duration::from_millis(20),
+    );
+}
+
+#[test]
+fn log_normal() {
+    assert_mean_near(
+        DelayDistribution::LogNormal(LogNormal::from_mean_cv(0.02, 0.5).unwrap()),
+        Duration::from_millis(20),
+    );
+}
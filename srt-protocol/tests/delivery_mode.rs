@@ -0,0 +1,112 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{
+    connection::{ConnectionEvent, Input},
+    options::*,
+    packet::*,
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const MESSAGES: u32 = 100;
+// two packets each
+const MESSAGE_SIZE: usize = 2_000;
+// closer together than missing packets are waited for, once too late
+const MESSAGE_SPACING: Duration = Duration::from_millis(2);
+const LATENCY: Duration = Duration::from_millis(100);
+// the second packet of this message never makes it through, not even retransmitted
+const LOST: u32 = 50;
+
+struct Outcome {
+    // the messages received, their time and when they were released
+    received: Vec<(u32, Instant, Instant)>,
+    gaps: Vec<(Instant, PacketCount)>,
+}
+
+#[test]
+fn as_available_delivers_past_the_gap() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let after_gap =
+        |outcome: &Outcome| *outcome.received.iter().find(|(n, _, _)| *n > LOST).unwrap();
+
+    let as_available = simulate(start, DeliveryMode::AsAvailable);
+    let strict = simulate(start, DeliveryMode::Strict);
+
+    for outcome in [&as_available, &strict] {
+        // everything is delivered in order, save for the gap
+        let (n, time, _) = after_gap(outcome);
+        let expected: Vec<_> = (0..LOST).chain(n..MESSAGES).collect();
+        let received: Vec<_> = outcome.received.iter().map(|(n, _, _)| *n).collect();
+        assert_eq!(received, expected);
+        // which is marked, with the time of the data following it
+        assert_eq!(outcome.gaps, [(time, PacketCount(2 * u64::from(n - LOST)))]);
+    }
+
+    // the data after the gap is released when it's due...
+    let (n, time, released) = after_gap(&as_available);
+    assert_eq!(n, LOST + 1);
+    assert_eq!(released, time + LATENCY);
+
+    // ...instead of waiting for the rest of the message in front of it, until that's given up on
+    // for being too late
+    let (n, time, released) = after_gap(&strict);
+    assert_eq!(n, LOST + 1);
+    assert!(released > time + LATENCY);
+}
+
+fn simulate(start: Instant, delivery_mode: DeliveryMode) -> Outcome {
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(5)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build_with(start, LATENCY, PacketCount(8192), |settings| {
+            settings.delivery_mode = delivery_mode;
+        });
+    let init_seq_num = sender.settings().init_seq_num;
+    for n in 0..MESSAGES {
+        let t = start + MESSAGE_SPACING * (n + 1);
+        let mut payload = vec![0; MESSAGE_SIZE];
+        payload[..4].copy_from_slice(&n.to_be_bytes());
+        let payload = Bytes::from(payload);
+        network
+            .sender
+            .schedule_input(t, Input::Data(Some((t, payload))));
+    }
+    network
+        .sender
+        .schedule_input(start + MESSAGE_SPACING * (MESSAGES + 1), Input::Data(None));
+
+    let mut received = Vec::new();
+    let mut gaps = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while let Some((time, payload)) = step.receiver.next_data(now) {
+            let n = u32::from_be_bytes(payload[..4].try_into().unwrap());
+            received.push((n, time, now));
+        }
+        while let Some(event) = step.receiver.next_event() {
+            if let ConnectionEvent::Gap(time, packets) = event {
+                gaps.push((time, packets));
+            }
+        }
+        for packet in step.next_packets() {
+            if let Packet::Data(data) = &packet.0 {
+                if data.seq_number - init_seq_num == 2 * LOST + 1 {
+                    continue;
+                }
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    Outcome { received, gaps }
+}
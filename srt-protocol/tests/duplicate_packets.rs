@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 20;
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn duplicate_packets_delivered_once() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    input_data_simulation(
+        start,
+        PACKETS,
+        Duration::from_millis(1),
+        &mut network.sender,
+    );
+
+    let mut sent = Vec::new();
+    let mut replayed = false;
+    let mut delivered = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while let Some((_, payload)) = step.receiver.next_data(now) {
+            delivered.push(payload);
+        }
+        // once half of the stream is delivered, send everything sent so far again
+        if !replayed && delivered.len() == PACKETS / 2 {
+            replayed = true;
+            for packet in sent.drain(..) {
+                step.network.send_lossy(&mut simulation, now, packet);
+            }
+        }
+
+        for packet in step.next_packets() {
+            // every data packet arrives twice, as if retransmitted right away
+            if let Packet::Data(_) = &packet.0 {
+                step.network
+                    .send_lossy(&mut simulation, now, packet.clone());
+                sent.push(packet.clone());
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    let expected: Vec<_> = (1..=PACKETS).map(|i| Bytes::from(i.to_string())).collect();
+    assert_eq!(delivered, expected);
+
+    let statistics = receiver.statistics();
+    assert_eq!(statistics.rx_unique_data, PACKETS as u64);
+    // the copies sent right away find the originals still buffered, of the replayed ones at least
+    // those already delivered are too late
+    assert_eq!(
+        statistics.rx_duplicate_data + statistics.rx_belated_data,
+        2 * PACKETS as u64
+    );
+    assert!(statistics.rx_belated_data >= PACKETS as u64 / 2);
+}
@@ -0,0 +1,98 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{options::*, statistics::SocketStatistics};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 4_000;
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+const LOSS: f64 = 0.05;
+// well below the initial retransmission timeout, so only lost packets are retransmitted
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn fec_recovers_random_loss() {
+    let _ = pretty_env_logger::try_init();
+
+    let seed = rand::random();
+    info!("Seed is: {}", seed);
+
+    let (arq_sender, arq_receiver, arq_delivered) = simulate(seed, None);
+    let (fec_sender, fec_receiver, fec_delivered) = simulate(seed, Some("fec,cols:10,rows:10"));
+    info!(
+        "retransmitted {} without FEC, {} with FEC",
+        arq_sender.tx_retransmit_data, fec_sender.tx_retransmit_data
+    );
+
+    // nothing is missing without FEC, everything lost is retransmitted until it arrives
+    assert_eq!(arq_delivered, PACKETS, "seed {seed}");
+    assert_eq!(arq_receiver.rx_filter_supply_data, 0, "seed {seed}");
+    assert!(arq_sender.tx_retransmit_data > 0, "seed {seed}");
+
+    // FEC packets are sent for every row and column, the last few columns are left incomplete
+    assert!(
+        fec_sender.tx_filter_extra_data >= 2 * PACKETS as u64 / 10 - 10,
+        "seed {seed}"
+    );
+    assert!(
+        fec_receiver.rx_filter_extra_data * 10 >= fec_sender.tx_filter_extra_data * 9,
+        "seed {seed}"
+    );
+
+    // most of the losses are rebuilt, the rest is only requested once FEC gave up on them
+    let missing = PACKETS as u64 - fec_receiver.rx_unique_data;
+    assert!(
+        fec_receiver.rx_filter_supply_data * 10 >= missing * 9,
+        "rebuilt {} of {missing}, seed {seed}",
+        fec_receiver.rx_filter_supply_data
+    );
+    assert!(
+        fec_sender.tx_retransmit_data * 5 < arq_sender.tx_retransmit_data,
+        "seed {seed}"
+    );
+    assert!(fec_sender.rx_nak * 5 < arq_sender.rx_nak, "seed {seed}");
+    // without blind retransmissions, a packet is lost for good if its retransmission is lost too,
+    // and the losses in the last few groups of the stream are never given up on
+    assert!(fec_delivered * 100 >= PACKETS * 99, "seed {seed}");
+}
+
+// returns the sender's and the receiver's statistics, and how many packets were delivered
+fn simulate(seed: u64, filter: Option<&str>) -> (SocketStatistics, SocketStatistics, usize) {
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(LOSS).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(1),
+        PacketCount(8192),
+        |settings| settings.packet_filter = filter.map(|f| f.parse().unwrap()),
+    );
+    input_data_simulation(start, PACKETS, PACKET_SPACING, &mut network.sender);
+
+    let mut delivered = 0;
+    let now = run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while step.receiver.next_data(now).is_some() {
+            delivered += 1;
+        }
+        for packet in step.next_packets() {
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    sender.update_statistics(now);
+    receiver.update_statistics(now);
+    (
+        sender.statistics().clone(),
+        receiver.statistics().clone(),
+        delivered,
+    )
+}
@@ -0,0 +1,149 @@
+use std::{
+    cmp::min,
+    str,
+    time::{Duration, Instant},
+};
+
+use log::{info, trace};
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Normal;
+use srt_protocol::{connection::Input, options::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const TO_BAD: f64 = 0.01;
+const TO_GOOD: f64 = 0.25;
+
+#[test]
+fn burst_lengths() {
+    const PACKETS: usize = 1_000_000;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut model = GilbertElliott::new(TO_BAD, TO_GOOD, 0., 1.).unwrap();
+
+    let mut bursts = Vec::new();
+    let mut burst = 0;
+    let mut lost = 0;
+    for _ in 0..PACKETS {
+        if model.is_dropped(&mut rng) {
+            burst += 1;
+            lost += 1;
+        } else if burst > 0 {
+            bursts.push(burst);
+            burst = 0;
+        }
+    }
+
+    // with lossless good and fully lossy bad states, burst lengths are geometric with p = TO_GOOD
+    let mean_burst = bursts.iter().sum::<u32>() as f64 / bursts.len() as f64;
+    let expected_mean_burst = 1. / TO_GOOD;
+    assert!(
+        (mean_burst - expected_mean_burst).abs() < expected_mean_burst * 0.05,
+        "mean burst length was {mean_burst}, expected {expected_mean_burst}"
+    );
+
+    for length in 1..=4 {
+        let actual = bursts.iter().filter(|b| **b == length).count() as f64 / bursts.len() as f64;
+        let expected = (1. - TO_GOOD).powi(length as i32 - 1) * TO_GOOD;
+        assert!(
+            (actual - expected).abs() < expected * 0.1,
+            "P(burst = {length}) was {actual}, expected {expected}"
+        );
+    }
+
+    // stationary probability of the bad state
+    let loss_rate = lost as f64 / PACKETS as f64;
+    let expected_loss_rate = TO_BAD / (TO_BAD + TO_GOOD);
+    assert!(
+        (loss_rate - expected_loss_rate).abs() < expected_loss_rate * 0.05,
+        "loss rate was {loss_rate}, expected {expected_loss_rate}"
+    );
+}
+
+#[test]
+fn recovers_from_bursts() {
+    let _ = pretty_env_logger::try_init();
+
+    for _ in 0..5 {
+        do_burst_recovery(rand::random(), 10_000);
+    }
+}
+
+fn do_burst_recovery(seed: u64, count: usize) {
+    info!("Seed is: {}, count is: {}", seed, count);
+
+    const PACKET_SPACING: Duration = Duration::from_millis(1);
+
+    let start = Instant::now();
+
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Normal(Normal::new(20e-3, 4e-3).unwrap()),
+        drop_dist: LossModel::GilbertElliott(
+            GilbertElliott::new(TO_BAD, TO_GOOD, 0.001, 0.75).unwrap(),
+        ),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    input_data_simulation(start, count, PACKET_SPACING, &mut network.sender);
+
+    let mut now = start;
+    let mut next_data = 1;
+    let mut received = 0;
+    loop {
+        let sender_next_time = if sender.is_open() {
+            while let Some(packet) = sender.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = sender.check_timers(now);
+            let (next_time, input) = network.sender.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => sender.handle_data_input(next_time, data),
+                Input::Packet(packet) => sender.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let receiver_next_time = if receiver.is_open() {
+            while let Some((_, payload)) = receiver.next_data(now) {
+                let actual: usize = str::from_utf8(&payload[..]).unwrap().parse().unwrap();
+                assert_eq!(actual, next_data, "seed {seed}");
+                next_data += 1;
+                received += 1;
+            }
+
+            while let Some(packet) = receiver.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = receiver.check_timers(now);
+            let (next_time, input) = network.receiver.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => receiver.handle_data_input(next_time, data),
+                Input::Packet(packet) => receiver.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let next_time = match (sender_next_time, receiver_next_time) {
+            (Some(s), Some(r)) => min(s, r),
+            (Some(s), None) => s,
+            (None, Some(r)) => r,
+            _ => break,
+        };
+
+        trace!("Delta = {:?}", next_time - now);
+        now = next_time;
+    }
+
+    assert_eq!(received, count, "seed {seed}");
+}
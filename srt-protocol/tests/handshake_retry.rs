@@ -0,0 +1,185 @@
+pub mod simulator;
+
+use std::{
+    cmp::min,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+
+use srt_protocol::{
+    connection::{Connection, Input},
+    packet::*,
+    protocol::pending_connection::{connect::Connect, listen::Listen, ConnectionResult},
+    settings::*,
+};
+
+use simulator::*;
+
+// the caller is ticked much more often than it's allowed to resend
+const TICK: Duration = Duration::from_millis(10);
+const RETRY_INTERVAL: Duration = Duration::from_millis(750);
+const MAX_RETRIES: u32 = 12;
+
+#[test]
+fn high_delay_lossy_handshake() {
+    let _ = pretty_env_logger::try_init();
+
+    for _ in 0..20 {
+        let seed = rand::random();
+        println!("Handshake retry seed is {seed}");
+
+        // the round trip time is well above the default retry interval
+        let (sent, connected) = simulate(seed, Duration::from_millis(300), 0.3);
+        assert!(connected, "seed {seed}");
+        assert_retries(&sent, ShakeType::Induction);
+        assert_retries(&sent, ShakeType::Conclusion);
+    }
+}
+
+#[test]
+fn retries_exhausted() {
+    let _ = pretty_env_logger::try_init();
+
+    let (sent, connected) = simulate(0, Duration::from_millis(300), 1.);
+    assert!(!connected);
+    assert_eq!(sent.len() as u32, MAX_RETRIES + 1);
+    assert_retries(&sent, ShakeType::Induction);
+}
+
+fn assert_retries(sent: &[(Instant, ShakeType)], shake_type: ShakeType) {
+    let sent: Vec<_> = sent
+        .iter()
+        .filter(|(_, t)| *t == shake_type)
+        .map(|(time, _)| *time)
+        .collect();
+    assert!(
+        sent.len() as u32 <= MAX_RETRIES + 1,
+        "{shake_type:?} sent {} times",
+        sent.len()
+    );
+    for w in sent.windows(2) {
+        assert!(
+            w[1] - w[0] >= RETRY_INTERVAL,
+            "{shake_type:?} resent after {:?}",
+            w[1] - w[0]
+        );
+    }
+}
+
+// returns the handshakes sent by the caller, and whether both sides connected
+fn simulate(seed: u64, delay: Duration, loss: f64) -> (Vec<(Instant, ShakeType)>, bool) {
+    let c_sa: SocketAddr = ([127, 0, 0, 1], 2222).into();
+    let l_sa: SocketAddr = ([127, 0, 0, 1], 2224).into();
+
+    let start = Instant::now();
+    let mut network = NetworkSimulator::new(c_sa, l_sa);
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Constant(delay),
+        drop_dist: LossModel::Random(Bernoulli::new(loss).unwrap()),
+    };
+
+    let mut connect = Connect::new(
+        l_sa,
+        c_sa.ip(),
+        ConnInitSettings {
+            local_sockid: SocketId(1234),
+            handshake_retry_interval: RETRY_INTERVAL,
+            max_handshake_retries: Some(MAX_RETRIES),
+            ..ConnInitSettings::default()
+        },
+        None,
+        SeqNumber::new_truncate(0),
+    );
+    let mut listen = Listen::new(
+        ConnInitSettings {
+            local_sockid: SocketId(5678),
+            ..ConnInitSettings::default()
+        },
+        false,
+    );
+
+    let mut sent = Vec::new();
+    let mut caller: Option<Connection> = None;
+    let mut listener: Option<Connection> = None;
+    let mut now = start;
+    loop {
+        assert!(now - start < Duration::from_secs(30));
+
+        use ConnectionResult::*;
+        let mut outgoing = Vec::new();
+        while let (time, Input::Packet(packet)) = network.receiver.select_next_input(now, now) {
+            match (&listener, packet) {
+                // the caller resends its conclusion until it sees the listener's answer
+                (Some(connection), Ok((Packet::Control(control), from))) => {
+                    if let ControlTypes::Handshake(shake) = control.control_type.clone() {
+                        if let Some(control_type) = connection.handshake.handle_handshake(shake) {
+                            let response = ControlPacket {
+                                control_type,
+                                ..control
+                            };
+                            outgoing.push((response.into(), from));
+                        }
+                    }
+                }
+                (Some(_), _) => {}
+                (None, packet) => match listen.handle_packet(time, packet) {
+                    SendPacket(packet) => outgoing.push(packet),
+                    Connected(packet, connection) => {
+                        outgoing.extend(packet);
+                        listener = Some(connection);
+                    }
+                    _ => {}
+                },
+            }
+        }
+        let mut caller_results = Vec::new();
+        while let (time, Input::Packet(packet)) = network.sender.select_next_input(now, now) {
+            caller_results.push(connect.handle_packet(packet, time));
+        }
+        caller_results.push(connect.handle_tick(now));
+        for result in caller_results {
+            match result {
+                _ if caller.is_some() => {}
+                SendPacket(packet) => outgoing.push(packet),
+                Connected(packet, connection) => {
+                    outgoing.extend(packet);
+                    caller = Some(connection);
+                }
+                Failure(_) => return (sent, false),
+                _ => {}
+            }
+        }
+
+        for packet in outgoing {
+            if let (
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Handshake(shake),
+                    ..
+                }),
+                to,
+            ) = &packet
+            {
+                if *to == l_sa {
+                    sent.push((now, shake.shake_type));
+                }
+            }
+            network.send_lossy(&mut simulation, now, packet);
+        }
+
+        if caller.is_some() && listener.is_some() {
+            return (sent, true);
+        }
+
+        now = [
+            network.sender.next_input_time(),
+            network.receiver.next_input_time(),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(now + TICK, min);
+    }
+}
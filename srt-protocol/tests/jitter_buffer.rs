@@ -0,0 +1,147 @@
+use std::{
+    cmp::{max, min},
+    time::{Duration, Instant},
+};
+
+use log::{info, trace};
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::{Bernoulli, Normal};
+use srt_protocol::{
+    connection::{DuplexConnection, Input},
+    options::*,
+    statistics::SocketStatistics,
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 2_000;
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+const DELAY: Duration = Duration::from_millis(50);
+const JITTER: Duration = Duration::from_millis(10);
+
+#[test]
+fn depth_within_latency() {
+    let _ = pretty_env_logger::try_init();
+
+    let latency = Duration::from_millis(200);
+    let (received, stats, max_depth) = simulate(rand::random(), latency);
+
+    assert_eq!(received, PACKETS);
+    assert_eq!(stats.rx_too_late_dropped_data, 0);
+    assert_eq!(stats.rx_buffered_data, 0);
+    assert_eq!(stats.rx_pending_data, 0);
+
+    // the receiver's clock is based on the first packet to arrive, so in addition to the 5ms
+    // too late packet drop tolerance, the window can be offset by up to the spread of the jitter
+    let window = latency + Duration::from_millis(5) + JITTER * 6;
+    assert!(
+        max_depth.rx_buffered_time <= window,
+        "buffer depth {:?} exceeded latency window {:?}",
+        max_depth.rx_buffered_time,
+        window
+    );
+    assert!(max_depth.rx_buffered_time >= latency / 2);
+    assert!(max_depth.rx_buffered_data > 0);
+    assert!(max_depth.rx_pending_data > 0);
+    assert!(max_depth.rx_pending_data <= max_depth.rx_buffered_data);
+}
+
+#[test]
+fn too_late_drops_counted() {
+    let _ = pretty_env_logger::try_init();
+
+    // far less than the jitter, so reordered packets regularly miss their time-to-play
+    let latency = Duration::from_millis(5);
+    let (received, stats, _) = simulate(rand::random(), latency);
+
+    assert!(stats.rx_too_late_dropped_data > 0);
+    assert!(stats.rx_dropped_data >= stats.rx_too_late_dropped_data);
+    assert!(received + stats.rx_too_late_dropped_data as usize <= PACKETS);
+}
+
+fn simulate(seed: u64, latency: Duration) -> (usize, SocketStatistics, SocketStatistics) {
+    info!("Seed is: {}, latency is: {:?}", seed, latency);
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Normal(
+            Normal::new(DELAY.as_secs_f64(), JITTER.as_secs_f64()).unwrap(),
+        ),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, latency, PacketCount(8192));
+    input_data_simulation(start, PACKETS, PACKET_SPACING, &mut network.sender);
+
+    let mut now = start;
+    let mut received = 0;
+    let mut max_depth = SocketStatistics::new();
+    loop {
+        let sender_next_time = if sender.is_open() {
+            while let Some(packet) = sender.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = sender.check_timers(now);
+            let (next_time, input) = network.sender.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => sender.handle_data_input(next_time, data),
+                Input::Packet(packet) => sender.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let receiver_next_time = if receiver.is_open() {
+            while receiver.next_data(now).is_some() {
+                received += 1;
+            }
+            update_max_depth(now, &mut receiver, &mut max_depth);
+
+            while let Some(packet) = receiver.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = receiver.check_timers(now);
+            let (next_time, input) = network.receiver.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => receiver.handle_data_input(next_time, data),
+                Input::Packet(packet) => receiver.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let next_time = match (sender_next_time, receiver_next_time) {
+            (Some(s), Some(r)) => min(s, r),
+            (Some(s), None) => s,
+            (None, Some(r)) => r,
+            _ => break,
+        };
+
+        trace!("Delta = {:?}", next_time - now);
+        now = next_time;
+    }
+
+    receiver.update_statistics(now);
+    (received, receiver.statistics().clone(), max_depth)
+}
+
+fn update_max_depth(
+    now: Instant,
+    receiver: &mut DuplexConnection,
+    max_depth: &mut SocketStatistics,
+) {
+    receiver.update_statistics(now);
+    let stats = receiver.statistics();
+    max_depth.rx_buffered_data = max(max_depth.rx_buffered_data, stats.rx_buffered_data);
+    max_depth.rx_buffered_time = max(max_depth.rx_buffered_time, stats.rx_buffered_time);
+    max_depth.rx_pending_data = max(max_depth.rx_pending_data, stats.rx_pending_data);
+}
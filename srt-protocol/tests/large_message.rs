@@ -0,0 +1,166 @@
+use std::{
+    cmp::min,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use log::trace;
+use rand::{distributions::Bernoulli, prelude::StdRng, SeedableRng};
+use srt_protocol::{
+    connection::{ConnectionSettings, DuplexConnection, Input},
+    options::*,
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const FLOW_WINDOW: PacketCount = PacketCount(32);
+const PACKET_SIZE: PacketSize = PacketSize(1316);
+
+#[test]
+fn message_larger_than_flow_window() {
+    let _ = pretty_env_logger::try_init();
+
+    let message = new_message((FLOW_WINDOW * PACKET_SIZE).0 * 10);
+    let (received, _) = simulate(Duration::from_secs(1), PacketCount(8192), None, |_| {
+        message.clone()
+    });
+
+    assert_eq!(received, [message]);
+}
+
+#[test]
+fn message_later_than_latency() {
+    let _ = pretty_env_logger::try_init();
+
+    // takes several round trips to transmit, so the message can't arrive before its release time
+    let message = new_message((FLOW_WINDOW * PACKET_SIZE).0 * 10);
+    let (received, _) = simulate(Duration::from_millis(100), PacketCount(8192), None, |_| {
+        message
+    });
+
+    assert_eq!(received, Vec::<Bytes>::new());
+}
+
+#[test]
+fn message_larger_than_buffer() {
+    let _ = pretty_env_logger::try_init();
+
+    let buffer_size = PacketCount(64);
+    let message = new_message((buffer_size * PACKET_SIZE).0 + 1);
+    let (received, sender) = simulate(Duration::from_secs(1), buffer_size, None, |_| message);
+
+    assert_eq!(received, Vec::<Bytes>::new());
+    assert_eq!(sender.statistics().tx_dropped_data, 1);
+    assert_eq!(sender.statistics().tx_unique_data, 0);
+}
+
+#[test]
+fn largest_message_with_fec() {
+    let _ = pretty_env_logger::try_init();
+
+    // the FEC header takes room in every packet, the largest message has to fit the buffers still
+    let buffer_size = PacketCount(64);
+    let mut sent = Bytes::new();
+    let (received, sender) = simulate(
+        Duration::from_secs(1),
+        buffer_size,
+        Some("fec,cols:4,rows:2"),
+        |settings| {
+            sent = new_message(settings.max_message_size().0);
+            sent.clone()
+        },
+    );
+
+    assert_eq!(sent.len() as u64, (buffer_size * PacketSize(1316 - 4)).0);
+    assert_eq!(received, [sent]);
+    assert_eq!(sender.statistics().tx_dropped_data, 0);
+}
+
+fn new_message(size: u64) -> Bytes {
+    (0..size).map(|i| i as u8).collect()
+}
+
+fn simulate(
+    latency: Duration,
+    buffer_size: PacketCount,
+    packet_filter: Option<&str>,
+    message: impl FnOnce(&ConnectionSettings) -> Bytes,
+) -> (Vec<Bytes>, DuplexConnection) {
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(20)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build_with(start, latency, buffer_size, |settings| {
+            settings.max_flow_size = FLOW_WINDOW;
+            settings.max_packet_size = PACKET_SIZE;
+            settings.send_buffer_size = buffer_size;
+            settings.packet_filter = packet_filter.map(|f| f.parse().unwrap());
+        });
+    let message = message(sender.settings());
+
+    network
+        .sender
+        .schedule_input(start, Input::Data(Some((start, message))));
+    network
+        .sender
+        .schedule_input(start + Duration::from_millis(1), Input::Data(None));
+
+    let mut now = start;
+    let mut received = Vec::new();
+    loop {
+        let sender_next_time = if sender.is_open() {
+            while let Some(packet) = sender.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = sender.check_timers(now);
+            let (next_time, input) = network.sender.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => sender.handle_data_input(next_time, data),
+                Input::Packet(packet) => sender.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let receiver_next_time = if receiver.is_open() {
+            while let Some((_, payload)) = receiver.next_data(now) {
+                received.push(payload);
+            }
+
+            while let Some(packet) = receiver.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+
+            let next_timer = receiver.check_timers(now);
+            let (next_time, input) = network.receiver.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => receiver.handle_data_input(next_time, data),
+                Input::Packet(packet) => receiver.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let next_time = match (sender_next_time, receiver_next_time) {
+            (Some(s), Some(r)) => min(s, r),
+            (Some(s), None) => s,
+            (None, Some(r)) => r,
+            _ => break,
+        };
+
+        trace!("Delta = {:?}", next_time - now);
+        now = next_time;
+    }
+
+    (received, sender)
+}
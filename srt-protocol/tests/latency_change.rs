@@ -0,0 +1,202 @@
+use std::{
+    str,
+    time::{Duration, Instant},
+};
+
+use log::info;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::{Bernoulli, Normal};
+use srt_protocol::{
+    connection::ConnectionSettings, options::*, packet::*, statistics::SocketStatistics,
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+const DELAY: Duration = Duration::from_millis(20);
+const CHANGE_AT: Duration = Duration::from_secs(1);
+
+#[test]
+fn raise_latency() {
+    let _ = pretty_env_logger::try_init();
+
+    let (old, new) = (Duration::from_millis(100), Duration::from_millis(400));
+    let packets = 5_000;
+    let run = simulate(4_211, packets, old, new, 0., 0, agree_on_latency_change);
+
+    // nothing is lost while the buffer grows
+    assert_eq!(run.delivered.len(), packets);
+    assert!(run.delivered.windows(2).all(|w| w[0].0 + 1 == w[1].0));
+    assert_eq!(run.receiver.rx_too_late_dropped_data, 0);
+
+    run.assert_delays(old, new);
+    assert_eq!(run.receiver.rx_tsbpd_delay, new);
+    assert_eq!(run.sender.tx_tsbpd_delay, new);
+}
+
+#[test]
+fn lower_latency() {
+    let _ = pretty_env_logger::try_init();
+
+    let (old, new) = (Duration::from_millis(500), Duration::from_millis(100));
+    let packets = 5_000;
+    let run = simulate(1_207, packets, old, new, 0.01, 0, agree_on_latency_change);
+
+    // every packet is either delivered in order or accounted for as too late
+    let dropped = run.receiver.rx_too_late_dropped_data as usize;
+    assert_eq!(run.delivered.len() + dropped, packets);
+    assert!(run.delivered.windows(2).all(|w| w[0].0 < w[1].0));
+    // only lost packets that couldn't be recovered in time are dropped, well below the loss rate
+    assert!(dropped < packets / 200, "{dropped} packets dropped");
+
+    run.assert_delays(old, new);
+    assert_eq!(run.receiver.rx_tsbpd_delay, new);
+    assert_eq!(run.sender.tx_tsbpd_delay, new);
+}
+
+#[test]
+fn lost_latency_change() {
+    let _ = pretty_env_logger::try_init();
+
+    // the first two requests and the response to the third are lost, the fourth gets through
+    let (old, new) = (Duration::from_millis(100), Duration::from_millis(300));
+    let packets = 3_000;
+    let run = simulate(88, packets, old, new, 0., 3, agree_on_latency_change);
+
+    assert_eq!(run.delivered.len(), packets);
+    // every request until the response to the fourth arrives
+    assert!(run.latency_changes >= 6, "{}", run.latency_changes);
+    run.assert_delays(old, new);
+    assert_eq!(run.receiver.rx_tsbpd_delay, new);
+    assert_eq!(run.sender.tx_tsbpd_delay, new);
+}
+
+#[test]
+fn latency_change_not_agreed_on() {
+    let _ = pretty_env_logger::try_init();
+
+    // without the flag, e.g. with the reference implementation, which wouldn't answer
+    let (old, new) = (Duration::from_millis(100), Duration::from_millis(300));
+    let packets = 2_000;
+    let run = simulate(5, packets, old, new, 0., 0, |_| {});
+
+    assert_eq!(run.delivered.len(), packets);
+    assert_eq!(run.latency_changes, 0);
+    assert_eq!(run.receiver.rx_tsbpd_delay, old);
+    assert_eq!(run.sender.tx_tsbpd_delay, old);
+}
+
+fn agree_on_latency_change(settings: &mut ConnectionSettings) {
+    settings.features |= SrtShakeFlags::LATENCY_CHANGE;
+}
+
+struct Run {
+    // (packet number, time between the packet's origin time and its release)
+    delivered: Vec<(usize, Duration)>,
+    sender: SocketStatistics,
+    receiver: SocketStatistics,
+    // latency change requests and responses sent, lost ones included
+    latency_changes: usize,
+}
+
+impl Run {
+    fn assert_delays(&self, old: Duration, new: Duration) {
+        // the receiver's clock is based on the arrival of the first packet, so every delay is
+        // offset by roughly the network delay
+        let tolerance = DELAY + Duration::from_millis(10);
+        let within =
+            |delay: Duration, latency: Duration| delay >= latency && delay <= latency + tolerance;
+
+        let (first, last) = (self.delivered[0].1, self.delivered.last().unwrap().1);
+        assert!(
+            within(first, old),
+            "first delay {first:?}, expected {old:?}"
+        );
+        assert!(within(last, new), "last delay {last:?}, expected {new:?}");
+
+        // the delay moves towards the new latency gradually instead of jumping
+        let max_step = self
+            .delivered
+            .windows(2)
+            .map(|w| w[0].1.abs_diff(w[1].1))
+            .max()
+            .unwrap();
+        assert!(max_step < Duration::from_millis(5), "{max_step:?}");
+    }
+}
+
+fn simulate(
+    seed: u64,
+    packets: usize,
+    old_latency: Duration,
+    new_latency: Duration,
+    loss: f64,
+    lost_latency_changes: usize,
+    configure: impl FnOnce(&mut ConnectionSettings),
+) -> Run {
+    info!(
+        "Seed is: {}, latency change: {:?} -> {:?}",
+        seed, old_latency, new_latency
+    );
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Normal(Normal::new(DELAY.as_secs_f64(), 2e-3).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(loss).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build_with(start, old_latency, PacketCount(8192), configure);
+    input_data_simulation(start, packets, PACKET_SPACING, &mut network.sender);
+
+    let mut changed = false;
+    let mut delivered = Vec::new();
+    let mut latency_changes = 0;
+    let end = run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        if !changed && now >= start + CHANGE_AT {
+            step.receiver.set_latency(now, new_latency);
+            changed = true;
+        }
+
+        while let Some((_, payload)) = step.receiver.next_data(now) {
+            let number: usize = str::from_utf8(&payload[..]).unwrap().parse().unwrap();
+            let origin = start + PACKET_SPACING * number as u32;
+            delivered.push((number, now - origin));
+        }
+
+        for packet in step.next_packets() {
+            if is_latency_change(&packet.0) {
+                latency_changes += 1;
+                if latency_changes <= lost_latency_changes {
+                    continue;
+                }
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    sender.update_statistics(end);
+    receiver.update_statistics(end);
+    Run {
+        delivered,
+        sender: sender.statistics().clone(),
+        receiver: receiver.statistics().clone(),
+        latency_changes,
+    }
+}
+
+fn is_latency_change(packet: &Packet) -> bool {
+    matches!(
+        packet,
+        Packet::Control(ControlPacket {
+            control_type: ControlTypes::Srt(
+                SrtControlPacket::LatencyChangeRequest(_)
+                    | SrtControlPacket::LatencyChangeResponse(_)
+            ),
+            ..
+        })
+    )
+}
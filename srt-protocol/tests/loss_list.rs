@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 20;
+// well below the initial retransmission timeout, so only the lost packets are retransmitted
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn loss_list_snapshot() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    input_data_simulation(
+        start,
+        PACKETS,
+        Duration::from_millis(1),
+        &mut network.sender,
+    );
+    let init_seq_num = sender.settings().init_seq_num;
+
+    let mut dropped = Vec::new();
+    let mut snapshot = None;
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        // packet 10 is in, the retransmissions requested when 7 arrived are still on their way
+        if snapshot.is_none() && step.receiver.statistics().rx_unique_data == 7 {
+            snapshot = Some(step.receiver.receiver_loss_list());
+        }
+
+        while step.receiver.next_data(now).is_some() {}
+        for packet in step.next_packets() {
+            match &packet.0 {
+                Packet::Data(data)
+                    if !data.retransmitted
+                        && [&b"5"[..], b"6", b"9"].contains(&&data.payload[..]) =>
+                {
+                    dropped.push(data.seq_number)
+                }
+                _ => step.network.send_lossy(&mut simulation, now, packet),
+            }
+        }
+    });
+
+    assert_eq!(
+        dropped,
+        [4, 5, 8].map(|n| init_seq_num + n),
+        "payloads are numbered from 1"
+    );
+    assert_eq!(
+        snapshot,
+        Some(vec![
+            init_seq_num + 4..init_seq_num + 6,
+            init_seq_num + 8..init_seq_num + 9,
+        ])
+    );
+
+    // everything was retransmitted in the end
+    assert_eq!(receiver.statistics().rx_retransmit_data, 3);
+    assert_eq!(receiver.receiver_loss_list(), vec![]);
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::{Bernoulli, Normal};
+use srt_protocol::options::*;
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 12_000;
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+const LOSS: f64 = 0.05;
+
+#[test]
+fn recent_loss_rate_converges() {
+    let _ = pretty_env_logger::try_init();
+
+    let seed = rand::random();
+    info!("Seed is: {}", seed);
+
+    let start = Instant::now();
+    // keep the jitter well below the packet spacing, reordered packets count as missing too
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Normal(Normal::new(20e-3, 1e-4).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(LOSS).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    input_data_simulation(start, PACKETS, PACKET_SPACING, &mut network.sender);
+
+    // once the first window has filled up, sample every couple of seconds while data is flowing
+    let mut sample_times = [6, 8, 10]
+        .map(|s| start + Duration::from_secs(s))
+        .into_iter();
+    let mut next_sample = sample_times.next();
+    let mut samples = Vec::new();
+
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        if next_sample.is_some_and(|sample| now >= sample) {
+            step.sender.update_statistics(now);
+            step.receiver.update_statistics(now);
+            // in parts per million
+            samples.push((
+                f64::from(step.sender.statistics().tx_loss_rate_recent_ppm) / 1e6,
+                f64::from(step.receiver.statistics().rx_loss_rate_recent_ppm) / 1e6,
+            ));
+            next_sample = sample_times.next();
+        }
+
+        while step.receiver.next_data(now).is_some() {}
+        for packet in step.next_packets() {
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    // each window covers ~5000 packets, the odd packet sent back to back and reordered by the
+    // jitter is counted as missing as well
+    assert_eq!(samples.len(), 3, "seed {seed}");
+    for (tx, rx) in samples {
+        info!("tx loss rate {:.4}, rx loss rate {:.4}", tx, rx);
+        assert!((rx - LOSS).abs() < 0.015, "rx loss rate {rx}, seed {seed}");
+        assert!((tx - LOSS).abs() < 0.015, "tx loss rate {tx}, seed {seed}");
+    }
+}
@@ -53,8 +53,10 @@ fn do_lossy_test(seed: u64, count: usize) {
 
     let mut simulation = RandomLossSimulation {
         rng: StdRng::seed_from_u64(seed),
-        delay_dist: Normal::new(delay_mean.as_secs_f64(), delay_stdev.as_secs_f64()).unwrap(),
-        drop_dist: Bernoulli::new(DROP_RATE).unwrap(),
+        delay_dist: DelayDistribution::Normal(
+            Normal::new(delay_mean.as_secs_f64(), delay_stdev.as_secs_f64()).unwrap(),
+        ),
+        drop_dist: LossModel::Random(Bernoulli::new(DROP_RATE).unwrap()),
     };
     let (mut network, mut sender, mut receiver) =
         simulation.build(start, Duration::from_secs(1), PacketCount(8192));
@@ -171,8 +173,10 @@ fn do_high_bandwidth_deterministic(seed: u64, count: usize) {
 
     let mut simulation = RandomLossSimulation {
         rng: StdRng::seed_from_u64(seed),
-        delay_dist: Normal::new(delay_mean.as_secs_f64(), delay_stdev.as_secs_f64()).unwrap(),
-        drop_dist: Bernoulli::new(drop_rate).unwrap(),
+        delay_dist: DelayDistribution::Normal(
+            Normal::new(delay_mean.as_secs_f64(), delay_stdev.as_secs_f64()).unwrap(),
+        ),
+        drop_dist: LossModel::Random(Bernoulli::new(drop_rate).unwrap()),
     };
     let (mut network, mut sender, mut receiver) =
         simulation.build(start, latency, recv_buffer_size / packet_size);
@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 20;
+
+fn faster() -> NakPeriodConfig {
+    NakPeriodConfig {
+        multiplier: Percent(10),
+        floor: Duration::from_millis(5),
+    }
+}
+
+#[test]
+fn smaller_multiplier_recovers_sooner() {
+    let _ = pretty_env_logger::try_init();
+
+    // the packet and its first two retransmissions are lost, the receiver has to report it again
+    let delay = Duration::from_millis(20);
+    let lose = |copy, _| copy < 3;
+    let default = recover(NakPeriodConfig::default(), delay, lose);
+    let faster = recover(faster(), delay, lose);
+
+    assert!(
+        faster.naks[1] < default.naks[1],
+        "re-NAKed after {:?}, not sooner than {:?}",
+        faster.naks[1],
+        default.naks[1]
+    );
+    assert!(
+        faster.recovered_after < default.recovered_after,
+        "recovered after {:?}, not sooner than {:?}",
+        faster.recovered_after,
+        default.recovered_after
+    );
+}
+
+#[test]
+fn smaller_multiplier_sends_more_naks() {
+    let _ = pretty_env_logger::try_init();
+
+    // every copy of the packet is lost for a while, and reported again all along
+    let delay = Duration::from_millis(5);
+    let lose = |_, elapsed| elapsed < Duration::from_millis(200);
+    let default = recover(NakPeriodConfig::default(), delay, lose);
+    let faster = recover(faster(), delay, lose);
+
+    assert!(
+        faster.naks.len() > default.naks.len(),
+        "{} NAKs, not more than {}",
+        faster.naks.len(),
+        default.naks.len()
+    );
+}
+
+struct Recovery {
+    // when each NAK was sent, since the packet was lost
+    naks: Vec<Duration>,
+    recovered_after: Duration,
+}
+
+// packet "5" is lost, along with the copies of it that `lose` picks by their index and the time
+// since it was first sent
+fn recover(
+    nak_period: NakPeriodConfig,
+    delay: Duration,
+    lose: impl Fn(usize, Duration) -> bool,
+) -> Recovery {
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(delay),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(1),
+        PacketCount(8192),
+        |settings| {
+            settings.nak_period = nak_period;
+        },
+    );
+    input_data_simulation(
+        start,
+        PACKETS,
+        Duration::from_millis(1),
+        &mut network.sender,
+    );
+
+    let mut lost = None;
+    let mut copies = 0;
+    let mut recovered_at = None;
+    let mut naks = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while step.receiver.next_data(now).is_some() {}
+        for packet in step.next_packets() {
+            match (&packet.0, lost) {
+                (Packet::Data(data), None) if &data.payload[..] == b"5" => {
+                    lost = Some((data.seq_number, now));
+                    copies = 1;
+                    continue;
+                }
+                (Packet::Data(data), Some((seq_number, lost_at)))
+                    if data.seq_number == seq_number && recovered_at.is_none() =>
+                {
+                    copies += 1;
+                    if lose(copies - 1, now - lost_at) {
+                        continue;
+                    }
+                    recovered_at = Some(now + delay);
+                }
+                (
+                    Packet::Control(ControlPacket {
+                        control_type: ControlTypes::Nak(_),
+                        ..
+                    }),
+                    Some(_),
+                ) => naks.push(now),
+                _ => {}
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    let (_, lost_at) = lost.unwrap();
+    Recovery {
+        naks: naks.into_iter().map(|nak| nak - lost_at).collect(),
+        recovered_after: recovered_at.unwrap() - lost_at,
+    }
+}
@@ -40,8 +40,8 @@ fn do_not_enough_latency(seed: u64, packets: usize) {
     // 4% packet loss, 4 sec latency with 0.2 s variance
     let mut simulation = RandomLossSimulation {
         rng,
-        delay_dist: Normal::new(1.5, 0.2).unwrap(),
-        drop_dist: Bernoulli::new(0.01).unwrap(),
+        delay_dist: DelayDistribution::Normal(Normal::new(1.5, 0.2).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(0.01).unwrap()),
     };
 
     let (mut network, mut sender, mut receiver) =
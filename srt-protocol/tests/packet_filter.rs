@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, BufMut, Bytes};
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{
+    options::*,
+    packet::*,
+    protocol::filter::{FilteredPackets, PacketFilter},
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 20;
+// well below the initial retransmission timeout, so only the lost packet could be retransmitted
+const DELAY: Duration = Duration::from_millis(5);
+const BLOCK: u32 = 4;
+// the sender is nowhere near this message number, so it tells the parity packets apart
+const PARITY: MsgNumber = MsgNumber(MsgNumber::MAX - 1);
+
+/// Sends a parity packet after each block of packets, from which any single packet lost in the
+/// block can be rebuilt
+#[derive(Debug, Default)]
+struct XorFilter {
+    // the first sequence number of the block being sent, how many were sent and their parity
+    sending: Option<(SeqNumber, u32, Vec<u8>)>,
+    received: Vec<DataPacket>,
+}
+
+impl XorFilter {
+    // the header fields that can't be derived from the parity packet are covered too
+    fn xor_into(parity: &mut Vec<u8>, packet: &DataPacket) {
+        let mut bytes = Vec::new();
+        bytes.put_u16(packet.payload.len() as u16);
+        bytes.put_u32(packet.timestamp.as_micros());
+        bytes.put_u32(packet.message_number.as_raw());
+        bytes.extend_from_slice(&packet.payload);
+
+        if parity.len() < bytes.len() {
+            parity.resize(bytes.len(), 0);
+        }
+        for (p, b) in parity.iter_mut().zip(bytes) {
+            *p ^= b;
+        }
+    }
+}
+
+impl PacketFilter for XorFilter {
+    fn on_send(&mut self, packet: &DataPacket) -> Vec<DataPacket> {
+        let (_, sent, parity) = self
+            .sending
+            .get_or_insert_with(|| (packet.seq_number, 0, Vec::new()));
+        Self::xor_into(parity, packet);
+        *sent += 1;
+        if *sent < BLOCK {
+            return Vec::new();
+        }
+
+        let (first, _, parity) = self.sending.take().unwrap();
+        vec![DataPacket {
+            seq_number: first,
+            message_number: PARITY,
+            payload: parity.into(),
+            ..packet.clone()
+        }]
+    }
+
+    fn on_receive(&mut self, packet: DataPacket) -> FilteredPackets {
+        if packet.message_number != PARITY {
+            self.received.push(packet.clone());
+            if self.received.len() > 2 * BLOCK as usize {
+                self.received.remove(0);
+            }
+            return FilteredPackets {
+                packet: Some(packet),
+                ..Default::default()
+            };
+        }
+
+        let block: Vec<_> = (0..BLOCK).map(|i| packet.seq_number + i).collect();
+        let missing: Vec<_> = block
+            .iter()
+            .filter(|s| !self.received.iter().any(|p| p.seq_number == **s))
+            .collect();
+        let rebuilt = match missing[..] {
+            [seq_number] => {
+                let mut parity = packet.payload.to_vec();
+                for received in &self.received {
+                    if block.contains(&received.seq_number) {
+                        Self::xor_into(&mut parity, received);
+                    }
+                }
+                let mut header = &parity[..10];
+                let len = header.get_u16() as usize;
+                let timestamp = TimeStamp::from_micros(header.get_u32());
+                let message_number = MsgNumber(header.get_u32());
+                vec![DataPacket {
+                    seq_number: *seq_number,
+                    message_number,
+                    timestamp,
+                    payload: Bytes::copy_from_slice(&parity[10..10 + len]),
+                    ..packet
+                }]
+            }
+            _ => Vec::new(),
+        };
+        FilteredPackets {
+            packet: None,
+            rebuilt,
+            ..Default::default()
+        }
+    }
+
+    fn report_losses(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn xor_filter_recovers_lost_packet() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    sender.set_packet_filter(Box::<XorFilter>::default());
+    receiver.set_packet_filter(Box::<XorFilter>::default());
+    input_data_simulation(
+        start,
+        PACKETS,
+        Duration::from_millis(1),
+        &mut network.sender,
+    );
+
+    let mut dropped = false;
+    let mut next_data = 1;
+    let now = run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while let Some((_, payload)) = step.receiver.next_data(now) {
+            assert_eq!(payload, next_data.to_string());
+            next_data += 1;
+        }
+        for packet in step.next_packets() {
+            match &packet.0 {
+                // lose a single packet in the middle of the second block
+                Packet::Data(data) if data.payload == "6" && !dropped => dropped = true,
+                // make sure the parity arrives behind the last packet of its block, packets
+                // due at the same time may be delivered in any order
+                Packet::Data(data) if data.message_number == PARITY => step
+                    .network
+                    .send(now + DELAY + Duration::from_millis(1), packet),
+                _ => step.network.send_lossy(&mut simulation, now, packet),
+            }
+        }
+    });
+
+    assert!(dropped);
+    assert_eq!(next_data, PACKETS + 1);
+
+    let blocks = PACKETS as u64 / BLOCK as u64;
+    sender.update_statistics(now);
+    receiver.update_statistics(now);
+    let sender_stats = sender.statistics();
+    assert_eq!(sender_stats.tx_unique_data, PACKETS as u64);
+    assert_eq!(sender_stats.tx_filter_extra_data, blocks);
+    assert_eq!(sender_stats.tx_retransmit_data, 0);
+    assert_eq!(sender_stats.rx_nak, 0);
+
+    let receiver_stats = receiver.statistics();
+    assert_eq!(receiver_stats.rx_filter_extra_data, blocks);
+    assert_eq!(receiver_stats.rx_filter_supply_data, 1);
+    assert_eq!(receiver_stats.rx_unique_data, PACKETS as u64 - 1);
+    assert_eq!(receiver_stats.rx_loss_data, 1);
+}
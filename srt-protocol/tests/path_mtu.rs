@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{connection::Input, options::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+// loses every packet larger than this, smaller ones all get through
+const PATH_MTU: usize = 1100;
+const BUFFER_SIZE: PacketCount = PacketCount(8192);
+
+#[test]
+fn max_message_size_follows_path_mtu() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(20)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), BUFFER_SIZE);
+    assert_eq!(
+        sender.settings().max_message_size(),
+        BUFFER_SIZE * PacketSize(1316)
+    );
+
+    // mostly full packets, with a small one now and then, until it has fallen back
+    for i in 0..1_000 {
+        let size = if i % 4 == 0 { 200 } else { 1316 };
+        let time = start + Duration::from_millis(i);
+        network
+            .sender
+            .schedule_input(time, Input::Data(Some((time, new_message(size)))));
+    }
+
+    // the payload of the first plateau below the default of 1316 is still too large, so it falls
+    // back twice, and the largest message with it
+    let max_message_size = (BUFFER_SIZE * PacketSize(1006 - 44)).0;
+    let too_large_at = start + Duration::from_secs(2);
+    let largest_at = too_large_at + Duration::from_millis(100);
+    for (time, size) in [
+        (too_large_at, max_message_size + 1),
+        (largest_at, max_message_size),
+    ] {
+        network
+            .sender
+            .schedule_input(time, Input::Data(Some((time, new_message(size)))));
+    }
+    network
+        .sender
+        .schedule_input(largest_at + Duration::from_millis(100), Input::Data(None));
+
+    let mut received = Vec::new();
+    let end = run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while let Some((_, data)) = step.receiver.next_data(now) {
+            received.push(data.len() as u64);
+        }
+        for packet in step.next_packets() {
+            if packet.0.wire_size() <= PATH_MTU {
+                step.network.send_lossy(&mut simulation, now, packet);
+            }
+        }
+    });
+
+    sender.update_statistics(end);
+    assert_eq!(sender.statistics().tx_max_payload_size, 1006 - 44);
+    // the message that no longer fits is turned down rather than stalling the connection, the
+    // one that does makes it
+    assert_eq!(received.last(), Some(&max_message_size));
+    assert!(!received.contains(&(max_message_size + 1)));
+}
+
+fn new_message(size: u64) -> Bytes {
+    (0..size).map(|i| i as u8).collect()
+}
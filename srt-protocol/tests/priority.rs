@@ -0,0 +1,111 @@
+use std::time::{Duration, Instant};
+
+use bytes::{BufMut, BytesMut};
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{connection::Priority, options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const MESSAGES: u32 = 500;
+const PAYLOAD: usize = 1_000;
+// 1 MB/s of input, more than the cap lets through, so the messages wait to be sent
+const MESSAGE_SPACING: Duration = Duration::from_millis(1);
+const CAP: DataRate = DataRate(800_000);
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn high_priority_goes_first_under_a_bandwidth_cap() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(1),
+        PacketCount(8192),
+        |settings| {
+            settings.bandwidth = LiveBandwidthMode::Max(CAP);
+        },
+    );
+
+    // every fifth message is metadata, sent with a high priority
+    let mut messages = (0..MESSAGES).map(|n| {
+        let priority = if n % 5 == 0 {
+            Priority::High
+        } else {
+            Priority::Low
+        };
+        let mut payload = BytesMut::with_capacity(PAYLOAD);
+        payload.put_u32(n);
+        payload.put_bytes(0, PAYLOAD - 4);
+        (start + MESSAGE_SPACING * n, payload.freeze(), priority)
+    });
+    let mut next_message = messages.next();
+
+    let mut arrived = Vec::new();
+    let mut received = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        let mut queued = false;
+        while let Some((time, payload, priority)) = next_message.clone() {
+            if time > now {
+                step.wake_at(time);
+                break;
+            }
+            let sender = &mut *step.sender;
+            sender.handle_prioritized_data_input(now, (time, payload), priority);
+            next_message = messages.next();
+            if next_message.is_none() {
+                sender.handle_data_input(now, None);
+            }
+            queued = true;
+        }
+        // what was just queued may be due to go out already
+        if queued {
+            let next_time = step.sender.check_timers(now);
+            step.wake_at(next_time);
+        }
+
+        while let Some((_, payload)) = step.receiver.next_data(now) {
+            received.push(message_number(&payload));
+        }
+        for packet in step.next_packets() {
+            if let Packet::Data(data) = &packet.0 {
+                if !data.retransmitted {
+                    let n = message_number(&data.payload);
+                    let sent_at = start + MESSAGE_SPACING * n;
+                    arrived.push((n, now + DELAY - sent_at));
+                }
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    let mean_latency = |high: bool| {
+        let latencies = arrived
+            .iter()
+            .filter(|(n, _)| (n % 5 == 0) == high)
+            .map(|(_, latency)| *latency)
+            .collect::<Vec<_>>();
+        latencies.iter().sum::<Duration>() / latencies.len() as u32
+    };
+    let (high, low) = (mean_latency(true), mean_latency(false));
+    assert!(high * 4 < low, "high {high:?}, low {low:?}");
+
+    // everything is delivered, in the order it was sent in
+    let mut sent_order = arrived.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+    assert_eq!(received, sent_order);
+    sent_order.sort_unstable();
+    assert_eq!(sent_order, (0..MESSAGES).collect::<Vec<_>>());
+}
+
+fn message_number(payload: &[u8]) -> u32 {
+    u32::from_be_bytes(payload[..4].try_into().unwrap())
+}
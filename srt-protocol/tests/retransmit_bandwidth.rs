@@ -0,0 +1,131 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{connection::Input, options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: u32 = 2_000;
+const PAYLOAD: usize = 1_000;
+// 1 MB/s of input, with a quarter of that to spare under the cap
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+const CAP: DataRate = DataRate(1_250_000);
+// a burst of loss well after the sending period settled
+const LOST: Range<u32> = 1_000..1_050;
+const WINDOW: Duration = Duration::from_millis(20);
+
+struct Outcome {
+    // the most payload sent in any window
+    peak: u64,
+    // from the last packet lost until all of them were sent again
+    recovery: Duration,
+    received: usize,
+}
+
+#[test]
+fn capped_stays_within_the_bandwidth() {
+    let _ = pretty_env_logger::try_init();
+
+    let capped = simulate(RetransmitBandwidth::Capped);
+    let burst = simulate(RetransmitBandwidth::Burst);
+
+    let window_cap = CAP.0 * WINDOW.as_micros() as u64 / 1_000_000;
+    // every 16th packet goes out right after the previous one, whatever the sending period
+    let tolerance = window_cap / 16 + PAYLOAD as u64;
+    assert!(
+        capped.peak <= window_cap + tolerance,
+        "capped peak {} > {window_cap}",
+        capped.peak
+    );
+    assert!(
+        burst.peak > window_cap + tolerance,
+        "burst peak {} <= {window_cap}",
+        burst.peak
+    );
+    assert!(
+        capped.recovery > burst.recovery * 2,
+        "capped {:?}, burst {:?}",
+        capped.recovery,
+        burst.recovery
+    );
+    assert_eq!(capped.received, PACKETS as usize);
+    assert_eq!(burst.received, PACKETS as usize);
+}
+
+fn simulate(retransmit_bandwidth: RetransmitBandwidth) -> Outcome {
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(5)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(1),
+        PacketCount(8192),
+        |settings| {
+            settings.bandwidth = LiveBandwidthMode::Max(CAP);
+            settings.retransmit_bandwidth = retransmit_bandwidth;
+        },
+    );
+    let init_seq_num = sender.settings().init_seq_num;
+    for i in 1..=PACKETS {
+        let t = start + PACKET_SPACING * i;
+        let payload = Bytes::from(vec![0; PAYLOAD]);
+        network
+            .sender
+            .schedule_input(t, Input::Data(Some((t, payload))));
+    }
+    network
+        .sender
+        .schedule_input(start + PACKET_SPACING * (PACKETS + 1), Input::Data(None));
+
+    let mut sent = Vec::new();
+    let mut last_lost = None;
+    let mut last_recovered = None;
+    let mut received = 0;
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while step.receiver.next_data(now).is_some() {
+            received += 1;
+        }
+        for packet in step.next_packets() {
+            if let Packet::Data(data) = &packet.0 {
+                sent.push((now, data.payload.len() as u64));
+                let n = data.seq_number - init_seq_num;
+                if LOST.contains(&n) {
+                    if !data.retransmitted {
+                        last_lost = Some(now);
+                        continue;
+                    }
+                    last_recovered = Some(now);
+                }
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    let peak = sent
+        .iter()
+        .map(|(start, _)| {
+            let end = *start + WINDOW;
+            sent.iter()
+                .filter(|(t, _)| (start..&end).contains(&t))
+                .map(|(_, size)| size)
+                .sum()
+        })
+        .max()
+        .unwrap();
+    Outcome {
+        peak,
+        recovery: last_recovered.unwrap() - last_lost.unwrap(),
+        received,
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, Instant};
+
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 20;
+// well below the initial retransmission timeout, so only the lost packet is retransmitted
+const DELAY: Duration = Duration::from_millis(5);
+
+#[test]
+fn retransmission_is_flagged_and_not_reported_again() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(DELAY),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, Duration::from_secs(1), PacketCount(8192));
+    input_data_simulation(
+        start,
+        PACKETS,
+        Duration::from_millis(1),
+        &mut network.sender,
+    );
+
+    let mut lost = None;
+    let mut retransmitted = Vec::new();
+    let mut naks = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while step.receiver.next_data(now).is_some() {}
+        for packet in step.next_packets() {
+            match &packet.0 {
+                Packet::Data(data) if lost.is_none() && &data.payload[..] == b"5" => {
+                    lost = Some(data.seq_number);
+                    continue;
+                }
+                Packet::Data(data) if Some(data.seq_number) == lost => {
+                    retransmitted.push(data.retransmitted)
+                }
+                Packet::Control(ControlPacket {
+                    control_type: ControlTypes::Nak(loss_list),
+                    ..
+                }) => naks.push(loss_list.iter_decompressed().collect::<Vec<_>>()),
+                _ => {}
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    // the retransmission that answered the NAK carries the flag, and closed the gap for good
+    let lost = lost.unwrap();
+    assert_eq!(retransmitted, [true]);
+    assert_eq!(naks, [vec![lost]]);
+    assert_eq!(receiver.statistics().tx_nak, 1);
+    assert_eq!(receiver.statistics().rx_retransmit_data, 1);
+}
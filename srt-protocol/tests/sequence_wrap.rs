@@ -0,0 +1,203 @@
+use std::{
+    cmp::min,
+    str,
+    time::{Duration, Instant},
+};
+
+use log::{info, trace};
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::{Bernoulli, Normal};
+use srt_protocol::{
+    connection::{DuplexConnection, Input},
+    options::*,
+    packet::SeqNumber,
+    statistics::SocketStatistics,
+};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: usize = 5_000;
+const PACKET_SPACING: Duration = Duration::from_millis(1);
+
+#[test]
+fn sequence_number_wraps() {
+    let _ = pretty_env_logger::try_init();
+
+    // wrap at the very start, in the middle, and right at the end of the stream
+    for offset in [1, 10, PACKETS as u32 / 2, PACKETS as u32 - 10] {
+        do_sequence_number_wrap(rand::random(), SeqNumber(SeqNumber::MAX - offset));
+    }
+}
+
+fn do_sequence_number_wrap(seed: u64, init_seq_num: SeqNumber) {
+    info!(
+        "Seed is: {}, initial sequence number is: {:?}",
+        seed, init_seq_num
+    );
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(seed),
+        delay_dist: DelayDistribution::Normal(Normal::new(20e-3, 4e-3).unwrap()),
+        drop_dist: LossModel::Random(Bernoulli::new(0.01).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) = simulation.build_with(
+        start,
+        Duration::from_secs(1),
+        PacketCount(8192),
+        |settings| {
+            settings.init_seq_num = init_seq_num;
+        },
+    );
+    input_data_simulation(start, PACKETS, PACKET_SPACING, &mut network.sender);
+
+    let mut now = start;
+    let mut next_data = 1;
+    let mut payload_bytes = 0;
+    let mut sender_stats = SocketStatistics::new();
+    let mut receiver_stats = SocketStatistics::new();
+    loop {
+        let sender_next_time = if sender.is_open() {
+            while let Some(packet) = sender.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+            sender_stats = check_monotonic(now, &mut sender, sender_stats);
+
+            let next_timer = sender.check_timers(now);
+            let (next_time, input) = network.sender.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => sender.handle_data_input(next_time, data),
+                Input::Packet(packet) => sender.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let receiver_next_time = if receiver.is_open() {
+            while let Some((_, payload)) = receiver.next_data(now) {
+                let actual: usize = str::from_utf8(&payload[..]).unwrap().parse().unwrap();
+                assert_eq!(actual, next_data, "seed {seed}");
+                next_data += 1;
+                payload_bytes += payload.len() as u64;
+            }
+
+            while let Some(packet) = receiver.next_packet(now) {
+                network.send_lossy(&mut simulation, now, packet);
+            }
+            receiver_stats = check_monotonic(now, &mut receiver, receiver_stats);
+
+            let next_timer = receiver.check_timers(now);
+            let (next_time, input) = network.receiver.select_next_input(now, next_timer);
+            match input {
+                Input::Data(data) => receiver.handle_data_input(next_time, data),
+                Input::Packet(packet) => receiver.handle_packet_input(next_time, packet),
+                _ => {}
+            };
+            Some(next_time)
+        } else {
+            None
+        };
+
+        let next_time = match (sender_next_time, receiver_next_time) {
+            (Some(s), Some(r)) => min(s, r),
+            (Some(s), None) => s,
+            (None, Some(r)) => r,
+            _ => break,
+        };
+
+        trace!("Delta = {:?}", next_time - now);
+        now = next_time;
+    }
+
+    assert_eq!(next_data, PACKETS + 1, "seed {seed}");
+
+    let sender_stats = check_monotonic(now, &mut sender, sender_stats);
+    let receiver_stats = check_monotonic(now, &mut receiver, receiver_stats);
+
+    let packets = PACKETS as u64;
+    assert_eq!(sender_stats.tx_unique_data, packets, "seed {seed}");
+    assert_eq!(
+        sender_stats.tx_data,
+        packets + sender_stats.tx_retransmit_data,
+        "seed {seed}"
+    );
+    // packets arriving out of order fill a gap, so they are counted as recovered
+    assert_eq!(
+        receiver_stats.rx_unique_data + receiver_stats.rx_retransmit_data,
+        packets,
+        "seed {seed}"
+    );
+    assert_eq!(receiver_stats.rx_dropped_data, 0, "seed {seed}");
+    assert_eq!(sender_stats.tx_unacknowledged_data, 0, "seed {seed}");
+    assert_eq!(sender_stats.tx_buffered_bytes, 0, "seed {seed}");
+    assert!(receiver_stats.rx_data >= packets, "seed {seed}");
+
+    // 16 bytes SRT + 8 bytes UDP + 20 bytes IPv4 headers
+    let header_bytes = 44 * packets;
+    assert_eq!(
+        sender_stats.tx_unique_bytes,
+        payload_bytes + header_bytes,
+        "seed {seed}"
+    );
+    assert_eq!(
+        sender_stats.tx_bytes,
+        sender_stats.tx_unique_bytes + sender_stats.tx_retransmit_bytes,
+        "seed {seed}"
+    );
+}
+
+fn check_monotonic(
+    now: Instant,
+    connection: &mut DuplexConnection,
+    last: SocketStatistics,
+) -> SocketStatistics {
+    connection.update_statistics(now);
+    let stats = connection.statistics().clone();
+
+    let counters = |s: &SocketStatistics| {
+        [
+            s.tx_all_packets,
+            s.rx_all_packets,
+            s.tx_all_bytes,
+            s.rx_all_bytes,
+            s.tx_data,
+            s.rx_data,
+            s.tx_unique_data,
+            s.rx_unique_data,
+            s.tx_loss_data,
+            s.rx_loss_data,
+            s.tx_retransmit_data,
+            s.rx_retransmit_data,
+            s.tx_ack,
+            s.rx_ack,
+            s.tx_nak,
+            s.rx_nak,
+            s.tx_bytes,
+            s.rx_bytes,
+            s.tx_unique_bytes,
+            s.rx_unique_bytes,
+            s.rx_loss_bytes,
+            s.tx_retransmit_bytes,
+        ]
+    };
+    for (i, (previous, current)) in counters(&last).iter().zip(counters(&stats)).enumerate() {
+        assert!(
+            current >= *previous,
+            "counter {i} went backwards: {previous} -> {current}"
+        );
+    }
+
+    // in flight packets are counted from the front of the send buffer, across the wrap too
+    assert!(
+        stats.tx_unacknowledged_data <= stats.tx_buffered_data,
+        "{} packets in flight, {} buffered",
+        stats.tx_unacknowledged_data,
+        stats.tx_buffered_data
+    );
+
+    stats
+}
@@ -2,13 +2,17 @@ use std::{
     cmp::max,
     collections::BinaryHeap,
     convert::TryFrom,
+    iter,
     net::SocketAddr,
     time::{Duration, Instant},
 };
 
 use log::{error, warn};
-use rand::{distributions::Bernoulli, prelude::*};
-use rand_distr::Normal;
+use rand::{
+    distributions::{Bernoulli, BernoulliError, Uniform},
+    prelude::*,
+};
+use rand_distr::{Exp, LogNormal, Normal};
 
 use srt_protocol::{
     connection::{Connection, ConnectionSettings, DuplexConnection, Input},
@@ -17,11 +21,13 @@ use srt_protocol::{
     protocol::handshake::Handshake,
 };
 
-struct ScheduledInput(Instant, Input);
+// inputs due at the same time are taken in the order they were scheduled, so packets sent back
+// to back over a link with a constant delay arrive in order
+struct ScheduledInput(Instant, u64, Input);
 
 impl PartialEq for ScheduledInput {
     fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
+        (self.0, self.1) == (other.0, other.1)
     }
 }
 
@@ -35,7 +41,7 @@ impl PartialOrd for ScheduledInput {
 
 impl Ord for ScheduledInput {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0.cmp(&other.0).reverse() // reverse to make it a min-heap
+        (self.0, self.1).cmp(&(other.0, other.1)).reverse() // reverse to make it a min-heap
     }
 }
 
@@ -56,6 +62,7 @@ pub fn input_data_simulation(
 pub struct PeerSimulator {
     addr: SocketAddr,
     input: BinaryHeap<ScheduledInput>,
+    scheduled: u64,
 }
 
 impl PeerSimulator {
@@ -63,6 +70,7 @@ impl PeerSimulator {
         PeerSimulator {
             addr,
             input: BinaryHeap::new(),
+            scheduled: 0,
         }
     }
 
@@ -71,12 +79,19 @@ impl PeerSimulator {
     }
 
     pub fn schedule_input(&mut self, release_at: Instant, input: Input) {
-        self.input.push(ScheduledInput(release_at, input));
+        self.input
+            .push(ScheduledInput(release_at, self.scheduled, input));
+        self.scheduled += 1;
+    }
+
+    /// When the earliest scheduled input is due, without taking it
+    pub fn next_input_time(&self) -> Option<Instant> {
+        self.input.peek().map(|i| i.0)
     }
 
     pub fn select_next_input(&mut self, now: Instant, next_timer: Instant) -> (Instant, Input) {
         if self.has_scheduled_input(next_timer) {
-            self.input.pop().map(|i| (max(now, i.0), i.1)).unwrap()
+            self.input.pop().map(|i| (max(now, i.0), i.2)).unwrap()
         } else {
             (next_timer, Input::Timer)
         }
@@ -137,10 +152,176 @@ impl NetworkSimulator {
     }
 }
 
+/// A point in time of [`run_until_closed`], once the inputs due by then were handled
+pub struct Step<'a> {
+    pub now: Instant,
+    pub sender: &'a mut DuplexConnection,
+    pub receiver: &'a mut DuplexConnection,
+    pub network: &'a mut NetworkSimulator,
+    next_times: &'a mut Vec<Instant>,
+}
+
+impl Step<'_> {
+    /// The packets both peers have to send now, the sender's first
+    pub fn next_packets(&mut self) -> Vec<(Packet, SocketAddr)> {
+        let now = self.now;
+        let sender = iter::from_fn(|| self.sender.next_packet(now));
+        let receiver = iter::from_fn(|| self.receiver.next_packet(now));
+        sender.chain(receiver).collect()
+    }
+
+    /// Makes sure there's another step at `time`, when there's nothing else due by then
+    pub fn wake_at(&mut self, time: Instant) {
+        self.next_times.push(time);
+    }
+}
+
+/// Steps both peers on the same clock from `start`, so neither gets ahead of the packets in
+/// flight, until they're closed and the network is drained. On every step, `step` takes the data
+/// released and the packets sent, whether it passes them on to the network is up to it. Returns
+/// the time of the last step.
+pub fn run_until_closed(
+    start: Instant,
+    sender: &mut DuplexConnection,
+    receiver: &mut DuplexConnection,
+    network: &mut NetworkSimulator,
+    mut step: impl FnMut(&mut Step),
+) -> Instant {
+    let mut now = start;
+    loop {
+        let mut next_times = Vec::new();
+        for (connection, peer) in [
+            (&mut *sender, &mut network.sender),
+            (&mut *receiver, &mut network.receiver),
+        ] {
+            if connection.is_open() {
+                handle_due_inputs(now, connection, peer);
+                next_times.push(connection.check_timers(now));
+                next_times.extend(peer.next_input_time());
+            }
+        }
+
+        step(&mut Step {
+            now,
+            sender,
+            receiver,
+            network,
+            next_times: &mut next_times,
+        });
+
+        match next_times.into_iter().min() {
+            Some(next_time) => now = now.max(next_time),
+            None => return now,
+        }
+    }
+}
+
+fn handle_due_inputs(now: Instant, connection: &mut DuplexConnection, peer: &mut PeerSimulator) {
+    loop {
+        match peer.select_next_input(now, now).1 {
+            Input::Data(data) => connection.handle_data_input(now, data),
+            Input::Packet(packet) => connection.handle_packet_input(now, packet),
+            _ => break,
+        }
+    }
+}
+
+/// One-way network delay model, distributions are sampled in seconds
+#[derive(Clone, Debug)]
+pub enum DelayDistribution {
+    Constant(Duration),
+    Uniform(Uniform<f64>),
+    /// Negative samples are folded back to positive delays. The positive side gets the mass below
+    /// zero mirrored onto it, so the mean and the deviation come out above the configured ones
+    /// once the deviation isn't small next to the mean.
+    Normal(Normal<f64>),
+    /// Negative samples are clamped to zero delay. The positive side is left as configured, so the
+    /// mean overshoots by half as much as when folding, and not at all for a small deviation.
+    ClampedNormal(Normal<f64>),
+    Exponential(Exp<f64>),
+    LogNormal(LogNormal<f64>),
+}
+
+impl Distribution<Duration> for DelayDistribution {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Duration {
+        use DelayDistribution::*;
+        let delay = match self {
+            Constant(delay) => return *delay,
+            Uniform(dist) => dist.sample(rng),
+            Normal(dist) => dist.sample(rng).abs(),
+            ClampedNormal(dist) => dist.sample(rng).max(0.),
+            Exponential(dist) => dist.sample(rng),
+            LogNormal(dist) => dist.sample(rng),
+        };
+        Duration::from_secs_f64(delay)
+    }
+}
+
+/// Packet loss model, sampled once per packet
+#[derive(Clone, Debug)]
+pub enum LossModel {
+    /// Independent loss with a fixed probability per packet
+    Random(Bernoulli),
+    /// Correlated (bursty) loss
+    GilbertElliott(GilbertElliott),
+}
+
+impl LossModel {
+    pub fn is_dropped<R: Rng + ?Sized>(&mut self, rng: &mut R) -> bool {
+        match self {
+            LossModel::Random(dist) => dist.sample(rng),
+            LossModel::GilbertElliott(model) => model.is_dropped(rng),
+        }
+    }
+}
+
+/// Two state Markov chain loss model, packets are lost with a per state probability and the state
+/// transitions after every packet
+#[derive(Clone, Debug)]
+pub struct GilbertElliott {
+    to_bad: Bernoulli,
+    to_good: Bernoulli,
+    good_loss: Bernoulli,
+    bad_loss: Bernoulli,
+    bad: bool,
+}
+
+impl GilbertElliott {
+    /// Starts in the good state
+    pub fn new(
+        to_bad: f64,
+        to_good: f64,
+        good_loss: f64,
+        bad_loss: f64,
+    ) -> Result<Self, BernoulliError> {
+        Ok(Self {
+            to_bad: Bernoulli::new(to_bad)?,
+            to_good: Bernoulli::new(to_good)?,
+            good_loss: Bernoulli::new(good_loss)?,
+            bad_loss: Bernoulli::new(bad_loss)?,
+            bad: false,
+        })
+    }
+
+    pub fn is_dropped<R: Rng + ?Sized>(&mut self, rng: &mut R) -> bool {
+        let dropped = if self.bad {
+            self.bad_loss.sample(rng)
+        } else {
+            self.good_loss.sample(rng)
+        };
+        self.bad = if self.bad {
+            !self.to_good.sample(rng)
+        } else {
+            self.to_bad.sample(rng)
+        };
+        dropped
+    }
+}
+
 pub struct RandomLossSimulation {
     pub rng: StdRng,
-    pub delay_dist: Normal<f64>,
-    pub drop_dist: Bernoulli,
+    pub delay_dist: DelayDistribution,
+    pub drop_dist: LossModel,
 }
 
 impl RandomLossSimulation {
@@ -150,7 +331,19 @@ impl RandomLossSimulation {
         latency: Duration,
         recv_buffer_size: PacketCount,
     ) -> (NetworkSimulator, DuplexConnection, DuplexConnection) {
-        let sender = self.new_connection_settings(start, latency);
+        self.build_with(start, latency, recv_buffer_size, |_| {})
+    }
+
+    /// Like [`Self::build`], but `configure` can adjust the settings shared by both peers
+    pub fn build_with(
+        &mut self,
+        start: Instant,
+        latency: Duration,
+        recv_buffer_size: PacketCount,
+        configure: impl FnOnce(&mut ConnectionSettings),
+    ) -> (NetworkSimulator, DuplexConnection, DuplexConnection) {
+        let mut sender = self.new_connection_settings(start, latency);
+        configure(&mut sender);
         let receiver = ConnectionSettings {
             remote: (sender.remote.ip(), sender.remote.port().wrapping_add(1)).into(),
             remote_sockid: sender.local_sockid,
@@ -174,8 +367,8 @@ impl RandomLossSimulation {
     }
 
     pub fn next_packet_schedule(&mut self, now: Instant) -> Option<Instant> {
-        if !self.drop_dist.sample(&mut self.rng) {
-            Some(now + Duration::from_secs_f64(self.delay_dist.sample(&mut self.rng).abs()))
+        if !self.drop_dist.is_dropped(&mut self.rng) {
+            Some(now + self.delay_dist.sample(&mut self.rng))
         } else {
             None
         }
@@ -190,17 +383,37 @@ impl RandomLossSimulation {
             rtt: Duration::default(),
             init_seq_num: self.rng.gen(),
             max_packet_size: PacketSize(1316),
+            max_segment_size: PacketSize(1500),
             max_flow_size: PacketCount(8192),
+            idle_restart_threshold: None,
+            max_retransmits: None,
             send_tsbpd_latency: latency,
             recv_tsbpd_latency: latency,
             cipher: None,
             stream_id: None,
             bandwidth: Default::default(),
+            retransmit_bandwidth: Default::default(),
+            send_buffer_full: Default::default(),
+            flow_window_full: Default::default(),
+            input_overflow: Default::default(),
             recv_buffer_size: PacketCount(8192),
+            recv_buffer_cap: None,
             send_buffer_size: PacketCount(8192),
             statistics_interval: Duration::from_secs(1),
+            loss_rate_window: Duration::from_secs(5),
+            initial_rtt: None,
+            too_late_drop_margin: Percent(0),
             peer_idle_timeout: Duration::from_secs(5),
             too_late_packet_drop: true,
+            delivery_mode: Default::default(),
+            nak_period: Default::default(),
+            recv_granularity: Default::default(),
+            peer_version: SrtVersion::CURRENT,
+            features: SrtShakeFlags::SUPPORTED,
+            packet_filter: None,
+            negotiation_adjustments: Vec::new(),
+            session_token: None,
+            resumable_sessions: None,
         }
     }
 }
@@ -0,0 +1,392 @@
+use std::time::Duration;
+
+use srt_protocol::{
+    options::{
+        DataRate, FlowWindowFull, InputOverflowPolicy, LiveBandwidthMode, PacketCount, Percent,
+    },
+    packet::*,
+    test_util::{Side, TestHarness, Transmission},
+};
+
+const DELAY: Duration = Duration::from_millis(20);
+const PACE: Duration = Duration::from_millis(10);
+
+fn data_seq(transmission: &Transmission) -> Option<(u32, bool)> {
+    match &transmission.packet {
+        Packet::Data(data) => Some((data.seq_number.as_raw(), data.retransmitted)),
+        _ => None,
+    }
+}
+
+#[test]
+fn single_loss_retransmit_timing() {
+    let _ = pretty_env_logger::try_init();
+
+    let mut harness = TestHarness::with_settings(|settings| {
+        settings.send_tsbpd_latency = Duration::from_millis(200);
+        settings.recv_tsbpd_latency = Duration::from_millis(200);
+        // the round trip of the link, so nothing is retransmitted on a timeout before the NAK
+        settings.initial_rtt = Some(DELAY * 2);
+    });
+    harness.set_delay(DELAY);
+    // the second packet is lost the first time it's sent
+    harness.drop_where(|transmission| data_seq(transmission) == Some((1, false)));
+
+    for _ in 0..3 {
+        harness.send("data");
+        harness.step(PACE);
+    }
+    harness.step(Duration::from_millis(500));
+
+    let transmissions = harness.transmissions();
+    let find = |matches: &dyn Fn(&Transmission) -> bool| {
+        transmissions
+            .iter()
+            .find(|transmission| matches(transmission))
+            .unwrap()
+    };
+    let first = find(&|t| data_seq(t) == Some((0, false)));
+    let lost = find(&|t| data_seq(t) == Some((1, false)));
+    let next = find(&|t| data_seq(t) == Some((2, false)));
+    let nak = find(&|t| {
+        matches!(
+            t.packet,
+            Packet::Control(ControlPacket {
+                control_type: ControlTypes::Nak(_),
+                ..
+            })
+        )
+    });
+    let retransmitted = find(&|t| data_seq(t) == Some((1, true)));
+
+    // the sender paces its packets out on its send period, from when the data is handed to it
+    let send_period = first.at - harness.start();
+    assert!(lost.dropped);
+    assert_eq!(lost.at, first.at + PACE);
+    assert_eq!(next.at, lost.at + PACE);
+
+    // the receiver sees the gap as soon as the packet after it arrives, and the sender
+    // retransmits on its first send period after the NAK gets back
+    assert_eq!(nak.from, Side::Receiver);
+    assert_eq!(nak.at, next.at + DELAY);
+    assert_eq!(retransmitted.at, nak.at + DELAY + send_period);
+    assert!(!retransmitted.dropped);
+
+    // nothing else was retransmitted, and everything got through, in order
+    let retransmissions = transmissions
+        .iter()
+        .filter(|t| matches!(data_seq(t), Some((_, true))))
+        .count();
+    assert_eq!(retransmissions, 1);
+    let received = harness.take_received();
+    assert_eq!(received.len(), 3);
+    assert!(received.windows(2).all(|pair| pair[0].0 < pair[1].0));
+}
+
+#[test]
+fn adaptive_buffer_avoids_overflow() {
+    let _ = pretty_env_logger::try_init();
+
+    // a packet every millisecond over a long link, far more than 32 packets in flight at once
+    let run = |cap: Option<PacketCount>| {
+        let mut harness = TestHarness::with_settings(|settings| {
+            settings.send_tsbpd_latency = Duration::from_secs(1);
+            settings.recv_tsbpd_latency = Duration::from_secs(1);
+            settings.initial_rtt = Some(Duration::from_millis(200));
+            settings.idle_restart_threshold = None;
+            settings.send_buffer_size = PacketCount(8192);
+            settings.recv_buffer_size = PacketCount(32);
+            settings.recv_buffer_cap = cap;
+        });
+        harness.set_delay(Duration::from_millis(100));
+        for n in 0..2000 {
+            harness.send(format!("{n}"));
+            harness.step(Duration::from_millis(1));
+        }
+        harness.step(Duration::from_secs(2));
+        let now = harness.now();
+        harness.receiver.update_statistics(now);
+        let statistics = harness.receiver.statistics().clone();
+        (harness.take_received().len(), statistics)
+    };
+
+    let (received, statistics) = run(None);
+    assert!(received < 2000, "{received}");
+    assert!(statistics.rx_dropped_data > 0);
+    assert_eq!(statistics.rx_buffer_size, 32);
+
+    // the buffer grows to what the arrival rate over the latency and round trip takes
+    let (received, statistics) = run(Some(PacketCount(8192)));
+    assert_eq!(received, 2000);
+    assert_eq!(statistics.rx_dropped_data, 0);
+    assert!(
+        statistics.rx_buffer_size > 1000,
+        "{}",
+        statistics.rx_buffer_size
+    );
+}
+
+#[test]
+fn input_overflow_drops_oldest() {
+    let _ = pretty_env_logger::try_init();
+
+    const MAX_BW: u64 = 500_000;
+    const DURATION: Duration = Duration::from_secs(2);
+
+    let mut harness = TestHarness::with_settings(|settings| {
+        settings.send_tsbpd_latency = Duration::from_millis(200);
+        settings.recv_tsbpd_latency = Duration::from_millis(200);
+        settings.idle_restart_threshold = None;
+        settings.send_buffer_size = PacketCount(8192);
+        settings.bandwidth = LiveBandwidthMode::Max(DataRate(MAX_BW));
+        settings.input_overflow = InputOverflowPolicy::DropOldest(Duration::from_millis(100));
+    });
+    harness.set_delay(DELAY);
+
+    // a payload every millisecond is more than twice what the bandwidth allows
+    for _ in 0..DURATION.as_millis() {
+        harness.send(vec![0; 1316]);
+        harness.step(Duration::from_millis(1));
+    }
+    let now = harness.now();
+    harness.sender.update_statistics(now);
+    let statistics = harness.sender.statistics().clone();
+
+    // the oldest data was dropped before it was sent, rather than the send buffer filling up
+    assert!(
+        statistics.tx_input_dropped_data > 1000,
+        "{}",
+        statistics.tx_input_dropped_data
+    );
+    assert_eq!(statistics.tx_buffer_full_dropped_data, 0);
+    assert!(statistics.tx_dropped_data >= statistics.tx_input_dropped_data);
+
+    // while the link stayed within the bandwidth, once the sender measured the input to pace it
+    let paced = harness.start() + Duration::from_millis(200);
+    let sent: usize = harness
+        .transmissions()
+        .iter()
+        .filter(|t| t.from == Side::Sender && t.at >= paced)
+        .filter_map(|t| match &t.packet {
+            Packet::Data(data) => Some(data.payload.len()),
+            _ => None,
+        })
+        .sum();
+    let rate = sent as f64 / (now - paced).as_secs_f64();
+    // give or take the packet after every 16th, which goes out right along with it as a probe
+    assert!(rate <= (MAX_BW * 17 / 16) as f64 * 1.01, "{rate}");
+    assert!(rate >= MAX_BW as f64 * 0.9, "{rate}");
+
+    // and what was sent stayed current
+    harness.step(Duration::from_millis(500));
+    let received = harness.take_received();
+    let latest = received.last().unwrap().0;
+    assert!(
+        now - latest < Duration::from_millis(150),
+        "{:?}",
+        now - latest
+    );
+}
+
+#[test]
+fn queued_data_keeps_origin_spacing() {
+    let _ = pretty_env_logger::try_init();
+
+    const MAX_BW: u64 = 200_000;
+    const MESSAGES: u32 = 100;
+    const SPACING: Duration = Duration::from_millis(2);
+
+    let mut harness = TestHarness::with_settings(|settings| {
+        settings.send_tsbpd_latency = Duration::from_secs(1);
+        settings.recv_tsbpd_latency = Duration::from_secs(1);
+        settings.idle_restart_threshold = None;
+        settings.send_buffer_size = PacketCount(8192);
+        settings.bandwidth = LiveBandwidthMode::Max(DataRate(MAX_BW));
+    });
+    harness.set_delay(DELAY);
+
+    // a payload every 2ms is more than twice what the bandwidth allows, so it queues up
+    for n in 0..MESSAGES {
+        let mut payload = vec![0; 1316];
+        payload[..4].copy_from_slice(&n.to_be_bytes());
+        harness.send(payload);
+        harness.step(SPACING);
+    }
+
+    // release what the receiver has, with when it did
+    let mut released = Vec::new();
+    for _ in 0..2000 {
+        harness.step(Duration::from_millis(1));
+        let now = harness.now();
+        released.extend(
+            harness
+                .take_received()
+                .into_iter()
+                .map(|(_, data)| (now, u32::from_be_bytes(data[..4].try_into().unwrap()))),
+        );
+    }
+    assert_eq!(
+        released.iter().map(|(_, n)| *n).collect::<Vec<_>>(),
+        (0..MESSAGES).collect::<Vec<_>>()
+    );
+
+    // the sender spread the messages out over more than twice the time they were handed over in
+    let sent: Vec<_> = harness
+        .transmissions()
+        .iter()
+        .filter(|t| matches!(data_seq(t), Some((_, false))))
+        .map(|t| t.at)
+        .collect();
+    let origin_span = SPACING * (MESSAGES - 1);
+    let sent_span = *sent.last().unwrap() - sent[0];
+    assert!(sent_span > origin_span * 2, "{sent_span:?}");
+
+    // but they were stamped when they were handed over, so they play out as they came in, to the
+    // millisecond the harness steps by
+    for pair in released.windows(2) {
+        let interval = pair[1].0 - pair[0].0;
+        assert!(
+            interval >= SPACING - Duration::from_millis(1)
+                && interval <= SPACING + Duration::from_millis(1),
+            "{interval:?} between {} and {}",
+            pair[0].1,
+            pair[1].1
+        );
+    }
+    let released_span = released.last().unwrap().0 - released[0].0;
+    assert!(
+        released_span >= origin_span - Duration::from_millis(1)
+            && released_span <= origin_span + Duration::from_millis(1),
+        "{released_span:?}"
+    );
+}
+
+#[test]
+fn flow_window_full() {
+    let _ = pretty_env_logger::try_init();
+
+    const MESSAGES: usize = 200;
+
+    // returns the messages the sender took, and its statistics once they were sent
+    let run = |policy: FlowWindowFull| {
+        let mut harness = TestHarness::with_settings(|settings| {
+            settings.send_tsbpd_latency = Duration::from_millis(200);
+            settings.recv_tsbpd_latency = Duration::from_millis(200);
+            settings.idle_restart_threshold = None;
+            settings.send_buffer_size = PacketCount(8192);
+            settings.max_flow_size = PacketCount(32);
+            settings.flow_window_full = policy;
+        });
+        harness.set_delay(DELAY);
+        // no ACKs get back, so the window runs out after the first packets
+        harness.drop_where(|transmission| transmission.from == Side::Receiver);
+
+        let mut taken = 0;
+        for n in 0..MESSAGES {
+            if harness.sender.is_accepting_data() {
+                harness.send(format!("{n}"));
+                taken += 1;
+            }
+            harness.step(Duration::from_millis(1));
+        }
+        let now = harness.now();
+        harness.sender.update_statistics(now);
+        let statistics = harness.sender.statistics().clone();
+
+        // once the ACKs get through again, the window opens for what's left
+        harness.drop_where(|_| false);
+        harness.step(Duration::from_millis(500));
+        let received = harness.take_received();
+        (taken, statistics, received)
+    };
+
+    // waiting, everything is taken and sent once the window opens
+    let (taken, statistics, received) = run(FlowWindowFull::Wait);
+    assert_eq!(taken, MESSAGES);
+    assert_eq!(statistics.tx_dropped_data, 0);
+    assert_eq!(received.len(), MESSAGES);
+
+    // blocking, no more is taken once the window is exhausted
+    let (taken, statistics, received) = run(FlowWindowFull::Block);
+    assert!(taken < MESSAGES / 2, "{taken}");
+    assert!(taken > 32, "{taken}");
+    assert_eq!(statistics.tx_dropped_data, 0);
+    assert_eq!(received.len(), taken);
+
+    // dropping, everything is taken but only the newest of what waited for the window is sent
+    let (taken, statistics, received) = run(FlowWindowFull::DropOldest);
+    assert_eq!(taken, MESSAGES);
+    assert!(
+        statistics.tx_flow_window_dropped_data > MESSAGES as u64 / 2,
+        "{}",
+        statistics.tx_flow_window_dropped_data
+    );
+    assert_eq!(statistics.tx_buffer_full_dropped_data, 0);
+    assert!(statistics.tx_dropped_data >= statistics.tx_flow_window_dropped_data);
+    assert_eq!(
+        received.len() as u64 + statistics.tx_flow_window_dropped_data,
+        MESSAGES as u64
+    );
+    let last = format!("{}", MESSAGES - 1);
+    assert_eq!(received.last().unwrap().1, last.as_bytes());
+}
+
+#[test]
+fn drop_margin_under_bursty_delay() {
+    let _ = pretty_env_logger::try_init();
+
+    const LATENCY: Duration = Duration::from_millis(200);
+
+    // returns the packets the receiver dropped, and how late it released the data sent after the
+    // burst at most
+    let run = |margin: Percent| {
+        let mut harness = TestHarness::with_settings(|settings| {
+            settings.send_tsbpd_latency = LATENCY;
+            settings.recv_tsbpd_latency = LATENCY;
+            settings.too_late_drop_margin = margin;
+            settings.send_buffer_size = PacketCount(8192);
+            settings.idle_restart_threshold = None;
+        });
+        harness.set_delay(DELAY);
+        // the retransmissions don't make it either, the packets held up only get through by
+        // being waited for
+        harness.drop_where(|transmission| matches!(data_seq(transmission), Some((_, true))));
+
+        let mut latest = Duration::ZERO;
+        for n in 0..1000 {
+            // the data sent for 100ms takes longer than the latency to get across
+            match n {
+                300 => harness.set_delay(LATENCY + DELAY * 3),
+                400 => harness.set_delay(DELAY),
+                _ => {}
+            }
+            if n % 5 == 0 {
+                harness.send(format!("{n}"));
+            }
+            harness.step(Duration::from_millis(1));
+            for (origin, data) in harness.take_received() {
+                let sent: u32 = std::str::from_utf8(&data).unwrap().parse().unwrap();
+                if sent >= 400 {
+                    latest = latest.max(harness.now() - origin);
+                }
+            }
+        }
+        harness.step(LATENCY * 3);
+
+        let now = harness.now();
+        harness.receiver.update_statistics(now);
+        (harness.receiver.statistics().rx_dropped_data, latest)
+    };
+
+    // without a margin, the data after the burst is released on time and the last of the delayed
+    // packets are dropped in its favour
+    let (dropped, latest) = run(Percent(0));
+    assert!(dropped > 0);
+    assert_eq!(latest, LATENCY);
+
+    // with a margin it's held back for them instead, released later than the latency
+    let (dropped, latest) = run(Percent(50));
+    assert_eq!(dropped, 0);
+    assert!(latest > LATENCY, "{latest:?}");
+    assert!(latest <= LATENCY * 3 / 2, "{latest:?}");
+}
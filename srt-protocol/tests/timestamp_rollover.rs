@@ -10,7 +10,7 @@ use log::trace;
 
 use srt_protocol::{
     connection::{Connection, ConnectionSettings, DuplexConnection, Input},
-    options::{PacketCount, PacketSize},
+    options::{PacketCount, PacketSize, Percent, SrtVersion},
     packet::*,
     protocol::handshake::Handshake,
 };
@@ -37,17 +37,37 @@ fn timestamp_rollover() {
         rtt: Duration::default(),
         init_seq_num: init_seqnum,
         max_packet_size: PacketSize(1316),
+        max_segment_size: PacketSize(1500),
         max_flow_size: PacketCount(8192),
+        idle_restart_threshold: None,
+        max_retransmits: None,
         send_tsbpd_latency: Duration::from_millis(20),
         recv_tsbpd_latency: Duration::from_millis(20),
         cipher: None,
         stream_id: None,
         bandwidth: Default::default(),
+        retransmit_bandwidth: Default::default(),
+        send_buffer_full: Default::default(),
+        flow_window_full: Default::default(),
+        input_overflow: Default::default(),
         recv_buffer_size: PacketCount(8192),
+        recv_buffer_cap: None,
         send_buffer_size: PacketCount(8192),
         statistics_interval: Duration::from_secs(1),
+        loss_rate_window: Duration::from_secs(5),
+        initial_rtt: None,
+        too_late_drop_margin: Percent(0),
         peer_idle_timeout: Duration::from_secs(5),
         too_late_packet_drop: true,
+        delivery_mode: Default::default(),
+        nak_period: Default::default(),
+        recv_granularity: Default::default(),
+        peer_version: SrtVersion::CURRENT,
+        features: SrtShakeFlags::SUPPORTED,
+        packet_filter: None,
+        negotiation_adjustments: Vec::new(),
+        session_token: None,
+        resumable_sessions: None,
     };
 
     let s2 = ConnectionSettings {
@@ -58,18 +78,38 @@ fn timestamp_rollover() {
         rtt: Duration::default(),
         init_seq_num: init_seqnum,
         max_packet_size: PacketSize(1316),
+        max_segment_size: PacketSize(1500),
         max_flow_size: PacketCount(8192),
+        idle_restart_threshold: None,
+        max_retransmits: None,
 
         send_tsbpd_latency: Duration::from_millis(20),
         recv_tsbpd_latency: Duration::from_millis(20),
         cipher: None,
         stream_id: None,
         bandwidth: Default::default(),
+        retransmit_bandwidth: Default::default(),
+        send_buffer_full: Default::default(),
+        flow_window_full: Default::default(),
+        input_overflow: Default::default(),
         recv_buffer_size: PacketCount(8192),
+        recv_buffer_cap: None,
         send_buffer_size: PacketCount(8192),
         statistics_interval: Duration::from_secs(1),
+        loss_rate_window: Duration::from_secs(5),
+        initial_rtt: None,
+        too_late_drop_margin: Percent(0),
         peer_idle_timeout: Duration::from_secs(5),
         too_late_packet_drop: true,
+        delivery_mode: Default::default(),
+        nak_period: Default::default(),
+        recv_granularity: Default::default(),
+        peer_version: SrtVersion::CURRENT,
+        features: SrtShakeFlags::SUPPORTED,
+        packet_filter: None,
+        negotiation_adjustments: Vec::new(),
+        session_token: None,
+        resumable_sessions: None,
     };
 
     const PACKET_RATE: u32 = 10; // 10 packet/s
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use rand::{prelude::StdRng, SeedableRng};
+use rand_distr::Bernoulli;
+use srt_protocol::{connection::Input, options::*, packet::*};
+
+pub mod simulator;
+
+use simulator::*;
+
+const PACKETS: u32 = 100;
+const PACKET_SPACING: Duration = Duration::from_millis(10);
+const LATENCY: Duration = Duration::from_millis(100);
+// never makes it through, not even retransmitted
+const LOST: u32 = 50;
+const TOLERANCE: Duration = Duration::from_millis(1);
+
+#[test]
+fn drop_at_the_deadline() {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let mut simulation = RandomLossSimulation {
+        rng: StdRng::seed_from_u64(0),
+        delay_dist: DelayDistribution::Constant(Duration::from_millis(5)),
+        drop_dist: LossModel::Random(Bernoulli::new(0.).unwrap()),
+    };
+    let (mut network, mut sender, mut receiver) =
+        simulation.build(start, LATENCY, PacketCount(8192));
+    let init_seq_num = sender.settings().init_seq_num;
+    for n in 0..PACKETS {
+        let t = start + PACKET_SPACING * (n + 1);
+        let payload = Bytes::from(n.to_be_bytes().to_vec());
+        network
+            .sender
+            .schedule_input(t, Input::Data(Some((t, payload))));
+    }
+    network
+        .sender
+        .schedule_input(start + PACKET_SPACING * (PACKETS + 1), Input::Data(None));
+
+    let mut received = Vec::new();
+    run_until_closed(start, &mut sender, &mut receiver, &mut network, |step| {
+        let now = step.now;
+        while let Some((time, payload)) = step.receiver.next_data(now) {
+            let n = u32::from_be_bytes(payload[..].try_into().unwrap());
+            received.push((n, time, now));
+        }
+        for packet in step.next_packets() {
+            if let Packet::Data(data) = &packet.0 {
+                if data.seq_number - init_seq_num == LOST {
+                    continue;
+                }
+            }
+            step.network.send_lossy(&mut simulation, now, packet);
+        }
+    });
+
+    // the missing packet is skipped when the one after it is due, which is delivered right away,
+    // and so is everything after it
+    let expected: Vec<_> = (0..PACKETS).filter(|n| *n != LOST).collect();
+    let delivered: Vec<_> = received.iter().map(|(n, _, _)| *n).collect();
+    assert_eq!(delivered, expected);
+    for (n, time, released) in received {
+        let deadline = time + LATENCY;
+        assert!(
+            released >= deadline && released - deadline < TOLERANCE,
+            "{n} released {:?} after its deadline",
+            released.saturating_duration_since(deadline)
+        );
+    }
+}
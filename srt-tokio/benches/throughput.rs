@@ -0,0 +1,55 @@
+//! The goodput of a connection between two sockets in one process, over an in-memory link so the
+//! network doesn't come into it
+//!
+//! `cargo bench -p srt-tokio --bench throughput` times transfers offered at a few rates and
+//! compares them to the last run, `cargo test --benches` only runs a short one to check it still
+//! works.
+
+#[path = "../tests/lossy_conn/mod.rs"]
+mod lossy_conn;
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+// the usual payload of live streams, 7 MPEG-TS packets
+const PAYLOAD_SIZE: usize = 1316;
+
+// a fifth of a second of data at each rate
+const DURATION: f64 = 0.2;
+
+fn throughput(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("goodput");
+    // every transfer warms the connection up first, so there's only time for a few
+    group.sample_size(10);
+    for rate_mbps in [50, 100, 200, 400, 800] {
+        let rate = rate_mbps * 1_000_000 / 8;
+        let count = (rate as f64 * DURATION / PAYLOAD_SIZE as f64) as usize;
+        group.throughput(Throughput::Bytes((count * PAYLOAD_SIZE) as u64));
+        // what doesn't arrive in time is dropped as too late, so the time measured is until the
+        // last payload that did, scaled up to all of them
+        group.bench_with_input(
+            BenchmarkId::new("offered_mbps", rate_mbps),
+            &rate,
+            |b, &rate| {
+                b.to_async(&runtime).iter_custom(|iters| async move {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let (bytes, took) = lossy_conn::transfer(count, PAYLOAD_SIZE, rate)
+                            .await
+                            .unwrap();
+                        assert!(bytes > 0, "nothing arrived");
+                        elapsed += took.mul_f64((count * PAYLOAD_SIZE) as f64 / bytes as f64);
+                    }
+                    elapsed
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
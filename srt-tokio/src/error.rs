@@ -0,0 +1,114 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    sync::Arc,
+};
+
+use srt_protocol::{
+    connection::CloseReason,
+    packet::{CoreRejectReason, RejectReason},
+    protocol::pending_connection::ConnectionReject,
+};
+
+/// Why connecting failed, or why a connection ended, in a form that can be matched on.
+///
+/// The API returns [`io::Error`]s, which convert into this with `SrtError::from`. For a connection
+/// that was up, [`SrtSocket::last_error`](crate::SrtSocket::last_error) tells why it ended.
+#[derive(Debug, Clone)]
+pub enum SrtError {
+    /// The peer didn't answer the handshake within the connect timeout, or nothing was received
+    /// from it within the peer idle timeout
+    Timeout,
+    /// The connection was rejected, by the peer or by this side
+    Rejected(ConnectionReject),
+    /// The keys couldn't be unwrapped, the two sides don't have the same passphrase
+    Decryption(ConnectionReject),
+    /// The peer shut the connection down
+    PeerReset,
+    /// A packet was retransmitted as many times as allowed without getting through, see
+    /// [`Sender::max_retransmits`](srt_protocol::options::Sender::max_retransmits)
+    RetransmitLimit,
+    /// Sending or receiving on the network failed
+    Io(Arc<io::Error>),
+}
+
+impl SrtError {
+    fn kind(&self) -> io::ErrorKind {
+        use SrtError::*;
+        match self {
+            Timeout => io::ErrorKind::TimedOut,
+            Rejected(_) | Decryption(_) => io::ErrorKind::ConnectionRefused,
+            PeerReset => io::ErrorKind::ConnectionReset,
+            RetransmitLimit => io::ErrorKind::ConnectionAborted,
+            Io(error) => error.kind(),
+        }
+    }
+}
+
+impl From<CloseReason> for SrtError {
+    fn from(reason: CloseReason) -> Self {
+        match reason {
+            CloseReason::PeerShutdown => SrtError::PeerReset,
+            CloseReason::PeerIdle => SrtError::Timeout,
+            CloseReason::RetransmitLimit => SrtError::RetransmitLimit,
+        }
+    }
+}
+
+impl From<ConnectionReject> for SrtError {
+    fn from(reject: ConnectionReject) -> Self {
+        match reject.reason() {
+            RejectReason::Core(CoreRejectReason::BadSecret) => SrtError::Decryption(reject),
+            _ => SrtError::Rejected(reject),
+        }
+    }
+}
+
+impl From<io::Error> for SrtError {
+    fn from(error: io::Error) -> Self {
+        let inner = error.get_ref();
+        if let Some(error) = inner.and_then(|e| e.downcast_ref::<SrtError>()) {
+            error.clone()
+        } else if let Some(reject) = inner.and_then(|e| e.downcast_ref::<ConnectionReject>()) {
+            (*reject).into()
+        } else if error.kind() == io::ErrorKind::TimedOut {
+            // e.g. the handshake retries running out
+            SrtError::Timeout
+        } else {
+            SrtError::Io(Arc::new(error))
+        }
+    }
+}
+
+impl From<SrtError> for io::Error {
+    fn from(error: SrtError) -> Self {
+        io::Error::new(error.kind(), error)
+    }
+}
+
+impl Display for SrtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use SrtError::*;
+        match self {
+            Timeout => write!(f, "the peer didn't respond in time"),
+            Rejected(reject) => write!(f, "{reject}"),
+            Decryption(reject) => write!(f, "decryption failed: {reject}"),
+            PeerReset => write!(f, "the peer shut the connection down"),
+            RetransmitLimit => write!(
+                f,
+                "a packet didn't get through within the retransmissions allowed"
+            ),
+            Io(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for SrtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SrtError::Io(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -47,16 +47,28 @@
 //! ```
 //!
 
+mod error;
 mod listener;
+mod multiplex;
 mod net;
+mod relay;
 mod socket;
+mod tap;
 mod watch;
 
+pub use multiplex::{multiplex, LogicalStream};
 pub use net::bind_socket;
+pub use relay::relay;
 pub use srt_protocol::access;
 pub use srt_protocol::options;
+pub use srt_protocol::{compatible_features, protocol_version};
 
 pub use crate::{
+    error::SrtError,
     listener::{ConnectionRequest, ListenerStatistics, SrtIncoming, SrtListener},
-    socket::{SocketStatistics, SrtSocket, SrtSocketBuilder},
+    socket::{
+        ConnectionEvent, CryptoState, MessageInfo, PolledSrtSocket, Priority, SocketStatistics,
+        SrtSocket, SrtSocketBuilder,
+    },
+    tap::RawTap,
 };
@@ -2,12 +2,17 @@ use std::{convert::TryInto, io, time::Duration};
 
 use tokio::net::UdpSocket;
 
-use crate::options::*;
+use crate::{access::*, net::bind_socket, options::*};
 
-use super::{SrtIncoming, SrtListener};
+use super::{AcceptCallback, ConnectionRequest, SrtIncoming, SrtListener};
 
 #[derive(Default)]
-pub struct SrtListenerBuilder(SocketOptions, Option<UdpSocket>);
+pub struct SrtListenerBuilder(
+    SocketOptions,
+    Option<UdpSocket>,
+    Option<usize>,
+    Option<AcceptCallback>,
+);
 
 /// Struct to build a multiplexed listener.
 ///
@@ -54,6 +59,35 @@ impl SrtListenerBuilder {
         self
     }
 
+    /// Accept keys wrapped with this passphrase too, to roll over to a new passphrase without
+    /// turning away peers that still use the old one, see [`Encryption::alternate_passphrases`]
+    ///
+    /// # Panics:
+    /// * the passphrase is not 10 to 79 characters long
+    pub fn alternate_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.0
+            .encryption
+            .alternate_passphrases
+            .push(passphrase.into().try_into().unwrap());
+        self
+    }
+
+    /// Prove this identity to peers and only connect to the ones trusted by the credentials, see
+    /// [`Encryption::credentials`]
+    #[cfg(feature = "authentication")]
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.0.encryption.credentials = Some(credentials);
+        self
+    }
+
+    /// Reject handshakes that are subtly malformed rather than warn about them, see
+    /// [`Connect::strict_handshake`]
+    #[cfg(feature = "strict-handshake")]
+    pub fn strict_handshake(mut self, strict_handshake: bool) -> Self {
+        self.0.connect.strict_handshake = strict_handshake;
+        self
+    }
+
     /// the minimum latency to receive at
     pub fn receive_latency(mut self, latency: Duration) -> Self {
         self.0.receiver.latency = latency;
@@ -71,11 +105,65 @@ impl SrtListenerBuilder {
         self
     }
 
+    /// Allows binding to an address that is in use, see [`Connect::reuse_address`]
+    pub fn reuse_address(mut self, reuse: bool) -> Self {
+        self.0.connect.reuse_address = reuse;
+        self
+    }
+
+    /// Allows other listeners that allow it too to bind to the same port, so they share the
+    /// incoming connections, see [`Connect::reuse_port`]. Binding fails on platforms other than
+    /// Unix.
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.0.connect.reuse_port = reuse;
+        self
+    }
+
+    /// Reads the UDP socket on a task of its own, in batches, to keep up with high packet rates
+    /// over all the connections, see [`UdpReadLoop`]
+    pub fn udp_read_loop(mut self, read_loop: UdpReadLoop) -> Self {
+        self.0.connect.udp_read_loop = Some(read_loop);
+        self
+    }
+
     pub fn socket(mut self, socket: UdpSocket) -> Self {
         self.1 = Some(socket);
         self
     }
 
+    /// Reject new connections while this many are open, see [`SrtListener::connection_count`]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.2 = Some(max_connections);
+        self
+    }
+
+    /// Only take handshakes from these networks, rejecting everyone else at their first
+    /// handshake, see [`Connect::allow_from`]
+    pub fn allow_from(mut self, allowed: Vec<IpNet>) -> Self {
+        self.0.connect.allow_from = Some(allowed);
+        self
+    }
+
+    /// Lets callers resume a connection that dropped for this long after the listener last heard
+    /// of it, see [`Connect::resumption_window`]
+    pub fn resumption_window(mut self, window: Duration) -> Self {
+        self.0.connect.resumption_window = Some(window);
+        self
+    }
+
+    /// Decides on each connection before it's handed to [`SrtIncoming`], while its handshake
+    /// waits: a rejection is sent to the caller right away, and the parameters of an acceptance,
+    /// e.g. a latency for the stream id, are what [`ConnectionRequest::accept`] accepts it with.
+    pub fn on_accept(
+        mut self,
+        on_accept: impl FnMut(&ConnectionRequest) -> Result<AcceptParameters, RejectReason>
+            + Send
+            + 'static,
+    ) -> Self {
+        self.3 = Some(Box::new(on_accept));
+        self
+    }
+
     pub fn with<O>(mut self, options: O) -> Self
     where
         SocketOptions: OptionsOf<O>,
@@ -94,11 +182,14 @@ impl SrtListenerBuilder {
         self,
         local: impl TryInto<SocketAddress>,
     ) -> Result<(SrtListener, SrtIncoming), io::Error> {
-        let options = ListenerOptions::with(local, self.0)?;
-        match self.1 {
-            None => SrtListener::bind(options).await,
-            Some(socket) => SrtListener::bind_with_socket(options, socket).await,
-        }
+        let max_connections = self.2;
+        let options = ListenerOptions::with(local, self.0)?
+            .set(|options| options.max_connections = max_connections)?;
+        let socket = match self.1 {
+            None => bind_socket(&options.socket).await?,
+            Some(socket) => socket,
+        };
+        SrtListener::bind_with(options, socket, self.3).await
     }
 }
 
@@ -126,6 +217,8 @@ mod tests {
             .encryption(0, "super secret passcode")
             .bandwidth(LiveBandwidthMode::Max(DataRate(1_000_000)))
             .socket(socket)
+            .max_connections(10)
+            .on_accept(|_| Ok(AcceptParameters::new()))
             .bind(9999)
             .await
             .unwrap();
@@ -2,10 +2,16 @@ mod builder;
 mod session;
 mod state;
 
-use std::{io, sync::Arc};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use futures::{channel::mpsc, prelude::*};
-use srt_protocol::settings::ConnInitSettings;
+use srt_protocol::{access::*, settings::ConnInitSettings};
 use tokio::{net::UdpSocket, sync::oneshot, task::JoinHandle};
 
 use crate::net::bind_socket;
@@ -16,10 +22,15 @@ pub use builder::SrtListenerBuilder;
 pub use session::ConnectionRequest;
 pub use srt_protocol::statistics::ListenerStatistics;
 
+/// Decides on a connection for the listener, see [`SrtListenerBuilder::on_accept`]
+pub type AcceptCallback =
+    Box<dyn FnMut(&ConnectionRequest) -> Result<AcceptParameters, RejectReason> + Send>;
+
 #[derive(Debug)]
 pub struct SrtListener {
     settings: ConnInitSettings,
     statistics_receiver: watch::Receiver<ListenerStatistics>,
+    connection_count: Arc<AtomicUsize>,
     close_req: Option<oneshot::Sender<()>>,
     task: JoinHandle<()>,
 }
@@ -42,21 +53,40 @@ impl SrtListener {
     pub async fn bind_with_socket(
         options: Valid<ListenerOptions>,
         socket: UdpSocket,
+    ) -> Result<(Self, SrtIncoming), io::Error> {
+        Self::bind_with(options, socket, None).await
+    }
+
+    async fn bind_with(
+        options: Valid<ListenerOptions>,
+        socket: UdpSocket,
+        on_accept: Option<AcceptCallback>,
     ) -> Result<(Self, SrtIncoming), io::Error> {
         use state::SrtListenerState;
-        let socket_options = options.into_value().socket;
+        let options = options.into_value();
+        let socket_options = options.socket;
         let local_address = socket.local_addr()?;
         let socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024);
+        #[cfg(feature = "strict-handshake")]
+        let socket = socket.with_strict_handshake(socket_options.connect.strict_handshake);
+        let socket = match socket_options.connect.udp_read_loop {
+            Some(read_loop) => socket.with_read_loop(read_loop),
+            None => socket,
+        };
         let settings = ConnInitSettings::from(socket_options);
         let (close_req, close_resp) = oneshot::channel();
         let (request_sender, request_receiver) = mpsc::channel(100);
         let (statistics_sender, statistics_receiver) = watch::channel();
+        let connection_count = Arc::new(AtomicUsize::new(0));
         let state = SrtListenerState::new(
             socket,
             local_address,
             settings.clone(),
+            options.max_connections,
             request_sender,
             statistics_sender,
+            connection_count.clone(),
+            on_accept,
             close_resp,
         );
         let task = tokio::spawn(async move {
@@ -66,6 +96,7 @@ impl SrtListener {
             Self {
                 settings,
                 statistics_receiver,
+                connection_count,
                 close_req: Some(close_req),
                 task,
             },
@@ -81,6 +112,11 @@ impl SrtListener {
         &mut self.statistics_receiver
     }
 
+    /// The number of connections currently open through this listener
+    pub fn connection_count(&self) -> usize {
+        self.connection_count.load(Ordering::Relaxed)
+    }
+
     pub async fn close(&mut self) {
         let _ = self.close_req.take().unwrap().send(());
         (&mut self.task).await.unwrap();
@@ -108,13 +144,14 @@ mod tests {
     use futures::{channel::oneshot, future::join_all, prelude::*};
     use log::{debug, info};
 
-    use crate::{access::*, SrtSocket};
+    use crate::SrtSocket;
 
     use super::*;
 
     #[tokio::test]
     async fn accept_reject() -> Result<()> {
         #[derive(Debug)]
+        #[allow(clippy::large_enum_variant)]
         enum Select {
             Connection(Option<ConnectionRequest>),
             Statistics(Option<ListenerStatistics>),
@@ -200,6 +237,7 @@ mod tests {
     #[tokio::test]
     async fn accept_reject_encryption() -> Result<()> {
         #[derive(Debug)]
+        #[allow(clippy::large_enum_variant)]
         enum Select {
             Connection(Option<ConnectionRequest>),
             Statistics(Option<ListenerStatistics>),
@@ -16,6 +16,7 @@ use tokio::task::JoinHandle;
 use crate::{
     net::PacketSocket,
     socket::factory::{self, SrtSocketFactory, SrtSocketTaskFactory},
+    tap::RawTap,
     SrtSocket,
 };
 
@@ -23,6 +24,7 @@ use crate::{
 pub struct ConnectionRequest {
     response_sender: ResponseSender,
     request: AccessControlRequest,
+    parameters: AcceptParameters,
     settings_receiver: oneshot::Receiver<(ConnectionSettings, JoinHandle<()>)>,
     socket_factory: SrtSocketFactory,
 }
@@ -44,12 +46,17 @@ impl ConnectionRequest {
         self.request.key_size
     }
 
+    /// Accepts the connection with the parameters the listener's `on_accept` callback returned
+    /// for it, if it has one, with `key_settings` in place of theirs if set
     pub async fn accept(
-        self,
+        mut self,
         key_settings: Option<KeySettings>,
     ) -> Result<SrtSocket, std::io::Error> {
+        if let Some(key_settings) = key_settings {
+            self.parameters.set_validated_key_settings(key_settings);
+        }
         self.response_sender
-            .send(AccessControlResponse::Accepted(key_settings))
+            .send(AccessControlResponse::Accepted(self.parameters))
             .await?;
 
         let (settings, jh) = self
@@ -60,6 +67,10 @@ impl ConnectionRequest {
         Ok(self.socket_factory.create_socket(settings, jh))
     }
 
+    pub(crate) fn set_parameters(&mut self, parameters: AcceptParameters) {
+        self.parameters = parameters;
+    }
+
     pub async fn reject(self, reason: RejectReason) -> Result<(), std::io::Error> {
         self.response_sender
             .send(AccessControlResponse::Rejected(reason))
@@ -78,8 +89,12 @@ impl PendingConnection {
         session_id: SessionId,
         request: AccessControlRequest,
         response_sender: mpsc::Sender<(SessionId, AccessControlResponse)>,
+        send_queue_depth: usize,
     ) -> (PendingConnection, ConnectionRequest) {
-        let (socket_factory, task_factory) = factory::split_new();
+        let (socket_factory, task_factory) =
+            factory::split_new(RawTap::default(), send_queue_depth);
+        #[cfg(feature = "tracing")]
+        task_factory.span("listener");
 
         let (settings_sender, settings_receiver) = oneshot::channel();
         let response_sender = ResponseSender(session_id, response_sender);
@@ -91,6 +106,7 @@ impl PendingConnection {
 
         let request = ConnectionRequest {
             request,
+            parameters: AcceptParameters::new(),
             response_sender,
             settings_receiver,
             socket_factory,
@@ -127,6 +143,11 @@ impl OpenConnection {
         }
     }
 
+    /// Whether the socket was closed or dropped, so it no longer takes packets
+    pub fn is_closed(&self) -> bool {
+        self.packet_sender.is_closed()
+    }
+
     pub async fn close(&mut self) -> Result<(), ()> {
         self.packet_sender.close().await.ok().ok_or(())
     }
@@ -1,6 +1,10 @@
 use std::{
     collections::HashMap,
     net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
@@ -10,7 +14,7 @@ use tokio::sync::oneshot;
 
 use crate::{net::PacketSocket, watch};
 
-use super::session::*;
+use super::{session::*, AcceptCallback};
 
 pub struct SrtListenerState {
     local_address: SocketAddr,
@@ -20,21 +24,30 @@ pub struct SrtListenerState {
     response_sender: mpsc::Sender<(SessionId, AccessControlResponse)>,
     response_receiver: mpsc::Receiver<(SessionId, AccessControlResponse)>,
     statistics_sender: watch::Sender<ListenerStatistics>,
+    connection_count: Arc<AtomicUsize>,
+    on_accept: Option<AcceptCallback>,
+    send_queue_depth: usize,
     pending_connections: HashMap<SessionId, PendingConnection>,
     open_connections: HashMap<SessionId, OpenConnection>,
     close_recvr: Fuse<oneshot::Receiver<()>>,
 }
 
 impl SrtListenerState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         socket: PacketSocket,
         local_address: SocketAddr,
         settings: ConnInitSettings,
+        max_connections: Option<usize>,
         request_sender: mpsc::Sender<ConnectionRequest>,
         statistics_sender: watch::Sender<ListenerStatistics>,
+        connection_count: Arc<AtomicUsize>,
+        on_accept: Option<AcceptCallback>,
         close_recvr: oneshot::Receiver<()>,
     ) -> Self {
-        let listener = MultiplexListener::new(Instant::now(), local_address, settings);
+        let send_queue_depth = settings.send_queue_depth;
+        let mut listener = MultiplexListener::new(Instant::now(), local_address, settings);
+        listener.set_max_connections(max_connections);
         let (response_sender, response_receiver) = mpsc::channel(100);
         Self {
             local_address,
@@ -44,6 +57,9 @@ impl SrtListenerState {
             response_sender,
             response_receiver,
             statistics_sender,
+            connection_count,
+            on_accept,
+            send_queue_depth,
             pending_connections: Default::default(),
             open_connections: Default::default(),
             close_recvr: close_recvr.fuse(),
@@ -95,12 +111,19 @@ impl SrtListenerState {
                 UpdateStatistics(statistics) => {
                     next.input_from(self.statistics_sender.send(statistics.clone()))
                 }
-                WaitForInput => select! {
-                    packet = self.socket.receive().fuse() => Input::Packet(packet),
-                    response = self.response_receiver.next() => Input::AccessResponse(response),
-                    _ = timer_interval.tick().fuse() => Input::Timer,
-                    _ = &mut self.close_recvr => break,
-                },
+                WaitForInput => {
+                    self.connection_count
+                        .store(self.listener.connection_count(), Ordering::Relaxed);
+                    match self.closed_connection() {
+                        Some(session_id) => Input::ConnectionClosed(session_id),
+                        None => select! {
+                            packet = self.socket.receive().fuse() => Input::Packet(packet),
+                            response = self.response_receiver.next() => Input::AccessResponse(response),
+                            _ = timer_interval.tick().fuse() => Input::Timer,
+                            _ = &mut self.close_recvr => break,
+                        },
+                    }
+                }
                 Close => break,
             }
         }
@@ -110,16 +133,36 @@ impl SrtListenerState {
         }
     }
 
+    // the timer tick makes sure this is checked regularly, even if no packets arrive
+    fn closed_connection(&self) -> Option<SessionId> {
+        self.open_connections
+            .iter()
+            .find(|(_, connection)| connection.is_closed())
+            .map(|(session_id, _)| *session_id)
+    }
+
     async fn request_access(
         &mut self,
         session_id: SessionId,
         request: AccessControlRequest,
     ) -> Result<(), ()> {
-        let request_sender = &mut self.request_sender;
         let response_sender = self.response_sender.clone();
-        let (pending, request) =
-            PendingConnection::start_approval(session_id, request, response_sender);
-        request_sender.send(request).await.ok().ok_or(())?;
+        let (pending, mut request) = PendingConnection::start_approval(
+            session_id,
+            request,
+            response_sender,
+            self.send_queue_depth,
+        );
+        if let Some(on_accept) = &mut self.on_accept {
+            match on_accept(&request) {
+                Ok(parameters) => request.set_parameters(parameters),
+                Err(reason) => {
+                    let _ = self.pending_connections.insert(session_id, pending);
+                    return request.reject(reason).await.ok().ok_or(());
+                }
+            }
+        }
+        self.request_sender.send(request).await.ok().ok_or(())?;
         let _ = self.pending_connections.insert(session_id, pending);
         Ok(())
     }
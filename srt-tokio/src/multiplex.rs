@@ -0,0 +1,183 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{channel::mpsc, prelude::*, ready, select};
+use log::warn;
+use srt_protocol::packet::SrtShakeFlags;
+
+use crate::SrtSocket;
+
+// how many messages each logical stream holds until it's read, as many as the socket itself holds
+const STREAM_QUEUE_DEPTH: usize = 128;
+
+/// Splits the connection into `count` logical streams, each a sink and a stream of its own that
+/// keeps the order of its data. Every message is tagged with the index of its stream in the first
+/// byte of the payload, so a message can be a byte shorter than on the socket itself.
+///
+/// Both sides have to offer it with `Session::multiplex`, or it fails as unsupported: a peer that
+/// doesn't know about it would take the tags for data. The connection is closed once every
+/// stream is closed or dropped, and the streams end when the connection does.
+///
+/// Each stream holds a limited number of messages until it's read. Once one of them is full,
+/// nothing more is read from the socket, so no stream gets its data until it has room again, and
+/// nothing is sent on any stream meanwhile either. A stream that isn't read holds up the others,
+/// drop it to discard what's received on it instead.
+pub fn multiplex(socket: SrtSocket, count: u8) -> Result<Vec<LogicalStream>, io::Error> {
+    if !socket
+        .settings()
+        .features
+        .contains(SrtShakeFlags::MULTIPLEX)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the peer didn't agree to multiplex the connection",
+        ));
+    }
+
+    let max_message_size = socket.settings().max_message_size().0 - 1;
+    let (outgoing_sender, outgoing) = mpsc::channel(0);
+    let (incoming, streams): (Vec<_>, Vec<_>) = (0..count)
+        .map(|index| {
+            let (incoming_sender, incoming) = mpsc::channel(STREAM_QUEUE_DEPTH);
+            let stream = LogicalStream {
+                index,
+                max_message_size,
+                outgoing: Some(outgoing_sender.clone()),
+                incoming,
+            };
+            (incoming_sender, stream)
+        })
+        .unzip();
+    tokio::spawn(run_multiplexer(socket, outgoing, incoming));
+    Ok(streams)
+}
+
+/// One of the logical streams of a multiplexed connection
+#[derive(Debug)]
+pub struct LogicalStream {
+    index: u8,
+    max_message_size: u64,
+    outgoing: Option<mpsc::Sender<(u8, Instant, Bytes)>>,
+    incoming: mpsc::Receiver<(Instant, Bytes)>,
+}
+
+impl LogicalStream {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+}
+
+impl Stream for LogicalStream {
+    type Item = Result<(Instant, Bytes), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Poll::Ready(ready!(Pin::new(&mut self.incoming).poll_next(cx)).map(Ok))
+    }
+}
+
+impl Sink<(Instant, Bytes)> for LogicalStream {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        let outgoing = self.outgoing.as_mut().ok_or_else(closed)?;
+        Poll::Ready(ready!(outgoing.poll_ready(cx)).map_err(not_connected))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, (time, data): (Instant, Bytes)) -> io::Result<()> {
+        if data.len() as u64 > self.max_message_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "message of {} bytes exceeds the maximum message size of {} bytes on a logical stream",
+                    data.len(),
+                    self.max_message_size
+                ),
+            ));
+        }
+        let index = self.index;
+        let outgoing = self.outgoing.as_mut().ok_or_else(closed)?;
+        outgoing
+            .start_send((index, time, data))
+            .map_err(not_connected)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        match self.outgoing.as_mut() {
+            Some(outgoing) => {
+                Poll::Ready(ready!(outgoing.poll_flush_unpin(cx)).map_err(not_connected))
+            }
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        // only this stream's sender goes, the others keep the connection open
+        self.outgoing = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn closed() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "the logical stream is closed")
+}
+
+fn not_connected(error: mpsc::SendError) -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, error)
+}
+
+async fn run_multiplexer(
+    mut socket: SrtSocket,
+    outgoing: mpsc::Receiver<(u8, Instant, Bytes)>,
+    mut incoming: Vec<mpsc::Sender<(Instant, Bytes)>>,
+) {
+    let mut outgoing = outgoing.fuse();
+    loop {
+        select! {
+            received = socket.next().fuse() => {
+                let Some(Ok((time, mut data))) = received else {
+                    break;
+                };
+                if data.is_empty() {
+                    warn!("Multiplexed data without a logical stream index, dropping it");
+                    continue;
+                }
+                let index = data.split_to(1)[0];
+                let open = incoming.len();
+                match incoming.get_mut(usize::from(index)) {
+                    // waits for room in a stream that's full, a stream that was dropped doesn't
+                    // take in more
+                    Some(stream) => {
+                        let _ = stream.send((time, data)).await;
+                    }
+                    None => warn!(
+                        "Data for logical stream {index}, but only {open} are open, dropping it"
+                    ),
+                }
+            }
+            sent = outgoing.next() => match sent {
+                Some((index, time, data)) => {
+                    let mut tagged = BytesMut::with_capacity(data.len() + 1);
+                    tagged.put_u8(index);
+                    tagged.put(data);
+                    if let Err(e) = socket.send((time, tagged.freeze())).await {
+                        warn!("Error while sending multiplexed data: {e}");
+                        break;
+                    }
+                }
+                // every stream is closed, what's received until the peer closes still goes out
+                None => {
+                    if let Err(e) = socket.close().await {
+                        warn!("Error while closing the multiplexed connection: {e}");
+                        break;
+                    }
+                }
+            },
+        }
+    }
+}
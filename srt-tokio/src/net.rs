@@ -4,17 +4,25 @@ use std::{
     fmt::{Debug, Display, Formatter},
     io::{self, Cursor, ErrorKind},
     net::SocketAddr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
 };
 
 use bytes::BytesMut;
 use futures::channel::mpsc::Receiver;
-use futures::{channel::mpsc, prelude::*};
+use futures::{
+    channel::{mpsc, oneshot},
+    prelude::*,
+    ready, select,
+};
 use socket2::{Domain, Protocol, Socket, Type};
-use srt_protocol::packet::{Packet, ReceivePacketResult};
+use srt_protocol::packet::{Packet, ReceivePacketResult, SeqNumber};
 use tokio::net::{lookup_host, UdpSocket};
 
-use crate::options::*;
+use crate::{options::*, tap::RawTap};
 
 pub async fn bind_socket(options: &SocketOptions) -> Result<UdpSocket, io::Error> {
     let socket = Socket::new(
@@ -33,11 +41,91 @@ pub async fn bind_socket(options: &SocketOptions) -> Result<UdpSocket, io::Error
     socket.set_nonblocking(true)?; // required for passing to tokio
     socket.set_recv_buffer_size(recv_buffer_size)?;
     socket.set_send_buffer_size(send_buffer_size)?;
+    socket.set_reuse_address(options.connect.reuse_address)?;
+    if options.connect.reuse_port {
+        reuse_port(&socket)?;
+    }
+    if let Some(device) = &options.connect.bind_device {
+        bind_device(&socket, device)?;
+    }
+    if let Some(tos) = options.connect.ip_tos {
+        set_ip_tos(&socket, tos, options.connect.local.is_ipv6()).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("setting the IP type of service to {tos:#04x} failed: {e}"),
+            )
+        })?;
+    }
     socket.bind(&options.connect.local.into())?;
 
     UdpSocket::from_std(socket.into())
 }
 
+#[cfg(target_os = "linux")]
+fn bind_device(socket: &Socket, device: &str) -> Result<(), io::Error> {
+    socket.bind_device(Some(device.as_bytes()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bind_device(_socket: &Socket, device: &str) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        format!("binding to device {device} is only supported on Linux"),
+    ))
+}
+
+#[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+fn reuse_port(socket: &Socket) -> Result<(), io::Error> {
+    socket.set_reuse_port(true)
+}
+
+#[cfg(not(all(unix, not(any(target_os = "solaris", target_os = "illumos")))))]
+fn reuse_port(_socket: &Socket) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "reusing the port is not supported on this platform",
+    ))
+}
+
+fn set_ip_tos(socket: &Socket, tos: u8, ipv6: bool) -> Result<(), io::Error> {
+    if ipv6 {
+        set_ipv6_tclass(socket, tos)
+    } else {
+        socket.set_tos(tos.into())
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn set_ipv6_tclass(socket: &Socket, tclass: u8) -> Result<(), io::Error> {
+    socket.set_tclass_v6(tclass.into())
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn set_ipv6_tclass(_socket: &Socket, _tclass: u8) -> Result<(), io::Error> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "setting the IPv6 traffic class is not supported on this platform",
+    ))
+}
+
 pub async fn lookup_remote_host(remote: &SocketAddress) -> Result<SocketAddr, io::Error> {
     use SocketHost::*;
     let mut remote_address = match &remote.host {
@@ -56,46 +144,281 @@ pub async fn lookup_remote_host(remote: &SocketAddress) -> Result<SocketAddr, io
     Ok(remote_address)
 }
 
+#[derive(Clone)]
+enum PacketTransport {
+    Udp(Arc<UdpSocket>),
+    // in-memory transport, packets are delivered to the peer as if sent from the local address
+    Channel(SocketAddr, mpsc::Sender<ReceivePacketResult>),
+    // user supplied transport, packets are forwarded to its sink by a separate task
+    Custom(mpsc::Sender<(Packet, SocketAddr)>),
+}
+
 pub struct PacketSocket {
-    socket: Arc<UdpSocket>,
+    transport: PacketTransport,
     stream: Option<mpsc::Receiver<ReceivePacketResult>>,
     buffer: BytesMut,
+    tap: RawTap,
+    read_loop: Option<ReadLoopHandle>,
+    strict_handshake: bool,
+}
+
+// the reading task stops once this is dropped along with the socket
+struct ReadLoopHandle {
+    _stop: oneshot::Sender<()>,
+    suspected_kernel_drops: Arc<AtomicU64>,
 }
 
 impl PacketSocket {
     pub fn from_socket(socket: Arc<UdpSocket>, buffer_capacity: usize) -> Self {
         Self {
-            socket,
+            transport: PacketTransport::Udp(socket),
             stream: None,
             buffer: BytesMut::with_capacity(buffer_capacity),
+            tap: RawTap::default(),
+            read_loop: None,
+            strict_handshake: false,
         }
     }
 
-    pub fn clone_channel(
-        &self,
+    /// Parses the datagrams received from the UDP socket with
+    /// [`Packet::parse_strict`], so handshakes lenient parsing only warns about fail to parse.
+    /// Packets received from a stream come parsed already.
+    #[cfg(feature = "strict-handshake")]
+    pub fn with_strict_handshake(mut self, strict_handshake: bool) -> Self {
+        self.strict_handshake = strict_handshake;
+        self
+    }
+
+    /// Copies every datagram received from the UDP socket to `tap`. The in-memory and custom
+    /// transports have no datagrams, so nothing is copied for packets received from a stream.
+    pub fn with_tap(mut self, tap: RawTap) -> Self {
+        self.tap = tap;
+        self
+    }
+
+    /// Reads the UDP socket on a task of its own, in batches, see [`UdpReadLoop`]. Other
+    /// transports are read as they are.
+    ///
+    /// # Panics:
+    /// * There is no tokio runtime
+    pub fn with_read_loop(mut self, read_loop: UdpReadLoop) -> Self {
+        let PacketTransport::Udp(socket) = &self.transport else {
+            return self;
+        };
+        let (sender, receiver) = mpsc::channel(read_loop.queue_depth);
+        let (stop, stopped) = oneshot::channel();
+        let suspected_kernel_drops = Arc::new(AtomicU64::new(0));
+        tokio::spawn(Self::read_loop(
+            socket.clone(),
+            read_loop.batch_size,
+            sender,
+            self.tap.clone(),
+            self.strict_handshake,
+            suspected_kernel_drops.clone(),
+            stopped,
+        ));
+        self.stream = Some(receiver);
+        self.read_loop = Some(ReadLoopHandle {
+            _stop: stop,
+            suspected_kernel_drops,
+        });
+        self
+    }
+
+    /// The DATA packets suspected to have been dropped by the system since last asked, see
+    /// [`SocketStatistics::rx_suspected_kernel_drops`](crate::SocketStatistics::rx_suspected_kernel_drops)
+    pub fn take_suspected_kernel_drops(&self) -> u64 {
+        self.read_loop.as_ref().map_or(0, |read_loop| {
+            read_loop.suspected_kernel_drops.swap(0, Ordering::Relaxed)
+        })
+    }
+
+    pub fn pair(
+        (local_a, local_b): (SocketAddr, SocketAddr),
         channel_buffer: usize,
-    ) -> (mpsc::Sender<ReceivePacketResult>, Self) {
-        let (packet_sender, packet_receiver) = mpsc::channel(channel_buffer);
+    ) -> (Self, Self) {
+        let (sender_a, receiver_a) = mpsc::channel(channel_buffer);
+        let (sender_b, receiver_b) = mpsc::channel(channel_buffer);
         (
-            packet_sender,
             Self {
-                socket: self.socket.clone(),
-                stream: Some(packet_receiver),
-                buffer: BytesMut::with_capacity(self.buffer.capacity()),
+                transport: PacketTransport::Channel(local_a, sender_b),
+                stream: Some(receiver_a),
+                buffer: BytesMut::new(),
+                tap: RawTap::default(),
+                read_loop: None,
+                strict_handshake: false,
+            },
+            Self {
+                transport: PacketTransport::Channel(local_b, sender_a),
+                stream: Some(receiver_b),
+                buffer: BytesMut::new(),
+                tap: RawTap::default(),
+                read_loop: None,
+                strict_handshake: false,
             },
         )
     }
 
+    /// Sends and receives packets over `transport` instead of a UDP socket, the transport is
+    /// driven by tasks of its own until either side of it closes
+    ///
+    /// # Panics:
+    /// * There is no tokio runtime
+    pub fn from_transport<T>(transport: T, channel_buffer: usize) -> Self
+    where
+        T: Stream<Item = (Packet, SocketAddr)>
+            + Sink<(Packet, SocketAddr), Error = io::Error>
+            + Send
+            + 'static,
+    {
+        let (sink, stream) = transport.split();
+        let (outgoing_sender, outgoing_receiver) = mpsc::channel(channel_buffer);
+        let (incoming_sender, incoming_receiver) = mpsc::channel(channel_buffer);
+        tokio::spawn(outgoing_receiver.map(Ok).forward(sink));
+        tokio::spawn(stream.map(|packet| Ok(Ok(packet))).forward(incoming_sender));
+        Self {
+            transport: PacketTransport::Custom(outgoing_sender),
+            stream: Some(incoming_receiver),
+            buffer: BytesMut::new(),
+            tap: RawTap::default(),
+            read_loop: None,
+            strict_handshake: false,
+        }
+    }
+
+    pub fn clone_channel(
+        &self,
+        channel_buffer: usize,
+    ) -> (mpsc::Sender<ReceivePacketResult>, Self) {
+        let (packet_sender, packet_receiver) = mpsc::channel(channel_buffer);
+        (packet_sender, self.clone_with_stream(packet_receiver))
+    }
+
+    pub fn clone_with_stream(&self, stream: mpsc::Receiver<ReceivePacketResult>) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            stream: Some(stream),
+            buffer: BytesMut::with_capacity(self.buffer.capacity()),
+            tap: self.tap.clone(),
+            read_loop: None,
+            strict_handshake: self.strict_handshake,
+        }
+    }
+
+    /// Like [`clone_with_stream`](Self::clone_with_stream), for a task that forwards what this
+    /// socket receives to `stream`. The receiver returned fires once the clone is dropped, for the
+    /// task to stop reading this socket and let go of it.
+    #[cfg(feature = "test-util")]
+    pub fn clone_with_forwarded_stream(
+        &self,
+        stream: mpsc::Receiver<ReceivePacketResult>,
+    ) -> (Self, oneshot::Receiver<()>) {
+        let (stop, stopped) = oneshot::channel();
+        let suspected_kernel_drops = self
+            .read_loop
+            .as_ref()
+            .map_or_else(Default::default, |r| r.suspected_kernel_drops.clone());
+        let clone = Self {
+            read_loop: Some(ReadLoopHandle {
+                _stop: stop,
+                suspected_kernel_drops,
+            }),
+            ..self.clone_with_stream(stream)
+        };
+        (clone, stopped)
+    }
+
     pub async fn send(&mut self, packet: (Packet, SocketAddr)) -> Result<usize, io::Error> {
-        self.buffer.clear();
-        packet.0.serialize(&mut self.buffer);
-        self.socket.send_to(&self.buffer, packet.1).await
+        match &mut self.transport {
+            PacketTransport::Udp(socket) => {
+                self.buffer.clear();
+                packet.0.serialize(&mut self.buffer);
+                socket.send_to(&self.buffer, packet.1).await
+            }
+            PacketTransport::Channel(local, sender) => {
+                let size = packet.0.wire_size();
+                sender.send(Ok((packet.0, *local))).await.map_err(|_| {
+                    io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError)
+                })?;
+                Ok(size)
+            }
+            PacketTransport::Custom(sender) => {
+                let size = packet.0.wire_size();
+                sender.send(packet).await.map_err(|_| {
+                    io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError)
+                })?;
+                Ok(size)
+            }
+        }
     }
 
     pub async fn receive(&mut self) -> ReceivePacketResult {
-        match self.stream.as_mut() {
-            Some(stream) => Self::stream_receive(stream).await,
-            None => self.socket_receive().await,
+        match (self.stream.as_mut(), &self.transport) {
+            (Some(stream), _) => Self::stream_receive(stream).await,
+            (None, PacketTransport::Udp(socket)) => {
+                Self::socket_receive(socket, &mut self.buffer, &self.tap, self.strict_handshake)
+                    .await
+            }
+            (None, PacketTransport::Channel(_, _) | PacketTransport::Custom(_)) => {
+                Err(io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError).into())
+            }
+        }
+    }
+
+    /// Sends `packet` without a future of its own, for sockets driven by polling. It's serialized
+    /// again each time it's polled, until it's sent.
+    pub fn poll_send(
+        &mut self,
+        cx: &mut Context,
+        packet: &(Packet, SocketAddr),
+    ) -> Poll<Result<usize, io::Error>> {
+        let not_connected = |_| io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError);
+        match &mut self.transport {
+            PacketTransport::Udp(socket) => {
+                self.buffer.clear();
+                packet.0.serialize(&mut self.buffer);
+                socket.poll_send_to(cx, &self.buffer, packet.1)
+            }
+            PacketTransport::Channel(local, sender) => {
+                ready!(sender.poll_ready(cx)).map_err(not_connected)?;
+                let size = packet.0.wire_size();
+                sender
+                    .start_send(Ok((packet.0.clone(), *local)))
+                    .map_err(not_connected)?;
+                Poll::Ready(Ok(size))
+            }
+            PacketTransport::Custom(sender) => {
+                ready!(sender.poll_ready(cx)).map_err(not_connected)?;
+                let size = packet.0.wire_size();
+                sender.start_send(packet.clone()).map_err(not_connected)?;
+                Poll::Ready(Ok(size))
+            }
+        }
+    }
+
+    /// The next packet received, without a future of its own, for sockets driven by polling
+    pub fn poll_receive(&mut self, cx: &mut Context) -> Poll<ReceivePacketResult> {
+        match (self.stream.as_mut(), &self.transport) {
+            (Some(stream), _) => {
+                Poll::Ready(ready!(stream.poll_next_unpin(cx)).unwrap_or_else(|| {
+                    Err(io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError).into())
+                }))
+            }
+            (None, PacketTransport::Udp(socket)) => loop {
+                ready!(socket.poll_recv_ready(cx))?;
+                self.buffer.clear();
+                return Poll::Ready(match socket.try_recv_buf_from(&mut self.buffer) {
+                    Ok((size, from)) => {
+                        self.tap.observe(&self.buffer[0..size], from);
+                        Self::parse(socket, &self.buffer[0..size], from, self.strict_handshake)
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                    Err(e) => Err(e.into()),
+                });
+            },
+            (None, PacketTransport::Channel(_, _) | PacketTransport::Custom(_)) => Poll::Ready(
+                Err(io::Error::new(ErrorKind::NotConnected, PacketStreamClosedError).into()),
+            ),
         }
     }
 
@@ -105,27 +428,127 @@ impl PacketSocket {
         })
     }
 
-    async fn socket_receive(&mut self) -> ReceivePacketResult {
+    async fn socket_receive(
+        socket: &UdpSocket,
+        buffer: &mut BytesMut,
+        tap: &RawTap,
+        strict_handshake: bool,
+    ) -> ReceivePacketResult {
         loop {
-            self.socket.readable().await?;
-            self.buffer.clear();
-            return match self.socket.try_recv_buf_from(&mut self.buffer) {
-                Ok((size, from)) => self.parse(size, from),
+            socket.readable().await?;
+            buffer.clear();
+            return match socket.try_recv_buf_from(buffer) {
+                Ok((size, from)) => {
+                    tap.observe(&buffer[0..size], from);
+                    Self::parse(socket, &buffer[0..size], from, strict_handshake)
+                }
                 Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
                 Err(e) => Err(e.into()),
             };
         }
     }
 
-    fn parse(&mut self, size: usize, from: SocketAddr) -> ReceivePacketResult {
-        let packet = Packet::parse(
-            &mut Cursor::new(&self.buffer[0..size]),
-            self.socket.local_addr()?.is_ipv6(),
-        )?;
+    // drains the socket whenever it's readable, up to a batch at a time, so that the kernel buffer
+    // empties into the queue even while the connection is busy
+    async fn read_loop(
+        socket: Arc<UdpSocket>,
+        batch_size: usize,
+        mut packets: mpsc::Sender<ReceivePacketResult>,
+        tap: RawTap,
+        strict_handshake: bool,
+        suspected_kernel_drops: Arc<AtomicU64>,
+        stopped: oneshot::Receiver<()>,
+    ) {
+        // the receiver reports itself terminated once the sender is dropped, so select! would skip
+        // it, fused it completes with the cancellation instead
+        let mut stopped = stopped.fuse();
+        let mut buffer = BytesMut::with_capacity(65536);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut gaps = SequenceGaps::default();
+        loop {
+            select! {
+                _ = stopped => return,
+                readable = socket.readable().fuse() => {
+                    if let Err(e) = readable {
+                        batch.push(Err(e.into()));
+                    }
+                }
+            }
+            while batch.len() < batch_size {
+                buffer.clear();
+                match socket.try_recv_buf_from(&mut buffer) {
+                    Ok((size, from)) => {
+                        tap.observe(&buffer[0..size], from);
+                        batch.push(Self::parse(
+                            &socket,
+                            &buffer[0..size],
+                            from,
+                            strict_handshake,
+                        ));
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                    Err(e) => {
+                        batch.push(Err(e.into()));
+                        break;
+                    }
+                }
+            }
+
+            // a full batch leaves datagrams waiting in the socket, if it's falling behind that
+            // much, the gaps are likely the kernel dropping what didn't fit in its buffer
+            let backlogged = batch.len() == batch_size;
+            for packet in batch.drain(..) {
+                if let Ok((Packet::Data(data), _)) = &packet {
+                    let gap = gaps.on_data(data.seq_number, data.retransmitted);
+                    if backlogged && gap > 0 {
+                        suspected_kernel_drops.fetch_add(gap as u64, Ordering::Relaxed);
+                    }
+                }
+                if packets.send(packet).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn parse(
+        socket: &UdpSocket,
+        buffer: &[u8],
+        from: SocketAddr,
+        strict_handshake: bool,
+    ) -> ReceivePacketResult {
+        let is_ipv6 = socket.local_addr()?.is_ipv6();
+        let packet = match strict_handshake {
+            true => Packet::parse_strict(&mut Cursor::new(buffer), is_ipv6)?,
+            false => Packet::parse(&mut Cursor::new(buffer), is_ipv6)?,
+        };
         Ok((packet, from))
     }
 }
 
+/// Follows the sequence numbers of the original DATA packets read, for the gaps between them
+#[derive(Debug, Default)]
+struct SequenceGaps {
+    next: Option<SeqNumber>,
+}
+
+impl SequenceGaps {
+    /// The packets missing right before this one, retransmissions fill gaps rather than leave them
+    fn on_data(&mut self, seq_number: SeqNumber, retransmitted: bool) -> usize {
+        if retransmitted {
+            return 0;
+        }
+        match self.next {
+            // reordered
+            Some(next) if seq_number < next => 0,
+            next => {
+                self.next = Some(seq_number + 1);
+                next.map_or(0, |next| seq_number - next) as usize
+            }
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct PacketStreamClosedError;
 
@@ -154,4 +577,20 @@ mod tests {
             "127.0.0.1:3000" | "[::1]:3000"
         );
     }
+
+    #[test]
+    fn sequence_gaps() {
+        let mut gaps = SequenceGaps::default();
+        assert_eq!(gaps.on_data(SeqNumber(10), false), 0);
+        assert_eq!(gaps.on_data(SeqNumber(11), false), 0);
+        assert_eq!(gaps.on_data(SeqNumber(15), false), 3);
+        // reordered, or retransmitted, packets don't leave gaps
+        assert_eq!(gaps.on_data(SeqNumber(13), false), 0);
+        assert_eq!(gaps.on_data(SeqNumber(12), true), 0);
+        assert_eq!(gaps.on_data(SeqNumber(16), false), 0);
+        // across the wrap
+        let mut gaps = SequenceGaps::default();
+        assert_eq!(gaps.on_data(SeqNumber(SeqNumber::MAX - 1), false), 0);
+        assert_eq!(gaps.on_data(SeqNumber(1), false), 1);
+    }
 }
@@ -0,0 +1,22 @@
+use std::io;
+
+use futures::prelude::*;
+
+use crate::SrtSocket;
+
+/// Forwards the data received on `ingress` to `egress`, as an SRT relay or repeater does, until
+/// the ingress connection ends. Then `egress` is closed, once what was forwarded is sent.
+///
+/// Data comes out of `ingress` with its origin instant, one ingress latency before it's released.
+/// It goes into `egress` with the instant it was released at instead, keeping the spacing of the
+/// stream, so the egress gets all of its own latency whatever the ingress latency is. The latency
+/// from end to end is the sum of both.
+///
+/// The sockets are only borrowed, their statistics and errors can be looked at afterwards.
+pub async fn relay(ingress: &mut SrtSocket, egress: &mut SrtSocket) -> Result<(), io::Error> {
+    let latency = ingress.settings().recv_tsbpd_latency;
+    while let Some((origin, data)) = ingress.try_next().await? {
+        egress.send((origin + latency, data)).await?;
+    }
+    egress.close().await
+}
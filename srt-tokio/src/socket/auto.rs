@@ -0,0 +1,26 @@
+use std::io;
+
+use log::info;
+
+use srt_protocol::{connection::Connection, options::*};
+
+use crate::net::PacketSocket;
+
+use super::{call, rendezvous, HandshakeEvents};
+
+pub async fn bind_with(
+    socket: &mut PacketSocket,
+    options: Valid<AutoOptions>,
+    events: &mut HandshakeEvents,
+) -> Result<Connection, io::Error> {
+    match call::bind_with(socket, options.caller()?, events, None).await {
+        Ok(connection) => return Ok(connection),
+        // someone answered and turned it away, meeting in rendezvous won't change their mind
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => return Err(error),
+        Err(error) => info!(
+            "Calling {:?} failed, trying rendezvous: {error}",
+            options.remote
+        ),
+    }
+    rendezvous::bind_with(socket, options.rendezvous()?, events).await
+}
@@ -3,12 +3,20 @@ use std::{convert::TryInto, io, net::IpAddr, time::Duration};
 
 use tokio::net::UdpSocket;
 
-use crate::options::*;
+use srt_protocol::connection::Resumption;
 
-use super::SrtSocket;
+use crate::{options::*, tap::RawTap};
+
+use super::{ConnectionEvent, HandshakeCallback, SrtSocket};
 
 #[derive(Default)]
-pub struct SrtSocketBuilder(SocketOptions, Option<UdpSocket>);
+pub struct SrtSocketBuilder(
+    SocketOptions,
+    Option<UdpSocket>,
+    RawTap,
+    Option<Resumption>,
+    Option<HandshakeCallback>,
+);
 
 /// Struct to build sockets.
 ///
@@ -84,6 +92,53 @@ impl SrtSocketBuilder {
         self
     }
 
+    // SRTO_BINDTODEVICE
+    /// Binds the socket to a network interface, e.g. `eth1`, so it sends and receives only through
+    /// it. Binding fails on platforms other than Linux.
+    pub fn bind_device(mut self, device: impl Into<String>) -> Self {
+        self.0.connect.bind_device = Some(device.into());
+        self
+    }
+
+    // SRTO_REUSEADDR
+    /// Allows binding to an address that is in use, see [`Connect::reuse_address`]
+    pub fn reuse_address(mut self, reuse: bool) -> Self {
+        self.0.connect.reuse_address = reuse;
+        self
+    }
+
+    /// Allows other sockets that allow it too to bind to the same port, see
+    /// [`Connect::reuse_port`]. Binding fails on platforms other than Unix.
+    pub fn reuse_port(mut self, reuse: bool) -> Self {
+        self.0.connect.reuse_port = reuse;
+        self
+    }
+
+    // SRTO_IPTOS
+    /// Sets the IPv4 type of service or IPv6 traffic class of the packets sent, for QoS. Binding
+    /// fails if the platform refuses it.
+    pub fn ip_tos(mut self, tos: u8) -> Self {
+        self.0.connect.ip_tos = Some(tos);
+        self
+    }
+
+    /// Marks the packets sent with a DSCP, the upper six bits of the type of service, see
+    /// [`ip_tos`](Self::ip_tos).
+    ///
+    /// # Panics:
+    /// * dscp is greater than 63
+    pub fn dscp(self, dscp: u8) -> Self {
+        assert!(dscp < 64, "DSCP {dscp} is more than six bits");
+        self.ip_tos(dscp << 2)
+    }
+
+    /// Reads the UDP socket on a task of its own, in batches, to keep up with high packet rates,
+    /// see [`UdpReadLoop`]
+    pub fn udp_read_loop(mut self, read_loop: UdpReadLoop) -> Self {
+        self.0.connect.udp_read_loop = Some(read_loop);
+        self
+    }
+
     // SRTO_LATENCY
     /// Set the latency of the connection. The more latency, the more time SRT has to recover lost packets.
     /// This sets both the send and receive latency
@@ -94,6 +149,13 @@ impl SrtSocketBuilder {
         self
     }
 
+    /// Starts from this round trip time until it's measured, for links known to have a long one,
+    /// see [`Session::initial_rtt`]
+    pub fn initial_rtt(mut self, rtt: Duration) -> Self {
+        self.0.session.initial_rtt = Some(rtt);
+        self
+    }
+
     /// Set the encryption parameters.
     ///
     /// # Panics:
@@ -104,6 +166,35 @@ impl SrtSocketBuilder {
 
         self
     }
+
+    /// Accept keys wrapped with this passphrase too, to roll over to a new passphrase without
+    /// turning away peers that still use the old one, see [`Encryption::alternate_passphrases`]
+    ///
+    /// # Panics:
+    /// * the passphrase is not 10 to 79 characters long
+    pub fn alternate_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.0
+            .encryption
+            .alternate_passphrases
+            .push(passphrase.into().try_into().unwrap());
+        self
+    }
+    /// Prove this identity to peers and only connect to the ones trusted by the credentials, see
+    /// [`Encryption::credentials`]
+    #[cfg(feature = "authentication")]
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.0.encryption.credentials = Some(credentials);
+        self
+    }
+
+    /// Reject handshakes that are subtly malformed rather than warn about them, see
+    /// [`Connect::strict_handshake`]
+    #[cfg(feature = "strict-handshake")]
+    pub fn strict_handshake(mut self, strict_handshake: bool) -> Self {
+        self.0.connect.strict_handshake = strict_handshake;
+        self
+    }
+
     /// the minimum latency to receive at
     pub fn receive_latency(mut self, latency: Duration) -> Self {
         self.0.receiver.latency = latency;
@@ -116,16 +207,132 @@ impl SrtSocketBuilder {
         self
     }
 
+    /// The interval at which an unanswered handshake is sent again, when calling or in rendezvous
+    pub fn handshake_retry_interval(mut self, interval: Duration) -> Self {
+        self.0.connect.handshake_retry_interval = interval;
+        self
+    }
+
+    /// Give up connecting once a handshake packet was sent again this many times, instead of
+    /// waiting for the connect timeout
+    pub fn max_handshake_retries(mut self, retries: u32) -> Self {
+        self.0.connect.max_handshake_retries = Some(retries);
+        self
+    }
+
+    /// Give up on the connection once a packet was retransmitted this many times, instead of
+    /// retransmitting until the peer idle timeout, see [`Sender::max_retransmits`]
+    pub fn max_retransmits(mut self, retransmits: u32) -> Self {
+        self.0.sender.max_retransmits = Some(retransmits);
+        self
+    }
+
+    /// Offer the peer to carry several logical streams over the connection, see [`crate::multiplex`]
+    pub fn multiplex(mut self, multiplex: bool) -> Self {
+        self.0.session.multiplex = multiplex;
+        self
+    }
+
+    /// When listening, drop inductions once this many callers are half-open, waiting to conclude
+    pub fn max_half_open(mut self, max: usize) -> Self {
+        self.0.connect.max_half_open = max;
+        self
+    }
+
+    /// When listening, only take handshakes from these networks, see [`Connect::allow_from`]
+    pub fn allow_from(mut self, allowed: Vec<IpNet>) -> Self {
+        self.0.connect.allow_from = Some(allowed);
+        self
+    }
+
+    /// Makes the connection resumable for this long after it drops, see
+    /// [`Connect::resumption_window`]
+    pub fn resumption_window(mut self, window: Duration) -> Self {
+        self.0.connect.resumption_window = Some(window);
+        self
+    }
+
+    /// When calling, resumes the connection `resumption` was taken from, sending what it left
+    /// unacknowledged first, see [`SrtSocket::resumption`]
+    pub fn resume(mut self, resumption: Resumption) -> Self {
+        self.3 = Some(resumption);
+        self
+    }
+
     pub fn bandwidth(mut self, bandwidth: LiveBandwidthMode) -> Self {
         self.0.sender.bandwidth = bandwidth;
         self
     }
 
+    /// Whether data sent while the send buffer is full drops the oldest packets, or waits for room
+    pub fn send_buffer_full(mut self, policy: SendBufferFull) -> Self {
+        self.0.sender.buffer_full = policy;
+        self
+    }
+
+    /// Whether data sent while the flow window is exhausted waits, blocks sending, or drops the
+    /// oldest of what's waiting for the window, see [`FlowWindowFull`]
+    pub fn flow_window_full(mut self, policy: FlowWindowFull) -> Self {
+        self.0.sender.flow_window_full = policy;
+        self
+    }
+
+    /// Whether data sent faster than the bandwidth settings let it out waits its turn, or the
+    /// oldest of it is dropped once it falls too far behind, see [`InputOverflowPolicy`]
+    pub fn input_overflow(mut self, policy: InputOverflowPolicy) -> Self {
+        self.0.sender.input_overflow = policy;
+        self
+    }
+
+    /// How many messages wait for the connection to take them while the send buffer pushes back,
+    /// see [`Sender::queue_depth`]
+    pub fn send_queue_depth(mut self, depth: usize) -> Self {
+        self.0.sender.queue_depth = depth;
+        self
+    }
+
+    /// Whether messages after missing packets wait for them, or are delivered as they become due
+    pub fn delivery_mode(mut self, mode: DeliveryMode) -> Self {
+        self.0.receiver.delivery_mode = mode;
+        self
+    }
+
+    /// Whether each item received is a whole message, or the payload of one of its packets
+    pub fn recv_granularity(mut self, granularity: RecvGranularity) -> Self {
+        self.0.receiver.granularity = granularity;
+        self
+    }
+
+    /// How often the packets still missing are reported again, as a share of the RTT with a floor
+    pub fn nak_period(mut self, nak_period: NakPeriodConfig) -> Self {
+        self.0.receiver.nak_period = nak_period;
+        self
+    }
+
     pub fn socket(mut self, socket: UdpSocket) -> Self {
         self.1 = Some(socket);
         self
     }
 
+    /// Copies every UDP datagram the socket receives to `tap`, starting with the handshake, see
+    /// [`RawTap`].
+    pub fn raw_tap(mut self, tap: &RawTap) -> Self {
+        self.2 = tap.clone();
+        self
+    }
+
+    /// Calls `on_event` with each event of the handshake as it happens: the handshake packets
+    /// sent and received, the retransmissions, and the [`ConnectionEvent::Connected`] or
+    /// [`ConnectionEvent::Rejected`] it ends in. The events stream of the socket has these too,
+    /// but only once it's connected, the callback also hears of handshakes that fail.
+    pub fn on_handshake_event(
+        mut self,
+        on_event: impl FnMut(ConnectionEvent) + Send + 'static,
+    ) -> Self {
+        self.4 = Some(Box::new(on_event));
+        self
+    }
+
     pub fn with<O>(mut self, options: O) -> Self
     where
         SocketOptions: OptionsOf<O>,
@@ -149,8 +356,16 @@ impl SrtSocketBuilder {
 
     pub async fn listen(self) -> Result<SrtSocket, io::Error> {
         Self::bind(
-            ListenerOptions { socket: self.0 }.try_validate()?.into(),
+            ListenerOptions {
+                socket: self.0,
+                max_connections: None,
+            }
+            .try_validate()?
+            .into(),
             self.1,
+            self.2,
+            self.3,
+            self.4,
         )
         .await
     }
@@ -161,7 +376,7 @@ impl SrtSocketBuilder {
         stream_id: Option<&str>,
     ) -> Result<SrtSocket, io::Error> {
         let options = CallerOptions::with(remote, stream_id, self.0)?;
-        Self::bind(options.into(), self.1).await
+        Self::bind(options.into(), self.1, self.2, self.3, self.4).await
     }
 
     pub async fn rendezvous(
@@ -169,13 +384,33 @@ impl SrtSocketBuilder {
         remote: impl TryInto<SocketAddress>,
     ) -> Result<SrtSocket, io::Error> {
         let options = RendezvousOptions::with(remote, self.0)?;
-        Self::bind(options.into(), self.1).await
+        Self::bind(options.into(), self.1, self.2, self.3, self.4).await
     }
 
-    async fn bind(options: BindOptions, socket: Option<UdpSocket>) -> Result<SrtSocket, io::Error> {
-        match socket {
-            None => SrtSocket::bind(options).await,
-            Some(socket) => SrtSocket::bind_with_socket(options, socket).await,
-        }
+    /// Calls `remote` for `call_timeout`, then falls back to rendezvous with it on the local port,
+    /// for peers that could start in either order, see [`AutoOptions`]. Both peers need a local
+    /// port set.
+    pub async fn auto(
+        self,
+        remote: impl TryInto<SocketAddress>,
+        call_timeout: Duration,
+    ) -> Result<SrtSocket, io::Error> {
+        let options = AutoOptions::with(remote, call_timeout, self.0)?;
+        Self::bind(options.into(), self.1, self.2, self.3, self.4).await
+    }
+
+    /// Creates two sockets with these options connected to each other in memory, see [`SrtSocket::pair`].
+    pub fn pair(self) -> Result<(SrtSocket, SrtSocket), io::Error> {
+        Ok(SrtSocket::bind_pair(self.0.try_validate()?))
+    }
+
+    async fn bind(
+        options: BindOptions,
+        socket: Option<UdpSocket>,
+        raw_tap: RawTap,
+        resumption: Option<Resumption>,
+        on_handshake_event: Option<HandshakeCallback>,
+    ) -> Result<SrtSocket, io::Error> {
+        SrtSocket::bind_with(options, socket, raw_tap, resumption, on_handshake_event).await
     }
 }
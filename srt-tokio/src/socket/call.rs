@@ -1,55 +1,81 @@
-use std::{
-    io,
-    time::{Duration, Instant},
-};
+use std::{io, time::Instant};
 
 use futures::{prelude::*, select};
 use log::{debug, info, trace, warn};
 use tokio::time::interval;
 
 use srt_protocol::{
-    connection::Connection,
+    connection::{Connection, ConnectionEvent},
     options::*,
+    packet::SessionResume,
     protocol::pending_connection::{connect::Connect, ConnectionResult},
+    settings::ConnInitSettings,
+};
+
+use crate::{
+    net::{lookup_remote_host, PacketSocket},
+    SrtError,
 };
 
-use crate::net::{lookup_remote_host, PacketSocket};
+use super::HandshakeEvents;
 
 pub async fn bind_with(
-    mut socket: PacketSocket,
+    socket: &mut PacketSocket,
     options: Valid<CallerOptions>,
-) -> Result<(PacketSocket, Connection), io::Error> {
+    events: &mut HandshakeEvents,
+    resume: Option<SessionResume>,
+) -> Result<Connection, io::Error> {
     let stream_id = options.stream_id.as_ref().map(|s| s.to_string());
     let remote = lookup_remote_host(&options.remote).await?;
 
-    let mut tick_interval = interval(Duration::from_millis(100));
+    let mut init_settings: ConnInitSettings = options.socket.clone().into();
+    init_settings.resume = resume;
+    #[cfg(feature = "tracing")]
+    super::trace::record_handshake(init_settings.local_sockid, Some(remote));
+
+    let mut tick_interval = interval(options.socket.connect.handshake_retry_interval);
     let mut connect = Connect::new(
         remote,
         options.socket.connect.local.ip(),
-        options.socket.clone().into(),
+        init_settings,
         stream_id.clone(),
         rand::random(),
     );
 
     let start_time = Instant::now();
+    let mut handshake_sent = false;
 
     loop {
         if start_time.elapsed() > options.socket.connect.timeout {
-            return Err(io::Error::new(io::ErrorKind::TimedOut, ""));
+            return Err(SrtError::Timeout.into());
         }
 
-        let result = select! {
+        let (now, received, ticked, result) = select! {
             now = tick_interval.tick().fuse() => {
                 trace!("caller interval elapsed, passing tick");
-                connect.handle_tick(now.into())
+                let now = now.into();
+                (now, None, true, connect.handle_tick(now))
             }
             packet = socket.receive().fuse() => {
                 trace!("caller got packet {packet:?}");
-                connect.handle_packet(packet, Instant::now())
+                let now = Instant::now();
+                let received = packet.as_ref().ok().map(|(packet, _)| packet.clone());
+                (now, received, false, connect.handle_packet(packet, now))
             }
         };
+        // the first tick sends the initial handshake, later ones resend it once the retry
+        // interval has passed since the last one went out
+        if ticked && matches!(result, ConnectionResult::SendPacket(_)) {
+            if handshake_sent {
+                events.publish(ConnectionEvent::Reconnecting(now));
+            }
+            handshake_sent = true;
+        }
+        events.handshake(now, received.as_ref(), &result);
 
         debug!("{:?}:connect - {:?}", stream_id, result);
+        #[cfg(feature = "tracing")]
+        super::trace::handshake(&result);
         use ConnectionResult::*;
         match result {
             SendPacket(packet) => {
@@ -68,7 +94,7 @@ pub async fn bind_with(
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
                 }
-                return Ok((socket, connection));
+                return Ok(connection);
             }
             NoAction => {}
             RequestAccess(_) => {}
@@ -111,4 +137,20 @@ mod test {
         assert!(start.elapsed() > Duration::from_millis(5000));
         assert!(start.elapsed() < Duration::from_millis(5500));
     }
+
+    #[tokio::test]
+    async fn max_handshake_retries() {
+        let _ = pretty_env_logger::try_init();
+
+        // the first induction and 3 retries, 200ms apart
+        let start = Instant::now();
+        let ret = SrtSocket::builder()
+            .handshake_retry_interval(Duration::from_millis(200))
+            .max_handshake_retries(3)
+            .call("127.0.0.1:11112", None)
+            .await;
+        assert_matches!(ret, Err(e) if e.kind() == io::ErrorKind::TimedOut);
+        assert!(start.elapsed() >= Duration::from_millis(790));
+        assert!(start.elapsed() < Duration::from_millis(1300));
+    }
 }
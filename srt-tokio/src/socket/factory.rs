@@ -1,22 +1,188 @@
-use std::time::Instant;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use bytes::Bytes;
-use futures::{channel::mpsc, prelude::*, select, stream::StreamExt};
+use futures::{
+    channel::mpsc,
+    lock::Mutex,
+    prelude::*,
+    select,
+    stream::{FusedStream, StreamExt},
+};
 use log::{error, trace};
 use srt_protocol::{
-    connection::{Action, Connection, ConnectionSettings, DuplexConnection, Input},
-    packet::TimeSpan,
+    connection::{
+        Action, Connection, ConnectionEvent, ConnectionSettings, CryptoState, DuplexConnection,
+        Input, MessageInfo, Priority, Resumption,
+    },
+    options::Passphrase,
+    packet::{ReceivePacketError, ReceivePacketResult, SeqNumber, TimeSpan},
 };
 use tokio::{task::JoinHandle, time::sleep_until};
+#[cfg(feature = "tracing")]
+use tracing::Instrument;
+
+use crate::{net::PacketSocket, tap::RawTap, watch, SocketStatistics, SrtError, SrtSocket};
+
+#[cfg(feature = "test-util")]
+use super::test_util;
+#[cfg(feature = "tracing")]
+use super::trace;
+
+/// When packets were last received from and sent to the peer, and how much of the data handed
+/// to the task the peer has yet to acknowledge
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Activity {
+    pub last_recv: Option<Instant>,
+    pub last_send: Option<Instant>,
+    /// The messages taken from the input data channel so far
+    pub data_taken: u64,
+    pub unacked_bytes: usize,
+    /// One past the last packet of the data taken, and of what the peer acknowledged, None
+    /// until the connection is serviced
+    pub written_seq_number: Option<SeqNumber>,
+    pub acked_seq_number: Option<SeqNumber>,
+}
+
+impl Activity {
+    fn of(connection: &DuplexConnection, data_taken: u64) -> Self {
+        Self {
+            last_recv: connection.last_recv(),
+            last_send: connection.last_send(),
+            data_taken,
+            unacked_bytes: connection.unacked_bytes(),
+            written_seq_number: Some(connection.written_seq_number()),
+            acked_seq_number: Some(connection.acked_seq_number()),
+        }
+    }
+}
+
+/// What the socket asks of the task running the connection
+#[derive(Debug)]
+pub enum Command {
+    SetLatency(Duration),
+    SetPassphrase(Passphrase),
+    Pause,
+    Resume,
+    Ping,
+}
+
+/// What the task publishes about the connection for the socket to look up or wait for
+#[derive(Clone, Debug)]
+pub struct SocketStatus {
+    pub activity: Activity,
+    pub crypto_state: CryptoState,
+    /// Why the connection ended if it's the peer's doing, or else the last error sending to the
+    /// network
+    pub error: Option<SrtError>,
+    /// What the peer didn't acknowledge, once the connection is closed
+    pub resumption: Option<Resumption>,
+}
+
+impl Default for SocketStatus {
+    fn default() -> Self {
+        Self {
+            activity: Activity::default(),
+            // replaced with the negotiated state once connected
+            crypto_state: CryptoState::Unencrypted,
+            error: None,
+            resumption: None,
+        }
+    }
+}
+
+/// What was put into the input data channel, for the task to tell how much of it waits there
+#[derive(Debug, Default)]
+pub struct DataSent {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+}
+
+impl DataSent {
+    pub fn add(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::SeqCst);
+        self.messages.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn messages(&self) -> u64 {
+        self.messages.load(Ordering::SeqCst)
+    }
+
+    // the messages and bytes sent that weren't taken yet
+    fn queued(&self, messages_taken: u64, bytes_taken: u64) -> (u64, u64) {
+        let messages = self.messages().saturating_sub(messages_taken);
+        let bytes = self
+            .bytes
+            .load(Ordering::SeqCst)
+            .saturating_sub(bytes_taken);
+        (messages, bytes)
+    }
+}
+
+fn publish_status(
+    status_sender: &watch::Sender<SocketStatus>,
+    connection: &DuplexConnection,
+    data_taken: u64,
+) {
+    let activity = Activity::of(connection, data_taken);
+    let crypto_state = connection.crypto_state();
+    status_sender.send_if_modified(|status| {
+        let modified = status.activity != activity || status.crypto_state != crypto_state;
+        status.activity = activity;
+        status.crypto_state = crypto_state;
+        modified
+    });
+}
+
+fn publish_error(status_sender: &watch::Sender<SocketStatus>, error: SrtError) {
+    status_sender.send_if_modified(|status| {
+        status.error = Some(error);
+        true
+    });
+}
+
+/// Why the connection ended, if it's the peer's doing, and what the peer didn't acknowledge
+fn publish_closed(status_sender: &watch::Sender<SocketStatus>, connection: &mut DuplexConnection) {
+    let reason = connection.close_reason();
+    let resumption = connection.take_resumption();
+    status_sender.send_if_modified(|status| {
+        let modified = reason.is_some() || resumption.is_some();
+        if let Some(reason) = reason {
+            status.error = Some(reason.into());
+        }
+        if resumption.is_some() {
+            status.resumption = resumption;
+        }
+        modified
+    });
+}
 
-use crate::{net::PacketSocket, watch, SocketStatistics, SrtSocket};
+fn handle_command(connection: &mut DuplexConnection, now: Instant, command: Command) {
+    use Command::*;
+    match command {
+        SetLatency(latency) => connection.set_latency(now, latency),
+        SetPassphrase(passphrase) => connection.set_passphrase(passphrase),
+        Pause => connection.pause(),
+        Resume => connection.resume(),
+        Ping => connection.ping(now),
+    }
+}
 
 struct SrtSocketState {
     socket: PacketSocket,
     connection: DuplexConnection,
     statistics_sender: watch::Sender<SocketStatistics>,
-    output_data_sender: mpsc::Sender<(Instant, Bytes)>,
-    input_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+    status_sender: watch::Sender<SocketStatus>,
+    events_sender: mpsc::UnboundedSender<ConnectionEvent>,
+    output_data_sender: mpsc::Sender<(Instant, Bytes, MessageInfo)>,
+    input_data_receiver: mpsc::Receiver<(Instant, Bytes, Priority)>,
+    data_sent: Arc<DataSent>,
+    command_receiver: mpsc::UnboundedReceiver<Command>,
 }
 
 impl SrtSocketState {
@@ -37,20 +203,37 @@ impl SrtSocketState {
         let mut socket = self.socket;
         let mut input_data = self.input_data_receiver.fuse();
         let mut output_data = self.output_data_sender;
+        let mut commands = self.command_receiver.fuse();
         let mut connection = self.connection;
         let statistics_sender = self.statistics_sender;
+        let status_sender = self.status_sender;
+        let events_sender = self.events_sender;
+        let data_sent = self.data_sent;
+        #[cfg(feature = "tracing")]
+        let mut dropped_packets = trace::DroppedPackets::default();
+        let mut socket_closed = false;
+        let mut data_taken = 0;
+        let mut bytes_taken = 0;
         while connection.is_open() {
-            if connection.should_update_statistics(Instant::now()) {
+            connection.on_suspected_kernel_drops(socket.take_suspected_kernel_drops());
+            let (queued, queued_bytes) = data_sent.queued(data_taken, bytes_taken);
+            connection.on_send_queue(queued, queued_bytes);
+            let now = Instant::now();
+            if connection.should_update_statistics(now) {
+                connection.update_statistics(now);
+                #[cfg(feature = "tracing")]
+                dropped_packets.update(connection.statistics());
                 let _ = statistics_sender.send(connection.statistics().clone());
             }
 
             while let Some(packet) = connection.next_packet(Instant::now()) {
                 if let Err(e) = socket.send(packet).await {
                     error!("Error while sending packet: {:?}", e); // TODO: real error handling
+                    publish_error(&status_sender, SrtError::Io(Arc::new(e)));
                 }
             }
 
-            while let Some(data) = connection.next_data(Instant::now()) {
+            while let Some(data) = connection.next_data_with_info(Instant::now()) {
                 if output_data.is_closed() {
                     continue;
                 }
@@ -59,6 +242,11 @@ impl SrtSocketState {
                 }
             }
 
+            while let Some(event) = connection.next_event() {
+                publish_event(&events_sender, event);
+            }
+            publish_status(&status_sender, &connection, data_taken);
+
             let timeout = connection.check_timers(Instant::now());
             let timeout_fut = async {
                 let now = Instant::now();
@@ -70,24 +258,47 @@ impl SrtSocketState {
                 sleep_until(timeout.into()).await
             };
 
+            // with backpressure, the data waits in the channel while the send buffer is full
+            let accepting_data = connection.is_accepting_data();
             let input = select! {
                 // one of the entities requested wakeup
                 _ = timeout_fut.fuse() => Input::Timer,
                 // new packet received
-                packet = socket.receive().fuse() =>
+                packet = next_packet(&mut socket, socket_closed).fuse() =>
                     Input::Packet(packet),
                 // new packet queued
-                data = input_data.next() => {
-                    Input::Data(data)
+                data = next_input_data(&mut input_data, accepting_data).fuse() => {
+                    match data {
+                        Some((time, data, priority)) => {
+                            data_taken += 1;
+                            bytes_taken += data.len() as u64;
+                            connection.handle_prioritized_data_input(
+                                Instant::now(),
+                                (time, data),
+                                priority,
+                            )
+                        }
+                        None => connection.handle_data_input(Instant::now(), None),
+                    }
+                    Input::Timer
+                }
+                command = commands.next() => {
+                    if let Some(command) = command {
+                        handle_command(&mut connection, Instant::now(), command);
+                    }
+                    Input::Timer
                 }
             };
 
-            match input {
-                Input::Packet(packet) => connection.handle_packet_input(Instant::now(), packet),
-                Input::Data(data) => connection.handle_data_input(Instant::now(), data),
-                _ => {}
+            if let Input::Packet(packet) = input {
+                socket_closed |= matches!(packet, Err(ReceivePacketError::Io(_)));
+                connection.handle_packet_input(Instant::now(), packet)
             }
         }
+        while let Some(event) = connection.next_event() {
+            publish_event(&events_sender, event);
+        }
+        publish_closed(&status_sender, &mut connection);
         if let Err(e) = output_data.close().await {
             error!("Error while closing data output stream {:?}", e);
         }
@@ -97,11 +308,36 @@ impl SrtSocketState {
         let mut socket = self.socket;
         let mut input_data = self.input_data_receiver.fuse();
         let mut output_data = self.output_data_sender;
+        let mut commands = self.command_receiver.fuse();
         let mut connection = self.connection;
         let statistics_sender = self.statistics_sender;
+        let status_sender = self.status_sender;
+        let events_sender = self.events_sender;
         let mut input = Input::Timer;
+        let mut command = None;
+        let mut data_input: Option<(Instant, Bytes, Priority)> = None;
+        let data_sent = self.data_sent;
+        let mut data_taken = 0;
+        let mut bytes_taken = 0;
+        let mut socket_closed = false;
+        #[cfg(feature = "tracing")]
+        let mut dropped_packets = trace::DroppedPackets::default();
         loop {
+            while let Some(event) = connection.next_event() {
+                publish_event(&events_sender, event);
+            }
+            connection.on_suspected_kernel_drops(socket.take_suspected_kernel_drops());
+            let (queued, queued_bytes) = data_sent.queued(data_taken, bytes_taken);
+            connection.on_send_queue(queued, queued_bytes);
             let now = Instant::now();
+            if let Some(command) = command.take() {
+                handle_command(&mut connection, now, command);
+            }
+            if let Some((time, data, priority)) = data_input.take() {
+                data_taken += 1;
+                bytes_taken += data.len() as u64;
+                connection.handle_prioritized_data_input(now, (time, data), priority);
+            }
             input = match connection.handle_input(now, input) {
                 Action::Close => break,
                 Action::ReleaseData(data) => {
@@ -115,37 +351,92 @@ impl SrtSocketState {
                 Action::SendPacket(packet) => {
                     if let Err(e) = socket.send(packet).await {
                         error!("Error while seding packet: {:?}", e); // TODO: real error handling
+                        publish_error(&status_sender, SrtError::Io(Arc::new(e)));
                     }
                     Input::PacketSent
                 }
                 Action::UpdateStatistics(statistics) => {
+                    #[cfg(feature = "tracing")]
+                    dropped_packets.update(statistics);
                     let _ = statistics_sender.send(statistics.clone());
                     Input::StatisticsUpdated
                 }
                 Action::WaitForData(wait) => {
+                    publish_status(&status_sender, &connection, data_taken);
                     let timeout = now + wait;
+                    let accepting_data = connection.is_accepting_data();
                     select! {
                         _ = sleep_until(timeout.into()).fuse() => Input::Timer,
-                        packet = socket.receive().fuse() =>
-                            Input::Packet(packet),
-                        res = input_data.next() => {
-                            Input::Data(res)
+                        packet = next_packet(&mut socket, socket_closed).fuse() => {
+                            socket_closed |= matches!(packet, Err(ReceivePacketError::Io(_)));
+                            Input::Packet(packet)
+                        }
+                        res = next_input_data(&mut input_data, accepting_data).fuse() => {
+                            match res {
+                                Some(item) => {
+                                    data_input = Some(item);
+                                    Input::Timer
+                                }
+                                None => Input::Data(None),
+                            }
+                        }
+                        next = commands.next() => {
+                            command = next;
+                            Input::Timer
                         }
                     }
                 }
             }
         }
+        while let Some(event) = connection.next_event() {
+            publish_event(&events_sender, event);
+        }
+        publish_closed(&status_sender, &mut connection);
         if let Err(e) = output_data.close().await {
             error!("Error while closing data output stream {:?}", e);
         }
     }
 }
 
+// pending once the socket has closed, or it'd fail again at once while the receiver drains
+async fn next_packet(socket: &mut PacketSocket, closed: bool) -> ReceivePacketResult {
+    if closed {
+        future::pending().await
+    } else {
+        socket.receive().await
+    }
+}
+
+// pending once the input is closed too, like select! skips a terminated stream
+async fn next_input_data(
+    input_data: &mut (impl FusedStream<Item = (Instant, Bytes, Priority)> + Unpin),
+    accepting_data: bool,
+) -> Option<(Instant, Bytes, Priority)> {
+    if accepting_data && !input_data.is_terminated() {
+        input_data.next().await
+    } else {
+        future::pending().await
+    }
+}
+
+fn publish_event(events_sender: &mpsc::UnboundedSender<ConnectionEvent>, event: ConnectionEvent) {
+    #[cfg(feature = "tracing")]
+    trace::connection_event(&event);
+    let _ = events_sender.unbounded_send(event);
+}
+
 #[derive(Debug)]
 pub struct SrtSocketFactory {
-    output_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
-    input_data_sender: mpsc::Sender<(Instant, Bytes)>,
+    output_data_receiver: mpsc::Receiver<(Instant, Bytes, MessageInfo)>,
+    input_data_sender: mpsc::Sender<(Instant, Bytes, Priority)>,
+    data_sent: Arc<DataSent>,
     statistics_receiver: watch::Receiver<SocketStatistics>,
+    status_receiver: watch::Receiver<SocketStatus>,
+    events_receiver: mpsc::UnboundedReceiver<ConnectionEvent>,
+    command_sender: mpsc::UnboundedSender<Command>,
+    raw_tap: RawTap,
+    #[cfg(feature = "test-util")]
+    packet_injector: test_util::PacketInjector,
 }
 
 impl SrtSocketFactory {
@@ -153,57 +444,144 @@ impl SrtSocketFactory {
         SrtSocket {
             settings,
             output_data_receiver: self.output_data_receiver.peekable(),
+            shared_input_data_sender: Arc::new(Mutex::new(self.input_data_sender.clone())),
             input_data_sender: self.input_data_sender,
+            data_sent: self.data_sent,
             statistics_receiver: self.statistics_receiver,
+            status_receiver: self.status_receiver,
+            events_receiver: self.events_receiver,
+            command_sender: self.command_sender,
+            raw_tap: self.raw_tap,
             task,
+            #[cfg(feature = "test-util")]
+            packet_injector: self.packet_injector,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct SrtSocketTaskFactory {
-    output_data_sender: mpsc::Sender<(Instant, Bytes)>,
-    input_data_receiver: mpsc::Receiver<(Instant, Bytes)>,
+    output_data_sender: mpsc::Sender<(Instant, Bytes, MessageInfo)>,
+    input_data_receiver: mpsc::Receiver<(Instant, Bytes, Priority)>,
+    data_sent: Arc<DataSent>,
     statistics_sender: watch::Sender<SocketStatistics>,
+    status_sender: watch::Sender<SocketStatus>,
+    events_sender: mpsc::UnboundedSender<ConnectionEvent>,
+    command_receiver: mpsc::UnboundedReceiver<Command>,
+    resumption: Option<Resumption>,
+    #[cfg(feature = "test-util")]
+    injected_packets: test_util::InjectedPackets,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
 }
 
 impl SrtSocketTaskFactory {
+    /// Sender for events that happen before the task is spawned, e.g. during the handshake
+    pub fn events_sender(&self) -> mpsc::UnboundedSender<ConnectionEvent> {
+        self.events_sender.clone()
+    }
+
+    /// What the connection this one resumes left unacknowledged, to send first
+    pub fn resume(&mut self, resumption: Resumption) {
+        self.resumption = Some(resumption);
+    }
+
+    /// The span the handshake and the connection task run in, `role` is how the socket connects
+    #[cfg(feature = "tracing")]
+    pub fn span(&self, role: &str) -> tracing::Span {
+        self.span.record("role", role);
+        self.span.clone()
+    }
+
     pub fn spawn_task(
         self,
         socket: PacketSocket,
         connection: Connection,
     ) -> (JoinHandle<()>, ConnectionSettings) {
         let settings = connection.settings.clone();
+        #[cfg(feature = "tracing")]
+        let _entered = {
+            trace::record_settings(&self.span, &settings);
+            self.span.enter()
+        };
+        publish_event(
+            &self.events_sender,
+            ConnectionEvent::Connected(Instant::now()),
+        );
+        self.status_sender.send_if_modified(|status| {
+            status.crypto_state = settings.crypto_state();
+            true
+        });
+
+        #[cfg(feature = "test-util")]
+        let socket = test_util::intercept(socket, self.injected_packets);
+
+        let mut connection = DuplexConnection::new(connection);
+        if let Some(resumption) = self.resumption {
+            connection.handle_resumption(Instant::now(), resumption);
+        }
 
         let state = SrtSocketState {
             socket,
-            connection: DuplexConnection::new(connection),
+            connection,
             statistics_sender: self.statistics_sender,
+            status_sender: self.status_sender,
+            events_sender: self.events_sender,
             output_data_sender: self.output_data_sender,
             input_data_receiver: self.input_data_receiver,
+            data_sent: self.data_sent,
+            command_receiver: self.command_receiver,
         };
 
-        let handle = tokio::spawn(async move { state.run_loop().await });
+        let task = async move { state.run_loop().await };
+        #[cfg(feature = "tracing")]
+        let task = task.instrument(self.span.clone());
+        let handle = tokio::spawn(task);
 
         (handle, settings)
     }
 }
 
-pub fn split_new() -> (SrtSocketFactory, SrtSocketTaskFactory) {
+/// `queue_depth` is how many messages the socket's sink can queue up for the task, at least one
+pub fn split_new(raw_tap: RawTap, queue_depth: usize) -> (SrtSocketFactory, SrtSocketTaskFactory) {
     let (output_data_sender, output_data_receiver) = mpsc::channel(128);
-    let (input_data_sender, input_data_receiver) = mpsc::channel(128);
+    // every sender has a slot of its own on top of the buffer
+    let (input_data_sender, input_data_receiver) = mpsc::channel(queue_depth.saturating_sub(1));
+    let data_sent = Arc::new(DataSent::default());
     let (statistics_sender, statistics_receiver) = watch::channel();
+    let (status_sender, status_receiver) = watch::channel();
+    let (events_sender, events_receiver) = mpsc::unbounded();
+    let (command_sender, command_receiver) = mpsc::unbounded();
+    let _ = events_sender.unbounded_send(ConnectionEvent::Connecting(Instant::now()));
+    #[cfg(feature = "test-util")]
+    let (packet_injector, injected_packets) = test_util::channel();
 
     let socket_factory = SrtSocketFactory {
         output_data_receiver,
         input_data_sender,
+        data_sent: data_sent.clone(),
         statistics_receiver,
+        status_receiver,
+        events_receiver,
+        command_sender,
+        raw_tap,
+        #[cfg(feature = "test-util")]
+        packet_injector,
     };
 
     let state_factory = SrtSocketTaskFactory {
         output_data_sender,
         input_data_receiver,
+        data_sent,
         statistics_sender,
+        status_sender,
+        events_sender,
+        command_receiver,
+        resumption: None,
+        #[cfg(feature = "test-util")]
+        injected_packets,
+        #[cfg(feature = "tracing")]
+        span: trace::connection_span(),
     };
 
     (socket_factory, state_factory)
@@ -11,20 +11,30 @@ use srt_protocol::{
 
 use crate::net::PacketSocket;
 
+use super::HandshakeEvents;
+
 pub async fn bind_with(
-    mut socket: PacketSocket,
+    socket: &mut PacketSocket,
     options: Valid<ListenerOptions>,
-) -> Result<(PacketSocket, Connection), io::Error> {
+    events: &mut HandshakeEvents,
+) -> Result<Connection, io::Error> {
     let init_settings: ConnInitSettings = options.socket.clone().into();
     let socket_id = init_settings.local_sockid;
+    #[cfg(feature = "tracing")]
+    super::trace::record_handshake(socket_id, None);
 
     let mut listen = Listen::new(init_settings, false);
     loop {
         let packet = socket.receive().await;
         debug!("{:?}:listen  - {:?}", socket_id, packet);
 
-        let result = listen.handle_packet(Instant::now(), packet);
+        let now = Instant::now();
+        let received = packet.as_ref().ok().map(|(packet, _)| packet.clone());
+        let result = listen.handle_packet(now, packet);
         debug!("{:?}:listen  - {:?}", socket_id, result);
+        events.handshake(now, received.as_ref(), &result);
+        #[cfg(feature = "tracing")]
+        super::trace::handshake(&result);
 
         use ConnectionResult::*;
         match result {
@@ -34,12 +44,17 @@ pub async fn bind_with(
             NotHandled(e) => {
                 warn!("{:?}", e);
             }
-            Reject(_, _) => todo!(),
+            Reject(packet, reason) => {
+                if let Some(packet) = packet {
+                    let _ = socket.send(packet).await?;
+                }
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason));
+            }
             Connected(p, connection) => {
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
                 }
-                return Ok((socket, connection));
+                return Ok(connection);
             }
             NoAction => {}
             RequestAccess(_) => {}
@@ -1,51 +1,81 @@
+mod auto;
 mod builder;
 mod call;
 mod listen;
+mod pair;
+mod polled;
 mod rendezvous;
+#[cfg(feature = "test-util")]
+mod test_util;
+#[cfg(feature = "tracing")]
+mod trace;
 
 pub(crate) mod factory;
 
 use std::{
+    convert::TryInto,
     fmt::Debug,
     io,
+    net::SocketAddr,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use bytes::Bytes;
-use futures::{
-    channel::mpsc::{self, TrySendError},
-    prelude::*,
-    ready,
-    stream::Peekable,
-};
+use futures::{channel::mpsc, lock::Mutex, prelude::*, ready, stream::Peekable};
 use srt_protocol::{
-    connection::ConnectionSettings,
-    options::{OptionsError, OptionsOf, SocketOptions, Validation},
+    connection::{Connection, ConnectionSettings, Resumption},
+    options::{
+        OptionsError, OptionsOf, Passphrase, SocketAddress, SocketOptions, Valid, Validation,
+    },
+    packet::{Packet, SessionResume, SocketId, SrtShakeFlags},
+    protocol::pending_connection::ConnectionResult,
 };
-use tokio::{net::UdpSocket, task::JoinHandle};
+use tokio::{net::UdpSocket, task::JoinHandle, time::timeout};
 
-use super::{net::*, options::BindOptions, watch};
+use super::{net::*, options::BindOptions, tap::RawTap, watch, SrtError};
 
 pub use builder::SrtSocketBuilder;
-pub use srt_protocol::statistics::SocketStatistics;
+pub use polled::PolledSrtSocket;
+pub use srt_protocol::{
+    connection::{ConnectionEvent, CryptoState, MessageInfo, Priority},
+    statistics::SocketStatistics,
+};
+
+/// Hears of each event of the handshake as it happens, see
+/// [`SrtSocketBuilder::on_handshake_event`]
+pub type HandshakeCallback = Box<dyn FnMut(ConnectionEvent) + Send>;
 
 /// Connected SRT connection, generally created with [`SrtSocketBuilder`](crate::SrtSocketBuilder).
 ///
 /// These are bidirectional sockets, meaning data can be sent in either direction.
-/// Use the `Stream + Sink` implementation to send or receive data.
+/// Use the `Stream + Sink` implementation to send or receive data, or [`recv`](SrtSocket::recv)
+/// and [`send_data`](SrtSocket::send_data) from within a `select!`.
 ///
 /// The sockets yield and consume `(Instant, Bytes)`, representing the data and the origin instant. This instant
 /// defines when the packet will be released on the receiving side, at more or less one latency later.
+/// Data that should go out ahead of the rest while it waits to be sent is sent with
+/// [`send_prioritized`](SrtSocket::send_prioritized) instead.
 #[derive(Debug)]
 pub struct SrtSocket {
-    output_data_receiver: Peekable<mpsc::Receiver<(Instant, Bytes)>>,
-    input_data_sender: mpsc::Sender<(Instant, Bytes)>,
+    output_data_receiver: Peekable<mpsc::Receiver<(Instant, Bytes, MessageInfo)>>,
+    input_data_sender: mpsc::Sender<(Instant, Bytes, Priority)>,
+    // used by send_data, whose futures don't borrow the socket. Every clone of a sender comes
+    // with a slot of its own, so a single long-lived one keeps the channel's back pressure.
+    shared_input_data_sender: Arc<Mutex<mpsc::Sender<(Instant, Bytes, Priority)>>>,
+    // the messages put into the input data channel, for flushed to wait for the task to take
+    data_sent: Arc<factory::DataSent>,
     statistics_receiver: watch::Receiver<SocketStatistics>,
+    status_receiver: watch::Receiver<factory::SocketStatus>,
+    events_receiver: mpsc::UnboundedReceiver<ConnectionEvent>,
+    command_sender: mpsc::UnboundedSender<factory::Command>,
+    raw_tap: RawTap,
     settings: ConnectionSettings,
     task: JoinHandle<()>,
+    #[cfg(feature = "test-util")]
+    packet_injector: test_util::PacketInjector,
 }
 
 impl SrtSocket {
@@ -54,9 +84,129 @@ impl SrtSocket {
     }
 
     pub fn try_send(&mut self, srctime: Instant, data: Bytes) -> Result<(), (Instant, Bytes)> {
+        let bytes = data.len();
         self.input_data_sender
-            .try_send((srctime, data))
-            .map_err(TrySendError::into_inner)
+            .try_send((srctime, data, Priority::Normal))
+            .map_err(|e| {
+                let (srctime, data, _) = e.into_inner();
+                (srctime, data)
+            })?;
+        self.data_sent.add(bytes);
+        Ok(())
+    }
+
+    /// The next payload received, or `None` once the connection is closed. A shorthand for
+    /// [`StreamExt::next`] for use in `select!`, where the data is the same as what the stream
+    /// yields.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. If the future is dropped before it completes, no data was
+    /// taken off the socket and the next call returns it.
+    pub async fn recv(&mut self) -> Option<(Instant, Bytes)> {
+        let (time, data, _) = self.output_data_receiver.next().await?;
+        Some((time, data))
+    }
+
+    /// Like [`recv`](SrtSocket::recv), along with the message number of the data and where it
+    /// is in the message, which with [`RecvGranularity::Payload`](crate::options::RecvGranularity)
+    /// tells the payloads of one message apart from the next.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, like [`recv`](SrtSocket::recv).
+    pub async fn recv_with_info(&mut self) -> Option<(Instant, Bytes, MessageInfo)> {
+        self.output_data_receiver.next().await
+    }
+
+    /// Like [`recv`](SrtSocket::recv), copying the payload into `buf` instead of handing over a
+    /// [`Bytes`] of its own, so a buffer the application reuses is all it needs to receive into.
+    /// Returns the origin time and the length of the payload.
+    ///
+    /// A payload longer than `buf` fails with [`io::ErrorKind::InvalidInput`] and stays on the
+    /// socket, to be received with a larger buffer or with [`recv`](SrtSocket::recv).
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe, like [`recv`](SrtSocket::recv).
+    pub async fn recv_into(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Option<Result<(Instant, usize), io::Error>> {
+        let mut receiver = Pin::new(&mut self.output_data_receiver);
+        let (_, data, _) = receiver.as_mut().peek().await?;
+        if data.len() > buf.len() {
+            return Some(Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "payload of {} bytes doesn't fit into a buffer of {} bytes",
+                    data.len(),
+                    buf.len()
+                ),
+            )));
+        }
+        let (time, data, _) = receiver.next().await?;
+        buf[..data.len()].copy_from_slice(&data);
+        Some(Ok((time, data.len())))
+    }
+
+    /// Sends `data` with the current time as its origin instant, completing once it's buffered
+    /// for sending. The future doesn't borrow the socket, so it can be kept across iterations of
+    /// a `select!` loop that receives on the same socket:
+    ///
+    /// ```no_run
+    /// # use srt_tokio::SrtSocket;
+    /// # use bytes::Bytes;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), std::io::Error> {
+    /// let mut socket = SrtSocket::builder().call("127.0.0.1:3333", None).await?;
+    /// let sending = socket.send_data(Bytes::from("hello"));
+    /// tokio::pin!(sending);
+    /// loop {
+    ///     tokio::select! {
+    ///         result = &mut sending => break result?,
+    ///         Some((_, data)) = socket.recv() => println!("{data:?}"),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is cancel safe. The data is buffered in the same poll the future completes
+    /// in, so if it's dropped before completing, the data was not sent and can be sent again
+    /// without being duplicated.
+    pub fn send_data(&self, data: Bytes) -> impl Future<Output = Result<(), io::Error>> {
+        self.send_prioritized((Instant::now(), data), Priority::Normal)
+    }
+
+    /// Sends data with a priority, completing once it's buffered for sending. While data waits to
+    /// be sent, e.g. under a bandwidth cap, what has a higher priority goes out first, and is
+    /// retransmitted first, see [`Priority`]. Like [`send_data`](SrtSocket::send_data), the future
+    /// doesn't borrow the socket, and it's cancel safe.
+    pub fn send_prioritized(
+        &self,
+        (time, data): (Instant, Bytes),
+        priority: Priority,
+    ) -> impl Future<Output = Result<(), io::Error>> {
+        let item = (time, data, priority);
+        let checked = check_message_size(&self.settings, &item.1);
+        let sender = self.shared_input_data_sender.clone();
+        let data_sent = self.data_sent.clone();
+        async move {
+            checked?;
+            let bytes = item.1.len();
+            let mut sender = sender.lock().await;
+            let not_connected = |e| io::Error::new(io::ErrorKind::NotConnected, e);
+            future::poll_fn(|cx| sender.poll_ready(cx))
+                .await
+                .map_err(not_connected)?;
+            // nothing is awaited from here on, so the data can't be buffered without completing
+            sender.start_send(item).map_err(not_connected)?;
+            data_sent.add(bytes);
+            Ok(())
+        }
     }
 
     pub fn with<O>(options: O) -> SrtSocketBuilder
@@ -67,32 +217,179 @@ impl SrtSocket {
         Self::builder().with(options)
     }
 
+    /// Calls `remote` with a complete set of options, for when the same options are used for many
+    /// connections, e.g. loaded from a configuration file with the `serde` feature.
+    pub async fn connect_with_options(
+        remote: impl TryInto<SocketAddress>,
+        options: &SocketOptions,
+    ) -> Result<Self, io::Error> {
+        Self::builder()
+            .set(|socket| *socket = options.clone())
+            .call(remote, None)
+            .await
+    }
+
     pub async fn bind(options: BindOptions) -> Result<Self, io::Error> {
+        Self::bind_with(options, None, RawTap::default(), None, None).await
+    }
+
+    async fn bind_with(
+        options: BindOptions,
+        socket: Option<UdpSocket>,
+        raw_tap: RawTap,
+        resumption: Option<Resumption>,
+        on_handshake_event: Option<HandshakeCallback>,
+    ) -> Result<Self, io::Error> {
+        let socket = Self::bind_udp(&options, socket, raw_tap.clone()).await?;
+        Self::bind_packet_socket(options, socket, raw_tap, resumption, on_handshake_event).await
+    }
+
+    async fn bind_udp(
+        options: &BindOptions,
+        socket: Option<UdpSocket>,
+        raw_tap: RawTap,
+    ) -> Result<PacketSocket, io::Error> {
+        let socket_options = Self::socket_options(options);
+        let socket = match socket {
+            Some(socket) => socket,
+            None => bind_socket(socket_options).await?,
+        };
+
+        let socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024).with_tap(raw_tap);
+        #[cfg(feature = "strict-handshake")]
+        let socket = socket.with_strict_handshake(socket_options.connect.strict_handshake);
+        Ok(match socket_options.connect.udp_read_loop {
+            Some(read_loop) => socket.with_read_loop(read_loop),
+            None => socket,
+        })
+    }
+
+    fn socket_options(options: &BindOptions) -> &SocketOptions {
         use BindOptions::*;
-        let socket_options = match &options {
+        match options {
             Listen(options) => &options.socket,
             Call(options) => &options.socket,
             Rendezvous(options) => &options.socket,
-        };
-        let socket = bind_socket(socket_options).await?;
-        Self::bind_with_socket(options, socket).await
+            Auto(options) => &options.socket,
+        }
     }
 
-    async fn bind_with_socket(options: BindOptions, socket: UdpSocket) -> Result<Self, io::Error> {
-        let socket = PacketSocket::from_socket(Arc::new(socket), 1024 * 1024);
+    /// Connects over `transport` instead of a UDP socket, e.g. an in-process channel or a
+    /// tunnel. The handshake and the connection run exactly as they would over UDP, the
+    /// addresses are whatever the transport reports packets to come from and sends them to.
+    ///
+    /// ```no_run
+    /// # use std::{io, net::SocketAddr};
+    /// # use futures::prelude::*;
+    /// # use srt_protocol::packet::Packet;
+    /// # use srt_tokio::{options::*, SrtSocket};
+    /// # async fn connect(
+    /// #     transport: impl Stream<Item = (Packet, SocketAddr)>
+    /// #         + Sink<(Packet, SocketAddr), Error = io::Error>
+    /// #         + Send
+    /// #         + 'static,
+    /// # ) -> Result<(), io::Error> {
+    /// let options = CallerOptions::new("127.0.0.1:3000", None)?;
+    /// let socket = SrtSocket::with_transport(transport, options.into()).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Panics:
+    /// * There is no tokio runtime
+    pub async fn with_transport<T>(transport: T, options: BindOptions) -> Result<Self, io::Error>
+    where
+        T: Stream<Item = (Packet, SocketAddr)>
+            + Sink<(Packet, SocketAddr), Error = io::Error>
+            + Send
+            + 'static,
+    {
+        let socket = PacketSocket::from_transport(transport, 1024);
+        Self::bind_packet_socket(options, socket, RawTap::default(), None, None).await
+    }
 
+    async fn bind_packet_socket(
+        options: BindOptions,
+        socket: PacketSocket,
+        raw_tap: RawTap,
+        resumption: Option<Resumption>,
+        on_handshake_event: Option<HandshakeCallback>,
+    ) -> Result<Self, io::Error> {
+        #[cfg(feature = "tracing")]
         use BindOptions::*;
-        let (socket, connection) = match options {
-            Listen(options) => listen::bind_with(socket, options).await?,
-            Call(options) => call::bind_with(socket, options).await?,
-            Rendezvous(options) => rendezvous::bind_with(socket, options).await?,
+        let queue_depth = Self::socket_options(&options).sender.queue_depth;
+        let (new_socket, mut new_state) = factory::split_new(raw_tap, queue_depth);
+
+        #[cfg(feature = "tracing")]
+        let span = new_state.span(match &options {
+            Listen(_) => "listener",
+            Call(_) => "caller",
+            Rendezvous(_) => "rendezvous",
+            Auto(_) => "auto",
+        });
+        let resume = resumption.as_ref().map(Resumption::session);
+        let events = HandshakeEvents::new(new_state.events_sender(), on_handshake_event);
+        let handshake = Self::handshake(socket, options, events, resume);
+        #[cfg(feature = "tracing")]
+        let handshake = {
+            use tracing::Instrument;
+            handshake
+                .instrument(trace::handshake_span(&span))
+                .instrument(span)
         };
+        let (socket, connection) = handshake.await?;
 
-        let (new_socket, new_state) = factory::split_new();
+        if let Some(resumption) = resumption {
+            new_state.resume(resumption);
+        }
         let (task, settings) = new_state.spawn_task(socket, connection);
-        let socket = new_socket.create_socket(settings, task);
+        Ok(new_socket.create_socket(settings, task))
+    }
+
+    async fn handshake(
+        mut socket: PacketSocket,
+        options: BindOptions,
+        mut events: HandshakeEvents,
+        resume: Option<SessionResume>,
+    ) -> Result<(PacketSocket, Connection), io::Error> {
+        use BindOptions::*;
+        let events = &mut events;
+        let connection = match options {
+            Listen(options) => listen::bind_with(&mut socket, options, events).await?,
+            Call(options) => call::bind_with(&mut socket, options, events, resume).await?,
+            Rendezvous(options) => rendezvous::bind_with(&mut socket, options, events).await?,
+            Auto(options) => auto::bind_with(&mut socket, options, events).await?,
+        };
+        #[cfg(feature = "tracing")]
+        trace::record_settings(&tracing::Span::current(), &connection.settings);
+        events.connected(Instant::now());
+        Ok((socket, connection))
+    }
+
+    /// Creates two sockets connected to each other over an in-memory transport, skipping the
+    /// handshake and the network entirely. Useful for testing code that consumes an [`SrtSocket`].
+    ///
+    /// # Panics:
+    /// * There is no tokio runtime
+    pub fn pair() -> (Self, Self) {
+        Self::bind_pair(SocketOptions::new())
+    }
+
+    fn bind_pair(options: Valid<SocketOptions>) -> (Self, Self) {
+        let queue_depth = options.sender.queue_depth;
+        let ((socket_a, connection_a), (socket_b, connection_b)) = pair::bind_pair(options);
+        (
+            Self::spawn(socket_a, connection_a, queue_depth),
+            Self::spawn(socket_b, connection_b, queue_depth),
+        )
+    }
 
-        Ok(socket)
+    fn spawn(socket: PacketSocket, connection: Connection, queue_depth: usize) -> Self {
+        let (new_socket, new_state) = factory::split_new(RawTap::default(), queue_depth);
+        #[cfg(feature = "tracing")]
+        new_state.span("pair");
+        let (task, settings) = new_state.spawn_task(socket, connection);
+        new_socket.create_socket(settings, task)
     }
 
     pub async fn close_and_finish(&mut self) -> Result<(), io::Error> {
@@ -101,16 +398,20 @@ impl SrtSocket {
         Ok(())
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn split_mut(
         &mut self,
     ) -> (
-        Pin<&mut Peekable<impl Stream<Item = (Instant, Bytes)> + Unpin>>,
-        Pin<&mut (impl Sink<(Instant, Bytes), Error = impl Debug> + Unpin)>,
+        Pin<&mut Peekable<impl Stream<Item = (Instant, Bytes, MessageInfo)> + Unpin>>,
+        impl Sink<(Instant, Bytes), Error = impl Debug> + Unpin + '_,
     ) {
-        (
-            Pin::new(&mut self.output_data_receiver),
-            Pin::new(&mut self.input_data_sender),
-        )
+        let data_sent = &self.data_sent;
+        let input_data_sender =
+            (&mut self.input_data_sender).with(move |(time, data): (Instant, Bytes)| {
+                data_sent.add(data.len());
+                future::ready(Ok::<_, mpsc::SendError>((time, data, Priority::Normal)))
+            });
+        (Pin::new(&mut self.output_data_receiver), input_data_sender)
     }
 }
 
@@ -119,16 +420,238 @@ impl SrtSocket {
         &self.settings
     }
 
+    /// The SRT socket id of the peer, as it gave it in the handshake
+    pub fn peer_socket_id(&self) -> SocketId {
+        self.settings.remote_sockid
+    }
+
+    /// The UDP address the peer connected from, or was called at
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.settings.remote
+    }
+
     pub fn statistics(&mut self) -> &mut (impl Stream<Item = SocketStatistics> + Clone) {
         &mut self.statistics_receiver
     }
+
+    /// How long the connection has been up, counting from when the handshake started
+    pub fn uptime(&self) -> Duration {
+        self.settings.socket_start_time.elapsed()
+    }
+
+    /// When the last packet was received from the peer, keepalives included. Once the connection
+    /// is closed, it stays at the last one received.
+    pub fn last_recv(&self) -> Option<Instant> {
+        self.status_receiver.borrow().activity.last_recv
+    }
+
+    /// When the last packet was sent to the peer, whether it carried data or not
+    pub fn last_send(&self) -> Option<Instant> {
+        self.status_receiver.borrow().activity.last_send
+    }
+
+    /// The payload bytes of the data sent that the peer hasn't acknowledged yet, as of the last
+    /// time the connection was serviced. Data still on its way from the socket to the connection
+    /// isn't counted, see [`flushed`](SrtSocket::flushed) to wait for all of it.
+    pub fn unacked_bytes(&self) -> usize {
+        self.status_receiver.borrow().activity.unacked_bytes
+    }
+
+    /// Completes once the peer has acknowledged the last packet of the data sent so far, e.g.
+    /// before closing the connection or switching over to another one. Flushing the [`Sink`] only
+    /// waits for the data to be handed to the connection, this waits for the peer to have it all.
+    /// Data dropped on this side for being too late only counts once the peer acknowledges past
+    /// it, so this doesn't complete while the peer can't be heard from.
+    ///
+    /// Fails if the connection closes before then, e.g. once the peer idle timeout passes.
+    pub async fn flushed(&self) -> Result<(), io::Error> {
+        let sent = self.data_sent.messages();
+        let mut status = self.status_receiver.clone();
+        let written = status
+            .wait_for(|status| {
+                status.activity.data_taken >= sent && status.activity.written_seq_number.is_some()
+            })
+            .await
+            .map(|status| status.activity.written_seq_number);
+        let acked = match written {
+            Ok(Some(written)) => status
+                .wait_for(|status| {
+                    status
+                        .activity
+                        .acked_seq_number
+                        .is_some_and(|acked| acked >= written)
+                })
+                .await
+                .is_ok(),
+            _ => false,
+        };
+        if acked {
+            return Ok(());
+        }
+        Err(self.unacknowledged_error())
+    }
+
+    /// Probes the link with a keepalive, without waiting for data to flow. Completes with the time
+    /// until the peer is next heard from. The peer answers the keepalive right away, so this is
+    /// the round trip on an idle link, but with data flowing a packet that was already on its way
+    /// gets there first, and the time is shorter. [`SocketStatistics::tx_average_rtt`] is the round
+    /// trip measured from the acknowledgements of the data instead.
+    ///
+    /// Fails as unsupported if the peer didn't agree to answer keepalives during the handshake, as
+    /// the reference implementation doesn't. Fails as well if the peer isn't heard from within
+    /// the peer idle timeout, or the connection closes.
+    pub async fn ping(&self) -> Result<Duration, io::Error> {
+        if !self
+            .settings
+            .features
+            .contains(SrtShakeFlags::KEEPALIVE_ECHO)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the peer didn't agree to answer keepalives",
+            ));
+        }
+        let sent = Instant::now();
+        self.send_command(factory::Command::Ping)?;
+        let mut status = self.status_receiver.clone();
+        let answered = async {
+            let answer = status
+                .wait_for(|status| status.activity.last_recv.is_some_and(|recv| recv > sent))
+                .await;
+            answer.map(|status| status.activity.last_recv)
+        };
+        match timeout(self.settings.peer_idle_timeout, answered).await {
+            Ok(Ok(recv)) => Ok(recv.map_or(Duration::ZERO, |recv| recv - sent)),
+            Ok(Err(_)) => Err(match self.last_error() {
+                Some(error) => error.into(),
+                None => io::Error::new(io::ErrorKind::NotConnected, "the connection closed"),
+            }),
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the peer didn't answer the ping",
+            )),
+        }
+    }
+
+    fn send_command(&self, command: factory::Command) -> Result<(), io::Error> {
+        self.command_sender
+            .unbounded_send(command)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+
+    fn unacknowledged_error(&self) -> io::Error {
+        match self.last_error() {
+            Some(error) => error.into(),
+            None => io::Error::new(
+                io::ErrorKind::NotConnected,
+                "the connection closed before the data sent was acknowledged",
+            ),
+        }
+    }
+
+    /// What the peer didn't acknowledge once the connection is closed, to resume it with
+    /// [`SrtSocketBuilder::resume`] on a new connection that carries on the stream without gaps.
+    /// None while the connection is open, or if it isn't resumable, see
+    /// [`SrtSocketBuilder::resumption_window`].
+    pub fn resumption(&self) -> Option<Resumption> {
+        self.status_receiver.borrow().resumption.clone()
+    }
+
+    /// Whether the connection is encrypted, and with keys of what size. Goes bad while the peer
+    /// sends keys wrapped with a passphrase other than this side's.
+    pub fn crypto_state(&self) -> CryptoState {
+        self.status_receiver.borrow().crypto_state
+    }
+
+    /// What went wrong last: why the connection ended if the peer ended it, by shutting it down or
+    /// going quiet for longer than the peer idle timeout, or else the last error sending to the
+    /// network. `None` while nothing did, including once the connection is closed from this side.
+    pub fn last_error(&self) -> Option<SrtError> {
+        self.status_receiver.borrow().error.clone()
+    }
+
+    /// Lifecycle events of the connection, starting with the ones that happened during the
+    /// handshake. The stream ends once the connection is closed.
+    pub fn events(&mut self) -> &mut (impl Stream<Item = ConnectionEvent> + Unpin) {
+        &mut self.events_receiver
+    }
+
+    /// Asks the peer to use a new latency for the data it sends to this socket. Once the peer
+    /// confirms, the receive buffer moves towards the new latency gradually, so delivery slows
+    /// down or speeds up slightly for a while instead of stalling or bursting. Packets already
+    /// buffered are not dropped when the latency is lowered.
+    ///
+    /// The latency in effect is reported in [`SocketStatistics::rx_tsbpd_delay`]; [`settings`]
+    /// keeps the value negotiated during the handshake.
+    ///
+    /// Fails as unsupported if the peer didn't agree to latency changes during the handshake, as
+    /// the reference implementation doesn't.
+    ///
+    /// [`settings`]: SrtSocket::settings
+    pub fn set_latency(&mut self, latency: Duration) -> Result<(), io::Error> {
+        if !self
+            .settings
+            .features
+            .contains(SrtShakeFlags::LATENCY_CHANGE)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the peer didn't agree to latency changes",
+            ));
+        }
+        self.send_command(factory::Command::SetLatency(latency))
+    }
+
+    /// Switches to keys wrapped with a new passphrase without reconnecting. A key refresh starts
+    /// right away, and data keeps being sent with the current key until the peer confirms the new
+    /// keys, which it can only do once it has the new passphrase too. Until then, the keys are sent
+    /// again regularly, so it doesn't matter which side changes the passphrase first.
+    ///
+    /// Fails if the connection isn't encrypted, or the passphrase isn't 10 to 79 characters long.
+    pub fn set_passphrase(&mut self, passphrase: impl Into<String>) -> Result<(), io::Error> {
+        if self.settings.cipher.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the connection is not encrypted",
+            ));
+        }
+        let passphrase = Passphrase::try_from(passphrase.into())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        self.send_command(factory::Command::SetPassphrase(passphrase))
+    }
+
+    /// Stops sending data until [`resume`](SrtSocket::resume) is called. Data sent to the socket
+    /// meanwhile is buffered, subject to the send buffer and its latency like any other, and the
+    /// connection is kept alive: ACKs, NAKs and keepalives still go both ways, and data from the
+    /// peer is still received.
+    pub fn pause(&mut self) -> Result<(), io::Error> {
+        self.send_command(factory::Command::Pause)
+    }
+
+    /// Goes on sending data after [`pause`](SrtSocket::pause). What was buffered meanwhile isn't
+    /// sent in one burst, it's clocked out by the peer's ACKs starting from a small window, the
+    /// same as after being idle.
+    pub fn resume(&mut self) -> Result<(), io::Error> {
+        self.send_command(factory::Command::Resume)
+    }
+
+    /// Every UDP datagram this socket receives from now on, as it arrived, before SRT processing.
+    /// Subscribe through [`SrtSocketBuilder::raw_tap`] instead to see the handshake too, or to count
+    /// the datagrams missed by falling too far behind with [`RawTap::dropped`].
+    ///
+    /// Sockets created with [`SrtSocket::pair`] have no network underneath them, so the stream
+    /// never yields anything.
+    pub fn raw_tap(&self) -> impl Stream<Item = (Bytes, SocketAddr)> + Unpin {
+        self.raw_tap.subscribe()
+    }
 }
 
 impl Stream for SrtSocket {
     type Item = Result<(Instant, Bytes), io::Error>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        Poll::Ready(ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx)).map(Ok))
+        let item = ready!(Pin::new(&mut self.output_data_receiver).poll_next(cx));
+        Poll::Ready(item.map(|(time, data, _)| Ok((time, data))))
     }
 }
 
@@ -142,18 +665,84 @@ impl Sink<(Instant, Bytes)> for SrtSocket {
         .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?))
     }
     fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
+        check_message_size(&self.settings, &item.1)?;
+        let (time, data) = item;
+        let bytes = data.len();
         self.input_data_sender
-            .start_send(item)
-            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+            .start_send((time, data, Priority::Normal))
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+        self.data_sent.add(bytes);
+        Ok(())
     }
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.input_data_sender)
-            .poll_flush(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+        ready!(Pin::new(&mut self.input_data_sender).poll_flush(cx))
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))?;
+        // the channel flushes fine once the connection is gone, but what's buffered won't be sent
+        if self.input_data_sender.is_closed() {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "the connection is closed",
+            )));
+        }
+        Poll::Ready(Ok(()))
     }
-    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
-        Pin::new(&mut self.input_data_sender)
-            .poll_close(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        // closes the channel for the sender send_data uses as well, what's already buffered is
+        // still sent
+        self.input_data_sender.close_channel();
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn check_message_size(settings: &ConnectionSettings, data: &Bytes) -> Result<(), io::Error> {
+    let max_message_size = settings.max_message_size();
+    if data.len() as u64 > max_message_size.0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "message of {} bytes exceeds the maximum message size of {}",
+                data.len(),
+                max_message_size
+            ),
+        ));
+    }
+    Ok(())
+}
+
+// where the events of the handshake go: the events stream of the socket it makes, and the callback,
+// which hears of the handshakes that fail as well
+struct HandshakeEvents {
+    stream: mpsc::UnboundedSender<ConnectionEvent>,
+    callback: Option<HandshakeCallback>,
+}
+
+impl HandshakeEvents {
+    fn new(
+        stream: mpsc::UnboundedSender<ConnectionEvent>,
+        callback: Option<HandshakeCallback>,
+    ) -> Self {
+        Self { stream, callback }
+    }
+
+    fn publish(&mut self, event: ConnectionEvent) {
+        #[cfg(feature = "tracing")]
+        trace::connection_event(&event);
+        if let Some(callback) = &mut self.callback {
+            callback(event);
+        }
+        let _ = self.stream.unbounded_send(event);
+    }
+
+    fn handshake(&mut self, now: Instant, received: Option<&Packet>, result: &ConnectionResult) {
+        for event in ConnectionEvent::of_handshake(now, received, result) {
+            self.publish(event);
+        }
+    }
+
+    // the connection puts it on the stream itself, once it's running
+    fn connected(&mut self, now: Instant) {
+        if let Some(callback) = &mut self.callback {
+            callback(ConnectionEvent::Connected(now));
+        }
     }
 }
@@ -0,0 +1,92 @@
+use std::{
+    cmp::max,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use srt_protocol::{
+    connection::{Connection, ConnectionSettings},
+    options::*,
+    packet::SeqNumber,
+    protocol::handshake::Handshake,
+    settings::*,
+};
+
+use crate::net::PacketSocket;
+
+// the in-memory transport has no real addresses, but the connection still validates the source
+// of each packet, so each side gets a distinct loopback address
+const LOCAL_A: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 1);
+const LOCAL_B: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 2);
+
+pub fn bind_pair(
+    options: Valid<SocketOptions>,
+) -> ((PacketSocket, Connection), (PacketSocket, Connection)) {
+    let now = Instant::now();
+    let init_seq_num = rand::random();
+
+    let a: ConnInitSettings = options.into_value().into();
+    let b = a.copy_randomize();
+    let cipher = a
+        .key_settings
+        .as_ref()
+        .map(|key_settings| CipherSettings::new_random(key_settings, &a.key_refresh));
+
+    let (socket_a, socket_b) = PacketSocket::pair((LOCAL_A, LOCAL_B), 1024);
+    let connection_a = new_connection(now, init_seq_num, &a, (&b, LOCAL_B), cipher.clone());
+    let connection_b = new_connection(now, init_seq_num, &b, (&a, LOCAL_A), cipher);
+
+    ((socket_a, connection_a), (socket_b, connection_b))
+}
+
+fn new_connection(
+    now: Instant,
+    init_seq_num: SeqNumber,
+    local: &ConnInitSettings,
+    (remote, remote_addr): (&ConnInitSettings, SocketAddr),
+    cipher: Option<CipherSettings>,
+) -> Connection {
+    Connection {
+        settings: ConnectionSettings {
+            remote: remote_addr,
+            remote_sockid: remote.local_sockid,
+            local_sockid: local.local_sockid,
+            socket_start_time: now,
+            rtt: Duration::ZERO,
+            init_seq_num,
+            max_packet_size: local.max_packet_size,
+            max_segment_size: local.max_segment_size,
+            max_flow_size: local.max_flow_size,
+            idle_restart_threshold: local.idle_restart_threshold,
+            max_retransmits: local.max_retransmits,
+            send_tsbpd_latency: max(local.send_latency, remote.recv_latency),
+            recv_tsbpd_latency: max(local.recv_latency, remote.send_latency),
+            too_late_packet_drop: local.too_late_packet_drop,
+            delivery_mode: local.delivery_mode,
+            nak_period: local.nak_period,
+            recv_granularity: local.recv_granularity,
+            peer_idle_timeout: local.peer_idle_timeout,
+            recv_buffer_size: local.recv_buffer_size,
+            recv_buffer_cap: local.recv_buffer_cap,
+            send_buffer_size: local.send_buffer_size,
+            cipher,
+            stream_id: None,
+            bandwidth: local.bandwidth.clone(),
+            retransmit_bandwidth: local.retransmit_bandwidth,
+            send_buffer_full: local.send_buffer_full,
+            flow_window_full: local.flow_window_full,
+            input_overflow: local.input_overflow,
+            statistics_interval: local.statistics_interval,
+            loss_rate_window: local.loss_rate_window,
+            initial_rtt: local.initial_rtt,
+            too_late_drop_margin: local.too_late_drop_margin,
+            peer_version: SrtVersion::CURRENT,
+            features: local.features & remote.features,
+            packet_filter: local.packet_filter.clone().or(remote.packet_filter.clone()),
+            negotiation_adjustments: Vec::new(),
+            session_token: None,
+            resumable_sessions: None,
+        },
+        handshake: Handshake::Connector,
+    }
+}
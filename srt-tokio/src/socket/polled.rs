@@ -0,0 +1,214 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*};
+use log::error;
+use srt_protocol::{
+    connection::{Connection, ConnectionSettings, CryptoState, DuplexConnection},
+    packet::{Packet, ReceivePacketError},
+    statistics::SocketStatistics,
+};
+use tokio::time::{sleep_until, Sleep};
+
+use crate::{net::PacketSocket, options::BindOptions, tap::RawTap, SrtError};
+
+use super::{check_message_size, HandshakeEvents, SrtSocket};
+
+#[cfg(feature = "tracing")]
+use super::trace;
+
+/// An SRT connection that runs entirely within the polls of its `Stream + Sink` implementation,
+/// for where tasks can't be spawned. Unlike [`SrtSocket`], there is no task handling control
+/// packets and timers in the background: ACKs, NAKs, retransmissions and keepalives only happen
+/// while the socket is polled, so keep polling it, typically the stream, for as long as the
+/// connection should stay up.
+///
+/// Only UDP sockets are supported, and the remote address should be an IP address, as host names
+/// are resolved on tokio's blocking thread pool.
+pub struct PolledSrtSocket {
+    socket: PacketSocket,
+    connection: DuplexConnection,
+    timer: Pin<Box<Sleep>>,
+    // polled for sending, but not sent yet
+    outgoing: Option<(Packet, SocketAddr)>,
+    // released, but not taken off the stream yet
+    released: VecDeque<(Instant, Bytes)>,
+    closing: bool,
+    send_error: Option<SrtError>,
+}
+
+impl PolledSrtSocket {
+    /// Binds a UDP socket and connects with `options`, the handshake runs within the returned
+    /// future.
+    pub async fn bind(options: BindOptions) -> Result<Self, io::Error> {
+        let socket = SrtSocket::bind_udp(&options, None, RawTap::default()).await?;
+        // there is no one to report reconnecting to
+        let (events, _) = mpsc::unbounded();
+        let events = HandshakeEvents::new(events, None);
+        let (socket, connection) = SrtSocket::handshake(socket, options, events, None).await?;
+        Ok(Self::new(socket, connection))
+    }
+
+    fn new(socket: PacketSocket, connection: Connection) -> Self {
+        let connection = DuplexConnection::new(connection);
+        Self {
+            socket,
+            timer: Box::pin(sleep_until(connection.next_timer(Instant::now()).into())),
+            connection,
+            outgoing: None,
+            released: VecDeque::new(),
+            closing: false,
+            send_error: None,
+        }
+    }
+
+    pub fn settings(&self) -> &ConnectionSettings {
+        self.connection.settings()
+    }
+
+    /// Whether the connection is encrypted, see [`SrtSocket::crypto_state`]
+    pub fn crypto_state(&self) -> CryptoState {
+        self.connection.crypto_state()
+    }
+
+    /// What went wrong last, see [`SrtSocket::last_error`]
+    pub fn last_error(&self) -> Option<SrtError> {
+        self.connection
+            .close_reason()
+            .map(SrtError::from)
+            .or_else(|| self.send_error.clone())
+    }
+
+    /// The statistics as of the last time the socket was polled
+    pub fn statistics(&self) -> &SocketStatistics {
+        self.connection.statistics()
+    }
+
+    /// Runs the connection until it's waiting on the network or a timer, registering `cx` for
+    /// both
+    fn poll_connection(&mut self, cx: &mut Context) {
+        while self.connection.is_open() {
+            let now = Instant::now();
+            if self.connection.should_update_statistics(now) {
+                self.connection.update_statistics(now);
+            }
+
+            while let Some(packet) = self
+                .outgoing
+                .take()
+                .or_else(|| self.connection.next_packet(now))
+            {
+                match self.socket.poll_send(cx, &packet) {
+                    Poll::Ready(Err(e)) => {
+                        error!("Error while sending packet: {:?}", e);
+                        self.send_error = Some(SrtError::Io(Arc::new(e)));
+                    }
+                    Poll::Ready(Ok(_)) => {}
+                    Poll::Pending => {
+                        self.outgoing = Some(packet);
+                        break;
+                    }
+                }
+            }
+
+            while let Some(data) = self.connection.next_data(now) {
+                self.released.push_back(data);
+            }
+
+            while let Some(_event) = self.connection.next_event() {
+                #[cfg(feature = "tracing")]
+                trace::connection_event(&_event);
+            }
+
+            let timeout = self.connection.check_timers(now);
+            if let Poll::Ready(packet) = self.socket.poll_receive(cx) {
+                // the socket stays ready once it fails, leave it to the timers to close the
+                // connection
+                let failed = matches!(packet, Err(ReceivePacketError::Io(_)));
+                self.connection.handle_packet_input(Instant::now(), packet);
+                if !failed {
+                    continue;
+                }
+            }
+
+            self.timer.as_mut().reset(timeout.into());
+            if self.timer.as_mut().poll(cx).is_pending() {
+                break;
+            }
+        }
+    }
+
+    fn not_connected() -> io::Error {
+        io::Error::new(io::ErrorKind::NotConnected, "the connection is closed")
+    }
+}
+
+impl Stream for PolledSrtSocket {
+    type Item = Result<(Instant, Bytes), io::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.poll_connection(cx);
+        match self.released.pop_front() {
+            Some(data) => Poll::Ready(Some(Ok(data))),
+            None if !self.connection.is_open() => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Sink<(Instant, Bytes)> for PolledSrtSocket {
+    type Error = io::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_connection(cx);
+        if !self.connection.is_open() || self.closing {
+            return Poll::Ready(Err(Self::not_connected()));
+        }
+        // with backpressure, the timers that drain the send buffer wake the task up again
+        if !self.connection.is_accepting_data() {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+    fn start_send(mut self: Pin<&mut Self>, item: (Instant, Bytes)) -> Result<(), Self::Error> {
+        check_message_size(self.settings(), &item.1)?;
+        if !self.connection.is_open() || self.closing {
+            return Err(Self::not_connected());
+        }
+        self.connection
+            .handle_data_input(Instant::now(), Some(item));
+        Ok(())
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_connection(cx);
+        if !self.connection.is_open() {
+            return Poll::Ready(Err(Self::not_connected()));
+        }
+        // the data is buffered in the connection as soon as it's sent to the sink, flushed means
+        // nothing is waiting for the network
+        match self.outgoing {
+            Some(_) => Poll::Pending,
+            None => Poll::Ready(Ok(())),
+        }
+    }
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if !self.closing && self.connection.is_open() {
+            self.closing = true;
+            self.connection.handle_data_input(Instant::now(), None);
+        }
+        // nothing else drives the shutdown
+        self.poll_connection(cx);
+        if self.connection.is_open() {
+            return Poll::Pending;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
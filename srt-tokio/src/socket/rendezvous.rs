@@ -1,7 +1,4 @@
-use std::{
-    io,
-    time::{Duration, Instant},
-};
+use std::{io, time::Instant};
 
 use futures::{prelude::*, select};
 use log::{debug, warn};
@@ -16,25 +13,40 @@ use srt_protocol::{
 
 use crate::net::{lookup_remote_host, PacketSocket};
 
+use super::HandshakeEvents;
+
 pub async fn bind_with(
-    mut socket: PacketSocket,
+    socket: &mut PacketSocket,
     options: Valid<RendezvousOptions>,
-) -> Result<(PacketSocket, Connection), io::Error> {
+    events: &mut HandshakeEvents,
+) -> Result<Connection, io::Error> {
     let local_addr = options.socket.connect.local;
     let remote_public = lookup_remote_host(&options.remote).await?;
     let starting_seqno = rand::random();
     let init_settings: ConnInitSettings = options.socket.clone().into();
     let socket_id = init_settings.local_sockid;
+    #[cfg(feature = "tracing")]
+    super::trace::record_handshake(socket_id, Some(remote_public));
 
-    let mut tick_interval = interval(Duration::from_millis(100));
+    let mut tick_interval = interval(options.socket.connect.handshake_retry_interval);
     let mut rendezvous = Rendezvous::new(local_addr, remote_public, init_settings, starting_seqno);
     loop {
-        let result = select! {
-            now = tick_interval.tick().fuse() => rendezvous.handle_tick(now.into()),
-            packet = socket.receive().fuse() => rendezvous.handle_packet(packet, Instant::now()),
+        let (now, received, result) = select! {
+            now = tick_interval.tick().fuse() => {
+                let now = now.into();
+                (now, None, rendezvous.handle_tick(now))
+            }
+            packet = socket.receive().fuse() => {
+                let now = Instant::now();
+                let received = packet.as_ref().ok().map(|(packet, _)| packet.clone());
+                (now, received, rendezvous.handle_packet(packet, now))
+            }
         };
 
         debug!("{:?}:rendezvous - {:?}", socket_id, result);
+        events.handshake(now, received.as_ref(), &result);
+        #[cfg(feature = "tracing")]
+        super::trace::handshake(&result);
         use ConnectionResult::*;
         match result {
             SendPacket(packet) => {
@@ -43,12 +55,17 @@ pub async fn bind_with(
             NotHandled(e) => {
                 warn!("rendezvous {:?} error: {}", socket_id, e);
             }
-            Reject(_, _) => todo!(),
+            Reject(packet, reason) => {
+                if let Some(packet) = packet {
+                    let _ = socket.send(packet).await?;
+                }
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, reason));
+            }
             Connected(p, connection) => {
                 if let Some(packet) = p {
                     let _ = socket.send(packet).await?;
                 }
-                return Ok((socket, connection));
+                return Ok(connection);
             }
             NoAction => {}
             RequestAccess(_) => {}
@@ -0,0 +1,56 @@
+use std::io;
+
+use futures::{channel::mpsc, prelude::*, select};
+use srt_protocol::packet::{ControlPacket, Packet, ReceivePacketError, ReceivePacketResult};
+
+use crate::{net::PacketSocket, SrtSocket};
+
+pub type PacketInjector = mpsc::Sender<ReceivePacketResult>;
+pub type InjectedPackets = (
+    mpsc::Sender<ReceivePacketResult>,
+    mpsc::Receiver<ReceivePacketResult>,
+);
+
+pub fn channel() -> (PacketInjector, InjectedPackets) {
+    let (sender, receiver) = mpsc::channel(128);
+    (sender.clone(), (sender, receiver))
+}
+
+// forwards everything received on the socket through the injection channel, so the connection
+// sees injected packets interleaved with the ones from the network. The forwarding stops once the
+// connection drops the socket returned, releasing the socket it forwards from.
+pub fn intercept(
+    mut socket: PacketSocket,
+    (mut sender, receiver): InjectedPackets,
+) -> PacketSocket {
+    let (intercepted, stopped) = socket.clone_with_forwarded_stream(receiver);
+    // the receiver reports itself terminated once the sender is dropped, so select! would skip it,
+    // fused it completes with the cancellation instead
+    let mut stopped = stopped.fuse();
+    tokio::spawn(async move {
+        loop {
+            let packet = select! {
+                _ = stopped => break,
+                packet = socket.receive().fuse() => packet,
+            };
+            let closed = matches!(packet, Err(ReceivePacketError::Io(_)));
+            if sender.send(packet).await.is_err() || closed {
+                break;
+            }
+        }
+    });
+    intercepted
+}
+
+impl SrtSocket {
+    /// Delivers `packet` to this socket as if it was sent by the peer, skipping the network.
+    ///
+    /// Only available with the `test-util` feature.
+    pub async fn inject_control(&mut self, packet: ControlPacket) -> Result<(), io::Error> {
+        let from = self.settings.remote;
+        self.packet_injector
+            .send(Ok((Packet::Control(packet), from)))
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::NotConnected, e))
+    }
+}
@@ -0,0 +1,122 @@
+//! Spans and structured events for the optional `tracing` integration. Every connection gets a
+//! `srt_connection` span that the handshake and the connection task run in, so events from
+//! concurrent connections can be told apart by their socket ID, peer address and role. The
+//! handshake runs in an `srt_handshake` span within it, tagged with the socket ID as soon as it's
+//! picked, and with the peer address once it's known.
+
+use std::net::SocketAddr;
+
+use srt_protocol::{
+    connection::{ConnectionEvent, ConnectionSettings},
+    packet::{Packet, SocketId},
+    protocol::pending_connection::ConnectionResult,
+};
+use tracing::{debug, field::Empty, info, info_span, warn, Span};
+
+use crate::SocketStatistics;
+
+pub fn connection_span() -> Span {
+    info_span!(
+        "srt_connection",
+        role = Empty,
+        socket_id = Empty,
+        peer = Empty
+    )
+}
+
+pub fn handshake_span(connection: &Span) -> Span {
+    info_span!(parent: connection, "srt_handshake", socket_id = Empty, peer = Empty)
+}
+
+/// Tags the current span, the handshake's, with the socket and the peer it's shaking hands with,
+/// if it's known already
+pub fn record_handshake(socket_id: SocketId, peer: Option<SocketAddr>) {
+    let span = Span::current();
+    span.record("socket_id", tracing::field::debug(socket_id));
+    if let Some(peer) = peer {
+        span.record("peer", tracing::field::display(peer));
+    }
+}
+
+pub fn record_settings(span: &Span, settings: &ConnectionSettings) {
+    span.record("socket_id", tracing::field::debug(settings.local_sockid));
+    span.record("peer", tracing::field::display(settings.remote));
+}
+
+pub fn handshake(result: &ConnectionResult) {
+    use ConnectionResult::*;
+    match result {
+        SendPacket(packet) => handshake_sent(packet),
+        Connected(packet, connection) => {
+            if let Some(packet) = packet {
+                handshake_sent(packet);
+            }
+            let settings = &connection.settings;
+            info!(
+                peer = %settings.remote,
+                peer_socket_id = ?settings.remote_sockid,
+                send_latency = ?settings.send_tsbpd_latency,
+                recv_latency = ?settings.recv_tsbpd_latency,
+                "handshake completed"
+            );
+        }
+        Reject(packet, reason) => {
+            if let Some(packet) = packet {
+                handshake_sent(packet);
+            }
+            warn!(reason = %reason, "handshake rejected");
+        }
+        Failure(error) => warn!(error = %error, "handshake failed"),
+        NotHandled(_) | NoAction | RequestAccess(_) => {}
+    }
+}
+
+fn handshake_sent((packet, to): &(Packet, SocketAddr)) {
+    if let Some(hs) = packet.control().and_then(|control| control.handshake()) {
+        debug!(shake_type = ?hs.shake_type, to = %to, "handshake sent");
+    }
+}
+
+pub fn connection_event(event: &ConnectionEvent) {
+    use ConnectionEvent::*;
+    match event {
+        Connected(_) => info!("connected"),
+        KeyRefreshed(_) => info!("stream encrypting key refreshed"),
+        Gap(_, packets) => warn!(%packets, "gap in the data delivered"),
+        SendBufferFull(_, packets) => warn!(%packets, "send buffer full, dropped packets"),
+        PeerIdle(_) => warn!("peer idle, closing the connection"),
+        Closed(_) => info!("closed"),
+        Reconnecting(_) => debug!("handshake unanswered, sending it again"),
+        HandshakeReceived(_, shake_type) => debug!(?shake_type, "handshake received"),
+        // the handshake results are traced as they come
+        Connecting(_) | HandshakeSent(..) | Rejected(..) => {}
+    }
+}
+
+/// Reports the packets dropped by TLPKTDROP since the last statistics update
+#[derive(Debug, Default)]
+pub struct DroppedPackets {
+    tx: u64,
+    rx: u64,
+}
+
+impl DroppedPackets {
+    pub fn update(&mut self, statistics: &SocketStatistics) {
+        if statistics.tx_dropped_data > self.tx {
+            warn!(
+                dropped = statistics.tx_dropped_data - self.tx,
+                total = statistics.tx_dropped_data,
+                "sender dropped too late packets"
+            );
+        }
+        if statistics.rx_dropped_data > self.rx {
+            warn!(
+                dropped = statistics.rx_dropped_data - self.rx,
+                total = statistics.rx_dropped_data,
+                "receiver dropped too late packets"
+            );
+        }
+        self.tx = statistics.tx_dropped_data;
+        self.rx = statistics.rx_dropped_data;
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bytes::Bytes;
+use futures::{channel::mpsc, Stream};
+
+// how many datagrams a subscriber is let fall behind before it misses the ones that follow
+const SUBSCRIBER_QUEUE_DEPTH: usize = 1024;
+
+/// A tap on the UDP datagrams received by a socket, before they are parsed or handed to the
+/// connection. Meant for debugging tools and proxies that want to inspect or relay the traffic.
+///
+/// Attach it to a socket with [`SrtSocketBuilder::raw_tap`] to also see the handshake, or
+/// subscribe later through [`SrtSocket::raw_tap`]. The datagrams are only copied while there is
+/// at least one subscriber, and a subscriber that can't keep up never slows down the connection:
+/// once it's too far behind, it misses datagrams until it catches up, and [`dropped`] counts them.
+///
+/// [`dropped`]: RawTap::dropped
+/// [`SrtSocketBuilder::raw_tap`]: crate::SrtSocketBuilder::raw_tap
+/// [`SrtSocket::raw_tap`]: crate::SrtSocket::raw_tap
+#[derive(Clone, Debug, Default)]
+pub struct RawTap(Arc<Subscribers>);
+
+#[derive(Debug, Default)]
+struct Subscribers {
+    active: AtomicBool,
+    dropped: AtomicU64,
+    senders: Mutex<Vec<mpsc::Sender<(Bytes, SocketAddr)>>>,
+}
+
+impl RawTap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every datagram received from now on, with the address it was received from. The stream
+    /// ends once the socket is closed and every handle to the tap is dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = (Bytes, SocketAddr)> + Unpin {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_QUEUE_DEPTH);
+        let mut senders = self.0.senders.lock().unwrap();
+        senders.push(sender);
+        self.0.active.store(true, Ordering::Release);
+        receiver
+    }
+
+    /// How many datagrams subscribers missed because they fell too far behind, over all of them
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn observe(&self, datagram: &[u8], from: SocketAddr) {
+        if !self.0.active.load(Ordering::Acquire) {
+            return;
+        }
+
+        let datagram = Bytes::copy_from_slice(datagram);
+        let mut senders = self.0.senders.lock().unwrap();
+        senders.retain_mut(|sender| match sender.try_send((datagram.clone(), from)) {
+            Ok(()) => true,
+            Err(e) if e.is_full() => {
+                self.0.dropped.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(_) => false,
+        });
+        if senders.is_empty() {
+            self.0.active.store(false, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn copies_only_while_subscribed() {
+        let from = "127.0.0.1:1234".parse().unwrap();
+        let tap = RawTap::new();
+        tap.observe(b"before", from);
+
+        let mut first = tap.subscribe();
+        let second = tap.subscribe();
+        tap.observe(b"both", from);
+        drop(second);
+        tap.observe(b"first", from);
+        assert!(tap.0.active.load(Ordering::Acquire));
+
+        assert_eq!(
+            first.by_ref().take(2).collect::<Vec<_>>().await,
+            [
+                (Bytes::from_static(b"both"), from),
+                (Bytes::from_static(b"first"), from)
+            ]
+        );
+
+        drop(first);
+        tap.observe(b"after", from);
+        assert!(!tap.0.active.load(Ordering::Acquire));
+        assert!(tap.0.senders.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn drops_what_a_subscriber_falls_behind_on() {
+        let from = "127.0.0.1:1234".parse().unwrap();
+        let tap = RawTap::new();
+        let mut subscriber = tap.subscribe();
+
+        let datagrams = (0..SUBSCRIBER_QUEUE_DEPTH + 10)
+            .map(|i| Bytes::from(i.to_string()))
+            .collect::<Vec<_>>();
+        for datagram in &datagrams {
+            tap.observe(datagram, from);
+        }
+        // the channel holds one more for its only sender
+        assert_eq!(tap.dropped(), 9);
+
+        let received = subscriber
+            .by_ref()
+            .take(SUBSCRIBER_QUEUE_DEPTH + 1)
+            .map(|(datagram, _)| datagram)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(received, datagrams[..SUBSCRIBER_QUEUE_DEPTH + 1]);
+
+        // and gets what follows once it caught up
+        tap.observe(b"caught up", from);
+        assert_eq!(
+            subscriber.next().await,
+            Some((Bytes::from_static(b"caught up"), from))
+        );
+        assert_eq!(tap.dropped(), 9);
+    }
+}
@@ -5,7 +5,11 @@ use std::{
 };
 
 use futures::Stream;
-use tokio::sync::watch::{self, error::SendError};
+use tokio::sync::watch::{
+    self,
+    error::{RecvError, SendError},
+    Ref,
+};
 use tokio_stream::wrappers::WatchStream;
 
 pub fn channel<T: 'static + Debug + Default + Clone + Send + Sync + Unpin>(
@@ -22,6 +26,11 @@ impl<T: 'static + Debug + Default + Clone + Send + Sync + Unpin> Sender<T> {
     pub fn send(&self, item: T) -> Result<(), SendError<T>> {
         self.0.send(item)
     }
+
+    /// Changes the value in place, the receivers only hear of it if `modify` returns true
+    pub fn send_if_modified(&self, modify: impl FnOnce(&mut T) -> bool) -> bool {
+        self.0.send_if_modified(modify)
+    }
 }
 
 #[derive(Debug)]
@@ -30,6 +39,17 @@ pub struct Receiver<T: 'static + Debug + Default + Clone + Send + Sync + Unpin>(
     WatchStream<T>,
 );
 
+impl<T: 'static + Debug + Default + Clone + Send + Sync + Unpin> Receiver<T> {
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.0.borrow()
+    }
+
+    /// Waits for the value to satisfy `f`, fails once the sender is dropped without it doing so
+    pub async fn wait_for(&mut self, f: impl FnMut(&T) -> bool) -> Result<Ref<'_, T>, RecvError> {
+        self.0.wait_for(f).await
+    }
+}
+
 impl<T: 'static + Debug + Default + Clone + Send + Sync + Unpin> Clone for Receiver<T> {
     fn clone(&self) -> Self {
         let stream = WatchStream::new(self.0.clone());
@@ -1,4 +1,7 @@
-use std::{io, time::Instant};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
 
 use assert_matches::assert_matches;
 use bytes::Bytes;
@@ -181,3 +184,65 @@ async fn set_password() {
     server.close().await;
     listener.await.unwrap();
 }
+
+#[tokio::test]
+async fn per_stream_latency() {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut server, mut incoming) = SrtListener::builder()
+        .on_accept(|request| {
+            let mut parameters = AcceptParameters::new();
+            match request.stream_id().map(|id| id.as_str()) {
+                Some("low") => parameters.set_latency(Duration::from_millis(200)),
+                Some("high") => parameters.set_latency(Duration::from_millis(800)),
+                _ => return Err(ServerRejectReason::Forbidden.into()),
+            };
+            Ok(parameters)
+        })
+        .bind(2810)
+        .await
+        .unwrap();
+
+    let listener = tokio::spawn(async move {
+        let mut accepted = vec![];
+        while let Some(request) = incoming.incoming().next().await {
+            let stream_id = request.stream_id().unwrap().to_string();
+            let socket = request.accept(None).await.unwrap();
+            accepted.push((stream_id, socket.settings().clone()));
+            if accepted.len() == 2 {
+                break;
+            }
+        }
+        accepted
+    });
+
+    let call = |stream_id| {
+        SrtSocket::builder()
+            .latency(Duration::from_millis(20))
+            .call("127.0.0.1:2810", Some(stream_id))
+    };
+    let low = call("low").await.unwrap();
+    let high = call("high").await.unwrap();
+    let err = call("other").await.unwrap_err();
+    assert_eq!(
+        err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>()),
+        Some(Some(&ConnectionReject::Rejected(
+            ServerRejectReason::Forbidden.into()
+        )))
+    );
+
+    // the caller's latency is lower, so each connection goes with the one assigned to its stream
+    for (socket, latency) in [(&low, 200), (&high, 800)] {
+        let latency = Duration::from_millis(latency);
+        assert_eq!(socket.settings().send_tsbpd_latency, latency);
+        assert_eq!(socket.settings().recv_tsbpd_latency, latency);
+    }
+    let accepted = listener.await.unwrap();
+    for (stream_id, settings) in accepted {
+        let latency = Duration::from_millis(if stream_id == "low" { 200 } else { 800 });
+        assert_eq!(settings.send_tsbpd_latency, latency);
+        assert_eq!(settings.recv_tsbpd_latency, latency);
+    }
+
+    server.close().await;
+}
@@ -0,0 +1,186 @@
+use std::{io, time::Instant};
+
+use bytes::Bytes;
+use futures::{
+    future::{select, Either},
+    join, SinkExt, StreamExt,
+};
+use tokio::spawn;
+
+use srt_protocol::{packet::ServerRejectReason, protocol::pending_connection::ConnectionReject};
+use srt_tokio::{
+    options::{Credentials, SigningKey},
+    SrtIncoming, SrtListener, SrtSocket,
+};
+
+fn encoder_key() -> SigningKey {
+    SigningKey::from_bytes(&[1; 32])
+}
+
+fn decoder_key() -> SigningKey {
+    SigningKey::from_bytes(&[2; 32])
+}
+
+fn encoder() -> Credentials {
+    Credentials::new("encoder", encoder_key()).trust("decoder", decoder_key().verifying_key())
+}
+
+fn decoder() -> Credentials {
+    Credentials::new("decoder", decoder_key()).trust("encoder", encoder_key().verifying_key())
+}
+
+// accepts every request, the credentials decide who gets in
+async fn accept_all(mut incoming: SrtIncoming) {
+    while let Some(request) = incoming.incoming().next().await {
+        let _ = request.accept(None).await;
+    }
+}
+
+fn reject_reason(err: &io::Error) -> Option<Option<&ConnectionReject>> {
+    err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>())
+}
+
+#[tokio::test]
+async fn mutual() {
+    let _ = pretty_env_logger::try_init();
+
+    let (_server, mut incoming) = SrtListener::builder()
+        .credentials(decoder())
+        .bind(3060)
+        .await
+        .unwrap();
+    let listener = spawn(async move {
+        let request = incoming.incoming().next().await.unwrap();
+        let mut socket = request.accept(None).await.unwrap();
+        socket.next().await.unwrap().unwrap().1
+    });
+
+    let mut caller = SrtSocket::builder()
+        .credentials(encoder())
+        .call("127.0.0.1:3060", None)
+        .await
+        .unwrap();
+    caller
+        .send((Instant::now(), Bytes::from("authenticated")))
+        .await
+        .unwrap();
+
+    assert_eq!(listener.await.unwrap(), "authenticated");
+    caller.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn caller_rejected() {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut server, incoming) = SrtListener::builder()
+        .credentials(decoder())
+        .bind(3061)
+        .await
+        .unwrap();
+    let listener = spawn(accept_all(incoming));
+
+    let unauthorized = ConnectionReject::Rejected(ServerRejectReason::Unauthorized.into());
+    for credentials in [
+        // the wrong key
+        Some(
+            Credentials::new("encoder", SigningKey::from_bytes(&[3; 32]))
+                .trust("decoder", decoder_key().verifying_key()),
+        ),
+        // an identity the listener doesn't trust
+        Some(
+            Credentials::new("stranger", encoder_key())
+                .trust("decoder", decoder_key().verifying_key()),
+        ),
+        // no proof at all
+        None,
+    ] {
+        let mut builder = SrtSocket::builder();
+        if let Some(credentials) = credentials {
+            builder = builder.credentials(credentials);
+        }
+        let err = builder.call("127.0.0.1:3061", None).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(reject_reason(&err), Some(Some(&unauthorized)));
+    }
+
+    server.close().await;
+    listener.await.unwrap();
+}
+
+#[tokio::test]
+async fn listener_rejected() {
+    let _ = pretty_env_logger::try_init();
+
+    // the listener trusts the caller's identity, but doesn't hold the key of the one it claims
+    let impostor =
+        Credentials::new("decoder", encoder_key()).trust("encoder", encoder_key().verifying_key());
+    let (mut server, incoming) = SrtListener::builder()
+        .credentials(impostor)
+        .bind(3062)
+        .await
+        .unwrap();
+    let listener = spawn(accept_all(incoming));
+
+    let err = SrtSocket::builder()
+        .credentials(encoder())
+        .call("127.0.0.1:3062", None)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        reject_reason(&err),
+        Some(Some(&ConnectionReject::Rejecting(
+            ServerRejectReason::Unauthorized.into()
+        )))
+    );
+
+    server.close().await;
+    listener.await.unwrap();
+}
+
+#[tokio::test]
+async fn rendezvous() {
+    let _ = pretty_env_logger::try_init();
+
+    let a = SrtSocket::builder()
+        .credentials(encoder())
+        .local_port(3107)
+        .rendezvous("127.0.0.1:3108");
+    let b = SrtSocket::builder()
+        .credentials(decoder())
+        .local_port(3108)
+        .rendezvous("127.0.0.1:3107");
+    let (a, b) = join!(a, b);
+    let (mut a, mut b) = (a.unwrap(), b.unwrap());
+
+    a.send((Instant::now(), Bytes::from("authenticated")))
+        .await
+        .unwrap();
+    assert_eq!(b.next().await.unwrap().unwrap().1, "authenticated");
+
+    a.close().await.unwrap();
+    b.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn rendezvous_rejected() {
+    let _ = pretty_env_logger::try_init();
+
+    // whichever side initiates, it can't prove the identity it claims
+    let impostor =
+        Credentials::new("encoder", decoder_key()).trust("decoder", decoder_key().verifying_key());
+    let a = SrtSocket::builder()
+        .credentials(impostor)
+        .local_port(3109)
+        .rendezvous("127.0.0.1:3110");
+    let b = SrtSocket::builder()
+        .credentials(decoder())
+        .local_port(3110)
+        .rendezvous("127.0.0.1:3109");
+
+    // whichever side checks the impostor's proof rejects it, the other goes on until it times out
+    match select(Box::pin(a), Box::pin(b)).await {
+        Either::Left((a, _)) => assert!(a.is_err()),
+        Either::Right((b, _)) => assert!(b.is_err()),
+    }
+}
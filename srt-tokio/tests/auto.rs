@@ -0,0 +1,68 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::SrtSocket;
+use tokio::time::{sleep, timeout};
+
+const CALL_TIMEOUT: Duration = Duration::from_millis(500);
+
+async fn auto(local: u16, remote: u16, start_after: Duration) -> Result<SrtSocket> {
+    sleep(start_after).await;
+    let socket = SrtSocket::builder()
+        .local_port(local)
+        .auto(format!("127.0.0.1:{remote}").as_str(), CALL_TIMEOUT)
+        .await?;
+    Ok(socket)
+}
+
+async fn exchange(mut a: SrtSocket, mut b: SrtSocket) -> Result<()> {
+    a.send((Instant::now(), Bytes::from_static(b"hi"))).await?;
+    let (_, data) = timeout(Duration::from_secs(2), b.try_next())
+        .await??
+        .unwrap();
+    assert_eq!(data, "hi");
+
+    a.close().await?;
+    assert_eq!(timeout(Duration::from_secs(2), b.try_next()).await??, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn both_auto() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // whichever starts first, or at the same time, they end up connected
+    for (port, (a_after, b_after)) in [
+        (Duration::ZERO, Duration::ZERO),
+        (Duration::ZERO, Duration::from_millis(50)),
+        (Duration::from_millis(300), Duration::ZERO),
+        (Duration::ZERO, Duration::from_millis(800)),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, starts)| (2760 + 2 * i as u16, starts))
+    {
+        let (a, b) = timeout(
+            Duration::from_secs(5),
+            future::try_join(auto(port, port + 1, a_after), auto(port + 1, port, b_after)),
+        )
+        .await??;
+        exchange(a, b).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn auto_calls_a_listener() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let start = Instant::now();
+    let (a, b) = futures::try_join!(auto(2770, 2771, Duration::ZERO), async {
+        Ok(SrtSocket::builder().listen_on(":2771").await?)
+    },)?;
+    // connected as a caller, without waiting for the call to time out
+    assert!(start.elapsed() < CALL_TIMEOUT);
+    exchange(a, b).await
+}
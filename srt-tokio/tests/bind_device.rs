@@ -0,0 +1,45 @@
+use anyhow::Result;
+use srt_tokio::SrtSocket;
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn bind_device() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async {
+        SrtSocket::builder()
+            .bind_device("lo")
+            .listen_on(":2690")
+            .await
+    });
+    let caller = SrtSocket::builder()
+        .bind_device("lo")
+        .call("127.0.0.1:2690", None)
+        .await?;
+    let listener = listener.await??;
+    assert_eq!(caller.settings().remote, "127.0.0.1:2690".parse()?);
+    assert_eq!(
+        listener.settings().remote.ip(),
+        caller.settings().remote.ip()
+    );
+
+    // binding fails for a device that doesn't exist
+    let result = SrtSocket::builder()
+        .bind_device("srt-missing0")
+        .call("127.0.0.1:2691", None)
+        .await;
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+#[tokio::test]
+async fn bind_device_unsupported() -> Result<()> {
+    let result = SrtSocket::builder()
+        .bind_device("lo")
+        .call("127.0.0.1:2690", None)
+        .await;
+    assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::Unsupported);
+    Ok(())
+}
@@ -1,21 +1,29 @@
-use std::time::{Duration, Instant};
+use std::{
+    io::Cursor,
+    time::{Duration, Instant},
+};
 
-use srt_tokio::SrtSocket;
+use srt_protocol::packet::{DataEncryption, Packet};
+use srt_tokio::{options::*, ConnectionEvent, CryptoState, RawTap, SrtListener, SrtSocket};
 
 use bytes::Bytes;
-use futures::{SinkExt, TryStreamExt};
+use futures::{FutureExt, SinkExt, StreamExt, TryStreamExt};
 use log::info;
 
 use tokio::{spawn, time::sleep};
 
 async fn test_crypto(size: u16) {
+    test_crypto_sizes(2000, size, size).await
+}
+
+async fn test_crypto_sizes(port: u16, listener_size: u16, caller_size: u16) {
     let sender = SrtSocket::builder()
-        .encryption(size, "password123")
-        .listen_on(":2000");
+        .encryption(listener_size, "password123")
+        .listen_on(port);
 
     let recvr = SrtSocket::builder()
-        .encryption(size, "password123")
-        .call("127.0.0.1:2000", None);
+        .encryption(caller_size, "password123")
+        .call(format!("127.0.0.1:{port}"), None);
 
     let t = spawn(async move {
         let mut sender = sender.await.unwrap();
@@ -49,5 +57,175 @@ async fn crypto_exchange() {
     test_crypto(32).await;
 }
 
+#[tokio::test]
+async fn crypto_key_size_adopted() {
+    let _ = pretty_env_logger::try_init();
+
+    // the listener uses the caller's key size, whether it has one configured or not
+    test_crypto_sizes(2001, 0, 32).await;
+    sleep(Duration::from_millis(100)).await;
+    test_crypto_sizes(2001, 16, 24).await;
+}
+
 // TODO: bad password
-// TODO: mismatch
+
+/// Sends data until the receiver's crypto state becomes `state`, keys are sent along with it
+async fn send_until_crypto_state(
+    sender: &mut SrtSocket,
+    receiver: &mut SrtSocket,
+    state: CryptoState,
+) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while receiver.crypto_state() != state {
+        assert!(Instant::now() < deadline, "{:?}", receiver.crypto_state());
+        sender
+            .send((Instant::now(), Bytes::from("data")))
+            .await
+            .unwrap();
+        sleep(Duration::from_millis(5)).await;
+        while receiver.recv().now_or_never().flatten().is_some() {}
+    }
+}
+
+#[tokio::test]
+async fn crypto_state() {
+    let _ = pretty_env_logger::try_init();
+
+    let (a, b) = SrtSocket::pair();
+    assert_eq!(a.crypto_state(), CryptoState::Unencrypted);
+    assert_eq!(b.crypto_state(), CryptoState::Unencrypted);
+
+    // the keys are sent again every so many packets until the peer unwraps them
+    let builder = |key_size| {
+        SrtSocket::builder()
+            .encryption(key_size, "password123")
+            .set(|options| options.encryption.km_refresh.pre_announcement_period = PacketCount(100))
+    };
+    let listener = spawn(builder(0).listen_on(2004));
+    let mut caller = builder(16).call("127.0.0.1:2004", None).await.unwrap();
+    let mut listener = listener.await.unwrap().unwrap();
+    let secured = CryptoState::Secured { key_bits: 128 };
+    assert_eq!(caller.crypto_state(), secured);
+    assert_eq!(listener.crypto_state(), secured);
+
+    // keys wrapped with a passphrase the listener doesn't have yet can't be unwrapped
+    caller.set_passphrase("new password456").unwrap();
+    send_until_crypto_state(&mut caller, &mut listener, CryptoState::BadSecret).await;
+
+    // until it does
+    listener.set_passphrase("new password456").unwrap();
+    send_until_crypto_state(&mut caller, &mut listener, secured).await;
+
+    caller.close().await.unwrap();
+    listener.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn listener_passphrase_rollover() {
+    let _ = pretty_env_logger::try_init();
+
+    let (_listener, mut incoming) = SrtListener::builder()
+        .encryption(16, "new password456")
+        .alternate_passphrase("password123")
+        .bind(2003)
+        .await
+        .unwrap();
+    let accepted = spawn(async move {
+        let mut passphrases = Vec::new();
+        for _ in 0..2 {
+            let request = incoming.incoming().next().await.unwrap();
+            let mut socket = request.accept(None).await.unwrap();
+            passphrases.push(socket.settings().passphrase().cloned());
+            let (_, data) = socket.try_next().await.unwrap().unwrap();
+            assert_eq!(data, "Hello");
+        }
+        passphrases
+    });
+
+    // callers that have moved on to the new passphrase and ones that haven't are both let in
+    for passphrase in ["password123", "new password456"] {
+        let mut caller = SrtSocket::builder()
+            .encryption(16, passphrase)
+            .call("127.0.0.1:2003", None)
+            .await
+            .unwrap();
+        caller
+            .send((Instant::now(), Bytes::from("Hello")))
+            .await
+            .unwrap();
+        caller.close().await.unwrap();
+    }
+
+    // each connection goes on with the passphrase its caller used
+    assert_eq!(
+        accepted.await.unwrap(),
+        [Some("password123".into()), Some("new password456".into())]
+    );
+
+    // anything else is still turned away
+    let rejected = SrtSocket::builder()
+        .encryption(16, "wrong password789")
+        .call("127.0.0.1:2003", None)
+        .await;
+    assert!(rejected.is_err());
+}
+
+#[tokio::test]
+async fn crypto_passphrase_change() {
+    let _ = pretty_env_logger::try_init();
+
+    const MESSAGES: usize = 1000;
+    let builder = || {
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .set(|options| options.encryption.km_refresh.pre_announcement_period = PacketCount(100))
+    };
+    let message = |i| Bytes::from(format!("message {i}"));
+
+    let tap = RawTap::new();
+    let mut datagrams = tap.subscribe();
+    let receiver = spawn(builder().raw_tap(&tap).listen_on(2002));
+    let mut sender = builder().call("127.0.0.1:2002", None).await.unwrap();
+    let mut receiver = receiver.await.unwrap().unwrap();
+
+    let received = spawn(async move {
+        for i in 0..MESSAGES {
+            // the sender changes it first, the new keys can't be used before this
+            if i == MESSAGES / 2 {
+                receiver.set_passphrase("new password456").unwrap();
+            }
+            let (_, data) = receiver.try_next().await.unwrap().unwrap();
+            assert_eq!(data, message(i));
+        }
+        let mut refreshed = 0;
+        receiver.close().await.unwrap();
+        while let Some(event) = receiver.events().next().await {
+            refreshed += matches!(event, ConnectionEvent::KeyRefreshed(_)) as usize;
+        }
+        refreshed
+    });
+
+    for i in 0..MESSAGES {
+        if i == MESSAGES / 4 {
+            sender.set_passphrase("new password456").unwrap();
+        }
+        sender.send((Instant::now(), message(i))).await.unwrap();
+        sleep(Duration::from_millis(1)).await;
+    }
+    // passphrases are checked like in the options
+    assert!(sender.set_passphrase("short").is_err());
+    assert!(received.await.unwrap() > 0);
+    sender.close().await.unwrap();
+
+    // every message was sent with the old key, then the new one
+    let mut keys = Vec::new();
+    while let Some(Some((datagram, _))) = datagrams.next().now_or_never() {
+        if let Ok(Packet::Data(data)) = Packet::parse(&mut Cursor::new(&datagram[..]), false) {
+            // retransmissions keep the key they were first sent with
+            if !data.retransmitted && keys.last() != Some(&data.encryption) {
+                keys.push(data.encryption);
+            }
+        }
+    }
+    assert_eq!(keys, [DataEncryption::Even, DataEncryption::Odd]);
+}
@@ -0,0 +1,746 @@
+mod lossy_conn;
+
+use std::{
+    io,
+    net::SocketAddr,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::packet::{ControlPacket, ControlTypes, Packet, ShakeType, SocketId, TimeStamp};
+use srt_tokio::{options::*, ConnectionEvent, SrtError, SrtSocket};
+use tokio::time::{sleep, timeout};
+
+use lossy_conn::*;
+
+const PACKETS: usize = 500;
+// the usual payload of live streams, 7 MPEG-TS packets
+const PAYLOAD_SIZE: u64 = 1316;
+
+#[tokio::test]
+async fn lossy_custom_transport() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) = LossyConn::pair(listener_addr, caller_addr, 0.05, 0);
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?;
+        let mut socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+        for i in 0..PACKETS {
+            let (_, payload) = socket.try_next().await?.unwrap();
+            assert_eq!(payload, Bytes::from(i.to_string()));
+        }
+        assert_eq!(socket.try_next().await?, None);
+        Ok(()) as Result<_>
+    });
+
+    let options = CallerOptions::new(listener_addr, None)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    assert_eq!(caller.settings().remote, listener_addr);
+    for i in 0..PACKETS {
+        caller
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+    caller.close().await?;
+
+    listener.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn paced_send_rate() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) = LossyConn::pair(listener_addr, caller_addr, 0., 0);
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?;
+        let socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+        Ok::<_, anyhow::Error>(socket.try_collect::<Vec<_>>().await?.len())
+    });
+
+    // 2 Mbps
+    let max_rate = DataRate(2_000_000 / 8);
+    let options = CallerOptions::new(listener_addr, None)?
+        .set(|options| options.socket.sender.bandwidth = LiveBandwidthMode::Max(max_rate))?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut statistics = caller.statistics().clone();
+
+    // well below the cap, so nothing is held back
+    let mut sent = 0;
+    let paced = loop {
+        let payload = Bytes::from(vec![0; PAYLOAD_SIZE as usize]);
+        caller.send((Instant::now(), payload)).await?;
+        sent += 1;
+        if let Ok(Some(stats)) = timeout(Duration::from_millis(10), statistics.next()).await {
+            if stats.tx_send_rate > 0 {
+                break stats;
+            }
+        }
+    };
+    caller.close().await?;
+    assert_eq!(listener.await??, sent);
+
+    let expected_period = Duration::from_micros(PAYLOAD_SIZE * 1_000_000 / max_rate.0);
+    // the mean payload size is estimated from averages of the byte and packet rates, which
+    // don't quite divide to the exact size
+    let tolerance = expected_period / 20;
+    assert!(
+        paced.tx_snd_period.abs_diff(expected_period) <= tolerance,
+        "{:?}",
+        paced.tx_snd_period
+    );
+    assert!(
+        paced.tx_send_rate.abs_diff(max_rate.0) <= max_rate.0 / 20,
+        "{}",
+        paced.tx_send_rate
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn initial_rtt() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // 150ms each way, 30 times the round trip time assumed by default
+    const DELAY: Duration = Duration::from_millis(150);
+    // nothing is lost during the handshake
+    const SEED: u64 = 3;
+
+    let default_naks = early_naks(None).await?;
+    let seeded_naks = early_naks(Some(2 * DELAY)).await?;
+
+    // with the round trip time known from the start, the losses aren't reported again before
+    // their retransmissions could have arrived
+    assert!(
+        seeded_naks < default_naks,
+        "{seeded_naks} NAKs seeded, {default_naks} by default"
+    );
+
+    async fn early_naks(initial_rtt: Option<Duration>) -> Result<u64> {
+        let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+        let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+        let (listener_conn, caller_conn) =
+            LossyConn::delayed_pair(listener_addr, caller_addr, 0.05, DELAY, SEED);
+
+        let listener = tokio::spawn(async move {
+            let options = ListenerOptions::new(listener_addr.port())?.set(|options| {
+                options.socket.session.initial_rtt = initial_rtt;
+                // the losses are waited for instead of dropped as too late
+                options.socket.receiver.latency = Duration::from_secs(1);
+                options.socket.session.statistics_interval = Duration::from_millis(200);
+            })?;
+            let mut socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+            let mut statistics = socket.statistics().clone();
+            tokio::spawn(async move { while socket.next().await.is_some() {} });
+            // the losses are recovered by then
+            loop {
+                let statistics = statistics.next().await.unwrap();
+                if statistics.elapsed_time > Duration::from_millis(1500) {
+                    break Ok(statistics.tx_nak) as Result<_>;
+                }
+            }
+        });
+
+        let options = CallerOptions::new(listener_addr, None)?
+            .set(|options| options.socket.session.initial_rtt = initial_rtt)?;
+        let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+        // all sent before the round trip time is first measured
+        for i in 0..100 {
+            caller
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(2)).await;
+        }
+
+        let naks = timeout(Duration::from_secs(3), listener).await???;
+        caller.close().await?;
+        Ok(naks)
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn relay() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const INGRESS_LATENCY: Duration = Duration::from_millis(300);
+    const EGRESS_LATENCY: Duration = Duration::from_millis(500);
+    const DELAY: Duration = Duration::from_millis(20);
+    const MESSAGES: usize = 200;
+
+    fn with_latency(latency: Duration) -> SocketOptions {
+        let mut options = SocketOptions::default();
+        options.sender.peer_latency = latency;
+        options.receiver.latency = latency;
+        options
+    }
+
+    // source -> relay over one lossy link, relay -> sink over another
+    let relay_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let source_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let sink_addr: SocketAddr = "127.0.0.1:3010".parse()?;
+    let relay_out_addr: SocketAddr = "127.0.0.1:3011".parse()?;
+    let (relay_conn, source_conn) =
+        LossyConn::delayed_pair(relay_addr, source_addr, 0.02, DELAY, 1);
+    let (sink_conn, relay_out_conn) =
+        LossyConn::delayed_pair(sink_addr, relay_out_addr, 0.02, DELAY, 4);
+
+    let sink = tokio::spawn(async move {
+        let options = ListenerOptions::with(sink_addr.port(), with_latency(EGRESS_LATENCY))?;
+        let mut socket = SrtSocket::with_transport(sink_conn, options.into()).await?;
+        let mut received = Vec::new();
+        while let Some((_, payload)) = socket.try_next().await? {
+            received.push((Instant::now(), payload));
+        }
+        Ok(received) as Result<Vec<(Instant, Bytes)>>
+    });
+    let relay = tokio::spawn(async move {
+        let options = ListenerOptions::with(relay_addr.port(), with_latency(INGRESS_LATENCY))?;
+        let mut ingress = SrtSocket::with_transport(relay_conn, options.into()).await?;
+        let options = CallerOptions::with(sink_addr, None, with_latency(EGRESS_LATENCY))?;
+        let mut egress = SrtSocket::with_transport(relay_out_conn, options.into()).await?;
+        srt_tokio::relay(&mut ingress, &mut egress).await?;
+        Ok(()) as Result<_>
+    });
+
+    let options = CallerOptions::with(relay_addr, None, with_latency(INGRESS_LATENCY))?;
+    let mut source = SrtSocket::with_transport(source_conn, options.into()).await?;
+    // the relay connects to the sink only once the source is connected to it
+    sleep(Duration::from_millis(500)).await;
+    let mut sent = Vec::new();
+    for i in 0..MESSAGES {
+        let now = Instant::now();
+        source.send((now, Bytes::from(i.to_string()))).await?;
+        sent.push(now);
+        sleep(Duration::from_millis(5)).await;
+    }
+    source.close().await?;
+
+    timeout(Duration::from_secs(10), relay).await???;
+    let received = timeout(Duration::from_secs(10), sink).await???;
+
+    // everything arrives, in order, after both latencies
+    let expected = INGRESS_LATENCY + EGRESS_LATENCY;
+    assert_eq!(received.len(), MESSAGES);
+    for (i, (sent, (arrived, payload))) in sent.iter().zip(received).enumerate() {
+        assert_eq!(payload, Bytes::from(i.to_string()));
+        let latency = arrived - *sent;
+        assert!(
+            latency >= expected - Duration::from_millis(20)
+                && latency < expected + Duration::from_millis(100),
+            "message {i} arrived after {latency:?}"
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn delayed_packet_on_time() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(100);
+
+    let a_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let b_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (mut a, mut b) = LossyConn::delayed_pair(a_addr, b_addr, 0., DELAY, 0);
+
+    // a lone packet, with no other traffic to wake the receiving side up
+    let packet = Packet::Control(ControlPacket {
+        timestamp: TimeStamp::MIN,
+        dest_sockid: SocketId(1),
+        control_type: ControlTypes::KeepAlive,
+    });
+    let sent = Instant::now();
+    a.send((packet.clone(), b_addr)).await?;
+    assert!(b.next().now_or_never().is_none());
+
+    let received = timeout(DELAY * 2, b.next()).await?;
+    let elapsed = sent.elapsed();
+    assert_eq!(received, Some((packet, a_addr)));
+    assert!(
+        elapsed >= DELAY && elapsed < DELAY + Duration::from_millis(50),
+        "delivered after {elapsed:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn full_link() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(100);
+    const CAPACITY: usize = 4;
+
+    let a_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let b_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let packet = |n: usize| {
+        Packet::Control(ControlPacket {
+            timestamp: TimeStamp::MIN,
+            dest_sockid: SocketId(n as u32),
+            control_type: ControlTypes::KeepAlive,
+        })
+    };
+
+    for when_full in [WhenFull::Drop, WhenFull::Count, WhenFull::Error] {
+        let (a, mut b) = LossyConn::delayed_pair(a_addr, b_addr, 0., DELAY, 0);
+        let mut a = a.with_capacity(CAPACITY, when_full);
+        let overflowed = a.overflowed.clone();
+
+        // two more than fit on the link at once
+        let mut sent = Vec::new();
+        for n in 0..CAPACITY + 2 {
+            sent.push(a.send((packet(n), b_addr)).await);
+        }
+        let failed = sent.iter().filter(|result| result.is_err()).count();
+        match when_full {
+            WhenFull::Drop => assert_eq!((failed, overflowed.load(Ordering::Relaxed)), (0, 0)),
+            WhenFull::Count => assert_eq!((failed, overflowed.load(Ordering::Relaxed)), (0, 2)),
+            WhenFull::Error => {
+                assert_eq!((failed, overflowed.load(Ordering::Relaxed)), (2, 0));
+                let error = sent.pop().unwrap().unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+            }
+        }
+
+        // only the ones that fit get through
+        for n in 0..CAPACITY {
+            assert_eq!(
+                timeout(DELAY * 2, b.next()).await?,
+                Some((packet(n), a_addr))
+            );
+        }
+        assert!(timeout(DELAY * 2, b.next()).await.is_err());
+
+        // and there's room again once they're through
+        a.send((packet(0), b_addr)).await?;
+        assert_eq!(
+            timeout(DELAY * 2, b.next()).await?,
+            Some((packet(0), a_addr))
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn high_rtt_jitter() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // 150ms each way, and up to 100ms more: later than the latency, but not than the latency
+    // and half the round trip time, which the packets are due after
+    const DELAY: Duration = Duration::from_millis(150);
+    const JITTER: Duration = Duration::from_millis(100);
+    const LATENCY: Duration = Duration::from_millis(200);
+    const MESSAGES: usize = 100;
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::jittered_pair(listener_addr, caller_addr, 0., DELAY, JITTER, 0);
+
+    let mut options = SocketOptions::default();
+    options.sender.peer_latency = LATENCY;
+    options.receiver.latency = LATENCY;
+    // sent as they come, though the acknowledgements take long to come back
+    options.sender.buffer_size = ByteCount(1_000_000);
+    options.sender.idle_restart_threshold = None;
+
+    let listener_options = ListenerOptions::with(listener_addr.port(), options.clone())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let caller_options = CallerOptions::with(listener_addr, None, options)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, caller_options.into()).await?;
+    let mut listener = listener.await??;
+
+    // from right after the handshake, before the clocks are synchronized by the first control
+    // packets
+    let receive = tokio::spawn(async move {
+        let mut received = Vec::new();
+        while received.len() < MESSAGES {
+            match timeout(Duration::from_secs(2), listener.next()).await {
+                Ok(Some(Ok((_, data)))) => received.push(data),
+                _ => break,
+            }
+        }
+        received
+    });
+    for i in 0..MESSAGES {
+        caller
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await?;
+        sleep(Duration::from_millis(5)).await;
+    }
+
+    // none were dropped as too late
+    let received = receive.await?;
+    let expected = (0..MESSAGES)
+        .map(|i| Bytes::from(i.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(received, expected);
+
+    caller.close().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_retransmits() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) = LossyConn::pair(listener_addr, caller_addr, 0., 0);
+    let down = caller_conn.down.clone();
+
+    // long enough that neither the peer idle timeout, nor the packets getting too late, end it
+    let mut options = SocketOptions::default();
+    options.session.peer_idle_timeout = Duration::from_secs(30);
+    options.sender.peer_latency = Duration::from_secs(3);
+    options.receiver.latency = Duration::from_secs(3);
+
+    let listener_options = ListenerOptions::with(listener_addr.port(), options.clone())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+
+    options.sender.max_retransmits = Some(2);
+    let options = CallerOptions::with(listener_addr, None, options)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let _listener = listener.await??;
+
+    // the link goes down for good once connected
+    down.store(true, Ordering::Relaxed);
+    let start = Instant::now();
+    caller.send((Instant::now(), Bytes::from("lost"))).await?;
+    timeout(Duration::from_secs(10), async {
+        while caller.events().next().await.is_some() {}
+    })
+    .await?;
+
+    assert!(
+        start.elapsed() < Duration::from_secs(3),
+        "gave up after {:?}",
+        start.elapsed()
+    );
+    assert_matches!(caller.last_error(), Some(SrtError::RetransmitLimit));
+    // what was sent never got through
+    let error = caller.flushed().await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionAborted);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flushed() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(100);
+    // fewer than the sender starts out with in flight, so they're all sent at once
+    const MESSAGES: usize = 10;
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::delayed_pair(listener_addr, caller_addr, 0., DELAY, 0);
+
+    let listener_options = ListenerOptions::new(listener_addr.port())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let options = CallerOptions::new(listener_addr, None)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut listener = listener.await??;
+
+    for _ in 0..MESSAGES {
+        caller
+            .send((Instant::now(), Bytes::from_static(&[0; 1000])))
+            .await?;
+    }
+
+    // the data takes DELAY to get there and its ACK as long to get back, flushing the sink
+    // doesn't wait for either
+    let sent = Instant::now();
+    assert!(caller.flushed().now_or_never().is_none());
+    assert!(timeout(DELAY * 3 / 2, caller.flushed()).await.is_err());
+    timeout(DELAY * 2, caller.flushed()).await??;
+    assert!(
+        sent.elapsed() >= DELAY * 2,
+        "flushed after {:?}",
+        sent.elapsed()
+    );
+    assert_eq!(caller.unacked_bytes(), 0);
+
+    for _ in 0..MESSAGES {
+        timeout(Duration::from_secs(2), listener.next())
+            .await?
+            .unwrap()?;
+    }
+
+    // nothing more to wait for
+    timeout(Duration::from_millis(10), caller.flushed()).await??;
+
+    caller.close().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn flushed_until_acked() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(100);
+    const MESSAGES: usize = 10;
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::delayed_pair(listener_addr, caller_addr, 0., DELAY, 0);
+    let down = caller_conn.down.clone();
+
+    let mut options = SocketOptions::default();
+    options.session.peer_idle_timeout = Duration::from_secs(3);
+    options.sender.peer_latency = Duration::from_millis(200);
+    options.receiver.latency = Duration::from_millis(200);
+
+    let listener_options = ListenerOptions::with(listener_addr.port(), options.clone())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let options = CallerOptions::with(listener_addr, None, options)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut listener = listener.await??;
+
+    for n in 0..MESSAGES {
+        caller
+            .send((Instant::now(), Bytes::from(format!("message {n}"))))
+            .await?;
+    }
+
+    // acknowledged a round trip later, by when the peer has it all
+    let sent = Instant::now();
+    timeout(DELAY * 3, caller.flushed()).await??;
+    assert!(
+        sent.elapsed() >= DELAY * 2,
+        "acked after {:?}",
+        sent.elapsed()
+    );
+    for n in 0..MESSAGES {
+        let (_, data) = timeout(Duration::from_secs(1), listener.next())
+            .await?
+            .unwrap()?;
+        assert_eq!(data, format!("message {n}"));
+    }
+
+    // with the link down, the data is dropped on this side once it's too late, which doesn't
+    // flush it without the peer acknowledging it
+    down.store(true, Ordering::Relaxed);
+    caller
+        .send((Instant::now(), Bytes::from_static(b"lost")))
+        .await?;
+    assert!(timeout(Duration::from_secs(2), caller.flushed())
+        .await
+        .is_err());
+    assert_eq!(caller.unacked_bytes(), 0);
+    // until the peer idle timeout ends the connection
+    let error = timeout(Duration::from_secs(10), caller.flushed())
+        .await?
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    assert_matches!(caller.last_error(), Some(SrtError::Timeout));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn ping() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(50);
+    const SLACK: Duration = Duration::from_millis(30);
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::delayed_pair(listener_addr, caller_addr, 0., DELAY, 0);
+
+    let listener_options = ListenerOptions::new(listener_addr.port())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let options = CallerOptions::new(listener_addr, None)?;
+    let caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut listener = listener.await??;
+
+    // no data flows, the keepalive goes there and its answer comes back
+    for _ in 0..3 {
+        let rtt = timeout(Duration::from_secs(1), caller.ping()).await??;
+        assert!(
+            rtt >= DELAY * 2 && rtt < DELAY * 2 + SLACK,
+            "round trip of {rtt:?}"
+        );
+    }
+    let rtt = timeout(Duration::from_secs(1), listener.ping()).await??;
+    assert!(
+        rtt >= DELAY * 2 && rtt < DELAY * 2 + SLACK,
+        "round trip of {rtt:?}"
+    );
+
+    listener.close().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn handshake_events() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const DELAY: Duration = Duration::from_millis(20);
+    const RETRY_INTERVAL: Duration = Duration::from_millis(200);
+    // for the tasks to get their turn
+    const SLACK: Duration = Duration::from_millis(100);
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::delayed_pair(listener_addr, caller_addr, 0., DELAY, 0);
+    // the first induction is lost
+    let mut inductions = 0;
+    let caller_conn = caller_conn.dropping(move |packet| {
+        let shake = packet.control().and_then(|control| control.handshake());
+        if !matches!(shake, Some(shake) if shake.shake_type == ShakeType::Induction) {
+            return false;
+        }
+        inductions += 1;
+        inductions == 1
+    });
+
+    let listener_options = ListenerOptions::new(listener_addr.port())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let options = CallerOptions::new(listener_addr, None)?.set(|options| {
+        options.socket.connect.handshake_retry_interval = RETRY_INTERVAL;
+    })?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut listener = listener.await??;
+
+    async fn until_connected(socket: &mut SrtSocket) -> Result<Vec<ConnectionEvent>> {
+        let mut events = Vec::new();
+        while let Some(event) = timeout(Duration::from_secs(1), socket.events().next()).await? {
+            events.push(event);
+            if matches!(event, ConnectionEvent::Connected(_)) {
+                break;
+            }
+        }
+        Ok(events)
+    }
+
+    use ConnectionEvent::*;
+    use ShakeType::*;
+    // the induction is sent again once the retry interval passes, then the handshake takes two
+    // round trips
+    assert_matches!(
+        until_connected(&mut caller).await?[..],
+        [
+            Connecting(_),
+            HandshakeSent(first, Induction),
+            Reconnecting(retry),
+            HandshakeSent(_, Induction),
+            HandshakeReceived(_, Induction),
+            HandshakeSent(_, Conclusion),
+            HandshakeReceived(_, Conclusion),
+            Connected(connected),
+        ] => {
+            assert!(retry - first >= RETRY_INTERVAL - SLACK, "{:?}", retry - first);
+            assert!(retry - first < RETRY_INTERVAL + SLACK, "{:?}", retry - first);
+            assert!(connected - retry >= DELAY * 4, "{:?}", connected - retry);
+            assert!(connected - retry < DELAY * 4 + SLACK, "{:?}", connected - retry);
+        }
+    );
+    // the listener only got the induction sent again
+    assert_matches!(
+        until_connected(&mut listener).await?[..],
+        [
+            Connecting(_),
+            HandshakeReceived(_, Induction),
+            HandshakeSent(_, Induction),
+            HandshakeReceived(_, Conclusion),
+            HandshakeSent(_, Conclusion),
+            Connected(_),
+        ]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn no_reconnecting_without_resend() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the induction is answered before the first retry, which comes while the conclusion is
+    // still on its way, too early to send it again
+    const DELAY: Duration = Duration::from_millis(50);
+    const RETRY_INTERVAL: Duration = Duration::from_millis(150);
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) =
+        LossyConn::delayed_pair(listener_addr, caller_addr, 0., DELAY, 0);
+
+    let listener_options = ListenerOptions::new(listener_addr.port())?;
+    let listener = tokio::spawn(SrtSocket::with_transport(
+        listener_conn,
+        listener_options.into(),
+    ));
+    let options = CallerOptions::new(listener_addr, None)?.set(|options| {
+        options.socket.connect.handshake_retry_interval = RETRY_INTERVAL;
+    })?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut listener = listener.await??;
+
+    let mut events = Vec::new();
+    while let Some(event) = timeout(Duration::from_secs(1), caller.events().next()).await? {
+        events.push(event);
+        if matches!(event, ConnectionEvent::Connected(_)) {
+            break;
+        }
+    }
+
+    use ConnectionEvent::*;
+    use ShakeType::*;
+    assert_matches!(
+        events[..],
+        [
+            Connecting(_),
+            HandshakeSent(_, Induction),
+            HandshakeReceived(_, Induction),
+            HandshakeSent(_, Conclusion),
+            HandshakeReceived(_, Conclusion),
+            Connected(_),
+        ],
+        "{events:?}"
+    );
+
+    listener.close().await?;
+    Ok(())
+}
@@ -0,0 +1,257 @@
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*};
+use srt_protocol::{
+    packet::{CoreRejectReason, Packet, RejectReason},
+    protocol::pending_connection::ConnectionReject,
+};
+use srt_tokio::{options::*, SrtError, SrtSocket};
+use tokio::{runtime::Runtime, time::timeout};
+
+#[tokio::test]
+async fn connect_timeout() {
+    let _ = pretty_env_logger::try_init();
+
+    let error = SrtSocket::builder()
+        .set(|options| options.connect.timeout = Duration::from_millis(500))
+        .call("127.0.0.1:3030", None)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+    assert_matches!(SrtError::from(error), SrtError::Timeout);
+}
+
+#[tokio::test]
+async fn rejected() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .listen_on(3031),
+    );
+    let error = SrtSocket::builder()
+        .call("127.0.0.1:3031", None)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        SrtError::from(error),
+        SrtError::Rejected(ConnectionReject::Rejected(RejectReason::Core(
+            CoreRejectReason::Unsecure
+        )))
+    );
+    assert_matches!(
+        SrtError::from(listener.await.unwrap().unwrap_err()),
+        SrtError::Rejected(ConnectionReject::Rejecting(RejectReason::Core(
+            CoreRejectReason::Unsecure
+        )))
+    );
+}
+
+#[tokio::test]
+async fn decryption() {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(
+        SrtSocket::builder()
+            .encryption(16, "password123")
+            .listen_on(3032),
+    );
+    let error = SrtSocket::builder()
+        .encryption(16, "password456")
+        .call("127.0.0.1:3032", None)
+        .await
+        .unwrap_err();
+    assert_matches!(
+        SrtError::from(error),
+        SrtError::Decryption(ConnectionReject::Rejected(_))
+    );
+    assert_matches!(
+        SrtError::from(listener.await.unwrap().unwrap_err()),
+        SrtError::Decryption(ConnectionReject::Rejecting(_))
+    );
+}
+
+#[tokio::test]
+async fn peer_reset() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut a, mut b) = SrtSocket::pair();
+    assert_matches!(b.last_error(), None);
+    a.send((Instant::now(), Bytes::from("hello"))).await?;
+    a.close().await?;
+
+    assert_eq!(b.try_next().await?.unwrap().1, "hello");
+    assert_eq!(b.try_next().await?, None);
+    assert_matches!(b.last_error(), Some(SrtError::PeerReset));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn peer_idle_timeout() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the peer runs on a runtime of its own, so it can vanish without shutting down the connection
+    let peer_runtime = Runtime::new()?;
+    let peer = peer_runtime.spawn(SrtSocket::builder().listen_on(3033));
+    let mut caller = SrtSocket::builder()
+        .set(|options| options.session.peer_idle_timeout = Duration::from_secs(1))
+        .call("127.0.0.1:3033", None)
+        .await?;
+    let peer = peer.await??;
+    assert_matches!(caller.last_error(), None);
+
+    peer_runtime.shutdown_background();
+    timeout(Duration::from_secs(10), async {
+        while caller.events().next().await.is_some() {}
+    })
+    .await?;
+    assert_matches!(caller.last_error(), Some(SrtError::Timeout));
+
+    drop(peer);
+    Ok(())
+}
+
+/// An in-memory link that fails to send once broken
+struct BreakingConn {
+    local: SocketAddr,
+    broken: Arc<AtomicBool>,
+    incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+    outgoing: mpsc::UnboundedSender<(Packet, SocketAddr)>,
+}
+
+impl BreakingConn {
+    fn pair(a: SocketAddr, b: SocketAddr, broken: Arc<AtomicBool>) -> (Self, Self) {
+        let (a_sender, a_receiver) = mpsc::unbounded();
+        let (b_sender, b_receiver) = mpsc::unbounded();
+        (
+            BreakingConn {
+                local: a,
+                broken: broken.clone(),
+                incoming: a_receiver,
+                outgoing: b_sender,
+            },
+            BreakingConn {
+                local: b,
+                broken,
+                incoming: b_receiver,
+                outgoing: a_sender,
+            },
+        )
+    }
+}
+
+impl Stream for BreakingConn {
+    type Item = (Packet, SocketAddr);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for BreakingConn {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (packet, _): (Packet, SocketAddr)) -> io::Result<()> {
+        if self.broken.load(Ordering::SeqCst) {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "the link is down",
+            ));
+        }
+        let _ = self.outgoing.unbounded_send((packet, self.local));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn io_error() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3034".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3035".parse()?;
+    let broken = Arc::new(AtomicBool::new(false));
+    let (listener_conn, caller_conn) =
+        BreakingConn::pair(listener_addr, caller_addr, broken.clone());
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?;
+        SrtSocket::with_transport(listener_conn, options.into()).await
+    });
+    let options = CallerOptions::new(listener_addr, None)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let _listener = listener.await??;
+
+    broken.store(true, Ordering::SeqCst);
+    caller.send((Instant::now(), Bytes::from("hello"))).await?;
+    let error = timeout(Duration::from_secs(1), async {
+        loop {
+            if let Some(error) = caller.last_error() {
+                break error;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    // the link failing takes down the packet channel of a custom transport
+    assert_matches!(error, SrtError::Io(e) if e.kind() == io::ErrorKind::NotConnected);
+
+    Ok(())
+}
+
+#[test]
+fn std_error() {
+    fn is_error<E: std::error::Error + Send + Sync + 'static>() {}
+    is_error::<SrtError>();
+
+    // the io::Error behind a failed link is its source
+    let error = SrtError::from(io::Error::new(io::ErrorKind::BrokenPipe, "link down"));
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(
+        source.downcast_ref::<io::Error>().unwrap().kind(),
+        io::ErrorKind::BrokenPipe
+    );
+
+    // and it survives the trip through the io::Errors the API returns
+    let error = io::Error::from(SrtError::PeerReset);
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionReset);
+    assert_matches!(SrtError::from(error), SrtError::PeerReset);
+}
+
+#[test]
+fn boxed_error() {
+    fn connect() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let error: io::Error = SrtError::Timeout.into();
+        Err(SrtError::from(error))?;
+        Ok(())
+    }
+
+    let error = connect().unwrap_err();
+    assert_matches!(error.downcast_ref::<SrtError>(), Some(SrtError::Timeout));
+}
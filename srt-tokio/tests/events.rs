@@ -0,0 +1,112 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::{
+    access::ServerRejectReason, packet::ShakeType, protocol::pending_connection::ConnectionReject,
+};
+use srt_tokio::{ConnectionEvent, SrtListener, SrtSocket};
+
+#[tokio::test]
+async fn connect_close_events() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async {
+        let mut listener = SrtSocket::builder().listen_on(2470).await?;
+        let (_, payload) = listener.try_next().await?.unwrap();
+        assert_eq!(payload, Bytes::from("hello"));
+        assert_eq!(listener.try_next().await?, None);
+        listener.close().await?;
+        Ok(listener.events().collect::<Vec<_>>().await) as Result<_>
+    });
+
+    let mut caller = SrtSocket::builder().call("127.0.0.1:2470", None).await?;
+    caller.send((Instant::now(), Bytes::from("hello"))).await?;
+    caller.close().await?;
+    let caller_events = caller.events().collect::<Vec<_>>().await;
+    let listener_events = listener.await??;
+
+    for events in [caller_events, listener_events] {
+        // the caller may have resent the handshake before the listener was up, the handshake
+        // packets are covered by handshake_event_callback
+        let events: Vec<_> = events
+            .into_iter()
+            .filter(|e| {
+                !matches!(
+                    e,
+                    ConnectionEvent::Reconnecting(_)
+                        | ConnectionEvent::HandshakeSent(..)
+                        | ConnectionEvent::HandshakeReceived(..)
+                )
+            })
+            .collect();
+
+        assert!(
+            matches!(
+                events[..],
+                [
+                    ConnectionEvent::Connecting(_),
+                    ConnectionEvent::Connected(_),
+                    ConnectionEvent::Closed(_)
+                ]
+            ),
+            "{events:?}"
+        );
+        assert!(events.windows(2).all(|w| w[0].time() <= w[1].time()));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn handshake_event_callback() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (_listener, _incoming) = SrtListener::builder()
+        .on_accept(|_| Err(ServerRejectReason::Forbidden.into()))
+        .bind(2471)
+        .await?;
+
+    // a rejected caller has no socket to take the events from, the callback still hears of them
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let on_event = {
+        let events = events.clone();
+        move |event| events.lock().unwrap().push(event)
+    };
+    let error = SrtSocket::builder()
+        .on_handshake_event(on_event)
+        .call("127.0.0.1:2471", None)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+
+    use ConnectionEvent::*;
+    use ShakeType::*;
+    let reason = ServerRejectReason::Forbidden.into();
+    let events: Vec<_> = events
+        .lock()
+        .unwrap()
+        .drain(..)
+        .filter(|e| !matches!(e, Reconnecting(_)))
+        .collect();
+    assert_matches!(
+        events[..],
+        [
+            HandshakeSent(_, Induction),
+            HandshakeReceived(_, Induction),
+            HandshakeSent(_, Conclusion),
+            HandshakeReceived(_, Rejection(received)),
+            Rejected(_, ConnectionReject::Rejected(rejected)),
+        ] if received == reason && rejected == reason,
+        "{events:?}"
+    );
+    assert!(events.windows(2).all(|w| w[0].time() <= w[1].time()));
+
+    Ok(())
+}
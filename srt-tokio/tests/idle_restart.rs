@@ -0,0 +1,180 @@
+use std::{
+    cmp::max,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*};
+use srt_protocol::packet::Packet;
+use srt_tokio::{options::*, SrtSocket};
+use tokio::{sync::mpsc as bounded, time::sleep};
+
+// 4 packets a ms, about 40 Mbps
+const LINK_INTERVAL: Duration = Duration::from_micros(250);
+const LINK_QUEUE: usize = 200;
+const STEADY_MESSAGES: usize = 200;
+const BURST_MESSAGES: usize = 400;
+
+#[derive(Debug, Default)]
+struct LinkRecord {
+    // when every data packet was handed to the link
+    offered: Vec<Instant>,
+    dropped: usize,
+}
+
+/// One end of an in-memory link that sends at a fixed rate, and loses what doesn't fit in its
+/// queue
+struct BottleneckConn {
+    local: SocketAddr,
+    incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+    queue: bounded::Sender<(Instant, Packet, SocketAddr)>,
+    record: Arc<Mutex<LinkRecord>>,
+}
+
+impl BottleneckConn {
+    fn pair(a: SocketAddr, b: SocketAddr) -> (Self, Self) {
+        let (a_sender, a_receiver) = mpsc::unbounded();
+        let (b_sender, b_receiver) = mpsc::unbounded();
+        (
+            Self::new(a, a_receiver, b_sender),
+            Self::new(b, b_receiver, a_sender),
+        )
+    }
+
+    fn new(
+        local: SocketAddr,
+        incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+        outgoing: mpsc::UnboundedSender<(Packet, SocketAddr)>,
+    ) -> Self {
+        let (queue, mut queued) = bounded::channel(LINK_QUEUE);
+        tokio::spawn(async move {
+            let mut next = Instant::now();
+            while let Some((enqueued, packet, from)) = queued.recv().await {
+                next = max(next, enqueued) + LINK_INTERVAL;
+                tokio::time::sleep_until(next.into()).await;
+                let _ = outgoing.unbounded_send((packet, from));
+            }
+        });
+        BottleneckConn {
+            local,
+            incoming,
+            queue,
+            record: Default::default(),
+        }
+    }
+}
+
+impl Stream for BottleneckConn {
+    type Item = (Packet, SocketAddr);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for BottleneckConn {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (packet, _): (Packet, SocketAddr)) -> io::Result<()> {
+        let now = Instant::now();
+        let mut record = self.record.lock().unwrap();
+        if matches!(packet, Packet::Data(_)) {
+            record.offered.push(now);
+        }
+        if self.queue.try_send((now, packet, self.local)).is_err() {
+            record.dropped += 1;
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn bounded_burst_after_idle() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3010".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3011".parse()?;
+    let (listener_conn, caller_conn) = BottleneckConn::pair(listener_addr, caller_addr);
+    let record = caller_conn.record.clone();
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?
+            .set(|options| options.socket.receiver.latency = Duration::from_secs(1))?;
+        let mut socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+        let mut received = 0;
+        while received < STEADY_MESSAGES + BURST_MESSAGES {
+            if socket.try_next().await?.is_none() {
+                break;
+            }
+            received += 1;
+        }
+        Ok::<_, anyhow::Error>(received)
+    });
+
+    // room for the whole backlog, and late enough for it to make it at the rate of the link
+    let options = CallerOptions::new(listener_addr, None)?.set(|options| {
+        options.socket.sender.buffer_size = ByteCount(1_000_000);
+        options.socket.receiver.latency = Duration::from_secs(1);
+        options.socket.sender.peer_latency = Duration::from_secs(1);
+    })?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let message = Bytes::from(vec![0; 1316]);
+    for _ in 0..STEADY_MESSAGES {
+        caller.send((Instant::now(), message.clone())).await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+
+    sleep(Duration::from_secs(2)).await;
+    let resumed = Instant::now();
+    let dropped_before = record.lock().unwrap().dropped;
+    for _ in 0..BURST_MESSAGES {
+        caller.feed((Instant::now(), message.clone())).await?;
+    }
+    caller.flush().await?;
+
+    let received = listener.await??;
+    caller.close().await?;
+    assert_eq!(received, STEADY_MESSAGES + BURST_MESSAGES);
+
+    // the backlog isn't dumped on the link at once, it's clocked out by the ACKs in bursts that fit
+    // in the queue of the link
+    let link = record.lock().unwrap();
+    let offered: Vec<_> = link
+        .offered
+        .iter()
+        .copied()
+        .filter(|t| *t >= resumed)
+        .collect();
+    let largest_burst = offered
+        .iter()
+        .map(|start| {
+            let end = *start + Duration::from_millis(5);
+            offered.iter().filter(|t| (start..&end).contains(t)).count()
+        })
+        .max()
+        .unwrap();
+    assert!(largest_burst <= LINK_QUEUE, "burst of {largest_burst}");
+
+    let dropped = link.dropped - dropped_before;
+    assert!(dropped < BURST_MESSAGES / 10, "dropped {dropped}");
+
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::packet::{ControlPacket, ControlTypes, TimeStamp};
+use srt_tokio::SrtSocket;
+use tokio::time::sleep;
+
+#[tokio::test]
+async fn inject_keepalive() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut a, mut b) = SrtSocket::pair();
+
+    let keepalive = ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: b.settings().local_sockid,
+        control_type: ControlTypes::KeepAlive,
+    };
+    for _ in 0..10 {
+        b.inject_control(keepalive.clone()).await?;
+    }
+    sleep(Duration::from_millis(100)).await;
+
+    a.send((Instant::now(), Bytes::from("after keepalive")))
+        .await?;
+    let (_, payload) = b.try_next().await?.unwrap();
+    assert_eq!(payload, Bytes::from("after keepalive"));
+
+    a.close().await?;
+    assert_eq!(b.try_next().await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn port_released_on_close() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the same port again, once the connection before is done with it
+    for _ in 0..2 {
+        let listener = SrtSocket::builder().listen_on(3105);
+        let caller = SrtSocket::builder().call("127.0.0.1:3105", None);
+        let (mut listener, caller) = futures::try_join!(listener, caller)?;
+        listener.close().await?;
+        drop(caller);
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    Ok(())
+}
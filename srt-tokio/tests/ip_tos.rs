@@ -0,0 +1,45 @@
+use srt_tokio::SrtSocket;
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn ip_tos() -> anyhow::Result<()> {
+    use std::time::Instant;
+
+    use bytes::Bytes;
+    use futures::{SinkExt, TryStreamExt};
+    use socket2::SockRef;
+    use srt_tokio::{bind_socket, options::*};
+
+    let _ = pretty_env_logger::try_init();
+
+    // set on the UDP socket before anything is sent
+    let mut options = SocketOptions::default();
+    options.connect.ip_tos = Some(0xb8);
+    let socket = bind_socket(&options).await?;
+    assert_eq!(SockRef::from(&socket).tos()?, 0xb8);
+
+    options.connect.local = "[::1]:0".parse()?;
+    let socket = bind_socket(&options).await?;
+    assert_eq!(SockRef::from(&socket).tclass_v6()?, 0xb8);
+
+    // and the connection still works
+    let listener = tokio::spawn(async {
+        let mut listener = SrtSocket::builder().dscp(46).listen_on(":2710").await?;
+        listener.try_next().await
+    });
+    let mut caller = SrtSocket::builder()
+        .dscp(46)
+        .call("127.0.0.1:2710", None)
+        .await?;
+    caller.send((Instant::now(), Bytes::from("marked"))).await?;
+    let (_, received) = listener.await??.unwrap();
+    assert_eq!(received, "marked");
+
+    Ok(())
+}
+
+#[test]
+#[should_panic]
+fn dscp_out_of_range() {
+    let _ = SrtSocket::builder().dscp(64);
+}
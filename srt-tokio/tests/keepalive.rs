@@ -36,3 +36,33 @@ async fn keepalive() {
     };
     futures::join!(s, r);
 }
+
+#[tokio::test]
+async fn activity() {
+    let _ = pretty_env_logger::try_init();
+
+    let (caller, listener) = futures::try_join!(
+        SrtSocket::builder().call("127.0.0.1:4445", None),
+        SrtSocket::builder().listen_on(":4445"),
+    )
+    .unwrap();
+    let connected = Instant::now();
+
+    // nothing but keepalives goes either way
+    let mut uptime = caller.uptime();
+    for _ in 0..25 {
+        sleep(Duration::from_millis(100)).await;
+        assert!(caller.uptime() > uptime);
+        uptime = caller.uptime();
+    }
+    assert!(uptime >= Duration::from_millis(2500));
+
+    let now = Instant::now();
+    for socket in [&caller, &listener] {
+        for last in [socket.last_recv(), socket.last_send()] {
+            let last = last.unwrap();
+            assert!(last > connected + Duration::from_millis(500), "{last:?}");
+            assert!(now - last < Duration::from_millis(1500), "{:?}", now - last);
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use anyhow::Result;
+use bytes::Bytes;
 use srt_tokio::SrtSocket;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::time::{Duration, Instant};
+use tokio::time::{sleep, timeout};
 
 use futures::prelude::*;
 
@@ -61,3 +62,83 @@ async fn latency_exchange() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn change_latency() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (old, new) = (Duration::from_millis(100), Duration::from_millis(300));
+    let listener = SrtSocket::builder().latency(old).listen_on(":4201");
+    let caller = SrtSocket::builder()
+        .latency(old)
+        .call("127.0.0.1:4201", None);
+    let (listener, caller) = futures::join!(listener, caller);
+    let (mut listener, mut caller) = (listener?, caller?);
+
+    listener.set_latency(new)?;
+    assert_eq!(listener.settings().recv_tsbpd_latency, old);
+
+    let mut receiver_statistics = listener.statistics().clone();
+    let receiver = tokio::spawn(listener.try_collect::<Vec<_>>());
+
+    let mut sender_statistics = caller.statistics().clone();
+    let sender = async {
+        for i in 0..300 {
+            caller
+                .send((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+            sleep(Duration::from_millis(10)).await;
+        }
+        Ok(()) as Result<_>
+    };
+    let confirmed = async {
+        while sender_statistics.next().await.unwrap().tx_tsbpd_delay != new {}
+        while receiver_statistics.next().await.unwrap().rx_tsbpd_delay != new {}
+    };
+    let (sent, confirmed) = futures::join!(sender, timeout(Duration::from_secs(5), confirmed));
+    sent?;
+    confirmed?;
+    caller.close().await?;
+
+    // nothing is dropped while the latency grows
+    assert_eq!(receiver.await??.len(), 300);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn latency_adjustment_reported() -> Result<()> {
+    use srt_protocol::connection::NegotiationAdjustment::*;
+
+    let _ = pretty_env_logger::try_init();
+
+    let (low, high) = (Duration::from_millis(20), Duration::from_secs(2));
+    let listener = SrtSocket::builder().latency(high).listen_on(":4202");
+    let caller = SrtSocket::builder()
+        .latency(low)
+        .call("127.0.0.1:4202", None);
+    let (listener, caller) = futures::join!(listener, caller);
+    let (mut listener, mut caller) = (listener?, caller?);
+
+    // the caller asked for less than it got, both ways
+    assert_eq!(
+        caller.settings().negotiation_adjustments,
+        [
+            SendLatency {
+                requested: low,
+                effective: high
+            },
+            RecvLatency {
+                requested: low,
+                effective: high
+            },
+        ]
+    );
+    // while the listener got what it asked for
+    assert_eq!(listener.settings().negotiation_adjustments, []);
+
+    caller.close().await?;
+    listener.close().await?;
+
+    Ok(())
+}
@@ -0,0 +1,293 @@
+//! An in-memory link for the integration tests and benchmarks to connect sockets over, through
+//! [`SrtSocket::with_transport`], and the transfer over it they measure goodput with
+
+// not every test uses every knob
+#![allow(dead_code)]
+
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*};
+use rand::{prelude::StdRng, Rng, SeedableRng};
+use srt_protocol::packet::Packet;
+use srt_tokio::{options::*, SrtSocket};
+use tokio::time;
+
+/// What the link does with a packet sent while it's full
+#[derive(Clone, Copy, Debug)]
+pub enum WhenFull {
+    /// Loses it, like any other packet lost
+    Drop,
+    /// Loses it, counting it in `overflowed`
+    Count,
+    /// Fails the send
+    Error,
+}
+
+/// Decides which packets the link loses on top of the random ones, `true` to drop it
+pub type DropFilter = Box<dyn FnMut(&Packet) -> bool + Send>;
+
+/// An in-memory link that loses some of the packets sent over it, and delays the others
+pub struct LossyConn {
+    local: SocketAddr,
+    loss: f64,
+    delay: Duration,
+    // up to this much longer for each packet, at random
+    jitter: Duration,
+    rng: StdRng,
+    // once set, everything sent either way is lost
+    pub down: Arc<AtomicBool>,
+    // at most this many packets on the way to the peer, see WhenFull
+    capacity: usize,
+    when_full: WhenFull,
+    // the packets on the way to the peer, until its delay line releases them
+    in_flight: Arc<AtomicUsize>,
+    // the packets lost to a full link, with WhenFull::Count
+    pub overflowed: Arc<AtomicUsize>,
+    drop_filter: Option<DropFilter>,
+    incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+    outgoing: mpsc::UnboundedSender<(time::Instant, (Packet, SocketAddr))>,
+}
+
+impl LossyConn {
+    pub fn pair(a: SocketAddr, b: SocketAddr, loss: f64, seed: u64) -> (Self, Self) {
+        Self::delayed_pair(a, b, loss, Duration::ZERO, seed)
+    }
+
+    pub fn delayed_pair(
+        a: SocketAddr,
+        b: SocketAddr,
+        loss: f64,
+        delay: Duration,
+        seed: u64,
+    ) -> (Self, Self) {
+        Self::jittered_pair(a, b, loss, delay, Duration::ZERO, seed)
+    }
+
+    pub fn jittered_pair(
+        a: SocketAddr,
+        b: SocketAddr,
+        loss: f64,
+        delay: Duration,
+        jitter: Duration,
+        seed: u64,
+    ) -> (Self, Self) {
+        let (a_sender, a_receiver, a_in_flight) = Self::delay_line();
+        let (b_sender, b_receiver, b_in_flight) = Self::delay_line();
+        let down = Arc::new(AtomicBool::new(false));
+        (
+            LossyConn {
+                local: a,
+                loss,
+                delay,
+                jitter,
+                rng: StdRng::seed_from_u64(seed),
+                down: down.clone(),
+                capacity: usize::MAX,
+                when_full: WhenFull::Drop,
+                in_flight: b_in_flight,
+                overflowed: Default::default(),
+                drop_filter: None,
+                incoming: a_receiver,
+                outgoing: b_sender,
+            },
+            LossyConn {
+                local: b,
+                loss,
+                delay,
+                jitter,
+                rng: StdRng::seed_from_u64(seed + 1),
+                down,
+                capacity: usize::MAX,
+                when_full: WhenFull::Drop,
+                in_flight: a_in_flight,
+                overflowed: Default::default(),
+                drop_filter: None,
+                incoming: b_receiver,
+                outgoing: a_sender,
+            },
+        )
+    }
+
+    /// Holds at most `capacity` packets on the way to the peer, `when_full` decides what happens
+    /// to the ones sent beyond that
+    pub fn with_capacity(mut self, capacity: usize, when_full: WhenFull) -> Self {
+        self.capacity = capacity;
+        self.when_full = when_full;
+        self
+    }
+
+    /// Loses the packets sent that `filter` returns true for
+    pub fn dropping(mut self, filter: impl FnMut(&Packet) -> bool + Send + 'static) -> Self {
+        self.drop_filter = Some(Box::new(filter));
+        self
+    }
+
+    // releases the packets in the order they were sent, each once its time comes. The task sleeps
+    // until then, and the channel wakes the connection as it hands the packet over, so there's
+    // nothing to keep time for in poll_next
+    #[allow(clippy::type_complexity)]
+    fn delay_line() -> (
+        mpsc::UnboundedSender<(time::Instant, (Packet, SocketAddr))>,
+        mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+        Arc<AtomicUsize>,
+    ) {
+        let (delayed_sender, delayed_receiver) = mpsc::unbounded();
+        let (sender, receiver) = mpsc::unbounded();
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let released = in_flight.clone();
+        tokio::spawn(
+            delayed_receiver
+                .then(move |(release_at, packet)| {
+                    let released = released.clone();
+                    async move {
+                        time::sleep_until(release_at).await;
+                        released.fetch_sub(1, Ordering::Relaxed);
+                        Ok(packet)
+                    }
+                })
+                .forward(sender),
+        );
+        (delayed_sender, receiver, in_flight)
+    }
+}
+
+impl Stream for LossyConn {
+    type Item = (Packet, SocketAddr);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for LossyConn {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, (packet, _): (Packet, SocketAddr)) -> io::Result<()> {
+        let loss = self.loss;
+        if self.rng.gen_bool(loss) || self.down.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if let Some(filter) = &mut self.drop_filter {
+            if filter(&packet) {
+                return Ok(());
+            }
+        }
+        if self.in_flight.load(Ordering::Relaxed) >= self.capacity {
+            return match self.when_full {
+                WhenFull::Drop => Ok(()),
+                WhenFull::Count => {
+                    self.overflowed.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                WhenFull::Error => Err(io::Error::new(
+                    io::ErrorKind::WouldBlock,
+                    "the link is full",
+                )),
+            };
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let release_at = time::Instant::now() + self.delay;
+        let local = self.local;
+        // drawn only with jitter, so the same seed loses the same packets either way
+        if self.jitter > Duration::ZERO {
+            // into the delay line once the jitter has passed, so the packets sent after it with
+            // less jitter overtake it
+            let jitter = self.jitter.mul_f64(self.rng.gen());
+            let outgoing = self.outgoing.clone();
+            tokio::spawn(async move {
+                time::sleep(jitter).await;
+                let _ = outgoing.unbounded_send((release_at + jitter, (packet, local)));
+            });
+            return Ok(());
+        }
+        // the peer may have hung up already, which is no different from a packet lost
+        let _ = self.outgoing.unbounded_send((release_at, (packet, local)));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Sends `count` payloads of `size` bytes from a caller to a listener over a lossless link, at
+/// `rate` bytes per second, as a live source would. Returns the payload bytes the listener
+/// received, and how long it took from the first send to the last one received.
+///
+/// The sender paces packets a millisecond apart until it has estimated the input rate, a tenth
+/// of a second in, and drops what waited longer than the latency by then, so the caller warms the
+/// connection up at that pace first. The caller only closes once the listener is done, the
+/// listener would drop the data it hasn't released yet otherwise. Payloads dropped as too late
+/// don't arrive, so the listener also stops after a second without any.
+pub async fn transfer(count: usize, size: usize, rate: u64) -> io::Result<(u64, Duration)> {
+    const WARM_UP: usize = 150;
+
+    let listener_addr: SocketAddr = "127.0.0.1:3090".parse().unwrap();
+    let caller_addr: SocketAddr = "127.0.0.1:3091".parse().unwrap();
+    let (listener_conn, caller_conn) = LossyConn::pair(listener_addr, caller_addr, 0., 0);
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?;
+        let mut socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+        let (mut received, mut bytes, mut last) = (0, 0, Instant::now());
+        while received < WARM_UP + count {
+            match time::timeout(Duration::from_secs(1), socket.try_next()).await {
+                Ok(Ok(Some((_, data)))) => {
+                    received += 1;
+                    if received > WARM_UP {
+                        bytes += data.len() as u64;
+                        last = Instant::now();
+                    }
+                }
+                Ok(Ok(None)) | Err(_) => break,
+                Ok(Err(e)) => return Err(e),
+            }
+        }
+        Ok((bytes, last, socket))
+    });
+
+    let options = CallerOptions::new(listener_addr, None)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let payload = Bytes::from(vec![0; size]);
+    for _ in 0..WARM_UP {
+        caller.send((Instant::now(), payload.clone())).await?;
+        time::sleep(Duration::from_millis(1)).await;
+    }
+
+    // every millisecond, whatever is due by then
+    let start = Instant::now();
+    let mut ticks = time::interval(Duration::from_millis(1));
+    let mut sent = 0;
+    while sent < count {
+        ticks.tick().await;
+        let due = (start.elapsed().as_secs_f64() * rate as f64 / size as f64).ceil() as usize;
+        while sent < due.min(count) {
+            caller.send((Instant::now(), payload.clone())).await?;
+            sent += 1;
+        }
+    }
+
+    let (bytes, last, mut socket) = listener.await??;
+    caller.close().await?;
+    socket.close().await?;
+    Ok((bytes, last.saturating_duration_since(start)))
+}
@@ -0,0 +1,69 @@
+use std::{
+    io::Cursor,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::packet::Packet;
+use srt_tokio::{options::*, RawTap, SrtSocket};
+use tokio::time::sleep;
+
+const MESSAGES: usize = 10;
+
+#[tokio::test]
+async fn smaller_mss_is_used() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let message = Bytes::from(vec![7; 1316]);
+
+    let tap = RawTap::new();
+    let mut datagrams = tap.subscribe();
+    let listener = tokio::spawn({
+        let message = message.clone();
+        async move {
+            let mut listener = SrtSocket::builder()
+                .set(|options| options.session.max_segment_size = PacketSize(1000))
+                .raw_tap(&tap)
+                .listen_on(2940)
+                .await?;
+            let settings = listener.settings().clone();
+            for _ in 0..MESSAGES {
+                assert_eq!(listener.try_next().await?.unwrap().1, message);
+            }
+            Ok::<_, anyhow::Error>(settings)
+        }
+    });
+
+    // the caller advertises the default of 1500
+    let mut caller = SrtSocket::builder().call("127.0.0.1:2940", None).await?;
+    for _ in 0..MESSAGES {
+        caller.send((Instant::now(), message.clone())).await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+    let listener_settings = listener.await??;
+    caller.close().await?;
+
+    for settings in [caller.settings(), &listener_settings] {
+        assert_eq!(settings.max_segment_size, PacketSize(1000));
+        assert_eq!(
+            settings.max_packet_size,
+            PacketSize(1000) - Packet::HEADER_SIZE
+        );
+    }
+
+    // every message is split in a full packet and the rest
+    let max_payload = (PacketSize(1000) - Packet::HEADER_SIZE).0 as usize;
+    let mut payloads = Vec::new();
+    while payloads.len() < 2 * MESSAGES {
+        let (datagram, _) = datagrams.next().await.unwrap();
+        if let Packet::Data(data) = Packet::parse(&mut Cursor::new(&datagram[..]), false)? {
+            payloads.push(data.payload.len());
+        }
+    }
+    let expected = [max_payload, message.len() - max_payload].repeat(MESSAGES);
+    assert_eq!(payloads, expected);
+
+    Ok(())
+}
@@ -1,10 +1,14 @@
-use std::time::{Duration, Instant};
+use std::{
+    io,
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
 use bytes::Bytes;
 use futures::prelude::*;
 
-use srt_tokio::SrtSocket;
+use srt_protocol::packet::PacketLocation;
+use srt_tokio::{options::*, SrtSocket};
 use tokio::time::sleep;
 
 const PACKET_SIZE: usize = 15 * 1500;
@@ -46,3 +50,113 @@ async fn message_splitting() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn message_too_large() -> Result<()> {
+    let (mut sender, _receiver) = SrtSocket::pair();
+
+    let max_message_size = sender.settings().max_message_size();
+    let message = Bytes::from(vec![b'8'; max_message_size.0 as usize + 1]);
+    let error = sender.send((Instant::now(), message)).await.unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+    let message = Bytes::from(vec![b'8'; max_message_size.0 as usize]);
+    sender.send((Instant::now(), message)).await?;
+
+    Ok(())
+}
+
+// two messages of three packets each
+fn messages() -> [Bytes; 2] {
+    [Bytes::from(vec![b'a'; 3000]), Bytes::from(vec![b'b'; 3000])]
+}
+
+async fn receive(granularity: RecvGranularity) -> Result<Vec<(Instant, Bytes)>> {
+    let (mut sender, receiver) = SrtSocket::builder().recv_granularity(granularity).pair()?;
+    for message in messages() {
+        sender.send((Instant::now(), message)).await?;
+    }
+    sender.close().await?;
+    Ok(receiver.try_collect().await?)
+}
+
+#[tokio::test]
+async fn message_granularity() -> Result<()> {
+    let received = receive(RecvGranularity::Message).await?;
+    let received: Vec<_> = received.into_iter().map(|(_, data)| data).collect();
+    assert_eq!(received, messages());
+    Ok(())
+}
+
+#[tokio::test]
+async fn payload_granularity() -> Result<()> {
+    let received = receive(RecvGranularity::Payload).await?;
+
+    // a packet's worth at a time, each with the time of its message
+    assert_eq!(received.len(), 6);
+    for message in received.chunks(3) {
+        assert!(message.iter().all(|(time, _)| *time == message[0].0));
+    }
+    let payloads: Vec<_> = received.iter().map(|(_, data)| &data[..]).collect();
+    assert_eq!(payloads.concat(), messages().concat());
+    assert!(payloads[..3].iter().all(|p| p.iter().all(|b| *b == b'a')));
+    assert!(payloads[3..].iter().all(|p| p.iter().all(|b| *b == b'b')));
+    Ok(())
+}
+
+#[tokio::test]
+async fn message_info() -> Result<()> {
+    let (mut sender, mut receiver) = SrtSocket::builder()
+        .recv_granularity(RecvGranularity::Payload)
+        .pair()?;
+    for message in messages() {
+        sender.send((Instant::now(), message)).await?;
+    }
+    sender.close().await?;
+
+    let mut received = Vec::new();
+    while let Some((_, _, info)) = receiver.recv_with_info().await {
+        received.push(info);
+    }
+
+    // the payloads of a message share its number, and tell where they go in it
+    assert_eq!(received.len(), 6);
+    for message in received.chunks(3) {
+        assert!(message
+            .iter()
+            .all(|info| info.message_number == message[0].message_number));
+        let locations: Vec<_> = message.iter().map(|info| info.location).collect();
+        assert_eq!(
+            locations,
+            [
+                PacketLocation::FIRST,
+                PacketLocation::MIDDLE,
+                PacketLocation::LAST
+            ]
+        );
+    }
+    assert_ne!(received[0].message_number, received[3].message_number);
+    Ok(())
+}
+
+#[tokio::test]
+async fn whole_message_info() -> Result<()> {
+    let (mut sender, mut receiver) = SrtSocket::pair();
+    for message in messages() {
+        sender.send((Instant::now(), message)).await?;
+    }
+    sender.close().await?;
+
+    let mut received = Vec::new();
+    while let Some((_, data, info)) = receiver.recv_with_info().await {
+        received.push((data, info));
+    }
+
+    // every packet of it
+    assert_eq!(received.len(), 2);
+    assert!(received
+        .iter()
+        .all(|(_, info)| info.location == PacketLocation::ONLY));
+    assert_ne!(received[0].1.message_number, received[1].1.message_number);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{multiplex, SrtSocket};
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn interleaved_streams() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const MESSAGES: usize = 50;
+
+    let (caller, listener) = futures::try_join!(
+        SrtSocket::builder()
+            .multiplex(true)
+            .call("127.0.0.1:2740", None),
+        SrtSocket::builder().multiplex(true).listen_on(":2740"),
+    )?;
+    let mut sending = multiplex(caller, 2)?;
+    let receiving = multiplex(listener, 2)?;
+
+    // the messages of both streams take turns on the connection
+    for i in 0..MESSAGES {
+        for stream in &mut sending {
+            let data = format!("{}:{i}", stream.index());
+            stream.send((Instant::now(), Bytes::from(data))).await?;
+        }
+        sleep(Duration::from_millis(1)).await;
+    }
+    for stream in &mut sending {
+        stream.close().await?;
+    }
+
+    // each comes out on its own, in order, and ends along with the connection
+    let received = receiving.into_iter().map(|stream| async move {
+        let index = stream.index();
+        let data = timeout(Duration::from_secs(5), stream.try_collect::<Vec<_>>()).await??;
+        let expected = (0..MESSAGES)
+            .map(|i| format!("{index}:{i}"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            data.into_iter().map(|(_, data)| data).collect::<Vec<_>>(),
+            expected
+        );
+        Ok(()) as Result<_>
+    });
+    future::try_join_all(received).await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsupported_peer() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // a peer that doesn't offer it, like the reference implementation, leaves it out
+    let (caller, listener) = futures::try_join!(
+        SrtSocket::builder()
+            .multiplex(true)
+            .call("127.0.0.1:2741", None),
+        SrtSocket::builder().listen_on(":2741"),
+    )?;
+    let error = multiplex(caller, 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    let error = multiplex(listener, 2).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn full_stream_holds_up_the_others() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // more than a stream holds until it's read, less than the socket underneath holds on top
+    const MESSAGES: usize = 200;
+
+    let (caller, listener) = futures::try_join!(
+        SrtSocket::builder()
+            .multiplex(true)
+            .call("127.0.0.1:2742", None),
+        SrtSocket::builder().multiplex(true).listen_on(":2742"),
+    )?;
+    let mut sending = multiplex(caller, 2)?;
+    let mut receiving = multiplex(listener, 2)?;
+
+    for i in 0..MESSAGES {
+        let data = Bytes::from(format!("1:{i}"));
+        sending[1].send((Instant::now(), data)).await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+    sending[0]
+        .send((Instant::now(), Bytes::from("0:0")))
+        .await?;
+
+    // the second stream isn't read, so nothing more is read from the socket
+    let held_up = timeout(Duration::from_secs(1), receiving[0].next()).await;
+    assert!(held_up.is_err(), "{held_up:?}");
+
+    // once it is, none of its data is lost, and the first stream gets its turn
+    for i in 0..MESSAGES {
+        let (_, data) = timeout(Duration::from_secs(5), receiving[1].next())
+            .await?
+            .unwrap()?;
+        assert_eq!(data, format!("1:{i}"));
+    }
+    let (_, data) = timeout(Duration::from_secs(5), receiving[0].next())
+        .await?
+        .unwrap()?;
+    assert_eq!(data, "0:0");
+
+    Ok(())
+}
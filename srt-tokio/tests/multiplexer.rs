@@ -1,4 +1,4 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use srt_tokio::{SrtListener, SrtSocket};
 
@@ -6,6 +6,7 @@ use anyhow::Result;
 use bytes::Bytes;
 use futures::{channel::oneshot, future::join_all, stream, FutureExt, SinkExt, StreamExt};
 use log::info;
+use tokio::time::{sleep, timeout};
 
 #[tokio::test]
 async fn multiplexer() -> Result<()> {
@@ -59,3 +60,76 @@ async fn multiplexer() -> Result<()> {
 }
 
 // crypto!!
+
+#[tokio::test]
+async fn max_connections() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (server, mut incoming) = SrtListener::builder().max_connections(2).bind(2100).await?;
+    tokio::spawn(async move {
+        while let Some(request) = incoming.incoming().next().await {
+            let mut socket = request.accept(None).await.unwrap();
+            tokio::spawn(async move { while socket.next().await.is_some() {} });
+        }
+    });
+
+    let mut first = SrtSocket::builder().call("127.0.0.1:2100", None).await?;
+    let mut second = SrtSocket::builder().call("127.0.0.1:2100", None).await?;
+    wait_for_connection_count(&server, 2).await?;
+
+    let rejected = SrtSocket::builder().call("127.0.0.1:2100", None).await;
+    assert!(rejected.is_err(), "{rejected:?}");
+
+    // the closed connection is no longer counted, and makes room for a new one
+    first.close().await?;
+    wait_for_connection_count(&server, 1).await?;
+
+    let mut third = SrtSocket::builder().call("127.0.0.1:2100", None).await?;
+    wait_for_connection_count(&server, 2).await?;
+
+    second.close().await?;
+    third.close().await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn allow_from() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (_server, mut incoming) = SrtListener::builder()
+        .allow_from(vec!["127.0.0.2/32".parse()?])
+        .bind(2790)
+        .await?;
+    tokio::spawn(async move {
+        while let Some(request) = incoming.incoming().next().await {
+            let mut socket = request.accept(None).await.unwrap();
+            tokio::spawn(async move { while socket.next().await.is_some() {} });
+        }
+    });
+
+    // turned away at the induction, well before the connect timeout
+    let start = Instant::now();
+    let rejected = SrtSocket::builder()
+        .local_ip([127, 0, 0, 1].into())
+        .call("127.0.0.1:2790", None)
+        .await;
+    assert!(rejected.is_err(), "{rejected:?}");
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    let mut allowed = SrtSocket::builder()
+        .local_ip([127, 0, 0, 2].into())
+        .call("127.0.0.1:2790", None)
+        .await?;
+    allowed.close().await?;
+    Ok(())
+}
+
+async fn wait_for_connection_count(server: &SrtListener, count: usize) -> Result<()> {
+    timeout(Duration::from_secs(2), async {
+        while server.connection_count() != count {
+            sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{options::*, SrtSocket};
+
+fn options() -> SocketOptions {
+    let mut options = SocketOptions::default();
+    options.connect.timeout = Duration::from_secs(2);
+    options.session.peer_idle_timeout = Duration::from_secs(3);
+    options.encryption.key_size = KeySize::AES192;
+    options.encryption.passphrase = Some("password12345".into());
+    options.sender.peer_latency = Duration::from_millis(250);
+    options.sender.bandwidth = LiveBandwidthMode::Estimated {
+        overhead: Percent(50),
+        expected: DataRate(1_000_000),
+    };
+    options.receiver.latency = Duration::from_millis(250);
+    options.receiver.buffer_size = ByteCount(100_000);
+    options
+}
+
+#[test]
+fn round_trip() -> Result<()> {
+    let options = options();
+    let json = serde_json::to_string(&options)?;
+    let deserialized: SocketOptions = serde_json::from_str(&json)?;
+    assert_eq!(deserialized, options);
+    Ok(())
+}
+
+#[test]
+fn partial() -> Result<()> {
+    // everything left out is the default
+    let options: SocketOptions = serde_json::from_str(
+        r#"{ "receiver": { "latency": { "secs": 1, "nanos": 0 } }, "sender": { "buffer_size": 1000000 } }"#,
+    )?;
+    let mut expected = SocketOptions::default();
+    expected.receiver.latency = Duration::from_secs(1);
+    expected.sender.buffer_size = ByteCount(1_000_000);
+    assert_eq!(options, expected);
+
+    // and the passphrase is checked like any other
+    let short =
+        serde_json::from_str::<SocketOptions>(r#"{ "encryption": { "passphrase": "short" } }"#);
+    assert!(short.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn connect_with_deserialized_options() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let options: SocketOptions = serde_json::from_str(&serde_json::to_string(&options())?)?;
+
+    let listener = tokio::spawn({
+        let options = options.clone();
+        async move {
+            let mut socket = SrtSocket::builder()
+                .set(|socket| *socket = options)
+                .listen_on(2950)
+                .await?;
+            let received = socket.try_next().await?.map(|(_, data)| data);
+            Ok::<_, anyhow::Error>((received, socket.settings().clone()))
+        }
+    });
+
+    let mut caller = SrtSocket::connect_with_options("127.0.0.1:2950", &options).await?;
+    caller
+        .send((Instant::now(), Bytes::from_static(b"hello")))
+        .await?;
+    let (received, listener_settings) = listener.await??;
+    caller.close().await?;
+
+    assert_eq!(received, Some(Bytes::from_static(b"hello")));
+    for settings in [caller.settings(), &listener_settings] {
+        assert_eq!(settings.send_tsbpd_latency, Duration::from_millis(250));
+        assert_eq!(settings.recv_tsbpd_latency, Duration::from_millis(250));
+        assert_eq!(
+            settings.cipher.as_ref().map(|c| c.key_settings.key_size),
+            Some(KeySize::AES192)
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::{packet::CoreRejectReason, protocol::pending_connection::ConnectionReject};
+use srt_tokio::SrtSocket;
+use tokio::time::sleep;
+
+fn with_filter(filter: &str) -> srt_tokio::SrtSocketBuilder {
+    let filter = filter.parse().unwrap();
+    SrtSocket::builder().set(|options| options.session.packet_filter = Some(filter))
+}
+
+#[tokio::test]
+async fn agreed_filter() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async {
+        let mut socket = with_filter("fec,cols:4").listen_on(":2920").await?;
+        let filter = socket.settings().packet_filter.clone();
+        for i in 0..10 {
+            let (_, payload) = socket.try_next().await?.unwrap();
+            assert_eq!(payload, Bytes::from(i.to_string()));
+        }
+        Ok::<_, anyhow::Error>(filter)
+    });
+
+    // what only the caller sets is added to the listener's config
+    let mut caller = with_filter("fec,cols:4,rows:2")
+        .call("127.0.0.1:2920", None)
+        .await?;
+    let agreed = Some("fec,cols:4,rows:2".parse()?);
+    assert_eq!(caller.settings().packet_filter, agreed);
+    for i in 0..10 {
+        caller
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+
+    assert_eq!(listener.await??, agreed);
+    caller.close().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn incompatible_filter() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async {
+        with_filter("fec,cols:4")
+            .listen_on(":2921")
+            .await
+            .unwrap_err()
+    });
+
+    let err = with_filter("fec,cols:8")
+        .call("127.0.0.1:2921", None)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    assert_eq!(
+        err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>()),
+        Some(Some(&ConnectionReject::Rejected(
+            CoreRejectReason::Filter.into()
+        )))
+    );
+
+    let err = listener.await?;
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    assert_eq!(
+        err.get_ref().map(|e| e.downcast_ref::<ConnectionReject>()),
+        Some(Some(&ConnectionReject::Rejecting(
+            CoreRejectReason::Filter.into()
+        )))
+    );
+
+    Ok(())
+}
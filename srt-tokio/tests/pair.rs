@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::SrtSocket;
+
+#[tokio::test]
+async fn pair_round_trip() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut a, mut b) = SrtSocket::pair();
+
+    a.send((Instant::now(), Bytes::from("ping"))).await?;
+    let (_, ping) = b.try_next().await?.unwrap();
+    assert_eq!(ping, Bytes::from("ping"));
+
+    b.send((Instant::now(), Bytes::from("pong"))).await?;
+    let (_, pong) = a.try_next().await?.unwrap();
+    assert_eq!(pong, Bytes::from("pong"));
+
+    a.close().await?;
+    assert_eq!(b.try_next().await?, None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn pair_encrypted() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut a, mut b) = SrtSocket::builder()
+        .latency(Duration::from_millis(50))
+        .encryption(16, "password123")
+        .pair()?;
+
+    let sender = tokio::spawn(async move {
+        let mut messages =
+            stream::iter(0..10).map(|i| Ok((Instant::now(), Bytes::from(i.to_string()))));
+        a.send_all(&mut messages).await?;
+        a.close().await?;
+        Ok(()) as Result<_>
+    });
+
+    for i in 0..10 {
+        let (_, payload) = b.try_next().await?.unwrap();
+        assert_eq!(payload, Bytes::from(i.to_string()));
+    }
+    assert_eq!(b.try_next().await?, None);
+
+    sender.await??;
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::{channel::mpsc, prelude::*};
+use srt_protocol::packet::Packet;
+use srt_tokio::{options::*, SrtSocket};
+use tokio::time::{sleep, timeout};
+
+const MESSAGES: u32 = 1_000;
+// larger than any payload the sender starts out with
+const PATH_MTU: usize = 1100;
+
+/// An in-memory link that loses every packet larger than its MTU
+struct MtuLimitedConn {
+    local: SocketAddr,
+    mtu: usize,
+    incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+    outgoing: mpsc::UnboundedSender<(Packet, SocketAddr)>,
+}
+
+impl MtuLimitedConn {
+    fn pair(a: SocketAddr, b: SocketAddr, mtu: usize) -> (Self, Self) {
+        let (a_sender, a_receiver) = mpsc::unbounded();
+        let (b_sender, b_receiver) = mpsc::unbounded();
+        (
+            MtuLimitedConn {
+                local: a,
+                mtu,
+                incoming: a_receiver,
+                outgoing: b_sender,
+            },
+            MtuLimitedConn {
+                local: b,
+                mtu,
+                incoming: b_receiver,
+                outgoing: a_sender,
+            },
+        )
+    }
+}
+
+impl Stream for MtuLimitedConn {
+    type Item = (Packet, SocketAddr);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for MtuLimitedConn {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (packet, _): (Packet, SocketAddr)) -> io::Result<()> {
+        if packet.wire_size() <= self.mtu {
+            let _ = self.outgoing.unbounded_send((packet, self.local));
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+// mostly full packets, with a small one now and then, numbered in the first 4 bytes
+fn message(i: u32) -> Bytes {
+    let size = if i.is_multiple_of(4) { 200 } else { 1316 };
+    let mut message = BytesMut::with_capacity(size);
+    message.put_u32(i);
+    message.resize(size, 0);
+    message.freeze()
+}
+
+#[tokio::test]
+async fn fall_back_to_path_mtu() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener_addr: SocketAddr = "127.0.0.1:3000".parse()?;
+    let caller_addr: SocketAddr = "127.0.0.1:3001".parse()?;
+    let (listener_conn, caller_conn) = MtuLimitedConn::pair(listener_addr, caller_addr, PATH_MTU);
+
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::new(listener_addr.port())?;
+        let socket = SrtSocket::with_transport(listener_conn, options.into()).await?;
+        let received = socket
+            .map_ok(|(_, message)| u32::from_be_bytes(message[..4].try_into().unwrap()))
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok::<_, anyhow::Error>(received)
+    });
+
+    let options = CallerOptions::new(listener_addr, None)?;
+    let mut caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let mut statistics = caller.statistics().clone();
+
+    // the payload of the first plateau below the default of 1316 is still too large, so it falls
+    // back twice, send until the statistics show it settled, however long that takes
+    let mut sent = 0;
+    let settled = timeout(Duration::from_secs(10), async {
+        loop {
+            caller.send((Instant::now(), message(sent))).await?;
+            sent += 1;
+            sleep(Duration::from_millis(1)).await;
+            if let Some(Some(stats)) = statistics.next().now_or_never() {
+                // the first ones are from before the connection sent anything
+                if (1..=1006 - 44).contains(&stats.tx_max_payload_size) {
+                    return Ok::<_, anyhow::Error>(stats.tx_max_payload_size);
+                }
+            }
+        }
+    })
+    .await??;
+    assert_eq!(settled, 1006 - 44);
+
+    // every message handed over from then on is split to fit
+    let settled_at = sent;
+    for i in settled_at..settled_at + MESSAGES {
+        caller.send((Instant::now(), message(i))).await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+    caller.close().await?;
+
+    // the full sized messages sent early on never made it, everything after falling back does
+    let received = listener.await??;
+    assert!(received.windows(2).all(|w| w[0] < w[1]));
+    let after_settling = received.iter().filter(|i| **i >= settled_at).count();
+    assert_eq!(after_settling, MESSAGES as usize);
+    assert!(received.len() < (settled_at + MESSAGES) as usize);
+
+    Ok(())
+}
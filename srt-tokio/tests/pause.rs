@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::SrtSocket;
+use tokio::time::{sleep, timeout};
+
+#[tokio::test]
+async fn pause_and_resume() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the latency is long enough for what's sent while paused to still be on time once resumed
+    let builder = || {
+        SrtSocket::builder()
+            .latency(Duration::from_secs(3))
+            .set(|options| {
+                options.session.peer_idle_timeout = Duration::from_secs(1);
+                options.session.statistics_interval = Duration::from_millis(200);
+            })
+    };
+    let (mut caller, mut listener) = futures::try_join!(
+        builder().call("127.0.0.1:2730", None),
+        builder().listen_on(":2730"),
+    )?;
+
+    for i in 0..10 {
+        caller
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await?;
+    }
+    for i in 0..10 {
+        let (_, data) = timeout(Duration::from_secs(5), listener.try_next())
+            .await??
+            .unwrap();
+        assert_eq!(data, i.to_string());
+    }
+
+    // paused for longer than the peer idle timeout, the data is buffered and only keepalives go out
+    caller.pause()?;
+    // the pause goes to the connection on a channel of its own, racing the data otherwise
+    sleep(Duration::from_millis(100)).await;
+    caller
+        .send((Instant::now(), Bytes::from("buffered")))
+        .await?;
+    sleep(Duration::from_secs(2)).await;
+    let statistics = caller.statistics().next().await.unwrap();
+    assert_eq!(statistics.tx_unique_data, 10);
+    assert_matches!(caller.last_error(), None);
+    assert_matches!(listener.last_error(), None);
+    let last_recv = listener.last_recv().unwrap();
+    assert!(last_recv.elapsed() < Duration::from_millis(1500));
+
+    // what was buffered goes out once resumed, and so does what comes after
+    caller.resume()?;
+    for i in 10..20 {
+        caller
+            .send((Instant::now(), Bytes::from(i.to_string())))
+            .await?;
+    }
+    let expected = ["buffered".to_string()]
+        .into_iter()
+        .chain((10..20).map(|i| i.to_string()));
+    for expected in expected {
+        let (_, data) = timeout(Duration::from_secs(5), listener.try_next())
+            .await??
+            .unwrap();
+        assert_eq!(data, expected);
+    }
+
+    caller.close().await?;
+    Ok(())
+}
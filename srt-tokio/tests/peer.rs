@@ -0,0 +1,21 @@
+use anyhow::Result;
+use srt_tokio::SrtSocket;
+
+#[tokio::test]
+async fn peer_socket_id_and_addr() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async { SrtSocket::builder().listen_on(":3070").await });
+    let caller = SrtSocket::builder()
+        .local_port(3071)
+        .call("127.0.0.1:3070", None)
+        .await?;
+    let listener = listener.await??;
+
+    assert_eq!(caller.peer_socket_id(), listener.settings().local_sockid);
+    assert_eq!(listener.peer_socket_id(), caller.settings().local_sockid);
+    assert_eq!(caller.peer_addr(), "127.0.0.1:3070".parse()?);
+    assert_eq!(listener.peer_addr(), "127.0.0.1:3071".parse()?);
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::SrtSocket;
+use tokio::{runtime::Runtime, time::timeout};
+
+#[tokio::test]
+async fn send_after_peer_is_gone() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the peer runs on a runtime of its own, so it can vanish without shutting down the connection
+    let peer_runtime = Runtime::new()?;
+    let peer = peer_runtime.spawn(SrtSocket::builder().listen_on(2960));
+    let mut caller = SrtSocket::builder()
+        .set(|options| options.session.peer_idle_timeout = Duration::from_secs(1))
+        .call("127.0.0.1:2960", None)
+        .await?;
+    let mut peer = peer.await??;
+
+    let message = || (Instant::now(), Bytes::from_static(b"hello"));
+    caller.send(message()).await?;
+    assert_eq!(peer.try_next().await?.unwrap().1, message().1);
+
+    peer_runtime.shutdown_background();
+    // buffered, but never sent
+    caller.feed(message()).await?;
+
+    // the connection times out...
+    timeout(Duration::from_secs(10), async {
+        while caller.events().next().await.is_some() {}
+    })
+    .await?;
+
+    // ...and from then on, every part of sending says so
+    let not_connected = |result: Result<(), io::Error>| {
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotConnected);
+    };
+    not_connected(caller.flush().await);
+    not_connected(future::poll_fn(|cx| caller.poll_ready_unpin(cx)).await);
+    not_connected(caller.send(message()).await);
+    not_connected(caller.send_data(message().1).await);
+    assert!(caller.try_send(Instant::now(), message().1).is_err());
+
+    drop(peer);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::{net::SocketAddr, time::Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{future, prelude::*};
+use srt_tokio::{options::*, PolledSrtSocket};
+use tokio::net::UdpSocket;
+
+const MESSAGES: usize = 100;
+// the first transmission of this data packet is lost on the way to the listener
+const LOST: usize = 10;
+
+// the whole session runs on the test's own task: both sockets and the proxy between them are
+// polled from a single join, nothing is spawned
+#[tokio::test(flavor = "current_thread")]
+async fn drive_a_session_by_polling() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let proxy = UdpSocket::bind("127.0.0.1:2981").await?;
+    let listener_address: SocketAddr = "127.0.0.1:2980".parse()?;
+
+    let listener = async {
+        let mut listener = PolledSrtSocket::bind(ListenerOptions::new(2980)?.into()).await?;
+        let mut received = Vec::new();
+        while let Some((_, data)) = listener.try_next().await? {
+            received.push(data);
+        }
+        Ok::<_, anyhow::Error>((received, listener.statistics().clone()))
+    };
+
+    let messages: Vec<_> = (0..MESSAGES).map(|i| Bytes::from(i.to_string())).collect();
+    let caller = async {
+        let mut options = SocketOptions::default();
+        options.sender.buffer_size = ByteCount(1_000_000);
+        let options = CallerOptions::with("127.0.0.1:2981", None, options)?;
+        let mut caller = PolledSrtSocket::bind(options.into()).await?;
+        for message in &messages {
+            caller.send((Instant::now(), message.clone())).await?;
+        }
+        caller.close().await?;
+        Ok::<_, anyhow::Error>(caller.statistics().clone())
+    };
+
+    let session = future::try_join(listener, caller);
+    let proxy = forward(&proxy, listener_address);
+    futures::pin_mut!(session, proxy);
+    let ((received, listener), caller) = match future::select(session, proxy).await {
+        future::Either::Left((session, _)) => session?,
+        future::Either::Right((proxy, _)) => return proxy,
+    };
+
+    assert_eq!(received, messages);
+    assert!(listener.tx_ack > 0, "{listener:?}");
+    assert!(caller.rx_ack > 0, "{caller:?}");
+    assert!(caller.tx_ack2 > 0, "{caller:?}");
+    // the loss was reported and repaired
+    assert!(listener.tx_nak > 0, "{listener:?}");
+    assert!(caller.rx_nak > 0, "{caller:?}");
+    assert!(caller.tx_retransmit_data > 0, "{caller:?}");
+
+    Ok(())
+}
+
+// forwards datagrams between the caller and the listener, dropping one data packet
+async fn forward(proxy: &UdpSocket, listener: SocketAddr) -> Result<()> {
+    let mut caller = None;
+    let mut data_packets = 0;
+    let mut buffer = [0; 2048];
+    loop {
+        let (size, from) = proxy.recv_from(&mut buffer).await?;
+        let to = if from == listener {
+            match caller {
+                Some(caller) => caller,
+                None => continue,
+            }
+        } else {
+            caller = Some(from);
+            // the first bit is clear on data packets
+            if buffer[0] & 0x80 == 0 {
+                data_packets += 1;
+                if data_packets == LOST + 1 {
+                    continue;
+                }
+            }
+            listener
+        };
+        proxy.send_to(&buffer[..size], to).await?;
+    }
+}
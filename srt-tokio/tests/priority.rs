@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{
+    options::{DataRate, LiveBandwidthMode},
+    Priority, SrtSocket,
+};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn prioritized_data_goes_first() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const MESSAGES: usize = 20;
+
+    // about 10ms a message, so they queue up, with the latency to still deliver them all
+    let (mut caller, mut listener) = futures::try_join!(
+        SrtSocket::builder()
+            .latency(Duration::from_secs(2))
+            .bandwidth(LiveBandwidthMode::Max(DataRate(100_000)))
+            .call("127.0.0.1:2750", None),
+        SrtSocket::builder()
+            .latency(Duration::from_secs(2))
+            .listen_on(":2750"),
+    )?;
+
+    for i in 0..MESSAGES {
+        caller
+            .send((Instant::now(), Bytes::from(format!("{i:0>1000}"))))
+            .await?;
+    }
+    caller
+        .send_prioritized((Instant::now(), Bytes::from("urgent")), Priority::High)
+        .await?;
+
+    let mut received = Vec::new();
+    for _ in 0..=MESSAGES {
+        let (_, data) = timeout(Duration::from_secs(5), listener.try_next())
+            .await??
+            .unwrap();
+        received.push(data);
+    }
+
+    // it overtook most of what was sent before it, and nothing was lost
+    let position = received.iter().position(|data| data == "urgent").unwrap();
+    assert!(position < MESSAGES / 2, "received at {position}");
+    assert_eq!(received.len(), MESSAGES + 1);
+
+    caller.close().await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::{
+    io::Cursor,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_protocol::packet::{ControlTypes, Packet};
+use srt_tokio::{RawTap, SrtSocket};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn tap_received_datagrams() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let listener = tokio::spawn(async {
+        let mut listener = SrtSocket::builder().listen_on(2680).await?;
+        // subscribed once connected, so this one only sees what comes after the handshake
+        let mut datagrams = listener.raw_tap();
+        listener
+            .send((Instant::now(), Bytes::from("hello")))
+            .await?;
+        let payload = find_data(&mut datagrams, "127.0.0.1:2681".parse()?).await;
+        assert_eq!(payload, Bytes::from("ping"));
+        assert_eq!(listener.try_next().await?.unwrap().1, Bytes::from("ping"));
+        listener.close().await?;
+        Ok(()) as Result<_>
+    });
+
+    let tap = RawTap::new();
+    let mut datagrams = tap.subscribe();
+    let mut caller = SrtSocket::builder()
+        .local_port(2681)
+        .raw_tap(&tap)
+        .call("127.0.0.1:2680", None)
+        .await?;
+    let from: SocketAddr = "127.0.0.1:2680".parse()?;
+
+    // the induction and conclusion responses come first, exactly as sent by the listener
+    for _ in 0..2 {
+        let (datagram, source) = datagrams.next().await.unwrap();
+        assert_eq!(source, from);
+        let packet = Packet::parse(&mut Cursor::new(&datagram[..]), false)?;
+        assert!(
+            matches!(&packet, Packet::Control(c) if matches!(c.control_type, ControlTypes::Handshake(_))),
+            "{packet:?}"
+        );
+
+        let mut serialized = Vec::new();
+        packet.serialize(&mut serialized);
+        assert_eq!(&serialized[..], &datagram[..]);
+    }
+
+    assert_eq!(caller.try_next().await?.unwrap().1, Bytes::from("hello"));
+    assert_eq!(find_data(&mut datagrams, from).await, Bytes::from("hello"));
+
+    caller.send((Instant::now(), Bytes::from("ping"))).await?;
+    listener.await??;
+    caller.close().await?;
+
+    Ok(())
+}
+
+async fn find_data(
+    datagrams: &mut (impl Stream<Item = (Bytes, SocketAddr)> + Unpin),
+    from: SocketAddr,
+) -> Bytes {
+    let find = async {
+        loop {
+            let (datagram, source) = datagrams.next().await.unwrap();
+            assert_eq!(source, from);
+            if let Packet::Data(data) =
+                Packet::parse(&mut Cursor::new(&datagram[..]), false).unwrap()
+            {
+                return data.payload;
+            }
+        }
+    };
+    timeout(Duration::from_secs(5), find).await.unwrap()
+}
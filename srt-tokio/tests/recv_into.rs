@@ -0,0 +1,57 @@
+use std::{
+    io,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::SinkExt;
+use srt_tokio::SrtSocket;
+use tokio::time::sleep;
+
+fn payload(n: usize) -> Bytes {
+    (0..n * 10 + 1).map(|i| (i + n) as u8).collect()
+}
+
+#[tokio::test]
+async fn reused_buffer() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const MESSAGES: usize = 100;
+
+    let listener = tokio::spawn(async { SrtSocket::builder().listen_on(":3080").await });
+    let mut sender = SrtSocket::builder().call("127.0.0.1:3080", None).await?;
+    let mut receiver = listener.await??;
+
+    tokio::spawn(async move {
+        for n in 0..MESSAGES {
+            sender.send((Instant::now(), payload(n))).await.unwrap();
+            sleep(Duration::from_millis(1)).await;
+        }
+        // too large for the buffer, it's made of more than one packet
+        sender
+            .send((Instant::now(), Bytes::from(vec![7; 3000])))
+            .await
+            .unwrap();
+        sender.close().await.unwrap();
+    });
+
+    let mut buf = vec![0; 1316];
+    let (ptr, capacity) = (buf.as_ptr(), buf.capacity());
+    for n in 0..MESSAGES {
+        let (_, len) = receiver.recv_into(&mut buf).await.unwrap()?;
+        assert_eq!(&buf[..len], &payload(n)[..]);
+    }
+    // the same buffer was received into every time
+    assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, capacity));
+
+    // a payload that doesn't fit is left for a buffer that does
+    let err = receiver.recv_into(&mut buf).await.unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    let mut large = vec![0; 4096];
+    let (_, len) = receiver.recv_into(&mut large).await.unwrap()?;
+    assert_eq!(&large[..len], &[7; 3000][..]);
+
+    assert!(receiver.recv_into(&mut buf).await.is_none());
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use srt_tokio::SrtListener;
+
+#[cfg(unix)]
+#[tokio::test]
+async fn reuse_port() -> Result<()> {
+    use std::time::{Duration, Instant};
+
+    use bytes::Bytes;
+    use futures::{SinkExt, StreamExt};
+    use srt_tokio::SrtSocket;
+    use tokio::{sync::mpsc, time::timeout};
+
+    let _ = pretty_env_logger::try_init();
+
+    // listeners that all allow it share the port
+    let (_first, first_incoming) = SrtListener::builder().reuse_port(true).bind(2720).await?;
+    let (_second, second_incoming) = SrtListener::builder().reuse_port(true).bind(2720).await?;
+
+    // but one that doesn't can't bind to it
+    let result = SrtListener::builder().bind(2720).await;
+    assert_eq!(result.err().unwrap().kind(), std::io::ErrorKind::AddrInUse);
+
+    // the caller connects to whichever listener the system picks
+    let (sender, mut received) = mpsc::unbounded_channel();
+    for mut incoming in [first_incoming, second_incoming] {
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            while let Some(request) = incoming.incoming().next().await {
+                let mut socket = request.accept(None).await.unwrap();
+                if let Some(Ok((_, data))) = socket.next().await {
+                    let _ = sender.send(data);
+                }
+            }
+        });
+    }
+    let mut caller = SrtSocket::builder().call("127.0.0.1:2720", None).await?;
+    caller.send((Instant::now(), Bytes::from("shared"))).await?;
+    let data = timeout(Duration::from_secs(2), received.recv()).await?;
+    assert_eq!(data, Some(Bytes::from("shared")));
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+#[tokio::test]
+async fn reuse_port_unsupported() -> Result<()> {
+    let result = SrtListener::builder().reuse_port(true).bind(2720).await;
+    assert_eq!(
+        result.err().unwrap().kind(),
+        std::io::ErrorKind::Unsupported
+    );
+    Ok(())
+}
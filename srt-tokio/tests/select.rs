@@ -0,0 +1,122 @@
+use std::{future::Future, task::Poll, time::Duration};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{poll, prelude::*};
+use srt_tokio::{options::*, SrtSocket};
+use tokio::{
+    select,
+    task::yield_now,
+    time::{sleep, timeout},
+};
+
+const MESSAGES: usize = 100;
+const CANCELLED_MESSAGES: usize = 1_000;
+
+#[tokio::test]
+async fn select_send_and_receive() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (a, b) = SrtSocket::pair();
+    let (a_received, b_received) = timeout(
+        Duration::from_secs(10),
+        future::try_join(exchange(a, "a"), exchange(b, "b")),
+    )
+    .await??;
+
+    let expected = |peer| (0..MESSAGES).map(move |i| Bytes::from(format!("{peer}{i}")));
+    assert!(a_received.into_iter().eq(expected("b")));
+    assert!(b_received.into_iter().eq(expected("a")));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancel_send_and_receive() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the whole burst is sent at once, leave enough room and time for it so that none of it is
+    // dropped for being late
+    let (mut a, mut b) = SrtSocket::builder()
+        .latency(Duration::from_secs(2))
+        .set(|options| options.sender.buffer_size = ByteCount(CANCELLED_MESSAGES as u64 * 1500))
+        .pair()?;
+
+    let sender = tokio::spawn(async move {
+        let mut cancelled = 0;
+        let mut i = 0;
+        while i < CANCELLED_MESSAGES {
+            // poll once and give up on anything that doesn't finish right away
+            let mut sending = Box::pin(a.send_data(Bytes::from(i.to_string())));
+            match poll!(&mut sending) {
+                Poll::Ready(result) => {
+                    result?;
+                    i += 1;
+                }
+                Poll::Pending => {
+                    drop(sending);
+                    cancelled += 1;
+                    yield_now().await;
+                }
+            }
+        }
+        a.close().await?;
+        Ok::<_, anyhow::Error>(cancelled)
+    });
+
+    let mut cancelled = 0;
+    let mut received = Vec::new();
+    loop {
+        let mut receiving = Box::pin(b.recv());
+        match poll!(&mut receiving) {
+            Poll::Ready(Some((_, data))) => received.push(data),
+            Poll::Ready(None) => break,
+            Poll::Pending => {
+                drop(receiving);
+                cancelled += 1;
+                yield_now().await;
+            }
+        }
+    }
+    let sender_cancelled = timeout(Duration::from_secs(10), sender).await???;
+
+    // nothing was lost or sent twice
+    assert!(received
+        .into_iter()
+        .eq((0..CANCELLED_MESSAGES).map(|i| Bytes::from(i.to_string()))));
+    assert!(sender_cancelled > 0);
+    assert!(cancelled > 0);
+
+    Ok(())
+}
+
+// sends MESSAGES messages while receiving as many from the peer, on the same socket
+async fn exchange(mut socket: SrtSocket, name: &str) -> Result<Vec<Bytes>> {
+    let mut sent = 0;
+    let mut received = Vec::new();
+    let mut sending = Box::pin(send_later(&socket, format!("{name}0")));
+    while sent < MESSAGES || received.len() < MESSAGES {
+        select! {
+            result = &mut sending, if sent < MESSAGES => {
+                result?;
+                sent += 1;
+                sending = Box::pin(send_later(&socket, format!("{name}{sent}")));
+            }
+            Some((_, data)) = socket.recv(), if received.len() < MESSAGES => received.push(data),
+        }
+    }
+    socket.close().await?;
+    Ok(received)
+}
+
+// spaces the messages out a little, like a live source would
+fn send_later(
+    socket: &SrtSocket,
+    data: String,
+) -> impl Future<Output = Result<(), std::io::Error>> {
+    let sending = socket.send_data(Bytes::from(data));
+    async move {
+        sleep(Duration::from_millis(1)).await;
+        sending.await
+    }
+}
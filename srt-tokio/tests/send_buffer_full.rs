@@ -0,0 +1,312 @@
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{channel::mpsc, prelude::*};
+use srt_protocol::{connection::ConnectionEvent, packet::Packet};
+use srt_tokio::{options::*, SrtSocket};
+use tokio::time::{sleep, timeout};
+
+// what the send buffer holds, in packets of up to the MSS
+const SEND_BUFFER_PACKETS: u64 = 32;
+// the most packets in flight with a small flow window
+const FLOW_WINDOW_PACKETS: u64 = 64;
+
+/// An in-memory link that drops everything sent over it, both ways, while stalled
+struct StallingConn {
+    local: SocketAddr,
+    stalled: Arc<AtomicBool>,
+    incoming: mpsc::UnboundedReceiver<(Packet, SocketAddr)>,
+    outgoing: mpsc::UnboundedSender<(Packet, SocketAddr)>,
+}
+
+impl StallingConn {
+    fn pair(a: SocketAddr, b: SocketAddr, stalled: Arc<AtomicBool>) -> (Self, Self) {
+        let (a_sender, a_receiver) = mpsc::unbounded();
+        let (b_sender, b_receiver) = mpsc::unbounded();
+        (
+            StallingConn {
+                local: a,
+                stalled: stalled.clone(),
+                incoming: a_receiver,
+                outgoing: b_sender,
+            },
+            StallingConn {
+                local: b,
+                stalled,
+                incoming: b_receiver,
+                outgoing: a_sender,
+            },
+        )
+    }
+}
+
+impl Stream for StallingConn {
+    type Item = (Packet, SocketAddr);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.incoming).poll_next(cx)
+    }
+}
+
+impl Sink<(Packet, SocketAddr)> for StallingConn {
+    type Error = io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (packet, _): (Packet, SocketAddr)) -> io::Result<()> {
+        if !self.stalled.load(Ordering::SeqCst) {
+            let _ = self.outgoing.unbounded_send((packet, self.local));
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A caller connected to a listener over a link that is stalled once connected
+async fn stalled_caller(
+    port: u16,
+    configure: impl FnOnce(&mut SocketOptions),
+) -> Result<(SrtSocket, SrtSocket)> {
+    let listener_addr: SocketAddr = format!("127.0.0.1:{port}").parse()?;
+    let caller_addr: SocketAddr = format!("127.0.0.1:{}", port + 1).parse()?;
+    let stalled = Arc::new(AtomicBool::new(false));
+    let (listener_conn, caller_conn) =
+        StallingConn::pair(listener_addr, caller_addr, stalled.clone());
+
+    let mut socket = SocketOptions::default();
+    socket.sender.buffer_size = ByteCount(SEND_BUFFER_PACKETS * 1500);
+    configure(&mut socket);
+
+    // the flow window is the larger of the two, the listener is configured the same
+    let listener_socket = socket.clone();
+    let listener = tokio::spawn(async move {
+        let options = ListenerOptions::with(listener_addr.port(), listener_socket)?;
+        SrtSocket::with_transport(listener_conn, options.into()).await
+    });
+    let options = CallerOptions::with(listener_addr, None, socket)?;
+    let caller = SrtSocket::with_transport(caller_conn, options.into()).await?;
+    let listener = listener.await??;
+
+    stalled.store(true, Ordering::SeqCst);
+    Ok((caller, listener))
+}
+
+// a small flow window, that the receive buffers fit in, with room to spare in the send buffer
+fn small_flow_window(socket: &mut SocketOptions, policy: FlowWindowFull) {
+    socket.sender.buffer_size = ByteCount(1_000_000);
+    socket.sender.flow_control_window_size = PacketCount(FLOW_WINDOW_PACKETS);
+    socket.sender.flow_window_full = policy;
+    // without ACKs, the window while restarting after idle would never grow to it
+    socket.sender.idle_restart_threshold = None;
+    socket.receiver.buffer_size = ByteCount(FLOW_WINDOW_PACKETS * 1500);
+}
+
+#[tokio::test]
+async fn drop_and_report() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut caller, _listener) = stalled_caller(3010, |socket| {
+        socket.sender.buffer_full = SendBufferFull::Drop;
+    })
+    .await?;
+
+    // sending never waits, the oldest packets make room
+    for i in 0..100 {
+        timeout(
+            Duration::from_millis(100),
+            caller.send((Instant::now(), Bytes::from(i.to_string()))),
+        )
+        .await??;
+    }
+
+    let expected = 100 - SEND_BUFFER_PACKETS;
+    let mut dropped = 0;
+    while dropped < expected {
+        let event = timeout(Duration::from_secs(1), caller.events().next()).await?;
+        if let Some(ConnectionEvent::SendBufferFull(_, packets)) = event {
+            dropped += packets.0;
+        }
+    }
+    assert_eq!(dropped, expected);
+
+    let statistics = timeout(Duration::from_secs(3), async {
+        loop {
+            let statistics = caller.statistics().next().await.unwrap();
+            if statistics.tx_buffer_full_dropped_data > 0 {
+                break statistics;
+            }
+        }
+    })
+    .await?;
+    assert_eq!(statistics.tx_buffer_full_dropped_data, expected);
+    assert!(statistics.tx_dropped_data >= expected);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn backpressure() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut caller, _listener) = stalled_caller(3020, |socket| {
+        socket.sender.buffer_full = SendBufferFull::Backpressure;
+    })
+    .await?;
+
+    // sending waits once the send buffer and the channel in front of it are full
+    let mut sent = 0;
+    loop {
+        let item = (Instant::now(), Bytes::from(sent.to_string()));
+        match timeout(Duration::from_millis(100), caller.send(item)).await {
+            Ok(result) => result?,
+            Err(_) => break,
+        }
+        sent += 1;
+        assert!(sent < 1000, "sending never waited");
+    }
+    assert!(sent > SEND_BUFFER_PACKETS);
+
+    // and goes on once the packets nobody acknowledged are too late and make room
+    timeout(
+        Duration::from_secs(3),
+        caller.send((Instant::now(), Bytes::from("more"))),
+    )
+    .await??;
+
+    // nothing was dropped to make room
+    let statistics = timeout(Duration::from_secs(2), caller.statistics().next())
+        .await?
+        .unwrap();
+    assert_eq!(statistics.tx_buffer_full_dropped_data, 0);
+    while let Ok(Some(event)) = timeout(Duration::ZERO, caller.events().next()).await {
+        assert!(!matches!(event, ConnectionEvent::SendBufferFull(..)));
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn queue_depth() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    const QUEUE_DEPTH: usize = 8;
+    let (mut caller, _listener) = stalled_caller(3030, |socket| {
+        socket.sender.buffer_full = SendBufferFull::Backpressure;
+        socket.sender.queue_depth = QUEUE_DEPTH;
+    })
+    .await?;
+
+    // sending waits once the send buffer is full and the queue in front of it is at its depth
+    let mut sent = 0;
+    loop {
+        let item = (Instant::now(), Bytes::from(sent.to_string()));
+        match timeout(Duration::from_millis(100), caller.send(item)).await {
+            Ok(result) => result?,
+            Err(_) => break,
+        }
+        sent += 1;
+        assert!(sent < 1000, "sending never waited");
+    }
+    assert!(sent > QUEUE_DEPTH);
+
+    let statistics = timeout(Duration::from_secs(2), async {
+        loop {
+            let statistics = caller.statistics().next().await.unwrap();
+            if statistics.tx_queued_messages > 0 {
+                break statistics;
+            }
+        }
+    })
+    .await?;
+    assert_eq!(statistics.tx_queued_messages, QUEUE_DEPTH as u64);
+    assert_eq!(statistics.tx_queued_messages_max, QUEUE_DEPTH as u64);
+    assert!(statistics.tx_queued_bytes > 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flow_window_block() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut caller, _listener) = stalled_caller(3040, |socket| {
+        small_flow_window(socket, FlowWindowFull::Block)
+    })
+    .await?;
+
+    // a live source, sending waits once the window is exhausted, with room to spare in the send
+    // buffer
+    let mut sent = 0;
+    loop {
+        let item = (Instant::now(), Bytes::from(sent.to_string()));
+        match timeout(Duration::from_millis(100), caller.send(item)).await {
+            Ok(result) => result?,
+            Err(_) => break,
+        }
+        sent += 1;
+        assert!(sent < 1000, "sending never waited");
+        sleep(Duration::from_millis(1)).await;
+    }
+    assert!(sent > FLOW_WINDOW_PACKETS);
+
+    let statistics = timeout(Duration::from_secs(2), caller.statistics().next())
+        .await?
+        .unwrap();
+    assert_eq!(statistics.tx_dropped_data, 0);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn flow_window_drop_oldest() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (mut caller, _listener) = stalled_caller(3050, |socket| {
+        small_flow_window(socket, FlowWindowFull::DropOldest)
+    })
+    .await?;
+
+    // a live source, sending never waits, what's queued behind the window makes room
+    for i in 0..500 {
+        timeout(
+            Duration::from_millis(100),
+            caller.send((Instant::now(), Bytes::from(i.to_string()))),
+        )
+        .await??;
+        sleep(Duration::from_millis(1)).await;
+    }
+
+    let statistics = timeout(Duration::from_secs(3), async {
+        loop {
+            let statistics = caller.statistics().next().await.unwrap();
+            if statistics.tx_flow_window_dropped_data > 0 {
+                break statistics;
+            }
+        }
+    })
+    .await?;
+    assert!(statistics.tx_dropped_data >= statistics.tx_flow_window_dropped_data);
+    assert_eq!(statistics.tx_buffer_full_dropped_data, 0);
+
+    Ok(())
+}
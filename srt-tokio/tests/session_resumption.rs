@@ -0,0 +1,121 @@
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{SrtListener, SrtSocket};
+use tokio::{net::UdpSocket, select, time::timeout};
+
+const LATENCY: Duration = Duration::from_secs(2);
+const WINDOW: Duration = Duration::from_secs(10);
+
+// forwards between whoever sends to `front` and `back`, dropping everything while `down` is set
+async fn relay(front: u16, back: SocketAddr, down: Arc<AtomicBool>) -> Result<()> {
+    let front = UdpSocket::bind(("127.0.0.1", front)).await?;
+    let upstream = UdpSocket::bind("127.0.0.1:0").await?;
+    upstream.connect(back).await?;
+    tokio::spawn(async move {
+        let mut caller = None;
+        let mut front_buf = [0; 1500];
+        let mut back_buf = [0; 1500];
+        loop {
+            select! {
+                Ok((size, from)) = front.recv_from(&mut front_buf) => {
+                    caller = Some(from);
+                    if !down.load(Ordering::SeqCst) {
+                        let _ = upstream.send(&front_buf[..size]).await;
+                    }
+                }
+                Ok(size) = upstream.recv(&mut back_buf) => {
+                    if let (Some(caller), false) = (caller, down.load(Ordering::SeqCst)) {
+                        let _ = front.send_to(&back_buf[..size], caller).await;
+                    }
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn receive(socket: &mut SrtSocket, count: usize) -> Result<Vec<Bytes>> {
+    let mut received = Vec::new();
+    while received.len() < count {
+        let (_, data) = timeout(LATENCY * 3, socket.try_next()).await??.unwrap();
+        received.push(data);
+    }
+    Ok(received)
+}
+
+#[tokio::test]
+async fn resume_after_link_drop() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let message = |n: usize| (Instant::now(), Bytes::from(format!("message {n}")));
+    let expected = |range: std::ops::Range<usize>| range.map(|n| message(n).1).collect::<Vec<_>>();
+
+    let (_listener, mut incoming) = SrtListener::builder()
+        .latency(LATENCY)
+        .resumption_window(WINDOW)
+        .bind(2800)
+        .await?;
+    let down = Arc::new(AtomicBool::new(false));
+    relay(2801, "127.0.0.1:2800".parse()?, down.clone()).await?;
+
+    let caller = SrtSocket::builder()
+        .latency(LATENCY)
+        .resumption_window(WINDOW)
+        .max_retransmits(3)
+        .call("127.0.0.1:2801", None);
+    let accept = async { incoming.incoming().next().await.unwrap().accept(None).await };
+    let (mut caller, mut first) = futures::try_join!(caller, accept)?;
+
+    for n in 0..10 {
+        caller.send(message(n)).await?;
+    }
+    caller.flushed().await?;
+
+    // the link goes down with these still in the send buffer, until the connection gives up on them
+    down.store(true, Ordering::SeqCst);
+    for n in 10..20 {
+        caller.send(message(n)).await?;
+    }
+    timeout(Duration::from_secs(10), async {
+        while caller.events().next().await.is_some() {}
+    })
+    .await?;
+    let resumption = caller.resumption().unwrap();
+    assert_eq!(resumption.packets.len(), 10);
+    let token = resumption.token;
+
+    // a new connection, straight to the listener, carries on from there
+    let resumed = SrtSocket::builder()
+        .latency(LATENCY)
+        .resume(resumption)
+        .call("127.0.0.1:2800", None);
+    let accept = async { incoming.incoming().next().await.unwrap().accept(None).await };
+    let (mut resumed, mut second) = futures::try_join!(resumed, accept)?;
+    assert_eq!(resumed.settings().session_token, Some(token));
+    assert_eq!(second.settings().session_token, Some(token));
+    assert_eq!(
+        second.settings().init_seq_num,
+        resumed.settings().init_seq_num
+    );
+    for n in 20..25 {
+        resumed.send(message(n)).await?;
+    }
+
+    // neither gaps nor duplicates
+    assert_eq!(receive(&mut first, 10).await?, expected(0..10));
+    assert_eq!(receive(&mut second, 15).await?, expected(10..25));
+    assert!(timeout(LATENCY * 2, second.try_next()).await.is_err());
+
+    resumed.close().await?;
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use assert_matches::assert_matches;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{options::*, ConnectionEvent, SrtSocket};
+use tokio::{sync::Barrier, time::timeout};
+
+const MESSAGES: usize = 100;
+
+#[tokio::test]
+async fn receive_everything_before_eof() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // a lot more latency towards the listener than back, so the data is still buffered, waiting
+    // to be released, when the shutdown arrives
+    let listener = tokio::spawn(async {
+        let mut listener = SrtSocket::builder()
+            .set(|options| options.receiver.latency = Duration::from_secs(1))
+            .listen_on(2970)
+            .await?;
+        let mut received = Vec::new();
+        while let Some((_, data)) = listener.try_next().await? {
+            received.push(data);
+        }
+        Ok::<_, anyhow::Error>(received)
+    });
+
+    let mut caller = SrtSocket::builder()
+        .set(|options| {
+            options.sender.buffer_size = ByteCount(1_000_000);
+            options.sender.peer_latency = Duration::from_millis(20);
+            options.receiver.latency = Duration::from_millis(20);
+        })
+        .call("127.0.0.1:2970", None)
+        .await?;
+    let messages: Vec<_> = (0..MESSAGES).map(|i| Bytes::from(i.to_string())).collect();
+    for message in &messages {
+        caller.feed((Instant::now(), message.clone())).await?;
+    }
+    caller.close().await?;
+
+    let received = listener.await??;
+    assert_eq!(received, messages);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn simultaneous_close() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // both ends close at once, with data still on its way in both directions, so their shutdowns
+    // cross
+    async fn send_and_close(
+        mut socket: SrtSocket,
+        barrier: Arc<Barrier>,
+    ) -> Result<(Vec<Bytes>, Vec<ConnectionEvent>)> {
+        for i in 0..MESSAGES {
+            socket
+                .feed((Instant::now(), Bytes::from(i.to_string())))
+                .await?;
+        }
+        barrier.wait().await;
+        socket.close().await?;
+        let mut received = Vec::new();
+        while let Some((_, data)) = timeout(Duration::from_secs(5), socket.try_next()).await?? {
+            received.push(data);
+        }
+        timeout(Duration::from_secs(5), socket.close_and_finish()).await??;
+        let mut events = Vec::new();
+        while let Some(event) = timeout(Duration::from_secs(1), socket.events().next()).await? {
+            events.push(event);
+        }
+        Ok((received, events))
+    }
+
+    let barrier = Arc::new(Barrier::new(2));
+    let builder =
+        || SrtSocket::builder().set(|options| options.sender.buffer_size = ByteCount(1_000_000));
+    let listener = builder().listen_on(2971);
+    let caller = builder().call("127.0.0.1:2971", None);
+    let (listener, caller) = futures::try_join!(listener, caller)?;
+    let listener = tokio::spawn(send_and_close(listener, barrier.clone()));
+    let caller = tokio::spawn(send_and_close(caller, barrier));
+
+    let messages: Vec<_> = (0..MESSAGES).map(|i| Bytes::from(i.to_string())).collect();
+    for side in [listener.await??, caller.await??] {
+        let (received, events) = side;
+        assert_eq!(received, messages);
+        assert_matches!(events.last(), Some(ConnectionEvent::Closed(_)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::{io, time::Duration};
+
+use bytes::BytesMut;
+use srt_protocol::{options::*, packet::*};
+use srt_tokio::{SrtListener, SrtSocket};
+use tokio::{net::UdpSocket, time::timeout};
+
+// a handshake that's fine but for the type-specific information of its control header, which is
+// reserved for handshakes and the reference implementation always leaves zero
+fn malformed(packet: &Packet) -> Vec<u8> {
+    let mut bytes = BytesMut::new();
+    packet.serialize(&mut bytes);
+    bytes[7] = 1;
+    bytes.to_vec()
+}
+
+fn induction() -> Packet {
+    Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: SocketId(0),
+        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+            init_seq_num: SeqNumber(1_827_131),
+            max_packet_size: PacketSize(1500),
+            max_flow_size: PacketCount(8192),
+            shake_type: ShakeType::Induction,
+            socket_id: SocketId(1231),
+            syn_cookie: 0,
+            peer_addr: [127, 0, 0, 1].into(),
+            info: HandshakeVsInfo::V4(SocketType::Datagram),
+        }),
+    })
+}
+
+async fn answers_malformed_induction(strict: bool, port: u16) -> io::Result<bool> {
+    let (_listener, _incoming) = SrtListener::builder()
+        .strict_handshake(strict)
+        .bind(port)
+        .await?;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await?;
+    socket
+        .send_to(&malformed(&induction()), ("127.0.0.1", port))
+        .await?;
+
+    let mut buffer = [0; 1500];
+    Ok(
+        timeout(Duration::from_millis(500), socket.recv_from(&mut buffer))
+            .await
+            .is_ok(),
+    )
+}
+
+#[tokio::test]
+async fn listener() -> io::Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    assert!(answers_malformed_induction(false, 3100).await?);
+    assert!(!answers_malformed_induction(true, 3101).await?);
+    Ok(())
+}
+
+// answers the induction of a caller with a malformed induction response, then returns what the
+// caller sent next, if anything
+async fn malformed_induction_response(socket: UdpSocket) -> io::Result<Option<Packet>> {
+    let mut buffer = [0; 1500];
+    let (size, from) = socket.recv_from(&mut buffer).await?;
+    let shake = match Packet::parse(&mut &buffer[..size], false) {
+        Ok(Packet::Control(ControlPacket {
+            control_type: ControlTypes::Handshake(shake),
+            ..
+        })) => shake,
+        other => panic!("expected an induction, got {other:?}"),
+    };
+
+    let response = Packet::Control(ControlPacket {
+        timestamp: TimeStamp::from_micros(0),
+        dest_sockid: shake.socket_id,
+        control_type: ControlTypes::Handshake(HandshakeControlInfo {
+            syn_cookie: 5678,
+            socket_id: SocketId(4321),
+            info: HandshakeVsInfo::V5(HsV5Info::default()),
+            ..shake
+        }),
+    });
+    socket.send_to(&malformed(&response), from).await?;
+
+    // the caller repeats its induction until it gets an answer it takes
+    loop {
+        let (size, _) =
+            match timeout(Duration::from_millis(500), socket.recv_from(&mut buffer)).await {
+                Ok(received) => received?,
+                Err(_) => return Ok(None),
+            };
+        match Packet::parse(&mut &buffer[..size], false) {
+            Ok(Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(next),
+                ..
+            })) if next.shake_type == ShakeType::Induction => continue,
+            Ok(next) => return Ok(Some(next)),
+            Err(e) => panic!("{e}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn lenient_caller() -> io::Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let peer = UdpSocket::bind("127.0.0.1:3102").await?;
+    let caller = tokio::spawn(
+        SrtSocket::builder()
+            .strict_handshake(false)
+            .call("127.0.0.1:3102", None),
+    );
+
+    // the caller goes on to conclude the handshake
+    let next = malformed_induction_response(peer).await?;
+    assert!(
+        matches!(
+            &next,
+            Some(Packet::Control(ControlPacket {
+                control_type: ControlTypes::Handshake(HandshakeControlInfo {
+                    shake_type: ShakeType::Conclusion,
+                    syn_cookie: 5678,
+                    ..
+                }),
+                ..
+            }))
+        ),
+        "{next:?}"
+    );
+    caller.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn strict_caller() -> io::Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let peer = UdpSocket::bind("127.0.0.1:3103").await?;
+    let caller = tokio::spawn(
+        SrtSocket::builder()
+            .strict_handshake(true)
+            .call("127.0.0.1:3103", None),
+    );
+
+    // the caller gives up on the peer rather than concluding the handshake
+    assert_eq!(malformed_induction_response(peer).await?, None);
+    let error = caller.await.unwrap().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let error = error.into_inner().unwrap();
+    assert!(
+        matches!(
+            error.downcast_ref(),
+            Some(ReceivePacketError::Parse(
+                PacketParseError::StrictHandshake(HandshakeViolation::ReservedField(..))
+            ))
+        ),
+        "{error}"
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn strict_both_sides() -> io::Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let (_listener, mut incoming) = SrtListener::builder()
+        .strict_handshake(true)
+        .bind(3104)
+        .await?;
+    let accept = async {
+        use futures::StreamExt;
+        let request = incoming.incoming().next().await.unwrap();
+        request.accept(None).await
+    };
+    let call = SrtSocket::builder()
+        .strict_handshake(true)
+        .call("127.0.0.1:3104", None);
+
+    let (accepted, called) = futures::join!(accept, call);
+    accepted?;
+    called?;
+    Ok(())
+}
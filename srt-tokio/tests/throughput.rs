@@ -0,0 +1,34 @@
+mod lossy_conn;
+
+use std::io;
+
+// the usual payload of live streams, 7 MPEG-TS packets
+const PAYLOAD_SIZE: usize = 1316;
+
+// even a debug build keeps up with 400 Mbit/s, an eighth of that leaves room for slower machines,
+// while still catching the sockets slowing down many times over. `cargo bench -p
+// srt-tokio --bench throughput` measures it closely.
+const RATE: u64 = 50_000_000 / 8;
+
+// `cargo test --release -p srt-tokio --test throughput -- --ignored`, as the CI workflow does
+#[tokio::test]
+#[ignore = "timed by the wall clock, run it on its own in a release build"]
+async fn goodput() -> io::Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // a second of it
+    let count = RATE as usize / PAYLOAD_SIZE;
+    let (bytes, elapsed) = lossy_conn::transfer(count, PAYLOAD_SIZE, RATE).await?;
+
+    // nothing is lost on the link, what doesn't arrive was dropped as too late
+    let sent = (count * PAYLOAD_SIZE) as u64;
+    assert!(bytes >= sent * 99 / 100, "{bytes} of {sent} bytes arrived");
+    // the last payloads arrive the latency after they were sent, a tenth of a second more
+    let goodput = bytes as f64 / elapsed.as_secs_f64();
+    assert!(
+        goodput >= RATE as f64 * 0.8,
+        "{:.1} Mbit/s in {elapsed:?}",
+        goodput * 8. / 1_000_000.
+    );
+    Ok(())
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{options::*, SrtSocket};
+use tokio::time::sleep;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+type Fields = BTreeMap<String, String>;
+
+#[derive(Debug, Default)]
+struct FieldVisitor(Fields);
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+}
+
+#[derive(Debug)]
+struct CapturedEvent {
+    message: String,
+    fields: Fields,
+    // the fields of the srt_connection span the event was emitted in
+    connection: Option<Fields>,
+}
+
+#[derive(Debug)]
+struct CapturedSpan {
+    name: String,
+    fields: Fields,
+    // the fields of the srt_connection span it's in
+    connection: Option<Fields>,
+}
+
+/// Records every event along with the fields of the connection span it's in, and every span as
+/// it closes
+#[derive(Clone, Default)]
+struct CaptureLayer(
+    Arc<Mutex<Vec<CapturedEvent>>>,
+    Arc<Mutex<Vec<CapturedSpan>>>,
+);
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CaptureLayer {
+    fn on_new_span(&self, attrs: &span::Attributes, id: &span::Id, ctx: Context<S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        ctx.span(id).unwrap().extensions_mut().insert(visitor.0);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record, ctx: Context<S>) {
+        let span = ctx.span(id).unwrap();
+        let mut extensions = span.extensions_mut();
+        let mut visitor = FieldVisitor(extensions.remove::<Fields>().unwrap());
+        values.record(&mut visitor);
+        extensions.insert(visitor.0);
+    }
+
+    fn on_event(&self, event: &Event, ctx: Context<S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut fields = visitor.0;
+        let connection = ctx.event_scope(event).and_then(|mut scope| {
+            scope
+                .find(|span| span.name() == "srt_connection")
+                .map(|span| span.extensions().get::<Fields>().unwrap().clone())
+        });
+        self.0.lock().unwrap().push(CapturedEvent {
+            message: fields.remove("message").unwrap_or_default(),
+            fields,
+            connection,
+        });
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<S>) {
+        let span = ctx.span(&id).unwrap();
+        let connection = span
+            .scope()
+            .skip(1)
+            .find(|span| span.name() == "srt_connection")
+            .map(|span| span.extensions().get::<Fields>().unwrap().clone());
+        let fields = span.extensions().get::<Fields>().unwrap().clone();
+        self.1.lock().unwrap().push(CapturedSpan {
+            name: span.name().to_string(),
+            fields,
+            connection,
+        });
+    }
+}
+
+impl CaptureLayer {
+    fn find(&self, message: &str, role: &str) -> Vec<(Fields, Fields)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|event| event.message == message)
+            .filter_map(|event| Some((event.fields.clone(), event.connection.clone()?)))
+            .filter(|(_, connection)| connection.get("role").map(String::as_str) == Some(role))
+            .collect()
+    }
+
+    fn spans(&self, name: &str, role: &str) -> Vec<Fields> {
+        self.1
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|span| span.name == name)
+            .filter(|span| {
+                let connection = span.connection.as_ref();
+                connection.and_then(|c| c.get("role")).map(String::as_str) == Some(role)
+            })
+            .map(|span| span.fields.clone())
+            .collect()
+    }
+}
+
+fn encrypted() -> srt_tokio::SrtSocketBuilder {
+    SrtSocket::builder()
+        .encryption(16, "password123")
+        .set(|options| {
+            options.encryption.km_refresh.period = PacketCount(128);
+            options.encryption.km_refresh.pre_announcement_period = PacketCount(60);
+        })
+}
+
+#[tokio::test]
+async fn connection_spans_and_events() -> Result<()> {
+    let capture = CaptureLayer::default();
+    let _guard = tracing_subscriber::registry()
+        .with(capture.clone())
+        .set_default();
+
+    let listener = tokio::spawn(async {
+        let mut socket = encrypted().listen_on(":2930").await?;
+        let settings = socket.settings().clone();
+        while socket.try_next().await?.is_some() {}
+        Ok::<_, anyhow::Error>(settings)
+    });
+
+    let mut caller = encrypted().call("127.0.0.1:2930", None).await?;
+    let caller_settings = caller.settings().clone();
+    for _ in 0..300 {
+        caller
+            .send((Instant::now(), Bytes::from_static(b"1234")))
+            .await?;
+        sleep(Duration::from_millis(1)).await;
+    }
+    caller.close().await?;
+    let listener_settings = listener.await??;
+
+    // every event of the connection task carries the socket it's from
+    for (role, settings) in [
+        ("caller", &caller_settings),
+        ("listener", &listener_settings),
+    ] {
+        for message in ["connected", "closed"] {
+            let events = capture.find(message, role);
+            assert_eq!(events.len(), 1, "{role} {message}");
+            let (_, connection) = &events[0];
+            assert_eq!(
+                connection["socket_id"],
+                format!("{:?}", settings.local_sockid)
+            );
+            assert_eq!(connection["peer"], settings.remote.to_string());
+        }
+
+        let (completed, _) = &capture.find("handshake completed", role)[0];
+        assert_eq!(
+            completed["peer_socket_id"],
+            format!("{:?}", settings.remote_sockid)
+        );
+    }
+
+    // the caller starts with the induction, and concludes it
+    let shake_types: Vec<_> = capture
+        .find("handshake sent", "caller")
+        .into_iter()
+        .map(|(fields, _)| fields["shake_type"].clone())
+        .collect();
+    assert_eq!(shake_types.first().map(String::as_str), Some("Induction"));
+    assert_eq!(shake_types.last().map(String::as_str), Some("Conclusion"));
+    let shake_types: Vec<_> = capture
+        .find("handshake sent", "listener")
+        .into_iter()
+        .map(|(fields, _)| fields["shake_type"].clone())
+        .collect();
+    assert_eq!(shake_types, ["Induction", "Conclusion"]);
+
+    // the listener receives the caller's new keys
+    assert!(!capture
+        .find("stream encrypting key refreshed", "listener")
+        .is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn handshake_spans_and_control_events() -> Result<()> {
+    let capture = CaptureLayer::default();
+    let _guard = tracing_subscriber::registry()
+        .with(capture.clone())
+        .set_default();
+
+    let listener = tokio::spawn(async {
+        let mut socket = SrtSocket::builder().listen_on(":2931").await?;
+        let settings = socket.settings().clone();
+        while socket.try_next().await?.is_some() {}
+        Ok::<_, anyhow::Error>(settings)
+    });
+
+    let mut caller = SrtSocket::builder().call("127.0.0.1:2931", None).await?;
+    let caller_settings = caller.settings().clone();
+    for _ in 0..100 {
+        caller
+            .send((Instant::now(), Bytes::from_static(b"1234")))
+            .await?;
+        sleep(Duration::from_millis(2)).await;
+    }
+    caller.close().await?;
+    let listener_settings = listener.await??;
+
+    // the handshake has a span of its own, tagged with the socket it picked and its peer
+    for (role, settings) in [
+        ("caller", &caller_settings),
+        ("listener", &listener_settings),
+    ] {
+        let spans = capture.spans("srt_handshake", role);
+        assert_eq!(spans.len(), 1, "{role}");
+        assert_eq!(
+            spans[0].get("socket_id"),
+            Some(&format!("{:?}", settings.local_sockid))
+        );
+        assert_eq!(spans[0].get("peer"), Some(&settings.remote.to_string()));
+    }
+
+    // the connection acknowledges the data in the span of its socket
+    let acks = capture.find("ack received", "caller");
+    assert!(!acks.is_empty());
+    for (_, connection) in acks {
+        assert_eq!(
+            connection["socket_id"],
+            format!("{:?}", caller_settings.local_sockid)
+        );
+    }
+    assert!(!capture.find("ack sent", "listener").is_empty());
+
+    Ok(())
+}
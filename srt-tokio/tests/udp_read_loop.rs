@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::prelude::*;
+use srt_tokio::{options::*, SrtListener, SrtSocket};
+use tokio::time::{sleep, timeout};
+
+const PACKETS: usize = 20_000;
+const PAYLOAD_SIZE: usize = 1316;
+const LATENCY: Duration = Duration::from_millis(500);
+
+fn options(options: &mut SocketOptions) {
+    options.sender.buffer_size = ByteCount(20_000_000);
+    options.receiver.buffer_size = ByteCount(20_000_000);
+    options.connect.udp_recv_buffer_size = ByteCount(1_000_000);
+    options.session.statistics_interval = Duration::from_millis(200);
+}
+
+#[tokio::test]
+async fn high_rate() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    let receiver = async {
+        let mut socket = SrtSocket::builder()
+            .set(options)
+            .latency(LATENCY)
+            .udp_read_loop(UdpReadLoop::default())
+            .listen_on(":2780")
+            .await?;
+        let mut statistics = socket.statistics().clone();
+        let last_statistics = tokio::spawn(async move {
+            let mut last = None;
+            while let Some(statistics) = statistics.next().await {
+                last = Some(statistics);
+            }
+            last
+        });
+        let mut received = 0;
+        while socket.try_next().await?.is_some() {
+            received += 1;
+        }
+        Ok::<_, anyhow::Error>((received, last_statistics.await?.unwrap()))
+    };
+
+    let sender = async {
+        let mut socket = SrtSocket::builder()
+            .set(options)
+            .latency(LATENCY)
+            .call("127.0.0.1:2780", None)
+            .await?;
+        // a sustained 20 packets, about 200 Mbps, every millisecond
+        let payload = Bytes::from(vec![0; PAYLOAD_SIZE]);
+        for i in 0..PACKETS {
+            socket.feed((Instant::now(), payload.clone())).await?;
+            if i % 20 == 19 {
+                socket.flush().await?;
+                sleep(Duration::from_millis(1)).await;
+            }
+        }
+        socket.close().await?;
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let ((received, statistics), ()) =
+        timeout(Duration::from_secs(30), future::try_join(receiver, sender)).await??;
+
+    println!(
+        "received {received}, lost {}, suspected kernel drops {}",
+        statistics.rx_loss_data, statistics.rx_suspected_kernel_drops
+    );
+    assert!(received >= PACKETS * 99 / 100, "received {received}");
+    assert!(
+        statistics.rx_suspected_kernel_drops < PACKETS as u64 / 100,
+        "{} suspected kernel drops",
+        statistics.rx_suspected_kernel_drops
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn port_released_on_close() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // the same port again, once the connection before is done with it
+    for _ in 0..2 {
+        let listener = SrtSocket::builder()
+            .udp_read_loop(UdpReadLoop::default())
+            .listen_on(3106);
+        let caller = SrtSocket::builder().call("127.0.0.1:3106", None);
+        let (mut listener, caller) = future::try_join(listener, caller).await?;
+        listener.close().await?;
+        drop(caller);
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn listener() -> Result<()> {
+    let _ = pretty_env_logger::try_init();
+
+    // every connection is read through the one loop, and the port is released once the listener
+    // is closed
+    for _ in 0..2 {
+        let (mut server, mut incoming) = SrtListener::builder()
+            .udp_read_loop(UdpReadLoop::default())
+            .bind(3111)
+            .await?;
+        let accepted = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                let request = incoming.incoming().next().await.unwrap();
+                let mut socket = request.accept(None).await?;
+                received.push(socket.try_next().await?.unwrap().1);
+            }
+            Ok::<_, anyhow::Error>(received)
+        });
+
+        let mut callers = Vec::new();
+        for message in ["first", "second"] {
+            let mut caller = SrtSocket::builder().call("127.0.0.1:3111", None).await?;
+            caller.send((Instant::now(), Bytes::from(message))).await?;
+            callers.push(caller);
+        }
+        let received = timeout(Duration::from_secs(5), accepted).await???;
+        assert_eq!(received, ["first", "second"]);
+
+        server.close().await;
+        for mut caller in callers {
+            caller.close().await?;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    Ok(())
+}